@@ -0,0 +1,44 @@
+//! Compute the `left` and `downloaded` announce parameters of a torrent
+//! for a partially completed download.
+//!
+//! Usage: `cargo run --example announce_left [TORRENT] [PERCENT]`
+//!
+//! `TORRENT` defaults to a sample torrent, and `PERCENT` (the share of
+//! pieces that have been downloaded and verified) defaults to 50.
+//! The verified pieces are picked at random.
+
+use lava_torrent::torrent::v1::Torrent;
+use rand::Rng;
+
+const SAMPLE: &str = "tests/files/tails-amd64-3.6.1.torrent";
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let path = args.next().unwrap_or_else(|| SAMPLE.to_owned());
+    let percent: f64 = args
+        .next()
+        .map(|arg| arg.parse().expect("PERCENT must be a number"))
+        .unwrap_or(50.0);
+
+    let torrent = Torrent::read_from_file(&path).unwrap();
+
+    // bitfield as sent in a BitTorrent `bitfield` message:
+    // the high bit of the first byte is piece 0
+    let n_pieces = torrent.pieces.len();
+    let mut have = vec![0u8; n_pieces.div_ceil(8)];
+    let mut rng = rand::thread_rng();
+    for index in 0..n_pieces {
+        if rng.gen_bool((percent / 100.0).clamp(0.0, 1.0)) {
+            have[index / 8] |= 0x80 >> (index % 8);
+        }
+    }
+
+    // padding files (BEP 47) are never requested,
+    // so they should not count towards `left`
+    let left = torrent.bytes_left_with(&have, true).unwrap();
+    let completed = torrent.bytes_completed_with(&have, true).unwrap();
+    println!("{}", torrent.name);
+    println!("left:       {}", left);
+    println!("completed:  {}", completed);
+    println!("total:      {}", left + completed);
+}
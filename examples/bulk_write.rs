@@ -0,0 +1,46 @@
+//! Compare the cost of each `SyncPolicy` when writing many small torrents.
+//!
+//! Usage: `cargo run --release --example bulk_write [COUNT] [DIR]`
+//!
+//! `COUNT` defaults to 1000 and `DIR` defaults to the system's temp dir.
+//! Results vary wildly with the filesystem: the gap between `Full` and
+//! `None` is usually much larger on network filesystems.
+
+use lava_torrent::bencode::write::{SyncPolicy, WriteFileOptions};
+use lava_torrent::torrent::v1::Torrent;
+use std::path::PathBuf;
+use std::time::Instant;
+
+const SAMPLE: &str = "tests/files/ubuntu-16.04.4-desktop-amd64.iso.torrent";
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let count = args
+        .next()
+        .map(|arg| arg.parse().expect("COUNT must be a number"))
+        .unwrap_or(1000);
+    let root = args
+        .next()
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir)
+        .join("lava_torrent_bulk_write");
+    std::fs::create_dir_all(&root).unwrap();
+
+    let torrent = Torrent::read_from_file(SAMPLE).unwrap();
+
+    for sync in [SyncPolicy::Full, SyncPolicy::DataOnly, SyncPolicy::None] {
+        let start = Instant::now();
+        for i in 0..count {
+            torrent
+                .clone()
+                .write_into_file_with(
+                    root.join(format!("{}.torrent", i)),
+                    WriteFileOptions::default().set_sync(sync),
+                )
+                .unwrap();
+        }
+        println!("{:?}: {} files in {:?}", sync, count, start.elapsed());
+    }
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
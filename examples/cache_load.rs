@@ -0,0 +1,48 @@
+//! Compare loading torrents from cache entries against parsing them.
+//!
+//! Usage: `cargo run --release --example cache_load [COUNT]`
+//!
+//! `COUNT` defaults to 1000. Everything is kept in memory so that only
+//! decoding is measured, i.e. this is the best case for a cold load
+//! where entries have to be read from disk first.
+
+use lava_torrent::torrent::v1::Torrent;
+use std::time::Instant;
+
+const SAMPLES: [&str; 2] = [
+    "tests/files/ubuntu-16.04.4-desktop-amd64.iso.torrent",
+    "tests/files/tails-amd64-3.6.1.torrent",
+];
+
+fn main() {
+    let count = std::env::args()
+        .nth(1)
+        .map(|arg| arg.parse().expect("COUNT must be a number"))
+        .unwrap_or(1000);
+
+    for sample in SAMPLES {
+        let raw = std::fs::read(sample).unwrap();
+        let torrent = Torrent::read_from_bytes(&raw).unwrap();
+        println!("{}:", sample);
+
+        let start = Instant::now();
+        for _ in 0..count {
+            Torrent::read_from_bytes(&raw).unwrap();
+        }
+        println!("  parse: {} in {:?}", count, start.elapsed());
+
+        for include_pieces in [true, false] {
+            let entry = torrent.to_cache_bytes(include_pieces);
+            let start = Instant::now();
+            for _ in 0..count {
+                Torrent::from_cache_bytes(&entry).unwrap();
+            }
+            println!(
+                "  cache (include_pieces: {}): {} in {:?}",
+                include_pieces,
+                count,
+                start.elapsed()
+            );
+        }
+    }
+}
@@ -0,0 +1,29 @@
+//! Write the corpus of edge-case torrents used by `tests/read_corpus.rs`.
+//!
+//! Usage: `cargo run --example gen_corpus [DIR]`
+//!
+//! `DIR` defaults to `tests/corpus/out`. Each case is written to
+//! `DIR/<name>.torrent`, where the prefix of `name` tells the
+//! expected outcome of reading it (see `tests/corpus/mod.rs`).
+//! The path of each file is printed along with its info hash.
+
+#[path = "../tests/corpus/mod.rs"]
+mod corpus;
+
+use sha1::{Digest, Sha1};
+use std::path::PathBuf;
+
+fn main() {
+    let root = std::env::args()
+        .nth(1)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("tests/corpus/out"));
+    std::fs::create_dir_all(&root).unwrap();
+
+    for case in corpus::cases() {
+        let path = root.join(format!("{}.torrent", case.name));
+        std::fs::write(&path, &case.bytes).unwrap();
+        let info_hash = Sha1::digest(&case.info);
+        println!("{:x} {}", info_hash, path.display());
+    }
+}
@@ -0,0 +1,88 @@
+//! Compare the speed of each `HashingStrategy` on a generated directory.
+//!
+//! Usage: `cargo run --release --example hashing_strategies [SIZE_MIB] [DIR]`
+//!
+//! A dataset of about `SIZE_MIB` MiB (default 512), split into files of
+//! varying sizes, is generated under `DIR` (defaults to the system's temp
+//! dir) unless it already exists. Keep in mind that the page cache hides
+//! most of the difference between the strategies: to measure what a
+//! spinning disk would do, drop the cache between runs or use a dataset
+//! larger than RAM.
+
+use lava_torrent::torrent::v1::{HashingStrategy, TorrentBuilder};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+const PIECE_LENGTH: i64 = 1024 * 1024; // 1 MiB
+
+// Write files of 1 KiB to 32 MiB (with odd sizes, so that most pieces span
+// multiple files) until about `size` bytes are written.
+fn generate(root: &Path, size: u64) {
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let mut written = 0;
+    let mut i = 0;
+
+    while written < size {
+        let len = (1024 << (i % 16)).min(size - written) + i % 7;
+        let mut file = std::io::BufWriter::new(
+            std::fs::File::create(root.join(format!("{:04}.bin", i))).unwrap(),
+        );
+        for _ in 0..len {
+            // xorshift, so that the content doesn't compress
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            file.write_all(&[state as u8]).unwrap();
+        }
+        file.flush().unwrap();
+        written += len;
+        i += 1;
+    }
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let size_mib: u64 = args
+        .next()
+        .map(|arg| arg.parse().expect("SIZE_MIB must be a number"))
+        .unwrap_or(512);
+    let root = args
+        .next()
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir)
+        .join(format!("lava_torrent_hashing_strategies_{}", size_mib));
+
+    if !root.exists() {
+        std::fs::create_dir_all(&root).unwrap();
+        println!("generating {} MiB in {}...", size_mib, root.display());
+        generate(&root, size_mib * 1024 * 1024);
+    }
+
+    let mut expected = None;
+    for strategy in [
+        HashingStrategy::Sequential,
+        HashingStrategy::Parallel,
+        HashingStrategy::Pipelined,
+    ] {
+        let start = Instant::now();
+        let torrent = TorrentBuilder::new(&root, PIECE_LENGTH)
+            .set_hashing_strategy(strategy)
+            .build()
+            .unwrap();
+        let elapsed = start.elapsed();
+        println!(
+            "{:?}: {} pieces in {:?} ({:.1} MiB/s)",
+            strategy,
+            torrent.pieces.len(),
+            elapsed,
+            torrent.length as f64 / 1024.0 / 1024.0 / elapsed.as_secs_f64(),
+        );
+
+        // all strategies must agree
+        match expected {
+            None => expected = Some(torrent),
+            Some(ref expected) => assert_eq!(&torrent, expected),
+        }
+    }
+}
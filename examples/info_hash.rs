@@ -0,0 +1,101 @@
+//! Compare `Torrent::info_hash_bytes()`, which streams `info` into the
+//! hasher, with hashing the fully encoded `info` dict.
+//!
+//! Usage: `cargo run --release --example info_hash [N_PIECES]`
+//!
+//! A torrent with `N_PIECES` pieces (default 500000) is generated in
+//! memory, and its info hash is calculated both ways. For each, the best
+//! time of a few runs is reported, along with the peak # of bytes allocated
+//! while hashing (on top of the torrent itself).
+
+use lava_torrent::hash;
+use lava_torrent::torrent::v1::{Torrent, TorrentBuilder};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+const PIECE_LENGTH: i64 = 256 * 1024;
+const RUNS: usize = 5;
+
+// Tracks the # of bytes currently allocated, and the peak since the last reset.
+struct PeakAlloc;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for PeakAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let current = CURRENT.fetch_add(layout.size(), Ordering::SeqCst) + layout.size();
+        PEAK.fetch_max(current, Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        CURRENT.fetch_sub(layout.size(), Ordering::SeqCst);
+        System.dealloc(ptr, layout);
+    }
+}
+
+#[global_allocator]
+static ALLOC: PeakAlloc = PeakAlloc;
+
+// Run `f` `RUNS` times, and return its result along with
+// the best time and the peak # of bytes allocated by a run.
+fn measure<T, F: Fn() -> T>(f: F) -> (T, Duration, usize) {
+    let mut best = Duration::MAX;
+    let mut peak = 0;
+    let mut result = None;
+
+    for _ in 0..RUNS {
+        let before = CURRENT.load(Ordering::SeqCst);
+        PEAK.store(before, Ordering::SeqCst);
+        let start = Instant::now();
+        result = Some(f());
+        best = best.min(start.elapsed());
+        peak = peak.max(PEAK.load(Ordering::SeqCst) - before);
+    }
+    (result.unwrap(), best, peak)
+}
+
+fn main() {
+    let n_pieces: usize = std::env::args()
+        .nth(1)
+        .map(|arg| arg.parse().expect("N_PIECES must be a number"))
+        .unwrap_or(500_000);
+
+    // the content of the pieces doesn't matter, so
+    // build a tiny torrent and swap them in
+    let path = std::env::temp_dir().join("lava_torrent_info_hash_example");
+    std::fs::write(&path, b"sample").unwrap();
+    let torrent = Torrent {
+        length: PIECE_LENGTH * n_pieces as i64,
+        pieces: (0..n_pieces)
+            .map(|i| {
+                let mut piece = [0; 20];
+                piece[..8].copy_from_slice(&i.to_be_bytes());
+                piece
+            })
+            .collect(),
+        ..TorrentBuilder::new(&path, PIECE_LENGTH).build().unwrap()
+    };
+    std::fs::remove_file(&path).unwrap();
+    println!(
+        "{} pieces ({} MiB of piece hashes)",
+        n_pieces,
+        n_pieces * 20 / 1024 / 1024
+    );
+
+    let (encoded, encoded_time, encoded_peak) =
+        measure(|| hash::sha1(&torrent.construct_info().encode()));
+    let (streamed, streamed_time, streamed_peak) = measure(|| torrent.info_hash_bytes());
+    assert_eq!(streamed, encoded);
+
+    println!(
+        "sha1(construct_info().encode()): {:?}, peak {} bytes allocated",
+        encoded_time, encoded_peak
+    );
+    println!(
+        "info_hash_bytes():                {:?}, peak {} bytes allocated",
+        streamed_time, streamed_peak
+    );
+}
@@ -0,0 +1,599 @@
+use super::*;
+use core::cmp::Ordering;
+use core::ops::Range;
+
+// # of bytes shown on each side of the offending byte
+const CONTEXT: usize = 32;
+
+// A list or dictionary that has been opened but not closed yet.
+enum Frame {
+    List {
+        start: usize,
+    },
+    // `key` is the last key read (for checking the order of keys),
+    // and `has_key` tells whether its value is still pending
+    Dictionary {
+        start: usize,
+        key: Option<Range<usize>>,
+        has_key: bool,
+    },
+}
+
+// Where the problem is, and what it is.
+type ScanError = (usize, Cow<'static, str>);
+
+// Validate bencode without building anything, keeping track of
+// where everything is so that errors can point at the right byte.
+struct StrictScanner<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    stack: Vec<Frame>,
+    // where the last string read starts, and where its content is
+    last_string: Option<(usize, Range<usize>)>,
+}
+
+impl BencodeElem {
+    /// Parse `bytes` like [`from_bytes()`], but as strictly as possible and
+    /// with detailed errors. This is meant for debugging hand-written
+    /// bencode (e.g. literals in tests), which is easy to get subtly wrong.
+    ///
+    /// On top of what `from_bytes()` checks, the following are rejected:
+    /// - Strings shorter than their declared length. `from_bytes()`
+    ///   silently truncates a string running past the end of `bytes`.
+    /// - Integers and string lengths containing anything besides ASCII
+    ///   digits (and a leading `-` for integers), e.g. `+` or whitespace.
+    /// - Duplicate dictionary keys.
+    ///
+    /// On failure, the [`AnnotatedBencodeError`] returned shows `bytes` with
+    /// a caret at the offending byte, along with the lists and dictionaries
+    /// open there and hints for common typos (e.g. an off-by-one string
+    /// length swallowing the `e` meant to close a list).
+    ///
+    /// This is slower than `from_bytes()`, as `bytes` is scanned one more time
+    /// beforehand. Use `from_bytes()` for anything but diagnostics.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lava_torrent::bencode::BencodeElem;
+    ///
+    /// // `5:eggse` swallows the `e` that should close the dictionary
+    /// let error = BencodeElem::from_bytes_strict_annotated("d4:spam5:eggse").unwrap_err();
+    /// assert_eq!(error.offset, 14);
+    /// println!("{}", error);
+    /// ```
+    ///
+    /// [`from_bytes()`]: #method.from_bytes
+    /// [`AnnotatedBencodeError`]: struct.AnnotatedBencodeError.html
+    pub fn from_bytes_strict_annotated<B>(
+        bytes: B,
+    ) -> Result<Vec<BencodeElem>, AnnotatedBencodeError>
+    where
+        B: AsRef<[u8]>,
+    {
+        let bytes = bytes.as_ref();
+        let mut scanner = StrictScanner {
+            bytes,
+            pos: 0,
+            stack: Vec::new(),
+            last_string: None,
+        };
+        if let Err((offset, message)) = scanner.scan() {
+            return Err(scanner.annotate(offset, message));
+        }
+
+        // everything `from_bytes()` checks has been checked
+        // by now, so this is not expected to fail
+        Self::from_bytes(bytes).map_err(|e| scanner.annotate(0, Cow::Owned(format!("{}", e))))
+    }
+}
+
+impl StrictScanner<'_> {
+    fn scan(&mut self) -> Result<(), ScanError> {
+        while (self.pos < self.bytes.len()) || !self.stack.is_empty() {
+            let byte = match self.bytes.get(self.pos) {
+                Some(&byte) => byte,
+                None => return Err(self.unexpected_end()),
+            };
+
+            if let Some(Frame::Dictionary { has_key: false, .. }) = self.stack.last() {
+                if byte == DICTIONARY_POSTFIX {
+                    self.close();
+                } else if byte.is_ascii_digit() {
+                    self.scan_key()?;
+                } else {
+                    return Err((self.pos, Cow::Borrowed("Non-string dictionary key.")));
+                }
+                continue;
+            }
+
+            match byte {
+                DICTIONARY_PREFIX => {
+                    self.stack.push(Frame::Dictionary {
+                        start: self.pos,
+                        key: None,
+                        has_key: false,
+                    });
+                    self.pos += 1;
+                }
+                LIST_PREFIX => {
+                    self.stack.push(Frame::List { start: self.pos });
+                    self.pos += 1;
+                }
+                LIST_POSTFIX => match self.stack.last() {
+                    Some(Frame::List { .. }) => self.close(),
+                    Some(Frame::Dictionary { .. }) => {
+                        return Err((
+                            self.pos,
+                            Cow::Borrowed("Dictionary key found without a value."),
+                        ));
+                    }
+                    None => {
+                        return Err((
+                            self.pos,
+                            Cow::Borrowed("Unexpected `e` outside of any list or dictionary."),
+                        ));
+                    }
+                },
+                INTEGER_PREFIX => {
+                    self.scan_integer()?;
+                    self.end_value();
+                }
+                b'0'..=b'9' => {
+                    self.scan_string()?;
+                    self.end_value();
+                }
+                _ => {
+                    return Err((
+                        self.pos,
+                        Cow::Owned(format!(
+                            "Unexpected byte `{}` found where an element should start.",
+                            escape_byte(byte)
+                        )),
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Close the innermost list/dictionary, whose postfix is at `self.pos`.
+    fn close(&mut self) {
+        self.stack.pop();
+        self.pos += 1;
+        self.end_value();
+    }
+
+    // Note that a value has been read, which completes
+    // the pending entry of the innermost dictionary (if any).
+    fn end_value(&mut self) {
+        if let Some(Frame::Dictionary {
+            ref mut has_key, ..
+        }) = self.stack.last_mut()
+        {
+            *has_key = false;
+        }
+    }
+
+    fn scan_key(&mut self) -> Result<(), ScanError> {
+        let start = self.pos;
+        let key = self.scan_string()?;
+        let bytes = self.bytes;
+
+        if let Some(Frame::Dictionary {
+            key: ref mut previous,
+            ref mut has_key,
+            ..
+        }) = self.stack.last_mut()
+        {
+            if let Some(previous) = previous {
+                // "sorted as raw strings, not alphanumerics"
+                match bytes[previous.clone()].cmp(&bytes[key.clone()]) {
+                    Ordering::Greater => {
+                        return Err((start, Cow::Borrowed("A dictionary is not properly sorted.")));
+                    }
+                    Ordering::Equal => {
+                        return Err((start, Cow::Borrowed("Duplicate dictionary key found.")));
+                    }
+                    Ordering::Less => (),
+                }
+            }
+            *previous = Some(key);
+            *has_key = true;
+        }
+        Ok(())
+    }
+
+    fn scan_integer(&mut self) -> Result<(), ScanError> {
+        self.pos += 1; // skip the prefix
+        let start = self.pos;
+        if self.bytes.get(self.pos) == Some(&b'-') {
+            self.pos += 1;
+        }
+        let digits_start = self.pos;
+        self.skip_digits();
+
+        match self.bytes.get(self.pos) {
+            Some(&INTEGER_POSTFIX) if self.pos > digits_start => (),
+            Some(&byte) => {
+                return Err((
+                    self.pos,
+                    Cow::Owned(format!(
+                        "Unexpected byte `{}` found in an integer.",
+                        escape_byte(byte)
+                    )),
+                ));
+            }
+            None => return Err(self.unexpected_end()),
+        }
+
+        // only `-` and ASCII digits are in here
+        let digits = core::str::from_utf8(&self.bytes[start..self.pos]).unwrap_or_default();
+        if digits.starts_with("-0") {
+            return Err((start, Cow::Borrowed("-0 found.")));
+        } else if digits.starts_with('0') && (digits.len() != 1) {
+            return Err((start, Cow::Borrowed("Integer with leading zero(s) found.")));
+        } else if digits.parse::<i64>().is_err() {
+            return Err((
+                start,
+                Cow::Owned(format!("Input contains invalid integer: {}.", digits)),
+            ));
+        }
+        self.pos += 1; // skip the postfix
+        Ok(())
+    }
+
+    // Return where the content of the string is.
+    fn scan_string(&mut self) -> Result<Range<usize>, ScanError> {
+        let start = self.pos;
+        self.skip_digits();
+
+        match self.bytes.get(self.pos) {
+            Some(&STRING_DELIMITER) => (),
+            Some(&byte) => {
+                return Err((
+                    self.pos,
+                    Cow::Owned(format!(
+                        "Unexpected byte `{}` found in a string's length.",
+                        escape_byte(byte)
+                    )),
+                ));
+            }
+            None => return Err(self.unexpected_end()),
+        }
+
+        let digits = core::str::from_utf8(&self.bytes[start..self.pos]).unwrap_or_default();
+        if digits.starts_with('0') && (digits.len() != 1) {
+            return Err((
+                start,
+                Cow::Borrowed("String length with leading zero(s) found."),
+            ));
+        }
+        let length = digits.parse::<usize>().map_err(|_| {
+            (
+                start,
+                Cow::Borrowed("A string's length does not fit into `usize`."),
+            )
+        })?;
+
+        let content_start = self.pos + 1;
+        let remaining = self.bytes.len() - content_start;
+        if length > remaining {
+            return Err((
+                start,
+                Cow::Owned(format!(
+                    "String declared [{}] bytes long, but only [{}] remain.",
+                    length, remaining
+                )),
+            ));
+        }
+        self.pos = content_start + length;
+        self.last_string = Some((start, content_start..self.pos));
+        Ok(content_start..self.pos)
+    }
+
+    fn skip_digits(&mut self) {
+        while self
+            .bytes
+            .get(self.pos)
+            .is_some_and(|byte| byte.is_ascii_digit())
+        {
+            self.pos += 1;
+        }
+    }
+
+    fn unexpected_end(&self) -> ScanError {
+        (
+            self.bytes.len(),
+            Cow::Borrowed("Expected more bytes, but none found."),
+        )
+    }
+
+    fn annotate(&self, offset: usize, message: Cow<'static, str>) -> AnnotatedBencodeError {
+        let stack = self
+            .stack
+            .iter()
+            .map(|frame| match *frame {
+                Frame::List { start } => (BencodeKind::List, start),
+                Frame::Dictionary { start, .. } => (BencodeKind::Dictionary, start),
+            })
+            .collect::<Vec<(BencodeKind, usize)>>();
+
+        let mut annotated = format!("malformed bencode at offset {}: {}\n", offset, message);
+
+        // the input around `offset`, and a caret under `offset`
+        let start = offset.saturating_sub(CONTEXT);
+        let end = self.bytes.len().min(offset + CONTEXT + 1);
+        let mut line = String::from(if start > 0 { "..." } else { "" });
+        let mut caret = None;
+        for (i, &byte) in self.bytes[start..end].iter().enumerate() {
+            if start + i == offset {
+                caret = Some(line.len());
+            }
+            line.push_str(&escape_byte(byte));
+        }
+        // past the end of the input
+        let caret = caret.unwrap_or(line.len());
+        if end < self.bytes.len() {
+            line.push_str("...");
+        }
+        annotated.push_str(&format!("    {}\n    {}^\n", line, " ".repeat(caret)));
+
+        if stack.is_empty() {
+            annotated.push_str("not in any list or dictionary\n");
+        } else {
+            annotated.push_str("in (outermost first):\n");
+            for (kind, start) in &stack {
+                annotated.push_str(&format!("    {} at offset {}\n", kind, start));
+            }
+        }
+
+        // a string right before `offset` is the usual suspect
+        if let Some((string_start, ref content)) = self.last_string {
+            if content.end == offset {
+                match (self.bytes[content.clone()].last(), stack.last()) {
+                    (Some(&LIST_POSTFIX), Some((kind, start))) => annotated.push_str(&format!(
+                        "note: the string at offset {} ends with `e`; if that `e` was \
+                             meant to close the {} at offset {}, the string's declared \
+                             length is 1 too large\n",
+                        string_start, kind, start
+                    )),
+                    _ => annotated.push_str(&format!(
+                        "note: the string at offset {} ends right before this byte; \
+                         its declared length may be too small\n",
+                        string_start
+                    )),
+                }
+            }
+        }
+
+        AnnotatedBencodeError {
+            offset,
+            message,
+            stack,
+            annotated,
+        }
+    }
+}
+
+impl From<AnnotatedBencodeError> for LavaTorrentError {
+    fn from(e: AnnotatedBencodeError) -> LavaTorrentError {
+        LavaTorrentError::MalformedBencode(Cow::Owned(format!(
+            "{} (at offset {})",
+            e.message, e.offset
+        )))
+    }
+}
+
+fn escape_byte(byte: u8) -> String {
+    match byte {
+        b'\\' => String::from("\\\\"),
+        0x20..=0x7e => String::from(char::from(byte)),
+        _ => format!("\\x{:02x}", byte),
+    }
+}
+
+#[cfg(test)]
+mod annotated_tests {
+    use super::*;
+
+    // Return the byte the caret points at in `error`'s dump,
+    // or `None` if it points past the end of the input.
+    fn caret_byte(error: &AnnotatedBencodeError) -> Option<char> {
+        let lines = error.annotated.lines().collect::<Vec<&str>>();
+        let column = lines[2].find('^').unwrap();
+        lines[1].chars().nth(column)
+    }
+
+    #[test]
+    fn strict_annotated_ok() {
+        let bytes = "d4:eggsl1:ai-3ee4:spam4:eggse";
+        assert_eq!(
+            BencodeElem::from_bytes_strict_annotated(bytes).unwrap(),
+            BencodeElem::from_bytes(bytes).unwrap()
+        );
+        assert!(BencodeElem::from_bytes_strict_annotated("")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn strict_annotated_length_too_large() {
+        let error = BencodeElem::from_bytes_strict_annotated("d4:spam5:eggse").unwrap_err();
+
+        assert_eq!(error.offset, 14);
+        assert_eq!(error.message, "Expected more bytes, but none found.");
+        assert_eq!(error.stack, vec![(BencodeKind::Dictionary, 0)]);
+        assert_eq!(caret_byte(&error), None);
+        assert_eq!(
+            error.to_string(),
+            "malformed bencode at offset 14: Expected more bytes, but none found.\n    \
+             d4:spam5:eggse\n    \
+             \x20             ^\n\
+             in (outermost first):\n    \
+             Dictionary at offset 0\n\
+             note: the string at offset 7 ends with `e`; if that `e` was meant to close \
+             the Dictionary at offset 0, the string's declared length is 1 too large\n"
+        );
+    }
+
+    #[test]
+    fn strict_annotated_length_too_small() {
+        let error = BencodeElem::from_bytes_strict_annotated("d4:spam3:eggse").unwrap_err();
+
+        assert_eq!(error.offset, 12);
+        assert_eq!(error.message, "Non-string dictionary key.");
+        assert_eq!(caret_byte(&error), Some('s'));
+        assert!(error.annotated.contains(
+            "note: the string at offset 7 ends right before this byte; \
+             its declared length may be too small"
+        ));
+    }
+
+    #[test]
+    fn strict_annotated_truncated_string() {
+        // silently truncated by `from_bytes()`
+        assert_eq!(
+            BencodeElem::from_bytes("5:spam").unwrap(),
+            vec![bencode_elem!("spam")]
+        );
+
+        let error = BencodeElem::from_bytes_strict_annotated("5:spam").unwrap_err();
+        assert_eq!(error.offset, 0);
+        assert_eq!(
+            error.message,
+            "String declared [5] bytes long, but only [4] remain."
+        );
+        assert!(error.stack.is_empty());
+        assert_eq!(caret_byte(&error), Some('5'));
+        assert!(error.annotated.contains("not in any list or dictionary"));
+    }
+
+    #[test]
+    fn strict_annotated_unclosed_list() {
+        let error = BencodeElem::from_bytes_strict_annotated("ll4:spam4:eggse").unwrap_err();
+
+        assert_eq!(error.offset, 15);
+        assert_eq!(
+            error.stack,
+            vec![(BencodeKind::List, 0)],
+            "the inner list is closed"
+        );
+        assert!(error.annotated.contains("    List at offset 0\n"));
+    }
+
+    #[test]
+    fn strict_annotated_stray_postfix() {
+        let error = BencodeElem::from_bytes_strict_annotated("d4:spam4:eggsee").unwrap_err();
+
+        assert_eq!(error.offset, 14);
+        assert_eq!(
+            error.message,
+            "Unexpected `e` outside of any list or dictionary."
+        );
+        assert_eq!(caret_byte(&error), Some('e'));
+    }
+
+    #[test]
+    fn strict_annotated_missing_value() {
+        let error = BencodeElem::from_bytes_strict_annotated("d4:spame").unwrap_err();
+
+        assert_eq!(error.offset, 7);
+        assert_eq!(error.message, "Dictionary key found without a value.");
+    }
+
+    #[test]
+    fn strict_annotated_integers() {
+        // accepted by `from_bytes()` thanks to `str::parse()`
+        assert_eq!(BencodeElem::from_bytes("i+5e").unwrap(), vec![5.into()]);
+
+        for (bytes, offset, caret) in [
+            ("i+5e", 1, Some('+')),
+            ("li4 2ee", 3, Some(' ')),
+            ("i-0e", 1, Some('-')),
+            ("i03e", 1, Some('0')),
+            ("ie", 1, Some('e')),
+            ("i-e", 2, Some('e')),
+            ("i99999999999999999999e", 1, Some('9')),
+            ("i42", 3, None),
+        ] {
+            let error = BencodeElem::from_bytes_strict_annotated(bytes).unwrap_err();
+            assert_eq!(error.offset, offset, "{}", bytes);
+            assert_eq!(caret_byte(&error), caret, "{}", bytes);
+        }
+    }
+
+    #[test]
+    fn strict_annotated_string_lengths() {
+        for (bytes, offset, caret) in [
+            ("04:spam", 0, Some('0')),
+            ("4 :spam", 1, Some(' ')),
+            ("l4", 2, None),
+            ("d4:spam4:eggs+e", 13, Some('+')),
+        ] {
+            let error = BencodeElem::from_bytes_strict_annotated(bytes).unwrap_err();
+            assert_eq!(error.offset, offset, "{}", bytes);
+            assert_eq!(caret_byte(&error), caret, "{}", bytes);
+        }
+    }
+
+    #[test]
+    fn strict_annotated_keys() {
+        let error = BencodeElem::from_bytes_strict_annotated("d3:bar1:a3:bar1:be").unwrap_err();
+        assert_eq!(error.offset, 9);
+        assert_eq!(error.message, "Duplicate dictionary key found.");
+        assert_eq!(caret_byte(&error), Some('3'));
+
+        let error = BencodeElem::from_bytes_strict_annotated("d3:foo1:a3:bar1:be").unwrap_err();
+        assert_eq!(error.offset, 9);
+        assert_eq!(error.message, "A dictionary is not properly sorted.");
+
+        let error = BencodeElem::from_bytes_strict_annotated("di1e1:ae").unwrap_err();
+        assert_eq!(error.offset, 1);
+        assert_eq!(error.message, "Non-string dictionary key.");
+    }
+
+    #[test]
+    fn strict_annotated_long_input() {
+        let bytes = format!("l{}i1x", "0:".repeat(50));
+        let error = BencodeElem::from_bytes_strict_annotated(&bytes).unwrap_err();
+
+        assert_eq!(error.offset, 103);
+        assert_eq!(caret_byte(&error), Some('x'));
+        let input = error.annotated.lines().nth(1).unwrap();
+        assert!(input.starts_with("    ...0:0:"));
+        assert!(!input.ends_with("..."));
+
+        let bytes = format!("lx{}e", "0:".repeat(50));
+        let error = BencodeElem::from_bytes_strict_annotated(&bytes).unwrap_err();
+        assert_eq!(error.offset, 1);
+        assert_eq!(caret_byte(&error), Some('x'));
+        assert!(error.annotated.lines().nth(1).unwrap().ends_with("0:..."));
+    }
+
+    #[test]
+    fn strict_annotated_escaped_bytes() {
+        let error = BencodeElem::from_bytes_strict_annotated(b"l1:\xff\x01e").unwrap_err();
+
+        assert_eq!(error.offset, 4);
+        assert_eq!(
+            error.message,
+            "Unexpected byte `\\x01` found where an element should start."
+        );
+        let lines = error.annotated.lines().collect::<Vec<&str>>();
+        assert_eq!(lines[1], "    l1:\\xff\\x01e");
+        assert_eq!(lines[2], "           ^");
+    }
+
+    #[test]
+    fn strict_annotated_into_lava_torrent_error() {
+        let error = BencodeElem::from_bytes_strict_annotated("i42").unwrap_err();
+        match LavaTorrentError::from(error) {
+            LavaTorrentError::MalformedBencode(m) => {
+                assert_eq!(m, "Expected more bytes, but none found. (at offset 3)")
+            }
+            _ => panic!(),
+        }
+    }
+}
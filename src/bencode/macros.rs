@@ -1,52 +1,87 @@
-use super::*;
-
-// a macro to be used in tests to reduce boilerplate code
-// informal syntax:
-// -integer: as-is, works for (u8, u16, u32, i8, i16, i32, i64), conversion should be lossless
-// -string: as-is, works for both owned and borrowed strings
-// -bytes: (b1, b2, ...), support trailing comma
-// -list: [e1, e2, ...], support trailing comma
-// -dictionary: { (k1, v1), (k2, v2), ... }, support trailing comma (no trailing comma in K-V pair)
-// -raw dictionary: { ([k1], v1), ([k2], v2), ... }, support trailing comma (no trailing comma in K-V pair)
+/// Build a [`BencodeElem`](crate::bencode::BencodeElem) with a JSON-like syntax.
+///
+/// - integer: as-is, works for `u8`, `u16`, `u32`, `i8`, `i16`, `i32`, and `i64`.
+/// - string: as-is, works for both owned and borrowed strings.
+/// - bytes: `(b1, b2, ...)`.
+/// - list: `[e1, e2, ...]`.
+/// - dictionary: `{ (k1, v1), (k2, v2), ... }`.
+/// - raw dictionary (i.e. keyed by bytes): `r{ ([k1, ...], v1), ([k2, ...], v2), ... }`.
+///
+/// Trailing commas are allowed, except within a key-value pair. Elements
+/// can be nested, except in raw dictionaries. Anything that is not one of
+/// the above is passed to `BencodeElem::from()`.
+///
+/// # Example
+///
+/// ```
+/// use lava_torrent::bencode::BencodeElem;
+/// use lava_torrent::bencode_elem;
+///
+/// let torrent = bencode_elem!({
+///     ("announce", "udp://tracker.example:1337"),
+///     ("announce-list", [["udp://tracker.example:1337"], ["udp://backup.example:80"]]),
+///     ("info", {
+///         ("length", 4),
+///         ("name", "sample"),
+///         ("piece length", 16384),
+///         ("pieces", (0xde, 0xad, 0xbe, 0xef)),
+///     }),
+/// });
+///
+/// assert_eq!(
+///     torrent.encode(),
+///     b"d8:announce26:udp://tracker.example:133713:announce-listll26:udp://\
+///       tracker.example:1337el23:udp://backup.example:80ee4:infod6:lengthi4e4:\
+///       name6:sample12:piece lengthi16384e6:pieces4:\xde\xad\xbe\xefee"
+/// );
+/// ```
 #[macro_export]
 macro_rules! bencode_elem {
     ([ $( $element:tt ),* ]) => {
-        $crate::bencode::BencodeElem::List(vec![ $( bencode_elem!($element) ),* ])
+        $crate::bencode::BencodeElem::List($crate::bencode::__private::vec![
+            $( $crate::bencode_elem!($element) ),*
+        ])
     };
     ([ $( $element:tt ),+ ,]) => {
-        bencode_elem!([ $( $element ),* ])
+        $crate::bencode_elem!([ $( $element ),* ])
     };
     (( $( $element:tt ),* )) => {
-        $crate::bencode::BencodeElem::Bytes(vec![ $( $element ),* ])
+        $crate::bencode::BencodeElem::Bytes($crate::bencode::__private::vec![ $( $element ),* ])
     };
     (( $( $element:tt ),+ ,)) => {
-        bencode_elem!(( $( $element ),* ))
+        $crate::bencode_elem!(( $( $element ),* ))
     };
     ({ $( ($key:tt, $val:tt) ),* }) => {
-        $crate::bencode::BencodeElem::Dictionary(
-            ::std::collections::HashMap::from_iter(
-                vec![ $( ($key.to_owned(), bencode_elem!($val)) ),* ].into_iter()
-            )
-        )
+        $crate::bencode::BencodeElem::Dictionary(::core::iter::Iterator::collect(
+            ::core::iter::IntoIterator::into_iter($crate::bencode::__private::vec![
+                $( (
+                    $crate::bencode::__private::ToOwned::to_owned($key),
+                    $crate::bencode_elem!($val),
+                ) ),*
+            ])
+        ))
     };
     ({ $( ($key:tt, $val:tt) ),+ ,}) => {
-        bencode_elem!({ $( ($key, $val) ),* })
+        $crate::bencode_elem!({ $( ($key, $val) ),* })
     };
     (r{ $( ( [ $( $key:tt ),* ] , $val:tt) ),* }) => {
-        $crate::bencode::BencodeElem::RawDictionary(
-            ::std::collections::HashMap::from_iter(
-                vec![ $( (vec![ $( $key ),* ], bencode_elem!($val)) ),* ].into_iter()
-            )
-        )
+        $crate::bencode::BencodeElem::RawDictionary(::core::iter::Iterator::collect(
+            ::core::iter::IntoIterator::into_iter($crate::bencode::__private::vec![
+                $( (
+                    $crate::bencode::__private::vec![ $( $key ),* ],
+                    $crate::bencode_elem!($val),
+                ) ),*
+            ])
+        ))
     };
     (r{ $( ( [ $( $key:tt ),+ ,] , $val:tt) ),* }) => {
-        bencode_elem!(r{ $( ( [ $( $key ),* ], $val) ),* })
+        $crate::bencode_elem!(r{ $( ( [ $( $key ),* ], $val) ),* })
     };
     (r{ $( ( [ $( $key:tt ),* ] , $val:tt) ),+ ,}) => {
-        bencode_elem!(r{ $( ( [ $( $key ),* ], $val) ),* })
+        $crate::bencode_elem!(r{ $( ( [ $( $key ),* ], $val) ),* })
     };
     (r{ $( ( [ $( $key:tt ),+ ,] , $val:tt) ),+ ,}) => {
-        bencode_elem!(r{ $( ( [ $( $key ),* ], $val) ),* })
+        $crate::bencode_elem!(r{ $( ( [ $( $key ),* ], $val) ),* })
     };
     ($other:expr) => {
         $crate::bencode::BencodeElem::from($other)
@@ -55,7 +90,7 @@ macro_rules! bencode_elem {
 
 #[cfg(test)]
 mod bencode_elem_macro_tests {
-    use super::*;
+    use crate::bencode::*;
     use std::iter::FromIterator;
 
     #[test]
@@ -3,18 +3,37 @@
 //! Most of methods are associated methods of `BencodeElem`. Some general methods
 //! are placed at the module level, and they can be found in [`write`](write/index.html).
 
+use crate::display::display_bytes_summary;
+use crate::LavaTorrentError;
+use alloc::borrow::{Cow, ToOwned};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::convert::From;
+use core::fmt;
+use core::hash::BuildHasher;
+use core::iter::FromIterator;
+#[cfg(not(feature = "std"))]
+pub(crate) use hashbrown::HashMap;
 use itertools;
 use itertools::Itertools;
-use std::collections::HashMap;
-use std::convert::From;
-use std::fmt;
+#[cfg(feature = "std")]
+pub(crate) use std::collections::HashMap;
 
-#[cfg(test)]
 #[macro_use]
 mod macros;
+mod annotated;
 mod read;
 pub mod write;
 
+// Used by `bencode_elem!`, which can't assume
+// that its caller has access to `alloc`.
+#[doc(hidden)]
+pub mod __private {
+    pub use alloc::borrow::ToOwned;
+    pub use alloc::vec;
+}
+
 const DICTIONARY_PREFIX: u8 = b'd';
 const DICTIONARY_POSTFIX: u8 = b'e';
 const LIST_PREFIX: u8 = b'l';
@@ -23,6 +42,12 @@ const INTEGER_PREFIX: u8 = b'i';
 const INTEGER_POSTFIX: u8 = b'e';
 const STRING_DELIMITER: u8 = b':';
 
+/// Key under which a [`BencodeElem::Dictionary`] keeps its entries whose
+/// keys are not valid utf8 (as a [`BencodeElem::RawDictionary`]).
+///
+/// See [`BencodeElem::raw_entries()`].
+pub const RAW_KEYS: &str = "__raw_keys__";
+
 /// Represent a single bencode element.
 ///
 /// There are 4 variants in the [spec], but this enum has 6 variants. The extra variants are
@@ -32,6 +57,12 @@ const STRING_DELIMITER: u8 = b':';
 /// valid utf8 strings. They are best treated differently
 /// in actual implementations to make things easier.
 ///
+/// When parsing, a dictionary only becomes a `RawDictionary` if none of its keys
+/// is valid utf8. Otherwise it becomes a `Dictionary`, and the entries with
+/// non-utf8 keys (if any) are kept under the reserved key [`RAW_KEYS`], so
+/// that the rest of the dictionary can be used as usual. When encoding, such
+/// entries are put back in place, so the encoded form is unchanged.
+///
 /// Note that the `Integer` variant here uses `i64` explicitly instead of using a type alias like
 /// [`Integer`]. The reasoning behind this is that if you have to handle
 /// bencode directly then what you are doing is relatively low-level. In this case, exposing the
@@ -49,6 +80,220 @@ pub enum BencodeElem {
     RawDictionary(HashMap<Vec<u8>, BencodeElem>),
 }
 
+/// The kind (i.e. variant) of a [`BencodeElem`], without its data.
+///
+/// Useful for describing a `BencodeElem` (e.g. in error messages, or
+/// across FFI boundaries) without matching on it.
+///
+/// The `u8` discriminants (obtainable with `kind as u8`) are part of
+/// the API and will not change:
+///
+/// | Kind            | Discriminant |
+/// |-----------------|--------------|
+/// | `String`        | 0            |
+/// | `Bytes`         | 1            |
+/// | `Integer`       | 2            |
+/// | `List`          | 3            |
+/// | `Dictionary`    | 4            |
+/// | `RawDictionary` | 5            |
+///
+/// [`BencodeElem`]: enum.BencodeElem.html
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[repr(u8)]
+pub enum BencodeKind {
+    String = 0,
+    Bytes = 1,
+    Integer = 2,
+    List = 3,
+    Dictionary = 4,
+    RawDictionary = 5,
+}
+
+impl BencodeKind {
+    /// Return the name of this kind, which is the same as the
+    /// name of the corresponding `BencodeElem` variant.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BencodeKind::String => "String",
+            BencodeKind::Bytes => "Bytes",
+            BencodeKind::Integer => "Integer",
+            BencodeKind::List => "List",
+            BencodeKind::Dictionary => "Dictionary",
+            BencodeKind::RawDictionary => "RawDictionary",
+        }
+    }
+}
+
+impl fmt::Display for BencodeKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Where each element of an encoded bencode value is, so that the exact
+/// bytes of any of its elements can be retrieved later.
+///
+/// Captured by [`TorrentSpans::capture()`] and queried by
+/// [`BencodeElem::raw_bytes_at()`]. Spans only describe the bytes they
+/// were captured from: they are not updated when anything decoded from
+/// those bytes is mutated, and must be captured again after re-encoding.
+#[derive(Clone, Debug)]
+pub struct TorrentSpans {
+    root: read::Spanned,
+}
+
+/// Error returned by [`BencodeElem::from_bytes_strict_annotated()`].
+///
+/// Besides where parsing stopped and why, it holds a human-readable
+/// dump of the input around that point, with a caret under the
+/// offending byte, which is what `Display` prints.
+///
+/// It converts into [`LavaTorrentError::MalformedBencode`] (without
+/// the dump) for use with `?`.
+///
+/// [`BencodeElem::from_bytes_strict_annotated()`]: enum.BencodeElem.html#method.from_bytes_strict_annotated
+/// [`LavaTorrentError::MalformedBencode`]: ../enum.LavaTorrentError.html#variant.MalformedBencode
+#[derive(Clone, Debug, Eq, PartialEq, thiserror::Error)]
+#[error("{annotated}")]
+pub struct AnnotatedBencodeError {
+    /// Offset of the offending byte, or the length of
+    /// the input if it ended too early.
+    pub offset: usize,
+    /// What is wrong.
+    pub message: Cow<'static, str>,
+    /// The lists and dictionaries still open at `offset`, as their kind and
+    /// the offset they start at, from the outermost to the innermost.
+    pub stack: Vec<(BencodeKind, usize)>,
+    /// The multi-line dump printed by `Display`.
+    pub annotated: String,
+}
+
+impl BencodeElem {
+    /// Return the kind of `self`.
+    pub fn kind(&self) -> BencodeKind {
+        match *self {
+            BencodeElem::String(_) => BencodeKind::String,
+            BencodeElem::Bytes(_) => BencodeKind::Bytes,
+            BencodeElem::Integer(_) => BencodeKind::Integer,
+            BencodeElem::List(_) => BencodeKind::List,
+            BencodeElem::Dictionary(_) => BencodeKind::Dictionary,
+            BencodeElem::RawDictionary(_) => BencodeKind::RawDictionary,
+        }
+    }
+
+    /// Return the name of the kind of `self`.
+    ///
+    /// Same as `self.kind().as_str()`.
+    pub fn kind_str(&self) -> &'static str {
+        self.kind().as_str()
+    }
+
+    /// Return the entries of `self` whose keys are not valid utf8.
+    ///
+    /// For a `Dictionary`, these are the entries kept under [`RAW_KEYS`]
+    /// (if any). For a `RawDictionary`, this is the dictionary itself.
+    /// Otherwise `None` is returned.
+    pub fn raw_entries(&self) -> Option<&HashMap<Vec<u8>, BencodeElem>> {
+        match *self {
+            BencodeElem::Dictionary(ref dict) => match dict.get(RAW_KEYS) {
+                Some(BencodeElem::RawDictionary(raw)) => Some(raw),
+                _ => None,
+            },
+            BencodeElem::RawDictionary(ref dict) => Some(dict),
+            _ => None,
+        }
+    }
+
+    /// Append `elem` to `self`, which must be a `List`.
+    ///
+    /// If `self` is not a `List`, then `Err(error)` will be returned
+    /// and `self` is left unchanged.
+    pub fn push(&mut self, elem: BencodeElem) -> Result<(), LavaTorrentError> {
+        match *self {
+            BencodeElem::List(ref mut list) => {
+                list.push(elem);
+                Ok(())
+            }
+            ref other => Err(LavaTorrentError::InvalidArgument(Cow::Owned(format!(
+                "Cannot push into a {} element, expected List.",
+                other.kind()
+            )))),
+        }
+    }
+
+    /// Insert `elem` under `key` into `self`, which must be a `Dictionary`
+    /// or a `RawDictionary`, and return the value previously under `key` (if any).
+    ///
+    /// If `self` is neither, then `Err(error)` will be
+    /// returned and `self` is left unchanged.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lava_torrent::bencode::BencodeElem;
+    /// use lava_torrent::bencode_elem;
+    ///
+    /// let trackers = ["udp://a.example:1337", "udp://b.example:80"];
+    /// let mut torrent = bencode_elem!({ ("announce", "udp://a.example:1337") });
+    /// torrent
+    ///     .insert(
+    ///         "announce-list".to_owned(),
+    ///         // 1 tier per tracker
+    ///         trackers.iter().map(|&url| bencode_elem!([url])).collect(),
+    ///     )
+    ///     .unwrap();
+    ///
+    /// assert_eq!(
+    ///     torrent,
+    ///     bencode_elem!({
+    ///         ("announce", "udp://a.example:1337"),
+    ///         ("announce-list", [["udp://a.example:1337"], ["udp://b.example:80"]]),
+    ///     })
+    /// );
+    /// ```
+    pub fn insert(
+        &mut self,
+        key: String,
+        elem: BencodeElem,
+    ) -> Result<Option<BencodeElem>, LavaTorrentError> {
+        match *self {
+            BencodeElem::Dictionary(ref mut dict) => Ok(dict.insert(key, elem)),
+            BencodeElem::RawDictionary(ref mut dict) => Ok(dict.insert(key.into_bytes(), elem)),
+            ref other => Err(LavaTorrentError::InvalidArgument(Cow::Owned(format!(
+                "Cannot insert into a {} element, expected Dictionary.",
+                other.kind()
+            )))),
+        }
+    }
+
+    /// Convert `self` into a dictionary keyed by raw bytes, with all entries
+    /// (including those kept under [`RAW_KEYS`]) at the same level.
+    ///
+    /// If `self` is neither a `Dictionary` nor a `RawDictionary`,
+    /// then `Err(self)` will be returned.
+    pub fn into_raw_dictionary(self) -> Result<HashMap<Vec<u8>, BencodeElem>, BencodeElem> {
+        match self {
+            BencodeElem::Dictionary(mut dict) => {
+                let raw = match dict.remove(RAW_KEYS) {
+                    Some(BencodeElem::RawDictionary(raw)) => raw,
+                    Some(other) => {
+                        dict.insert(RAW_KEYS.to_owned(), other);
+                        HashMap::new()
+                    }
+                    None => HashMap::new(),
+                };
+                Ok(dict
+                    .into_iter()
+                    .map(|(key, val)| (key.into_bytes(), val))
+                    .chain(raw)
+                    .collect())
+            }
+            BencodeElem::RawDictionary(dict) => Ok(dict),
+            other => Err(other),
+        }
+    }
+}
+
 impl From<u8> for BencodeElem {
     fn from(val: u8) -> BencodeElem {
         BencodeElem::Integer(i64::from(val))
@@ -115,18 +360,67 @@ impl From<Vec<u8>> for BencodeElem {
     }
 }
 
+impl FromIterator<BencodeElem> for BencodeElem {
+    /// Collect the elements into a `List`.
+    fn from_iter<I>(iter: I) -> BencodeElem
+    where
+        I: IntoIterator<Item = BencodeElem>,
+    {
+        BencodeElem::List(iter.into_iter().collect())
+    }
+}
+
+impl FromIterator<(String, BencodeElem)> for BencodeElem {
+    /// Collect the entries into a `Dictionary`. If a key
+    /// occurs more than once, its last value is kept.
+    fn from_iter<I>(iter: I) -> BencodeElem
+    where
+        I: IntoIterator<Item = (String, BencodeElem)>,
+    {
+        BencodeElem::Dictionary(iter.into_iter().collect())
+    }
+}
+
+/// Return the entries of `dict` sorted by key, i.e. in the order
+/// they are encoded in.
+///
+/// Keys are compared as raw bytes, as required by bencode. Use this
+/// instead of iterating `dict` directly whenever the order is visible
+/// (e.g. in logs or diffs), since `HashMap`s iterate in a random order.
+///
+/// ```
+/// use lava_torrent::bencode::{self, BencodeElem};
+///
+/// let elem = lava_torrent::bencode_elem!({ ("b", 2), ("a", 1), ("B", 3) });
+/// if let BencodeElem::Dictionary(ref dict) = elem {
+///     let keys = bencode::sorted_entries(dict)
+///         .into_iter()
+///         .map(|(key, _)| key.as_str())
+///         .collect::<Vec<_>>();
+///     assert_eq!(keys, ["B", "a", "b"]);
+/// }
+/// ```
+pub fn sorted_entries<S>(dict: &HashMap<String, BencodeElem, S>) -> Vec<(&String, &BencodeElem)>
+where
+    S: BuildHasher,
+{
+    dict.iter()
+        .sorted_by_key(|&(key, _)| key.as_bytes())
+        .collect()
+}
+
 impl fmt::Display for BencodeElem {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             BencodeElem::String(ref string) => write!(f, r#""{}""#, string),
-            BencodeElem::Bytes(ref bytes) => write!(f, "[{:#02x}]", bytes.iter().format(", ")),
+            BencodeElem::Bytes(ref bytes) => write!(f, "{}", display_bytes_summary(bytes)),
             BencodeElem::Integer(ref int) => write!(f, "{}", int),
             BencodeElem::List(ref list) => write!(f, "[{}]", itertools::join(list, ", ")),
             BencodeElem::Dictionary(ref dict) => write!(
                 f,
                 "{{ {} }}",
-                dict.iter()
-                    .sorted_by_key(|&(key, _)| key.as_bytes())
+                sorted_entries(dict)
+                    .into_iter()
                     .format_with(", ", |(k, v), f| f(&format_args!(r#"("{}", {})"#, k, v)))
             ),
             BencodeElem::RawDictionary(ref dict) => write!(
@@ -135,8 +429,8 @@ impl fmt::Display for BencodeElem {
                 dict.iter()
                     .sorted_by_key(|&(key, _)| key)
                     .format_with(", ", |(k, v), f| f(&format_args!(
-                        r#"("{}", {})"#,
-                        k.iter().map(|b| format!("{:x}", b)).format(""),
+                        "({}, {})",
+                        display_bytes_summary(k),
                         v
                     )))
             ),
@@ -144,10 +438,184 @@ impl fmt::Display for BencodeElem {
     }
 }
 
+#[cfg(test)]
+mod bencode_kind_tests {
+    use super::*;
+
+    #[test]
+    fn kind_ok() {
+        assert_eq!(bencode_elem!("spam").kind(), BencodeKind::String);
+        assert_eq!(bencode_elem!((0xff, 0xfe)).kind(), BencodeKind::Bytes);
+        assert_eq!(bencode_elem!(42).kind(), BencodeKind::Integer);
+        assert_eq!(bencode_elem!([42, "spam"]).kind(), BencodeKind::List);
+        assert_eq!(
+            bencode_elem!({ ("spam", 42) }).kind(),
+            BencodeKind::Dictionary
+        );
+        assert_eq!(
+            BencodeElem::RawDictionary(HashMap::new()).kind(),
+            BencodeKind::RawDictionary
+        );
+    }
+
+    #[test]
+    fn kind_str_ok() {
+        assert_eq!(bencode_elem!("spam").kind_str(), "String");
+        assert_eq!(bencode_elem!([42, "spam"]).kind_str(), "List");
+        assert_eq!(BencodeKind::RawDictionary.to_string(), "RawDictionary");
+    }
+
+    #[test]
+    fn discriminant_ok() {
+        // these values are part of the API, do NOT change them
+        assert_eq!(BencodeKind::String as u8, 0);
+        assert_eq!(BencodeKind::Bytes as u8, 1);
+        assert_eq!(BencodeKind::Integer as u8, 2);
+        assert_eq!(BencodeKind::List as u8, 3);
+        assert_eq!(BencodeKind::Dictionary as u8, 4);
+        assert_eq!(BencodeKind::RawDictionary as u8, 5);
+    }
+}
+
+#[cfg(test)]
+mod bencode_elem_raw_entries_tests {
+    use super::*;
+
+    #[test]
+    fn raw_entries_ok() {
+        let elem = BencodeElem::from_bytes(b"d3:zoo3:moo2:\xff\xfe4:eggse").unwrap();
+        assert_eq!(
+            elem[0].raw_entries(),
+            Some(&HashMap::from_iter(vec![(
+                vec![0xff, 0xfe],
+                bencode_elem!("eggs")
+            )]))
+        );
+        assert_eq!(bencode_elem!({ ("zoo", "moo") }).raw_entries(), None);
+        assert_eq!(bencode_elem!("zoo").raw_entries(), None);
+    }
+
+    #[test]
+    fn into_raw_dictionary_ok() {
+        let elem = BencodeElem::from_bytes(b"d3:zoo3:moo2:\xff\xfe4:eggse")
+            .unwrap()
+            .remove(0);
+        assert_eq!(
+            elem.into_raw_dictionary().unwrap(),
+            HashMap::from_iter(vec![
+                (b"zoo".to_vec(), bencode_elem!("moo")),
+                (vec![0xff, 0xfe], bencode_elem!("eggs")),
+            ])
+        );
+    }
+
+    #[test]
+    fn into_raw_dictionary_not_dictionary() {
+        assert_eq!(
+            bencode_elem!("zoo").into_raw_dictionary(),
+            Err(bencode_elem!("zoo"))
+        );
+    }
+}
+
+#[cfg(test)]
+mod bencode_elem_build_tests {
+    use super::*;
+
+    #[test]
+    fn from_iter_list() {
+        let list = (1..=3).map(BencodeElem::from).collect::<BencodeElem>();
+        assert_eq!(list, bencode_elem!([1, 2, 3]));
+        assert_eq!(
+            Vec::<BencodeElem>::new()
+                .into_iter()
+                .collect::<BencodeElem>(),
+            bencode_elem!([])
+        );
+    }
+
+    #[test]
+    fn from_iter_dictionary() {
+        let dict = vec![
+            ("spam".to_owned(), bencode_elem!("eggs")),
+            ("cow".to_owned(), bencode_elem!("bull")),
+            ("cow".to_owned(), bencode_elem!("moo")),
+        ]
+        .into_iter()
+        .collect::<BencodeElem>();
+        assert_eq!(dict, bencode_elem!({ ("cow", "moo"), ("spam", "eggs") }));
+    }
+
+    #[test]
+    fn push_ok() {
+        let mut list = bencode_elem!([1]);
+        list.push(bencode_elem!("two")).unwrap();
+        assert_eq!(list, bencode_elem!([1, "two"]));
+    }
+
+    #[test]
+    fn push_not_list() {
+        for mut elem in [
+            bencode_elem!(1),
+            bencode_elem!("list"),
+            bencode_elem!((0x01)),
+            bencode_elem!({ ("a", 1) }),
+            bencode_elem!(r{ ([0xff], 1) }),
+        ] {
+            let original = elem.clone();
+            match elem.push(bencode_elem!(2)) {
+                Err(LavaTorrentError::InvalidArgument(m)) => assert_eq!(
+                    m,
+                    format!("Cannot push into a {} element, expected List.", elem.kind())
+                ),
+                _ => panic!(),
+            }
+            assert_eq!(elem, original);
+        }
+    }
+
+    #[test]
+    fn insert_ok() {
+        let mut dict = bencode_elem!({ ("a", 1) });
+        assert_eq!(dict.insert("b".to_owned(), bencode_elem!(2)).unwrap(), None);
+        assert_eq!(
+            dict.insert("a".to_owned(), bencode_elem!(3)).unwrap(),
+            Some(bencode_elem!(1))
+        );
+        assert_eq!(dict, bencode_elem!({ ("a", 3), ("b", 2) }));
+
+        let mut raw = bencode_elem!(r{ ([0xff], 1) });
+        assert_eq!(raw.insert("b".to_owned(), bencode_elem!(2)).unwrap(), None);
+        assert_eq!(raw, bencode_elem!(r{ ([0xff], 1), ([b'b'], 2) }));
+    }
+
+    #[test]
+    fn insert_not_dictionary() {
+        for mut elem in [
+            bencode_elem!(1),
+            bencode_elem!("dict"),
+            bencode_elem!((0x01)),
+            bencode_elem!([1]),
+        ] {
+            let original = elem.clone();
+            match elem.insert("a".to_owned(), bencode_elem!(2)) {
+                Err(LavaTorrentError::InvalidArgument(m)) => assert_eq!(
+                    m,
+                    format!(
+                        "Cannot insert into a {} element, expected Dictionary.",
+                        elem.kind()
+                    )
+                ),
+                _ => panic!(),
+            }
+            assert_eq!(elem, original);
+        }
+    }
+}
+
 #[cfg(test)]
 mod bencode_elem_display_tests {
     use super::*;
-    use std::iter::FromIterator;
 
     #[test]
     fn display_test_string() {
@@ -158,7 +626,27 @@ mod bencode_elem_display_tests {
     fn display_test_bytes() {
         assert_eq!(
             bencode_elem!((0xff, 0xf8, 0xff, 0xee)).to_string(),
-            "[0xff, 0xf8, 0xff, 0xee]"
+            "<4 bytes: 0xfff8ffee>"
+        );
+    }
+
+    #[test]
+    fn display_test_huge_bytes() {
+        assert_eq!(
+            BencodeElem::Bytes(vec![0; 1024 * 1024]).to_string(),
+            format!("<1048576 bytes: 0x{}...>", "00".repeat(32))
+        );
+    }
+
+    #[test]
+    fn display_test_raw_dictionary() {
+        let dict = BencodeElem::RawDictionary(HashMap::from_iter(vec![
+            (vec![0xff, 0x0a], bencode_elem!(1)),
+            (b"key".to_vec(), bencode_elem!((0xff, 0xfe))),
+        ]));
+        assert_eq!(
+            dict.to_string(),
+            "{ (<3 bytes: 0x6b6579>, <2 bytes: 0xfffe>), (<2 bytes: 0xff0a>, 1) }"
         );
     }
 
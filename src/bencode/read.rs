@@ -1,14 +1,64 @@
+#[cfg(feature = "std")]
+use super::write::with_path_context;
 use super::*;
 use crate::util;
 use crate::util::ByteBuffer;
 use crate::LavaTorrentError;
-use std::borrow::Cow;
-use std::collections::HashMap;
+use alloc::borrow::Cow;
+use core::ops::Range;
+#[cfg(feature = "std")]
 use std::fs::File;
+#[cfg(feature = "std")]
 use std::io::{BufReader, Read};
-use std::iter::FromIterator;
+#[cfg(feature = "std")]
 use std::path::Path;
 
+// Where an element is in its encoded form, along with where its children are.
+#[derive(Clone, Debug)]
+pub(crate) struct Spanned {
+    pub(crate) span: Range<usize>,
+    pub(crate) kind: SpannedKind,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) enum SpannedKind {
+    Leaf(BencodeElem),
+    List(Vec<Spanned>),
+    // entries are in encoded order, duplicate keys included
+    Dictionary(Vec<(Vec<u8>, Spanned)>),
+}
+
+// Errors found while decoding integers (including string lengths).
+//
+// Unlike `LavaTorrentError`, these borrow whatever they refer to, so
+// messages are only formatted if an error makes it out of the parser.
+#[derive(Clone, Copy, Debug)]
+enum DecodeError<'a> {
+    MissingDelimiter,
+    InvalidUtf8,
+    NegativeZero,
+    LeadingZero,
+    SignWithoutDigits,
+    InvalidInteger(&'a str),
+}
+
+impl From<DecodeError<'_>> for LavaTorrentError {
+    fn from(e: DecodeError<'_>) -> LavaTorrentError {
+        LavaTorrentError::MalformedBencode(match e {
+            DecodeError::MissingDelimiter => Cow::Borrowed("Integer delimiter not found."),
+            DecodeError::InvalidUtf8 => Cow::Borrowed("Input contains invalid UTF-8."),
+            DecodeError::NegativeZero => Cow::Borrowed("-0 found."),
+            DecodeError::LeadingZero => Cow::Borrowed("Integer with leading zero(s) found."),
+            DecodeError::SignWithoutDigits => {
+                Cow::Borrowed("Integer with a sign but no digits found.")
+            }
+            DecodeError::InvalidInteger(int_string) => {
+                Cow::Owned(format!("Input contains invalid integer: {}.", int_string))
+            }
+        })
+    }
+}
+
 impl BencodeElem {
     /// Parse `bytes` and return all `BencodeElem` found.
     ///
@@ -33,6 +83,26 @@ impl BencodeElem {
         Ok(elements)
     }
 
+    /// Parse the first `BencodeElem` in `bytes`, and return it along
+    /// with the number of bytes it is encoded in.
+    ///
+    /// Unlike [`from_bytes()`], whatever follows the first element is
+    /// not parsed (and so doesn't have to be valid bencode). This is
+    /// useful when bencode is embedded in some other data.
+    ///
+    /// If `bytes` is empty or does not start with a valid element,
+    /// then `Err(error)` will be returned.
+    ///
+    /// [`from_bytes()`]: #method.from_bytes
+    pub fn from_bytes_prefix<B>(bytes: B) -> Result<(BencodeElem, usize), LavaTorrentError>
+    where
+        B: AsRef<[u8]>,
+    {
+        let mut bytes = ByteBuffer::new(bytes.as_ref());
+        let element = BencodeElem::parse(&mut bytes)?;
+        Ok((element, bytes.pos()))
+    }
+
     /// Parse the content of the file at `path` and return all `BencodeElem` found.
     ///
     /// If the file at `path` is empty, then `Ok(vec)` will be returned, but
@@ -40,16 +110,21 @@ impl BencodeElem {
     ///
     /// If the file at `path` contains any malformed bencode, or if any other
     /// error is encountered (e.g. `IOError`), then `Err(error)`
-    /// will be returned.
+    /// will be returned. Errors name `path` in their message.
+    #[cfg(feature = "std")]
     pub fn from_file<P>(path: P) -> Result<Vec<BencodeElem>, LavaTorrentError>
     where
         P: AsRef<Path>,
     {
-        let file = File::open(&path)?;
-        let mut bytes = Vec::new();
+        let path = path.as_ref();
+        let read = || {
+            let file = File::open(path)?;
+            let mut bytes = Vec::new();
 
-        BufReader::new(file).read_to_end(&mut bytes)?;
-        Self::from_bytes(bytes)
+            BufReader::new(file).read_to_end(&mut bytes)?;
+            Self::from_bytes(bytes)
+        };
+        read().map_err(|e| with_path_context(e, path))
     }
 
     fn peek_byte(bytes: &mut ByteBuffer) -> Result<u8, LavaTorrentError> {
@@ -84,15 +159,8 @@ impl BencodeElem {
 
         while Self::peek_byte(bytes)? != DICTIONARY_POSTFIX {
             // more to parse
-            match Self::decode_bytes(bytes) {
-                Ok(BencodeElem::Bytes(key)) => entries.push((key, Self::parse(bytes)?)),
-                Ok(_) => {
-                    return Err(LavaTorrentError::MalformedBencode(Cow::Borrowed(
-                        "Non-string dictionary key.",
-                    )));
-                }
-                Err(e) => return Err(e),
-            }
+            let key = Self::decode_key(bytes)?;
+            entries.push((key, Self::parse(bytes)?));
         }
         bytes.advance(1); // consume the postfix
 
@@ -107,21 +175,52 @@ impl BencodeElem {
             }
         }
 
-        // convert to Dictionary if possible
-        let mut entries2 = Vec::new();
-        for (k, v) in &entries {
-            match String::from_utf8(k.to_owned()) {
-                Ok(s) => entries2.push((s, v.to_owned())),
-                Err(_) => {
-                    return Ok(BencodeElem::RawDictionary(HashMap::from_iter(
-                        entries.into_iter(),
-                    )));
+        // convert to Dictionary if possible, setting aside non-utf8 keys
+        let mut dict = HashMap::with_capacity(entries.len());
+        let mut raw = HashMap::new();
+        for (k, v) in entries {
+            match String::from_utf8(k) {
+                Ok(k) => {
+                    dict.insert(k, v);
+                }
+                Err(e) => {
+                    raw.insert(e.into_bytes(), v);
                 }
             }
         }
-        Ok(BencodeElem::Dictionary(HashMap::from_iter(
-            entries2.into_iter(),
-        )))
+
+        // an actual `RAW_KEYS` mapping to a RawDictionary would be
+        // mistaken for set-aside keys when encoding
+        let is_ambiguous = matches!(dict.get(RAW_KEYS), Some(BencodeElem::RawDictionary(_)));
+        if raw.is_empty() && !is_ambiguous {
+            Ok(BencodeElem::Dictionary(dict))
+        } else if dict.is_empty() || dict.contains_key(RAW_KEYS) {
+            // nothing to salvage, or `RAW_KEYS` is taken by an actual key
+            raw.extend(dict.into_iter().map(|(k, v)| (k.into_bytes(), v)));
+            Ok(BencodeElem::RawDictionary(raw))
+        } else {
+            dict.insert(RAW_KEYS.to_owned(), BencodeElem::RawDictionary(raw));
+            Ok(BencodeElem::Dictionary(dict))
+        }
+    }
+
+    // Keys must be strings. Check before decoding one, as the string
+    // decoder would otherwise read e.g. `i4e3:` as a garbled length.
+    fn decode_key(bytes: &mut ByteBuffer) -> Result<Vec<u8>, LavaTorrentError> {
+        let found = match Self::peek_byte(bytes)? {
+            INTEGER_PREFIX => "integer",
+            LIST_PREFIX => "list",
+            DICTIONARY_PREFIX => "dictionary",
+            _ => match Self::decode_bytes(bytes)? {
+                BencodeElem::Bytes(key) => return Ok(key),
+                _ => panic!("decode_bytes() did not return bytes."),
+            },
+        };
+        Err(LavaTorrentError::MalformedBencode(Cow::Owned(format!(
+            "Dictionary key must be a string, found {} at offset {}.",
+            found,
+            bytes.pos()
+        ))))
     }
 
     fn decode_list(bytes: &mut ByteBuffer) -> Result<BencodeElem, LavaTorrentError> {
@@ -140,39 +239,43 @@ impl BencodeElem {
         bytes: &mut ByteBuffer,
         delimiter: u8,
     ) -> Result<BencodeElem, LavaTorrentError> {
-        let old_pos = bytes.pos();
-        let read: Vec<u8> = bytes.take_while(|&&b| b != delimiter).cloned().collect();
-        let bytes_read = bytes.pos() - old_pos;
+        Ok(BencodeElem::Integer(Self::decode_raw_integer(
+            bytes, delimiter,
+        )?))
+    }
 
-        if read.len() == bytes_read {
-            Err(LavaTorrentError::MalformedBencode(Cow::Borrowed(
-                "Integer delimiter not found.",
-            )))
-        } else {
-            match String::from_utf8(read) {
-                Ok(int_string) => {
-                    if int_string.starts_with("-0") {
-                        Err(LavaTorrentError::MalformedBencode(Cow::Borrowed(
-                            "-0 found.",
-                        )))
-                    } else if (int_string.starts_with('0')) && (int_string.len() != 1) {
-                        Err(LavaTorrentError::MalformedBencode(Cow::Borrowed(
-                            "Integer with leading zero(s) found.",
-                        )))
-                    } else {
-                        match int_string.parse() {
-                            Ok(int) => Ok(BencodeElem::Integer(int)),
-                            Err(_) => Err(LavaTorrentError::MalformedBencode(Cow::Owned(format!(
-                                "Input contains invalid integer: {}.",
-                                int_string
-                            )))),
-                        }
-                    }
-                }
-                Err(_) => Err(LavaTorrentError::MalformedBencode(Cow::Borrowed(
-                    "Input contains invalid UTF-8.",
-                ))),
+    // Like `decode_integer()`, but nothing is allocated, even on errors.
+    fn decode_raw_integer<'a>(
+        bytes: &mut ByteBuffer<'a>,
+        delimiter: u8,
+    ) -> Result<i64, DecodeError<'a>> {
+        let remaining = bytes.remaining();
+        let len = match remaining.iter().position(|&b| b == delimiter) {
+            Some(len) => len,
+            None => {
+                bytes.advance(remaining.len());
+                return Err(DecodeError::MissingDelimiter);
             }
+        };
+        bytes.advance(len + 1); // consume the delimiter as well
+
+        let int_string =
+            core::str::from_utf8(&remaining[..len]).map_err(|_| DecodeError::InvalidUtf8)?;
+        // a leading '+' is tolerated, as `str::parse()` has always accepted it
+        let digits = int_string.strip_prefix(['-', '+']).unwrap_or(int_string);
+        if digits.is_empty() && !int_string.is_empty() {
+            Err(DecodeError::SignWithoutDigits)
+        } else if !digits.bytes().all(|b| b.is_ascii_digit()) {
+            // e.g. `-0x1`, which would be reported as `-0` otherwise
+            Err(DecodeError::InvalidInteger(int_string))
+        } else if int_string.starts_with("-0") {
+            Err(DecodeError::NegativeZero)
+        } else if (int_string.starts_with('0')) && (int_string.len() != 1) {
+            Err(DecodeError::LeadingZero)
+        } else {
+            int_string
+                .parse()
+                .map_err(|_| DecodeError::InvalidInteger(int_string))
         }
     }
 
@@ -188,19 +291,159 @@ impl BencodeElem {
     }
 
     fn decode_bytes(bytes: &mut ByteBuffer) -> Result<BencodeElem, LavaTorrentError> {
-        match Self::decode_integer(bytes, STRING_DELIMITER) {
-            Ok(BencodeElem::Integer(len)) => {
-                if let Ok(len) = util::i64_to_usize(len) {
-                    Ok(BencodeElem::Bytes(bytes.take(len).cloned().collect()))
-                } else {
-                    Err(LavaTorrentError::MalformedBencode(Cow::Borrowed(
-                        "A string's length does not fit into `usize`.",
-                    )))
+        let len = Self::decode_raw_integer(bytes, STRING_DELIMITER)?;
+        if let Ok(len) = util::i64_to_usize(len) {
+            Ok(BencodeElem::Bytes(bytes.take_up_to(len).to_vec()))
+        } else {
+            Err(LavaTorrentError::MalformedBencode(Cow::Borrowed(
+                "A string's length does not fit into `usize`.",
+            )))
+        }
+    }
+
+    // Parse `bytes`, which must contain exactly 1 element, and
+    // record where the element and each of its descendants are.
+    pub(crate) fn parse_spanned(bytes: &[u8]) -> Result<Spanned, LavaTorrentError> {
+        Ok(Self::parse_spanned_elem(bytes)?.1)
+    }
+
+    // Same as `parse_spanned()`, but also return the element itself.
+    pub(crate) fn parse_spanned_elem(
+        bytes: &[u8],
+    ) -> Result<(BencodeElem, Spanned), LavaTorrentError> {
+        // validate with the regular parser first, so that
+        // `scan()` doesn't have to duplicate its checks
+        let mut parsed = Self::from_bytes(bytes)?;
+        if parsed.len() != 1 {
+            return Err(LavaTorrentError::MalformedBencode(Cow::Owned(format!(
+                "Expected 1 and only 1 top-level element, {} found.",
+                parsed.len()
+            ))));
+        }
+
+        Ok((parsed.remove(0), Self::scan(&mut ByteBuffer::new(bytes))?))
+    }
+
+    #[cfg(feature = "std")]
+    // Like `parse_spanned()`, but `bytes` must contain a dictionary, whose
+    // keys don't have to be sorted. Values are still parsed strictly.
+    pub(crate) fn parse_spanned_unsorted(bytes: &[u8]) -> Result<Spanned, LavaTorrentError> {
+        let mut buf = ByteBuffer::new(bytes);
+        if Self::peek_byte(&mut buf)? != DICTIONARY_PREFIX {
+            return Err(LavaTorrentError::MalformedBencode(Cow::Borrowed(
+                "Expected a dictionary.",
+            )));
+        }
+        buf.advance(1);
+
+        let mut entries = Vec::new();
+        while Self::peek_byte(&mut buf)? != DICTIONARY_POSTFIX {
+            let key = Self::decode_key(&mut buf)?;
+            // as in `parse_spanned()`, validate before scanning
+            let start = buf.pos();
+            Self::parse(&mut buf)?;
+            let mut value = ByteBuffer::new(bytes);
+            value.advance(start);
+            entries.push((key, Self::scan(&mut value)?));
+        }
+        buf.advance(1);
+
+        if !buf.is_empty() {
+            return Err(LavaTorrentError::MalformedBencode(Cow::Borrowed(
+                "Unexpected bytes after the dictionary.",
+            )));
+        }
+        Ok(Spanned {
+            span: 0..bytes.len(),
+            kind: SpannedKind::Dictionary(entries),
+        })
+    }
+
+    fn scan(bytes: &mut ByteBuffer) -> Result<Spanned, LavaTorrentError> {
+        let start = bytes.pos();
+        let kind = match Self::peek_byte(bytes)? {
+            DICTIONARY_PREFIX => {
+                bytes.advance(1);
+                let mut entries = Vec::new();
+                while Self::peek_byte(bytes)? != DICTIONARY_POSTFIX {
+                    let key = Self::decode_key(bytes)?;
+                    entries.push((key, Self::scan(bytes)?));
                 }
+                bytes.advance(1);
+                SpannedKind::Dictionary(entries)
             }
-            Ok(_) => panic!("decode_integer() did not return an integer."),
-            Err(e) => Err(e),
+            LIST_PREFIX => {
+                bytes.advance(1);
+                let mut items = Vec::new();
+                while Self::peek_byte(bytes)? != LIST_POSTFIX {
+                    items.push(Self::scan(bytes)?);
+                }
+                bytes.advance(1);
+                SpannedKind::List(items)
+            }
+            _ => SpannedKind::Leaf(Self::parse(bytes)?),
+        };
+
+        Ok(Spanned {
+            span: start..bytes.pos(),
+            kind,
+        })
+    }
+}
+
+impl TorrentSpans {
+    /// Parse `bytes`, which must contain exactly 1 element, and record
+    /// where the element and each of its descendants are.
+    pub fn capture(bytes: &[u8]) -> Result<TorrentSpans, LavaTorrentError> {
+        Ok(TorrentSpans {
+            root: BencodeElem::parse_spanned(bytes)?,
+        })
+    }
+
+    #[cfg(feature = "std")]
+    // Same as `capture()`, but also return the parsed element,
+    // so that callers needing both don't have to parse `bytes` again.
+    pub(crate) fn capture_elem(
+        bytes: &[u8],
+    ) -> Result<(BencodeElem, TorrentSpans), LavaTorrentError> {
+        let (elem, root) = BencodeElem::parse_spanned_elem(bytes)?;
+        Ok((elem, TorrentSpans { root }))
+    }
+}
+
+impl BencodeElem {
+    /// Return the encoded bytes of the element at `path` in `bytes`,
+    /// exactly as they appear there.
+    ///
+    /// `spans` must have been captured from `bytes`. Each step in `path` is a
+    /// dictionary key, and the last occurrence of a duplicate key is used
+    /// (as [`from_bytes()`](BencodeElem::from_bytes) does). An empty `path`
+    /// returns the whole element.
+    ///
+    /// `None` is returned if there is no element at `path`, or if `bytes`
+    /// is evidently not what `spans` was captured from (i.e. its length
+    /// differs). Nothing is ever re-encoded.
+    pub fn raw_bytes_at<'a>(
+        spans: &TorrentSpans,
+        bytes: &'a [u8],
+        path: &[&[u8]],
+    ) -> Option<&'a [u8]> {
+        if spans.root.span.end != bytes.len() {
+            return None;
         }
+
+        let mut spanned = &spans.root;
+        for key in path {
+            spanned = match spanned.kind {
+                SpannedKind::Dictionary(ref entries) => entries
+                    .iter()
+                    .rev()
+                    .find(|(k, _)| k == key)
+                    .map(|(_, v)| v)?,
+                _ => return None,
+            };
+        }
+        bytes.get(spanned.span.clone())
     }
 }
 
@@ -232,6 +475,24 @@ mod bencode_elem_read_tests {
         }
     }
 
+    #[test]
+    fn from_bytes_prefix_ok() {
+        let (elem, len) = BencodeElem::from_bytes_prefix("d1:ai1ee\r\n<html>").unwrap();
+        assert_eq!(elem, bencode_elem!({ ("a", 1) }));
+        assert_eq!(len, 8);
+    }
+
+    #[test]
+    fn from_bytes_prefix_err() {
+        match BencodeElem::from_bytes_prefix("") {
+            Err(LavaTorrentError::MalformedBencode(m)) => {
+                assert_eq!(m, "Expected more bytes, but none found.");
+            }
+            _ => panic!(),
+        }
+        assert!(BencodeElem::from_bytes_prefix("d1:ai1e").is_err());
+    }
+
     #[test]
     fn decode_integer_ok() {
         let bytes = "0e".as_bytes();
@@ -312,6 +573,128 @@ mod bencode_elem_read_tests {
         }
     }
 
+    #[test]
+    fn decode_integer_boundaries() {
+        for (bytes, expected) in [
+            ("-9223372036854775808e", i64::MIN),
+            ("9223372036854775807e", i64::MAX),
+            // 19 digits
+            ("1000000000000000000e", 1_000_000_000_000_000_000),
+            ("-1000000000000000000e", -1_000_000_000_000_000_000),
+        ] {
+            assert_eq!(
+                BencodeElem::decode_integer(
+                    &mut ByteBuffer::new(bytes.as_bytes()),
+                    INTEGER_POSTFIX
+                )
+                .unwrap(),
+                bencode_elem!(expected),
+                "{}",
+                bytes
+            );
+        }
+    }
+
+    #[test]
+    fn decode_integer_out_of_range() {
+        for int_string in [
+            "-9223372036854775809",
+            // 19 digits
+            "9999999999999999999",
+            "-9999999999999999999",
+            // 20 digits
+            "10000000000000000000",
+            "-10000000000000000000",
+        ] {
+            let bytes = format!("{}e", int_string);
+            match BencodeElem::decode_integer(
+                &mut ByteBuffer::new(bytes.as_bytes()),
+                INTEGER_POSTFIX,
+            ) {
+                Err(LavaTorrentError::MalformedBencode(m)) => {
+                    assert_eq!(
+                        m,
+                        format!("Input contains invalid integer: {}.", int_string)
+                    );
+                }
+                _ => panic!("{}", int_string),
+            }
+        }
+    }
+
+    #[test]
+    fn decode_integer_sign_only() {
+        for bytes in ["-e", "+e"] {
+            match BencodeElem::decode_integer(
+                &mut ByteBuffer::new(bytes.as_bytes()),
+                INTEGER_POSTFIX,
+            ) {
+                Err(LavaTorrentError::MalformedBencode(m)) => {
+                    assert_eq!(m, "Integer with a sign but no digits found.");
+                }
+                _ => panic!("{}", bytes),
+            }
+        }
+    }
+
+    #[test]
+    fn decode_integer_invalid_forms() {
+        for (int_string, expected) in [
+            ("-+5", "Input contains invalid integer: -+5."),
+            ("+-5", "Input contains invalid integer: +-5."),
+            // not digits after a zero
+            ("0x1", "Input contains invalid integer: 0x1."),
+            ("-0x1", "Input contains invalid integer: -0x1."),
+            ("0-", "Input contains invalid integer: 0-."),
+            ("1-", "Input contains invalid integer: 1-."),
+            (" 1", "Input contains invalid integer:  1."),
+            // zeros only
+            ("00", "Integer with leading zero(s) found."),
+            ("-00", "-0 found."),
+            ("-09", "-0 found."),
+        ] {
+            let bytes = format!("{}e", int_string);
+            match BencodeElem::decode_integer(
+                &mut ByteBuffer::new(bytes.as_bytes()),
+                INTEGER_POSTFIX,
+            ) {
+                Err(LavaTorrentError::MalformedBencode(m)) => assert_eq!(m, expected),
+                _ => panic!("{}", int_string),
+            }
+        }
+    }
+
+    #[test]
+    fn decode_integer_round_trip() {
+        let mut samples = vec![i64::MIN, i64::MIN + 1, -1, 0, 1, i64::MAX - 1, i64::MAX];
+        for power in (0..19).map(|exp| 10_i64.pow(exp)) {
+            for n in [power - 1, power, power + 1] {
+                samples.extend([n, -n]);
+            }
+        }
+        // spread over the whole range
+        samples.extend((0..1000_i64).map(|i| i.wrapping_mul(0x9e37_79b9_7f4a_7c15_u64 as i64)));
+
+        for n in samples {
+            let encoded = BencodeElem::Integer(n).encode();
+            assert_eq!(
+                BencodeElem::from_bytes(&encoded).unwrap(),
+                vec![BencodeElem::Integer(n)]
+            );
+
+            // the same routine decodes string lengths
+            let len = format!("{}:", n);
+            assert_eq!(
+                BencodeElem::decode_raw_integer(
+                    &mut ByteBuffer::new(len.as_bytes()),
+                    STRING_DELIMITER
+                )
+                .unwrap(),
+                n
+            );
+        }
+    }
+
     #[test]
     fn decode_integer_no_delimiter() {
         let bytes = "9223372036854775807".as_bytes();
@@ -376,6 +759,17 @@ mod bencode_elem_read_tests {
         }
     }
 
+    #[test]
+    fn decode_string_sign_only_len() {
+        let bytes = "-:spam".as_bytes();
+        match BencodeElem::decode_string(&mut ByteBuffer::new(bytes)) {
+            Err(LavaTorrentError::MalformedBencode(m)) => {
+                assert_eq!(m, "Integer with a sign but no digits found.");
+            }
+            _ => panic!(),
+        }
+    }
+
     #[test]
     fn decode_string_no_delimiter() {
         let bytes = "4spam".as_bytes();
@@ -488,12 +882,95 @@ mod bencode_elem_read_tests {
         let bytes = "i4e3:moo4:spam4:eggse".as_bytes();
         match BencodeElem::decode_dictionary(&mut ByteBuffer::new(bytes)) {
             Err(LavaTorrentError::MalformedBencode(m)) => {
-                assert_eq!(m, "Input contains invalid integer: i4e3.");
+                assert_eq!(
+                    m,
+                    "Dictionary key must be a string, found integer at offset 0."
+                );
             }
             _ => panic!(),
         }
     }
 
+    #[test]
+    fn decode_dictionary_non_string_key_2() {
+        for (bytes, expected) in [
+            (
+                "li4ee3:mooe",
+                "Dictionary key must be a string, found list at offset 0.",
+            ),
+            (
+                "d1:ai1ee3:mooe",
+                "Dictionary key must be a string, found dictionary at offset 0.",
+            ),
+            // after several valid entries
+            (
+                "1:ai1e1:bli2ee1:cd1:xi3eei4e3:mooe",
+                "Dictionary key must be a string, found integer at offset 25.",
+            ),
+        ] {
+            match BencodeElem::decode_dictionary(&mut ByteBuffer::new(bytes.as_bytes())) {
+                Err(LavaTorrentError::MalformedBencode(m)) => assert_eq!(m, expected),
+                other => panic!("{:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn from_bytes_non_string_key() {
+        // offsets are from the start of the input
+        match BencodeElem::from_bytes("d3:cowi1ei4e3:mooe") {
+            Err(LavaTorrentError::MalformedBencode(m)) => assert_eq!(
+                m,
+                "Dictionary key must be a string, found integer at offset 9."
+            ),
+            other => panic!("{:?}", other),
+        }
+        match BencodeElem::parse_spanned(b"ld3:cowi1eli4ee3:mooee") {
+            Err(LavaTorrentError::MalformedBencode(m)) => assert_eq!(
+                m,
+                "Dictionary key must be a string, found list at offset 10."
+            ),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn parse_spanned_unsorted_ok() {
+        let bytes = b"d4:spami1e3:cowd1:ai2eee";
+        let spanned = BencodeElem::parse_spanned_unsorted(bytes).unwrap();
+        assert_eq!(spanned.span, 0..bytes.len());
+        match spanned.kind {
+            SpannedKind::Dictionary(entries) => {
+                let keys: Vec<_> = entries.iter().map(|(key, _)| key.as_slice()).collect();
+                assert_eq!(keys, vec![&b"spam"[..], b"cow"]);
+                assert_eq!(&bytes[entries[0].1.span.clone()], b"i1e");
+                assert_eq!(&bytes[entries[1].1.span.clone()], b"d1:ai2ee");
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn parse_spanned_unsorted_invalid() {
+        for (bytes, message) in [
+            (&b"li1ee"[..], "Expected a dictionary."),
+            (b"d1:ai1eei2e", "Unexpected bytes after the dictionary."),
+            // only the top level may be unsorted
+            (
+                b"d1:ad1:bi1e1:ai2eee",
+                "A dictionary is not properly sorted.",
+            ),
+            (b"d1:ai1e", "Expected more bytes, but none found."),
+        ] {
+            match BencodeElem::parse_spanned_unsorted(bytes) {
+                Err(LavaTorrentError::MalformedBencode(m)) => assert_eq!(m, message),
+                _ => panic!(),
+            }
+        }
+    }
+
     #[test]
     fn decode_dictionary_not_sorted() {
         let bytes = "3:zoo3:moo4:spam4:eggse".as_bytes();
@@ -525,7 +1002,43 @@ mod bencode_elem_read_tests {
 
         assert_eq!(
             BencodeElem::decode_dictionary(&mut ByteBuffer::new(&bytes)).unwrap(),
-            bencode_elem!(r{ ([b'z', b'o', b'o'], "moo"), ([0xff, 0xf8, 0xff, 0xee], "eggs") })
+            BencodeElem::Dictionary(HashMap::from_iter(vec![
+                ("zoo".to_owned(), bencode_elem!("moo")),
+                (
+                    RAW_KEYS.to_owned(),
+                    bencode_elem!(r{ ([0xff, 0xf8, 0xff, 0xee], "eggs") })
+                ),
+            ]))
+        );
+    }
+
+    #[test]
+    fn decode_raw_dictionary_ok_3() {
+        // a real `__raw_keys__` key cannot be told apart from the
+        // companion, so the whole dictionary is kept raw
+        let mut bytes = "12:__raw_keys__d3:foo3:bare".as_bytes().to_owned();
+        bytes.extend(vec![b'4', b':', 0xff, 0xf8, 0xff, 0xee]);
+        bytes.extend("4:eggse".as_bytes());
+
+        assert_eq!(
+            BencodeElem::decode_dictionary(&mut ByteBuffer::new(&bytes)).unwrap(),
+            bencode_elem!(r{
+                ([b'_', b'_', b'r', b'a', b'w', b'_', b'k', b'e', b'y', b's', b'_', b'_'], { ("foo", "bar") }),
+                ([0xff, 0xf8, 0xff, 0xee], "eggs")
+            })
+        );
+    }
+
+    #[test]
+    fn decode_raw_dictionary_ok_4() {
+        // no invalid utf8 key, so `__raw_keys__` is an ordinary key
+        let bytes = "12:__raw_keys__d3:foo3:bare3:zoo3:mooe"
+            .as_bytes()
+            .to_owned();
+
+        assert_eq!(
+            BencodeElem::decode_dictionary(&mut ByteBuffer::new(&bytes)).unwrap(),
+            bencode_elem!({ ("__raw_keys__", { ("foo", "bar") }), ("zoo", "moo") })
         );
     }
 
@@ -576,4 +1089,73 @@ mod bencode_elem_read_tests {
             bencode_elem!({ ("cow", "moo"), ("spam", "eggs") })
         );
     }
+
+    #[test]
+    fn raw_bytes_at_ok() {
+        // non-canonical: `i+5e` and a duplicate key
+        let bytes = "d1:ai1e1:ai2e1:bd1:ci+5eee".as_bytes();
+        let spans = TorrentSpans::capture(bytes).unwrap();
+
+        assert_eq!(BencodeElem::raw_bytes_at(&spans, bytes, &[]), Some(bytes));
+        assert_eq!(
+            BencodeElem::raw_bytes_at(&spans, bytes, &[b"b"]),
+            Some("d1:ci+5ee".as_bytes())
+        );
+        assert_eq!(
+            BencodeElem::raw_bytes_at(&spans, bytes, &[b"b", b"c"]),
+            Some("i+5e".as_bytes())
+        );
+        // the last duplicate wins
+        assert_eq!(
+            BencodeElem::raw_bytes_at(&spans, bytes, &[b"a"]),
+            Some("i2e".as_bytes())
+        );
+    }
+
+    #[test]
+    fn raw_bytes_at_missing() {
+        let bytes = "d1:al1:xe1:bi0ee".as_bytes();
+        let spans = TorrentSpans::capture(bytes).unwrap();
+
+        assert_eq!(BencodeElem::raw_bytes_at(&spans, bytes, &[b"c"]), None);
+        // only dictionaries can be stepped into
+        assert_eq!(
+            BencodeElem::raw_bytes_at(&spans, bytes, &[b"a", b"x"]),
+            None
+        );
+        assert_eq!(
+            BencodeElem::raw_bytes_at(&spans, bytes, &[b"b", b"x"]),
+            None
+        );
+    }
+
+    #[test]
+    fn raw_bytes_at_other_bytes() {
+        let bytes = "d1:ai1ee".as_bytes();
+        let spans = TorrentSpans::capture(bytes).unwrap();
+
+        assert_eq!(
+            BencodeElem::raw_bytes_at(&spans, "d1:ai10ee".as_bytes(), &[b"a"]),
+            None
+        );
+    }
+
+    #[test]
+    fn capture_spans_multiple_elements() {
+        assert!(TorrentSpans::capture("i1ei2e".as_bytes()).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn capture_elem_ok() {
+        let bytes = "d1:ai1e1:bl1:xee".as_bytes();
+        let (elem, spans) = TorrentSpans::capture_elem(bytes).unwrap();
+
+        assert_eq!(vec![elem], BencodeElem::from_bytes(bytes).unwrap());
+        assert!(TorrentSpans::capture_elem("i1ei2e".as_bytes()).is_err());
+        assert_eq!(
+            BencodeElem::raw_bytes_at(&spans, bytes, &[b"b"]),
+            Some("l1:xe".as_bytes())
+        );
+    }
 }
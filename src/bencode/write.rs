@@ -1,65 +1,512 @@
 //! Module for bencode-related encoding.
+//!
+//! The `write_*()` functions require the `std` feature,
+//! while the `encode_*()` functions are always available.
 
+use super::read::{Spanned, SpannedKind};
 use super::*;
 use crate::LavaTorrentError;
-use std::fs::File;
-use std::hash::BuildHasher;
+use alloc::vec;
+use core::hash::BuildHasher;
+#[cfg(feature = "std")]
+use sha1::{Digest, Sha1};
+#[cfg(feature = "std")]
+use std::fs::{File, OpenOptions};
+#[cfg(feature = "std")]
 use std::io::{BufWriter, Write};
-use std::path::Path;
+#[cfg(feature = "std")]
+use std::path::{Path, PathBuf};
+
+// Destination of encoded bytes. With `std`, this is anything
+// that implements `io::Write`. Without it, only `Vec<u8>`.
+trait Sink {
+    fn put(&mut self, bytes: &[u8]) -> Result<(), LavaTorrentError>;
+}
+
+#[cfg(feature = "std")]
+impl<W> Sink for W
+where
+    W: Write + ?Sized,
+{
+    fn put(&mut self, bytes: &[u8]) -> Result<(), LavaTorrentError> {
+        Ok(self.write_all(bytes)?)
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl Sink for Vec<u8> {
+    fn put(&mut self, bytes: &[u8]) -> Result<(), LavaTorrentError> {
+        self.extend_from_slice(bytes);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+/// How [`write_into_file_with()`] persists a file after writing it.
+///
+/// [`write_into_file_with()`]: BencodeElem::write_into_file_with
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum SyncPolicy {
+    /// Call `sync_all()`, i.e. flush both data and metadata to disk.
+    /// This is what `write_into_file()` does.
+    #[default]
+    Full,
+    /// Call `sync_data()`, i.e. flush data but not necessarily
+    /// metadata (e.g. modification time) to disk.
+    DataOnly,
+    /// Do not sync. The data is handed to the OS, which flushes
+    /// it to disk at its own pace. Useful for bulk writes where
+    /// an fsync per file would be prohibitively slow.
+    None,
+}
+
+#[cfg(feature = "std")]
+/// Options for [`write_into_file_with()`].
+///
+/// More options may be added, so create this with `default()`
+/// and the `set_*()` methods:
+///
+/// ```
+/// use lava_torrent::bencode::write::{SyncPolicy, WriteFileOptions};
+///
+/// let options = WriteFileOptions::default()
+///     .set_sync(SyncPolicy::None)
+///     .set_create_parent_dirs(true);
+/// assert_eq!(options.sync, SyncPolicy::None);
+/// ```
+///
+/// [`write_into_file_with()`]: BencodeElem::write_into_file_with
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub struct WriteFileOptions {
+    /// How the file is synced after being written. Defaults
+    /// to [`SyncPolicy::Full`], like `write_into_file()`.
+    pub sync: SyncPolicy,
+    /// Create the parent directories of the file (like
+    /// [`create_dir_all()`](std::fs::create_dir_all) does) if they
+    /// don't exist yet. Defaults to `false`.
+    pub create_parent_dirs: bool,
+}
+
+#[cfg(feature = "std")]
+impl WriteFileOptions {
+    /// Set `sync`.
+    pub fn set_sync(self, sync: SyncPolicy) -> WriteFileOptions {
+        WriteFileOptions { sync, ..self }
+    }
+
+    /// Set `create_parent_dirs`.
+    pub fn set_create_parent_dirs(self, create_parent_dirs: bool) -> WriteFileOptions {
+        WriteFileOptions {
+            create_parent_dirs,
+            ..self
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+/// Create (or truncate) `path`, call `write` with a buffered writer
+/// wrapping it, then sync the file as specified by `options`.
+///
+/// Errors name `path` (see [`with_path_context()`]).
+pub(crate) fn write_file_with<P, F>(
+    path: P,
+    options: WriteFileOptions,
+    write: F,
+) -> Result<(), LavaTorrentError>
+where
+    P: AsRef<Path>,
+    F: FnOnce(&mut BufWriter<&File>) -> Result<(), LavaTorrentError>,
+{
+    let path = path.as_ref();
+    let create_and_write = || {
+        if options.create_parent_dirs {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+        }
+        write_opened_file_with(File::create(path)?, options, write)
+    };
+    create_and_write().map_err(|e| with_path_context(e, path))
+}
+
+#[cfg(feature = "std")]
+/// Prefix the message of `e` with `path`, so that it says which file
+/// it is about. The variant (and the `io::ErrorKind` of IO errors) is kept,
+/// so the error can still be matched on as before. IO errors are wrapped
+/// in a [`PathContext`], so the original one (e.g. its `raw_os_error()`)
+/// is still reachable through `source()`.
+///
+/// Only used by file-level entry points, as byte-level ones have no path.
+pub(crate) fn with_path_context(e: LavaTorrentError, path: &Path) -> LavaTorrentError {
+    let prefixed = |m: Cow<'static, str>| Cow::Owned(format!("[{}]: {}", path.display(), m));
+    match e {
+        LavaTorrentError::Io(e) => {
+            // `e` is rarely shared, but if it is, rebuild what we can of it
+            let source = std::sync::Arc::try_unwrap(e).unwrap_or_else(|e| match e.raw_os_error() {
+                Some(code) => std::io::Error::from_raw_os_error(code),
+                None => std::io::Error::new(e.kind(), e.to_string()),
+            });
+            let kind = source.kind();
+            let context = PathContext {
+                path: path.to_path_buf(),
+                source,
+            };
+            std::io::Error::new(kind, context).into()
+        }
+        LavaTorrentError::MalformedBencode(m) => LavaTorrentError::MalformedBencode(prefixed(m)),
+        LavaTorrentError::MalformedTorrent(m) => LavaTorrentError::MalformedTorrent(prefixed(m)),
+        LavaTorrentError::MalformedResponse(m) => LavaTorrentError::MalformedResponse(prefixed(m)),
+        LavaTorrentError::TorrentBuilderFailure(m) => {
+            LavaTorrentError::TorrentBuilderFailure(prefixed(m))
+        }
+        LavaTorrentError::InvalidArgument(m) => LavaTorrentError::InvalidArgument(prefixed(m)),
+        LavaTorrentError::FailedNumericConv(m) => LavaTorrentError::FailedNumericConv(prefixed(m)),
+    }
+}
+
+#[cfg(feature = "std")]
+/// An IO error on the file at `path`, as built by [`with_path_context()`].
+#[derive(Debug, thiserror::Error)]
+#[error("[{}]: {}", .path.display(), .source)]
+pub(crate) struct PathContext {
+    path: PathBuf,
+    source: std::io::Error,
+}
+
+#[cfg(feature = "std")]
+/// Like [`write_file_with()`], but writes into an already opened `file`
+/// (e.g. one opened with `create_new`).
+pub(crate) fn write_opened_file_with<F>(
+    file: File,
+    options: WriteFileOptions,
+    write: F,
+) -> Result<(), LavaTorrentError>
+where
+    F: FnOnce(&mut BufWriter<&File>) -> Result<(), LavaTorrentError>,
+{
+    write_buffered(&file, write)?;
+    match options.sync {
+        SyncPolicy::Full => file.sync_all()?,
+        SyncPolicy::DataOnly => file.sync_data()?,
+        SyncPolicy::None => (),
+    }
+    Ok(())
+}
+
+#[cfg(feature = "std")]
+/// Like [`write_opened_file_with()`], but replaces `path` atomically:
+/// a temporary file is written in the same directory, then renamed over
+/// `path`. `path` is thus never left truncated or half-written, and
+/// other links to the file it replaces keep their content.
+///
+/// The temporary file is removed if anything fails.
+pub(crate) fn replace_file_with<F>(
+    path: &Path,
+    options: WriteFileOptions,
+    write: F,
+) -> Result<(), LavaTorrentError>
+where
+    F: FnOnce(&mut BufWriter<&File>) -> Result<(), LavaTorrentError>,
+{
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let mut n = 0_u64;
+    let (tmp_path, file) = loop {
+        let tmp_path = dir.join(format!(".{}.{}-{}.tmp", file_name, std::process::id(), n));
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&tmp_path)
+        {
+            Ok(file) => break (tmp_path, file),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => n += 1,
+            Err(e) => return Err(e.into()),
+        }
+    };
+
+    let result = write_opened_file_with(file, options, write)
+        .and_then(|()| std::fs::rename(&tmp_path, path).map_err(LavaTorrentError::from));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+#[cfg(feature = "std")]
+// Explicitly flush the buffer so that errors are reported
+// instead of being silently dropped along with `BufWriter`.
+fn write_buffered<W, F>(dst: W, write: F) -> Result<W, LavaTorrentError>
+where
+    W: Write,
+    F: FnOnce(&mut BufWriter<W>) -> Result<(), LavaTorrentError>,
+{
+    let mut writer = BufWriter::new(dst);
+    write(&mut writer)?;
+    Ok(writer.into_inner().map_err(|e| e.into_error())?)
+}
+
+// Write `magnitude` in decimal (prefixed with `-` if `negative`). Unlike
+// `to_string()`, this doesn't allocate, which adds up when every string
+// length and integer is written straight into a hasher (e.g. for info hashes).
+fn put_decimal<K>(negative: bool, mut magnitude: u64, dst: &mut K) -> Result<(), LavaTorrentError>
+where
+    K: Sink + ?Sized,
+{
+    // `u64::MAX` has 20 digits
+    let mut buf = [0; 21];
+    let mut start = buf.len();
+    loop {
+        start -= 1;
+        buf[start] = b'0' + (magnitude % 10) as u8;
+        magnitude /= 10;
+        if magnitude == 0 {
+            break;
+        }
+    }
+    if negative {
+        start -= 1;
+        buf[start] = b'-';
+    }
+    dst.put(&buf[start..])
+}
+
+fn put_string<S, K>(string: S, dst: &mut K) -> Result<(), LavaTorrentError>
+where
+    S: AsRef<str>,
+    K: Sink + ?Sized,
+{
+    let string = string.as_ref();
+    put_decimal(false, string.len() as u64, dst)?;
+    dst.put(&[STRING_DELIMITER])?;
+    dst.put(string.as_bytes())?;
+    Ok(())
+}
+
+fn put_bytes<B, K>(bytes: B, dst: &mut K) -> Result<(), LavaTorrentError>
+where
+    B: AsRef<[u8]>,
+    K: Sink + ?Sized,
+{
+    let bytes = bytes.as_ref();
+    put_decimal(false, bytes.len() as u64, dst)?;
+    dst.put(&[STRING_DELIMITER])?;
+    dst.put(bytes)?;
+    Ok(())
+}
+
+fn put_integer<K>(int: i64, dst: &mut K) -> Result<(), LavaTorrentError>
+where
+    K: Sink + ?Sized,
+{
+    dst.put(&[INTEGER_PREFIX])?;
+    put_decimal(int < 0, int.unsigned_abs(), dst)?;
+    dst.put(&[INTEGER_POSTFIX])?;
+    Ok(())
+}
+
+fn put_list<L, K>(list: L, dst: &mut K) -> Result<(), LavaTorrentError>
+where
+    L: AsRef<[BencodeElem]>,
+    K: Sink + ?Sized,
+{
+    let list = list.as_ref();
+    dst.put(&[LIST_PREFIX])?;
+    for item in list {
+        item.put_into(dst)?;
+    }
+    dst.put(&[LIST_POSTFIX])?;
+    Ok(())
+}
+
+fn put_dictionary<K, S>(
+    dict: &HashMap<String, BencodeElem, S>,
+    dst: &mut K,
+) -> Result<(), LavaTorrentError>
+where
+    K: Sink + ?Sized,
+    S: BuildHasher,
+{
+    dst.put(&[DICTIONARY_PREFIX])?;
+    for (key, val) in sorted_entries(dict) {
+        put_bytes(key, dst)?;
+        val.put_into(dst)?;
+    }
+    dst.put(&[DICTIONARY_POSTFIX])?;
+    Ok(())
+}
+
+// Return the entries of `dict` in encoding order, with the
+// entries set aside under `RAW_KEYS` (if any) put back in place.
+fn sorted_entries<S>(dict: &HashMap<String, BencodeElem, S>) -> Vec<(&[u8], &BencodeElem)>
+where
+    S: BuildHasher,
+{
+    let raw = match dict.get(RAW_KEYS) {
+        Some(BencodeElem::RawDictionary(raw)) => Some(raw),
+        _ => None,
+    };
+    let mut sorted = dict
+        .iter()
+        .filter(|&(key, _)| raw.is_none() || key != RAW_KEYS)
+        .map(|(key, val)| (key.as_bytes(), val))
+        .chain(
+            raw.into_iter()
+                .flatten()
+                .map(|(key, val)| (key.as_slice(), val)),
+        )
+        .collect::<Vec<(&[u8], &BencodeElem)>>();
+    // "Keys must be strings and appear in sorted order
+    // (sorted as raw strings, not alphanumerics)."
+    sorted.sort_by_key(|&(key, _)| key);
+    sorted
+}
+
+#[cfg(feature = "std")]
+/// Like [`write_dictionary()`], except that the value of `key` is
+/// written by `put_value` instead of being taken from `dict`. This allows
+/// streaming a large value without building it in memory first.
+///
+/// If `dict` does contain `key`, its value wins and `put_value` is not called.
+pub(crate) fn write_dictionary_with<W, S, F>(
+    dict: &HashMap<String, BencodeElem, S>,
+    key: &str,
+    put_value: F,
+    dst: &mut W,
+) -> Result<(), LavaTorrentError>
+where
+    W: Write + ?Sized,
+    S: BuildHasher,
+    F: FnOnce(&mut W) -> Result<(), LavaTorrentError>,
+{
+    let mut put_value = if dict.contains_key(key) {
+        None
+    } else {
+        Some(put_value)
+    };
+
+    dst.put(&[DICTIONARY_PREFIX])?;
+    for (k, val) in sorted_entries(dict) {
+        if k > key.as_bytes() {
+            if let Some(put_value) = put_value.take() {
+                put_bytes(key, dst)?;
+                put_value(dst)?;
+            }
+        }
+        put_bytes(k, dst)?;
+        val.put_into(dst)?;
+    }
+    if let Some(put_value) = put_value {
+        put_bytes(key, dst)?;
+        put_value(dst)?;
+    }
+    dst.put(&[DICTIONARY_POSTFIX])?;
+    Ok(())
+}
+
+#[cfg(feature = "std")]
+/// Encode the concatenation of `chunks` and write the result to `dst`,
+/// without concatenating them first. `len` must be their total length.
+pub(crate) fn write_bytes_chunked<'a, W, I>(
+    len: usize,
+    chunks: I,
+    dst: &mut W,
+) -> Result<(), LavaTorrentError>
+where
+    W: Write + ?Sized,
+    I: IntoIterator<Item = &'a [u8]>,
+{
+    put_decimal(false, len as u64, dst)?;
+    dst.put(&[STRING_DELIMITER])?;
+    let mut written = 0;
+    for chunk in chunks {
+        dst.put(chunk)?;
+        written += chunk.len();
+    }
+    debug_assert_eq!(written, len);
+    Ok(())
+}
+
+fn put_raw_dictionary<K, S>(
+    dict: &HashMap<Vec<u8>, BencodeElem, S>,
+    dst: &mut K,
+) -> Result<(), LavaTorrentError>
+where
+    K: Sink + ?Sized,
+    S: BuildHasher,
+{
+    dst.put(&[DICTIONARY_PREFIX])?;
+    for (key, val) in sorted_raw_entries(dict) {
+        put_bytes(key, dst)?;
+        val.put_into(dst)?;
+    }
+    dst.put(&[DICTIONARY_POSTFIX])?;
+    Ok(())
+}
+
+// Return the entries of `dict` in encoding order.
+fn sorted_raw_entries<S>(dict: &HashMap<Vec<u8>, BencodeElem, S>) -> Vec<(&[u8], &BencodeElem)>
+where
+    S: BuildHasher,
+{
+    let mut sorted = dict
+        .iter()
+        .map(|(key, val)| (key.as_slice(), val))
+        .collect::<Vec<(&[u8], &BencodeElem)>>();
+    // "Keys must be strings and appear in sorted order
+    // (sorted as raw strings, not alphanumerics)."
+    sorted.sort_by_key(|&(key, _)| key);
+    sorted
+}
+
+// A step of `BencodeElem::put_into()`.
+enum PutStep<'a> {
+    Elem(&'a BencodeElem),
+    Key(&'a [u8]),
+    Postfix(u8),
+}
 
 /// Encode `string` and write the result to `dst`.
+#[cfg(feature = "std")]
 pub fn write_string<S, W>(string: S, dst: &mut W) -> Result<(), LavaTorrentError>
 where
     S: AsRef<str>,
     W: Write,
 {
-    let string = string.as_ref();
-    dst.write_all(&string.len().to_string().into_bytes())?;
-    dst.write_all(&[STRING_DELIMITER])?;
-    dst.write_all(string.as_bytes())?;
-    Ok(())
+    put_string(string, dst)
 }
 
 /// Encode `bytes` and write the result to `dst`.
+#[cfg(feature = "std")]
 pub fn write_bytes<B, W>(bytes: B, dst: &mut W) -> Result<(), LavaTorrentError>
 where
     B: AsRef<[u8]>,
     W: Write,
 {
-    let bytes = bytes.as_ref();
-    dst.write_all(&bytes.len().to_string().into_bytes())?;
-    dst.write_all(&[STRING_DELIMITER])?;
-    dst.write_all(bytes)?;
-    Ok(())
+    put_bytes(bytes, dst)
 }
 
 /// Encode `int` and write the result to `dst`.
+#[cfg(feature = "std")]
 pub fn write_integer<W>(int: i64, dst: &mut W) -> Result<(), LavaTorrentError>
 where
     W: Write,
 {
-    dst.write_all(&[INTEGER_PREFIX])?;
-    dst.write_all(int.to_string().as_bytes())?;
-    dst.write_all(&[INTEGER_POSTFIX])?;
-    Ok(())
+    put_integer(int, dst)
 }
 
 /// Encode `list` and write the result to `dst`.
+#[cfg(feature = "std")]
 pub fn write_list<L, W>(list: L, dst: &mut W) -> Result<(), LavaTorrentError>
 where
     L: AsRef<[BencodeElem]>,
     W: Write,
 {
-    let list = list.as_ref();
-    dst.write_all(&[LIST_PREFIX])?;
-    for item in list {
-        item.write_into(dst)?;
-    }
-    dst.write_all(&[LIST_POSTFIX])?;
-    Ok(())
+    put_list(list, dst)
 }
 
 /// Encode `dict` and write the result to `dst`.
+#[cfg(feature = "std")]
 pub fn write_dictionary<W, S>(
     dict: &HashMap<String, BencodeElem, S>,
     dst: &mut W,
@@ -68,21 +515,11 @@ where
     W: Write,
     S: BuildHasher,
 {
-    // "Keys must be strings and appear in sorted order
-    // (sorted as raw strings, not alphanumerics)."
-    let mut sorted = dict.iter().collect::<Vec<(&String, &BencodeElem)>>();
-    sorted.sort_by_key(|&(key, _)| key.as_bytes());
-
-    dst.write_all(&[DICTIONARY_PREFIX])?;
-    for (key, val) in sorted {
-        write_string(key, dst)?;
-        val.write_into(dst)?;
-    }
-    dst.write_all(&[DICTIONARY_POSTFIX])?;
-    Ok(())
+    put_dictionary(dict, dst)
 }
 
 /// Encode `dict` and write the result to `dst`.
+#[cfg(feature = "std")]
 pub fn write_raw_dictionary<W, S>(
     dict: &HashMap<Vec<u8>, BencodeElem, S>,
     dst: &mut W,
@@ -91,18 +528,7 @@ where
     W: Write,
     S: BuildHasher,
 {
-    // "Keys must be strings and appear in sorted order
-    // (sorted as raw strings, not alphanumerics)."
-    let mut sorted = dict.iter().collect::<Vec<(&Vec<u8>, &BencodeElem)>>();
-    sorted.sort_by_key(|&(key, _)| key);
-
-    dst.write_all(&[DICTIONARY_PREFIX])?;
-    for (key, val) in sorted {
-        write_bytes(key, dst)?;
-        val.write_into(dst)?;
-    }
-    dst.write_all(&[DICTIONARY_POSTFIX])?;
-    Ok(())
+    put_raw_dictionary(dict, dst)
 }
 
 /// Encode `string` and return the result in a `Vec`.
@@ -112,7 +538,7 @@ where
 {
     let string = string.as_ref();
     let mut encoded = Vec::with_capacity(string.len() + 2);
-    write_string(string, &mut encoded).expect("Write to vec failed!");
+    put_string(string, &mut encoded).expect("Write to vec failed!");
     encoded
 }
 
@@ -123,14 +549,14 @@ where
 {
     let bytes = bytes.as_ref();
     let mut encoded = Vec::with_capacity(bytes.len() + 2);
-    write_bytes(bytes, &mut encoded).expect("Write to vec failed!");
+    put_bytes(bytes, &mut encoded).expect("Write to vec failed!");
     encoded
 }
 
 /// Encode `int` and return the result in a `Vec`.
 pub fn encode_integer(int: i64) -> Vec<u8> {
     let mut encoded = Vec::new();
-    write_integer(int, &mut encoded).expect("Write to vec failed!");
+    put_integer(int, &mut encoded).expect("Write to vec failed!");
     encoded
 }
 
@@ -140,7 +566,7 @@ where
     L: AsRef<[BencodeElem]>,
 {
     let mut encoded = Vec::new();
-    write_list(list, &mut encoded).expect("Write to vec failed!");
+    put_list(list, &mut encoded).expect("Write to vec failed!");
     encoded
 }
 
@@ -150,7 +576,7 @@ where
     S: BuildHasher,
 {
     let mut encoded = Vec::new();
-    write_dictionary(dict, &mut encoded).expect("Write to vec failed!");
+    put_dictionary(dict, &mut encoded).expect("Write to vec failed!");
     encoded
 }
 
@@ -160,24 +586,228 @@ where
     S: BuildHasher,
 {
     let mut encoded = Vec::new();
-    write_raw_dictionary(dict, &mut encoded).expect("Write to vec failed!");
+    put_raw_dictionary(dict, &mut encoded).expect("Write to vec failed!");
     encoded
 }
 
+/// Encode `elem`, copying bytes from `original` verbatim
+/// wherever `elem` is unchanged from what `original` decodes to.
+///
+/// `original` must contain exactly 1 element, typically the one `elem`
+/// was parsed from before being edited. Non-canonical encodings accepted by
+/// [`BencodeElem::from_bytes()`] (e.g. `i+5e`, or duplicate dictionary keys)
+/// thus survive in untouched parts of `original`, while edited parts are
+/// encoded as usual.
+///
+/// Dictionary entries are matched by key and list items by index, so
+/// inserting into a list re-encodes every item after the insertion point.
+pub fn reencode_preserving(
+    original: &[u8],
+    elem: &BencodeElem,
+) -> Result<Vec<u8>, LavaTorrentError> {
+    let spanned = BencodeElem::parse_spanned(original)?;
+    let mut encoded = Vec::with_capacity(original.len());
+    put_preserving(original, &spanned, elem, &mut encoded);
+    Ok(encoded)
+}
+
+#[cfg(feature = "std")]
+// Apply `edits` to the entries of the dictionary `original` (`None`
+// removes an entry), copying everything else verbatim. Unlike with
+// `reencode_preserving()`, the entries of `original` don't have to be
+// sorted and stay in their original order. Edited values are re-encoded
+// with `reencode_preserving()`. New entries are placed right after the
+// greatest existing key that sorts before them (or first if there is none),
+// which is where sorted order would put them if `original` is sorted.
+// If a key occurs more than once, all occurrences are edited.
+pub(crate) fn splice_dictionary(
+    original: &[u8],
+    edits: &[(&str, Option<BencodeElem>)],
+) -> Result<Vec<u8>, LavaTorrentError> {
+    let entries = match BencodeElem::parse_spanned_unsorted(original)?.kind {
+        SpannedKind::Dictionary(entries) => entries,
+        _ => panic!("parse_spanned_unsorted() did not return a dictionary."),
+    };
+    // the last edit of a key wins
+    let edits = edits
+        .iter()
+        .map(|(key, val)| (key.as_bytes(), val.as_ref()))
+        .collect::<HashMap<&[u8], Option<&BencodeElem>>>();
+
+    // new entries to put after each existing one (the first list goes
+    // before all of them), in encoding order
+    let mut insertions = vec![Vec::new(); entries.len() + 1];
+    for (&key, val) in edits.iter() {
+        if let Some(val) = val {
+            if entries.iter().all(|(k, _)| k.as_slice() != key) {
+                let position = entries
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (k, _))| k.as_slice() < key)
+                    .max_by_key(|(_, (k, _))| k)
+                    .map_or(0, |(i, _)| i + 1);
+                insertions[position].push((key, *val));
+            }
+        }
+    }
+    for inserted in insertions.iter_mut() {
+        inserted.sort_by_key(|&(key, _)| key);
+    }
+
+    let mut dst = Vec::with_capacity(original.len());
+    let put_inserted = |inserted: &[(&[u8], &BencodeElem)], dst: &mut Vec<u8>| {
+        for (key, val) in inserted {
+            put_bytes(key, dst).expect("Write to vec failed!");
+            val.put_into(dst).expect("Write to vec failed!");
+        }
+    };
+    dst.push(DICTIONARY_PREFIX);
+    put_inserted(&insertions[0], &mut dst);
+    // where the encoded key of the next entry starts
+    let mut key_start = 1;
+    for ((key, spanned), inserted) in entries.iter().zip(insertions.iter().skip(1)) {
+        match edits.get(key.as_slice()) {
+            None => dst.extend_from_slice(&original[key_start..spanned.span.end]),
+            Some(None) => (),
+            Some(Some(val)) => {
+                dst.extend_from_slice(&original[key_start..spanned.span.start]);
+                put_preserving(original, spanned, val, &mut dst);
+            }
+        }
+        put_inserted(inserted, &mut dst);
+        key_start = spanned.span.end;
+    }
+    dst.push(DICTIONARY_POSTFIX);
+    Ok(dst)
+}
+
+// Encode `elem` into `dst`, reusing `original` where possible.
+// Return `true` if `elem` is unchanged from `spanned`.
+fn put_preserving(
+    original: &[u8],
+    spanned: &Spanned,
+    elem: &BencodeElem,
+    dst: &mut Vec<u8>,
+) -> bool {
+    let start = dst.len();
+    let unchanged = match (&spanned.kind, elem) {
+        (SpannedKind::Leaf(leaf), elem) if leaf == elem => true,
+        (SpannedKind::List(items), BencodeElem::List(list)) => {
+            let mut unchanged = items.len() == list.len();
+            dst.push(LIST_PREFIX);
+            for (i, item) in list.iter().enumerate() {
+                match items.get(i) {
+                    Some(spanned) => unchanged &= put_preserving(original, spanned, item, dst),
+                    None => item.put_into(dst).expect("Write to vec failed!"),
+                }
+            }
+            dst.push(LIST_POSTFIX);
+            unchanged
+        }
+        (SpannedKind::Dictionary(entries), BencodeElem::Dictionary(dict)) => {
+            put_dictionary_preserving(original, entries, sorted_entries(dict), dst)
+        }
+        (SpannedKind::Dictionary(entries), BencodeElem::RawDictionary(dict)) => {
+            let mut sorted = dict
+                .iter()
+                .map(|(key, val)| (key.as_slice(), val))
+                .collect::<Vec<(&[u8], &BencodeElem)>>();
+            sorted.sort_by_key(|&(key, _)| key);
+            put_dictionary_preserving(original, entries, sorted, dst)
+        }
+        _ => {
+            elem.put_into(dst).expect("Write to vec failed!");
+            false
+        }
+    };
+
+    if unchanged {
+        dst.truncate(start);
+        dst.extend_from_slice(&original[spanned.span.clone()]);
+    }
+    unchanged
+}
+
+// `sorted` must be in encoding order.
+fn put_dictionary_preserving(
+    original: &[u8],
+    entries: &[(Vec<u8>, Spanned)],
+    sorted: Vec<(&[u8], &BencodeElem)>,
+    dst: &mut Vec<u8>,
+) -> bool {
+    // as with `from_bytes()`, the last of duplicate keys wins
+    let found = entries
+        .iter()
+        .map(|(key, spanned)| (key.as_slice(), spanned))
+        .collect::<HashMap<&[u8], &Spanned>>();
+    let mut unchanged = sorted.len() == found.len();
+    dst.push(DICTIONARY_PREFIX);
+    for (key, val) in sorted {
+        put_bytes(key, dst).expect("Write to vec failed!");
+        match found.get(key) {
+            Some(spanned) => unchanged &= put_preserving(original, spanned, val, dst),
+            None => {
+                val.put_into(dst).expect("Write to vec failed!");
+                unchanged = false;
+            }
+        }
+    }
+    dst.push(DICTIONARY_POSTFIX);
+    unchanged
+}
+
 impl BencodeElem {
+    // Containers are not encoded recursively, but by pushing their
+    // contents onto an explicit stack (in reverse, so that they are
+    // popped in order), followed by their postfix. This way, encoding
+    // deeply nested elements doesn't overflow the call stack.
+    fn put_into<K>(&self, dst: &mut K) -> Result<(), LavaTorrentError>
+    where
+        K: Sink + ?Sized,
+    {
+        let mut stack = vec![PutStep::Elem(self)];
+        while let Some(step) = stack.pop() {
+            match step {
+                PutStep::Elem(elem) => match *elem {
+                    BencodeElem::String(ref string) => put_string(string, dst)?,
+                    BencodeElem::Bytes(ref bytes) => put_bytes(bytes, dst)?,
+                    BencodeElem::Integer(int) => put_integer(int, dst)?,
+                    BencodeElem::List(ref list) => {
+                        dst.put(&[LIST_PREFIX])?;
+                        stack.push(PutStep::Postfix(LIST_POSTFIX));
+                        stack.extend(list.iter().rev().map(PutStep::Elem));
+                    }
+                    BencodeElem::Dictionary(ref dict) => {
+                        dst.put(&[DICTIONARY_PREFIX])?;
+                        stack.push(PutStep::Postfix(DICTIONARY_POSTFIX));
+                        for (key, val) in sorted_entries(dict).into_iter().rev() {
+                            stack.push(PutStep::Elem(val));
+                            stack.push(PutStep::Key(key));
+                        }
+                    }
+                    BencodeElem::RawDictionary(ref dict) => {
+                        dst.put(&[DICTIONARY_PREFIX])?;
+                        stack.push(PutStep::Postfix(DICTIONARY_POSTFIX));
+                        for (key, val) in sorted_raw_entries(dict).into_iter().rev() {
+                            stack.push(PutStep::Elem(val));
+                            stack.push(PutStep::Key(key));
+                        }
+                    }
+                },
+                PutStep::Key(key) => put_bytes(key, dst)?,
+                PutStep::Postfix(postfix) => dst.put(&[postfix])?,
+            }
+        }
+        Ok(())
+    }
+
     /// Encode `self` and write the result to `dst`.
+    #[cfg(feature = "std")]
     pub fn write_into<W>(&self, dst: &mut W) -> Result<(), LavaTorrentError>
     where
         W: Write,
     {
-        match *self {
-            BencodeElem::String(ref string) => write_string(string, dst),
-            BencodeElem::Bytes(ref bytes) => write_bytes(bytes, dst),
-            BencodeElem::Integer(int) => write_integer(int, dst),
-            BencodeElem::List(ref list) => write_list(list, dst),
-            BencodeElem::Dictionary(ref dict) => write_dictionary(dict, dst),
-            BencodeElem::RawDictionary(ref dict) => write_raw_dictionary(dict, dst),
-        }
+        self.put_into(dst)
     }
 
     /// Encode `self` and write the result to `path`.
@@ -187,17 +817,55 @@ impl BencodeElem {
     /// "This function will create a file if it does
     /// not exist, and will truncate it if it does."
     ///
-    /// Note: it is the client's responsibility to ensure
-    /// that all directories in `path` actually exist (e.g.
-    /// by calling [`create_dir_all`](https://doc.rust-lang.org/std/fs/fn.create_dir_all.html)).
+    /// Note: the directories in `path` must already exist, unless
+    /// [`WriteFileOptions::create_parent_dirs`] is set (see
+    /// [`write_into_file_with()`](Self::write_into_file_with)).
+    ///
+    /// Errors name `path` in their message.
+    #[cfg(feature = "std")]
     pub fn write_into_file<P>(&self, path: P) -> Result<(), LavaTorrentError>
     where
         P: AsRef<Path>,
     {
-        let file = File::create(&path)?;
-        self.write_into(&mut BufWriter::new(&file))?;
-        file.sync_all()?;
-        Ok(())
+        self.write_into_file_with(path, WriteFileOptions::default())
+    }
+
+    /// Like [`write_into_file()`](Self::write_into_file), but
+    /// allows controlling how the file is synced and whether
+    /// missing parent directories are created via `options`.
+    #[cfg(feature = "std")]
+    pub fn write_into_file_with<P>(
+        &self,
+        path: P,
+        options: WriteFileOptions,
+    ) -> Result<(), LavaTorrentError>
+    where
+        P: AsRef<Path>,
+    {
+        write_file_with(path, options, |dst| self.write_into(dst))
+    }
+
+    /// Calculate the SHA-1 digest of `self.encode()`.
+    ///
+    /// The encoded bytes are fed to the hasher as they are produced,
+    /// so they are never held in memory as a whole.
+    #[cfg(feature = "std")]
+    pub fn sha1(&self) -> [u8; 20] {
+        self.sha1_digest().into()
+    }
+
+    /// Same as [`sha1()`](Self::sha1), but return the digest as a hex string.
+    #[cfg(feature = "std")]
+    pub fn sha1_hex(&self) -> String {
+        crate::hex::encode(&self.sha1_digest())
+    }
+
+    #[cfg(feature = "std")]
+    fn sha1_digest(&self) -> sha1::digest::Output<Sha1> {
+        let mut hasher = Sha1::new();
+        self.write_into(&mut hasher)
+            .expect("Write to hasher failed!");
+        hasher.finalize()
     }
 
     /// Encode `self` and return the result in a `Vec`.
@@ -218,10 +886,46 @@ mod bencode_elem_write_tests {
     // @note: `write_into_file()` is not tested as it is best
     // left to integration tests (in `tests/`).
     use super::*;
+    #[cfg(feature = "std")]
     use std::collections::hash_map::RandomState;
+    #[cfg(feature = "std")]
+    use std::io;
     use std::iter::FromIterator;
 
+    // Rejects every write. Behind a `BufWriter`, the error
+    // only surfaces once the buffer is flushed.
+    #[cfg(feature = "std")]
+    struct FailingWriter;
+
+    #[cfg(feature = "std")]
+    impl Write for FailingWriter {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::other("write failed"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
     #[test]
+    #[cfg(feature = "std")]
+    fn write_buffered_ok() {
+        let result = write_buffered(Vec::new(), |dst| bencode_elem!("spam").write_into(dst));
+        assert_eq!(result.unwrap(), "4:spam".as_bytes().to_vec());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn write_buffered_flush_error() {
+        match write_buffered(FailingWriter, |dst| bencode_elem!("spam").write_into(dst)) {
+            Err(LavaTorrentError::Io(e)) => assert_eq!(e.to_string(), "write failed"),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
     fn write_string_ok() {
         let mut vec = Vec::new();
         write_string("spam", &mut vec).unwrap();
@@ -229,6 +933,7 @@ mod bencode_elem_write_tests {
     }
 
     #[test]
+    #[cfg(feature = "std")]
     fn write_bytes_ok() {
         let mut vec = Vec::new();
         write_bytes([0x01, 0x02, 0x03, 0x04], &mut vec).unwrap();
@@ -236,6 +941,7 @@ mod bencode_elem_write_tests {
     }
 
     #[test]
+    #[cfg(feature = "std")]
     fn write_integer_ok() {
         let mut vec = Vec::new();
         write_integer(42, &mut vec).unwrap();
@@ -243,9 +949,10 @@ mod bencode_elem_write_tests {
     }
 
     #[test]
+    #[cfg(feature = "std")]
     fn write_list_ok() {
         let mut vec = Vec::new();
-        write_list(&vec![bencode_elem!(42), bencode_elem!("spam")], &mut vec).unwrap();
+        write_list(vec![bencode_elem!(42), bencode_elem!("spam")], &mut vec).unwrap();
         assert_eq!(
             vec,
             vec![b'l', b'i', b'4', b'2', b'e', b'4', b':', b's', b'p', b'a', b'm', b'e']
@@ -253,16 +960,14 @@ mod bencode_elem_write_tests {
     }
 
     #[test]
+    #[cfg(feature = "std")]
     fn write_dictionary_ok() {
         let mut vec = Vec::new();
         write_dictionary::<_, RandomState>(
-            &HashMap::from_iter(
-                vec![
-                    ("spam".to_owned(), bencode_elem!(42)),
-                    ("cow".to_owned(), bencode_elem!("moo")),
-                ]
-                .into_iter(),
-            ),
+            &HashMap::from_iter(vec![
+                ("spam".to_owned(), bencode_elem!(42)),
+                ("cow".to_owned(), bencode_elem!("moo")),
+            ]),
             &mut vec,
         )
         .unwrap();
@@ -293,6 +998,23 @@ mod bencode_elem_write_tests {
         assert_eq!(encode_integer(42), vec![b'i', b'4', b'2', b'e'])
     }
 
+    #[test]
+    fn encode_integer_extremes() {
+        for int in [0, -1, 9, 10, -10, i64::MAX, i64::MIN, i64::MIN + 1] {
+            assert_eq!(encode_integer(int), format!("i{}e", int).into_bytes());
+        }
+    }
+
+    #[test]
+    fn encode_bytes_multi_digit_length() {
+        for len in [9, 10, 99, 100, 12345] {
+            let encoded = encode_bytes(vec![0; len]);
+            let prefix = format!("{}:", len);
+            assert_eq!(&encoded[..prefix.len()], prefix.as_bytes());
+            assert_eq!(encoded.len(), prefix.len() + len);
+        }
+    }
+
     #[test]
     fn encode_list_ok() {
         assert_eq!(
@@ -304,7 +1026,7 @@ mod bencode_elem_write_tests {
     #[test]
     fn encode_dictionary_ok() {
         assert_eq!(
-            encode_dictionary::<RandomState>(&HashMap::from_iter(
+            encode_dictionary(&HashMap::<_, _>::from_iter(
                 vec![
                     ("spam".to_owned(), bencode_elem!(42)),
                     ("cow".to_owned(), bencode_elem!("moo")),
@@ -319,6 +1041,7 @@ mod bencode_elem_write_tests {
     }
 
     #[test]
+    #[cfg(feature = "std")]
     fn bencode_elem_write_string_ok() {
         let mut vec = Vec::new();
         bencode_elem!("spam").write_into(&mut vec).unwrap();
@@ -326,6 +1049,7 @@ mod bencode_elem_write_tests {
     }
 
     #[test]
+    #[cfg(feature = "std")]
     fn bencode_elem_write_bytes_ok() {
         let mut vec = Vec::new();
         bencode_elem!((0x01, 0x02, 0x03, 0x04))
@@ -335,6 +1059,7 @@ mod bencode_elem_write_tests {
     }
 
     #[test]
+    #[cfg(feature = "std")]
     fn bencode_elem_write_integer_ok() {
         let mut vec = Vec::new();
         bencode_elem!(42).write_into(&mut vec).unwrap();
@@ -342,6 +1067,7 @@ mod bencode_elem_write_tests {
     }
 
     #[test]
+    #[cfg(feature = "std")]
     fn bencode_elem_write_list_ok() {
         let mut vec = Vec::new();
         bencode_elem!([42, "spam"]).write_into(&mut vec).unwrap();
@@ -352,6 +1078,7 @@ mod bencode_elem_write_tests {
     }
 
     #[test]
+    #[cfg(feature = "std")]
     fn bencode_elem_write_dictionary_ok() {
         let mut vec = Vec::new();
         bencode_elem!({ ("spam", 42), ("cow", "moo") })
@@ -402,4 +1129,248 @@ mod bencode_elem_write_tests {
             ],
         )
     }
+
+    #[test]
+    fn bencode_elem_encode_dictionary_raw_keys() {
+        // binary keys are interleaved with the others, in raw byte order
+        let original = b"d3:cow3:moo2:d\xff4:eggs4:spam3:ham2:\xff\xeei42ee";
+        let elem = BencodeElem::from_bytes(original).unwrap().remove(0);
+
+        assert!(matches!(elem, BencodeElem::Dictionary(_)));
+        assert_eq!(elem.encode(), original);
+    }
+
+    #[test]
+    fn bencode_elem_encode_dictionary_raw_keys_not_raw_dictionary() {
+        // `RAW_KEYS` is only special if it maps to a RawDictionary
+        let elem = bencode_elem!({ ("__raw_keys__", "moo") });
+        assert_eq!(elem.encode(), b"d12:__raw_keys__3:mooe");
+    }
+
+    // Dropping a deeply nested element recursively would
+    // overflow the stack, so take it apart level by level.
+    fn drop_nested(mut elem: BencodeElem) {
+        loop {
+            elem = match elem {
+                BencodeElem::List(mut list) => match list.pop() {
+                    Some(child) => child,
+                    None => break,
+                },
+                BencodeElem::Dictionary(dict) => match dict.into_iter().next() {
+                    Some((_, child)) => child,
+                    None => break,
+                },
+                BencodeElem::RawDictionary(dict) => match dict.into_iter().next() {
+                    Some((_, child)) => child,
+                    None => break,
+                },
+                _ => break,
+            }
+        }
+    }
+
+    #[test]
+    fn bencode_elem_encode_deeply_nested() {
+        const DEPTH: usize = 100_000;
+
+        let mut elem = BencodeElem::Integer(0);
+        let mut prefixes = Vec::new();
+        let mut postfixes = Vec::new();
+        for i in 0..DEPTH {
+            let (nested, prefix): (_, &[u8]) = match i % 3 {
+                0 => (BencodeElem::List(vec![elem]), b"l"),
+                1 => (
+                    BencodeElem::Dictionary(HashMap::from_iter(vec![("k".to_owned(), elem)])),
+                    b"d1:k",
+                ),
+                _ => (
+                    BencodeElem::RawDictionary(HashMap::from_iter(vec![(vec![0xff], elem)])),
+                    b"d1:\xff",
+                ),
+            };
+            elem = nested;
+            prefixes.extend(prefix.iter().rev());
+            postfixes.push(b'e');
+        }
+        prefixes.reverse();
+        let expected = [&prefixes[..], b"i0e", &postfixes[..]].concat();
+
+        assert_eq!(elem.encode(), expected);
+        #[cfg(feature = "std")]
+        {
+            let mut written = Vec::new();
+            elem.write_into(&mut written).unwrap();
+            assert_eq!(written, expected);
+            assert_eq!(elem.sha1(), <[u8; 20]>::from(Sha1::digest(&expected)));
+        }
+        drop_nested(elem);
+    }
+
+    #[test]
+    fn reencode_preserving_raw_keys() {
+        let original = b"d3:cowi+5e2:\xff\xeei+42ee";
+        let mut elem = BencodeElem::from_bytes(original).unwrap().remove(0);
+
+        assert_eq!(reencode_preserving(original, &elem).unwrap(), original);
+        if let BencodeElem::Dictionary(ref mut dict) = elem {
+            dict.insert("cow".to_owned(), bencode_elem!(6));
+        }
+        assert_eq!(
+            reencode_preserving(original, &elem).unwrap(),
+            b"d3:cowi6e2:\xff\xeei+42ee"
+        );
+    }
+
+    #[test]
+    fn reencode_preserving_unchanged() {
+        // `i+5e`, `+3:moo`, and the duplicate key are all non-canonical
+        let original = b"d3:cowi+5e3:cow+3:moo4:spamli-1eee";
+        let elem = BencodeElem::from_bytes(original).unwrap().remove(0);
+
+        assert_eq!(reencode_preserving(original, &elem).unwrap(), original);
+    }
+
+    #[test]
+    fn reencode_preserving_edit_dictionary() {
+        let original = b"d3:cowi+5e4:spami+1ee";
+        let mut elem = BencodeElem::from_bytes(original).unwrap().remove(0);
+        match elem {
+            BencodeElem::Dictionary(ref mut dict) => {
+                dict.insert("spam".to_owned(), bencode_elem!("eggs"));
+                dict.insert("moo".to_owned(), bencode_elem!(42));
+            }
+            _ => panic!(),
+        }
+
+        assert_eq!(
+            reencode_preserving(original, &elem).unwrap(),
+            b"d3:cowi+5e3:mooi42e4:spam4:eggse"
+        );
+    }
+
+    #[test]
+    fn reencode_preserving_edit_list() {
+        let original = b"li+1ei+2ei+3ee";
+
+        let elem = bencode_elem!([1, 2]);
+        assert_eq!(reencode_preserving(original, &elem).unwrap(), b"li+1ei+2ee");
+
+        let elem = bencode_elem!([1, 2, 3, 4]);
+        assert_eq!(
+            reencode_preserving(original, &elem).unwrap(),
+            b"li+1ei+2ei+3ei4ee"
+        );
+
+        let elem = bencode_elem!([0, 1, 2, 3]);
+        assert_eq!(
+            reencode_preserving(original, &elem).unwrap(),
+            b"li0ei1ei2ei3ee"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn splice_dictionary_unsorted() {
+        let original = b"d4:spami+1e3:cowi2e3:fooi3ee";
+
+        assert_eq!(splice_dictionary(original, &[]).unwrap(), original);
+        assert_eq!(
+            splice_dictionary(original, &[("cow", Some(bencode_elem!("moo")))]).unwrap(),
+            b"d4:spami+1e3:cow3:moo3:fooi3ee"
+        );
+        assert_eq!(
+            splice_dictionary(original, &[("spam", None), ("missing", None)]).unwrap(),
+            b"d3:cowi2e3:fooi3ee"
+        );
+        // the last edit of a key wins
+        assert_eq!(
+            splice_dictionary(original, &[("foo", None), ("foo", Some(bencode_elem!(4)))]).unwrap(),
+            b"d4:spami+1e3:cowi2e3:fooi4ee"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn splice_dictionary_insert() {
+        let original = b"d4:spami1e3:cowi2e3:fooi3ee";
+        // "dog" goes right after "cow", "bee" before everything,
+        // and "zoo" after "spam" (the greatest key)
+        assert_eq!(
+            splice_dictionary(
+                original,
+                &[
+                    ("zoo", Some(bencode_elem!(6))),
+                    ("dog", Some(bencode_elem!(4))),
+                    ("bee", Some(bencode_elem!(5))),
+                    ("egg", Some(bencode_elem!(7))),
+                ]
+            )
+            .unwrap(),
+            b"d3:beei5e4:spami1e3:zooi6e3:cowi2e3:dogi4e3:eggi7e3:fooi3ee"
+        );
+
+        // same as sorted order if `original` is sorted
+        let original = b"d3:cowi2e4:spami1ee";
+        assert_eq!(
+            splice_dictionary(original, &[("foo", Some(bencode_elem!(3)))]).unwrap(),
+            b"d3:cowi2e3:fooi3e4:spami1ee"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn splice_dictionary_edit_nested() {
+        // untouched parts of an edited value are preserved
+        let original = b"d4:infod3:cowi+1e3:fooi1ee3:bari1ee";
+        assert_eq!(
+            splice_dictionary(
+                original,
+                &[("info", Some(bencode_elem!({ ("cow", 1), ("foo", 2) })))]
+            )
+            .unwrap(),
+            b"d4:infod3:cowi+1e3:fooi2ee3:bari1ee"
+        );
+        assert!(splice_dictionary(b"li1ee", &[]).is_err());
+    }
+
+    #[test]
+    fn reencode_preserving_kind_changed() {
+        let original = b"d3:cowli+1eee";
+        let elem = bencode_elem!({ ("cow", { ("moo", 1) }) });
+
+        assert_eq!(
+            reencode_preserving(original, &elem).unwrap(),
+            b"d3:cowd3:mooi1eee"
+        );
+    }
+
+    #[test]
+    fn reencode_preserving_removed_duplicate() {
+        // the duplicate key has to go once the dictionary is touched
+        let original = b"d3:cowi+1e3:cowi+2e3:mooi3ee";
+        let elem = bencode_elem!({ ("cow", 2) });
+
+        assert_eq!(
+            reencode_preserving(original, &elem).unwrap(),
+            b"d3:cowi+2ee"
+        );
+    }
+
+    #[test]
+    fn reencode_preserving_multiple_elem() {
+        match reencode_preserving(b"i1ei2e", &bencode_elem!(1)) {
+            Err(LavaTorrentError::MalformedBencode(m)) => {
+                assert_eq!(m, "Expected 1 and only 1 top-level element, 2 found.");
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn sha1_ok() {
+        let elem = bencode_elem!({ ("spam", ["a", "b"]), ("cow", 42_i64) });
+        assert_eq!(elem.sha1(), crate::hash::sha1(&elem.encode()));
+        assert_eq!(elem.sha1_hex(), crate::hash::sha1_hex(&elem.encode()));
+    }
 }
@@ -0,0 +1,341 @@
+//! Shims for code written against the previous error API.
+//!
+//! The previous API had an `Error` type with a separate `ErrorKind`, and
+//! errors were told apart by comparing kinds. [`LavaTorrentError`] carries
+//! its kind as the variant now, and [`LavaTorrentError::kind()`] returns a
+//! [`LavaTorrentErrorKind`]. Everything in this module is deprecated and
+//! will be removed in a future release. It only exists so that existing code
+//! keeps compiling (with warnings) while it is migrated.
+//!
+//! | Previous API                    | Current API                                   |
+//! |---------------------------------|-----------------------------------------------|
+//! | `Error`                         | [`LavaTorrentError`]                          |
+//! | `ErrorKind::IOError`            | [`LavaTorrentErrorKind::Io`]                  |
+//! | `ErrorKind::MalformedTorrent`   | [`LavaTorrentErrorKind::MalformedTorrent`]    |
+//! | `Error::new(kind, message)`     | the variant, e.g. `LavaTorrentError::MalformedTorrent(message)` |
+//!
+//! All other kinds kept their names.
+//!
+//! # Migration
+//!
+//! Comparing kinds works as before:
+//!
+//! ```
+//! # #![allow(deprecated)]
+//! use lava_torrent::compat::{Error, ErrorKind};
+//! use lava_torrent::torrent::v1::Torrent;
+//!
+//! let error: Error = Torrent::read_from_bytes(b"d4:spam4:eggse").unwrap_err();
+//! assert!(error.kind() == ErrorKind::MalformedTorrent);
+//! ```
+//!
+//! It becomes:
+//!
+//! ```
+//! use lava_torrent::torrent::v1::Torrent;
+//! use lava_torrent::{LavaTorrentError, LavaTorrentErrorKind};
+//!
+//! let error: LavaTorrentError = Torrent::read_from_bytes(b"d4:spam4:eggse").unwrap_err();
+//! assert_eq!(error.kind(), LavaTorrentErrorKind::MalformedTorrent);
+//! // or, to get the message too
+//! assert!(matches!(error, LavaTorrentError::MalformedTorrent(_)));
+//! ```
+//!
+//! Matching on kinds needs [`kind()`] instead of the method:
+//!
+//! ```
+//! # #![allow(deprecated)]
+//! use lava_torrent::compat::{self, ErrorKind};
+//! use lava_torrent::hex;
+//!
+//! let error = hex::decode("abc").unwrap_err();
+//! match compat::kind(&error) {
+//!     ErrorKind::InvalidArgument => (),
+//!     _ => panic!(),
+//! }
+//! assert!(compat::matches_invalid_argument(&error));
+//! ```
+//!
+//! It becomes:
+//!
+//! ```
+//! use lava_torrent::hex;
+//! use lava_torrent::LavaTorrentErrorKind;
+//!
+//! let error = hex::decode("abc").unwrap_err();
+//! match error.kind() {
+//!     LavaTorrentErrorKind::InvalidArgument => (),
+//!     _ => panic!(),
+//! }
+//! ```
+//!
+//! And constructing errors:
+//!
+//! ```
+//! # #![allow(deprecated)]
+//! use lava_torrent::compat::{Error, ErrorKind};
+//! use lava_torrent::LavaTorrentError;
+//!
+//! let error = Error::new(ErrorKind::MalformedTorrent, "bad torrent");
+//! assert!(matches!(error, LavaTorrentError::MalformedTorrent(ref m) if m == "bad torrent"));
+//! ```
+//!
+//! It becomes:
+//!
+//! ```
+//! use lava_torrent::LavaTorrentError;
+//! use std::borrow::Cow;
+//!
+//! let error = LavaTorrentError::MalformedTorrent(Cow::Borrowed("bad torrent"));
+//! ```
+#![allow(deprecated)]
+
+use crate::{LavaTorrentError, LavaTorrentErrorKind};
+use alloc::borrow::Cow;
+
+/// The previous error type.
+#[deprecated(note = "use `LavaTorrentError` instead")]
+pub type Error = LavaTorrentError;
+
+/// The previous result type.
+#[deprecated(note = "use `Result<T, LavaTorrentError>` instead")]
+pub type Result<T> = core::result::Result<T, LavaTorrentError>;
+
+/// The previous kinds of errors, under their previous names.
+///
+/// Convertible from/to [`LavaTorrentErrorKind`], and comparable
+/// with it, so `error.kind() == ErrorKind::X` still compiles.
+#[deprecated(note = "use `LavaTorrentErrorKind` instead")]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ErrorKind {
+    /// Now [`LavaTorrentErrorKind::Io`].
+    #[cfg(feature = "std")]
+    IOError,
+    MalformedBencode,
+    MalformedTorrent,
+    MalformedResponse,
+    TorrentBuilderFailure,
+    InvalidArgument,
+    FailedNumericConv,
+}
+
+impl From<LavaTorrentErrorKind> for ErrorKind {
+    fn from(kind: LavaTorrentErrorKind) -> ErrorKind {
+        match kind {
+            #[cfg(feature = "std")]
+            LavaTorrentErrorKind::Io => ErrorKind::IOError,
+            LavaTorrentErrorKind::MalformedBencode => ErrorKind::MalformedBencode,
+            LavaTorrentErrorKind::MalformedTorrent => ErrorKind::MalformedTorrent,
+            LavaTorrentErrorKind::MalformedResponse => ErrorKind::MalformedResponse,
+            LavaTorrentErrorKind::TorrentBuilderFailure => ErrorKind::TorrentBuilderFailure,
+            LavaTorrentErrorKind::InvalidArgument => ErrorKind::InvalidArgument,
+            LavaTorrentErrorKind::FailedNumericConv => ErrorKind::FailedNumericConv,
+        }
+    }
+}
+
+impl From<ErrorKind> for LavaTorrentErrorKind {
+    fn from(kind: ErrorKind) -> LavaTorrentErrorKind {
+        match kind {
+            #[cfg(feature = "std")]
+            ErrorKind::IOError => LavaTorrentErrorKind::Io,
+            ErrorKind::MalformedBencode => LavaTorrentErrorKind::MalformedBencode,
+            ErrorKind::MalformedTorrent => LavaTorrentErrorKind::MalformedTorrent,
+            ErrorKind::MalformedResponse => LavaTorrentErrorKind::MalformedResponse,
+            ErrorKind::TorrentBuilderFailure => LavaTorrentErrorKind::TorrentBuilderFailure,
+            ErrorKind::InvalidArgument => LavaTorrentErrorKind::InvalidArgument,
+            ErrorKind::FailedNumericConv => LavaTorrentErrorKind::FailedNumericConv,
+        }
+    }
+}
+
+impl PartialEq<ErrorKind> for LavaTorrentErrorKind {
+    fn eq(&self, other: &ErrorKind) -> bool {
+        *self == LavaTorrentErrorKind::from(*other)
+    }
+}
+
+impl PartialEq<LavaTorrentErrorKind> for ErrorKind {
+    fn eq(&self, other: &LavaTorrentErrorKind) -> bool {
+        *other == *self
+    }
+}
+
+impl LavaTorrentError {
+    /// Construct an error of `kind` with `message`, as the previous
+    /// `Error::new()` did.
+    ///
+    /// An `ErrorKind::IOError` becomes an [`io::Error`] of kind
+    /// [`io::ErrorKind::Other`] with `message` as its payload.
+    ///
+    /// [`io::Error`]: std::io::Error
+    /// [`io::ErrorKind::Other`]: std::io::ErrorKind::Other
+    #[deprecated(
+        note = "construct the variant (e.g. `LavaTorrentError::MalformedTorrent`) instead"
+    )]
+    pub fn new<M>(kind: ErrorKind, message: M) -> LavaTorrentError
+    where
+        M: Into<Cow<'static, str>>,
+    {
+        let message = message.into();
+        match kind {
+            #[cfg(feature = "std")]
+            ErrorKind::IOError => LavaTorrentError::from(std::io::Error::other(message)),
+            ErrorKind::MalformedBencode => LavaTorrentError::MalformedBencode(message),
+            ErrorKind::MalformedTorrent => LavaTorrentError::MalformedTorrent(message),
+            ErrorKind::MalformedResponse => LavaTorrentError::MalformedResponse(message),
+            ErrorKind::TorrentBuilderFailure => LavaTorrentError::TorrentBuilderFailure(message),
+            ErrorKind::InvalidArgument => LavaTorrentError::InvalidArgument(message),
+            ErrorKind::FailedNumericConv => LavaTorrentError::FailedNumericConv(message),
+        }
+    }
+}
+
+/// Return the kind of `error` under its previous name, e.g. for
+/// matching on it with `ErrorKind` patterns.
+#[deprecated(note = "use `LavaTorrentError::kind()` instead")]
+pub fn kind(error: &LavaTorrentError) -> ErrorKind {
+    ErrorKind::from(error.kind())
+}
+
+/// Check if `error` is an IO error.
+#[cfg(feature = "std")]
+#[deprecated(note = "use `error.kind() == LavaTorrentErrorKind::Io` instead")]
+pub fn matches_io(error: &LavaTorrentError) -> bool {
+    error.kind() == LavaTorrentErrorKind::Io
+}
+
+/// Check if `error` is caused by malformed bencode.
+#[deprecated(note = "use `error.kind() == LavaTorrentErrorKind::MalformedBencode` instead")]
+pub fn matches_malformed_bencode(error: &LavaTorrentError) -> bool {
+    error.kind() == LavaTorrentErrorKind::MalformedBencode
+}
+
+/// Check if `error` is caused by a malformed torrent.
+#[deprecated(note = "use `error.kind() == LavaTorrentErrorKind::MalformedTorrent` instead")]
+pub fn matches_malformed_torrent(error: &LavaTorrentError) -> bool {
+    error.kind() == LavaTorrentErrorKind::MalformedTorrent
+}
+
+/// Check if `error` is caused by a malformed tracker response.
+#[deprecated(note = "use `error.kind() == LavaTorrentErrorKind::MalformedResponse` instead")]
+pub fn matches_malformed_response(error: &LavaTorrentError) -> bool {
+    error.kind() == LavaTorrentErrorKind::MalformedResponse
+}
+
+/// Check if `error` is a failure to build a torrent.
+#[deprecated(note = "use `error.kind() == LavaTorrentErrorKind::TorrentBuilderFailure` instead")]
+pub fn matches_torrent_builder_failure(error: &LavaTorrentError) -> bool {
+    error.kind() == LavaTorrentErrorKind::TorrentBuilderFailure
+}
+
+/// Check if `error` is caused by an invalid argument.
+#[deprecated(note = "use `error.kind() == LavaTorrentErrorKind::InvalidArgument` instead")]
+pub fn matches_invalid_argument(error: &LavaTorrentError) -> bool {
+    error.kind() == LavaTorrentErrorKind::InvalidArgument
+}
+
+/// Check if `error` is a failed numeric conversion.
+#[deprecated(note = "use `error.kind() == LavaTorrentErrorKind::FailedNumericConv` instead")]
+pub fn matches_failed_numeric_conv(error: &LavaTorrentError) -> bool {
+    error.kind() == LavaTorrentErrorKind::FailedNumericConv
+}
+
+#[cfg(test)]
+mod compat_tests {
+    use super::*;
+
+    const KINDS: [(ErrorKind, LavaTorrentErrorKind); 6] = [
+        (
+            ErrorKind::MalformedBencode,
+            LavaTorrentErrorKind::MalformedBencode,
+        ),
+        (
+            ErrorKind::MalformedTorrent,
+            LavaTorrentErrorKind::MalformedTorrent,
+        ),
+        (
+            ErrorKind::MalformedResponse,
+            LavaTorrentErrorKind::MalformedResponse,
+        ),
+        (
+            ErrorKind::TorrentBuilderFailure,
+            LavaTorrentErrorKind::TorrentBuilderFailure,
+        ),
+        (
+            ErrorKind::InvalidArgument,
+            LavaTorrentErrorKind::InvalidArgument,
+        ),
+        (
+            ErrorKind::FailedNumericConv,
+            LavaTorrentErrorKind::FailedNumericConv,
+        ),
+    ];
+
+    #[test]
+    fn kind_conversions() {
+        for (old, new) in KINDS {
+            assert_eq!(ErrorKind::from(new), old);
+            assert_eq!(LavaTorrentErrorKind::from(old), new);
+            assert!(old == new);
+            assert!(new == old);
+        }
+        assert!(ErrorKind::MalformedTorrent != LavaTorrentErrorKind::MalformedBencode);
+        assert!(LavaTorrentErrorKind::MalformedBencode != ErrorKind::MalformedTorrent);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn kind_conversions_io() {
+        assert_eq!(
+            ErrorKind::from(LavaTorrentErrorKind::Io),
+            ErrorKind::IOError
+        );
+        assert_eq!(
+            LavaTorrentErrorKind::from(ErrorKind::IOError),
+            LavaTorrentErrorKind::Io
+        );
+        assert!(ErrorKind::IOError == LavaTorrentErrorKind::Io);
+    }
+
+    #[test]
+    fn new_ok() {
+        for (old, new) in KINDS {
+            let error = LavaTorrentError::new(old, "message");
+            assert_eq!(error.kind(), new);
+            assert_eq!(kind(&error), old);
+            assert!(alloc::string::ToString::to_string(&error).ends_with(": message"));
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn new_io() {
+        let error = LavaTorrentError::new(ErrorKind::IOError, "message");
+        match error {
+            LavaTorrentError::Io(ref e) => assert_eq!(e.kind(), std::io::ErrorKind::Other),
+            _ => panic!(),
+        }
+        assert_eq!(error.to_string(), "message");
+        assert!(matches_io(&error));
+    }
+
+    #[test]
+    fn matches_ok() {
+        let matchers: [fn(&LavaTorrentError) -> bool; 6] = [
+            matches_malformed_bencode,
+            matches_malformed_torrent,
+            matches_malformed_response,
+            matches_torrent_builder_failure,
+            matches_invalid_argument,
+            matches_failed_numeric_conv,
+        ];
+
+        for (i, (old, _)) in KINDS.iter().enumerate() {
+            let error = LavaTorrentError::new(*old, "message");
+            for (j, matches) in matchers.iter().enumerate() {
+                assert_eq!(matches(&error), i == j);
+            }
+        }
+    }
+}
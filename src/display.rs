@@ -0,0 +1,343 @@
+//! Helpers for presenting torrent data to humans.
+//!
+//! These are what [`Torrent::display_humanized()`] and the alternate
+//! form of `Display` (i.e. `{:#}`) of [`File`] use, exposed for UIs that
+//! lay out the same data on their own.
+//!
+//! The byte helpers ([`display_bytes_summary()`] and
+//! [`display_text_bytes()`]) are what every `Display` impl in this crate
+//! uses for binary data, so that it is rendered the same way everywhere:
+//!
+//! - binary values are summarized as `<N bytes: 0xAABB...>` (or
+//!   `<1 byte: 0xAA>`), with at
+//!   most [`MAX_DISPLAYED_BYTES`] bytes in hex,
+//! - text that is not valid UTF-8 is decoded lossily and followed by
+//!   ` (non-UTF-8)`,
+//! - non-UTF-8 dictionary keys are summarized like binary values.
+//!
+//! Neither panics nor allocates, however large the input is.
+//!
+//! [`Torrent::display_humanized()`]: ../torrent/v1/struct.Torrent.html#method.display_humanized
+//! [`File`]: ../torrent/v1/struct.File.html
+#[cfg(feature = "std")]
+use crate::bencode::{self, BencodeElem, HashMap};
+use crate::hex;
+use alloc::format;
+use alloc::string::String;
+use core::fmt;
+
+const BINARY_UNITS: [&str; 7] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+const DECIMAL_UNITS: [&str; 7] = ["B", "kB", "MB", "GB", "TB", "PB", "EB"];
+
+/// Max # of bytes [`display_bytes_summary()`] renders in hex.
+///
+/// Large enough for SHA1 hashes and peer ids (20 bytes).
+pub const MAX_DISPLAYED_BYTES: usize = 32;
+
+/// Unit system used by [`format_size()`].
+///
+/// [`format_size()`]: fn.format_size.html
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum SizeStyle {
+    /// 1024-based units (i.e. KiB, MiB, GiB, ...).
+    Binary,
+    /// 1000-based units (i.e. kB, MB, GB, ...).
+    Decimal,
+}
+
+/// Format `bytes` (e.g. a file length or piece length) in the
+/// largest unit of `style` that it is at least 1 of.
+///
+/// Sizes that are an exact multiple of that unit are shown without
+/// decimals (e.g. `4 MiB`). Other sizes are rounded to 2 decimals
+/// below 10 (e.g. `1.51 GiB`) and to 1 decimal otherwise (e.g.
+/// `15.3 MiB`). If rounding reaches the next unit, the next unit
+/// is used instead (e.g. `1.00 MiB` rather than `1024.0 KiB`).
+///
+/// Negative sizes are formatted as their absolute value with a `-` prefix.
+///
+/// ```
+/// use lava_torrent::display::{format_size, SizeStyle};
+///
+/// assert_eq!(format_size(1_624_211_456, SizeStyle::Binary), "1.51 GiB");
+/// assert_eq!(format_size(1_624_211_456, SizeStyle::Decimal), "1.62 GB");
+/// assert_eq!(format_size(262_144, SizeStyle::Binary), "256 KiB");
+/// assert_eq!(format_size(1023, SizeStyle::Binary), "1023 B");
+/// ```
+pub fn format_size(bytes: i64, style: SizeStyle) -> String {
+    let (base, units) = match style {
+        SizeStyle::Binary => (1024, BINARY_UNITS),
+        SizeStyle::Decimal => (1000, DECIMAL_UNITS),
+    };
+    let sign = if bytes < 0 { "-" } else { "" };
+    // u128 so that scaling (below) can't overflow
+    let bytes = u128::from(bytes.unsigned_abs());
+
+    let mut exponent = 0;
+    let mut unit = 1;
+    while (exponent + 1 < units.len()) && (bytes >= unit * base) {
+        unit *= base;
+        exponent += 1;
+    }
+    if bytes % unit == 0 {
+        return format!("{}{} {}", sign, bytes / unit, units[exponent]);
+    }
+
+    loop {
+        let hundredths = div_round(bytes * 100, unit);
+        if hundredths < 1000 {
+            return format!(
+                "{}{}.{:02} {}",
+                sign,
+                hundredths / 100,
+                hundredths % 100,
+                units[exponent]
+            );
+        }
+
+        let tenths = div_round(bytes * 10, unit);
+        if (tenths < base * 10) || (exponent + 1 == units.len()) {
+            return format!(
+                "{}{}.{} {}",
+                sign,
+                tenths / 10,
+                tenths % 10,
+                units[exponent]
+            );
+        }
+        // rounded up to the next unit
+        unit *= base;
+        exponent += 1;
+    }
+}
+
+/// Summarize binary `bytes` as `<N bytes: 0xAABB...>`.
+///
+/// Only the first [`MAX_DISPLAYED_BYTES`] bytes are shown in hex, followed
+/// by `...` if there are more.
+///
+/// ```
+/// use lava_torrent::display::display_bytes_summary;
+///
+/// assert_eq!(
+///     display_bytes_summary(&[0xff, 0xf8, 0x00]).to_string(),
+///     "<3 bytes: 0xfff800>"
+/// );
+/// assert_eq!(
+///     display_bytes_summary(&[0xab; 1024]).to_string(),
+///     format!("<1024 bytes: 0x{}...>", "ab".repeat(32))
+/// );
+/// ```
+pub fn display_bytes_summary(bytes: &[u8]) -> impl fmt::Display + '_ {
+    BytesSummary(bytes)
+}
+
+/// Show `bytes` as quoted text (like a bencode string), decoded
+/// as UTF-8 on a best-effort basis.
+///
+/// Invalid sequences are replaced with `U+FFFD`, and ` (non-UTF-8)` is
+/// appended to mark the text as such.
+///
+/// ```
+/// use lava_torrent::display::display_text_bytes;
+///
+/// assert_eq!(display_text_bytes(b"spam").to_string(), r#""spam""#);
+/// assert_eq!(
+///     display_text_bytes(b"sp\xffam").to_string(),
+///     "\"sp\u{fffd}am\" (non-UTF-8)"
+/// );
+/// ```
+pub fn display_text_bytes(bytes: &[u8]) -> impl fmt::Display + '_ {
+    TextBytes(bytes)
+}
+
+// Show `fields` one per line as `-key: value`, sorted by key. This is
+// how every `Display` impl in this crate shows its extra fields.
+#[cfg(feature = "std")]
+pub(crate) fn display_fields(fields: &HashMap<String, BencodeElem>) -> impl fmt::Display + '_ {
+    Fields(fields)
+}
+
+struct BytesSummary<'a>(&'a [u8]);
+
+impl fmt::Display for BytesSummary<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "<{} {}: 0x{}",
+            self.0.len(),
+            if self.0.len() == 1 { "byte" } else { "bytes" },
+            hex::display(&self.0[..self.0.len().min(MAX_DISPLAYED_BYTES)])
+        )?;
+        if self.0.len() > MAX_DISPLAYED_BYTES {
+            f.write_str("...")?;
+        }
+        f.write_str(">")
+    }
+}
+
+struct TextBytes<'a>(&'a [u8]);
+
+impl fmt::Display for TextBytes<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut is_utf8 = true;
+        let mut rest = self.0;
+        f.write_str("\"")?;
+        loop {
+            match core::str::from_utf8(rest) {
+                Ok(valid) => {
+                    f.write_str(valid)?;
+                    break;
+                }
+                Err(e) => {
+                    let (valid, invalid) = rest.split_at(e.valid_up_to());
+                    // checked by `from_utf8()` above
+                    f.write_str(core::str::from_utf8(valid).map_err(|_| fmt::Error)?)?;
+                    f.write_str("\u{fffd}")?;
+                    is_utf8 = false;
+                    // `None` means an incomplete sequence at the end
+                    rest = &invalid[e.error_len().unwrap_or(invalid.len())..];
+                }
+            }
+        }
+        f.write_str("\"")?;
+        if !is_utf8 {
+            f.write_str(" (non-UTF-8)")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+struct Fields<'a>(&'a HashMap<String, BencodeElem>);
+
+#[cfg(feature = "std")]
+impl fmt::Display for Fields<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (key, val) in bencode::sorted_entries(self.0) {
+            writeln!(f, "-{}: {}", key, val)?;
+        }
+        Ok(())
+    }
+}
+
+// `a / b`, rounded half up.
+fn div_round(a: u128, b: u128) -> u128 {
+    (a + b / 2) / b
+}
+
+#[cfg(test)]
+mod display_tests {
+    use super::*;
+
+    const KI: i64 = 1024;
+    const MI: i64 = KI * 1024;
+    const GI: i64 = MI * 1024;
+    const TI: i64 = GI * 1024;
+
+    #[test]
+    fn format_size_binary() {
+        for (bytes, expected) in [
+            (0, "0 B"),
+            (1, "1 B"),
+            (999, "999 B"),
+            (1000, "1000 B"),
+            (1023, "1023 B"),
+            (KI, "1 KiB"),
+            (KI + 1, "1.00 KiB"),
+            (1536, "1.50 KiB"),
+            (10 * KI - 1, "10.0 KiB"),
+            (10 * KI, "10 KiB"),
+            (15_667, "15.3 KiB"),
+            (256 * KI, "256 KiB"),
+            (MI - 52, "1023.9 KiB"),
+            (MI - 51, "1.00 MiB"),
+            (MI - 1, "1.00 MiB"),
+            (MI, "1 MiB"),
+            (16 * MI, "16 MiB"),
+            (1_624_211_456, "1.51 GiB"),
+            (GI, "1 GiB"),
+            (4_700_000_000, "4.38 GiB"),
+            (TI - 1, "1.00 TiB"),
+            (TI, "1 TiB"),
+            (3 * TI + TI / 4, "3.25 TiB"),
+            (i64::MAX, "8.00 EiB"),
+            (i64::MIN, "-8 EiB"),
+            (-1536, "-1.50 KiB"),
+        ] {
+            assert_eq!(format_size(bytes, SizeStyle::Binary), expected, "{}", bytes);
+        }
+    }
+
+    #[test]
+    fn display_bytes_summary_ok() {
+        for (bytes, expected) in [
+            (&[][..], "<0 bytes: 0x>"),
+            (&[0xff][..], "<1 byte: 0xff>"),
+            (&[0x00, 0x0a, 0xff][..], "<3 bytes: 0x000aff>"),
+            (
+                &[0x01; MAX_DISPLAYED_BYTES][..],
+                "<32 bytes: 0x0101010101010101010101010101010101010101010101010101010101010101>",
+            ),
+            (
+                &[0x01; MAX_DISPLAYED_BYTES + 1][..],
+                "<33 bytes: 0x0101010101010101010101010101010101010101010101010101010101010101...>",
+            ),
+        ] {
+            assert_eq!(format!("{}", display_bytes_summary(bytes)), expected);
+        }
+
+        // rendering is bounded however large the input is
+        let huge = alloc::vec![0xab; 16 * 1024 * 1024];
+        assert_eq!(
+            format!("{}", display_bytes_summary(&huge)).len(),
+            "<16777216 bytes: 0x...>".len() + 2 * MAX_DISPLAYED_BYTES
+        );
+    }
+
+    #[test]
+    fn display_text_bytes_ok() {
+        for (bytes, expected) in [
+            (&b""[..], r#""""#),
+            (&b"spam"[..], r#""spam""#),
+            ("ä".as_bytes(), r#""ä""#),
+            (&b"\xff"[..], "\"\u{fffd}\" (non-UTF-8)"),
+            (&b"a\xffb\xf0\x9f"[..], "\"a\u{fffd}b\u{fffd}\" (non-UTF-8)"),
+            (
+                &b"\xf0\x9f\xff\xc3\xa4"[..],
+                "\"\u{fffd}\u{fffd}\u{00e4}\" (non-UTF-8)",
+            ),
+        ] {
+            assert_eq!(format!("{}", display_text_bytes(bytes)), expected);
+        }
+    }
+
+    #[test]
+    fn format_size_decimal() {
+        for (bytes, expected) in [
+            (0, "0 B"),
+            (999, "999 B"),
+            (1000, "1 kB"),
+            (1001, "1.00 kB"),
+            (1023, "1.02 kB"),
+            (1024, "1.02 kB"),
+            (1995, "2.00 kB"),
+            (9_995, "10.0 kB"),
+            (999_949, "999.9 kB"),
+            (999_950, "1.00 MB"),
+            (1_000_000, "1 MB"),
+            (1_624_211_456, "1.62 GB"),
+            (4_700_000_000, "4.70 GB"),
+            (1_000_000_000_000, "1 TB"),
+            (12_345_678_901_234, "12.3 TB"),
+            (-1_000, "-1 kB"),
+        ] {
+            assert_eq!(
+                format_size(bytes, SizeStyle::Decimal),
+                expected,
+                "{}",
+                bytes
+            );
+        }
+    }
+}
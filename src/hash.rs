@@ -0,0 +1,54 @@
+//! Module containing digest helpers.
+//!
+//! These use the same SHA-1 implementation as info hashes, so that
+//! e.g. a *.torrent* file can be identified by the digest of its bytes
+//! without pulling in another hashing dependency. Hex strings are
+//! lowercase, like those returned by [`Torrent::info_hash()`].
+//!
+//! [`Torrent::info_hash()`]: ../torrent/v1/struct.Torrent.html#method.info_hash
+
+use crate::hex;
+use sha1::{Digest, Sha1};
+use sha2::Sha256;
+
+/// Calculate the SHA-1 digest of `bytes`.
+pub fn sha1(bytes: &[u8]) -> [u8; 20] {
+    Sha1::digest(bytes).into()
+}
+
+/// Calculate the SHA-1 digest of `bytes` and return it as a hex string.
+pub fn sha1_hex(bytes: &[u8]) -> String {
+    hex::encode(&Sha1::digest(bytes))
+}
+
+/// Calculate the SHA-256 digest of `bytes`.
+pub fn sha256(bytes: &[u8]) -> [u8; 32] {
+    Sha256::digest(bytes).into()
+}
+
+/// Calculate the SHA-256 digest of `bytes` and return it as a hex string.
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    hex::encode(&Sha256::digest(bytes))
+}
+
+#[cfg(test)]
+mod hash_tests {
+    use super::*;
+
+    // test vectors from FIPS 180-2
+    const ABC_SHA1: &str = "a9993e364706816aba3e25717850c26c9cd0d89d";
+    const ABC_SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    #[test]
+    fn sha1_ok() {
+        assert_eq!(sha1_hex(b"abc"), ABC_SHA1);
+        assert_eq!(hex::encode(&sha1(b"abc")), ABC_SHA1);
+        assert_eq!(sha1_hex(b""), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+    }
+
+    #[test]
+    fn sha256_ok() {
+        assert_eq!(sha256_hex(b"abc"), ABC_SHA256);
+        assert_eq!(hex::encode(&sha256(b"abc")), ABC_SHA256);
+    }
+}
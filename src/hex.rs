@@ -0,0 +1,186 @@
+//! Hex encoding/decoding, e.g. for info hashes and peer ids.
+//!
+//! This is what every part of this crate that shows bytes in hex uses.
+//! Encoded strings are always zero-padded, i.e. 2 chars per byte.
+//!
+//! ```
+//! use lava_torrent::hex;
+//!
+//! assert_eq!(hex::encode(&[0x0a, 0xff]), "0aff");
+//! assert_eq!(hex::encode_upper(&[0x0a, 0xff]), "0AFF");
+//! assert_eq!(hex::decode("0aFF").unwrap(), vec![0x0a, 0xff]);
+//! ```
+use crate::LavaTorrentError;
+use alloc::borrow::Cow;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+
+const LOWER_DIGITS: &[u8; 16] = b"0123456789abcdef";
+const UPPER_DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+
+struct Hex<'a> {
+    bytes: &'a [u8],
+    digits: &'static [u8; 16],
+}
+
+impl fmt::Display for Hex<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for &byte in self.bytes {
+            f.write_char(char::from(self.digits[usize::from(byte >> 4)]))?;
+            f.write_char(char::from(self.digits[usize::from(byte & 0xf)]))?;
+        }
+        Ok(())
+    }
+}
+
+/// Encode `bytes` as a lowercase hex string.
+pub fn encode(bytes: &[u8]) -> String {
+    format!("{}", display(bytes))
+}
+
+/// Encode `bytes` as an uppercase hex string.
+pub fn encode_upper(bytes: &[u8]) -> String {
+    format!(
+        "{}",
+        Hex {
+            bytes,
+            digits: UPPER_DIGITS,
+        }
+    )
+}
+
+/// Decode the hex string `s`. Upper, lower, and mixed case are accepted.
+///
+/// An error is returned if `s` has an odd length or contains
+/// a char that is not a hex digit.
+pub fn decode(s: &str) -> Result<Vec<u8>, LavaTorrentError> {
+    if let Some((offset, c)) = s.char_indices().find(|(_, c)| !c.is_ascii_hexdigit()) {
+        return Err(LavaTorrentError::InvalidArgument(Cow::Owned(format!(
+            "Hex string has a non-hex char {:?} at offset {}.",
+            c, offset
+        ))));
+    }
+    if !s.len().is_multiple_of(2) {
+        return Err(LavaTorrentError::InvalidArgument(Cow::Owned(format!(
+            "Hex string has an odd length {}.",
+            s.len()
+        ))));
+    }
+
+    Ok(s.as_bytes()
+        .chunks(2)
+        .map(|pair| (digit_value(pair[0]) << 4) | digit_value(pair[1]))
+        .collect())
+}
+
+// Show `bytes` in lowercase hex without allocating.
+pub(crate) fn display(bytes: &[u8]) -> impl fmt::Display + '_ {
+    Hex {
+        bytes,
+        digits: LOWER_DIGITS,
+    }
+}
+
+// `digit` must be an ASCII hex digit.
+fn digit_value(digit: u8) -> u8 {
+    match digit {
+        b'0'..=b'9' => digit - b'0',
+        b'a'..=b'f' => digit - b'a' + 10,
+        _ => digit - b'A' + 10,
+    }
+}
+
+#[cfg(test)]
+mod hex_tests {
+    use super::*;
+    use alloc::string::ToString;
+    use alloc::vec;
+
+    #[test]
+    fn encode_ok() {
+        assert_eq!(encode(&[]), "");
+        assert_eq!(
+            encode(&[0x00, 0x01, 0x0a, 0x10, 0xab, 0xff]),
+            "00010a10abff"
+        );
+        assert_eq!(
+            encode_upper(&[0x00, 0x01, 0x0a, 0x10, 0xab, 0xff]),
+            "00010A10ABFF"
+        );
+    }
+
+    #[test]
+    fn encode_zero_padded() {
+        // `{:x}` would give "0a0" here
+        assert_eq!(encode(&[0x00, 0x0a, 0x00]), "000a00");
+        assert_eq!(encode(&[0; 20]).len(), 40);
+    }
+
+    #[test]
+    fn decode_ok() {
+        assert_eq!(decode("").unwrap(), vec![]);
+        assert_eq!(
+            decode("00010a10abff").unwrap(),
+            vec![0, 1, 0x0a, 0x10, 0xab, 0xff]
+        );
+        assert_eq!(
+            decode("00010A10ABFF").unwrap(),
+            vec![0, 1, 0x0a, 0x10, 0xab, 0xff]
+        );
+        assert_eq!(decode("aBcD").unwrap(), vec![0xab, 0xcd]);
+    }
+
+    #[test]
+    fn decode_round_trip() {
+        let bytes = (0..=255).collect::<Vec<u8>>();
+        assert_eq!(decode(&encode(&bytes)).unwrap(), bytes);
+        assert_eq!(decode(&encode_upper(&bytes)).unwrap(), bytes);
+    }
+
+    #[test]
+    fn decode_odd_length() {
+        match decode("abc") {
+            Err(LavaTorrentError::InvalidArgument(m)) => {
+                assert_eq!(m, "Hex string has an odd length 3.");
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn decode_non_hex() {
+        match decode("abcg") {
+            Err(LavaTorrentError::InvalidArgument(m)) => {
+                assert_eq!(m, "Hex string has a non-hex char 'g' at offset 3.");
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        match decode("0x12") {
+            Err(LavaTorrentError::InvalidArgument(m)) => {
+                assert_eq!(m, "Hex string has a non-hex char 'x' at offset 1.");
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        // offsets are in bytes
+        match decode("ab\u{e9}0") {
+            Err(LavaTorrentError::InvalidArgument(m)) => {
+                assert_eq!(m, "Hex string has a non-hex char '\u{e9}' at offset 2.");
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        // odd length is reported after non-hex chars
+        match decode(" ab") {
+            Err(LavaTorrentError::InvalidArgument(m)) => {
+                assert_eq!(m, "Hex string has a non-hex char ' ' at offset 0.");
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn display_ok() {
+        assert_eq!(display(&[0x0a, 0xbc]).to_string(), "0abc");
+    }
+}
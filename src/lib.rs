@@ -39,17 +39,34 @@
 //! # *Overview*
 //! - **It is not recommended to use [`lava_torrent`] in any critical system at this point.**
 //! - Methods for parsing and encoding are generally bound to structs (i.e. they are
-//! "associated methods"). Methods that are general enough are placed at the module-level (e.g.
-//! [`lava_torrent::bencode::write::encode_bytes()`]).
+//!   "associated methods"). Methods that are general enough are placed at the module-level (e.g.
+//!   [`lava_torrent::bencode::write::encode_bytes()`]).
 //!
 //! ## Functionality
 //! - bencode parsing/encoding (i.e. "bencoding/bdecoding") => [`BencodeElem`]
+//! - bencode construction with a JSON-like syntax => [`bencode_elem!`]
 //! - torrent parsing/encoding (based on [`BencodeElem`]) => [`Torrent`]
 //! - torrent creation => [`TorrentBuilder`]
-//! - tracker response parsing => [`tracker`]
+//! - tracker request/response parsing => [`tracker`]
+//! - SHA-1/SHA-256 digests of arbitrary bytes (e.g. whole *.torrent* files) => [`hash`]
+//! - hex encoding/decoding (e.g. of info hashes) => [`hex`]
+//! - human-readable sizes (e.g. `1.51 GiB`) => [`display::format_size()`]
+//! - transfer planning (e.g. time to reach a share ratio) => [`planning`]
+//! - deprecated shims for code written against the previous error API => [`compat`]
 //!
 //! ## Feature Flags
-//! None at the moment.
+//! - `std` (enabled by default): everything besides the bencode core, i.e. torrent
+//!   parsing/creation, tracker responses, and file/`io::Write`-based bencode methods.
+//! - `alloc`: build without `std`, i.e. `#![no_std]` + `alloc`. Only [`bencode`]'s core
+//!   (parsing, `encode*()`, and `Display`) and [`display`] are available, and dictionaries are backed by
+//!   [`hashbrown`]'s `HashMap` instead of `std`'s. Use it with `default-features = false`.
+//! - `encoding`: decode free-text fields (e.g. `comment`) that are not valid UTF-8
+//!   using the torrent's declared `encoding` (via [`encoding_rs`]).
+//! - `gzip`: transparently decompress gzip/zlib-compressed *.torrent* files
+//!   (via [`flate2`]) when reading them.
+//! - `paranoid`: check the piece accounting of every torrent build in release builds
+//!   too, as is always done with `debug_assertions`. A violation (i.e. a bug in
+//!   `lava_torrent`) fails the build instead of panicking.
 //!
 //! # *Correctness*
 //! [`lava_torrent`] is written without using any existing parser or parser generator.
@@ -61,19 +78,19 @@
 //!
 //! ## Known Issues
 //! 1. [BEP 3] specifies that a bencode integer has no
-//! size limit. This is a reasonable choice as it allows the protocol to be used
-//! in the future when file sizes grow significantly. However, using a 64-bit signed
-//! integer to represent a bencode integer should be more-than sufficient even in 2018.
-//! Therefore, while technically we should use something like
-//! [`bigint`] to represent bencode integers,
-//! `i64` is used in the current implementation. If a bencode integer larger than
-//! [`i64::max_value()`]
-//! is found, a `LavaTorrentError` will be returned.
+//!    size limit. This is a reasonable choice as it allows the protocol to be used
+//!    in the future when file sizes grow significantly. However, using a 64-bit signed
+//!    integer to represent a bencode integer should be more-than sufficient even in 2018.
+//!    Therefore, while technically we should use something like
+//!    [`bigint`] to represent bencode integers,
+//!    `i64` is used in the current implementation. If a bencode integer larger than
+//!    [`i64::max_value()`]
+//!    is found, a `LavaTorrentError` will be returned.
 //!
 //! 2. Several private methods will panic if something that "just won't happen"
-//! happens. For the purpose of full disclosure this behavior is mentioned here,
-//! but in reality panic should never be triggered. If you want to locate these
-//! private methods try searching for "panic", "unwrap", and "expect" in `*.rs` files.
+//!    happens. For the purpose of full disclosure this behavior is mentioned here,
+//!    but in reality panic should never be triggered. If you want to locate these
+//!    private methods try searching for "panic", "unwrap", and "expect" in `*.rs` files.
 //!
 //! # *Implemented BEPs*
 //! NOTE: Only the parsing/encoding aspects are implemented.
@@ -92,6 +109,15 @@
 //! [`Torrent`]: torrent/v1/struct.Torrent.html
 //! [`TorrentBuilder`]: torrent/v1/struct.TorrentBuilder.html
 //! [`tracker`]: tracker/index.html
+//! [`hash`]: hash/index.html
+//! [`hex`]: hex/index.html
+//! [`display`]: display/index.html
+//! [`display::format_size()`]: display/fn.format_size.html
+//! [`planning`]: planning/index.html
+//! [`compat`]: compat/index.html
+//! [`encoding_rs`]: https://docs.rs/encoding_rs
+//! [`hashbrown`]: https://docs.rs/hashbrown
+//! [`flate2`]: https://docs.rs/flate2
 //! [BitTorrent specification]: http://bittorrent.org/beps/bep_0003.html
 //! [BEP 3]: http://bittorrent.org/beps/bep_0003.html
 //! [`bigint`]: https://github.com/rust-num/num-bigint
@@ -100,58 +126,224 @@
 //! [BEP 12]: http://bittorrent.org/beps/bep_0012.html
 //! [BEP 27]: http://bittorrent.org/beps/bep_0027.html
 
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+#[cfg(not(any(feature = "std", feature = "alloc")))]
+compile_error!("either the `std` or the `alloc` feature must be enabled");
+
+extern crate alloc;
+#[cfg(feature = "std")]
 extern crate conv;
 extern crate itertools;
+#[cfg(feature = "std")]
 extern crate percent_encoding;
+#[cfg(feature = "std")]
 extern crate rayon;
+#[cfg(feature = "std")]
 extern crate sha1;
+#[cfg(feature = "std")]
+extern crate sha2;
 extern crate thiserror;
 
 pub(crate) mod util;
 #[macro_use]
 pub mod bencode;
+pub mod compat;
+pub mod display;
+#[cfg(feature = "std")]
+pub mod hash;
+pub mod hex;
+#[cfg(feature = "std")]
+pub mod planning;
+#[cfg(feature = "std")]
 pub mod torrent;
+#[cfg(feature = "std")]
 pub mod tracker;
 
+use alloc::borrow::Cow;
 use thiserror::Error;
 
 /// Custom error.
 ///
-#[derive(Error, Debug)]
+#[derive(Error, Clone, Debug)]
 pub enum LavaTorrentError {
     #[doc = "IO error occurred. \
     The bencode and the torrent may or may not be malformed \
-    (as we can't verify that)."]
+    (as we can't verify that). \
+    The `io::Error` is shared so that `LavaTorrentError` can be cloned."]
+    #[cfg(feature = "std")]
     #[error(transparent)]
-    Io(#[from] std::io::Error),
+    Io(std::sync::Arc<std::io::Error>),
 
     #[doc = "The bencode is found to be bad before we can parse \
     the torrent, so the torrent may or may not be malformed. \
     This is generally unexpected behavior and thus should be handled."]
     #[error("malformed bencode: {0}")]
-    MalformedBencode(std::borrow::Cow<'static, str>),
+    MalformedBencode(Cow<'static, str>),
 
     #[doc = "Bencode is fine, but parsed data is gibberish, so we \
     can't extract a torrent from it."]
     #[error("malformed torrent: {0}")]
-    MalformedTorrent(std::borrow::Cow<'static, str>),
+    MalformedTorrent(Cow<'static, str>),
 
     #[doc = "Bencode is fine, but parsed data is gibberish, so we \
     can't extract a response from it."]
     #[error("malformed response: {0}")]
-    MalformedResponse(std::borrow::Cow<'static, str>),
+    MalformedResponse(Cow<'static, str>),
 
     #[doc = "`TorrentBuilder` encounters problems when \
     building `Torrent`. For instance, a field is set to \
     an empty string by the caller."]
     #[error("failed to build torrent: {0}")]
-    TorrentBuilderFailure(std::borrow::Cow<'static, str>),
+    TorrentBuilderFailure(Cow<'static, str>),
 
     #[doc = "An invalid argument is passed to a function."]
     #[error("invalid argument: {0}")]
-    InvalidArgument(std::borrow::Cow<'static, str>),
+    InvalidArgument(Cow<'static, str>),
 
     #[doc = "Conversion between numeric types (e.g. `i64 -> u64`) has failed."]
     #[error("numeric conversion failed: {0}")]
-    FailedNumericConv(std::borrow::Cow<'static, str>),
+    FailedNumericConv(Cow<'static, str>),
+}
+
+/// The variant of a [`LavaTorrentError`], without the data it carries.
+///
+/// Useful for matching on errors without destructuring them.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum LavaTorrentErrorKind {
+    #[cfg(feature = "std")]
+    Io,
+    MalformedBencode,
+    MalformedTorrent,
+    MalformedResponse,
+    TorrentBuilderFailure,
+    InvalidArgument,
+    FailedNumericConv,
+}
+
+impl LavaTorrentError {
+    /// Return the kind of this error.
+    pub fn kind(&self) -> LavaTorrentErrorKind {
+        match self {
+            #[cfg(feature = "std")]
+            LavaTorrentError::Io(_) => LavaTorrentErrorKind::Io,
+            LavaTorrentError::MalformedBencode(_) => LavaTorrentErrorKind::MalformedBencode,
+            LavaTorrentError::MalformedTorrent(_) => LavaTorrentErrorKind::MalformedTorrent,
+            LavaTorrentError::MalformedResponse(_) => LavaTorrentErrorKind::MalformedResponse,
+            LavaTorrentError::TorrentBuilderFailure(_) => {
+                LavaTorrentErrorKind::TorrentBuilderFailure
+            }
+            LavaTorrentError::InvalidArgument(_) => LavaTorrentErrorKind::InvalidArgument,
+            LavaTorrentError::FailedNumericConv(_) => LavaTorrentErrorKind::FailedNumericConv,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for LavaTorrentError {
+    fn from(e: std::io::Error) -> LavaTorrentError {
+        LavaTorrentError::Io(std::sync::Arc::new(e))
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod lava_torrent_error_tests {
+    use super::*;
+    use std::error::Error;
+    use std::io;
+
+    #[test]
+    fn clone_io() {
+        let error = LavaTorrentError::from(io::Error::from(io::ErrorKind::PermissionDenied));
+        let cloned = error.clone();
+
+        match (&error, &cloned) {
+            (LavaTorrentError::Io(e1), LavaTorrentError::Io(e2)) => {
+                assert_eq!(e1.kind(), io::ErrorKind::PermissionDenied);
+                assert_eq!(e2.kind(), io::ErrorKind::PermissionDenied);
+            }
+            _ => panic!(),
+        }
+        assert_eq!(cloned.to_string(), error.to_string());
+        assert_eq!(cloned.kind(), LavaTorrentErrorKind::Io);
+    }
+
+    #[derive(Error, Debug)]
+    #[error("wrapper")]
+    struct Wrapper(#[source] LavaTorrentError);
+
+    #[test]
+    fn clone_io_source() {
+        // `Io` is transparent, i.e. both `Display` and `source()`
+        // are forwarded to the `io::Error` (which forwards them
+        // to `Wrapper` in turn)
+        let root = LavaTorrentError::InvalidArgument(Cow::Borrowed("root"));
+        let error = LavaTorrentError::from(io::Error::other(Wrapper(root)));
+        let cloned = error.clone();
+
+        for error in [error, cloned] {
+            assert_eq!(error.to_string(), "wrapper");
+            let source = error.source().unwrap();
+            assert_eq!(source.to_string(), "invalid argument: root");
+            assert!(source.source().is_none());
+        }
+    }
+
+    #[test]
+    fn clone_io_from_question_mark() {
+        fn open() -> Result<(), LavaTorrentError> {
+            std::fs::File::open("tests/files/does-not-exist")?;
+            Ok(())
+        }
+
+        let error = open().unwrap_err();
+        match error.clone() {
+            LavaTorrentError::Io(e) => assert_eq!(e.kind(), io::ErrorKind::NotFound),
+            _ => panic!(),
+        }
+        assert_eq!(error.clone().to_string(), error.to_string());
+    }
+
+    #[test]
+    fn clone_other_variants() {
+        for (error, kind, display) in [
+            (
+                LavaTorrentError::MalformedBencode(Cow::Borrowed("a")),
+                LavaTorrentErrorKind::MalformedBencode,
+                "malformed bencode: a",
+            ),
+            (
+                LavaTorrentError::MalformedTorrent(Cow::Owned("b".to_owned())),
+                LavaTorrentErrorKind::MalformedTorrent,
+                "malformed torrent: b",
+            ),
+            (
+                LavaTorrentError::MalformedResponse(Cow::Borrowed("c")),
+                LavaTorrentErrorKind::MalformedResponse,
+                "malformed response: c",
+            ),
+            (
+                LavaTorrentError::TorrentBuilderFailure(Cow::Borrowed("d")),
+                LavaTorrentErrorKind::TorrentBuilderFailure,
+                "failed to build torrent: d",
+            ),
+            (
+                LavaTorrentError::InvalidArgument(Cow::Borrowed("e")),
+                LavaTorrentErrorKind::InvalidArgument,
+                "invalid argument: e",
+            ),
+            (
+                LavaTorrentError::FailedNumericConv(Cow::Borrowed("f")),
+                LavaTorrentErrorKind::FailedNumericConv,
+                "numeric conversion failed: f",
+            ),
+        ] {
+            let cloned = error.clone();
+            assert_eq!(cloned.kind(), kind);
+            assert_eq!(error.kind(), kind);
+            assert_eq!(cloned.to_string(), display);
+            assert_eq!(error.to_string(), display);
+            assert!(cloned.source().is_none());
+        }
+    }
 }
@@ -0,0 +1,442 @@
+//! Back-of-the-envelope estimates for scheduling transfers.
+//!
+//! Everything here is plain arithmetic over a [`Torrent`]'s metadata and
+//! [`SwarmStats`] (e.g. from a scrape), so that e.g. a seedbox can decide
+//! which torrents to keep active without touching any data. Sizes are in
+//! bytes and rates are in bytes per second. The math saturates instead
+//! of overflowing.
+//!
+//! [`Torrent`]: ../torrent/v1/struct.Torrent.html
+//! [`SwarmStats`]: ../tracker/struct.SwarmStats.html
+
+use crate::torrent::v1::Torrent;
+use crate::tracker::SwarmStats;
+use std::time::Duration;
+
+/// Length of the blocks that pieces are requested in.
+const BLOCK_LENGTH: u64 = 16 * 1024;
+/// Length of a `request` message (length prefix, id, index, begin, length).
+const REQUEST_MESSAGE_LENGTH: u64 = 17;
+/// Length of a `piece` message minus the block itself
+/// (length prefix, id, index, begin).
+const PIECE_MESSAGE_HEADER_LENGTH: u64 = 13;
+/// Length of a `have` message (length prefix, id, index).
+const HAVE_MESSAGE_LENGTH: u64 = 9;
+
+// Counts the bytes written to it, and discards them.
+struct ByteCounter(u64);
+
+impl std::io::Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0 = self.0.saturating_add(buf.len() as u64);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Result of [`transfer_estimate()`].
+///
+/// [`transfer_estimate()`]: fn.transfer_estimate.html
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct TransferEstimate {
+    /// The torrent's `length` in bytes (0 if it is negative).
+    pub length: u64,
+    /// Upload budget in bytes per second.
+    pub upload_bps: u64,
+    /// Upload rate (bytes per second) each leecher gets if the upload
+    /// budget is split evenly among them. `None` if there are no leechers.
+    pub per_peer_share: Option<u64>,
+    /// Bytes each seeder (including us) uploads for every current leecher
+    /// to finish once, if the load is spread evenly. Without other
+    /// seeders, this is `length` times the number of leechers.
+    pub bytes_per_seeder: u64,
+}
+
+/// Estimate what seeding `torrent` to a swarm with `stats` takes,
+/// with an upload budget of `my_upload_bps` bytes per second.
+///
+/// Counts missing from `stats` are treated as 0. `completed` is not used.
+///
+/// ```
+/// use lava_torrent::planning;
+/// use lava_torrent::torrent::v1::Torrent;
+/// use lava_torrent::tracker::SwarmStats;
+/// use std::time::Duration;
+///
+/// let torrent = Torrent::read_from_file("tests/files/tails-amd64-3.6.1.torrent").unwrap();
+/// let stats = SwarmStats {
+///     seeders: Some(3),
+///     leechers: Some(5),
+///     completed: None,
+/// };
+/// let estimate = planning::transfer_estimate(&torrent, &stats, 1_000_000);
+///
+/// assert_eq!(estimate.per_peer_share, Some(200_000));
+/// assert_eq!(torrent.length, 1_225_568_484);
+/// assert_eq!(estimate.bytes_to_upload_for_ratio(2.0), 2_451_136_968);
+/// assert_eq!(
+///     estimate.time_to_ratio(2.0),
+///     Some(Duration::from_nanos(2_451_136_968_000)),
+/// );
+/// ```
+pub fn transfer_estimate(
+    torrent: &Torrent,
+    stats: &SwarmStats,
+    my_upload_bps: u64,
+) -> TransferEstimate {
+    let length = u64::try_from(torrent.length).unwrap_or(0);
+    let seeders = stats.seeders.unwrap_or(0);
+    let leechers = stats.leechers.unwrap_or(0);
+
+    TransferEstimate {
+        length,
+        upload_bps: my_upload_bps,
+        per_peer_share: my_upload_bps.checked_div(leechers),
+        bytes_per_seeder: length.saturating_mul(leechers) / seeders.saturating_add(1),
+    }
+}
+
+impl TransferEstimate {
+    /// Bytes to upload to reach a share ratio (uploaded / `length`)
+    /// of `ratio`, rounded up.
+    ///
+    /// A `ratio` that is not positive (or is NaN) needs nothing.
+    pub fn bytes_to_upload_for_ratio(&self, ratio: f64) -> u64 {
+        if ratio > 0.0 {
+            // `as` saturates
+            (self.length as f64 * ratio).ceil() as u64
+        } else {
+            0
+        }
+    }
+
+    /// Time to reach a share ratio of `ratio` when uploading
+    /// at `upload_bps` the whole time, i.e. assuming that leechers
+    /// take all of it.
+    ///
+    /// `None` is returned if that never happens, i.e. if something has to
+    /// be uploaded but `upload_bps` is 0.
+    pub fn time_to_ratio(&self, ratio: f64) -> Option<Duration> {
+        let bytes = self.bytes_to_upload_for_ratio(ratio);
+        if bytes == 0 {
+            return Some(Duration::ZERO);
+        }
+        let secs = bytes.checked_div(self.upload_bps)?;
+        let remainder = u128::from(bytes % self.upload_bps);
+        // < 1e9 since `remainder` < `upload_bps`
+        let nanos = remainder * 1_000_000_000 / u128::from(self.upload_bps);
+        Some(Duration::new(secs, nanos as u32))
+    }
+}
+
+/// Result of [`Torrent::overhead_estimate()`].
+///
+/// Counts what a single peer downloading the whole torrent exchanges on
+/// top of the data itself (i.e. one `request` and one `piece` header per
+/// 16 KiB block, and one `have` per piece), plus the size of the `info`
+/// dict (e.g. fetched via a magnet link). Handshakes, `bitfield`s and
+/// keep-alives are per connection, so they are not included.
+///
+/// [`Torrent::overhead_estimate()`]: ../torrent/v1/struct.Torrent.html#method.overhead_estimate
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct OverheadEstimate {
+    /// The torrent's `length` in bytes (0 if it is negative).
+    pub length: u64,
+    /// Number of pieces.
+    pub num_pieces: u64,
+    /// Number of 16 KiB blocks (the last block of each piece may be shorter).
+    pub num_blocks: u64,
+    /// Bytes of protocol messages.
+    pub message_bytes: u64,
+    /// Bytes of the encoded `info` dict.
+    pub metadata_bytes: u64,
+}
+
+impl OverheadEstimate {
+    /// Total overhead in bytes, i.e. `message_bytes` + `metadata_bytes`.
+    pub fn total(&self) -> u64 {
+        self.message_bytes.saturating_add(self.metadata_bytes)
+    }
+
+    /// Overhead relative to `length`, e.g. `0.01` for 1%.
+    ///
+    /// If `length` is 0, `f64::INFINITY` is returned, or `0.0` if there
+    /// is no overhead either (rather than NaN).
+    pub fn ratio(&self) -> f64 {
+        match self.total() {
+            0 => 0.0,
+            total => total as f64 / self.length as f64,
+        }
+    }
+}
+
+impl Torrent {
+    /// Estimate the protocol overhead of downloading `self` in full, which
+    /// makes the true cost of e.g. tiny pieces visible. See
+    /// [`OverheadEstimate`] for what is counted.
+    ///
+    /// Like [`piece_length_advisory()`], this never fails, even if `self`
+    /// is inconsistent. Pieces are counted from `pieces`, each of them
+    /// `piece_length` long except the last one (which takes the rest of
+    /// `length`), and each of them takes at least 1 block.
+    ///
+    /// [`piece_length_advisory()`]: ../torrent/v1/struct.Torrent.html#method.piece_length_advisory
+    pub fn overhead_estimate(&self) -> OverheadEstimate {
+        let length = u64::try_from(self.length).unwrap_or(0);
+        let piece_length = u64::try_from(self.piece_length).unwrap_or(0);
+        let num_pieces = self.pieces.len() as u64;
+        let blocks_in = |piece_length: u64| piece_length.div_ceil(BLOCK_LENGTH).max(1);
+
+        let num_blocks = match num_pieces.checked_sub(1) {
+            Some(num_full_pieces) => {
+                let last_piece_length =
+                    length.saturating_sub(num_full_pieces.saturating_mul(piece_length));
+                num_full_pieces
+                    .saturating_mul(blocks_in(piece_length))
+                    .saturating_add(blocks_in(last_piece_length))
+            }
+            None => 0,
+        };
+        let message_bytes = num_blocks
+            .saturating_mul(REQUEST_MESSAGE_LENGTH + PIECE_MESSAGE_HEADER_LENGTH)
+            .saturating_add(num_pieces.saturating_mul(HAVE_MESSAGE_LENGTH));
+
+        let mut metadata_bytes = ByteCounter(0);
+        self.write_info_streaming(&mut metadata_bytes)
+            .expect("Write to counter failed!");
+
+        OverheadEstimate {
+            length,
+            num_pieces,
+            num_blocks,
+            message_bytes,
+            metadata_bytes: metadata_bytes.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod planning_tests {
+    use super::*;
+    use crate::torrent::v1::torrent_tests::healthy;
+
+    const GI: i64 = 1024 * 1024 * 1024;
+
+    fn torrent(length: i64) -> Torrent {
+        Torrent {
+            length,
+            piece_length: 4 * 1024 * 1024,
+            ..healthy()
+        }
+    }
+
+    #[test]
+    fn transfer_estimate_shares() {
+        for (length, seeders, leechers, upload_bps, per_peer_share, bytes_per_seeder) in [
+            (
+                GI,
+                Some(3),
+                Some(5),
+                1_000_000,
+                Some(200_000),
+                5 * GI as u64 / 4,
+            ),
+            // we are the only seeder
+            (
+                GI,
+                Some(0),
+                Some(5),
+                1_000_000,
+                Some(200_000),
+                5 * GI as u64,
+            ),
+            (GI, Some(0), Some(1), 1_000_000, Some(1_000_000), GI as u64),
+            // no one to upload to
+            (GI, Some(3), Some(0), 1_000_000, None, 0),
+            (GI, Some(0), Some(0), 1_000_000, None, 0),
+            // no upload budget
+            (GI, Some(3), Some(5), 0, Some(0), 5 * GI as u64 / 4),
+            (0, Some(3), Some(5), 1_000_000, Some(200_000), 0),
+            (-1, Some(3), Some(5), 1_000_000, Some(200_000), 0),
+            (i64::MAX, Some(0), Some(u64::MAX), 1, Some(0), u64::MAX),
+            (i64::MAX, Some(u64::MAX), Some(1), 1, Some(1), 0),
+            // unreported counts are 0
+            (GI, None, Some(5), 1_000_000, Some(200_000), 5 * GI as u64),
+            (GI, Some(3), None, 1_000_000, None, 0),
+        ] {
+            let stats = SwarmStats {
+                seeders,
+                leechers,
+                completed: None,
+            };
+            let estimate = transfer_estimate(&torrent(length), &stats, upload_bps);
+            assert_eq!(estimate.length, u64::try_from(length).unwrap_or(0));
+            assert_eq!(estimate.upload_bps, upload_bps);
+            assert_eq!(estimate.per_peer_share, per_peer_share, "{:?}", stats);
+            assert_eq!(estimate.bytes_per_seeder, bytes_per_seeder, "{:?}", stats);
+        }
+    }
+
+    #[test]
+    fn bytes_to_upload_for_ratio() {
+        for (length, ratio, expected) in [
+            (1000, 1.0, 1000),
+            (1000, 1.5, 1500),
+            (1000, 0.0015, 2),
+            (1000, 0.0, 0),
+            (1000, -1.0, 0),
+            (1000, f64::NAN, 0),
+            (1000, f64::INFINITY, u64::MAX),
+            (0, 2.0, 0),
+            (i64::MAX, 4.0, u64::MAX),
+        ] {
+            let stats = SwarmStats::default();
+            let estimate = transfer_estimate(&torrent(length), &stats, 0);
+            assert_eq!(
+                estimate.bytes_to_upload_for_ratio(ratio),
+                expected,
+                "{} {}",
+                length,
+                ratio
+            );
+        }
+    }
+
+    #[test]
+    fn time_to_ratio() {
+        for (length, upload_bps, ratio, expected) in [
+            (1000, 100, 1.0, Some(Duration::from_secs(10))),
+            (1000, 300, 1.0, Some(Duration::new(3, 333_333_333))),
+            (1000, 1, 2.0, Some(Duration::from_secs(2000))),
+            (1000, u64::MAX, 1.0, Some(Duration::from_nanos(0))),
+            (GI, 1024 * 1024, 1.0, Some(Duration::from_secs(1024))),
+            // no upload budget
+            (1000, 0, 1.0, None),
+            (1000, 0, 0.0, Some(Duration::ZERO)),
+            // nothing to upload
+            (0, 0, 1.0, Some(Duration::ZERO)),
+            (0, 100, 1.0, Some(Duration::ZERO)),
+            (
+                i64::MAX,
+                1,
+                f64::INFINITY,
+                Some(Duration::from_secs(u64::MAX)),
+            ),
+        ] {
+            let stats = SwarmStats::default();
+            let estimate = transfer_estimate(&torrent(length), &stats, upload_bps);
+            assert_eq!(
+                estimate.time_to_ratio(ratio),
+                expected,
+                "{} {} {}",
+                length,
+                upload_bps,
+                ratio
+            );
+        }
+    }
+
+    #[test]
+    fn overhead_estimate_ok() {
+        const KI: i64 = 1024;
+        const MI: i64 = 1024 * KI;
+        for (length, piece_length, num_pieces, num_blocks, message_bytes) in [
+            (32 * KI, 16 * KI, 2, 2, 2 * 30 + 2 * 9),
+            (10 * MI, 4 * MI, 3, 2 * 256 + 128, 640 * 30 + 3 * 9),
+            // partial last block
+            (40_000, 32 * KI, 2, 3, 3 * 30 + 2 * 9),
+            // tiny pieces: 1 block per KiB instead of per 16 KiB
+            (10 * KI, KI, 10, 10, 10 * 30 + 10 * 9),
+            (10 * KI, 16 * KI, 1, 1, 30 + 9),
+            (0, 16 * KI, 0, 0, 0),
+            // inconsistent
+            (100, 0, 2, 2, 2 * 30 + 2 * 9),
+            (-1, 16 * KI, 1, 1, 30 + 9),
+        ] {
+            let mut torrent = healthy();
+            torrent.length = length;
+            torrent.piece_length = piece_length;
+            torrent.pieces = vec![[1; 20]; num_pieces];
+
+            let estimate = torrent.overhead_estimate();
+            assert_eq!(estimate.length, u64::try_from(length).unwrap_or(0));
+            assert_eq!(estimate.num_pieces, num_pieces as u64);
+            assert_eq!(
+                estimate.num_blocks, num_blocks,
+                "{} {}",
+                length, piece_length
+            );
+            assert_eq!(
+                estimate.message_bytes, message_bytes,
+                "{} {}",
+                length, piece_length
+            );
+            assert_eq!(
+                estimate.metadata_bytes,
+                torrent.construct_info().encode().len() as u64
+            );
+        }
+    }
+
+    #[test]
+    fn overhead_estimate_small_pieces_cost_more() {
+        const KI: i64 = 1024;
+        let mut torrent = healthy();
+        torrent.length = 64 * 1024 * 1024;
+        torrent.pieces = vec![[1; 20]; 4096];
+        torrent.piece_length = 16 * KI;
+        let small = torrent.overhead_estimate();
+
+        torrent.pieces = vec![[1; 20]; 16];
+        torrent.piece_length = 4 * 1024 * KI;
+        let large = torrent.overhead_estimate();
+
+        assert_eq!(small.num_blocks, large.num_blocks);
+        assert_eq!(
+            small.metadata_bytes - large.metadata_bytes,
+            (4096 - 16) * 20
+        );
+        assert!(small.ratio() > large.ratio());
+    }
+
+    #[test]
+    fn overhead_estimate_ratio() {
+        let estimate = OverheadEstimate {
+            length: 1000,
+            num_pieces: 1,
+            num_blocks: 1,
+            message_bytes: 39,
+            metadata_bytes: 61,
+        };
+        assert_eq!(estimate.total(), 100);
+        assert_eq!(estimate.ratio(), 0.1);
+
+        let estimate = OverheadEstimate {
+            length: 0,
+            ..estimate
+        };
+        assert_eq!(estimate.ratio(), f64::INFINITY);
+
+        let estimate = OverheadEstimate {
+            message_bytes: 0,
+            metadata_bytes: 0,
+            ..estimate
+        };
+        assert_eq!(estimate.ratio(), 0.0);
+
+        let estimate = OverheadEstimate {
+            length: 1000,
+            ..estimate
+        };
+        assert_eq!(estimate.ratio(), 0.0);
+
+        let estimate = OverheadEstimate {
+            message_bytes: u64::MAX,
+            ..estimate
+        };
+        assert_eq!(estimate.total(), u64::MAX);
+    }
+}
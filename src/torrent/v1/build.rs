@@ -2,10 +2,19 @@ use super::*;
 use crate::util;
 use rayon::prelude::*;
 use sha1::{Digest, Sha1};
+use std::collections::HashSet;
 use std::io::{BufReader, Read, Seek};
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
+const DEFAULT_CHUNK_SIZE: usize = 128 * 1024; // 128 KiB
+
+// Bump this whenever the meaning of an existing key in
+// `TorrentBuilder::to_bencode()` changes. New keys don't need a bump.
+const BUILDER_CONFIG_VERSION: Integer = 1;
+const MAX_PREALLOCATED_PIECES: usize = 64 * 1024;
+const DEFAULT_CHECKPOINT_INTERVAL: usize = 1024;
+
 impl TorrentBuilder {
     /// Create a new `TorrentBuilder` with required fields set.
     ///
@@ -30,7 +39,12 @@ impl TorrentBuilder {
     /// Build a `Torrent` from this `TorrentBuilder`.
     ///
     /// If `name` is not set, then the [last component] of `path`
-    /// will be used as the `Torrent`'s `name` field.
+    /// will be used as the `Torrent`'s `name` field. Note that this is
+    /// the last component of `path` *as given*, not of its canonicalized
+    /// form. For example, if `path` is `current` and `current` is a symlink
+    /// to `releases/v1.2.3`, then `name` will be `current`. Only if `path`
+    /// ends in `.` or `..` (which have no meaningful name) will the last component
+    /// of the canonicalized path (see [`resolved_root()`]) be used instead.
     ///
     /// `build()` **does not** provide comprehensive validation of
     /// any input. Basic cases such as setting `announce` to
@@ -40,34 +54,63 @@ impl TorrentBuilder {
     /// has to ensure that the values given to a `TorrentBuilder`
     /// are valid.
     ///
+    /// When building from a directory, all files are listed and checked before
+    /// any of them is hashed. Files with serious issues (e.g. ones that vanished
+    /// or can't be read) fail the build by default, see [`set_file_issue_policy()`].
+    /// Use [`build_with_report()`] to get a list of all issues found.
+    ///
     /// [last component]: https://doc.rust-lang.org/std/path/struct.Path.html#method.file_name
+    /// [`resolved_root()`]: #method.resolved_root
+    /// [`set_file_issue_policy()`]: #method.set_file_issue_policy
+    /// [`build_with_report()`]: #method.build_with_report
     pub fn build(self) -> Result<Torrent, LavaTorrentError> {
+        self.build_into_report(&mut BuildReport::default())
+    }
+
+    /// Same as [`build()`], but also return a [`BuildReport`] listing
+    /// every problem found with the files, whether the build succeeds or not.
+    ///
+    /// Problems are only looked for when building from a directory: entries
+    /// are all listed and checked before hashing starts, and every problem
+    /// found is recorded instead of aborting at the first one. If any of
+    /// them is serious (see [`FileIssue::is_serious()`]), then the build
+    /// either fails with all of them in its error message, or leaves the
+    /// affected files out, depending on [`set_file_issue_policy()`].
+    ///
+    /// Errors that occur afterwards (e.g. while hashing) are returned
+    /// as is and are not part of the report.
+    ///
+    /// [`build()`]: #method.build
+    /// [`BuildReport`]: struct.BuildReport.html
+    /// [`FileIssue::is_serious()`]: enum.FileIssue.html#method.is_serious
+    /// [`set_file_issue_policy()`]: #method.set_file_issue_policy
+    pub fn build_with_report(self) -> (Result<Torrent, LavaTorrentError>, BuildReport) {
+        let mut report = BuildReport::default();
+        let torrent = self.build_into_report(&mut report);
+        (torrent, report)
+    }
+
+    fn build_into_report(mut self, report: &mut BuildReport) -> Result<Torrent, LavaTorrentError> {
+        // normalize before validating
+        self.dedupe_trackers();
+
         // delegate validation to other methods
         self.validate_announce()?;
         self.validate_announce_list()?;
         self.validate_name()?;
         self.validate_path()?;
+        self.validate_layout()?;
         self.validate_piece_length()?;
         self.validate_extra_fields()?;
         self.validate_extra_info_fields()?;
 
-        // canonicalize path as it can be neither absolute nor canonicalized
-        let canonicalized_path = self.path.canonicalize()?;
-
-        // if `name` is not yet set, set it to the last component of `path`
-        let name = if let Some(name) = self.name {
-            name
-        } else {
-            util::last_component(&self.path)?
-        };
+        // canonicalize path as it can be neither absolute nor canonicalized,
+        // and derive `name` from it if `name` is not yet set
+        let (canonicalized_path, name, wrapped_file) = self.resolve_layout(report)?;
+        let mut pins = FilePins::new(self.secure_open, &canonicalized_path)?;
 
         // set `private = 1` in `info` if the torrent is private
-        let mut extra_info_fields = self.extra_info_fields;
-        if self.is_private {
-            extra_info_fields
-                .get_or_insert_with(HashMap::new)
-                .insert("private".to_owned(), BencodeElem::Integer(1));
-        }
+        let private = if self.is_private { Some(true) } else { None };
 
         // determine the # of threads to use
         let num_threads = if self.num_threads == 0 {
@@ -75,16 +118,60 @@ impl TorrentBuilder {
         } else {
             self.num_threads
         };
+        let chunk_size = if self.chunk_size == 0 {
+            DEFAULT_CHUNK_SIZE
+        } else {
+            self.chunk_size
+        };
+        let pipeline_depth = if self.pipeline_depth == 0 {
+            num_threads * 2
+        } else {
+            self.pipeline_depth
+        };
 
         // delegate the actual file reading to other methods
         if canonicalized_path.metadata()?.is_dir() {
-            let (length, files, pieces) = if num_threads == 1 {
-                Self::read_dir(canonicalized_path, self.piece_length)?
-            } else {
-                Self::read_dir_parallel(canonicalized_path, self.piece_length, num_threads)?
+            let entries = Self::walk_dir(
+                &canonicalized_path,
+                !self.preserve_symlinks,
+                self.file_issue_policy,
+                &|| Ok(()),
+                report,
+                &mut pins,
+            )?;
+            let entries = self.padded_entries(entries)?;
+            if self.keep_piece_map {
+                report.piece_map = Some(Self::named_piece_map(&entries, self.piece_length)?);
+            }
+            let (length, mut files, pieces) = match self.resolved_strategy(num_threads, true) {
+                HashingStrategy::Sequential => Self::read_dir(
+                    &canonicalized_path,
+                    entries,
+                    self.piece_length,
+                    chunk_size,
+                    &pins,
+                )?,
+                HashingStrategy::Pipelined => Self::read_dir_pipelined(
+                    &canonicalized_path,
+                    entries,
+                    self.piece_length,
+                    chunk_size,
+                    num_threads,
+                    pipeline_depth,
+                )?,
+                _ => Self::read_dir_parallel(
+                    &canonicalized_path,
+                    entries,
+                    self.piece_length,
+                    chunk_size,
+                    num_threads,
+                )?,
             };
+            if self.preserve_symlinks {
+                Self::record_symlinks(&canonicalized_path, &mut files)?;
+            }
 
-            Ok(Torrent {
+            Self::check_accounting(Torrent {
                 announce: self.announce,
                 announce_list: self.announce_list,
                 length,
@@ -92,26 +179,49 @@ impl TorrentBuilder {
                 name,
                 piece_length: self.piece_length,
                 pieces,
+                private,
                 extra_fields: self.extra_fields,
-                extra_info_fields,
+                extra_info_fields: self.extra_info_fields,
             })
         } else {
-            let (length, pieces) = if num_threads == 1 {
-                Self::read_file(canonicalized_path, self.piece_length)?
-            } else {
-                Self::read_file_parallel(canonicalized_path, self.piece_length, num_threads)?
+            if self.keep_piece_map {
+                let entries = [(
+                    canonicalized_path.clone(),
+                    canonicalized_path.metadata()?.len(),
+                )];
+                report.piece_map = Some(Self::named_piece_map(&entries, self.piece_length)?);
+            }
+            pins.pin_root_file();
+            let (length, pieces) = match self.resolved_strategy(num_threads, false) {
+                HashingStrategy::Sequential => {
+                    Self::read_file(canonicalized_path, self.piece_length, chunk_size, &pins)?
+                }
+                HashingStrategy::Pipelined => Self::read_file_pipelined(
+                    canonicalized_path,
+                    self.piece_length,
+                    chunk_size,
+                    num_threads,
+                    pipeline_depth,
+                )?,
+                _ => Self::read_file_parallel(
+                    canonicalized_path,
+                    self.piece_length,
+                    chunk_size,
+                    num_threads,
+                )?,
             };
 
-            Ok(Torrent {
+            Self::check_accounting(Torrent {
                 announce: self.announce,
                 announce_list: self.announce_list,
                 length,
-                files: None,
+                files: Self::wrap_file(wrapped_file, length),
                 name,
                 piece_length: self.piece_length,
                 pieces,
+                private,
                 extra_fields: self.extra_fields,
-                extra_info_fields,
+                extra_info_fields: self.extra_info_fields,
             })
         }
     }
@@ -135,33 +245,28 @@ impl TorrentBuilder {
     /// ```
     ///
     /// [`build()`]: #method.build
-    pub fn build_non_blocking(self) -> Result<TorrentBuild, LavaTorrentError> {
+    pub fn build_non_blocking(mut self) -> Result<TorrentBuild, LavaTorrentError> {
+        // normalize before validating
+        self.dedupe_trackers();
+
         // delegate validation to other methods
         self.validate_announce()?;
         self.validate_announce_list()?;
         self.validate_name()?;
         self.validate_path()?;
+        self.validate_layout()?;
         self.validate_piece_length()?;
         self.validate_extra_fields()?;
         self.validate_extra_info_fields()?;
 
-        // canonicalize path as it can be neither absolute nor canonicalized
-        let canonicalized_path = self.path.canonicalize()?;
-
-        // if `name` is not yet set, set it to the last component of `path`
-        let name = if let Some(name) = self.name {
-            name
-        } else {
-            util::last_component(&self.path)?
-        };
+        // canonicalize path as it can be neither absolute nor canonicalized,
+        // and derive `name` from it if `name` is not yet set
+        let (canonicalized_path, name, wrapped_file) =
+            self.resolve_layout(&mut BuildReport::default())?;
+        let mut pins = FilePins::new(self.secure_open, &canonicalized_path)?;
 
         // set `private = 1` in `info` if the torrent is private
-        let mut extra_info_fields = self.extra_info_fields;
-        if self.is_private {
-            extra_info_fields
-                .get_or_insert_with(HashMap::new)
-                .insert("private".to_owned(), BencodeElem::Integer(1));
-        }
+        let private = if self.is_private { Some(true) } else { None };
 
         // determine the # of threads to use
         let num_threads = if self.num_threads == 0 {
@@ -169,6 +274,16 @@ impl TorrentBuilder {
         } else {
             self.num_threads
         };
+        let chunk_size = if self.chunk_size == 0 {
+            DEFAULT_CHUNK_SIZE
+        } else {
+            self.chunk_size
+        };
+        let pipeline_depth = if self.pipeline_depth == 0 {
+            num_threads * 2
+        } else {
+            self.pipeline_depth
+        };
 
         // have another thread handle IO and hashing so that the current thread won't block
         let n_piece_processed = Arc::new(AtomicU64::new(0));
@@ -183,22 +298,47 @@ impl TorrentBuilder {
 
         let builder_thread = std::thread::spawn(move || {
             if canonicalized_path.metadata()?.is_dir() {
-                let (length, files, pieces) = if num_threads == 1 {
-                    Self::read_dir_non_blocking(
-                        canonicalized_path,
+                let entries = Self::walk_dir(
+                    &canonicalized_path,
+                    !self.preserve_symlinks,
+                    self.file_issue_policy,
+                    &|| torrent_build_internal.check_canceled(),
+                    &mut BuildReport::default(),
+                    &mut pins,
+                )?;
+                let entries = self.padded_entries(entries)?;
+                let (length, mut files, pieces) = match self.resolved_strategy(num_threads, true) {
+                    HashingStrategy::Sequential => Self::read_dir_non_blocking(
+                        &canonicalized_path,
+                        entries,
                         self.piece_length,
+                        chunk_size,
+                        &pins,
                         torrent_build_internal,
-                    )?
-                } else {
-                    Self::read_dir_parallel_non_blocking(
-                        canonicalized_path,
+                    )?,
+                    HashingStrategy::Pipelined => Self::read_dir_pipelined_non_blocking(
+                        &canonicalized_path,
+                        entries,
                         self.piece_length,
+                        chunk_size,
                         num_threads,
+                        pipeline_depth,
                         torrent_build_internal,
-                    )?
+                    )?,
+                    _ => Self::read_dir_parallel_non_blocking(
+                        &canonicalized_path,
+                        entries,
+                        self.piece_length,
+                        chunk_size,
+                        num_threads,
+                        torrent_build_internal,
+                    )?,
                 };
+                if self.preserve_symlinks {
+                    Self::record_symlinks(&canonicalized_path, &mut files)?;
+                }
 
-                Ok(Torrent {
+                Self::check_accounting(Torrent {
                     announce: self.announce,
                     announce_list: self.announce_list,
                     length,
@@ -206,35 +346,48 @@ impl TorrentBuilder {
                     name,
                     piece_length: self.piece_length,
                     pieces,
+                    private,
                     extra_fields: self.extra_fields,
-                    extra_info_fields,
+                    extra_info_fields: self.extra_info_fields,
                 })
             } else {
-                let (length, pieces) = if num_threads == 1 {
-                    Self::read_file_non_blocking(
+                pins.pin_root_file();
+                let (length, pieces) = match self.resolved_strategy(num_threads, false) {
+                    HashingStrategy::Sequential => Self::read_file_non_blocking(
                         canonicalized_path,
                         self.piece_length,
+                        chunk_size,
+                        &pins,
                         torrent_build_internal,
-                    )?
-                } else {
-                    Self::read_file_parallel_non_blocking(
+                    )?,
+                    HashingStrategy::Pipelined => Self::read_file_pipelined_non_blocking(
+                        canonicalized_path,
+                        self.piece_length,
+                        chunk_size,
+                        num_threads,
+                        pipeline_depth,
+                        torrent_build_internal,
+                    )?,
+                    _ => Self::read_file_parallel_non_blocking(
                         canonicalized_path,
                         self.piece_length,
+                        chunk_size,
                         num_threads,
                         torrent_build_internal,
-                    )?
+                    )?,
                 };
 
-                Ok(Torrent {
+                Self::check_accounting(Torrent {
                     announce: self.announce,
                     announce_list: self.announce_list,
                     length,
-                    files: None,
+                    files: Self::wrap_file(wrapped_file, length),
                     name,
                     piece_length: self.piece_length,
                     pieces,
+                    private,
                     extra_fields: self.extra_fields,
-                    extra_info_fields,
+                    extra_info_fields: self.extra_info_fields,
                 })
             }
         });
@@ -247,6 +400,654 @@ impl TorrentBuilder {
         })
     }
 
+    /// Like [`build_non_blocking()`], but the progress is saved to
+    /// `checkpoint_path` so that an interrupted build can be resumed.
+    ///
+    /// Every [`set_checkpoint_interval()`] pieces, a checkpoint is written
+    /// to `checkpoint_path`. It contains the listed files (with their sizes
+    /// and modification times), the piece length, and the pieces hashed so
+    /// far. A checkpoint is written once more when all pieces are hashed.
+    ///
+    /// If `checkpoint_path` already exists, the files are checked against
+    /// the checkpoint, and hashing resumes from the first piece it doesn't
+    /// contain. If any file has been added, removed, resized, or modified
+    /// since (or the piece length has changed), the build fails with an
+    /// error saying so. Delete the checkpoint to start over in that case.
+    /// The resulting `Torrent` is the same as the one [`build()`] would produce.
+    ///
+    /// Pieces are always hashed sequentially, regardless of
+    /// [`set_hashing_strategy()`], so that they are completed in order.
+    /// The checkpoint is not removed when the build is done, so that
+    /// calling `build_resumable()` again returns without re-hashing.
+    ///
+    /// ```no_run
+    /// use lava_torrent::torrent::v1::TorrentBuilder;
+    /// use std::path::Path;
+    ///
+    /// let build = TorrentBuilder::new("dir/", 1048576)
+    ///     .build_resumable(Path::new("dir.checkpoint"))
+    ///     .unwrap();
+    ///
+    /// // if the process is killed (or `build` is canceled) now,
+    /// // running it again picks up where it left off
+    /// let torrent = build.get_output().unwrap();
+    /// torrent.write_into_file("sample.torrent").unwrap();
+    /// std::fs::remove_file("dir.checkpoint").unwrap();
+    /// ```
+    ///
+    /// [`build_non_blocking()`]: #method.build_non_blocking
+    /// [`set_checkpoint_interval()`]: #method.set_checkpoint_interval
+    /// [`build()`]: #method.build
+    /// [`set_hashing_strategy()`]: #method.set_hashing_strategy
+    pub fn build_resumable(
+        mut self,
+        checkpoint_path: &Path,
+    ) -> Result<TorrentBuild, LavaTorrentError> {
+        // normalize before validating
+        self.dedupe_trackers();
+
+        // delegate validation to other methods
+        self.validate_announce()?;
+        self.validate_announce_list()?;
+        self.validate_name()?;
+        self.validate_path()?;
+        self.validate_layout()?;
+        self.validate_piece_length()?;
+        self.validate_extra_fields()?;
+        self.validate_extra_info_fields()?;
+
+        let (canonicalized_path, name, wrapped_file) =
+            self.resolve_layout(&mut BuildReport::default())?;
+        let mut pins = FilePins::new(self.secure_open, &canonicalized_path)?;
+        let private = if self.is_private { Some(true) } else { None };
+        let chunk_size = if self.chunk_size == 0 {
+            DEFAULT_CHUNK_SIZE
+        } else {
+            self.chunk_size
+        };
+        let checkpoint_interval = if self.checkpoint_interval == 0 {
+            DEFAULT_CHECKPOINT_INTERVAL
+        } else {
+            self.checkpoint_interval
+        };
+        let checkpoint_path = checkpoint_path.to_path_buf();
+
+        let n_piece_processed = Arc::new(AtomicU64::new(0));
+        let n_piece_total = Arc::new(AtomicU64::new(0));
+        let is_canceled = Arc::new(AtomicBool::new(false));
+
+        let torrent_build_internal = TorrentBuildInternal {
+            n_piece_processed: n_piece_processed.clone(),
+            n_piece_total: n_piece_total.clone(),
+            is_canceled: is_canceled.clone(),
+        };
+
+        let builder_thread = std::thread::spawn(move || {
+            let metadata = canonicalized_path.metadata()?;
+            let entries = if metadata.is_dir() {
+                let entries = Self::walk_dir(
+                    &canonicalized_path,
+                    !self.preserve_symlinks,
+                    self.file_issue_policy,
+                    &|| torrent_build_internal.check_canceled(),
+                    &mut BuildReport::default(),
+                    &mut pins,
+                )?;
+                self.padded_entries(entries)?
+            } else {
+                pins.pin_root_file();
+                vec![(canonicalized_path.clone(), metadata.len())]
+            };
+
+            let manifest =
+                BuildCheckpoint::manifest(&canonicalized_path, &entries, !self.preserve_symlinks)?;
+            let checkpoint = match BuildCheckpoint::load(&checkpoint_path)? {
+                Some(checkpoint) => {
+                    checkpoint.check(&checkpoint_path, &manifest, self.piece_length)?;
+                    checkpoint
+                }
+                None => BuildCheckpoint {
+                    piece_length: self.piece_length,
+                    manifest,
+                    pieces: Vec::new(),
+                },
+            };
+
+            let pieces = Self::read_entries_resumable(
+                &entries,
+                checkpoint,
+                &checkpoint_path,
+                checkpoint_interval,
+                chunk_size,
+                &pins,
+                &torrent_build_internal,
+            )?;
+            let length = util::u64_to_i64(Self::total_length(&entries)?)?;
+            let files = if metadata.is_dir() {
+                let mut files = Self::entries_to_files(&canonicalized_path, &entries)?;
+                if self.preserve_symlinks {
+                    Self::record_symlinks(&canonicalized_path, &mut files)?;
+                }
+                Some(files)
+            } else {
+                Self::wrap_file(wrapped_file, length)
+            };
+
+            Self::check_accounting(Torrent {
+                announce: self.announce,
+                announce_list: self.announce_list,
+                length,
+                files,
+                name,
+                piece_length: self.piece_length,
+                pieces,
+                private,
+                extra_fields: self.extra_fields,
+                extra_info_fields: self.extra_info_fields,
+            })
+        });
+
+        Ok(TorrentBuild {
+            n_piece_processed,
+            n_piece_total,
+            is_canceled,
+            builder_thread: Some(builder_thread),
+        })
+    }
+
+    /// Validate `self` and list the files like [`build()`] does,
+    /// but without reading or hashing any of them.
+    ///
+    /// The returned `DryRunTorrent` contains the same files (in the
+    /// same order) and the same # of pieces as the `Torrent` that
+    /// [`build()`] would produce. This is useful for showing the user
+    /// what a torrent would look like before committing to hashing it.
+    ///
+    /// [`build()`]: #method.build
+    pub fn dry_run(&self) -> Result<DryRunTorrent, LavaTorrentError> {
+        let mut this = self.clone();
+        this.dedupe_trackers();
+        let entries = this.list_entries()?;
+        let (canonicalized_path, name, wrapped_file) =
+            self.resolve_layout(&mut BuildReport::default())?;
+
+        let (length, files) = if canonicalized_path.metadata()?.is_dir() {
+            let entries = entries
+                .into_iter()
+                .map(|(path, length)| Ok((path, util::i64_to_u64(length)?)))
+                .collect::<Result<Vec<(PathBuf, u64)>, LavaTorrentError>>()?;
+            // paths in `entries` are already relative, and padded
+            let mut files = Self::entries_to_files("", &entries)?;
+            if self.preserve_symlinks {
+                Self::record_symlinks(&canonicalized_path, &mut files)?;
+            }
+            (files.iter().map(|file| file.length).sum(), Some(files))
+        } else {
+            let length = entries.iter().map(|&(_, length)| length).sum();
+            (length, Self::wrap_file(wrapped_file, length))
+        };
+
+        Ok(DryRunTorrent {
+            announce: this.announce,
+            announce_list: this.announce_list,
+            length,
+            files,
+            name,
+            piece_length: self.piece_length,
+            piece_count: Self::piece_count(length, self.piece_length)?,
+            private: if self.is_private { Some(true) } else { None },
+            extra_fields: self.extra_fields.clone(),
+            extra_info_fields: self.extra_info_fields.clone(),
+        })
+    }
+
+    /// Build a `Torrent` only if the content under `path` has changed
+    /// since `previous` was taken.
+    ///
+    /// This is meant for polling a directory on the caller's own schedule:
+    /// `None` is returned without hashing anything if the fingerprint of
+    /// `path` (see [`Torrent::content_fingerprint()`]) matches `previous`.
+    /// Otherwise, the built `Torrent` is returned along with the new
+    /// fingerprint, which should be passed as `previous` next time.
+    ///
+    /// Only the content is considered, i.e. changing other settings of
+    /// `self` (e.g. `announce`) does not cause a rebuild. The fingerprint is
+    /// taken before building, so changes made while building are picked
+    /// up by the next call.
+    ///
+    /// [`Torrent::content_fingerprint()`]: struct.Torrent.html#method.content_fingerprint
+    pub fn build_if_changed(
+        &self,
+        previous: Option<&ContentFingerprint>,
+    ) -> Result<Option<(Torrent, ContentFingerprint)>, LavaTorrentError> {
+        self.validate_path()?;
+        let fingerprint = ContentFingerprint::compute(
+            &self.resolved_root()?,
+            !self.preserve_symlinks,
+            self.file_issue_policy,
+        )?;
+
+        if previous.is_some_and(|previous| previous.matches(&fingerprint)) {
+            Ok(None)
+        } else {
+            Ok(Some((self.clone().build()?, fingerprint)))
+        }
+    }
+
+    /// Re-hash the content of `source` with a different piece length.
+    ///
+    /// Only `piece_length` and `pieces` differ in the returned `Torrent`,
+    /// everything else (e.g. `name`, the files and their order, trackers,
+    /// and extra fields) is copied from `source` as is. `content_path` is
+    /// where the content of `source` is: the file itself if `source`
+    /// has a single file, or the directory containing `files` otherwise.
+    ///
+    /// The content is read only once and is checked against the pieces
+    /// of `source` while being read, so a file that has changed since
+    /// `source` was created (e.g. due to bit rot) is caught instead of
+    /// silently ending up in the new pieces. In that case `Err(error)`
+    /// is returned with the index of the first mismatching piece. Padding
+    /// files (see [BEP 47]) are not read, as their content is all zeros.
+    ///
+    /// `new_piece_length` must be valid in the same way as
+    /// the `piece_length` given to [`new()`].
+    ///
+    /// [BEP 47]: http://bittorrent.org/beps/bep_0047.html
+    /// [`new()`]: #method.new
+    pub fn rebuild_with_piece_length<P>(
+        source: &Torrent,
+        content_path: P,
+        new_piece_length: Integer,
+    ) -> Result<Torrent, LavaTorrentError>
+    where
+        P: AsRef<Path>,
+    {
+        let content_path = content_path.as_ref();
+        TorrentBuilder::new(content_path, new_piece_length).validate_piece_length()?;
+        let entries = source.content_entries(content_path)?;
+
+        let mut old = PieceHasher::new(util::i64_to_u64(source.piece_length)?);
+        let mut new = PieceHasher::new(util::i64_to_u64(new_piece_length)?);
+        let mut buf = vec![0; DEFAULT_CHUNK_SIZE];
+        let mut n_verified = 0;
+        let mut verify = |pieces: &[Piece]| {
+            for (index, piece) in pieces.iter().enumerate().skip(n_verified) {
+                if source.pieces.get(index) != Some(piece) {
+                    return Err(LavaTorrentError::TorrentBuilderFailure(Cow::Owned(
+                        format!("Piece {} does not match the source torrent.", index),
+                    )));
+                }
+            }
+            n_verified = pieces.len();
+            Ok(())
+        };
+
+        for (path, length) in entries {
+            let mut remaining = length;
+            let mut file = match path {
+                Some(ref path) => {
+                    let file = std::fs::File::open(path)?;
+                    let actual = file.metadata()?.len();
+                    if actual != remaining {
+                        return Err(LavaTorrentError::TorrentBuilderFailure(Cow::Owned(
+                            format!(
+                                "[{}] is {} bytes long, {} expected.",
+                                path.display(),
+                                actual,
+                                remaining
+                            ),
+                        )));
+                    }
+                    Some(file)
+                }
+                None => {
+                    buf.fill(0);
+                    None
+                }
+            };
+
+            while remaining > 0 {
+                let chunk_len = util::u64_to_usize(remaining).unwrap_or(usize::MAX);
+                let chunk = &mut buf[..chunk_len.min(DEFAULT_CHUNK_SIZE)];
+                if let Some(ref mut file) = file {
+                    file.read_exact(chunk)?;
+                }
+
+                old.update(chunk)?;
+                new.update(chunk)?;
+                verify(&old.pieces)?;
+                remaining -= util::usize_to_u64(chunk.len())?;
+            }
+        }
+        verify(&old.finish())?;
+
+        Ok(Torrent {
+            announce: source.announce.clone(),
+            announce_list: source.announce_list.clone(),
+            length: source.length,
+            files: source.files.clone(),
+            name: source.name.clone(),
+            piece_length: new_piece_length,
+            pieces: new.finish(),
+            private: source.private,
+            extra_fields: source.extra_fields.clone(),
+            extra_info_fields: source.extra_info_fields.clone(),
+        })
+    }
+
+    pub(crate) fn build_each(
+        path: &Path,
+        template: &TorrentBuilder,
+        options: &BuildEachOptions,
+    ) -> Result<Vec<BuiltItem>, LavaTorrentError> {
+        let mut items = Vec::new();
+        for entry in path.read_dir()? {
+            let item = entry?.path();
+            if util::last_component(&item)?.starts_with('.') {
+                continue;
+            } // hidden files/dirs are ignored
+            if !options.include_files && !item.is_dir() {
+                continue;
+            }
+            if options.skip.as_ref().is_some_and(|skip| skip(&item)) {
+                continue;
+            }
+            items.push(item);
+        }
+        items.sort();
+
+        let num_threads = if options.num_threads == 0 {
+            num_cpus::get_physical()
+        } else {
+            options.num_threads
+        };
+        let thread_pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(num_threads)
+            .build()
+            .map_err(|e| {
+                LavaTorrentError::TorrentBuilderFailure(Cow::Owned(format!(
+                    "failed to create rayon thread pool: {}",
+                    e
+                )))
+            })?;
+
+        Ok(thread_pool.install(|| {
+            items
+                .into_par_iter()
+                .map(|item| {
+                    let torrent = util::last_component(&item).and_then(|name| {
+                        template
+                            .clone()
+                            .set_path(&item)
+                            .set_name(name)
+                            .set_num_threads(1)
+                            .build()
+                    });
+                    (item, torrent)
+                })
+                .collect()
+        }))
+    }
+
+    /// Serialize the configuration of this `TorrentBuilder` (i.e. every
+    /// setting, including `path` and the extra fields) into a bencode
+    /// dictionary, so that it can be stored and later restored with
+    /// [`from_bencode()`].
+    ///
+    /// The dictionary contains a `config version` key. Settings added
+    /// in the future will be optional, so configs serialized by this
+    /// version of the crate keep loading with defaults for them.
+    ///
+    /// `path` is stored as UTF-8, with invalid sequences replaced.
+    /// Use the `path_override` of [`from_bencode()`] for paths that are
+    /// not valid UTF-8. Settings that can't be serialized (e.g. closures)
+    /// are not supported by `TorrentBuilder` for this reason.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lava_torrent::bencode::BencodeElem;
+    /// use lava_torrent::torrent::v1::TorrentBuilder;
+    ///
+    /// let builder = TorrentBuilder::new("dir/", 1048576).set_privacy(true);
+    /// let job = builder.to_bencode().encode();
+    ///
+    /// // later, possibly on another machine
+    /// let config = BencodeElem::from_bytes(job).unwrap().remove(0);
+    /// let restored = TorrentBuilder::from_bencode(config, None).unwrap();
+    /// assert_eq!(restored, builder);
+    /// ```
+    ///
+    /// [`from_bencode()`]: #method.from_bencode
+    pub fn to_bencode(&self) -> BencodeElem {
+        let mut dict = HashMap::new();
+        let mut insert = |key: &str, val| {
+            dict.insert(key.to_owned(), val);
+        };
+        let integer = |n: usize| BencodeElem::Integer(Integer::try_from(n).unwrap_or(Integer::MAX));
+
+        insert(
+            "config version",
+            BencodeElem::Integer(BUILDER_CONFIG_VERSION),
+        );
+        if let Some(ref announce) = self.announce {
+            insert("announce", BencodeElem::String(announce.clone()));
+        }
+        if let Some(ref announce_list) = self.announce_list {
+            insert(
+                "announce list",
+                BencodeElem::List(
+                    announce_list
+                        .iter()
+                        .map(|tier| {
+                            BencodeElem::List(
+                                tier.iter().cloned().map(BencodeElem::String).collect(),
+                            )
+                        })
+                        .collect(),
+                ),
+            );
+        }
+        if let Some(ref name) = self.name {
+            insert("name", BencodeElem::String(name.clone()));
+        }
+        insert(
+            "path",
+            BencodeElem::String(self.path.to_string_lossy().into_owned()),
+        );
+        insert("piece length", BencodeElem::Integer(self.piece_length));
+        if let Some(ref extra_fields) = self.extra_fields {
+            insert(
+                "extra fields",
+                BencodeElem::Dictionary(extra_fields.clone()),
+            );
+        }
+        if let Some(ref extra_info_fields) = self.extra_info_fields {
+            insert(
+                "extra info fields",
+                BencodeElem::Dictionary(extra_info_fields.clone()),
+            );
+        }
+        insert("private", BencodeElem::Integer(self.is_private.into()));
+        insert(
+            "preserve symlinks",
+            BencodeElem::Integer(self.preserve_symlinks.into()),
+        );
+        insert(
+            "dedupe trackers",
+            BencodeElem::Integer(self.dedupe_trackers.into()),
+        );
+        insert(
+            "keep piece map",
+            BencodeElem::Integer(self.keep_piece_map.into()),
+        );
+        insert(
+            "hybrid compatible layout",
+            BencodeElem::Integer(self.hybrid_compatible_layout.into()),
+        );
+        insert(
+            "force multi file",
+            BencodeElem::Integer(self.force_multi_file.into()),
+        );
+        insert(
+            "force single file",
+            BencodeElem::Integer(self.force_single_file.into()),
+        );
+        insert(
+            "file issue policy",
+            BencodeElem::String(
+                match self.file_issue_policy {
+                    FileIssuePolicy::Fail => "fail",
+                    FileIssuePolicy::Skip => "skip",
+                }
+                .to_owned(),
+            ),
+        );
+        insert(
+            "hashing strategy",
+            BencodeElem::String(
+                match self.hashing_strategy {
+                    HashingStrategy::Auto => "auto",
+                    HashingStrategy::Sequential => "sequential",
+                    HashingStrategy::Parallel => "parallel",
+                    HashingStrategy::Pipelined => "pipelined",
+                }
+                .to_owned(),
+            ),
+        );
+        insert("num threads", integer(self.num_threads));
+        insert("chunk size", integer(self.chunk_size));
+        insert("pipeline depth", integer(self.pipeline_depth));
+        insert("checkpoint interval", integer(self.checkpoint_interval));
+        insert("secure open", BencodeElem::Integer(self.secure_open.into()));
+
+        BencodeElem::Dictionary(dict)
+    }
+
+    /// Restore a `TorrentBuilder` from a config serialized by [`to_bencode()`].
+    ///
+    /// If `path_override` is given, it is used as `path` instead of
+    /// the one in `config` (which may then be absent).
+    ///
+    /// Settings missing from `config` are set to their defaults. Unknown keys
+    /// are ignored. An error is returned if `config` is malformed or if its
+    /// `config version` is newer than what this version of the crate supports.
+    ///
+    /// [`to_bencode()`]: #method.to_bencode
+    pub fn from_bencode(
+        config: BencodeElem,
+        path_override: Option<PathBuf>,
+    ) -> Result<TorrentBuilder, LavaTorrentError> {
+        let mut dict = match config {
+            BencodeElem::Dictionary(dict) => dict,
+            other => {
+                return Err(LavaTorrentError::InvalidArgument(Cow::Owned(format!(
+                    "Builder config is a {}, expected Dictionary.",
+                    other.kind()
+                ))));
+            }
+        };
+
+        match Self::extract_config_integer(&mut dict, "config version")? {
+            Some(version) if version > BUILDER_CONFIG_VERSION => {
+                return Err(LavaTorrentError::InvalidArgument(Cow::Owned(format!(
+                    "Builder config version {} is newer than the supported version {}.",
+                    version, BUILDER_CONFIG_VERSION
+                ))));
+            }
+            Some(_) => (),
+            None => {
+                return Err(LavaTorrentError::InvalidArgument(Cow::Borrowed(
+                    r#""config version" does not exist."#,
+                )));
+            }
+        }
+
+        let path = match (
+            path_override,
+            Self::extract_config_string(&mut dict, "path")?,
+        ) {
+            (Some(path), _) => path,
+            (None, Some(path)) => PathBuf::from(path),
+            (None, None) => {
+                return Err(LavaTorrentError::InvalidArgument(Cow::Borrowed(
+                    r#""path" does not exist."#,
+                )));
+            }
+        };
+        let piece_length = match Self::extract_config_integer(&mut dict, "piece length")? {
+            Some(piece_length) => piece_length,
+            None => {
+                return Err(LavaTorrentError::InvalidArgument(Cow::Borrowed(
+                    r#""piece length" does not exist."#,
+                )));
+            }
+        };
+
+        let file_issue_policy =
+            match Self::extract_config_string(&mut dict, "file issue policy")?.as_deref() {
+                None | Some("fail") => FileIssuePolicy::Fail,
+                Some("skip") => FileIssuePolicy::Skip,
+                Some(other) => {
+                    return Err(LavaTorrentError::InvalidArgument(Cow::Owned(format!(
+                        r#""file issue policy" [{}] is not a known policy."#,
+                        other
+                    ))));
+                }
+            };
+        let hashing_strategy =
+            match Self::extract_config_string(&mut dict, "hashing strategy")?.as_deref() {
+                None | Some("auto") => HashingStrategy::Auto,
+                Some("sequential") => HashingStrategy::Sequential,
+                Some("parallel") => HashingStrategy::Parallel,
+                Some("pipelined") => HashingStrategy::Pipelined,
+                Some(other) => {
+                    return Err(LavaTorrentError::InvalidArgument(Cow::Owned(format!(
+                        r#""hashing strategy" [{}] is not a known strategy."#,
+                        other
+                    ))));
+                }
+            };
+
+        Ok(TorrentBuilder {
+            announce: Self::extract_config_string(&mut dict, "announce")?,
+            announce_list: Self::extract_config_announce_list(&mut dict)?,
+            name: Self::extract_config_string(&mut dict, "name")?,
+            path,
+            piece_length,
+            extra_fields: Self::extract_config_dictionary(&mut dict, "extra fields")?,
+            extra_info_fields: Self::extract_config_dictionary(&mut dict, "extra info fields")?,
+            is_private: Self::extract_config_bool(&mut dict, "private")?,
+            preserve_symlinks: Self::extract_config_bool(&mut dict, "preserve symlinks")?,
+            dedupe_trackers: Self::extract_config_bool(&mut dict, "dedupe trackers")?,
+            keep_piece_map: Self::extract_config_bool(&mut dict, "keep piece map")?,
+            hybrid_compatible_layout: Self::extract_config_bool(
+                &mut dict,
+                "hybrid compatible layout",
+            )?,
+            force_multi_file: Self::extract_config_bool(&mut dict, "force multi file")?,
+            force_single_file: Self::extract_config_bool(&mut dict, "force single file")?,
+            file_issue_policy,
+            hashing_strategy,
+            num_threads: Self::extract_config_usize(&mut dict, "num threads")?,
+            chunk_size: Self::extract_config_usize(&mut dict, "chunk size")?,
+            pipeline_depth: Self::extract_config_usize(&mut dict, "pipeline depth")?,
+            checkpoint_interval: Self::extract_config_usize(&mut dict, "checkpoint interval")?,
+            secure_open: Self::extract_config_bool(&mut dict, "secure open")?,
+        })
+    }
+
+    /// Return the path that will actually be read when building, i.e.
+    /// the canonicalized form of `path` with all symlinks resolved.
+    ///
+    /// This is useful for checking what a (potentially symlinked)
+    /// `path` points to before calling [`build()`].
+    ///
+    /// [`build()`]: #method.build
+    pub fn resolved_root(&self) -> Result<PathBuf, LavaTorrentError> {
+        Ok(self.path.canonicalize()?)
+    }
+
     /// Set the `announce` field of the `Torrent` to be built.
     ///
     /// Calling this method multiple times will simply override previous settings.
@@ -284,6 +1085,10 @@ impl TorrentBuilder {
     /// this method does not validate its value. If `name`
     /// turns out to be invalid, calling [`build()`] later will fail.
     ///
+    /// NOTE: **A valid `name` is not empty, `.`, or `..`, and contains
+    /// no path separator (`/` or `\`), drive letter prefix (e.g. `C:`),
+    /// or control characters.**
+    ///
     /// [`build()`]: #method.build
     pub fn set_name(self, name: String) -> TorrentBuilder {
         TorrentBuilder {
@@ -382,12 +1187,197 @@ impl TorrentBuilder {
         TorrentBuilder { is_private, ..self }
     }
 
-    /// Change the number of threads used when hashing pieces.
+    /// Preserve symlinks found under `path` instead of following them.
     ///
-    /// If set to 0, the number of threads used will be equal to the number
+    /// By default symlinks are followed, i.e. the content they point to
+    /// is included in the `Torrent` as if it were a regular file/dir. If this
+    /// is set to `true`, each symlink is instead recorded as a 0-length
+    /// file with `attr` containing `l` and `symlink path` set to the
+    /// symlink's target, as defined in [BEP 47]. Symlinks that are dangling
+    /// or point to something outside of `path` can't be recorded, and are
+    /// reported as a [`FileIssue`] (i.e. [`build()`] fails or leaves them
+    /// out, depending on [`set_file_issue_policy()`]).
+    ///
+    /// Note that if `path` itself is a symlink, it is always followed.
+    ///
+    /// Calling this method multiple times will simply override previous settings.
+    ///
+    /// [BEP 47]: http://bittorrent.org/beps/bep_0047.html
+    /// [`FileIssue`]: enum.FileIssue.html
+    /// [`build()`]: #method.build
+    /// [`set_file_issue_policy()`]: #method.set_file_issue_policy
+    pub fn set_preserve_symlinks(self, preserve_symlinks: bool) -> TorrentBuilder {
+        TorrentBuilder {
+            preserve_symlinks,
+            ..self
+        }
+    }
+
+    /// Remove redundant trackers when building.
+    ///
+    /// If set to `true`, `announce` and `announce_list` are normalized
+    /// before being validated:
+    /// - Duplicate URLs within a tier are removed (the first one is kept).
+    /// - Tiers that are empty are removed.
+    /// - `announce_list` is dropped if it ends up empty, or if it ends up
+    ///   containing a single tier with only the `announce` URL (which is
+    ///   what Deluge emits in that case).
+    ///
+    /// URLs are compared after normalizing them with
+    /// [`tracker::normalize_url()`](crate::tracker::normalize_url), so
+    /// `HTTP://a:80/` and `http://a` are duplicates. URLs that can't be
+    /// normalized are compared as-is after trimming surrounding whitespace.
+    ///
+    /// This is `false` by default.
+    ///
+    /// Calling this method multiple times will simply override previous settings.
+    pub fn set_dedupe_trackers(self, dedupe_trackers: bool) -> TorrentBuilder {
+        TorrentBuilder {
+            dedupe_trackers,
+            ..self
+        }
+    }
+
+    /// Decide what to do with files that have serious issues (e.g. ones
+    /// that vanished or can't be read) when building from a directory.
+    ///
+    /// With [`FileIssuePolicy::Fail`] (the default), the build fails with all
+    /// issues listed in its error. With [`FileIssuePolicy::Skip`], the affected
+    /// files are left out of the torrent. Either way, the issues can be
+    /// inspected with [`build_with_report()`].
+    ///
+    /// Calling this method multiple times will simply override previous settings.
+    ///
+    /// [`FileIssuePolicy::Fail`]: enum.FileIssuePolicy.html#variant.Fail
+    /// [`FileIssuePolicy::Skip`]: enum.FileIssuePolicy.html#variant.Skip
+    /// [`build_with_report()`]: #method.build_with_report
+    pub fn set_file_issue_policy(self, file_issue_policy: FileIssuePolicy) -> TorrentBuilder {
+        TorrentBuilder {
+            file_issue_policy,
+            ..self
+        }
+    }
+
+    /// Keep the piece map in the [`BuildReport`] returned by [`build_with_report()`].
+    ///
+    /// The map lists the chunks of files that make up each piece, which
+    /// helps tracking down the source of a bad piece (e.g. a file that was
+    /// modified during the build). It is the same layout [`Torrent::validate_files()`]
+    /// uses. Since it has an entry per piece, it is not kept by default.
+    ///
+    /// Calling this method multiple times will simply override previous settings.
+    ///
+    /// [`BuildReport`]: struct.BuildReport.html
+    /// [`build_with_report()`]: #method.build_with_report
+    /// [`Torrent::validate_files()`]: struct.Torrent.html#method.validate_files
+    pub fn set_keep_piece_map(self, keep_piece_map: bool) -> TorrentBuilder {
+        TorrentBuilder {
+            keep_piece_map,
+            ..self
+        }
+    }
+
+    /// Lay files out so that the torrent can be extended into a
+    /// [BEP 52] hybrid (v1 + v2) torrent later on.
+    ///
+    /// If set, [`build()`] guarantees that:
+    /// - every file starts at a piece boundary, i.e. a [BEP 47] padding file
+    ///   follows each file (except the last one) whose length is not a
+    ///   multiple of `piece_length`. Like libtorrent, the padding files are
+    ///   named `.pad/<length>` and have `attr` set to `p`.
+    /// - files are ordered by comparing their paths component by component,
+    ///   which is the order of the `file tree` in v2. For instance,
+    ///   `a/b` comes before `a-b` even though `/` > `-`.
+    /// - `piece_length` is at least 16 KiB, which is the minimum in v2.
+    ///   Otherwise [`build()`] fails.
+    ///
+    /// [`Torrent::is_hybrid_compatible_layout()`] tells whether a torrent
+    /// has such a layout. Single-file torrents need no padding, so this
+    /// only affects the piece length check for them.
+    ///
+    /// Calling this method multiple times will simply override previous settings.
+    ///
+    /// [BEP 52]: http://bittorrent.org/beps/bep_0052.html
+    /// [BEP 47]: http://bittorrent.org/beps/bep_0047.html
+    /// [`build()`]: #method.build
+    /// [`Torrent::is_hybrid_compatible_layout()`]: struct.Torrent.html#method.is_hybrid_compatible_layout
+    pub fn set_hybrid_compatible_layout(self, hybrid_compatible_layout: bool) -> TorrentBuilder {
+        TorrentBuilder {
+            hybrid_compatible_layout,
+            ..self
+        }
+    }
+
+    /// Build a multi-file torrent (i.e. with `files`) even if `path` is a
+    /// single file, for tools that only handle the multi-file layout.
+    ///
+    /// If set and `path` is a file, `files` lists that file alone, under
+    /// its own name (the [last component] of `path`, see [`build()`]).
+    /// `name`, i.e. the name of the directory containing it, is used as
+    /// is if set. Otherwise, it is the file's name without its extension
+    /// (e.g. `movie` for `movie.mkv`, `archive.tar` for `archive.tar.gz`).
+    ///
+    /// The file is hashed exactly like it would be for a single-file
+    /// torrent, so `pieces` are the same. The info hash differs though,
+    /// since `info` has `files` instead of `length`. This has no effect
+    /// if `path` is a directory.
+    ///
+    /// Cannot be combined with [`set_force_single_file()`].
+    /// Calling this method multiple times will simply override previous settings.
+    ///
+    /// [last component]: https://doc.rust-lang.org/std/path/struct.Path.html#method.file_name
+    /// [`build()`]: #method.build
+    /// [`set_force_single_file()`]: #method.set_force_single_file
+    pub fn set_force_multi_file(self, force_multi_file: bool) -> TorrentBuilder {
+        TorrentBuilder {
+            force_multi_file,
+            ..self
+        }
+    }
+
+    /// Build a single-file torrent (i.e. without `files`) even if `path`
+    /// is a directory, as long as it contains a single file.
+    ///
+    /// If set and `path` is a directory, building fails unless exactly one
+    /// file is found in it (after skipping hidden files, like a multi-file
+    /// build does). That file is then hashed as if `path` pointed to it, and
+    /// `name` defaults to the file's name. This has no effect if `path`
+    /// is a file.
+    ///
+    /// Cannot be combined with [`set_force_multi_file()`].
+    /// Calling this method multiple times will simply override previous settings.
+    ///
+    /// [`set_force_multi_file()`]: #method.set_force_multi_file
+    pub fn set_force_single_file(self, force_single_file: bool) -> TorrentBuilder {
+        TorrentBuilder {
+            force_single_file,
+            ..self
+        }
+    }
+
+    /// Change the size of the buffer used when reading files.
+    ///
+    /// Pieces are hashed incrementally as bytes are read, so a piece is
+    /// never loaded into memory in full. Instead, each hashing thread reads
+    /// (at most) `chunk_size` bytes at a time, which bounds memory usage
+    /// regardless of `piece_length`.
+    ///
+    /// Setting `chunk_size` to `0` restores the default (128 KiB).
+    ///
+    /// Calling this method multiple times will simply override previous settings.
+    pub fn set_chunk_size(self, chunk_size: usize) -> TorrentBuilder {
+        TorrentBuilder { chunk_size, ..self }
+    }
+
+    /// Change the number of threads used when hashing pieces.
+    ///
+    /// If set to 0, the number of threads used will be equal to the number
     /// of physical cores. **This is also the default behavior.**
     ///
-    /// Set this to 1 if you prefer single-threaded hashing.
+    /// Set this to 1 if you prefer single-threaded hashing. With
+    /// [`HashingStrategy::Pipelined`], an extra thread is used for reading.
+    ///
+    /// [`HashingStrategy::Pipelined`]: enum.HashingStrategy.html#variant.Pipelined
     pub fn set_num_threads(self, num_threads: usize) -> TorrentBuilder {
         TorrentBuilder {
             num_threads,
@@ -395,361 +1385,1104 @@ impl TorrentBuilder {
         }
     }
 
-    fn validate_announce(&self) -> Result<(), LavaTorrentError> {
-        match self.announce {
-            Some(ref announce) => {
-                if announce.is_empty() {
-                    Err(LavaTorrentError::TorrentBuilderFailure(Cow::Borrowed(
-                        "TorrentBuilder has `announce` but its length is 0.",
-                    )))
-                } else {
-                    Ok(())
-                }
-            }
-            None => Ok(()),
+    /// Change how pieces are read and hashed.
+    ///
+    /// All strategies produce the same `Torrent`; they only differ in
+    /// speed and memory usage. By default ([`HashingStrategy::Auto`]),
+    /// directories are hashed with [`HashingStrategy::Pipelined`] and single
+    /// files with [`HashingStrategy::Parallel`], unless [`set_num_threads()`]
+    /// is set to 1, in which case everything is hashed sequentially.
+    ///
+    /// Calling this method multiple times will simply override previous settings.
+    ///
+    /// [`HashingStrategy::Auto`]: enum.HashingStrategy.html#variant.Auto
+    /// [`HashingStrategy::Pipelined`]: enum.HashingStrategy.html#variant.Pipelined
+    /// [`HashingStrategy::Parallel`]: enum.HashingStrategy.html#variant.Parallel
+    /// [`set_num_threads()`]: #method.set_num_threads
+    pub fn set_hashing_strategy(self, hashing_strategy: HashingStrategy) -> TorrentBuilder {
+        TorrentBuilder {
+            hashing_strategy,
+            ..self
         }
     }
 
-    fn validate_announce_list(&self) -> Result<(), LavaTorrentError> {
-        if let Some(ref announce_list) = self.announce_list {
-            if announce_list.is_empty() {
-                return Err(LavaTorrentError::TorrentBuilderFailure(Cow::Borrowed(
-                    "TorrentBuilder has `announce_list` but it's empty.",
-                )));
-            } else {
-                for tier in announce_list {
-                    if tier.is_empty() {
-                        return Err(LavaTorrentError::TorrentBuilderFailure(Cow::Borrowed(
-                            "TorrentBuilder has `announce_list` but \
-                             one of its tiers is empty.",
-                        )));
-                    } else {
-                        for url in tier {
-                            if url.is_empty() {
-                                return Err(LavaTorrentError::TorrentBuilderFailure(
-                                    Cow::Borrowed(
-                                        "TorrentBuilder has `announce_list` but \
-                                     one of its tiers contains a 0-length url.",
-                                    ),
-                                ));
-                            }
-                        }
-                    }
+    /// Change the # of pieces that can wait to be hashed
+    /// when using [`HashingStrategy::Pipelined`].
+    ///
+    /// The reader thread blocks once `pipeline_depth` pieces are waiting,
+    /// so at most `pipeline_depth + num_threads + 1` pieces are held in memory
+    /// at a time (the waiting ones, one per hashing thread, and the one being
+    /// read). With large pieces, a small depth keeps memory usage down.
+    ///
+    /// Setting `pipeline_depth` to `0` restores the default (twice the # of threads).
+    ///
+    /// Calling this method multiple times will simply override previous settings.
+    ///
+    /// [`HashingStrategy::Pipelined`]: enum.HashingStrategy.html#variant.Pipelined
+    pub fn set_pipeline_depth(self, pipeline_depth: usize) -> TorrentBuilder {
+        TorrentBuilder {
+            pipeline_depth,
+            ..self
+        }
+    }
+
+    /// Change how often [`build_resumable()`] saves its progress,
+    /// in # of pieces.
+    ///
+    /// Each checkpoint contains all pieces hashed so far, so saving too often
+    /// slows down the build of a torrent with many pieces. On the other hand,
+    /// up to `checkpoint_interval` pieces have to be hashed again after
+    /// an interruption.
+    ///
+    /// Setting `checkpoint_interval` to `0` restores the default (1024 pieces).
+    ///
+    /// Calling this method multiple times will simply override previous settings.
+    ///
+    /// [`build_resumable()`]: #method.build_resumable
+    pub fn set_checkpoint_interval(self, checkpoint_interval: usize) -> TorrentBuilder {
+        TorrentBuilder {
+            checkpoint_interval,
+            ..self
+        }
+    }
+
+    /// Make sure that the files hashed are the ones found when scanning
+    /// `path`, even if `path` (or something in it) is replaced in between,
+    /// e.g. with a symlink to somewhere else.
+    ///
+    /// If set to `true`, the identity of `path` is recorded when it is
+    /// canonicalized and checked again once it has been scanned. The
+    /// identity of each file is recorded then as well, and checked every
+    /// time the file is opened for hashing. If anything has been replaced,
+    /// the build fails with an error saying so instead of hashing
+    /// the replacement.
+    ///
+    /// On Unix, identities are device and inode #s. Elsewhere, only
+    /// the lengths and modification times can be compared, which does
+    /// not catch a replacement that matches both.
+    ///
+    /// Pieces are always hashed sequentially in this case,
+    /// regardless of [`set_hashing_strategy()`].
+    /// This is `false` by default.
+    ///
+    /// Calling this method multiple times will simply override previous settings.
+    ///
+    /// [`set_hashing_strategy()`]: #method.set_hashing_strategy
+    pub fn set_secure_open(self, secure_open: bool) -> TorrentBuilder {
+        TorrentBuilder {
+            secure_open,
+            ..self
+        }
+    }
+
+    /// Check this `TorrentBuilder` against `rules` without hashing anything.
+    ///
+    /// The files that [`build()`] would include are listed (with the
+    /// same settings, e.g. [`set_preserve_symlinks()`]) and checked against
+    /// `rules`, so that a rule violation can be detected before spending
+    /// time on hashing.
+    ///
+    /// Padding files (see [`set_hybrid_compatible_layout()`]) count towards
+    /// `max_files` and `max_piece_count`, but are not checked individually.
+    ///
+    /// Returns all violations found. If the builder itself is invalid (i.e.
+    /// [`build()`] would fail), only [`RuleViolation::InvalidBuilder`]
+    /// is returned.
+    ///
+    /// [`build()`]: #method.build
+    /// [`set_preserve_symlinks()`]: #method.set_preserve_symlinks
+    /// [`set_hybrid_compatible_layout()`]: #method.set_hybrid_compatible_layout
+    /// [`RuleViolation::InvalidBuilder`]: enum.RuleViolation.html#variant.InvalidBuilder
+    pub fn check_rules(&self, rules: &UploadRules) -> Result<(), Vec<RuleViolation>> {
+        let entries = self
+            .list_entries()
+            .map_err(|e| vec![RuleViolation::InvalidBuilder(e)])?;
+        let mut violations = Vec::new();
+
+        if let Some(max) = rules.max_files {
+            if entries.len() > max {
+                violations.push(RuleViolation::TooManyFiles {
+                    count: entries.len(),
+                    max,
+                });
+            }
+        }
+
+        for (path, length) in &entries {
+            if Self::is_padding_entry(path) {
+                continue;
+            }
+
+            if let Some(max) = rules.max_file_size {
+                if *length > max {
+                    violations.push(RuleViolation::FileTooLarge {
+                        path: path.clone(),
+                        length: *length,
+                        max,
+                    });
+                }
+            }
+
+            if let Some(extension) = path.extension().map(|ext| ext.to_string_lossy()) {
+                if rules
+                    .forbidden_extensions
+                    .iter()
+                    .any(|forbidden| forbidden.eq_ignore_ascii_case(&extension))
+                {
+                    violations.push(RuleViolation::ForbiddenExtension {
+                        path: path.clone(),
+                        extension: extension.into_owned(),
+                    });
                 }
-                Ok(())
             }
-        } else {
-            Ok(())
         }
-    }
 
-    fn validate_name(&self) -> Result<(), LavaTorrentError> {
-        if let Some(ref name) = self.name {
-            if name.is_empty() {
-                return Err(LavaTorrentError::TorrentBuilderFailure(Cow::Borrowed(
-                    "TorrentBuilder has `name` but its length is 0.",
-                )));
-            } else {
-                Ok(())
+        if let Some(min) = rules.min_piece_length {
+            if self.piece_length < min {
+                violations.push(RuleViolation::PieceLengthTooSmall {
+                    piece_length: self.piece_length,
+                    min,
+                });
             }
-        } else {
+        }
+
+        if let Some(max) = rules.max_piece_length {
+            if self.piece_length > max {
+                violations.push(RuleViolation::PieceLengthTooLarge {
+                    piece_length: self.piece_length,
+                    max,
+                });
+            }
+        }
+
+        if let Some(max) = rules.max_piece_count {
+            // piece_length is validated by list_entries(), so this won't divide by 0
+            let total_length = entries.iter().map(|&(_, length)| length).sum::<Integer>();
+            let count = Self::piece_count(total_length, self.piece_length)
+                .map_err(|e| vec![RuleViolation::InvalidBuilder(e)])?;
+            if count > max {
+                violations.push(RuleViolation::TooManyPieces { count, max });
+            }
+        }
+
+        if rules.require_private && !self.is_private {
+            violations.push(RuleViolation::NotPrivate);
+        }
+
+        if rules.require_single_announce
+            && (self.announce.is_none() || self.announce_list.is_some())
+        {
+            violations.push(RuleViolation::NotSingleAnnounce);
+        }
+
+        if violations.is_empty() {
             Ok(())
+        } else {
+            Err(violations)
         }
     }
 
-    fn validate_path(&self) -> Result<(), LavaTorrentError> {
-        if self.path.exists() {
-            Ok(())
+    // Validate `self` and list the files (paths relative to the
+    // torrent's root, and lengths) that `build()` would include,
+    // padding entries included.
+    fn list_entries(&self) -> Result<Vec<(PathBuf, Integer)>, LavaTorrentError> {
+        self.validate_announce()?;
+        self.validate_announce_list()?;
+        self.validate_name()?;
+        self.validate_path()?;
+        self.validate_layout()?;
+        self.validate_piece_length()?;
+        self.validate_extra_fields()?;
+        self.validate_extra_info_fields()?;
+
+        let canonicalized_path = self.resolved_root()?;
+        let metadata = canonicalized_path.metadata()?;
+
+        if metadata.is_dir() {
+            let entries = Self::walk_dir(
+                &canonicalized_path,
+                !self.preserve_symlinks,
+                self.file_issue_policy,
+                &|| Ok(()),
+                &mut BuildReport::default(),
+                &mut FilePins::default(),
+            )?
+            .into_iter()
+            .map(|(path, length)| {
+                // Unwrap is fine here since canonicalized_path is
+                // by definition a parent to path.
+                (
+                    path.strip_prefix(&canonicalized_path)
+                        .unwrap()
+                        .to_path_buf(),
+                    length,
+                )
+            })
+            .collect();
+            self.padded_entries(entries)?
+                .into_iter()
+                .map(|(path, length)| Ok((path, util::u64_to_i64(length)?)))
+                .collect()
         } else {
-            return Err(LavaTorrentError::TorrentBuilderFailure(Cow::Borrowed(
-                "TorrentBuilder has `path` but it does not point to anything.",
-            )));
+            Ok(vec![(
+                PathBuf::from(Self::derive_name(&self.path, &canonicalized_path)?),
+                util::u64_to_i64(metadata.len())?,
+            )])
         }
     }
 
-    fn validate_piece_length(&self) -> Result<(), LavaTorrentError> {
-        if self.piece_length <= 0 {
-            return Err(LavaTorrentError::TorrentBuilderFailure(Cow::Borrowed(
-                "TorrentBuilder has `piece_length` <= 0.",
-            )));
-        } else if (self.piece_length & (self.piece_length - 1)) != 0 {
-            // bit trick to check if a number is a power of 2
-            // found at: https://stackoverflow.com/a/600306
-            return Err(LavaTorrentError::TorrentBuilderFailure(Cow::Borrowed(
-                "TorrentBuilder has `piece_length` that is not a power of 2.",
-            )));
+    // Pad `entries` (see `pad_entries()`) if `self` uses the
+    // hybrid-compatible layout, or return them as is otherwise.
+    fn padded_entries(
+        &self,
+        entries: Vec<(PathBuf, u64)>,
+    ) -> Result<Vec<(PathBuf, u64)>, LavaTorrentError> {
+        if self.hybrid_compatible_layout {
+            Self::pad_entries(entries, self.piece_length)
         } else {
-            Ok(())
+            Ok(entries)
         }
     }
 
-    fn validate_extra_fields(&self) -> Result<(), LavaTorrentError> {
-        if let Some(ref extra_fields) = self.extra_fields {
-            if extra_fields.is_empty() {
-                panic!("TorrentBuilder has `extra_fields` but it's empty.")
-            } else {
-                for key in extra_fields.keys() {
-                    if key.is_empty() {
-                        return Err(LavaTorrentError::TorrentBuilderFailure(Cow::Borrowed(
-                            "TorrentBuilder has `extra_fields` but it contains a 0-length key.",
-                        )));
-                    }
+    // List the files in `root` (recursively, ignoring hidden entries, and
+    // sorted by path), recording problems with individual entries in
+    // `report` and carrying on instead of aborting at the first one. The
+    // listed files are then checked again, so that files that changed
+    // during the walk are caught before anything is hashed. Finally,
+    // serious issues are dealt with according to `policy`.
+    //
+    // Each file is pinned with the metadata the walk itself found, and
+    // the root is checked once the walk is done (see `FilePins`).
+    //
+    // Special files (e.g. sockets) are skipped, and so are symlinks (if not
+    // followed) that can't be recorded, i.e. dangling ones and those
+    // pointing outside of `root`. Other symlinks that are not followed are
+    // listed with a length of 0.
+    fn walk_dir(
+        root: &Path,
+        follow_symlinks: bool,
+        policy: FileIssuePolicy,
+        check: &dyn Fn() -> Result<(), LavaTorrentError>,
+        report: &mut BuildReport,
+        pins: &mut FilePins,
+    ) -> Result<Vec<(PathBuf, u64)>, LavaTorrentError> {
+        let mut entries = Vec::new();
+        let mut issues = Vec::new();
+        // only the root itself being unreadable is fatal
+        Self::walk_dir_into(
+            root.read_dir()?,
+            follow_symlinks,
+            check,
+            &mut entries,
+            &mut issues,
+            pins,
+        )?;
+        pins.check_root()?;
+        entries.sort_by(|(p1, _, _), (p2, _, _)| p1.cmp(p2));
+
+        for (path, length, is_file) in &entries {
+            check()?;
+            if !is_file {
+                // symlinks that are not followed have nothing to read,
+                // but have to point to something inside `root`
+                match path.canonicalize() {
+                    Ok(target) if target.starts_with(root) => (),
+                    Ok(_) => issues.push((path.clone(), FileIssue::SymlinkOutsideRoot)),
+                    Err(e) => issues.push((path.clone(), Self::file_issue(path, &e))),
                 }
-                Ok(())
+                continue;
+            }
+            match std::fs::File::open(path).and_then(|file| file.metadata()) {
+                Ok(metadata) if metadata.len() != *length => issues.push((
+                    path.clone(),
+                    FileIssue::SizeChanged {
+                        expected: *length,
+                        actual: metadata.len(),
+                    },
+                )),
+                Ok(_) if *length == 0 => issues.push((path.clone(), FileIssue::Empty)),
+                Ok(_) => (),
+                Err(e) => issues.push((path.clone(), Self::file_issue(path, &e))),
             }
-        } else {
-            Ok(())
         }
+        issues.sort_by(|(p1, _), (p2, _)| p1.cmp(p2));
+
+        let serious = issues
+            .iter()
+            .filter(|(_, issue)| issue.is_serious())
+            .map(|(path, issue)| (path.as_path(), issue))
+            .collect::<Vec<_>>();
+        let result = if serious.is_empty() {
+            Ok(entries)
+        } else {
+            match policy {
+                FileIssuePolicy::Fail => Err(LavaTorrentError::TorrentBuilderFailure(Cow::Owned(
+                    format!(
+                        "{} file(s) have issues: {}.",
+                        serious.len(),
+                        serious.iter().format_with("; ", |(path, issue), f| {
+                            f(&format_args!("[{}] {}", path.display(), issue))
+                        })
+                    ),
+                ))),
+                FileIssuePolicy::Skip => {
+                    let skipped = serious
+                        .iter()
+                        .map(|&(path, _)| path)
+                        .collect::<std::collections::HashSet<_>>();
+                    entries.retain(|(path, _, _)| !skipped.contains(path.as_path()));
+                    Ok(entries)
+                }
+            }
+        };
+
+        report.file_issues.extend(issues);
+        Ok(result?
+            .into_iter()
+            .map(|(path, length, _)| (path, length))
+            .collect())
     }
 
-    fn validate_extra_info_fields(&self) -> Result<(), LavaTorrentError> {
-        if let Some(ref extra_info_fields) = self.extra_info_fields {
-            if extra_info_fields.is_empty() {
-                panic!("TorrentBuilder has `extra_info_fields` but it's empty.")
+    // Collect `(path, length, is regular file)` of each entry in `dir`,
+    // recursively. Hidden entries are ignored.
+    fn walk_dir_into(
+        dir: std::fs::ReadDir,
+        follow_symlinks: bool,
+        check: &dyn Fn() -> Result<(), LavaTorrentError>,
+        entries: &mut Vec<(PathBuf, u64, bool)>,
+        issues: &mut Vec<(PathBuf, FileIssue)>,
+        pins: &mut FilePins,
+    ) -> Result<(), LavaTorrentError> {
+        for entry in dir {
+            check()?;
+            let path = entry?.path();
+            if util::last_component(&path)?.starts_with('.') {
+                continue;
+            } // hidden files/dirs are ignored
+
+            let metadata = if follow_symlinks {
+                path.metadata()
             } else {
-                for key in extra_info_fields.keys() {
-                    if key.is_empty() {
-                        return Err(LavaTorrentError::TorrentBuilderFailure(Cow::Borrowed(
-                            "TorrentBuilder has `extra_info_fields` but it contains a 0-length key."
-                        )));
+                path.symlink_metadata()
+            };
+            let metadata = match metadata {
+                Ok(metadata) => metadata,
+                Err(e) => {
+                    issues.push((path.clone(), Self::file_issue(&path, &e)));
+                    continue;
+                }
+            };
+
+            if metadata.is_dir() {
+                match path.read_dir() {
+                    Ok(dir) => {
+                        Self::walk_dir_into(dir, follow_symlinks, check, entries, issues, pins)?
                     }
+                    Err(e) => issues.push((path.clone(), Self::file_issue(&path, &e))),
                 }
-                Ok(())
+            } else if metadata.file_type().is_symlink() {
+                entries.push((path, 0, false));
+            } else if metadata.is_file() {
+                pins.record(&path, &metadata);
+                entries.push((path, metadata.len(), true));
+            } else {
+                issues.push((path, FileIssue::SpecialFileSkipped));
             }
+        }
+
+        Ok(())
+    }
+
+    // A `NotFound` error means that `path` vanished, unless `path`
+    // itself still exists (i.e. it's a dangling symlink).
+    fn file_issue(path: &Path, e: &std::io::Error) -> FileIssue {
+        if e.kind() == std::io::ErrorKind::NotFound && path.symlink_metadata().is_err() {
+            FileIssue::Vanished
         } else {
-            Ok(())
+            FileIssue::Unreadable(e.kind())
         }
     }
 
-    fn read_file<P>(
-        path: P,
-        piece_length: Integer,
-    ) -> Result<(Integer, Vec<Piece>), LavaTorrentError>
-    where
-        P: AsRef<Path>,
-    {
-        let path = path.as_ref();
-        let length = path.metadata()?.len();
-        let piece_length = util::i64_to_u64(piece_length)?;
+    fn extract_config_string(
+        dict: &mut Dictionary,
+        key: &str,
+    ) -> Result<Option<String>, LavaTorrentError> {
+        match dict.remove(key) {
+            Some(BencodeElem::String(string)) => Ok(Some(string)),
+            Some(other) => Err(LavaTorrentError::InvalidArgument(Cow::Owned(format!(
+                r#""{}" maps to {}, expected String."#,
+                key,
+                other.kind()
+            )))),
+            None => Ok(None),
+        }
+    }
 
-        // read file content + calculate pieces/hashes
-        let mut file = BufReader::new(std::fs::File::open(path)?);
-        let mut piece = Vec::with_capacity(util::u64_to_usize(piece_length)?);
-        let mut pieces = Vec::with_capacity(util::u64_to_usize(length / piece_length + 1)?);
-        let mut total_read = 0;
+    fn extract_config_integer(
+        dict: &mut Dictionary,
+        key: &str,
+    ) -> Result<Option<Integer>, LavaTorrentError> {
+        match dict.remove(key) {
+            Some(BencodeElem::Integer(n)) => Ok(Some(n)),
+            Some(other) => Err(LavaTorrentError::InvalidArgument(Cow::Owned(format!(
+                r#""{}" maps to {}, expected Integer."#,
+                key,
+                other.kind()
+            )))),
+            None => Ok(None),
+        }
+    }
 
-        while total_read < length {
-            let read = file.by_ref().take(piece_length).read_to_end(&mut piece)?;
-            total_read += util::usize_to_u64(read)?;
+    // Missing keys default to `false`.
+    fn extract_config_bool(dict: &mut Dictionary, key: &str) -> Result<bool, LavaTorrentError> {
+        match Self::extract_config_integer(dict, key)? {
+            Some(0) | None => Ok(false),
+            Some(1) => Ok(true),
+            Some(other) => Err(LavaTorrentError::InvalidArgument(Cow::Owned(format!(
+                r#""{}" [{}] is neither 0 nor 1."#,
+                key, other
+            )))),
+        }
+    }
 
-            pieces.push(Sha1::digest(&piece).to_vec());
-            piece.clear();
+    // Missing keys default to `0`.
+    fn extract_config_usize(dict: &mut Dictionary, key: &str) -> Result<usize, LavaTorrentError> {
+        match Self::extract_config_integer(dict, key)? {
+            Some(n) => util::i64_to_usize(n),
+            None => Ok(0),
         }
+    }
 
-        Ok((util::u64_to_i64(length)?, pieces))
+    fn extract_config_dictionary(
+        dict: &mut Dictionary,
+        key: &str,
+    ) -> Result<Option<Dictionary>, LavaTorrentError> {
+        match dict.remove(key) {
+            Some(BencodeElem::Dictionary(dict)) => Ok(Some(dict)),
+            Some(other) => Err(LavaTorrentError::InvalidArgument(Cow::Owned(format!(
+                r#""{}" maps to {}, expected Dictionary."#,
+                key,
+                other.kind()
+            )))),
+            None => Ok(None),
+        }
     }
 
-    fn read_file_parallel<P>(
-        path: P,
-        piece_length: Integer,
-        num_threads: usize,
-    ) -> Result<(Integer, Vec<Piece>), LavaTorrentError>
-    where
-        P: AsRef<Path>,
-    {
-        let path = path.as_ref();
-        let length = path.metadata()?.len();
-        let piece_length_u64 = util::i64_to_u64(piece_length)?;
-        let piece_length_usize = util::u64_to_usize(piece_length_u64)?;
-        let n_pieces = (length + (piece_length_u64 - 1)) / piece_length_u64;
+    fn extract_config_announce_list(
+        dict: &mut Dictionary,
+    ) -> Result<Option<AnnounceList>, LavaTorrentError> {
+        let tiers = match dict.remove("announce list") {
+            Some(BencodeElem::List(tiers)) => tiers,
+            Some(other) => {
+                return Err(LavaTorrentError::InvalidArgument(Cow::Owned(format!(
+                    r#""announce list" maps to {}, expected List."#,
+                    other.kind()
+                ))));
+            }
+            None => return Ok(None),
+        };
 
-        let thread_pool = rayon::ThreadPoolBuilder::new()
-            .num_threads(num_threads)
-            .build()
-            .map_err(|e| {
-                LavaTorrentError::TorrentBuilderFailure(Cow::Owned(format!(
-                    "failed to create rayon thread pool: {}",
-                    e
-                )))
-            })?;
+        let mut announce_list = Vec::with_capacity(tiers.len());
+        for tier in tiers {
+            let urls = match tier {
+                BencodeElem::List(urls) => urls,
+                other => {
+                    return Err(LavaTorrentError::InvalidArgument(Cow::Owned(format!(
+                        r#""announce list" contains a {} element, expected List."#,
+                        other.kind()
+                    ))));
+                }
+            };
+            let mut tier = Vec::with_capacity(urls.len());
+            for url in urls {
+                match url {
+                    BencodeElem::String(url) => tier.push(url),
+                    other => {
+                        return Err(LavaTorrentError::InvalidArgument(Cow::Owned(format!(
+                            r#"A tier within "announce list" contains a {} element, expected String."#,
+                            other.kind()
+                        ))));
+                    }
+                }
+            }
+            announce_list.push(tier);
+        }
+        Ok(Some(announce_list))
+    }
 
-        let pieces = thread_pool.install(|| {
-            (0_u64..n_pieces)
-                .into_par_iter()
-                .map(|i| {
-                    let mut file = std::fs::File::open(path)?;
-                    let mut piece = Vec::with_capacity(piece_length_usize);
-                    file.seek(std::io::SeekFrom::Start(i * piece_length_u64))?;
-                    file.take(piece_length_u64).read_to_end(&mut piece)?;
-                    Ok(Sha1::digest(&piece).to_vec())
-                })
-                .collect::<Result<Vec<Vec<u8>>, LavaTorrentError>>()
-        })?;
+    // # of pieces needed to cover `length` bytes. `piece_length` must be > 0.
+    fn piece_count(length: Integer, piece_length: Integer) -> Result<usize, LavaTorrentError> {
+        let n_pieces =
+            Self::piece_count_u64(util::i64_to_u64(length)?, util::i64_to_u64(piece_length)?)?;
+        util::u64_to_usize(n_pieces)
+    }
 
-        Ok((util::u64_to_i64(length)?, pieces))
+    // Like `piece_count()`, but for lengths that are already u64. The returned
+    // count is guaranteed to fit into `usize`, so that it can be used as an
+    // index. Fails if `piece_length` is 0.
+    pub(crate) fn piece_count_u64(length: u64, piece_length: u64) -> Result<u64, LavaTorrentError> {
+        util::div_ceil_u64(length, piece_length)
+            .ok()
+            .filter(|&n_pieces| util::u64_to_usize(n_pieces).is_ok())
+            .ok_or_else(|| {
+                LavaTorrentError::TorrentBuilderFailure(Cow::Owned(format!(
+                    "Cannot count the pieces of length {} needed for {} bytes.",
+                    piece_length, length
+                )))
+            })
     }
 
-    fn read_dir<P>(
-        path: P,
+    // The capacity to reserve for `n_pieces` pieces. Vectors of pieces
+    // grow incrementally beyond `MAX_PREALLOCATED_PIECES`, so that a bogus
+    // count doesn't cause a huge allocation before anything is read.
+    fn preallocated_pieces(n_pieces: u64) -> usize {
+        usize::try_from(n_pieces)
+            .map_or(MAX_PREALLOCATED_PIECES, |n| n.min(MAX_PREALLOCATED_PIECES))
+    }
+
+    // Split the concatenation of the files in `entries` into pieces.
+    // Each piece is listed as its chunks, saved as (index of the file in
+    // `entries`, start pos in file, chunk length). For example, suppose
+    // the piece length is 256B, we might get:
+    //     piece #1 => [(file #1, 0, 256)]
+    //     piece #2 => [(file #1, 256, 25), (file #2, 0, 231)]
+    //     ...
+    // Empty files have no chunks.
+    //
+    // Everything that needs the layout of pieces (parallel hashing, the piece
+    // map in `BuildReport`, and `Torrent::validate_files()`) goes through here,
+    // so that they can't disagree about it.
+    pub(crate) fn map_pieces<T>(
+        entries: &[(T, u64)],
+        piece_length: u64,
+    ) -> Result<IndexedPieceMap, LavaTorrentError> {
+        let total_length = Self::total_length(entries)?;
+        let n_pieces = Self::piece_count_u64(total_length, piece_length)?;
+        let mut pieces: IndexedPieceMap = Vec::with_capacity(Self::preallocated_pieces(n_pieces));
+        let split_length = Self::split_length(piece_length);
+        let mut piece_remaining = 0;
+
+        for (index, &(_, length)) in entries.iter().enumerate() {
+            let mut file_remaining = length;
+
+            while file_remaining > 0 {
+                // start the next piece when appropriate
+                if piece_remaining == 0 {
+                    pieces.push(Vec::new());
+                    piece_remaining = split_length;
+                }
+
+                // calculate the # of bytes to allocate in this iteration
+                let to_allocate = file_remaining.min(piece_remaining);
+
+                // Unwrap is fine here since a piece was started above if needed.
+                pieces
+                    .last_mut()
+                    .unwrap()
+                    .push((index, length - file_remaining, to_allocate));
+
+                // update counters
+                piece_remaining -= to_allocate;
+                file_remaining -= to_allocate;
+            }
+        }
+
+        Self::check_piece_map(&pieces, total_length, piece_length)?;
+        Ok(pieces)
+    }
+
+    // `map_pieces()` with the indices replaced by the paths in `entries`.
+    fn named_piece_map(
+        entries: &[(PathBuf, u64)],
         piece_length: Integer,
-    ) -> Result<(Integer, Vec<File>, Vec<Piece>), LavaTorrentError>
+    ) -> Result<PieceMap, LavaTorrentError> {
+        Ok(Self::map_pieces(entries, util::i64_to_u64(piece_length)?)?
+            .into_iter()
+            .map(|chunks| {
+                chunks
+                    .into_iter()
+                    .filter(|&(index, _, _)| !Self::is_padding_entry(&entries[index].0))
+                    .map(|(index, offset, len)| (entries[index].0.clone(), offset, len))
+                    .collect()
+            })
+            .collect())
+    }
+
+    // The `File`s of `entries`, which are listed under `path`.
+    fn entries_to_files<P>(
+        path: P,
+        entries: &[(PathBuf, u64)],
+    ) -> Result<Vec<File>, LavaTorrentError>
     where
         P: AsRef<Path>,
     {
-        let piece_length_u64 = util::i64_to_u64(piece_length)?;
-        let piece_length_usize = util::i64_to_usize(piece_length)?;
-        let entries = util::list_dir(&path)?;
-        let total_length = entries.iter().fold(0, |acc, &(_, len)| acc + len);
-        let mut files = Vec::with_capacity(entries.len());
-        let mut piece = Vec::with_capacity(piece_length_usize);
-        let mut pieces =
-            Vec::with_capacity(util::u64_to_usize(total_length / piece_length_u64 + 1)?);
+        // Unwrap is fine here since path is by definition
+        // a parent to entry_path and path is canonicalized
+        // before this call. Thus this should never fail.
+        entries
+            .iter()
+            .map(|(entry_path, length)| {
+                if Self::is_padding_entry(entry_path) {
+                    return Self::padding_file(*length);
+                }
+                Ok(File {
+                    length: util::u64_to_i64(*length)?,
+                    path: Self::path_components(entry_path.strip_prefix(path.as_ref()).unwrap()),
+                    extra_fields: None,
+                })
+            })
+            .collect()
+    }
 
-        for (entry_path, length) in entries {
-            let mut file = BufReader::new(std::fs::File::open(&entry_path)?);
-            let mut file_remaining = length;
+    // Insert a padding entry after each entry (except the last one) that
+    // does not end at a piece boundary, so that every file starts at one.
+    //
+    // Padding entries have an empty path, which no listed file can have,
+    // and are read as zeros (see `open_entry()`).
+    fn pad_entries(
+        entries: Vec<(PathBuf, u64)>,
+        piece_length: Integer,
+    ) -> Result<Vec<(PathBuf, u64)>, LavaTorrentError> {
+        let piece_length = util::i64_to_u64(piece_length)?;
+        let n_entries = entries.len();
+        let mut padded = Vec::with_capacity(n_entries * 2);
+
+        for (index, (path, length)) in entries.into_iter().enumerate() {
+            let misalignment = length % piece_length;
+            padded.push((path, length));
+            if (misalignment != 0) && (index + 1 < n_entries) {
+                padded.push((PathBuf::new(), piece_length - misalignment));
+            }
+        }
 
-            while file_remaining > 0 {
-                // calculate the # of bytes to read in this iteration
-                let piece_filled = util::usize_to_u64(piece.len())?;
-                let piece_remaining = piece_length_u64 - piece_filled;
-                let to_read = if file_remaining < piece_remaining {
-                    file_remaining
-                } else {
-                    piece_remaining
-                };
+        Ok(padded)
+    }
 
-                // read bytes
-                file.by_ref().take(to_read).read_to_end(&mut piece)?;
-                file_remaining -= to_read;
+    fn is_padding_entry(path: &Path) -> bool {
+        path.as_os_str().is_empty()
+    }
+
+    // A padding file of `length` bytes, named the way libtorrent names them.
+    // Since hidden files are never listed, `.pad` can't clash with actual files.
+    fn padding_file(length: u64) -> Result<File, LavaTorrentError> {
+        Ok(File {
+            length: util::u64_to_i64(length)?,
+            path: vec![".pad".to_owned(), length.to_string()],
+            extra_fields: Some(Dictionary::from_iter([(
+                "attr".to_owned(),
+                BencodeElem::String("p".to_owned()),
+            )])),
+        })
+    }
+
+    // Open the entry at `path` for reading from `offset` onwards, checking
+    // it against `pins`. Padding entries are read as an endless stream of zeros.
+    fn open_entry(
+        path: &Path,
+        offset: u64,
+        pins: &FilePins,
+    ) -> Result<Box<dyn Read>, LavaTorrentError> {
+        if Self::is_padding_entry(path) {
+            return Ok(Box::new(std::io::repeat(0)));
+        }
+
+        let mut file = pins.open(path)?;
+        if offset > 0 {
+            file.seek(std::io::SeekFrom::Start(offset))?;
+        }
+        Ok(Box::new(file))
+    }
+
+    // Sum of the lengths of `entries`.
+    fn total_length<T>(entries: &[(T, u64)]) -> Result<u64, LavaTorrentError> {
+        entries
+            .iter()
+            .try_fold(0_u64, |acc, &(_, len)| acc.checked_add(len))
+            .ok_or(LavaTorrentError::TorrentBuilderFailure(Cow::Borrowed(
+                "Total length of the files overflowed in u64.",
+            )))
+    }
+
+    // Use the last component of `path` as given by the user. Fall back
+    // to the last component of `canonicalized_path` only if `path`
+    // doesn't have one (i.e. it ends in "." or "..").
+    fn derive_name<P, Q>(path: P, canonicalized_path: Q) -> Result<String, LavaTorrentError>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        match util::last_component(path) {
+            Ok(name) => Ok(name),
+            Err(_) => util::last_component(canonicalized_path),
+        }
+    }
 
-                // if piece is completely filled, hash it
-                if piece.len() == piece_length_usize {
-                    pieces.push(Sha1::digest(&piece).to_vec());
-                    piece.clear();
+    // `hashing_strategy` resolved, except that pieces are always
+    // hashed sequentially with `secure_open`.
+    fn resolved_strategy(&self, num_threads: usize, is_dir: bool) -> HashingStrategy {
+        if self.secure_open {
+            HashingStrategy::Sequential
+        } else {
+            self.hashing_strategy.resolve(num_threads, is_dir)
+        }
+    }
+
+    // Resolve the path to read from, the torrent's `name`, and the name
+    // of the single file to list in `files` (if any), taking
+    // `force_single_file` and `force_multi_file` into account.
+    fn resolve_layout(
+        &self,
+        report: &mut BuildReport,
+    ) -> Result<(PathBuf, String, Option<String>), LavaTorrentError> {
+        let canonicalized_path = self.resolved_root()?;
+        let is_dir = canonicalized_path.metadata()?.is_dir();
+
+        if self.force_single_file && is_dir {
+            let entries = Self::walk_dir(
+                &canonicalized_path,
+                !self.preserve_symlinks,
+                self.file_issue_policy,
+                &|| Ok(()),
+                report,
+                &mut FilePins::default(),
+            )?;
+            return match entries.as_slice() {
+                [(path, _)] => {
+                    let name = match self.name {
+                        Some(ref name) => name.clone(),
+                        None => util::last_component(path)?,
+                    };
+                    Ok((path.clone(), name, None))
                 }
-            }
+                _ => Err(LavaTorrentError::TorrentBuilderFailure(Cow::Owned(
+                    format!(
+                        "TorrentBuilder has `force_single_file` set but [{}] contains {} files.",
+                        canonicalized_path.display(),
+                        entries.len()
+                    ),
+                ))),
+            };
+        }
+
+        if self.force_multi_file && !is_dir {
+            // the wrapped file is always named after the path
+            let file_name = Self::derive_name(&self.path, &canonicalized_path)?;
+            let name = match self.name {
+                Some(ref name) => name.clone(),
+                None => Path::new(&file_name).file_stem().map_or_else(
+                    || file_name.clone(),
+                    |stem| stem.to_string_lossy().into_owned(),
+                ),
+            };
+            Ok((canonicalized_path, name, Some(file_name)))
+        } else {
+            let name = match self.name {
+                Some(ref name) => name.clone(),
+                None => Self::derive_name(&self.path, &canonicalized_path)?,
+            };
+            Ok((canonicalized_path, name, None))
+        }
+    }
 
-            // Unwrap is fine here since path is by definition
-            // a parent to entry_path and path is canonicalized
-            // before this call. Thus this should never fail.
-            files.push(File {
-                length: util::u64_to_i64(length)?,
-                path: entry_path.strip_prefix(&path).unwrap().to_path_buf(),
+    // `files` of a torrent built from a single file of `length` bytes,
+    // i.e. `wrapped_file` alone if set (see `resolve_layout()`).
+    fn wrap_file(wrapped_file: Option<String>, length: Integer) -> Option<Vec<File>> {
+        wrapped_file.map(|name| {
+            vec![File {
+                length,
+                path: vec![name],
                 extra_fields: None,
-            });
+            }]
+        })
+    }
+
+    // Normalize `announce_list` as described in `set_dedupe_trackers()`,
+    // if enabled.
+    fn dedupe_trackers(&mut self) {
+        if !self.dedupe_trackers {
+            return;
         }
+        let list = match self.announce_list.take() {
+            Some(list) => list,
+            None => return,
+        };
 
-        // if piece is empty then the total file size is divisible by the piece length
-        // otherwise the last piece is partially filled and we have to hash it
-        if !piece.is_empty() {
-            pieces.push(Sha1::digest(&piece).to_vec());
-            piece.clear();
+        let list = list
+            .into_iter()
+            .map(|tier| {
+                let mut seen = HashSet::with_capacity(tier.len());
+                tier.into_iter()
+                    .filter(|url| seen.insert(NormalizedTracker::dedupe_key(url)))
+                    .collect::<Vec<String>>()
+            })
+            .filter(|tier| !tier.is_empty())
+            .collect::<AnnounceList>();
+
+        let only_announce = match (&self.announce, list.as_slice()) {
+            (Some(announce), [tier]) => {
+                (tier.len() == 1)
+                    && (NormalizedTracker::dedupe_key(&tier[0])
+                        == NormalizedTracker::dedupe_key(announce))
+            }
+            _ => false,
+        };
+        if !list.is_empty() && !only_announce {
+            self.announce_list = Some(list);
         }
+    }
 
-        Ok((util::u64_to_i64(total_length)?, files, pieces))
+    // Split a relative `path` into the components of `File::path`.
+    pub(crate) fn path_components(path: &Path) -> Vec<String> {
+        path.iter()
+            .map(|component| component.to_string_lossy().into_owned())
+            .collect()
     }
 
-    // To parallelize read_dir(), we first find the chunk(s) of file(s) that belong to
-    // each piece. Then we can process the pieces in parallel. For example, suppose
-    // the piece length is 256B, we might get:
-    //     piece #1 => [(file #1, 0..256)]
-    //     piece #2 => [(file #1, 256..281), (file #2, 0..231)]
-    //     ...
-    // In other words, we generate the jobs first and then hand out the jobs to threads.
-    //
-    // @todo: The current implementation is not very memory efficient for a large dir.
-    // In the future it might be wise to switch to an iterator-based implementation.
-    fn read_dir_parallel<P>(
-        path: P,
-        piece_length: Integer,
-        num_threads: usize,
-    ) -> Result<(Integer, Vec<File>, Vec<Piece>), LavaTorrentError>
+    // Mark entries in `files` that are symlinks and record their
+    // targets (relative to `root`).
+    fn record_symlinks<P>(root: P, files: &mut [File]) -> Result<(), LavaTorrentError>
     where
         P: AsRef<Path>,
     {
-        let piece_length_u64 = util::i64_to_u64(piece_length)?;
-        let piece_length_usize = util::u64_to_usize(piece_length_u64)?;
-        let entries = util::list_dir(&path)?;
-        let total_length = entries.iter().fold(0, |acc, &(_, len)| acc + len);
-        let n_pieces = (total_length + (piece_length_u64 - 1)) / piece_length_u64;
-        let mut pieces = vec![vec![]; util::u64_to_usize(n_pieces)?];
-        let mut files = Vec::with_capacity(entries.len());
-
-        // find each piece's chunks
-        let mut pieces_iter = pieces.iter_mut();
-        let mut piece = pieces_iter.next().unwrap();
-        let mut piece_remaining = piece_length_u64;
+        let root = root.as_ref();
 
-        for (entry_path, length) in entries {
-            let entry_path = Arc::new(entry_path);
-            let mut file_remaining = length;
+        for file in files.iter_mut().filter(|file| !file.is_padding()) {
+            let path = root.join(file.to_path_buf());
+            if !path.symlink_metadata()?.file_type().is_symlink() {
+                continue;
+            }
 
-            while file_remaining > 0 {
-                // rotate to next piece when appropriate
-                if piece_remaining == 0 {
-                    piece = pieces_iter.next().unwrap();
-                    piece_remaining = piece_length_u64;
+            let target = path.canonicalize()?;
+            match target.strip_prefix(root) {
+                Ok(target) => file.set_symlink_target(Some(target.to_path_buf())),
+                Err(_) => {
+                    return Err(LavaTorrentError::TorrentBuilderFailure(Cow::Owned(
+                        format!(
+                            "Symlink [{}] points to [{}], which is outside of `path`.",
+                            path.display(),
+                            target.display(),
+                        ),
+                    )));
                 }
+            }
+        }
 
-                // calculate the # of bytes to allocate in this iteration
-                let to_allocate = if file_remaining < piece_remaining {
-                    file_remaining
-                } else {
-                    piece_remaining
-                };
+        Ok(())
+    }
 
-                // save chunk as (file path, start pos in file, chunk length)
-                piece.push((entry_path.clone(), length - file_remaining, to_allocate));
+    fn validate_announce(&self) -> Result<(), LavaTorrentError> {
+        Self::check_announce("TorrentBuilder", self.announce.as_deref())
+            .map_err(Self::builder_failure)
+    }
 
-                // update counters
-                piece_remaining -= to_allocate;
-                file_remaining -= to_allocate;
-            }
+    fn validate_announce_list(&self) -> Result<(), LavaTorrentError> {
+        Self::check_announce_list("TorrentBuilder", self.announce_list.as_ref())
+            .map_err(Self::builder_failure)
+    }
 
-            // Unwrap is fine here since path is by definition
-            // a parent to entry_path and path is canonicalized
-            // before this call. Thus this should never fail.
-            files.push(File {
-                length: util::u64_to_i64(length)?,
-                path: entry_path.strip_prefix(&path).unwrap().to_path_buf(),
-                extra_fields: None,
-            });
+    fn validate_name(&self) -> Result<(), LavaTorrentError> {
+        Self::check_name("TorrentBuilder", self.name.as_deref()).map_err(Self::builder_failure)
+    }
+
+    fn validate_path(&self) -> Result<(), LavaTorrentError> {
+        if self.path.exists() {
+            Ok(())
+        } else {
+            Err(LavaTorrentError::TorrentBuilderFailure(Cow::Borrowed(
+                "TorrentBuilder has `path` but it does not point to anything.",
+            )))
         }
+    }
 
-        // hash the pieces
-        let thread_pool = rayon::ThreadPoolBuilder::new()
-            .num_threads(num_threads)
-            .build()
-            .map_err(|e| {
-                LavaTorrentError::TorrentBuilderFailure(Cow::Owned(format!(
-                    "failed to create rayon thread pool: {}",
-                    e
-                )))
-            })?;
+    fn validate_layout(&self) -> Result<(), LavaTorrentError> {
+        if self.force_multi_file && self.force_single_file {
+            Err(LavaTorrentError::TorrentBuilderFailure(Cow::Borrowed(
+                "TorrentBuilder has both `force_multi_file` and `force_single_file` set.",
+            )))
+        } else {
+            Ok(())
+        }
+    }
 
-        let pieces = thread_pool.install(|| {
-            pieces
-                .into_par_iter()
-                .map(|chunks| {
-                    let mut bytes = Vec::with_capacity(piece_length_usize);
-                    for (file, offset, len) in chunks {
-                        let mut file = std::fs::File::open(file.as_ref())?;
-                        file.seek(std::io::SeekFrom::Start(offset))?;
-                        file.take(len).read_to_end(&mut bytes)?;
-                    }
-                    Ok(Sha1::digest(&bytes).to_vec())
-                })
-                .collect::<Result<Vec<Vec<u8>>, LavaTorrentError>>()
-        })?;
+    fn validate_piece_length(&self) -> Result<(), LavaTorrentError> {
+        Self::check_piece_length("TorrentBuilder", self.piece_length)
+            .map_err(Self::builder_failure)?;
+        if self.hybrid_compatible_layout && (self.piece_length < MIN_V2_PIECE_LENGTH) {
+            Err(LavaTorrentError::TorrentBuilderFailure(Cow::Borrowed(
+                "TorrentBuilder has `piece_length` < 16 KiB, which a hybrid compatible layout does not allow.",
+            )))
+        } else {
+            Ok(())
+        }
+    }
 
-        Ok((util::u64_to_i64(total_length)?, files, pieces))
+    fn validate_extra_fields(&self) -> Result<(), LavaTorrentError> {
+        if self.extra_fields.as_ref().is_some_and(HashMap::is_empty) {
+            panic!("TorrentBuilder has `extra_fields` but it's empty.")
+        }
+        Self::check_extra_fields("TorrentBuilder", "extra_fields", self.extra_fields.as_ref())
+            .map_err(Self::builder_failure)
     }
 
-    fn read_file_non_blocking<P>(
+    fn validate_extra_info_fields(&self) -> Result<(), LavaTorrentError> {
+        if self
+            .extra_info_fields
+            .as_ref()
+            .is_some_and(HashMap::is_empty)
+        {
+            panic!("TorrentBuilder has `extra_info_fields` but it's empty.")
+        }
+        Self::check_extra_fields(
+            "TorrentBuilder",
+            "extra_info_fields",
+            self.extra_info_fields.as_ref(),
+        )
+        .map_err(Self::builder_failure)
+    }
+
+    // The checks behind `validate_*()`, shared with `Torrent::from_pieces()`.
+    // `owner` is what the messages say has the invalid field. Failures are
+    // `InvalidArgument`, which `validate_*()` turn into `TorrentBuilderFailure`.
+
+    pub(crate) fn check_announce(
+        owner: &str,
+        announce: Option<&str>,
+    ) -> Result<(), LavaTorrentError> {
+        match announce {
+            Some("") => Err(Self::invalid_argument(format!(
+                "{} has `announce` but its length is 0.",
+                owner
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn check_announce_list(
+        owner: &str,
+        announce_list: Option<&AnnounceList>,
+    ) -> Result<(), LavaTorrentError> {
+        let announce_list = match announce_list {
+            Some(announce_list) => announce_list,
+            None => return Ok(()),
+        };
+
+        if announce_list.is_empty() {
+            Err(Self::invalid_argument(format!(
+                "{} has `announce_list` but it's empty.",
+                owner
+            )))
+        } else if announce_list.iter().any(Vec::is_empty) {
+            Err(Self::invalid_argument(format!(
+                "{} has `announce_list` but one of its tiers is empty.",
+                owner
+            )))
+        } else if announce_list.iter().flatten().any(String::is_empty) {
+            Err(Self::invalid_argument(format!(
+                "{} has `announce_list` but one of its tiers contains a 0-length url.",
+                owner
+            )))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub(crate) fn check_name(owner: &str, name: Option<&str>) -> Result<(), LavaTorrentError> {
+        match name.and_then(Torrent::component_issue) {
+            Some(issue) => Err(Self::invalid_argument(format!(
+                "{} has `name` but {}.",
+                owner, issue
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn check_piece_length(
+        owner: &str,
+        piece_length: Integer,
+    ) -> Result<(), LavaTorrentError> {
+        if piece_length <= 0 {
+            Err(Self::invalid_argument(format!(
+                "{} has `piece_length` <= 0.",
+                owner
+            )))
+        } else if (piece_length & (piece_length - 1)) != 0 {
+            // bit trick to check if a number is a power of 2
+            // found at: https://stackoverflow.com/a/600306
+            Err(Self::invalid_argument(format!(
+                "{} has `piece_length` that is not a power of 2.",
+                owner
+            )))
+        } else {
+            Ok(())
+        }
+    }
+
+    // `field` is the name of `fields`, e.g. `extra_fields`.
+    pub(crate) fn check_extra_fields(
+        owner: &str,
+        field: &str,
+        fields: Option<&Dictionary>,
+    ) -> Result<(), LavaTorrentError> {
+        if fields.into_iter().flatten().any(|(key, _)| key.is_empty()) {
+            Err(Self::invalid_argument(format!(
+                "{} has `{}` but it contains a 0-length key.",
+                owner, field
+            )))
+        } else {
+            Ok(())
+        }
+    }
+
+    // The error `check_*()` fail with.
+    fn invalid_argument(message: String) -> LavaTorrentError {
+        LavaTorrentError::InvalidArgument(Cow::Owned(message))
+    }
+
+    // Report a `check_*()` failure the way every other `validate_*()` failure is.
+    fn builder_failure(e: LavaTorrentError) -> LavaTorrentError {
+        match e {
+            LavaTorrentError::InvalidArgument(m) => LavaTorrentError::TorrentBuilderFailure(m),
+            e => e,
+        }
+    }
+
+    // Feed the next `len` bytes of `reader` into `hasher`.
+    //
+    // `buf` is the only intermediate buffer, i.e. a piece is never
+    // materialized in memory and memory usage is bounded by `buf.len()`
+    // regardless of the piece length.
+    //
+    // `check_canceled` is called before each read, so that a canceled
+    // build stops within 1 chunk instead of 1 (possibly huge) piece.
+    fn hash_from_reader<R>(
+        reader: &mut R,
+        len: u64,
+        hasher: &mut Sha1,
+        buf: &mut [u8],
+        check_canceled: &dyn Fn() -> Result<(), LavaTorrentError>,
+    ) -> Result<(), LavaTorrentError>
+    where
+        R: Read,
+    {
+        let buf_len = util::usize_to_u64(buf.len())?;
+        let mut remaining = len;
+
+        while remaining > 0 {
+            check_canceled()?;
+            let chunk = &mut buf[..util::u64_to_usize(remaining.min(buf_len))?];
+            reader.read_exact(chunk)?;
+            hasher.update(&chunk);
+            remaining -= util::usize_to_u64(chunk.len())?;
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn read_file<P>(
         path: P,
         piece_length: Integer,
-        torrent_build: TorrentBuildInternal,
+        chunk_size: usize,
+        pins: &FilePins,
     ) -> Result<(Integer, Vec<Piece>), LavaTorrentError>
     where
         P: AsRef<Path>,
@@ -757,38 +2490,35 @@ impl TorrentBuilder {
         let path = path.as_ref();
         let length = path.metadata()?.len();
         let piece_length = util::i64_to_u64(piece_length)?;
-        let n_pieces = (length + (piece_length - 1)) / piece_length;
-        torrent_build.set_piece_total(n_pieces);
 
         // read file content + calculate pieces/hashes
-        let mut file = BufReader::new(std::fs::File::open(path)?);
-        let mut piece = Vec::with_capacity(util::u64_to_usize(piece_length)?);
-        let mut pieces = Vec::with_capacity(util::u64_to_usize(n_pieces)?);
-        let mut total_read = 0;
-
-        while total_read < length {
-            if torrent_build.is_canceled() {
-                return Err(LavaTorrentError::TorrentBuilderFailure(Cow::Borrowed(
-                    "build canceled by client",
-                )));
-            }
-
-            let read = file.by_ref().take(piece_length).read_to_end(&mut piece)?;
-            total_read += util::usize_to_u64(read)?;
-
-            pieces.push(Sha1::digest(&piece).to_vec());
-            piece.clear();
-            torrent_build.inc_piece_processed();
+        let mut file = BufReader::new(pins.open(path)?);
+        let mut buf = vec![0; chunk_size];
+        let mut hasher = Sha1::new();
+        let n_pieces = Self::piece_count_u64(length, piece_length)?;
+        let mut pieces = Vec::with_capacity(Self::preallocated_pieces(n_pieces));
+        let mut remaining = length;
+        let split_length = Self::split_length(piece_length);
+        let mut max_filled = 0;
+
+        while remaining > 0 {
+            let to_read = remaining.min(split_length);
+            max_filled = max_filled.max(to_read);
+            Self::hash_from_reader(&mut file, to_read, &mut hasher, &mut buf, &|| Ok(()))?;
+            remaining -= to_read;
+
+            pieces.push(hasher.finalize_reset().into());
         }
+        Self::check_piece_filled(max_filled, piece_length)?;
 
         Ok((util::u64_to_i64(length)?, pieces))
     }
 
-    fn read_file_parallel_non_blocking<P>(
+    fn read_file_parallel<P>(
         path: P,
         piece_length: Integer,
+        chunk_size: usize,
         num_threads: usize,
-        torrent_build: TorrentBuildInternal,
     ) -> Result<(Integer, Vec<Piece>), LavaTorrentError>
     where
         P: AsRef<Path>,
@@ -796,9 +2526,7 @@ impl TorrentBuilder {
         let path = path.as_ref();
         let length = path.metadata()?.len();
         let piece_length_u64 = util::i64_to_u64(piece_length)?;
-        let piece_length_usize = util::u64_to_usize(piece_length_u64)?;
-        let n_pieces = (length + (piece_length_u64 - 1)) / piece_length_u64;
-        torrent_build.set_piece_total(n_pieces);
+        let n_pieces = Self::piece_count_u64(length, piece_length_u64)?;
 
         let thread_pool = rayon::ThreadPoolBuilder::new()
             .num_threads(num_threads)
@@ -814,160 +2542,512 @@ impl TorrentBuilder {
             (0_u64..n_pieces)
                 .into_par_iter()
                 .map(|i| {
-                    if torrent_build.is_canceled() {
-                        Err(LavaTorrentError::TorrentBuilderFailure(Cow::Borrowed(
-                            "build canceled by client",
-                        )))
-                    } else {
-                        let mut file = std::fs::File::open(path)?;
-                        let mut piece = Vec::with_capacity(piece_length_usize);
-                        file.seek(std::io::SeekFrom::Start(i * piece_length_u64))?;
-                        file.take(piece_length_u64).read_to_end(&mut piece)?;
-                        torrent_build.inc_piece_processed();
-                        Ok(Sha1::digest(&piece).to_vec())
-                    }
+                    let mut file = std::fs::File::open(path)?;
+                    let mut buf = vec![0; chunk_size];
+                    let mut hasher = Sha1::new();
+                    let offset = i * piece_length_u64;
+                    file.seek(std::io::SeekFrom::Start(offset))?;
+                    let to_read = piece_length_u64.min(length - offset);
+                    Self::hash_from_reader(&mut file, to_read, &mut hasher, &mut buf, &|| Ok(()))?;
+                    Ok(hasher.finalize().into())
                 })
-                .collect::<Result<Vec<Vec<u8>>, LavaTorrentError>>()
+                .collect::<Result<Vec<Piece>, LavaTorrentError>>()
         })?;
 
         Ok((util::u64_to_i64(length)?, pieces))
     }
 
-    fn read_dir_non_blocking<P>(
+    pub(crate) fn read_dir<P>(
         path: P,
+        entries: Vec<(PathBuf, u64)>,
         piece_length: Integer,
-        torrent_build: TorrentBuildInternal,
+        chunk_size: usize,
+        pins: &FilePins,
     ) -> Result<(Integer, Vec<File>, Vec<Piece>), LavaTorrentError>
     where
         P: AsRef<Path>,
     {
         let piece_length_u64 = util::i64_to_u64(piece_length)?;
-        let piece_length_usize = util::i64_to_usize(piece_length)?;
-        let entries = util::list_dir(&path)?;
-        let total_length = entries.iter().fold(0, |acc, &(_, len)| acc + len);
-        let n_pieces = (total_length + (piece_length_u64 - 1)) / piece_length_u64;
-        let mut files = Vec::with_capacity(entries.len());
-        let mut piece = Vec::with_capacity(piece_length_usize);
-        let mut pieces = Vec::with_capacity(util::u64_to_usize(n_pieces)?);
-        torrent_build.set_piece_total(n_pieces);
+        let total_length = Self::total_length(&entries)?;
+        let files = Self::entries_to_files(&path, &entries)?;
+        let mut buf = vec![0; chunk_size];
+        let mut hasher = Sha1::new();
+        let mut piece_filled = 0;
+        let split_length = Self::split_length(piece_length_u64);
+        let mut max_filled = 0;
+        let n_pieces = Self::piece_count_u64(total_length, piece_length_u64)?;
+        let mut pieces = Vec::with_capacity(Self::preallocated_pieces(n_pieces));
 
         for (entry_path, length) in entries {
-            let mut file = BufReader::new(std::fs::File::open(&entry_path)?);
+            let mut file = BufReader::new(Self::open_entry(&entry_path, 0, pins)?);
             let mut file_remaining = length;
 
             while file_remaining > 0 {
-                if torrent_build.is_canceled() {
-                    return Err(LavaTorrentError::TorrentBuilderFailure(Cow::Borrowed(
-                        "build canceled by client",
-                    )));
-                }
-
                 // calculate the # of bytes to read in this iteration
-                let piece_filled = util::usize_to_u64(piece.len())?;
-                let piece_remaining = piece_length_u64 - piece_filled;
+                let piece_remaining = split_length - piece_filled;
                 let to_read = if file_remaining < piece_remaining {
                     file_remaining
                 } else {
                     piece_remaining
                 };
 
-                // read bytes
-                file.by_ref().take(to_read).read_to_end(&mut piece)?;
+                // read + hash bytes
+                Self::hash_from_reader(&mut file, to_read, &mut hasher, &mut buf, &|| Ok(()))?;
                 file_remaining -= to_read;
+                piece_filled += to_read;
+                max_filled = max_filled.max(piece_filled);
 
-                // if piece is completely filled, hash it
-                if piece.len() == piece_length_usize {
-                    pieces.push(Sha1::digest(&piece).to_vec());
-                    piece.clear();
-                    torrent_build.inc_piece_processed();
+                // if piece is completely filled, finalize its hash
+                if piece_filled == split_length {
+                    pieces.push(hasher.finalize_reset().into());
+                    piece_filled = 0;
                 }
             }
-
-            // Unwrap is fine here since path is by definition
-            // a parent to entry_path and path is canonicalized
-            // before this call. Thus this should never fail.
-            files.push(File {
-                length: util::u64_to_i64(length)?,
-                path: entry_path.strip_prefix(&path).unwrap().to_path_buf(),
-                extra_fields: None,
-            });
         }
 
         // if piece is empty then the total file size is divisible by the piece length
         // otherwise the last piece is partially filled and we have to hash it
-        if !piece.is_empty() {
-            pieces.push(Sha1::digest(&piece).to_vec());
-            piece.clear();
-            torrent_build.inc_piece_processed();
+        if piece_filled > 0 {
+            pieces.push(hasher.finalize().into());
         }
+        Self::check_piece_filled(max_filled, piece_length_u64)?;
 
         Ok((util::u64_to_i64(total_length)?, files, pieces))
     }
 
-    fn read_dir_parallel_non_blocking<P>(
+    // To parallelize read_dir(), we first find the chunk(s) of file(s) that belong to
+    // each piece with map_pieces(). Then we can process the pieces in parallel.
+    // In other words, we generate the jobs first and then hand out the jobs to threads.
+    //
+    // @todo: The current implementation is not very memory efficient for a large dir.
+    // In the future it might be wise to switch to an iterator-based implementation.
+    fn read_dir_parallel<P>(
         path: P,
+        entries: Vec<(PathBuf, u64)>,
         piece_length: Integer,
+        chunk_size: usize,
         num_threads: usize,
-        torrent_build: TorrentBuildInternal,
     ) -> Result<(Integer, Vec<File>, Vec<Piece>), LavaTorrentError>
     where
         P: AsRef<Path>,
     {
+        let total_length = Self::total_length(&entries)?;
+        let pieces = Self::map_pieces(&entries, util::i64_to_u64(piece_length)?)?;
+        let files = Self::entries_to_files(&path, &entries)?;
+
+        // hash the pieces
+        let thread_pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(num_threads)
+            .build()
+            .map_err(|e| {
+                LavaTorrentError::TorrentBuilderFailure(Cow::Owned(format!(
+                    "failed to create rayon thread pool: {}",
+                    e
+                )))
+            })?;
+
+        let pieces = thread_pool.install(|| {
+            pieces
+                .into_par_iter()
+                .map(|chunks| {
+                    let mut buf = vec![0; chunk_size];
+                    let mut hasher = Sha1::new();
+                    for (index, offset, len) in chunks {
+                        let mut file =
+                            Self::open_entry(&entries[index].0, offset, &FilePins::default())?;
+                        Self::hash_from_reader(&mut file, len, &mut hasher, &mut buf, &|| Ok(()))?;
+                    }
+                    Ok(hasher.finalize().into())
+                })
+                .collect::<Result<Vec<Piece>, LavaTorrentError>>()
+        })?;
+
+        Ok((util::u64_to_i64(total_length)?, files, pieces))
+    }
+
+    // Unlike read_dir_parallel(), the pipelined implementation never seeks:
+    // a dedicated reader thread reads `entries` strictly in order (which is
+    // what spinning disks like) and fills one piece buffer at a time. Full
+    // buffers are sent over a bounded channel to the thread pool, which only
+    // hashes them. Since pieces may be hashed out of order, each buffer is
+    // tagged with its index and the hashes are sorted afterwards.
+    fn hash_pipelined(
+        entries: &[(PathBuf, u64)],
+        piece_length: u64,
+        chunk_size: usize,
+        num_threads: usize,
+        pipeline_depth: usize,
+        check_canceled: &(dyn Fn() -> Result<(), LavaTorrentError> + Sync),
+        inc_piece_processed: &(dyn Fn() + Sync),
+    ) -> Result<Vec<Piece>, LavaTorrentError> {
+        // no piece is longer than all entries combined, so this caps
+        // the allocations below even if `piece_length` is huge
+        let piece_capacity = util::u64_to_usize(piece_length.min(Self::total_length(entries)?))?;
+        let chunk_size_u64 = util::usize_to_u64(chunk_size)?;
+        let (sender, receiver) = std::sync::mpsc::sync_channel::<(usize, Vec<u8>)>(pipeline_depth);
+
+        let thread_pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(num_threads)
+            .build()
+            .map_err(|e| {
+                LavaTorrentError::TorrentBuilderFailure(Cow::Owned(format!(
+                    "failed to create rayon thread pool: {}",
+                    e
+                )))
+            })?;
+
+        // computed here rather than in the reader thread,
+        // as tests skew it per thread
+        let split_length = Self::split_length(piece_length);
+
+        std::thread::scope(|scope| {
+            let reader = scope.spawn(move || -> Result<(), LavaTorrentError> {
+                let mut max_filled = 0;
+                let mut index = 0;
+                let mut piece = Vec::with_capacity(piece_capacity);
+
+                for (entry_path, length) in entries {
+                    let mut file = Self::open_entry(entry_path, 0, &FilePins::default())?;
+                    let mut file_remaining = *length;
+
+                    while file_remaining > 0 {
+                        check_canceled()?;
+
+                        // read at most 1 chunk, without going past the piece's end
+                        let filled = piece.len();
+                        let to_read = (split_length - util::usize_to_u64(filled)?)
+                            .min(file_remaining)
+                            .min(chunk_size_u64);
+                        piece.resize(filled + util::u64_to_usize(to_read)?, 0);
+                        file.read_exact(&mut piece[filled..])?;
+                        file_remaining -= to_read;
+                        max_filled = max_filled.max(util::usize_to_u64(piece.len())?);
+
+                        // if piece is completely filled, hand it over
+                        if util::usize_to_u64(piece.len())? == split_length {
+                            let full =
+                                std::mem::replace(&mut piece, Vec::with_capacity(piece_capacity));
+                            // the receiving end only hangs up if hashing failed,
+                            // in which case there is no point in reading on
+                            if sender.send((index, full)).is_err() {
+                                return Ok(());
+                            }
+                            index += 1;
+                        }
+                    }
+                }
+
+                // hand over the last, partially filled piece (if any)
+                if !piece.is_empty() {
+                    let _ = sender.send((index, piece));
+                }
+                Self::check_piece_filled(max_filled, piece_length)
+            });
+
+            let mut pieces = thread_pool.install(|| {
+                receiver
+                    .into_iter()
+                    .par_bridge()
+                    .map(|(index, piece)| {
+                        let hash: Piece = Sha1::digest(&piece).into();
+                        inc_piece_processed();
+                        (index, hash)
+                    })
+                    .collect::<Vec<(usize, Piece)>>()
+            });
+
+            // the channel is closed once the reader is done, so this doesn't block
+            reader
+                .join()
+                .unwrap_or_else(|e| std::panic::resume_unwind(e))?;
+
+            pieces.sort_unstable_by_key(|&(index, _)| index);
+            Ok(pieces.into_iter().map(|(_, hash)| hash).collect())
+        })
+    }
+
+    fn read_file_pipelined<P>(
+        path: P,
+        piece_length: Integer,
+        chunk_size: usize,
+        num_threads: usize,
+        pipeline_depth: usize,
+    ) -> Result<(Integer, Vec<Piece>), LavaTorrentError>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let length = path.metadata()?.len();
+        let pieces = Self::hash_pipelined(
+            &[(path.to_path_buf(), length)],
+            util::i64_to_u64(piece_length)?,
+            chunk_size,
+            num_threads,
+            pipeline_depth,
+            &|| Ok(()),
+            &|| (),
+        )?;
+
+        Ok((util::u64_to_i64(length)?, pieces))
+    }
+
+    fn read_dir_pipelined<P>(
+        path: P,
+        entries: Vec<(PathBuf, u64)>,
+        piece_length: Integer,
+        chunk_size: usize,
+        num_threads: usize,
+        pipeline_depth: usize,
+    ) -> Result<(Integer, Vec<File>, Vec<Piece>), LavaTorrentError>
+    where
+        P: AsRef<Path>,
+    {
+        let total_length = Self::total_length(&entries)?;
+        let pieces = Self::hash_pipelined(
+            &entries,
+            util::i64_to_u64(piece_length)?,
+            chunk_size,
+            num_threads,
+            pipeline_depth,
+            &|| Ok(()),
+            &|| (),
+        )?;
+
+        let files = Self::entries_to_files(&path, &entries)?;
+
+        Ok((util::u64_to_i64(total_length)?, files, pieces))
+    }
+
+    fn read_file_non_blocking<P>(
+        path: P,
+        piece_length: Integer,
+        chunk_size: usize,
+        pins: &FilePins,
+        torrent_build: TorrentBuildInternal,
+    ) -> Result<(Integer, Vec<Piece>), LavaTorrentError>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let length = path.metadata()?.len();
+        let piece_length = util::i64_to_u64(piece_length)?;
+        let n_pieces = Self::piece_count_u64(length, piece_length)?;
+        torrent_build.set_piece_total(n_pieces);
+
+        // read file content + calculate pieces/hashes
+        let mut file = BufReader::new(pins.open(path)?);
+        let mut buf = vec![0; chunk_size];
+        let mut hasher = Sha1::new();
+        let mut pieces = Vec::with_capacity(Self::preallocated_pieces(n_pieces));
+        let mut remaining = length;
+        let split_length = Self::split_length(piece_length);
+        let mut max_filled = 0;
+
+        while remaining > 0 {
+            let to_read = remaining.min(split_length);
+            max_filled = max_filled.max(to_read);
+            Self::hash_from_reader(&mut file, to_read, &mut hasher, &mut buf, &|| {
+                torrent_build.check_canceled()
+            })?;
+            remaining -= to_read;
+
+            pieces.push(hasher.finalize_reset().into());
+            torrent_build.inc_piece_processed();
+        }
+        Self::check_piece_filled(max_filled, piece_length)?;
+
+        Ok((util::u64_to_i64(length)?, pieces))
+    }
+
+    fn read_file_parallel_non_blocking<P>(
+        path: P,
+        piece_length: Integer,
+        chunk_size: usize,
+        num_threads: usize,
+        torrent_build: TorrentBuildInternal,
+    ) -> Result<(Integer, Vec<Piece>), LavaTorrentError>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let length = path.metadata()?.len();
         let piece_length_u64 = util::i64_to_u64(piece_length)?;
-        let piece_length_usize = util::u64_to_usize(piece_length_u64)?;
-        let entries = util::list_dir(&path)?;
-        let total_length = entries.iter().fold(0, |acc, &(_, len)| acc + len);
-        let n_pieces = (total_length + (piece_length_u64 - 1)) / piece_length_u64;
-        let mut pieces = vec![vec![]; util::u64_to_usize(n_pieces)?];
-        let mut files = Vec::with_capacity(entries.len());
+        let n_pieces = Self::piece_count_u64(length, piece_length_u64)?;
         torrent_build.set_piece_total(n_pieces);
 
-        // find each piece's chunks
-        let mut pieces_iter = pieces.iter_mut();
-        let mut piece = pieces_iter.next().unwrap();
-        let mut piece_remaining = piece_length_u64;
+        let thread_pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(num_threads)
+            .build()
+            .map_err(|e| {
+                LavaTorrentError::TorrentBuilderFailure(Cow::Owned(format!(
+                    "failed to create rayon thread pool: {}",
+                    e
+                )))
+            })?;
+
+        let pieces = thread_pool.install(|| {
+            (0_u64..n_pieces)
+                .into_par_iter()
+                .map(|i| {
+                    torrent_build.check_canceled()?;
+                    let mut file = std::fs::File::open(path)?;
+                    let mut buf = vec![0; chunk_size];
+                    let mut hasher = Sha1::new();
+                    let offset = i * piece_length_u64;
+                    file.seek(std::io::SeekFrom::Start(offset))?;
+                    let to_read = piece_length_u64.min(length - offset);
+                    Self::hash_from_reader(&mut file, to_read, &mut hasher, &mut buf, &|| {
+                        torrent_build.check_canceled()
+                    })?;
+                    torrent_build.inc_piece_processed();
+                    Ok(hasher.finalize().into())
+                })
+                .collect::<Result<Vec<Piece>, LavaTorrentError>>()
+        })?;
+
+        Ok((util::u64_to_i64(length)?, pieces))
+    }
+
+    fn read_dir_non_blocking<P>(
+        path: P,
+        entries: Vec<(PathBuf, u64)>,
+        piece_length: Integer,
+        chunk_size: usize,
+        pins: &FilePins,
+        torrent_build: TorrentBuildInternal,
+    ) -> Result<(Integer, Vec<File>, Vec<Piece>), LavaTorrentError>
+    where
+        P: AsRef<Path>,
+    {
+        let piece_length_u64 = util::i64_to_u64(piece_length)?;
+        let total_length = Self::total_length(&entries)?;
+        let n_pieces = Self::piece_count_u64(total_length, piece_length_u64)?;
+        let files = Self::entries_to_files(&path, &entries)?;
+        let mut buf = vec![0; chunk_size];
+        let mut hasher = Sha1::new();
+        let mut piece_filled = 0;
+        let split_length = Self::split_length(piece_length_u64);
+        let mut max_filled = 0;
+        let mut pieces = Vec::with_capacity(Self::preallocated_pieces(n_pieces));
+        torrent_build.set_piece_total(n_pieces);
 
         for (entry_path, length) in entries {
-            let entry_path = Arc::new(entry_path);
+            let mut file = BufReader::new(Self::open_entry(&entry_path, 0, pins)?);
             let mut file_remaining = length;
 
             while file_remaining > 0 {
-                if torrent_build.is_canceled() {
-                    return Err(LavaTorrentError::TorrentBuilderFailure(Cow::Borrowed(
-                        "build canceled by client",
-                    )));
-                }
-
-                // rotate to next piece when appropriate
-                if piece_remaining == 0 {
-                    piece = pieces_iter.next().unwrap();
-                    piece_remaining = piece_length_u64;
-                }
-
-                // calculate the # of bytes to allocate in this iteration
-                let to_allocate = if file_remaining < piece_remaining {
+                // calculate the # of bytes to read in this iteration
+                let piece_remaining = split_length - piece_filled;
+                let to_read = if file_remaining < piece_remaining {
                     file_remaining
                 } else {
                     piece_remaining
                 };
 
-                // save chunk as (file path, start pos in file, chunk length)
-                piece.push((entry_path.clone(), length - file_remaining, to_allocate));
+                // read + hash bytes
+                Self::hash_from_reader(&mut file, to_read, &mut hasher, &mut buf, &|| {
+                    torrent_build.check_canceled()
+                })?;
+                file_remaining -= to_read;
+                piece_filled += to_read;
+                max_filled = max_filled.max(piece_filled);
 
-                // update counters
-                piece_remaining -= to_allocate;
-                file_remaining -= to_allocate;
+                // if piece is completely filled, finalize its hash
+                if piece_filled == split_length {
+                    pieces.push(hasher.finalize_reset().into());
+                    piece_filled = 0;
+                    torrent_build.inc_piece_processed();
+                }
             }
+        }
 
-            // Unwrap is fine here since path is by definition
-            // a parent to entry_path and path is canonicalized
-            // before this call. Thus this should never fail.
-            files.push(File {
-                length: util::u64_to_i64(length)?,
-                path: entry_path.strip_prefix(&path).unwrap().to_path_buf(),
-                extra_fields: None,
-            });
+        // if piece is empty then the total file size is divisible by the piece length
+        // otherwise the last piece is partially filled and we have to hash it
+        if piece_filled > 0 {
+            pieces.push(hasher.finalize().into());
+            torrent_build.inc_piece_processed();
         }
+        Self::check_piece_filled(max_filled, piece_length_u64)?;
+
+        Ok((util::u64_to_i64(total_length)?, files, pieces))
+    }
+
+    fn read_file_pipelined_non_blocking<P>(
+        path: P,
+        piece_length: Integer,
+        chunk_size: usize,
+        num_threads: usize,
+        pipeline_depth: usize,
+        torrent_build: TorrentBuildInternal,
+    ) -> Result<(Integer, Vec<Piece>), LavaTorrentError>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let length = path.metadata()?.len();
+        let piece_length = util::i64_to_u64(piece_length)?;
+        torrent_build.set_piece_total(Self::piece_count_u64(length, piece_length)?);
+
+        let pieces = Self::hash_pipelined(
+            &[(path.to_path_buf(), length)],
+            piece_length,
+            chunk_size,
+            num_threads,
+            pipeline_depth,
+            &|| torrent_build.check_canceled(),
+            &|| torrent_build.inc_piece_processed(),
+        )?;
+
+        Ok((util::u64_to_i64(length)?, pieces))
+    }
+
+    fn read_dir_pipelined_non_blocking<P>(
+        path: P,
+        entries: Vec<(PathBuf, u64)>,
+        piece_length: Integer,
+        chunk_size: usize,
+        num_threads: usize,
+        pipeline_depth: usize,
+        torrent_build: TorrentBuildInternal,
+    ) -> Result<(Integer, Vec<File>, Vec<Piece>), LavaTorrentError>
+    where
+        P: AsRef<Path>,
+    {
+        let piece_length = util::i64_to_u64(piece_length)?;
+        let total_length = Self::total_length(&entries)?;
+        torrent_build.set_piece_total(Self::piece_count_u64(total_length, piece_length)?);
+
+        let pieces = Self::hash_pipelined(
+            &entries,
+            piece_length,
+            chunk_size,
+            num_threads,
+            pipeline_depth,
+            &|| torrent_build.check_canceled(),
+            &|| torrent_build.inc_piece_processed(),
+        )?;
+
+        let files = Self::entries_to_files(&path, &entries)?;
+
+        Ok((util::u64_to_i64(total_length)?, files, pieces))
+    }
+
+    fn read_dir_parallel_non_blocking<P>(
+        path: P,
+        entries: Vec<(PathBuf, u64)>,
+        piece_length: Integer,
+        chunk_size: usize,
+        num_threads: usize,
+        torrent_build: TorrentBuildInternal,
+    ) -> Result<(Integer, Vec<File>, Vec<Piece>), LavaTorrentError>
+    where
+        P: AsRef<Path>,
+    {
+        let total_length = Self::total_length(&entries)?;
+        let pieces = Self::map_pieces(&entries, util::i64_to_u64(piece_length)?)?;
+        let files = Self::entries_to_files(&path, &entries)?;
+        torrent_build.set_piece_total(util::usize_to_u64(pieces.len())?);
+        torrent_build.check_canceled()?;
 
         // hash the pieces
         let thread_pool = rayon::ThreadPoolBuilder::new()
@@ -984,26 +3064,100 @@ impl TorrentBuilder {
             pieces
                 .into_par_iter()
                 .map(|chunks| {
-                    if torrent_build.is_canceled() {
-                        Err(LavaTorrentError::TorrentBuilderFailure(Cow::Borrowed(
-                            "build canceled by client",
-                        )))
-                    } else {
-                        let mut bytes = Vec::with_capacity(piece_length_usize);
-                        for (file, offset, len) in chunks {
-                            let mut file = std::fs::File::open(file.as_ref())?;
-                            file.seek(std::io::SeekFrom::Start(offset))?;
-                            file.take(len).read_to_end(&mut bytes)?;
-                        }
-                        torrent_build.inc_piece_processed();
-                        Ok(Sha1::digest(&bytes).to_vec())
+                    torrent_build.check_canceled()?;
+                    let mut buf = vec![0; chunk_size];
+                    let mut hasher = Sha1::new();
+                    for (index, offset, len) in chunks {
+                        let mut file =
+                            Self::open_entry(&entries[index].0, offset, &FilePins::default())?;
+                        Self::hash_from_reader(&mut file, len, &mut hasher, &mut buf, &|| {
+                            torrent_build.check_canceled()
+                        })?;
                     }
+                    torrent_build.inc_piece_processed();
+                    Ok(hasher.finalize().into())
                 })
-                .collect::<Result<Vec<Vec<u8>>, LavaTorrentError>>()
+                .collect::<Result<Vec<Piece>, LavaTorrentError>>()
         })?;
 
         Ok((util::u64_to_i64(total_length)?, files, pieces))
     }
+
+    // Like read_dir_non_blocking(), but starting after the pieces that are
+    // already in `checkpoint`. `checkpoint` is saved to `checkpoint_path`
+    // every `interval` new pieces, and once more at the end if needed.
+    fn read_entries_resumable(
+        entries: &[(PathBuf, u64)],
+        mut checkpoint: BuildCheckpoint,
+        checkpoint_path: &Path,
+        interval: usize,
+        chunk_size: usize,
+        pins: &FilePins,
+        torrent_build: &TorrentBuildInternal,
+    ) -> Result<Vec<Piece>, LavaTorrentError> {
+        let piece_length = util::i64_to_u64(checkpoint.piece_length)?;
+        let total_length = Self::total_length(entries)?;
+        let n_done = util::usize_to_u64(checkpoint.pieces.len())?;
+        torrent_build.set_piece_total(Self::piece_count_u64(total_length, piece_length)?);
+        torrent_build.set_piece_processed(n_done);
+
+        let mut buf = vec![0; chunk_size];
+        let mut hasher = Sha1::new();
+        let mut piece_filled = 0;
+        let split_length = Self::split_length(piece_length);
+        let mut max_filled = 0;
+        let mut n_unsaved = 0;
+        // the # of bytes covered by the pieces in `checkpoint`
+        let mut to_skip = n_done.saturating_mul(piece_length).min(total_length);
+
+        for (entry_path, length) in entries {
+            if to_skip >= *length {
+                to_skip -= length;
+                continue;
+            }
+
+            let mut file = BufReader::new(Self::open_entry(entry_path, to_skip, pins)?);
+            let mut file_remaining = length - to_skip;
+            to_skip = 0;
+
+            while file_remaining > 0 {
+                let to_read = file_remaining.min(split_length - piece_filled);
+                Self::hash_from_reader(&mut file, to_read, &mut hasher, &mut buf, &|| {
+                    torrent_build.check_canceled()
+                })?;
+                file_remaining -= to_read;
+                piece_filled += to_read;
+                max_filled = max_filled.max(piece_filled);
+
+                if piece_filled == split_length {
+                    checkpoint.pieces.push(hasher.finalize_reset().into());
+                    piece_filled = 0;
+                    n_unsaved += 1;
+                    // save before reporting progress, so that the
+                    // progress never includes unsaved pieces
+                    if n_unsaved == interval {
+                        checkpoint.save(checkpoint_path)?;
+                        n_unsaved = 0;
+                    }
+                    torrent_build.inc_piece_processed();
+                }
+            }
+        }
+
+        if piece_filled > 0 {
+            checkpoint.pieces.push(hasher.finalize().into());
+            n_unsaved += 1;
+        }
+        if n_unsaved > 0 {
+            checkpoint.save(checkpoint_path)?;
+        }
+        if piece_filled > 0 {
+            torrent_build.inc_piece_processed();
+        }
+        Self::check_piece_filled(max_filled, piece_length)?;
+
+        Ok(checkpoint.pieces)
+    }
 }
 
 impl TorrentBuild {
@@ -1052,102 +3206,975 @@ impl TorrentBuild {
     pub fn get_progress(&self) -> u8 {
         let n_piece_total = self.n_piece_total.load(Ordering::Acquire);
 
-        // in case get_progress() is called before n_piece_total is initialized
-        if n_piece_total == 0 {
-            return 0;
-        }
+        // in case get_progress() is called before n_piece_total is initialized
+        if n_piece_total == 0 {
+            return 0;
+        }
+
+        let n_piece_processed = self.n_piece_processed.load(Ordering::Acquire);
+
+        (n_piece_processed * 100 / n_piece_total) as u8
+    }
+
+    /// Get the number of pieces that have been processed so far.
+    pub fn get_n_piece_processed(&self) -> u64 {
+        self.n_piece_processed.load(Ordering::Acquire)
+    }
+
+    /// Get the total number of pieces to be processed.
+    ///
+    /// Note that 0 will be returned in at least 2 cases:
+    /// - the torrent contains only empty files/dirs
+    /// - the actual value has not been calculated yet
+    pub fn get_n_piece_total(&self) -> u64 {
+        self.n_piece_total.load(Ordering::Acquire)
+    }
+
+    /// Cancel the torrent build.
+    ///
+    /// `cancel()` does not consume the `TorrentBuild`. If you want, you can call
+    /// [`drop()`] yourself.
+    ///
+    /// Calling [`get_output()`] after `cancel()` will most likely give you an
+    /// [`Err(LavaTorrentError::TorrentBuilderFailure)`], but it's also possible
+    /// for you to get an `Ok(torrent)` (if you cancel after all pieces have been hashed).
+    ///
+    /// The build checks for cancellation before each read of (at most)
+    /// [`chunk_size`] bytes, so it stops promptly even with large pieces.
+    ///
+    /// [`chunk_size`]: TorrentBuilder::set_chunk_size
+    /// [`drop()`]: https://doc.rust-lang.org/std/mem/fn.drop.html
+    /// [`get_output()`]: #method.get_output
+    /// [`Err(LavaTorrentError::TorrentBuilderFailure)`]: ../../enum.LavaTorrentError.html#variant.TorrentBuilderFailure
+    pub fn cancel(&self) {
+        self.is_canceled.store(true, Ordering::Release)
+    }
+
+    /// Retrieve the output of the torrent build.
+    ///
+    /// This function will block if the build has not finished yet. You can
+    /// use [`is_finished()`] to check if the build has finished.
+    ///
+    /// [`is_finished()`]: #method.is_finished
+    pub fn get_output(mut self) -> Result<Torrent, LavaTorrentError> {
+        self.builder_thread.take().unwrap().join().map_err(|e| {
+            LavaTorrentError::TorrentBuilderFailure(Cow::Owned(format!(
+                "builder thread has unexpectedly panicked: {:?}",
+                e
+            )))
+        })?
+    }
+
+    /// Check if the torrent build has finished.
+    pub fn is_finished(&self) -> bool {
+        self.builder_thread.as_ref().unwrap().is_finished()
+    }
+}
+
+impl DryRunTorrent {
+    /// Return the exact length of the *.torrent* file that would be
+    /// produced by encoding the built `Torrent`.
+    ///
+    /// Each piece takes up 20 bytes regardless of its actual hash, so
+    /// this is computed by encoding `self` with placeholder pieces.
+    pub fn estimated_encoded_len(&self) -> usize {
+        Torrent {
+            announce: self.announce.clone(),
+            announce_list: self.announce_list.clone(),
+            length: self.length,
+            files: self.files.clone(),
+            name: self.name.clone(),
+            piece_length: self.piece_length,
+            pieces: vec![[0; PIECE_STRING_LENGTH]; self.piece_count],
+            private: self.private,
+            extra_fields: self.extra_fields.clone(),
+            extra_info_fields: self.extra_info_fields.clone(),
+        }
+        .into_bencode_elem()
+        .encode()
+        .len()
+    }
+}
+
+impl Torrent {
+    /// Assemble a torrent from `pieces` that were computed elsewhere (e.g.
+    /// by hashing shards of the content on other machines), without
+    /// reading the content.
+    ///
+    /// `files` are in the order they were hashed in, and `pieces` cover their
+    /// concatenation, as when building. For a single-file torrent, pass an
+    /// empty `files` and set [`AssembleOptions::length`] instead.
+    ///
+    /// `Err(InvalidArgument)` is returned if:
+    /// - `name` or `piece_length` would be rejected by [`TorrentBuilder`].
+    /// - a file has a negative length or an unsafe path (e.g. with a `..`
+    ///   component), or the total length is not positive.
+    /// - the # of `pieces` is not exactly the # required for the total length.
+    /// - anything in `options` would be rejected by the equivalent
+    ///   [`TorrentBuilder`] setting.
+    pub fn from_pieces(
+        name: String,
+        piece_length: Integer,
+        files: Vec<File>,
+        pieces: Vec<Piece>,
+        options: AssembleOptions,
+    ) -> Result<Torrent, LavaTorrentError> {
+        const OWNER: &str = "Assembled torrent";
+        let invalid = |m: String| LavaTorrentError::InvalidArgument(Cow::Owned(m));
+        // the builder never has empty dictionaries, so don't reject them
+        let extra_fields = options.extra_fields.filter(|fields| !fields.is_empty());
+        let extra_info_fields = options
+            .extra_info_fields
+            .filter(|fields| !fields.is_empty());
+
+        TorrentBuilder::check_name(OWNER, Some(&name))?;
+        TorrentBuilder::check_piece_length(OWNER, piece_length)?;
+        TorrentBuilder::check_announce(OWNER, options.announce.as_deref())?;
+        TorrentBuilder::check_announce_list(OWNER, options.announce_list.as_ref())?;
+        TorrentBuilder::check_extra_fields(OWNER, "extra_fields", extra_fields.as_ref())?;
+        TorrentBuilder::check_extra_fields(OWNER, "extra_info_fields", extra_info_fields.as_ref())?;
+
+        let (length, files) = match (files.is_empty(), options.length) {
+            (true, Some(length)) => (length, None),
+            (true, None) => {
+                return Err(invalid(format!(
+                    "{} has no `files` but `length` is not set.",
+                    OWNER
+                )));
+            }
+            (false, Some(_)) => {
+                return Err(invalid(format!(
+                    "{} has `files` but `length` is set as well.",
+                    OWNER
+                )));
+            }
+            (false, None) => {
+                for (index, file) in files.iter().enumerate() {
+                    if file.length < 0 {
+                        return Err(invalid(format!(
+                            "{} has file {} whose length < 0.",
+                            OWNER, index
+                        )));
+                    } else if !Torrent::is_safe_path(&file.path) {
+                        return Err(invalid(format!(
+                            "{} has file {} whose path is unsafe: {:?}.",
+                            OWNER, index, file.path
+                        )));
+                    }
+                }
+                let length = files
+                    .iter()
+                    .try_fold(0 as Integer, |total, file| total.checked_add(file.length))
+                    .ok_or_else(|| {
+                        invalid(format!(
+                            "{} has files whose lengths overflowed in i64.",
+                            OWNER
+                        ))
+                    })?;
+                (length, Some(files))
+            }
+        };
+
+        if length <= 0 {
+            return Err(invalid(format!("{} has `length` <= 0.", OWNER)));
+        }
+        match Torrent::expected_piece_count(length, piece_length) {
+            Some(expected) if expected == pieces.len() => (),
+            Some(expected) => {
+                return Err(invalid(format!(
+                    "{} has {} pieces, {} expected for length {}.",
+                    OWNER,
+                    pieces.len(),
+                    expected,
+                    length
+                )));
+            }
+            None => {
+                return Err(invalid(format!(
+                    "{} has more pieces than `usize` can count.",
+                    OWNER
+                )));
+            }
+        }
+
+        Ok(Torrent {
+            announce: options.announce,
+            announce_list: options.announce_list,
+            length,
+            files,
+            name,
+            piece_length,
+            pieces,
+            private: if options.private { Some(true) } else { None },
+            extra_fields,
+            extra_info_fields,
+        })
+    }
+
+    /// Check the content of `self` at `content_path` against its `pieces`,
+    /// and return the indices of the pieces that don't match.
+    ///
+    /// `content_path` is what the torrent would be built from, i.e. the file
+    /// itself for a single-file torrent, or the directory containing `files`
+    /// for a multi-file one. Missing or truncated files don't fail the check,
+    /// they make the pieces covering them bad instead. Padding files are not read.
+    ///
+    /// Pieces are laid out exactly as when building, so the indices can
+    /// be looked up in the piece map kept by [`TorrentBuilder::set_keep_piece_map()`].
+    ///
+    /// [`TorrentBuilder::set_keep_piece_map()`]: struct.TorrentBuilder.html#method.set_keep_piece_map
+    pub fn validate_files<P>(&self, content_path: P) -> Result<Vec<usize>, LavaTorrentError>
+    where
+        P: AsRef<Path>,
+    {
+        let entries = self.content_entries(content_path.as_ref())?;
+        let piece_map = TorrentBuilder::map_pieces(&entries, util::i64_to_u64(self.piece_length)?)?;
+
+        let mut bad_pieces = Vec::new();
+        // chunks are in order, so files can be kept open across pieces
+        let mut opened: Option<(usize, std::fs::File)> = None;
+        for (index, chunks) in piece_map.into_iter().enumerate() {
+            let mut hasher = Sha1::new();
+            let mut is_complete = true;
+
+            for (entry, offset, len) in chunks {
+                let n_read = match entries[entry].0 {
+                    Some(ref path) => {
+                        if opened.as_ref().map(|&(opened, _)| opened) != Some(entry) {
+                            opened = match std::fs::File::open(path) {
+                                Ok(file) => Some((entry, file)),
+                                Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => None,
+                                Err(e) => return Err(e.into()),
+                            };
+                        }
+                        match opened {
+                            Some((_, ref mut file)) => {
+                                file.seek(std::io::SeekFrom::Start(offset))?;
+                                std::io::copy(&mut file.take(len), &mut hasher)?
+                            }
+                            None => 0,
+                        }
+                    }
+                    None => std::io::copy(&mut std::io::repeat(0).take(len), &mut hasher)?,
+                };
+
+                if n_read < len {
+                    is_complete = false;
+                    break;
+                }
+            }
+
+            let piece: Piece = hasher.finalize().into();
+            if !is_complete || piece != self.pieces[index] {
+                bad_pieces.push(index);
+            }
+        }
+
+        Ok(bad_pieces)
+    }
+
+    // The files holding the content of `self` at `content_path`, as
+    // (path, length). Padding files have no path since they are not read.
+    // Fails if `files` or `pieces` don't match `length`, or if a path
+    // would escape `content_path`.
+    fn content_entries(
+        &self,
+        content_path: &Path,
+    ) -> Result<Vec<(Option<PathBuf>, u64)>, LavaTorrentError> {
+        if Torrent::expected_piece_count(self.length, self.piece_length) != Some(self.pieces.len())
+        {
+            return Err(LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
+                "Torrent has {} pieces, which does not match its length {} and piece length {}.",
+                self.pieces.len(),
+                self.length,
+                self.piece_length,
+            ))));
+        }
+
+        match self.files {
+            Some(ref files) => {
+                let sum = files
+                    .iter()
+                    .try_fold(0 as Integer, |sum, file| sum.checked_add(file.length));
+                if sum != Some(self.length) {
+                    return Err(LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
+                        "Sum of file lengths != torrent's length {}.",
+                        self.length
+                    ))));
+                }
+                files
+                    .iter()
+                    .enumerate()
+                    .map(|(index, file)| {
+                        if !Torrent::is_safe_path(&file.path) {
+                            Err(LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
+                                "Path of file {} is unsafe: {:?}.",
+                                index, file.path
+                            ))))
+                        } else if file.is_padding() {
+                            Ok((None, util::i64_to_u64(file.length)?))
+                        } else {
+                            Ok((
+                                Some(content_path.join(file.to_path_buf())),
+                                util::i64_to_u64(file.length)?,
+                            ))
+                        }
+                    })
+                    .collect()
+            }
+            None => Ok(vec![(
+                Some(content_path.to_path_buf()),
+                util::i64_to_u64(self.length)?,
+            )]),
+        }
+    }
+
+    /// Compute a [`ContentFingerprint`] of the file or directory at `path`.
+    ///
+    /// The files are listed the same way [`TorrentBuilder::build()`] does
+    /// (with symlinks followed and [`FileIssuePolicy::Fail`]), but only their
+    /// paths, sizes, and modification times are digested. This is much cheaper than hashing
+    /// the content, while still detecting files being modified, added,
+    /// or removed.
+    ///
+    /// [`TorrentBuilder::build()`]: struct.TorrentBuilder.html#method.build
+    pub fn content_fingerprint(path: &Path) -> Result<ContentFingerprint, LavaTorrentError> {
+        ContentFingerprint::compute(&path.canonicalize()?, true, FileIssuePolicy::Fail)
+    }
+}
+
+impl ContentFingerprint {
+    /// Check if `self` and `other` were taken from the same content.
+    pub fn matches(&self, other: &ContentFingerprint) -> bool {
+        self == other
+    }
+
+    /// Return the raw digest, e.g. for persisting `self`.
+    pub fn as_bytes(&self) -> &[u8; 20] {
+        &self.digest
+    }
+
+    fn compute(
+        root: &Path,
+        follow_symlinks: bool,
+        policy: FileIssuePolicy,
+    ) -> Result<ContentFingerprint, LavaTorrentError> {
+        let metadata = root.metadata()?;
+        let entries = if metadata.is_dir() {
+            // the same walk as `build()`, so that both see the same files
+            TorrentBuilder::walk_dir(
+                root,
+                follow_symlinks,
+                policy,
+                &|| Ok(()),
+                &mut BuildReport::default(),
+                &mut FilePins::default(),
+            )?
+        } else {
+            vec![(root.to_path_buf(), metadata.len())]
+        };
+
+        // every field is length-prefixed or fixed-size,
+        // so that different trees cannot produce the same input
+        let mut hasher = Sha1::new();
+        hasher.update(util::usize_to_u64(entries.len())?.to_be_bytes());
+        for (path, length) in entries {
+            // Unwrap is fine here since `root` is by definition a parent
+            // to `path`. The result is empty for a single file.
+            let components = TorrentBuilder::path_components(path.strip_prefix(root).unwrap());
+            hasher.update(util::usize_to_u64(components.len())?.to_be_bytes());
+            for component in components {
+                hasher.update(util::usize_to_u64(component.len())?.to_be_bytes());
+                hasher.update(component);
+            }
+            hasher.update(length.to_be_bytes());
+
+            let metadata = if follow_symlinks {
+                path.metadata()?
+            } else {
+                path.symlink_metadata()?
+            };
+            // mtime might not be available on all platforms
+            match metadata.modified().ok().map(|modified| {
+                modified
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map_err(|e| e.duration())
+            }) {
+                Some(Ok(after)) => Self::update_time(&mut hasher, 1, after),
+                Some(Err(before)) => Self::update_time(&mut hasher, 2, before),
+                None => hasher.update([0]),
+            }
+        }
+
+        Ok(ContentFingerprint {
+            digest: hasher.finalize().into(),
+        })
+    }
+
+    fn update_time(hasher: &mut Sha1, tag: u8, time: std::time::Duration) {
+        hasher.update([tag]);
+        hasher.update(time.as_secs().to_be_bytes());
+        hasher.update(time.subsec_nanos().to_be_bytes());
+    }
+}
+
+impl From<[u8; 20]> for ContentFingerprint {
+    fn from(digest: [u8; 20]) -> ContentFingerprint {
+        ContentFingerprint { digest }
+    }
+}
+
+impl HashingStrategy {
+    // Resolve `Auto` into the strategy actually used.
+    // Explicitly chosen strategies are used as is.
+    fn resolve(self, num_threads: usize, is_dir: bool) -> HashingStrategy {
+        match self {
+            HashingStrategy::Auto if num_threads == 1 => HashingStrategy::Sequential,
+            HashingStrategy::Auto if is_dir => HashingStrategy::Pipelined,
+            HashingStrategy::Auto => HashingStrategy::Parallel,
+            strategy => strategy,
+        }
+    }
+}
+
+impl FileId {
+    fn of(metadata: &std::fs::Metadata) -> FileId {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            FileId(metadata.dev(), metadata.ino())
+        }
+        #[cfg(not(unix))]
+        {
+            FileId(metadata.len(), metadata.modified().ok())
+        }
+    }
+}
+
+impl FilePins {
+    // Record the identity of `root` if `enabled`. `root` should
+    // have just been canonicalized, so that it is not a symlink.
+    fn new(enabled: bool, root: &Path) -> Result<FilePins, LavaTorrentError> {
+        Ok(FilePins {
+            root: if enabled {
+                Some((root.to_path_buf(), FileId::of(&root.symlink_metadata()?)))
+            } else {
+                None
+            },
+            files: HashMap::new(),
+        })
+    }
+
+    // Record the identity of the file at `path`. `metadata` must be what
+    // the scan found, rather than the result of looking `path` up again:
+    // a file swapped in between the two would be pinned otherwise.
+    fn record(&mut self, path: &Path, metadata: &std::fs::Metadata) {
+        if self.root.is_some() {
+            self.files.insert(path.to_path_buf(), FileId::of(metadata));
+        }
+    }
+
+    // Pin the root itself, for single-file torrents.
+    // Its identity was recorded by `new()`, so no lookup is needed.
+    fn pin_root_file(&mut self) {
+        if let Some((ref root, id)) = self.root {
+            self.files.insert(root.clone(), id);
+        }
+    }
+
+    // Check that the root has not been replaced while it was being scanned.
+    fn check_root(&self) -> Result<(), LavaTorrentError> {
+        match self.root {
+            Some((ref root, id)) if FileId::of(&root.symlink_metadata()?) != id => Err(
+                LavaTorrentError::TorrentBuilderFailure(Cow::Owned(format!(
+                    "[{}] was replaced while it was being scanned.",
+                    root.display()
+                ))),
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    // Open `path`, and check that it is still the file that was pinned.
+    fn open(&self, path: &Path) -> Result<std::fs::File, LavaTorrentError> {
+        let file = std::fs::File::open(path)?;
+        if self.root.is_some() && self.files.get(path) != Some(&FileId::of(&file.metadata()?)) {
+            return Err(LavaTorrentError::TorrentBuilderFailure(Cow::Owned(
+                format!("[{}] was replaced after it was scanned.", path.display()),
+            )));
+        }
+        Ok(file)
+    }
+}
+
+impl TorrentBuildInternal {
+    fn inc_piece_processed(&self) {
+        self.n_piece_processed.fetch_add(1, Ordering::AcqRel);
+    }
+
+    fn set_piece_total(&self, total: u64) {
+        self.n_piece_total.store(total, Ordering::Release)
+    }
+
+    fn set_piece_processed(&self, processed: u64) {
+        self.n_piece_processed.store(processed, Ordering::Release)
+    }
+
+    fn is_canceled(&self) -> bool {
+        self.is_canceled.load(Ordering::Acquire)
+    }
+
+    fn check_canceled(&self) -> Result<(), LavaTorrentError> {
+        if self.is_canceled() {
+            Err(LavaTorrentError::TorrentBuilderFailure(Cow::Borrowed(
+                "build canceled by client",
+            )))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl PieceHasher {
+    fn new(piece_length: u64) -> PieceHasher {
+        PieceHasher {
+            hasher: Sha1::new(),
+            piece_length,
+            filled: 0,
+            pieces: Vec::new(),
+        }
+    }
+
+    fn update(&mut self, mut bytes: &[u8]) -> Result<(), LavaTorrentError> {
+        while !bytes.is_empty() {
+            let room = util::u64_to_usize(self.piece_length - self.filled).unwrap_or(usize::MAX);
+            let (head, tail) = bytes.split_at(room.min(bytes.len()));
+
+            self.hasher.update(head);
+            self.filled += util::usize_to_u64(head.len())?;
+            if self.filled == self.piece_length {
+                self.pieces.push(self.hasher.finalize_reset().into());
+                self.filled = 0;
+            }
+            bytes = tail;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Vec<Piece> {
+        if self.filled > 0 {
+            self.pieces.push(self.hasher.finalize().into());
+        }
+        self.pieces
+    }
+}
+
+impl Drop for TorrentBuild {
+    fn drop(&mut self) {
+        self.cancel()
+    }
+}
+
+#[cfg(test)]
+mod torrent_builder_tests {
+    // @note: `build()` and `build_non_blocking()` are not tested here
+    // as they are best left to integration tests (in `tests/`)
+    //
+    // `read_dir()`, `read_dir_non_blocking()`, `read_dir_parallel()`,
+    // `read_dir_parallel_non_blocking()`, `read_dir_pipelined()`,
+    // and `read_dir_pipelined_non_blocking()` are also not tested here,
+    // as they are implicitly tested with `build()` and `build_non_blocking()`
+    use super::*;
+    use std::iter::FromIterator;
+
+    #[test]
+    fn new_ok() {
+        assert_eq!(
+            TorrentBuilder::new("dir/", 42),
+            TorrentBuilder {
+                path: PathBuf::from("dir"),
+                piece_length: 42,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn set_announce_ok() {
+        let builder = TorrentBuilder::new("dir/", 42);
+
+        let builder = builder.set_announce(Some("url".to_owned()));
+        assert_eq!(
+            builder,
+            TorrentBuilder {
+                announce: Some("url".to_owned()),
+                path: PathBuf::from("dir"),
+                piece_length: 42,
+                ..Default::default()
+            }
+        );
+
+        let builder = builder.set_announce(Some("url2".to_owned()));
+        assert_eq!(
+            builder,
+            TorrentBuilder {
+                announce: Some("url2".to_owned()),
+                path: PathBuf::from("dir"),
+                piece_length: 42,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn set_announce_list_ok() {
+        let builder = TorrentBuilder::new("dir/", 42);
+
+        let builder =
+            builder.set_announce_list(vec![vec!["url2".to_owned()], vec!["url3".to_owned()]]);
+        assert_eq!(
+            builder,
+            TorrentBuilder {
+                announce_list: Some(vec![vec!["url2".to_owned()], vec!["url3".to_owned()]]),
+                path: PathBuf::from("dir"),
+                piece_length: 42,
+                ..Default::default()
+            }
+        );
+
+        let builder = builder.set_announce_list(vec![vec!["url2".to_owned()]]);
+        assert_eq!(
+            builder,
+            TorrentBuilder {
+                announce_list: Some(vec![vec!["url2".to_owned()]]),
+                path: PathBuf::from("dir"),
+                piece_length: 42,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn set_name_ok() {
+        let builder = TorrentBuilder::new("dir/", 42);
+
+        let builder = builder.set_name("sample".to_owned());
+        assert_eq!(
+            builder,
+            TorrentBuilder {
+                name: Some("sample".to_owned()),
+                path: PathBuf::from("dir"),
+                piece_length: 42,
+                ..Default::default()
+            }
+        );
+
+        let builder = builder.set_name("sample2".to_owned());
+        assert_eq!(
+            builder,
+            TorrentBuilder {
+                name: Some("sample2".to_owned()),
+                path: PathBuf::from("dir"),
+                piece_length: 42,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn set_path_ok() {
+        let builder = TorrentBuilder::new("dir/", 42);
+
+        let builder = builder.set_path("dir2");
+        assert_eq!(
+            builder,
+            TorrentBuilder {
+                path: PathBuf::from("dir2"),
+                piece_length: 42,
+                ..Default::default()
+            }
+        );
+
+        let builder = builder.set_path("dir3");
+        assert_eq!(
+            builder,
+            TorrentBuilder {
+                path: PathBuf::from("dir3"),
+                piece_length: 42,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn set_piece_length_ok() {
+        let builder = TorrentBuilder::new("dir/", 42);
+
+        let builder = builder.set_piece_length(256);
+        assert_eq!(
+            builder,
+            TorrentBuilder {
+                path: PathBuf::from("dir"),
+                piece_length: 256,
+                ..Default::default()
+            }
+        );
+
+        let builder = builder.set_piece_length(512);
+        assert_eq!(
+            builder,
+            TorrentBuilder {
+                path: PathBuf::from("dir"),
+                piece_length: 512,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn add_extra_field_ok() {
+        let builder = TorrentBuilder::new("dir/", 42);
+
+        let builder = builder.add_extra_field("k1".to_owned(), bencode_elem!("v1"));
+        assert_eq!(
+            builder,
+            TorrentBuilder {
+                path: PathBuf::from("dir"),
+                piece_length: 42,
+                extra_fields: Some(HashMap::from_iter(
+                    vec![("k1".to_owned(), bencode_elem!("v1"))].into_iter()
+                )),
+                ..Default::default()
+            }
+        );
+
+        let builder = builder.add_extra_field("k2".to_owned(), bencode_elem!("v2"));
+        assert_eq!(
+            builder,
+            TorrentBuilder {
+                path: PathBuf::from("dir"),
+                piece_length: 42,
+                extra_fields: Some(HashMap::from_iter(
+                    vec![
+                        ("k1".to_owned(), bencode_elem!("v1")),
+                        ("k2".to_owned(), bencode_elem!("v2")),
+                    ]
+                    .into_iter()
+                )),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn add_extra_info_field_ok() {
+        let builder = TorrentBuilder::new("dir/", 42);
+
+        let builder = builder.add_extra_info_field("k1".to_owned(), bencode_elem!("v1"));
+        assert_eq!(
+            builder,
+            TorrentBuilder {
+                path: PathBuf::from("dir"),
+                piece_length: 42,
+                extra_info_fields: Some(HashMap::from_iter(
+                    vec![("k1".to_owned(), bencode_elem!("v1"))].into_iter()
+                )),
+                ..Default::default()
+            }
+        );
+
+        let builder = builder.add_extra_info_field("k2".to_owned(), bencode_elem!("v2"));
+        assert_eq!(
+            builder,
+            TorrentBuilder {
+                path: PathBuf::from("dir"),
+                piece_length: 42,
+                extra_info_fields: Some(HashMap::from_iter(
+                    vec![
+                        ("k1".to_owned(), bencode_elem!("v1")),
+                        ("k2".to_owned(), bencode_elem!("v2")),
+                    ]
+                    .into_iter()
+                )),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn set_privacy_ok() {
+        let builder = TorrentBuilder::new("dir/", 42);
+
+        let builder = builder.set_privacy(true);
+        assert_eq!(
+            builder,
+            TorrentBuilder {
+                path: PathBuf::from("dir"),
+                piece_length: 42,
+                is_private: true,
+                ..Default::default()
+            }
+        );
+
+        let builder = builder.set_privacy(false);
+        assert_eq!(
+            builder,
+            TorrentBuilder {
+                path: PathBuf::from("dir"),
+                piece_length: 42,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn set_preserve_symlinks_ok() {
+        let builder = TorrentBuilder::new("dir/", 42);
+
+        let builder = builder.set_preserve_symlinks(true);
+        assert_eq!(
+            builder,
+            TorrentBuilder {
+                path: PathBuf::from("dir"),
+                piece_length: 42,
+                preserve_symlinks: true,
+                ..Default::default()
+            }
+        );
+
+        let builder = builder.set_preserve_symlinks(false);
+        assert_eq!(
+            builder,
+            TorrentBuilder {
+                path: PathBuf::from("dir"),
+                piece_length: 42,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn set_keep_piece_map_ok() {
+        let builder = TorrentBuilder::new("dir/", 42);
 
-        let n_piece_processed = self.n_piece_processed.load(Ordering::Acquire);
+        let builder = builder.set_keep_piece_map(true);
+        assert_eq!(
+            builder,
+            TorrentBuilder {
+                path: PathBuf::from("dir"),
+                piece_length: 42,
+                keep_piece_map: true,
+                ..Default::default()
+            }
+        );
 
-        (n_piece_processed * 100 / n_piece_total) as u8
+        let builder = builder.set_keep_piece_map(false);
+        assert_eq!(
+            builder,
+            TorrentBuilder {
+                path: PathBuf::from("dir"),
+                piece_length: 42,
+                ..Default::default()
+            }
+        );
     }
 
-    /// Get the number of pieces that have been processed so far.
-    pub fn get_n_piece_processed(&self) -> u64 {
-        self.n_piece_processed.load(Ordering::Acquire)
-    }
+    #[test]
+    fn set_hybrid_compatible_layout_ok() {
+        let builder = TorrentBuilder::new("dir/", 42);
 
-    /// Get the total number of pieces to be processed.
-    ///
-    /// Note that 0 will be returned in at least 2 cases:
-    /// - the torrent contains only empty files/dirs
-    /// - the actual value has not been calculated yet
-    pub fn get_n_piece_total(&self) -> u64 {
-        self.n_piece_total.load(Ordering::Acquire)
-    }
+        let builder = builder.set_hybrid_compatible_layout(true);
+        assert_eq!(
+            builder,
+            TorrentBuilder {
+                path: PathBuf::from("dir"),
+                piece_length: 42,
+                hybrid_compatible_layout: true,
+                ..Default::default()
+            }
+        );
 
-    /// Cancel the torrent build.
-    ///
-    /// `cancel()` does not consume the `TorrentBuild`. If you want, you can call
-    /// [`drop()`] yourself.
-    ///
-    /// Calling [`get_output()`] after `cancel()` will most likely give you an
-    /// [`Err(LavaTorrentError::TorrentBuilderFailure)`], but it's also possible
-    /// for you to get an `Ok(torrent)` (if you cancel after all pieces have been hashed).
-    ///
-    /// [`drop()`]: https://doc.rust-lang.org/std/mem/fn.drop.html
-    /// [`get_output()`]: #method.get_output
-    /// [`Err(LavaTorrentError::TorrentBuilderFailure)`]: ../../enum.LavaTorrentError.html#variant.TorrentBuilderFailure
-    pub fn cancel(&self) {
-        self.is_canceled.store(true, Ordering::Release)
+        let builder = builder.set_hybrid_compatible_layout(false);
+        assert_eq!(
+            builder,
+            TorrentBuilder {
+                path: PathBuf::from("dir"),
+                piece_length: 42,
+                ..Default::default()
+            }
+        );
     }
 
-    /// Retrieve the output of the torrent build.
-    ///
-    /// This function will block if the build has not finished yet. You can
-    /// use [`is_finished()`] to check if the build has finished.
-    ///
-    /// [`is_finished()`]: #method.is_finished
-    pub fn get_output(mut self) -> Result<Torrent, LavaTorrentError> {
-        self.builder_thread.take().unwrap().join().map_err(|e| {
-            LavaTorrentError::TorrentBuilderFailure(Cow::Owned(format!(
-                "builder thread has unexpectedly panicked: {:?}",
-                e
-            )))
-        })?
-    }
+    #[test]
+    fn set_force_multi_file_ok() {
+        let builder = TorrentBuilder::new("dir/", 42);
 
-    /// Check if the torrent build has finished.
-    pub fn is_finished(&self) -> bool {
-        self.builder_thread.as_ref().unwrap().is_finished()
-    }
-}
+        let builder = builder.set_force_multi_file(true);
+        assert_eq!(
+            builder,
+            TorrentBuilder {
+                path: PathBuf::from("dir"),
+                piece_length: 42,
+                force_multi_file: true,
+                ..Default::default()
+            }
+        );
 
-impl TorrentBuildInternal {
-    fn inc_piece_processed(&self) {
-        self.n_piece_processed.fetch_add(1, Ordering::AcqRel);
+        let builder = builder.set_force_multi_file(false);
+        assert_eq!(
+            builder,
+            TorrentBuilder {
+                path: PathBuf::from("dir"),
+                piece_length: 42,
+                ..Default::default()
+            }
+        );
     }
 
-    fn set_piece_total(&self, total: u64) {
-        self.n_piece_total.store(total, Ordering::Release)
-    }
+    #[test]
+    fn set_force_single_file_ok() {
+        let builder = TorrentBuilder::new("dir/", 42);
 
-    fn is_canceled(&self) -> bool {
-        self.is_canceled.load(Ordering::Acquire)
-    }
-}
+        let builder = builder.set_force_single_file(true);
+        assert_eq!(
+            builder,
+            TorrentBuilder {
+                path: PathBuf::from("dir"),
+                piece_length: 42,
+                force_single_file: true,
+                ..Default::default()
+            }
+        );
 
-impl Drop for TorrentBuild {
-    fn drop(&mut self) {
-        self.cancel()
+        let builder = builder.set_force_single_file(false);
+        assert_eq!(
+            builder,
+            TorrentBuilder {
+                path: PathBuf::from("dir"),
+                piece_length: 42,
+                ..Default::default()
+            }
+        );
     }
-}
-
-#[cfg(test)]
-mod torrent_builder_tests {
-    // @note: `build()` and `build_non_blocking()` are not tested here
-    // as they are best left to integration tests (in `tests/`)
-    //
-    // `read_dir()`, `read_dir_non_blocking()`, `read_dir_parallel()`,
-    // and `read_dir_parallel_non_blocking()` are also not tested here,
-    // as they are implicitly tested with `build()` and `build_non_blocking()`
-    use super::*;
-    use std::iter::FromIterator;
 
     #[test]
-    fn new_ok() {
+    fn set_file_issue_policy_ok() {
+        let builder = TorrentBuilder::new("dir/", 42);
+
+        let builder = builder.set_file_issue_policy(FileIssuePolicy::Skip);
         assert_eq!(
-            TorrentBuilder::new("dir/", 42),
+            builder,
+            TorrentBuilder {
+                path: PathBuf::from("dir"),
+                piece_length: 42,
+                file_issue_policy: FileIssuePolicy::Skip,
+                ..Default::default()
+            }
+        );
+
+        let builder = builder.set_file_issue_policy(FileIssuePolicy::Fail);
+        assert_eq!(
+            builder,
             TorrentBuilder {
                 path: PathBuf::from("dir"),
                 piece_length: 42,
@@ -1157,25 +4184,24 @@ mod torrent_builder_tests {
     }
 
     #[test]
-    fn set_announce_ok() {
+    fn set_hashing_strategy_ok() {
         let builder = TorrentBuilder::new("dir/", 42);
 
-        let builder = builder.set_announce(Some("url".to_owned()));
+        let builder = builder.set_hashing_strategy(HashingStrategy::Pipelined);
         assert_eq!(
             builder,
             TorrentBuilder {
-                announce: Some("url".to_owned()),
                 path: PathBuf::from("dir"),
                 piece_length: 42,
+                hashing_strategy: HashingStrategy::Pipelined,
                 ..Default::default()
             }
         );
 
-        let builder = builder.set_announce(Some("url2".to_owned()));
+        let builder = builder.set_hashing_strategy(HashingStrategy::Auto);
         assert_eq!(
             builder,
             TorrentBuilder {
-                announce: Some("url2".to_owned()),
                 path: PathBuf::from("dir"),
                 piece_length: 42,
                 ..Default::default()
@@ -1184,26 +4210,38 @@ mod torrent_builder_tests {
     }
 
     #[test]
-    fn set_announce_list_ok() {
+    fn hashing_strategy_resolve_ok() {
+        use HashingStrategy::*;
+
+        assert_eq!(Auto.resolve(1, true), Sequential);
+        assert_eq!(Auto.resolve(1, false), Sequential);
+        assert_eq!(Auto.resolve(4, true), Pipelined);
+        assert_eq!(Auto.resolve(4, false), Parallel);
+        for strategy in [Sequential, Parallel, Pipelined] {
+            assert_eq!(strategy.resolve(1, true), strategy);
+            assert_eq!(strategy.resolve(4, false), strategy);
+        }
+    }
+
+    #[test]
+    fn set_pipeline_depth_ok() {
         let builder = TorrentBuilder::new("dir/", 42);
 
-        let builder =
-            builder.set_announce_list(vec![vec!["url2".to_owned()], vec!["url3".to_owned()]]);
+        let builder = builder.set_pipeline_depth(3);
         assert_eq!(
             builder,
             TorrentBuilder {
-                announce_list: Some(vec![vec!["url2".to_owned()], vec!["url3".to_owned()]]),
                 path: PathBuf::from("dir"),
                 piece_length: 42,
+                pipeline_depth: 3,
                 ..Default::default()
             }
         );
 
-        let builder = builder.set_announce_list(vec![vec!["url2".to_owned()]]);
+        let builder = builder.set_pipeline_depth(0);
         assert_eq!(
             builder,
             TorrentBuilder {
-                announce_list: Some(vec![vec!["url2".to_owned()]]),
                 path: PathBuf::from("dir"),
                 piece_length: 42,
                 ..Default::default()
@@ -1212,25 +4250,24 @@ mod torrent_builder_tests {
     }
 
     #[test]
-    fn set_name_ok() {
+    fn set_checkpoint_interval_ok() {
         let builder = TorrentBuilder::new("dir/", 42);
 
-        let builder = builder.set_name("sample".to_owned());
+        let builder = builder.set_checkpoint_interval(16);
         assert_eq!(
             builder,
             TorrentBuilder {
-                name: Some("sample".to_owned()),
                 path: PathBuf::from("dir"),
                 piece_length: 42,
+                checkpoint_interval: 16,
                 ..Default::default()
             }
         );
 
-        let builder = builder.set_name("sample2".to_owned());
+        let builder = builder.set_checkpoint_interval(0);
         assert_eq!(
             builder,
             TorrentBuilder {
-                name: Some("sample2".to_owned()),
                 path: PathBuf::from("dir"),
                 piece_length: 42,
                 ..Default::default()
@@ -1239,141 +4276,429 @@ mod torrent_builder_tests {
     }
 
     #[test]
-    fn set_path_ok() {
+    fn set_secure_open_ok() {
         let builder = TorrentBuilder::new("dir/", 42);
 
-        let builder = builder.set_path("dir2");
+        let builder = builder.set_secure_open(true);
         assert_eq!(
             builder,
             TorrentBuilder {
-                path: PathBuf::from("dir2"),
+                path: PathBuf::from("dir"),
+                piece_length: 42,
+                secure_open: true,
+                ..Default::default()
+            }
+        );
+
+        let builder = builder.set_secure_open(false);
+        assert_eq!(
+            builder,
+            TorrentBuilder {
+                path: PathBuf::from("dir"),
                 piece_length: 42,
                 ..Default::default()
             }
         );
+    }
+
+    fn walk(dir: &str, follow_symlinks: bool) -> Vec<(PathBuf, u64)> {
+        let root = PathBuf::from(dir).canonicalize().unwrap();
+        TorrentBuilder::walk_dir(
+            &root,
+            follow_symlinks,
+            FileIssuePolicy::Fail,
+            &|| Ok(()),
+            &mut BuildReport::default(),
+            &mut FilePins::default(),
+        )
+        .unwrap()
+        .into_iter()
+        .map(|(path, length)| (path.strip_prefix(&root).unwrap().to_path_buf(), length))
+        .collect()
+    }
+
+    #[test]
+    fn walk_dir_ok() {
+        assert_eq!(
+            walk("tests/files", true),
+            [
+                "byte_sequence",
+                "symlink",
+                "tails-amd64-3.6.1.torrent",
+                "ubuntu-16.04.4-desktop-amd64.iso.torrent",
+                // no [.hidden]
+            ]
+            .iter()
+            .map(PathBuf::from)
+            .map(|p| (
+                p.clone(),
+                Path::new("tests/files").join(p).metadata().unwrap().len()
+            ))
+            .collect::<Vec<(PathBuf, u64)>>()
+        );
+    }
+
+    #[test]
+    fn walk_dir_no_follow() {
+        assert_eq!(
+            walk("tests/files", false),
+            [
+                ("byte_sequence", 256),
+                ("symlink", 0),
+                ("tails-amd64-3.6.1.torrent", 94016),
+                ("ubuntu-16.04.4-desktop-amd64.iso.torrent", 62300),
+            ]
+            .iter()
+            .map(|&(p, len)| (PathBuf::from(p), len))
+            .collect::<Vec<(PathBuf, u64)>>()
+        );
+    }
+
+    #[test]
+    fn walk_dir_with_subdir() {
+        assert_eq!(
+            walk("src/torrent", true),
+            [
+                "mod.rs",
+                "v1/build.rs",
+                "v1/cache.rs",
+                "v1/checkpoint.rs",
+                "v1/health.rs",
+                "v1/inspect.rs",
+                "v1/invariants.rs",
+                "v1/mod.rs",
+                "v1/read.rs",
+                "v1/write.rs"
+            ]
+            .iter()
+            .map(PathBuf::from)
+            .map(|p| (
+                p.clone(),
+                Path::new("src/torrent").join(p).metadata().unwrap().len()
+            ))
+            .collect::<Vec<(PathBuf, u64)>>()
+        );
+    }
+
+    // `root/dir/a`, and `root/decoy/a` with other content,
+    // where `root` is a new directory under `tests/tmp`
+    #[cfg(unix)]
+    fn secure_open_dirs(test: &str) -> (PathBuf, PathBuf, PathBuf) {
+        let root = PathBuf::from(format!("tests/tmp/{}-{}", rand::random::<u16>(), test));
+        std::fs::create_dir_all(root.join("dir")).unwrap();
+        std::fs::create_dir_all(root.join("decoy")).unwrap();
+        // absolute, so that symlinks to `decoy` resolve from anywhere
+        let root = root.canonicalize().unwrap();
+        let (dir, decoy) = (root.join("dir"), root.join("decoy"));
+        std::fs::write(dir.join("a"), b"original").unwrap();
+        std::fs::write(decoy.join("a"), b"attacker").unwrap();
+        (root, dir, decoy)
+    }
+
+    // Scan `dir` the way `build()` does, pinning the files found.
+    #[cfg(unix)]
+    fn walk_pinned(
+        dir: &Path,
+        pins: &mut FilePins,
+    ) -> Result<Vec<(PathBuf, u64)>, LavaTorrentError> {
+        TorrentBuilder::walk_dir(
+            dir,
+            true,
+            FileIssuePolicy::Fail,
+            &|| Ok(()),
+            &mut BuildReport::default(),
+            pins,
+        )
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn secure_open_file_replaced() {
+        let (root, dir, decoy) = secure_open_dirs("file-replaced");
+        let mut pins = FilePins::new(true, &dir).unwrap();
+        let entries = walk_pinned(&dir, &mut pins).unwrap();
+        assert_eq!(entries, vec![(dir.join("a"), 8)]);
+        assert!(TorrentBuilder::read_dir(&dir, entries.clone(), 16, 16, &pins).is_ok());
+
+        // swapped between scanning and hashing
+        std::fs::rename(decoy.join("a"), dir.join("a")).unwrap();
+        match TorrentBuilder::read_dir(&dir, entries.clone(), 16, 16, &pins) {
+            Err(LavaTorrentError::TorrentBuilderFailure(m)) => assert_eq!(
+                m,
+                format!(
+                    "[{}] was replaced after it was scanned.",
+                    dir.join("a").display()
+                )
+            ),
+            _ => panic!(),
+        }
+        // without pins, the replacement is hashed
+        assert!(TorrentBuilder::read_dir(&dir, entries, 16, 16, &FilePins::default()).is_ok());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn secure_open_subdir_replaced() {
+        let (root, dir, decoy) = secure_open_dirs("subdir-replaced");
+        std::fs::create_dir(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("sub").join("a"), b"original").unwrap();
+        let mut pins = FilePins::new(true, &dir).unwrap();
+        let entries = walk_pinned(&dir, &mut pins).unwrap();
+        assert_eq!(
+            entries,
+            vec![(dir.join("a"), 8), (dir.join("sub").join("a"), 8)]
+        );
+
+        // swapped after scanning: `dir/sub/a` now resolves to `decoy/a`,
+        // which has the same length
+        std::fs::rename(dir.join("sub"), root.join("moved")).unwrap();
+        std::os::unix::fs::symlink(&decoy, dir.join("sub")).unwrap();
+        match TorrentBuilder::read_dir(&dir, entries, 16, 16, &pins) {
+            Err(LavaTorrentError::TorrentBuilderFailure(m)) => assert_eq!(
+                m,
+                format!(
+                    "[{}] was replaced after it was scanned.",
+                    dir.join("sub").join("a").display()
+                )
+            ),
+            _ => panic!(),
+        }
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn secure_open_root_replaced() {
+        let (root, dir, decoy) = secure_open_dirs("root-replaced");
+
+        // swapped after scanning: `dir/a` now resolves to `decoy/a`
+        let mut pins = FilePins::new(true, &dir).unwrap();
+        let entries = walk_pinned(&dir, &mut pins).unwrap();
+        std::fs::rename(&dir, root.join("moved")).unwrap();
+        std::os::unix::fs::symlink(&decoy, &dir).unwrap();
+        match TorrentBuilder::read_dir(&dir, entries.clone(), 16, 16, &pins) {
+            Err(LavaTorrentError::TorrentBuilderFailure(m)) => assert_eq!(
+                m,
+                format!(
+                    "[{}] was replaced after it was scanned.",
+                    dir.join("a").display()
+                )
+            ),
+            _ => panic!(),
+        }
+        std::fs::remove_dir_all(root).unwrap();
+
+        // swapped while scanning
+        let (root, dir, decoy) = secure_open_dirs("root-replaced");
+        let mut pins = FilePins::new(true, &dir).unwrap();
+        std::fs::rename(&dir, root.join("moved")).unwrap();
+        std::os::unix::fs::symlink(&decoy, &dir).unwrap();
+        match walk_pinned(&dir, &mut pins) {
+            Err(LavaTorrentError::TorrentBuilderFailure(m)) => assert_eq!(
+                m,
+                format!(
+                    "[{}] was replaced while it was being scanned.",
+                    dir.display()
+                )
+            ),
+            _ => panic!(),
+        }
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn to_bencode_ok() {
+        let builder = TorrentBuilder::new("dir", 42)
+            .set_announce(Some("url1".to_owned()))
+            .set_announce_list(vec![vec!["url2".to_owned(), "url3".to_owned()]])
+            .set_privacy(true)
+            .set_hashing_strategy(HashingStrategy::Pipelined)
+            .set_num_threads(3);
 
-        let builder = builder.set_path("dir3");
         assert_eq!(
-            builder,
-            TorrentBuilder {
-                path: PathBuf::from("dir3"),
-                piece_length: 42,
-                ..Default::default()
-            }
+            builder.to_bencode(),
+            BencodeElem::Dictionary(HashMap::from_iter(vec![
+                ("config version".to_owned(), BencodeElem::Integer(1)),
+                (
+                    "announce".to_owned(),
+                    BencodeElem::String("url1".to_owned())
+                ),
+                (
+                    "announce list".to_owned(),
+                    BencodeElem::List(vec![BencodeElem::List(vec![
+                        BencodeElem::String("url2".to_owned()),
+                        BencodeElem::String("url3".to_owned()),
+                    ])])
+                ),
+                ("path".to_owned(), BencodeElem::String("dir".to_owned())),
+                ("piece length".to_owned(), BencodeElem::Integer(42)),
+                ("private".to_owned(), BencodeElem::Integer(1)),
+                ("preserve symlinks".to_owned(), BencodeElem::Integer(0)),
+                ("dedupe trackers".to_owned(), BencodeElem::Integer(0)),
+                ("keep piece map".to_owned(), BencodeElem::Integer(0)),
+                (
+                    "hybrid compatible layout".to_owned(),
+                    BencodeElem::Integer(0)
+                ),
+                ("force multi file".to_owned(), BencodeElem::Integer(0)),
+                ("force single file".to_owned(), BencodeElem::Integer(0)),
+                (
+                    "file issue policy".to_owned(),
+                    BencodeElem::String("fail".to_owned())
+                ),
+                (
+                    "hashing strategy".to_owned(),
+                    BencodeElem::String("pipelined".to_owned())
+                ),
+                ("num threads".to_owned(), BencodeElem::Integer(3)),
+                ("chunk size".to_owned(), BencodeElem::Integer(0)),
+                ("pipeline depth".to_owned(), BencodeElem::Integer(0)),
+                ("checkpoint interval".to_owned(), BencodeElem::Integer(0)),
+                ("secure open".to_owned(), BencodeElem::Integer(0)),
+            ]))
         );
     }
 
     #[test]
-    fn set_piece_length_ok() {
-        let builder = TorrentBuilder::new("dir/", 42);
+    fn from_bencode_round_trip() {
+        let builder = TorrentBuilder::new("dir/", 42)
+            .set_announce(Some("url1".to_owned()))
+            .set_announce_list(vec![vec!["url2".to_owned()], vec!["url3".to_owned()]])
+            .set_name("sample".to_owned())
+            .add_extra_field("k1".to_owned(), BencodeElem::Bytes(vec![0xff]))
+            .add_extra_info_field("k2".to_owned(), BencodeElem::Integer(-1))
+            .set_privacy(true)
+            .set_preserve_symlinks(true)
+            .set_dedupe_trackers(true)
+            .set_keep_piece_map(true)
+            .set_hybrid_compatible_layout(true)
+            .set_force_multi_file(true)
+            .set_file_issue_policy(FileIssuePolicy::Skip)
+            .set_hashing_strategy(HashingStrategy::Sequential)
+            .set_num_threads(4)
+            .set_chunk_size(4096)
+            .set_pipeline_depth(8)
+            .set_checkpoint_interval(16);
 
-        let builder = builder.set_piece_length(256);
         assert_eq!(
-            builder,
-            TorrentBuilder {
-                path: PathBuf::from("dir"),
-                piece_length: 256,
-                ..Default::default()
-            }
+            TorrentBuilder::from_bencode(builder.to_bencode(), None).unwrap(),
+            builder
         );
-
-        let builder = builder.set_piece_length(512);
         assert_eq!(
-            builder,
-            TorrentBuilder {
-                path: PathBuf::from("dir"),
-                piece_length: 512,
-                ..Default::default()
-            }
+            TorrentBuilder::from_bencode(builder.to_bencode(), Some(PathBuf::from("other")))
+                .unwrap(),
+            builder.set_path("other")
         );
     }
 
     #[test]
-    fn add_extra_field_ok() {
-        let builder = TorrentBuilder::new("dir/", 42);
-
-        let builder = builder.add_extra_field("k1".to_owned(), bencode_elem!("v1"));
-        assert_eq!(
-            builder,
-            TorrentBuilder {
-                path: PathBuf::from("dir"),
-                piece_length: 42,
-                extra_fields: Some(HashMap::from_iter(
-                    vec![("k1".to_owned(), bencode_elem!("v1"))].into_iter()
-                )),
-                ..Default::default()
-            }
-        );
+    fn from_bencode_defaults() {
+        let config = BencodeElem::Dictionary(HashMap::from_iter(vec![
+            ("config version".to_owned(), BencodeElem::Integer(1)),
+            ("piece length".to_owned(), BencodeElem::Integer(42)),
+            // unknown keys are ignored
+            ("unknown".to_owned(), BencodeElem::Integer(0)),
+        ]));
 
-        let builder = builder.add_extra_field("k2".to_owned(), bencode_elem!("v2"));
         assert_eq!(
-            builder,
-            TorrentBuilder {
-                path: PathBuf::from("dir"),
-                piece_length: 42,
-                extra_fields: Some(HashMap::from_iter(
-                    vec![
-                        ("k1".to_owned(), bencode_elem!("v1")),
-                        ("k2".to_owned(), bencode_elem!("v2")),
-                    ]
-                    .into_iter()
-                )),
-                ..Default::default()
-            }
+            TorrentBuilder::from_bencode(config, Some(PathBuf::from("dir"))).unwrap(),
+            TorrentBuilder::new("dir", 42)
         );
     }
 
     #[test]
-    fn add_extra_info_field_ok() {
-        let builder = TorrentBuilder::new("dir/", 42);
+    fn from_bencode_invalid() {
+        let config = |entries: Vec<(&str, BencodeElem)>| {
+            let mut config = TorrentBuilder::new("dir", 42).to_bencode();
+            if let BencodeElem::Dictionary(ref mut dict) = config {
+                for (key, val) in entries {
+                    dict.insert(key.to_owned(), val);
+                }
+            }
+            config
+        };
+        let cases = vec![
+            (
+                BencodeElem::List(vec![]),
+                "Builder config is a List, expected Dictionary.",
+            ),
+            (
+                config(vec![("config version", BencodeElem::Integer(2))]),
+                "Builder config version 2 is newer than the supported version 1.",
+            ),
+            (
+                config(vec![("piece length", BencodeElem::Bytes(vec![]))]),
+                r#""piece length" maps to Bytes, expected Integer."#,
+            ),
+            (
+                config(vec![("private", BencodeElem::Integer(2))]),
+                r#""private" [2] is neither 0 nor 1."#,
+            ),
+            (
+                config(vec![(
+                    "hashing strategy",
+                    BencodeElem::String("magic".to_owned()),
+                )]),
+                r#""hashing strategy" [magic] is not a known strategy."#,
+            ),
+            (
+                config(vec![(
+                    "announce list",
+                    BencodeElem::List(vec![BencodeElem::List(vec![BencodeElem::Integer(0)])]),
+                )]),
+                r#"A tier within "announce list" contains a Integer element, expected String."#,
+            ),
+        ];
 
-        let builder = builder.add_extra_info_field("k1".to_owned(), bencode_elem!("v1"));
-        assert_eq!(
-            builder,
-            TorrentBuilder {
-                path: PathBuf::from("dir"),
-                piece_length: 42,
-                extra_info_fields: Some(HashMap::from_iter(
-                    vec![("k1".to_owned(), bencode_elem!("v1"))].into_iter()
-                )),
-                ..Default::default()
+        for (config, expected) in cases {
+            match TorrentBuilder::from_bencode(config, None) {
+                Err(LavaTorrentError::InvalidArgument(m)) => assert_eq!(m, expected),
+                _ => panic!(),
             }
-        );
+        }
 
-        let builder = builder.add_extra_info_field("k2".to_owned(), bencode_elem!("v2"));
-        assert_eq!(
-            builder,
-            TorrentBuilder {
-                path: PathBuf::from("dir"),
-                piece_length: 42,
-                extra_info_fields: Some(HashMap::from_iter(
-                    vec![
-                        ("k1".to_owned(), bencode_elem!("v1")),
-                        ("k2".to_owned(), bencode_elem!("v2")),
-                    ]
-                    .into_iter()
-                )),
-                ..Default::default()
+        let mut missing = config(vec![]);
+        if let BencodeElem::Dictionary(ref mut dict) = missing {
+            dict.remove("config version");
+        }
+        match TorrentBuilder::from_bencode(missing, None) {
+            Err(LavaTorrentError::InvalidArgument(m)) => {
+                assert_eq!(m, r#""config version" does not exist."#)
             }
-        );
+            _ => panic!(),
+        }
+
+        match TorrentBuilder::from_bencode(
+            config(vec![("num threads", BencodeElem::Integer(-1))]),
+            None,
+        ) {
+            Err(LavaTorrentError::FailedNumericConv(m)) => {
+                assert_eq!(m, "[-1] does not fit into usize.")
+            }
+            _ => panic!(),
+        }
     }
 
     #[test]
-    fn set_privacy_ok() {
+    fn set_chunk_size_ok() {
         let builder = TorrentBuilder::new("dir/", 42);
 
-        let builder = builder.set_privacy(true);
+        let builder = builder.set_chunk_size(4096);
         assert_eq!(
             builder,
             TorrentBuilder {
                 path: PathBuf::from("dir"),
                 piece_length: 42,
-                is_private: true,
+                chunk_size: 4096,
                 ..Default::default()
             }
         );
 
-        let builder = builder.set_privacy(false);
+        let builder = builder.set_chunk_size(0);
         assert_eq!(
             builder,
             TorrentBuilder {
@@ -1477,6 +4802,91 @@ mod torrent_builder_tests {
         }
     }
 
+    // `announce_list` after `dedupe_trackers()`
+    fn deduped(announce: Option<&str>, list: Vec<Vec<&str>>) -> Option<AnnounceList> {
+        let mut builder = TorrentBuilder::new("dir/", 42)
+            .set_announce(announce.map(str::to_owned))
+            .set_announce_list(
+                list.into_iter()
+                    .map(|tier| tier.into_iter().map(str::to_owned).collect())
+                    .collect(),
+            )
+            .set_dedupe_trackers(true);
+        builder.dedupe_trackers();
+        builder.announce_list
+    }
+
+    fn tiers(list: Vec<Vec<&str>>) -> Option<AnnounceList> {
+        Some(
+            list.into_iter()
+                .map(|tier| tier.into_iter().map(str::to_owned).collect())
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn dedupe_trackers_disabled() {
+        let mut builder = TorrentBuilder::new("dir/", 42)
+            .set_announce(Some("a".to_owned()))
+            .set_announce_list(vec![vec!["a".to_owned(), "a".to_owned()]]);
+        let original = builder.clone();
+
+        builder.dedupe_trackers();
+        assert_eq!(builder, original);
+    }
+
+    #[test]
+    fn dedupe_trackers_within_tier() {
+        assert_eq!(
+            deduped(None, vec![vec!["a", " a ", "b", "a"], vec!["b", "c"]]),
+            tiers(vec![vec!["a", "b"], vec!["b", "c"]])
+        );
+    }
+
+    #[test]
+    fn dedupe_trackers_normalized() {
+        assert_eq!(
+            deduped(
+                None,
+                vec![vec!["http://a", "HTTP://a", "http://A:80/", "http://a/b"]]
+            ),
+            tiers(vec![vec!["http://a", "http://a/b"]])
+        );
+        assert_eq!(
+            deduped(
+                Some("UDP://a:6969/announce"),
+                vec![vec!["udp://a:6969", "udp://A:6969"]]
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn dedupe_trackers_empty_tiers() {
+        assert_eq!(
+            deduped(None, vec![vec![], vec!["a"], vec![]]),
+            tiers(vec![vec!["a"]])
+        );
+        assert_eq!(deduped(None, vec![vec![], vec![]]), None);
+    }
+
+    #[test]
+    fn dedupe_trackers_only_announce() {
+        assert_eq!(deduped(Some("a"), vec![vec!["a", "a"]]), None);
+        assert_eq!(deduped(Some("a "), vec![vec!["a"], vec![]]), None);
+        // anything else is kept
+        assert_eq!(
+            deduped(Some("a"), vec![vec!["a"], vec!["b"]]),
+            tiers(vec![vec!["a"], vec!["b"]])
+        );
+        assert_eq!(
+            deduped(Some("a"), vec![vec!["a", "b"]]),
+            tiers(vec![vec!["a", "b"]])
+        );
+        assert_eq!(deduped(Some("b"), vec![vec!["a"]]), tiers(vec![vec!["a"]]));
+        assert_eq!(deduped(None, vec![vec!["a"]]), tiers(vec![vec!["a"]]));
+    }
+
     #[test]
     fn validate_name_ok() {
         let builder = TorrentBuilder::new("dir/", 42).set_name("sample".to_owned());
@@ -1510,6 +4920,29 @@ mod torrent_builder_tests {
         }
     }
 
+    #[test]
+    fn validate_name_invalid() {
+        for (name, issue) in [
+            ("..", "it is `.` or `..`"),
+            ("a/b", "it contains a path separator"),
+            ("a\\b", "it contains a path separator"),
+            ("C:", "it starts with a drive letter"),
+            ("a\tb", "it contains a control character"),
+        ] {
+            let builder = TorrentBuilder::new("dir/", 42).set_name(name.to_owned());
+
+            match builder.validate_name() {
+                Err(LavaTorrentError::TorrentBuilderFailure(m)) => {
+                    assert_eq!(m, format!("TorrentBuilder has `name` but {}.", issue));
+                }
+                _ => panic!(),
+            }
+        }
+
+        let builder = TorrentBuilder::new("dir/", 42).set_name("a..b".to_owned());
+        builder.validate_name().unwrap();
+    }
+
     #[test]
     fn validate_path_ok() {
         let builder = TorrentBuilder::new("./target", 42);
@@ -1532,55 +4965,304 @@ mod torrent_builder_tests {
     }
 
     #[test]
-    fn validate_path_has_invalid_component() {
-        let builder = TorrentBuilder::new("./target/..", 42);
-        assert!(builder.validate_path().is_ok())
+    fn validate_path_has_invalid_component() {
+        let builder = TorrentBuilder::new("./target/..", 42);
+        assert!(builder.validate_path().is_ok())
+    }
+
+    #[test]
+    fn validate_path_has_hidden_component() {
+        let builder = TorrentBuilder::new("./tests/files/.hidden", 42);
+        assert!(builder.validate_path().is_ok());
+    }
+
+    #[test]
+    fn validate_path_not_absolute() {
+        let builder = TorrentBuilder::new("target/", 42);
+        assert!(builder.validate_path().is_ok())
+    }
+
+    #[test]
+    fn validate_layout_ok() {
+        let builder = TorrentBuilder::new("dir/", 42);
+        assert!(builder.validate_layout().is_ok());
+        let builder = builder.set_force_multi_file(true);
+        assert!(builder.validate_layout().is_ok());
+        let builder = builder
+            .set_force_multi_file(false)
+            .set_force_single_file(true);
+        assert!(builder.validate_layout().is_ok());
+    }
+
+    #[test]
+    fn validate_layout_conflict() {
+        let builder = TorrentBuilder::new("tests/files/byte_sequence", 1024)
+            .set_force_multi_file(true)
+            .set_force_single_file(true);
+        match builder.validate_layout() {
+            Err(LavaTorrentError::TorrentBuilderFailure(m)) => assert_eq!(
+                m,
+                "TorrentBuilder has both `force_multi_file` and `force_single_file` set."
+            ),
+            _ => panic!(),
+        }
+        assert!(builder.build().is_err());
+    }
+
+    #[test]
+    fn validate_piece_length_ok() {
+        let builder = TorrentBuilder::new("target/", 1024);
+
+        builder.validate_piece_length().unwrap();
+        // validation methods should not modify builder
+        assert_eq!(builder, TorrentBuilder::new("target/", 1024),);
+    }
+
+    #[test]
+    fn validate_piece_length_not_positive() {
+        let builder = TorrentBuilder::new("dir/", -1024);
+
+        match builder.validate_piece_length() {
+            Err(LavaTorrentError::TorrentBuilderFailure(m)) => {
+                assert_eq!(m, "TorrentBuilder has `piece_length` <= 0.");
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn validate_piece_length_not_power_of_two() {
+        let builder = TorrentBuilder::new("dir/", 1023);
+
+        match builder.validate_piece_length() {
+            Err(LavaTorrentError::TorrentBuilderFailure(m)) => assert_eq!(
+                m,
+                "TorrentBuilder has `piece_length` that is not a power of 2."
+            ),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn validate_piece_length_hybrid_too_small() {
+        let builder = TorrentBuilder::new("dir/", 8192).set_hybrid_compatible_layout(true);
+
+        match builder.validate_piece_length() {
+            Err(LavaTorrentError::TorrentBuilderFailure(m)) => assert_eq!(
+                m,
+                "TorrentBuilder has `piece_length` < 16 KiB, which a hybrid compatible layout does not allow."
+            ),
+            _ => panic!(),
+        }
+
+        let builder = builder.set_piece_length(16384);
+        builder.validate_piece_length().unwrap();
+    }
+
+    #[test]
+    fn pad_entries_ok() {
+        let entries = vec![
+            (PathBuf::from("a"), 5),
+            (PathBuf::from("b"), 16),
+            (PathBuf::from("c"), 0),
+            (PathBuf::from("d"), 17),
+            (PathBuf::from("e"), 3),
+        ];
+
+        assert_eq!(
+            TorrentBuilder::pad_entries(entries, 16).unwrap(),
+            vec![
+                (PathBuf::from("a"), 5),
+                (PathBuf::new(), 11),
+                (PathBuf::from("b"), 16),
+                (PathBuf::from("c"), 0),
+                (PathBuf::from("d"), 17),
+                (PathBuf::new(), 15),
+                // the last entry is never padded
+                (PathBuf::from("e"), 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn entries_to_files_padding() {
+        let entries = vec![(PathBuf::from("/root/a"), 5), (PathBuf::new(), 11)];
+
+        assert_eq!(
+            TorrentBuilder::entries_to_files("/root", &entries).unwrap(),
+            vec![
+                File {
+                    length: 5,
+                    path: vec!["a".to_owned()],
+                    extra_fields: None,
+                },
+                File {
+                    length: 11,
+                    path: vec![".pad".to_owned(), "11".to_owned()],
+                    extra_fields: Some(HashMap::from_iter(vec![(
+                        "attr".to_owned(),
+                        bencode_elem!("p"),
+                    )])),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn piece_count_u64_ok() {
+        assert_eq!(TorrentBuilder::piece_count_u64(0, 1 << 62).unwrap(), 0);
+        assert_eq!(TorrentBuilder::piece_count_u64(1, 1 << 62).unwrap(), 1);
+        assert_eq!(
+            TorrentBuilder::piece_count_u64(u64::MAX, 1 << 62).unwrap(),
+            4
+        );
+        assert_eq!(
+            TorrentBuilder::piece_count_u64(u64::MAX, 2).unwrap(),
+            1 << 63
+        );
+    }
+
+    #[test]
+    fn piece_count_u64_invalid() {
+        match TorrentBuilder::piece_count_u64(42, 0) {
+            Err(LavaTorrentError::TorrentBuilderFailure(m)) => assert_eq!(
+                m,
+                "Cannot count the pieces of length 0 needed for 42 bytes."
+            ),
+            _ => panic!(),
+        }
+        match TorrentBuilder::piece_count(-1, 1024) {
+            Err(LavaTorrentError::FailedNumericConv(m)) => {
+                assert_eq!(m, "[-1] does not fit into u64.")
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn preallocated_pieces_ok() {
+        assert_eq!(TorrentBuilder::preallocated_pieces(0), 0);
+        assert_eq!(TorrentBuilder::preallocated_pieces(100), 100);
+        assert_eq!(
+            TorrentBuilder::preallocated_pieces(u64::MAX),
+            MAX_PREALLOCATED_PIECES
+        );
     }
 
     #[test]
-    fn validate_path_has_hidden_component() {
-        let builder = TorrentBuilder::new("./tests/files/.hidden", 42);
-        assert!(builder.validate_path().is_ok());
+    fn total_length_ok() {
+        let entries = vec![(PathBuf::from("a"), u64::MAX - 1), (PathBuf::from("b"), 1)];
+        assert_eq!(TorrentBuilder::total_length(&entries).unwrap(), u64::MAX);
+        assert_eq!(TorrentBuilder::total_length::<PathBuf>(&[]).unwrap(), 0);
     }
 
     #[test]
-    fn validate_path_not_absolute() {
-        let builder = TorrentBuilder::new("target/", 42);
-        assert!(builder.validate_path().is_ok())
+    fn total_length_overflow() {
+        let entries = vec![(PathBuf::from("a"), u64::MAX), (PathBuf::from("b"), 1)];
+        match TorrentBuilder::total_length(&entries) {
+            Err(LavaTorrentError::TorrentBuilderFailure(m)) => {
+                assert_eq!(m, "Total length of the files overflowed in u64.")
+            }
+            _ => panic!(),
+        }
     }
 
     #[test]
-    fn validate_piece_length_ok() {
-        let builder = TorrentBuilder::new("target/", 1024);
+    fn map_pieces_ok() {
+        let entries = vec![("a", 281), ("empty", 0), ("b", 231), ("c", 32)];
+        assert_eq!(
+            TorrentBuilder::map_pieces(&entries, 256).unwrap(),
+            vec![
+                vec![(0, 0, 256)],
+                vec![(0, 256, 25), (2, 0, 231)],
+                vec![(3, 0, 32)],
+            ]
+        );
 
-        builder.validate_piece_length().unwrap();
-        // validation methods should not modify builder
-        assert_eq!(builder, TorrentBuilder::new("target/", 1024),);
+        // a file spanning multiple pieces
+        assert_eq!(
+            TorrentBuilder::map_pieces(&[("a", 10), ("b", 2)], 4).unwrap(),
+            vec![vec![(0, 0, 4)], vec![(0, 4, 4)], vec![(0, 8, 2), (1, 0, 2)],]
+        );
+
+        // huge piece lengths are fine
+        assert_eq!(
+            TorrentBuilder::map_pieces(&[("a", 1), ("b", 1)], 1 << 62).unwrap(),
+            vec![vec![(0, 0, 1), (1, 0, 1)]]
+        );
+
+        assert!(TorrentBuilder::map_pieces(&[("empty", 0)], 256)
+            .unwrap()
+            .is_empty());
+        assert!(TorrentBuilder::map_pieces::<&str>(&[], 256)
+            .unwrap()
+            .is_empty());
     }
 
     #[test]
-    fn validate_piece_length_not_positive() {
-        let builder = TorrentBuilder::new("dir/", -1024);
-
-        match builder.validate_piece_length() {
+    fn map_pieces_invalid() {
+        match TorrentBuilder::map_pieces(&[("a", 42)], 0) {
+            Err(LavaTorrentError::TorrentBuilderFailure(m)) => assert_eq!(
+                m,
+                "Cannot count the pieces of length 0 needed for 42 bytes."
+            ),
+            _ => panic!(),
+        }
+        match TorrentBuilder::map_pieces(&[("a", u64::MAX), ("b", 1)], 256) {
             Err(LavaTorrentError::TorrentBuilderFailure(m)) => {
-                assert_eq!(m, "TorrentBuilder has `piece_length` <= 0.");
+                assert_eq!(m, "Total length of the files overflowed in u64.")
             }
             _ => panic!(),
         }
     }
 
     #[test]
-    fn validate_piece_length_not_power_of_two() {
-        let builder = TorrentBuilder::new("dir/", 1023);
+    fn named_piece_map_ok() {
+        let entries = vec![(PathBuf::from("a"), 3), (PathBuf::from("b"), 2)];
+        assert_eq!(
+            TorrentBuilder::named_piece_map(&entries, 4).unwrap(),
+            vec![
+                vec![(PathBuf::from("a"), 0, 3), (PathBuf::from("b"), 0, 1)],
+                vec![(PathBuf::from("b"), 1, 1)],
+            ]
+        );
+    }
 
-        match builder.validate_piece_length() {
+    #[test]
+    fn rebuild_with_piece_length_invalid() {
+        let source = Torrent::read_from_file("tests/samples/files.torrent").unwrap();
+
+        match TorrentBuilder::rebuild_with_piece_length(&source, "tests/files", 1023) {
             Err(LavaTorrentError::TorrentBuilderFailure(m)) => assert_eq!(
                 m,
                 "TorrentBuilder has `piece_length` that is not a power of 2."
             ),
             _ => panic!(),
         }
+
+        let mut truncated = source.clone();
+        truncated.pieces.pop();
+        match TorrentBuilder::rebuild_with_piece_length(&truncated, "tests/files", 1024) {
+            Err(LavaTorrentError::MalformedTorrent(m)) => assert_eq!(
+                m,
+                format!(
+                    "Torrent has {} pieces, which does not match its length {} and piece length {}.",
+                    source.pieces.len() - 1,
+                    source.length,
+                    source.piece_length,
+                )
+            ),
+            _ => panic!(),
+        }
+
+        let mut escaping = source;
+        escaping.files.as_mut().unwrap()[0].path = vec!["..".to_owned(), "x".to_owned()];
+        match TorrentBuilder::rebuild_with_piece_length(&escaping, "tests/files", 1024) {
+            Err(LavaTorrentError::MalformedTorrent(m)) => {
+                assert_eq!(m, r#"Path of file 0 is unsafe: ["..", "x"]."#)
+            }
+            _ => panic!(),
+        }
     }
 
     #[test]
@@ -1657,27 +5339,450 @@ mod torrent_builder_tests {
         }
     }
 
+    fn assembled(
+        files: Vec<File>,
+        pieces: usize,
+        options: AssembleOptions,
+    ) -> Result<Torrent, LavaTorrentError> {
+        Torrent::from_pieces(
+            "sample".to_owned(),
+            4,
+            files,
+            vec![[7; PIECE_STRING_LENGTH]; pieces],
+            options,
+        )
+    }
+
+    fn file(path: &[&str], length: Integer) -> File {
+        File {
+            length,
+            path: path.iter().map(|&component| component.to_owned()).collect(),
+            extra_fields: None,
+        }
+    }
+
+    fn assemble_err(result: Result<Torrent, LavaTorrentError>) -> String {
+        match result {
+            Err(LavaTorrentError::InvalidArgument(m)) => m.into_owned(),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn from_pieces_ok() {
+        let torrent = assembled(
+            vec![file(&["a"], 5), file(&["dir", "b"], 0), file(&["c"], 3)],
+            2,
+            AssembleOptions {
+                announce: Some("udp://tracker.example".to_owned()),
+                extra_fields: Some(Dictionary::new()),
+                private: true,
+                ..AssembleOptions::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(torrent.length, 8);
+        assert_eq!(torrent.files.as_ref().unwrap().len(), 3);
+        assert_eq!(torrent.private, Some(true));
+        // dropped rather than rejected
+        assert_eq!(torrent.extra_fields, None);
+        assert!(torrent.revalidate().is_ok());
+
+        let torrent = assembled(
+            vec![],
+            3,
+            AssembleOptions {
+                length: Some(9),
+                ..AssembleOptions::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(torrent.length, 9);
+        assert_eq!(torrent.files, None);
+        assert_eq!(torrent.private, None);
+        assert!(torrent.revalidate().is_ok());
+    }
+
+    #[test]
+    fn from_pieces_piece_count() {
+        for (pieces, expected) in [
+            (
+                1,
+                "Assembled torrent has 1 pieces, 2 expected for length 5.",
+            ),
+            (
+                3,
+                "Assembled torrent has 3 pieces, 2 expected for length 5.",
+            ),
+        ] {
+            assert_eq!(
+                assemble_err(assembled(
+                    vec![file(&["a"], 5)],
+                    pieces,
+                    AssembleOptions::default()
+                )),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn from_pieces_invalid_length() {
+        let single_file = |length| AssembleOptions {
+            length: Some(length),
+            ..AssembleOptions::default()
+        };
+
+        for (files, options, expected) in [
+            (
+                vec![],
+                AssembleOptions::default(),
+                "Assembled torrent has no `files` but `length` is not set.",
+            ),
+            (
+                vec![file(&["a"], 4)],
+                single_file(4),
+                "Assembled torrent has `files` but `length` is set as well.",
+            ),
+            (
+                vec![],
+                single_file(0),
+                "Assembled torrent has `length` <= 0.",
+            ),
+            (
+                vec![],
+                single_file(-4),
+                "Assembled torrent has `length` <= 0.",
+            ),
+            (
+                vec![file(&["a"], 0)],
+                AssembleOptions::default(),
+                "Assembled torrent has `length` <= 0.",
+            ),
+            (
+                vec![file(&["a"], 8), file(&["b"], -4)],
+                AssembleOptions::default(),
+                "Assembled torrent has file 1 whose length < 0.",
+            ),
+            (
+                vec![file(&["a"], Integer::MAX), file(&["b"], 1)],
+                AssembleOptions::default(),
+                "Assembled torrent has files whose lengths overflowed in i64.",
+            ),
+        ] {
+            assert_eq!(assemble_err(assembled(files, 1, options)), expected);
+        }
+    }
+
+    #[test]
+    fn from_pieces_unsafe_path() {
+        for path in [&[][..], &[""], &["."], &["dir", ".."], &["/etc"], &["a\\b"]] {
+            assert_eq!(
+                assemble_err(assembled(
+                    vec![file(path, 4)],
+                    1,
+                    AssembleOptions::default()
+                )),
+                format!(
+                    "Assembled torrent has file 0 whose path is unsafe: {:?}.",
+                    path
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn from_pieces_invalid_name_or_piece_length() {
+        let options = || AssembleOptions {
+            length: Some(4),
+            ..AssembleOptions::default()
+        };
+
+        for (name, piece_length, expected) in [
+            ("", 4, "Assembled torrent has `name` but its length is 0."),
+            (
+                "..",
+                4,
+                "Assembled torrent has `name` but it is `.` or `..`.",
+            ),
+            (
+                "a/b",
+                4,
+                "Assembled torrent has `name` but it contains a path separator.",
+            ),
+            ("sample", 0, "Assembled torrent has `piece_length` <= 0."),
+            (
+                "sample",
+                3,
+                "Assembled torrent has `piece_length` that is not a power of 2.",
+            ),
+        ] {
+            let result = Torrent::from_pieces(
+                name.to_owned(),
+                piece_length,
+                vec![],
+                vec![[7; PIECE_STRING_LENGTH]; 1],
+                options(),
+            );
+            assert_eq!(assemble_err(result), expected);
+        }
+    }
+
+    #[test]
+    fn from_pieces_invalid_options() {
+        let options = AssembleOptions {
+            length: Some(4),
+            ..AssembleOptions::default()
+        };
+
+        for (options, expected) in [
+            (
+                AssembleOptions {
+                    announce: Some(String::new()),
+                    ..options.clone()
+                },
+                "Assembled torrent has `announce` but its length is 0.",
+            ),
+            (
+                AssembleOptions {
+                    announce_list: Some(vec![]),
+                    ..options.clone()
+                },
+                "Assembled torrent has `announce_list` but it's empty.",
+            ),
+            (
+                AssembleOptions {
+                    announce_list: Some(vec![vec!["a".to_owned()], vec![]]),
+                    ..options.clone()
+                },
+                "Assembled torrent has `announce_list` but one of its tiers is empty.",
+            ),
+            (
+                AssembleOptions {
+                    announce_list: Some(vec![vec!["a".to_owned(), String::new()]]),
+                    ..options.clone()
+                },
+                "Assembled torrent has `announce_list` but one of its tiers contains a 0-length url.",
+            ),
+            (
+                AssembleOptions {
+                    extra_fields: Some(HashMap::from_iter(vec![(String::new(), bencode_elem!(1))])),
+                    ..options.clone()
+                },
+                "Assembled torrent has `extra_fields` but it contains a 0-length key.",
+            ),
+            (
+                AssembleOptions {
+                    extra_info_fields: Some(HashMap::from_iter(vec![(String::new(), bencode_elem!(1))])),
+                    ..options.clone()
+                },
+                "Assembled torrent has `extra_info_fields` but it contains a 0-length key.",
+            ),
+        ] {
+            assert_eq!(assemble_err(assembled(vec![], 1, options)), expected);
+        }
+    }
+
+    #[test]
+    fn resolved_root_ok() {
+        let builder = TorrentBuilder::new("tests/files/symlink", 42);
+        assert_eq!(
+            builder.resolved_root().unwrap(),
+            PathBuf::from("tests/files/ubuntu-16.04.4-desktop-amd64.iso.torrent")
+                .canonicalize()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn resolved_root_does_not_exist() {
+        let builder = TorrentBuilder::new("tests/files/does-not-exist", 42);
+        assert!(matches!(
+            builder.resolved_root(),
+            Err(LavaTorrentError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn derive_name_ok() {
+        assert_eq!(
+            TorrentBuilder::derive_name("dir/current", "/releases/v1.2.3").unwrap(),
+            "current"
+        );
+    }
+
+    #[test]
+    fn derive_name_dot() {
+        assert_eq!(
+            TorrentBuilder::derive_name(".", "/releases/v1.2.3").unwrap(),
+            "v1.2.3"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn resolve_layout_explicit_name() {
+        // `/` has no name to derive, which doesn't matter if one is set
+        let builder = TorrentBuilder::new("/", 42);
+        assert!(builder
+            .clone()
+            .resolve_layout(&mut BuildReport::default())
+            .is_err());
+        assert_eq!(
+            builder
+                .set_name("root".to_owned())
+                .resolve_layout(&mut BuildReport::default())
+                .unwrap(),
+            (
+                PathBuf::from("/").canonicalize().unwrap(),
+                "root".to_owned(),
+                None
+            )
+        );
+    }
+
+    #[test]
+    fn derive_name_dot_dot() {
+        assert_eq!(
+            TorrentBuilder::derive_name("current/..", "/releases/v1.2.3").unwrap(),
+            "v1.2.3"
+        );
+    }
+
+    // Records the largest single read.
+    struct MaxReadRecorder<R> {
+        inner: R,
+        max_read: usize,
+    }
+
+    impl<R: Read> Read for MaxReadRecorder<R> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let read = self.inner.read(buf)?;
+            self.max_read = self.max_read.max(read);
+            Ok(read)
+        }
+    }
+
+    #[test]
+    fn hash_from_reader_ok() {
+        let piece_length = 8 * 1024 * 1024; // 8 MiB
+        let chunk_size = 64 * 1024;
+        let bytes = std::fs::read("tests/files/byte_sequence")
+            .unwrap()
+            .into_iter()
+            .cycle()
+            .take(piece_length + 100)
+            .collect::<Vec<u8>>();
+        let mut reader = MaxReadRecorder {
+            inner: std::io::Cursor::new(&bytes),
+            max_read: 0,
+        };
+        let mut hasher = Sha1::new();
+        let mut buf = vec![0; chunk_size];
+
+        TorrentBuilder::hash_from_reader(
+            &mut reader,
+            piece_length as u64,
+            &mut hasher,
+            &mut buf,
+            &|| Ok(()),
+        )
+        .unwrap();
+
+        assert_eq!(hasher.finalize(), Sha1::digest(&bytes[..piece_length]));
+        assert!(reader.max_read <= chunk_size);
+    }
+
+    #[test]
+    fn hash_from_reader_unexpected_eof() {
+        let mut hasher = Sha1::new();
+        let mut buf = vec![0; 4];
+
+        match TorrentBuilder::hash_from_reader(
+            &mut std::io::Cursor::new(vec![0; 10]),
+            11,
+            &mut hasher,
+            &mut buf,
+            &|| Ok(()),
+        ) {
+            Err(LavaTorrentError::Io(e)) => {
+                assert_eq!(e.kind(), std::io::ErrorKind::UnexpectedEof)
+            }
+            _ => panic!(),
+        }
+    }
+
+    // Cancels the build on the first read, as if the
+    // client gave up while waiting on slow storage.
+    struct CancelingReader {
+        is_canceled: Arc<AtomicBool>,
+        n_reads: usize,
+    }
+
+    impl Read for CancelingReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.is_canceled.store(true, Ordering::Release);
+            self.n_reads += 1;
+            buf.fill(0);
+            Ok(buf.len())
+        }
+    }
+
+    #[test]
+    fn hash_from_reader_canceled() {
+        let piece_length = 8 * 1024 * 1024; // 8 MiB
+        let chunk_size = 64 * 1024;
+        let torrent_build = TorrentBuildInternal {
+            n_piece_processed: Arc::new(AtomicU64::new(0)),
+            n_piece_total: Arc::new(AtomicU64::new(0)),
+            is_canceled: Arc::new(AtomicBool::new(false)),
+        };
+        let mut reader = CancelingReader {
+            is_canceled: torrent_build.is_canceled.clone(),
+            n_reads: 0,
+        };
+        let mut hasher = Sha1::new();
+        let mut buf = vec![0; chunk_size];
+
+        match TorrentBuilder::hash_from_reader(
+            &mut reader,
+            piece_length,
+            &mut hasher,
+            &mut buf,
+            &|| torrent_build.check_canceled(),
+        ) {
+            Err(LavaTorrentError::TorrentBuilderFailure(m)) => {
+                assert_eq!(m, "build canceled by client")
+            }
+            _ => panic!(),
+        }
+        // stopped after 1 chunk instead of reading the whole piece
+        assert_eq!(reader.n_reads, 1);
+    }
+
     #[test]
     fn read_file_ok() {
         // byte_sequence contains 256 bytes ranging from 0x0 to 0xff
-        let (length, pieces) = TorrentBuilder::read_file("tests/files/byte_sequence", 64).unwrap();
+        let (length, pieces) =
+            TorrentBuilder::read_file("tests/files/byte_sequence", 64, 24, &FilePins::default())
+                .unwrap();
         assert_eq!(length, 256);
         assert_eq!(
             pieces,
             vec![
-                vec![
+                [
                     198, 19, 141, 81, 79, 250, 33, 53, 191, 206, 14, 208, 184, 250, 198, 86, 105,
                     145, 126, 199,
                 ],
-                vec![
+                [
                     8, 244, 44, 162, 89, 207, 18, 29, 46, 169, 205, 139, 108, 91, 36, 200, 109,
                     115, 61, 183,
                 ],
-                vec![
+                [
                     156, 122, 162, 177, 31, 39, 9, 152, 166, 59, 27, 23, 149, 207, 243, 137, 10,
                     78, 181, 111,
                 ],
-                vec![
+                [
                     185, 161, 57, 156, 18, 128, 41, 140, 193, 70, 116, 118, 156, 255, 135, 160,
                     167, 133, 230, 171,
                 ],
@@ -1689,24 +5794,24 @@ mod torrent_builder_tests {
     fn read_file_parallel_ok() {
         // byte_sequence contains 256 bytes ranging from 0x0 to 0xff
         let (length, pieces) =
-            TorrentBuilder::read_file_parallel("tests/files/byte_sequence", 64, 3).unwrap();
+            TorrentBuilder::read_file_parallel("tests/files/byte_sequence", 64, 24, 3).unwrap();
         assert_eq!(length, 256);
         assert_eq!(
             pieces,
             vec![
-                vec![
+                [
                     198, 19, 141, 81, 79, 250, 33, 53, 191, 206, 14, 208, 184, 250, 198, 86, 105,
                     145, 126, 199,
                 ],
-                vec![
+                [
                     8, 244, 44, 162, 89, 207, 18, 29, 46, 169, 205, 139, 108, 91, 36, 200, 109,
                     115, 61, 183,
                 ],
-                vec![
+                [
                     156, 122, 162, 177, 31, 39, 9, 152, 166, 59, 27, 23, 149, 207, 243, 137, 10,
                     78, 181, 111,
                 ],
-                vec![
+                [
                     185, 161, 57, 156, 18, 128, 41, 140, 193, 70, 116, 118, 156, 255, 135, 160,
                     167, 133, 230, 171,
                 ],
@@ -1730,6 +5835,8 @@ mod torrent_builder_tests {
             TorrentBuilder::read_file_non_blocking(
                 "tests/files/byte_sequence", // byte_sequence contains 256 bytes ranging from 0x0 to 0xff
                 64,
+                24,
+                &FilePins::default(),
                 torrent_build_internal,
             )
         })
@@ -1745,19 +5852,19 @@ mod torrent_builder_tests {
         assert_eq!(
             pieces,
             vec![
-                vec![
+                [
                     198, 19, 141, 81, 79, 250, 33, 53, 191, 206, 14, 208, 184, 250, 198, 86, 105,
                     145, 126, 199,
                 ],
-                vec![
+                [
                     8, 244, 44, 162, 89, 207, 18, 29, 46, 169, 205, 139, 108, 91, 36, 200, 109,
                     115, 61, 183,
                 ],
-                vec![
+                [
                     156, 122, 162, 177, 31, 39, 9, 152, 166, 59, 27, 23, 149, 207, 243, 137, 10,
                     78, 181, 111,
                 ],
-                vec![
+                [
                     185, 161, 57, 156, 18, 128, 41, 140, 193, 70, 116, 118, 156, 255, 135, 160,
                     167, 133, 230, 171,
                 ],
@@ -1782,6 +5889,8 @@ mod torrent_builder_tests {
             TorrentBuilder::read_file_non_blocking(
                 "tests/files/byte_sequence", // byte_sequence contains 256 bytes ranging from 0x0 to 0xff
                 64,
+                24,
+                &FilePins::default(),
                 torrent_build_internal,
             )
         });
@@ -1816,7 +5925,91 @@ mod torrent_builder_tests {
             TorrentBuilder::read_file_parallel_non_blocking(
                 "tests/files/byte_sequence", // byte_sequence contains 256 bytes ranging from 0x0 to 0xff
                 64,
+                24,
+                3,
+                torrent_build_internal,
+            )
+        })
+        .join()
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(n_piece_processed.load(Ordering::Acquire), 4);
+        assert_eq!(n_piece_total.load(Ordering::Acquire), 4);
+        assert!(!is_canceled.load(Ordering::Acquire));
+
+        assert_eq!(length, 256);
+        assert_eq!(
+            pieces,
+            vec![
+                [
+                    198, 19, 141, 81, 79, 250, 33, 53, 191, 206, 14, 208, 184, 250, 198, 86, 105,
+                    145, 126, 199,
+                ],
+                [
+                    8, 244, 44, 162, 89, 207, 18, 29, 46, 169, 205, 139, 108, 91, 36, 200, 109,
+                    115, 61, 183,
+                ],
+                [
+                    156, 122, 162, 177, 31, 39, 9, 152, 166, 59, 27, 23, 149, 207, 243, 137, 10,
+                    78, 181, 111,
+                ],
+                [
+                    185, 161, 57, 156, 18, 128, 41, 140, 193, 70, 116, 118, 156, 255, 135, 160,
+                    167, 133, 230, 171,
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn read_file_pipelined_ok() {
+        // byte_sequence contains 256 bytes ranging from 0x0 to 0xff
+        let (length, pieces) =
+            TorrentBuilder::read_file_pipelined("tests/files/byte_sequence", 64, 24, 3, 1).unwrap();
+        assert_eq!(length, 256);
+        assert_eq!(
+            pieces,
+            vec![
+                [
+                    198, 19, 141, 81, 79, 250, 33, 53, 191, 206, 14, 208, 184, 250, 198, 86, 105,
+                    145, 126, 199,
+                ],
+                [
+                    8, 244, 44, 162, 89, 207, 18, 29, 46, 169, 205, 139, 108, 91, 36, 200, 109,
+                    115, 61, 183,
+                ],
+                [
+                    156, 122, 162, 177, 31, 39, 9, 152, 166, 59, 27, 23, 149, 207, 243, 137, 10,
+                    78, 181, 111,
+                ],
+                [
+                    185, 161, 57, 156, 18, 128, 41, 140, 193, 70, 116, 118, 156, 255, 135, 160,
+                    167, 133, 230, 171,
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn read_file_pipelined_non_blocking_ok() {
+        let n_piece_processed = Arc::new(AtomicU64::new(0));
+        let n_piece_total = Arc::new(AtomicU64::new(0));
+        let is_canceled = Arc::new(AtomicBool::new(false));
+
+        let torrent_build_internal = TorrentBuildInternal {
+            n_piece_processed: n_piece_processed.clone(),
+            n_piece_total: n_piece_total.clone(),
+            is_canceled: is_canceled.clone(),
+        };
+
+        let (length, pieces) = std::thread::spawn(|| {
+            TorrentBuilder::read_file_pipelined_non_blocking(
+                "tests/files/byte_sequence", // byte_sequence contains 256 bytes ranging from 0x0 to 0xff
+                64,
+                24,
                 3,
+                1,
                 torrent_build_internal,
             )
         })
@@ -1832,19 +6025,19 @@ mod torrent_builder_tests {
         assert_eq!(
             pieces,
             vec![
-                vec![
+                [
                     198, 19, 141, 81, 79, 250, 33, 53, 191, 206, 14, 208, 184, 250, 198, 86, 105,
                     145, 126, 199,
                 ],
-                vec![
+                [
                     8, 244, 44, 162, 89, 207, 18, 29, 46, 169, 205, 139, 108, 91, 36, 200, 109,
                     115, 61, 183,
                 ],
-                vec![
+                [
                     156, 122, 162, 177, 31, 39, 9, 152, 166, 59, 27, 23, 149, 207, 243, 137, 10,
                     78, 181, 111,
                 ],
-                vec![
+                [
                     185, 161, 57, 156, 18, 128, 41, 140, 193, 70, 116, 118, 156, 255, 135, 160,
                     167, 133, 230, 171,
                 ],
@@ -1869,6 +6062,7 @@ mod torrent_builder_tests {
             TorrentBuilder::read_file_parallel_non_blocking(
                 "tests/files/byte_sequence", // byte_sequence contains 256 bytes ranging from 0x0 to 0xff
                 64,
+                24,
                 3,
                 torrent_build_internal,
             )
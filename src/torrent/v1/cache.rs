@@ -0,0 +1,459 @@
+use super::*;
+use crate::bencode::write::encode_dictionary;
+
+/// Marks the start of a cache entry.
+const CACHE_MAGIC: [u8; 4] = *b"LTC\x01";
+/// Bump whenever the layout changes.
+const CACHE_VERSION: u16 = 1;
+/// Set in the flags byte if pieces are included.
+const CACHE_FLAG_PIECES: u8 = 0b1;
+
+// Layout (all integers are little-endian):
+//
+// magic, version (u16), flags (u8),
+// announce (opt str), announce-list (opt list of list of str),
+// length (i64), files (opt list of (length (i64), path (list of str),
+// extra fields (opt dict))), name (str), piece length (i64),
+// pieces (u64 count, then the hashes back to back; only if flagged),
+// private (u8: 0 = none, 1 = false, 2 = true),
+// extra fields (opt dict), extra info fields (opt dict)
+//
+// Lengths and counts are u64. An "opt" is a u8 tag (0 = none, 1 = some)
+// followed by the value. A dict is a length-prefixed bencoded dictionary.
+
+impl Torrent {
+    /// Serialize `self` into a compact binary form meant to be cached,
+    /// e.g. on disk by a client that loads many torrents at startup.
+    ///
+    /// Loading it with [`from_cache_bytes()`](#method.from_cache_bytes) is
+    /// much faster than parsing the original *.torrent* file, as pieces are
+    /// stored as one contiguous blob and strings are length-prefixed.
+    ///
+    /// If `include_pieces` is `false`, pieces are left out. The loaded
+    /// torrent is then a stub with empty `pieces`
+    /// (see [`has_pieces()`](#method.has_pieces)), and the original file has
+    /// to be read again before verifying data.
+    ///
+    /// The format is specific to this library and versioned. It is not
+    /// meant to be exchanged with other programs.
+    pub fn to_cache_bytes(&self, include_pieces: bool) -> Vec<u8> {
+        let pieces_len = if include_pieces {
+            self.pieces.len() * PIECE_STRING_LENGTH
+        } else {
+            0
+        };
+        let mut dst = Vec::with_capacity(256 + pieces_len);
+
+        dst.extend_from_slice(&CACHE_MAGIC);
+        dst.extend_from_slice(&CACHE_VERSION.to_le_bytes());
+        dst.push(if include_pieces { CACHE_FLAG_PIECES } else { 0 });
+
+        put_option(&mut dst, self.announce.as_deref(), put_str);
+        put_option(&mut dst, self.announce_list.as_deref(), |dst, list| {
+            put_len(dst, list.len());
+            for tier in list {
+                put_strs(dst, tier);
+            }
+        });
+        dst.extend_from_slice(&self.length.to_le_bytes());
+        put_option(&mut dst, self.files.as_deref(), |dst, files| {
+            put_len(dst, files.len());
+            for file in files {
+                dst.extend_from_slice(&file.length.to_le_bytes());
+                put_strs(dst, &file.path);
+                put_option(dst, file.extra_fields.as_ref(), put_dictionary);
+            }
+        });
+        put_str(&mut dst, &self.name);
+        dst.extend_from_slice(&self.piece_length.to_le_bytes());
+        if include_pieces {
+            put_len(&mut dst, self.pieces.len());
+            dst.extend(self.pieces.iter().flatten());
+        }
+        dst.push(match self.private {
+            None => 0,
+            Some(false) => 1,
+            Some(true) => 2,
+        });
+        put_option(&mut dst, self.extra_fields.as_ref(), put_dictionary);
+        put_option(&mut dst, self.extra_info_fields.as_ref(), put_dictionary);
+
+        dst
+    }
+
+    /// Load a torrent serialized by [`to_cache_bytes()`](#method.to_cache_bytes).
+    ///
+    /// Entries written by a newer version of this library, as well
+    /// as corrupted or truncated entries, are rejected with
+    /// `LavaTorrentError::InvalidArgument`. Since entries are assumed to
+    /// come from `to_cache_bytes()`, the torrent is **not** validated
+    /// again (e.g. a stub without pieces is accepted).
+    pub fn from_cache_bytes<B>(bytes: B) -> Result<Torrent, LavaTorrentError>
+    where
+        B: AsRef<[u8]>,
+    {
+        let mut src = CacheReader {
+            bytes: bytes.as_ref(),
+        };
+
+        if src.take(CACHE_MAGIC.len())? != CACHE_MAGIC {
+            return Err(LavaTorrentError::InvalidArgument(Cow::Borrowed(
+                "Not a torrent cache entry.",
+            )));
+        }
+        let version = u16::from_le_bytes(src.array()?);
+        if version > CACHE_VERSION {
+            return Err(LavaTorrentError::InvalidArgument(Cow::Owned(format!(
+                "Cache entry version {} is newer than the supported version {}.",
+                version, CACHE_VERSION
+            ))));
+        }
+        let flags = src.u8()?;
+
+        let announce = src.option(CacheReader::string)?;
+        let announce_list = src.option(|src| src.list(CacheReader::strings))?;
+        let length = src.integer()?;
+        let files = src.option(|src| {
+            src.list(|src| {
+                Ok(File {
+                    length: src.integer()?,
+                    path: src.strings()?,
+                    extra_fields: src.option(CacheReader::dictionary)?,
+                })
+            })
+        })?;
+        let name = src.string()?;
+        let piece_length = src.integer()?;
+        let pieces = if flags & CACHE_FLAG_PIECES != 0 {
+            src.pieces()?
+        } else {
+            Vec::new()
+        };
+        let private = match src.u8()? {
+            0 => None,
+            1 => Some(false),
+            2 => Some(true),
+            _ => return Err(corrupted()),
+        };
+        let extra_fields = src.option(CacheReader::dictionary)?;
+        let extra_info_fields = src.option(CacheReader::dictionary)?;
+
+        if !src.bytes.is_empty() {
+            return Err(LavaTorrentError::InvalidArgument(Cow::Borrowed(
+                "Cache entry has trailing bytes.",
+            )));
+        }
+
+        Ok(Torrent {
+            announce,
+            announce_list,
+            length,
+            files,
+            name,
+            piece_length,
+            pieces,
+            private,
+            extra_fields,
+            extra_info_fields,
+        })
+    }
+
+    /// Check if `pieces` covers the whole torrent, i.e. if there is one
+    /// piece for every `piece_length` bytes.
+    ///
+    /// This is `false` for stubs loaded by
+    /// [`from_cache_bytes()`](#method.from_cache_bytes) from entries
+    /// written without pieces, in which case the original *.torrent* file
+    /// has to be read again before verifying data.
+    pub fn has_pieces(&self) -> bool {
+        Self::expected_piece_count(self.length, self.piece_length) == Some(self.pieces.len())
+    }
+}
+
+impl<'a> CacheReader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], LavaTorrentError> {
+        if n > self.bytes.len() {
+            return Err(LavaTorrentError::InvalidArgument(Cow::Borrowed(
+                "Cache entry is truncated.",
+            )));
+        }
+        let (taken, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], LavaTorrentError> {
+        // `take()` returns exactly `N` bytes
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, LavaTorrentError> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn integer(&mut self) -> Result<Integer, LavaTorrentError> {
+        Ok(Integer::from_le_bytes(self.array()?))
+    }
+
+    fn len(&mut self) -> Result<usize, LavaTorrentError> {
+        usize::try_from(u64::from_le_bytes(self.array()?)).map_err(|_| corrupted())
+    }
+
+    fn option<T, F>(&mut self, read: F) -> Result<Option<T>, LavaTorrentError>
+    where
+        F: FnOnce(&mut Self) -> Result<T, LavaTorrentError>,
+    {
+        match self.u8()? {
+            0 => Ok(None),
+            1 => read(self).map(Some),
+            _ => Err(corrupted()),
+        }
+    }
+
+    fn list<T, F>(&mut self, mut read: F) -> Result<Vec<T>, LavaTorrentError>
+    where
+        F: FnMut(&mut Self) -> Result<T, LavaTorrentError>,
+    {
+        let len = self.len()?;
+        // each element takes at least 1 byte, so don't trust `len` blindly
+        let mut list = Vec::with_capacity(len.min(self.bytes.len()));
+        for _ in 0..len {
+            list.push(read(self)?);
+        }
+        Ok(list)
+    }
+
+    fn string(&mut self) -> Result<String, LavaTorrentError> {
+        let len = self.len()?;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| corrupted())
+    }
+
+    fn strings(&mut self) -> Result<Vec<String>, LavaTorrentError> {
+        self.list(Self::string)
+    }
+
+    fn dictionary(&mut self) -> Result<Dictionary, LavaTorrentError> {
+        let len = self.len()?;
+        match BencodeElem::from_bytes_prefix(self.take(len)?) {
+            Ok((BencodeElem::Dictionary(dict), consumed)) if consumed == len => Ok(dict),
+            _ => Err(corrupted()),
+        }
+    }
+
+    fn pieces(&mut self) -> Result<Vec<Piece>, LavaTorrentError> {
+        let len = self.len()?;
+        let blob = self.take(len.checked_mul(PIECE_STRING_LENGTH).ok_or_else(corrupted)?)?;
+        Ok(blob
+            .chunks_exact(PIECE_STRING_LENGTH)
+            // chunks are exactly `PIECE_STRING_LENGTH` long
+            .map(|chunk| chunk.try_into().unwrap())
+            .collect())
+    }
+}
+
+fn corrupted() -> LavaTorrentError {
+    LavaTorrentError::InvalidArgument(Cow::Borrowed("Cache entry is corrupted."))
+}
+
+fn put_len(dst: &mut Vec<u8>, len: usize) {
+    dst.extend_from_slice(&(len as u64).to_le_bytes());
+}
+
+fn put_bytes(dst: &mut Vec<u8>, bytes: &[u8]) {
+    put_len(dst, bytes.len());
+    dst.extend_from_slice(bytes);
+}
+
+fn put_str(dst: &mut Vec<u8>, string: &str) {
+    put_bytes(dst, string.as_bytes());
+}
+
+fn put_strs(dst: &mut Vec<u8>, strings: &[String]) {
+    put_len(dst, strings.len());
+    for string in strings {
+        put_str(dst, string);
+    }
+}
+
+fn put_dictionary(dst: &mut Vec<u8>, dict: &Dictionary) {
+    put_bytes(dst, &encode_dictionary(dict));
+}
+
+fn put_option<T, F>(dst: &mut Vec<u8>, value: Option<T>, put: F)
+where
+    F: FnOnce(&mut Vec<u8>, T),
+{
+    match value {
+        Some(value) => {
+            dst.push(1);
+            put(dst, value);
+        }
+        None => dst.push(0),
+    }
+}
+
+#[cfg(test)]
+mod cache_tests {
+    use super::*;
+
+    fn sample() -> Torrent {
+        let mut extra_fields = HashMap::new();
+        extra_fields.insert(
+            "comment".to_owned(),
+            BencodeElem::String("sample".to_owned()),
+        );
+        let mut file_extra_fields = HashMap::new();
+        file_extra_fields.insert("md5sum".to_owned(), BencodeElem::String("0123".to_owned()));
+
+        Torrent {
+            announce: Some("udp://tracker.example.com:6969".to_owned()),
+            announce_list: Some(vec![
+                vec!["udp://a.example.com".to_owned()],
+                vec![
+                    "udp://b.example.com".to_owned(),
+                    "udp://c.example.com".to_owned(),
+                ],
+            ]),
+            length: 5,
+            files: Some(vec![
+                File {
+                    length: 2,
+                    path: vec!["dir".to_owned(), "ä.txt".to_owned()],
+                    extra_fields: Some(file_extra_fields),
+                },
+                File {
+                    length: 3,
+                    path: vec!["b".to_owned()],
+                    extra_fields: None,
+                },
+            ]),
+            name: "sample".to_owned(),
+            piece_length: 4,
+            pieces: vec![[1; PIECE_STRING_LENGTH], [2; PIECE_STRING_LENGTH]],
+            private: Some(true),
+            extra_fields: Some(extra_fields),
+            extra_info_fields: None,
+        }
+    }
+
+    #[test]
+    fn cache_round_trip() {
+        let torrent = sample();
+        let bytes = torrent.to_cache_bytes(true);
+        assert_eq!(Torrent::from_cache_bytes(&bytes).unwrap(), torrent);
+        assert!(torrent.has_pieces());
+    }
+
+    #[test]
+    fn cache_round_trip_minimal() {
+        let torrent = Torrent {
+            announce: None,
+            announce_list: None,
+            files: None,
+            private: None,
+            extra_fields: None,
+            ..sample()
+        };
+        let bytes = torrent.to_cache_bytes(true);
+        assert_eq!(Torrent::from_cache_bytes(&bytes).unwrap(), torrent);
+    }
+
+    #[test]
+    fn cache_stub() {
+        let torrent = sample();
+        let stub = Torrent::from_cache_bytes(torrent.to_cache_bytes(false)).unwrap();
+        assert!(!stub.has_pieces());
+        assert!(stub.pieces.is_empty());
+        assert_eq!(
+            stub,
+            Torrent {
+                pieces: Vec::new(),
+                ..torrent
+            }
+        );
+    }
+
+    #[test]
+    fn has_pieces_mismatch() {
+        let mut torrent = sample();
+        torrent.pieces.push([3; PIECE_STRING_LENGTH]);
+        assert!(!torrent.has_pieces());
+
+        let torrent = Torrent {
+            piece_length: 0,
+            ..sample()
+        };
+        assert!(!torrent.has_pieces());
+    }
+
+    #[test]
+    fn from_cache_bytes_future_version() {
+        let mut bytes = sample().to_cache_bytes(true);
+        bytes[CACHE_MAGIC.len()..CACHE_MAGIC.len() + 2]
+            .copy_from_slice(&(CACHE_VERSION + 1).to_le_bytes());
+        match Torrent::from_cache_bytes(&bytes) {
+            Err(LavaTorrentError::InvalidArgument(m)) => assert_eq!(
+                m,
+                format!(
+                    "Cache entry version {} is newer than the supported version {}.",
+                    CACHE_VERSION + 1,
+                    CACHE_VERSION
+                )
+            ),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn from_cache_bytes_bad_magic() {
+        let bytes = BencodeElem::String("not a cache entry".to_owned()).encode();
+        match Torrent::from_cache_bytes(bytes) {
+            Err(LavaTorrentError::InvalidArgument(m)) => {
+                assert_eq!(m, "Not a torrent cache entry.")
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn from_cache_bytes_truncated() {
+        let bytes = sample().to_cache_bytes(true);
+        for len in 0..bytes.len() {
+            match Torrent::from_cache_bytes(&bytes[..len]) {
+                Err(LavaTorrentError::InvalidArgument(m)) => {
+                    assert_eq!(m, "Cache entry is truncated.")
+                }
+                other => panic!("{} {:?}", len, other),
+            }
+        }
+    }
+
+    #[test]
+    fn from_cache_bytes_trailing_bytes() {
+        let mut bytes = sample().to_cache_bytes(true);
+        bytes.push(0);
+        match Torrent::from_cache_bytes(&bytes) {
+            Err(LavaTorrentError::InvalidArgument(m)) => {
+                assert_eq!(m, "Cache entry has trailing bytes.")
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn from_cache_bytes_corrupted() {
+        let torrent = Torrent {
+            announce: None,
+            ..sample()
+        };
+        let mut bytes = torrent.to_cache_bytes(true);
+        // the `announce` tag
+        bytes[CACHE_MAGIC.len() + 3] = 7;
+        match Torrent::from_cache_bytes(&bytes) {
+            Err(LavaTorrentError::InvalidArgument(m)) => {
+                assert_eq!(m, "Cache entry is corrupted.")
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+}
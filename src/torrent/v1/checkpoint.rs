@@ -0,0 +1,446 @@
+use super::*;
+use crate::util;
+
+/// Bump whenever the meaning of an existing key changes.
+const CHECKPOINT_VERSION: Integer = 1;
+
+// Layout (bencoded):
+//
+// d
+//   checkpoint version (int),
+//   piece length (int),
+//   files (list of d length (int), path (opt list of str), mtime (opt [secs, nanos]) e),
+//   pieces (bytes; the hashes back to back)
+// e
+//
+// `path` is absent for padding entries and `mtime` is absent
+// if the platform doesn't provide one.
+
+impl BuildCheckpoint {
+    // The manifest of `entries` (as listed under `root`).
+    pub(crate) fn manifest(
+        root: &Path,
+        entries: &[(PathBuf, u64)],
+        follow_symlinks: bool,
+    ) -> Result<Vec<ManifestEntry>, LavaTorrentError> {
+        entries
+            .iter()
+            .map(|(path, length)| {
+                if path.as_os_str().is_empty() {
+                    return Ok(ManifestEntry {
+                        path: None,
+                        length: *length,
+                        mtime: None,
+                    });
+                }
+
+                let metadata = if follow_symlinks {
+                    path.metadata()?
+                } else {
+                    path.symlink_metadata()?
+                };
+                // mtimes before the epoch are treated as unavailable
+                let mtime = metadata
+                    .modified()
+                    .ok()
+                    .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+                    .map(|mtime| (mtime.as_secs(), mtime.subsec_nanos()));
+
+                // Unwrap is fine here since `root` is by definition a parent
+                // to `path`. The result is empty for a single file.
+                Ok(ManifestEntry {
+                    path: Some(TorrentBuilder::path_components(
+                        path.strip_prefix(root).unwrap(),
+                    )),
+                    length: *length,
+                    mtime,
+                })
+            })
+            .collect()
+    }
+
+    // Load the checkpoint at `path`, if there is one.
+    pub(crate) fn load(path: &Path) -> Result<Option<BuildCheckpoint>, LavaTorrentError> {
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        Self::from_bencode(&bytes).map(Some).map_err(|reason| {
+            LavaTorrentError::TorrentBuilderFailure(Cow::Owned(format!(
+                "Checkpoint [{}] is malformed: {}.",
+                path.display(),
+                reason
+            )))
+        })
+    }
+
+    // Save `self` to `path`. A temporary file is written first and then
+    // renamed over `path`, so that a crash never leaves a partial checkpoint.
+    pub(crate) fn save(&self, path: &Path) -> Result<(), LavaTorrentError> {
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+
+        self.to_bencode().write_into_file(&tmp_path)?;
+        std::fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+
+    // Check that `self` was made from content listed as `manifest`
+    // and hashed with `piece_length`. `path` is where `self` was loaded from.
+    pub(crate) fn check(
+        &self,
+        path: &Path,
+        manifest: &[ManifestEntry],
+        piece_length: Integer,
+    ) -> Result<(), LavaTorrentError> {
+        let mismatch = |reason: String| {
+            Err(LavaTorrentError::TorrentBuilderFailure(Cow::Owned(
+                format!(
+                    "Checkpoint [{}] does not match the content: {}. Delete it to start over.",
+                    path.display(),
+                    reason
+                ),
+            )))
+        };
+
+        if self.piece_length != piece_length {
+            return mismatch(format!(
+                "it was made with a piece length of {}, not {}",
+                self.piece_length, piece_length
+            ));
+        }
+        if self.manifest.len() != manifest.len() {
+            return mismatch(format!(
+                "it lists {} file(s), but {} were found",
+                self.manifest.len(),
+                manifest.len()
+            ));
+        }
+        for (saved, found) in self.manifest.iter().zip(manifest) {
+            let display = |entry: &ManifestEntry| match entry.path {
+                Some(ref path) => format!("[{}]", path.join("/")),
+                None => "padding".to_owned(),
+            };
+            if saved.path != found.path {
+                return mismatch(format!(
+                    "it lists {} where {} was found",
+                    display(saved),
+                    display(found)
+                ));
+            }
+            if saved.length != found.length {
+                return mismatch(format!(
+                    "{} was {} bytes long, but is {} bytes long now",
+                    display(saved),
+                    saved.length,
+                    found.length
+                ));
+            }
+            if saved.mtime != found.mtime {
+                return mismatch(format!("{} has been modified", display(saved)));
+            }
+        }
+
+        let total_length = manifest
+            .iter()
+            .try_fold(0_u64, |acc, entry| acc.checked_add(entry.length))
+            .unwrap_or(u64::MAX);
+        let n_pieces = util::div_ceil_u64(total_length, util::i64_to_u64(piece_length)?)?;
+        if util::usize_to_u64(self.pieces.len())? > n_pieces {
+            return mismatch(format!(
+                "it has {} piece(s), but the content only has {}",
+                self.pieces.len(),
+                n_pieces
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn to_bencode(&self) -> BencodeElem {
+        let integer = |n: u64| BencodeElem::Integer(Integer::try_from(n).unwrap_or(Integer::MAX));
+        let files = self
+            .manifest
+            .iter()
+            .map(|entry| {
+                let mut dict = HashMap::new();
+                dict.insert("length".to_owned(), integer(entry.length));
+                if let Some(ref path) = entry.path {
+                    dict.insert(
+                        "path".to_owned(),
+                        BencodeElem::List(path.iter().cloned().map(BencodeElem::String).collect()),
+                    );
+                }
+                if let Some((secs, nanos)) = entry.mtime {
+                    dict.insert(
+                        "mtime".to_owned(),
+                        BencodeElem::List(vec![integer(secs), integer(nanos.into())]),
+                    );
+                }
+                BencodeElem::Dictionary(dict)
+            })
+            .collect();
+
+        BencodeElem::Dictionary(HashMap::from([
+            (
+                "checkpoint version".to_owned(),
+                BencodeElem::Integer(CHECKPOINT_VERSION),
+            ),
+            (
+                "piece length".to_owned(),
+                BencodeElem::Integer(self.piece_length),
+            ),
+            ("files".to_owned(), BencodeElem::List(files)),
+            (
+                "pieces".to_owned(),
+                BencodeElem::Bytes(self.pieces.concat()),
+            ),
+        ]))
+    }
+
+    // On error, the reason is returned for `load()` to wrap.
+    fn from_bencode(bytes: &[u8]) -> Result<BuildCheckpoint, Cow<'static, str>> {
+        let mut dict = match BencodeElem::from_bytes(bytes) {
+            Ok(mut elems) if elems.len() == 1 => match elems.remove(0) {
+                BencodeElem::Dictionary(dict) => dict,
+                other => {
+                    return Err(Cow::Owned(format!(
+                        "it is a {}, expected Dictionary",
+                        other.kind()
+                    )))
+                }
+            },
+            Ok(_) => return Err(Cow::Borrowed("it is not a single bencoded value")),
+            Err(e) => return Err(Cow::Owned(e.to_string())),
+        };
+
+        match dict.remove("checkpoint version") {
+            Some(BencodeElem::Integer(version)) if version > CHECKPOINT_VERSION => {
+                return Err(Cow::Owned(format!(
+                    "its version {} is newer than the supported version {}",
+                    version, CHECKPOINT_VERSION
+                )));
+            }
+            Some(BencodeElem::Integer(_)) => (),
+            _ => return Err(Cow::Borrowed(r#""checkpoint version" is missing"#)),
+        }
+        let piece_length = match dict.remove("piece length") {
+            Some(BencodeElem::Integer(piece_length)) if piece_length > 0 => piece_length,
+            _ => return Err(Cow::Borrowed(r#""piece length" is missing or invalid"#)),
+        };
+        let manifest = match dict.remove("files") {
+            Some(BencodeElem::List(files)) => files
+                .into_iter()
+                .map(Self::manifest_entry_from_bencode)
+                .collect::<Option<Vec<_>>>()
+                .ok_or(Cow::Borrowed(r#""files" is invalid"#))?,
+            _ => return Err(Cow::Borrowed(r#""files" is missing"#)),
+        };
+        let pieces = match dict.remove("pieces") {
+            Some(BencodeElem::Bytes(bytes)) if bytes.len() % PIECE_STRING_LENGTH == 0 => bytes,
+            // every piece is valid UTF-8 in the unlikely case
+            Some(BencodeElem::String(string)) if string.len() % PIECE_STRING_LENGTH == 0 => {
+                string.into_bytes()
+            }
+            _ => return Err(Cow::Borrowed(r#""pieces" is missing or invalid"#)),
+        };
+
+        Ok(BuildCheckpoint {
+            piece_length,
+            manifest,
+            // Unwrap is fine here since each chunk is exactly 1 piece long.
+            pieces: pieces
+                .chunks_exact(PIECE_STRING_LENGTH)
+                .map(|piece| piece.try_into().unwrap())
+                .collect(),
+        })
+    }
+
+    fn manifest_entry_from_bencode(elem: BencodeElem) -> Option<ManifestEntry> {
+        let mut dict = match elem {
+            BencodeElem::Dictionary(dict) => dict,
+            _ => return None,
+        };
+        let length = match dict.remove("length") {
+            Some(BencodeElem::Integer(length)) => u64::try_from(length).ok()?,
+            _ => return None,
+        };
+        let path = match dict.remove("path") {
+            Some(BencodeElem::List(components)) => Some(
+                components
+                    .into_iter()
+                    .map(|component| match component {
+                        BencodeElem::String(component) => Some(component),
+                        _ => None,
+                    })
+                    .collect::<Option<Vec<_>>>()?,
+            ),
+            Some(_) => return None,
+            None => None,
+        };
+        let mtime = match dict.remove("mtime") {
+            Some(BencodeElem::List(mtime)) => match mtime.as_slice() {
+                [BencodeElem::Integer(secs), BencodeElem::Integer(nanos)] => {
+                    Some((u64::try_from(*secs).ok()?, u32::try_from(*nanos).ok()?))
+                }
+                _ => return None,
+            },
+            Some(_) => return None,
+            None => None,
+        };
+
+        Some(ManifestEntry {
+            path,
+            length,
+            mtime,
+        })
+    }
+}
+
+#[cfg(test)]
+mod checkpoint_tests {
+    use super::*;
+
+    fn checkpoint() -> BuildCheckpoint {
+        BuildCheckpoint {
+            piece_length: 16,
+            manifest: vec![
+                ManifestEntry {
+                    path: Some(vec!["dir".to_owned(), "a".to_owned()]),
+                    length: 20,
+                    mtime: Some((1_700_000_000, 123)),
+                },
+                ManifestEntry {
+                    path: None,
+                    length: 12,
+                    mtime: None,
+                },
+                ManifestEntry {
+                    path: Some(vec!["b".to_owned()]),
+                    length: 5,
+                    mtime: None,
+                },
+            ],
+            pieces: vec![[1; PIECE_STRING_LENGTH], [2; PIECE_STRING_LENGTH]],
+        }
+    }
+
+    fn check_err(saved: &BuildCheckpoint, found: &BuildCheckpoint, expected: &str) {
+        match saved.check(Path::new("x.ckpt"), &found.manifest, found.piece_length) {
+            Err(LavaTorrentError::TorrentBuilderFailure(m)) => assert_eq!(
+                m,
+                format!(
+                    "Checkpoint [x.ckpt] does not match the content: {}. \
+                     Delete it to start over.",
+                    expected
+                )
+            ),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn bencode_round_trip() {
+        let checkpoint = checkpoint();
+        let bytes = checkpoint.to_bencode().encode();
+        assert_eq!(BuildCheckpoint::from_bencode(&bytes).unwrap(), checkpoint);
+
+        let checkpoint = BuildCheckpoint {
+            manifest: Vec::new(),
+            pieces: Vec::new(),
+            ..checkpoint
+        };
+        let bytes = checkpoint.to_bencode().encode();
+        assert_eq!(BuildCheckpoint::from_bencode(&bytes).unwrap(), checkpoint);
+    }
+
+    #[test]
+    fn from_bencode_invalid() {
+        let mut newer = checkpoint().to_bencode();
+        newer
+            .insert(
+                "checkpoint version".to_owned(),
+                BencodeElem::Integer(CHECKPOINT_VERSION + 1),
+            )
+            .unwrap();
+        let mut truncated = checkpoint().to_bencode();
+        truncated
+            .insert("pieces".to_owned(), BencodeElem::Bytes(vec![0; 21]))
+            .unwrap();
+        let mut negative = checkpoint().to_bencode();
+        negative
+            .insert(
+                "files".to_owned(),
+                BencodeElem::List(vec![BencodeElem::Dictionary(HashMap::from([(
+                    "length".to_owned(),
+                    BencodeElem::Integer(-1),
+                )]))]),
+            )
+            .unwrap();
+
+        for (bytes, expected) in [
+            (b"i1e".to_vec(), "it is a Integer, expected Dictionary"),
+            (b"dede".to_vec(), "it is not a single bencoded value"),
+            (
+                newer.encode(),
+                "its version 2 is newer than the supported version 1",
+            ),
+            (truncated.encode(), r#""pieces" is missing or invalid"#),
+            (negative.encode(), r#""files" is invalid"#),
+        ] {
+            assert_eq!(BuildCheckpoint::from_bencode(&bytes).unwrap_err(), expected);
+        }
+    }
+
+    #[test]
+    fn check_ok() {
+        let checkpoint = checkpoint();
+        checkpoint
+            .check(Path::new("x.ckpt"), &checkpoint.manifest, 16)
+            .unwrap();
+    }
+
+    #[test]
+    fn check_mismatch() {
+        let saved = checkpoint();
+
+        let mut found = saved.clone();
+        found.piece_length = 32;
+        check_err(
+            &saved,
+            &found,
+            "it was made with a piece length of 16, not 32",
+        );
+
+        let mut found = saved.clone();
+        found.manifest.pop();
+        check_err(&saved, &found, "it lists 3 file(s), but 2 were found");
+
+        let mut found = saved.clone();
+        found.manifest[2].path = Some(vec!["c".to_owned()]);
+        check_err(&saved, &found, "it lists [b] where [c] was found");
+
+        let mut found = saved.clone();
+        found.manifest[0].length = 21;
+        check_err(
+            &saved,
+            &found,
+            "[dir/a] was 20 bytes long, but is 21 bytes long now",
+        );
+
+        let mut found = saved.clone();
+        found.manifest[0].mtime = Some((1_700_000_000, 124));
+        check_err(&saved, &found, "[dir/a] has been modified");
+
+        // 37 bytes are 3 pieces
+        let mut too_many = saved.clone();
+        too_many.pieces = vec![[0; PIECE_STRING_LENGTH]; 4];
+        check_err(
+            &too_many,
+            &saved,
+            "it has 4 piece(s), but the content only has 3",
+        );
+    }
+}
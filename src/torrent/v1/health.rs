@@ -0,0 +1,1156 @@
+use super::*;
+
+/// Smallest piece length considered conventional (16 KiB).
+const MIN_PIECE_LENGTH: Integer = 16 * 1024;
+/// Largest piece length considered conventional (16 MiB).
+const MAX_PIECE_LENGTH: Integer = 16 * 1024 * 1024;
+/// Length of the SHA1 hash of a piece.
+const PIECE_HASH_LENGTH: u64 = 20;
+/// Max # of pieces accepted by libtorrent by default.
+const MAX_PIECE_COUNT: usize = 0x20_0000;
+
+impl Torrent {
+    /// Check `self` for common problems, e.g. before adding it to a client.
+    ///
+    /// This is purely informational: `self` is not modified. See
+    /// [`HealthIssue`] for the issues checked and [`HealthIssue::severity()`]
+    /// for how serious each of them is.
+    pub fn health_check(&self) -> HealthReport {
+        let mut issues = Vec::new();
+
+        let is_private = self.is_private();
+        if self.is_trackerless() {
+            if is_private {
+                issues.push(HealthIssue::PrivateWithoutTracker);
+            } else if self.nodes().is_empty() {
+                issues.push(HealthIssue::NoTrackersOrNodes);
+            }
+        }
+        match self.privacy_diagnosis() {
+            PrivacyStatus::MisplacedPrivateFlag => issues.push(HealthIssue::MisplacedPrivateFlag),
+            PrivacyStatus::ConflictingFlags => issues.push(HealthIssue::ConflictingPrivateFlags),
+            PrivacyStatus::Private | PrivacyStatus::Public => (),
+        }
+
+        if !Self::is_safe_component(&self.name) {
+            issues.push(HealthIssue::SuspiciousName {
+                name: self.name.clone(),
+            });
+        } else {
+            let spoofing = self.name_spoofing_issues();
+            if !spoofing.is_empty() {
+                issues.push(HealthIssue::SpoofableName { issues: spoofing });
+            }
+        }
+        if let Some(ref files) = self.files {
+            for (index, file) in files.iter().enumerate() {
+                if !Self::is_safe_path(&file.path) {
+                    issues.push(HealthIssue::UnsafeFilePath {
+                        index,
+                        path: file.path.clone(),
+                    });
+                } else {
+                    let spoofing = file.path_spoofing_issues();
+                    if !spoofing.is_empty() {
+                        issues.push(HealthIssue::SpoofableFilePath {
+                            index,
+                            issues: spoofing,
+                        });
+                    }
+                }
+            }
+        }
+
+        if (self.piece_length < MIN_PIECE_LENGTH)
+            || ((self.piece_length & (self.piece_length - 1)) != 0)
+        {
+            issues.push(HealthIssue::UnconventionalPieceLength {
+                piece_length: self.piece_length,
+            });
+        }
+        if let Some(PieceLengthAdvisory::TooLargeForMemoryBudget {
+            piece_length,
+            per_piece_bytes,
+        }) = self.piece_length_advisory()
+        {
+            issues.push(HealthIssue::OversizedPieceLength {
+                piece_length,
+                per_piece_bytes,
+            });
+        }
+        if self.pieces.len() > MAX_PIECE_COUNT {
+            issues.push(HealthIssue::ExcessivePieceCount {
+                count: self.pieces.len(),
+                max: MAX_PIECE_COUNT,
+            });
+        }
+        if self.length == 0 {
+            issues.push(HealthIssue::ZeroLengthTorrent);
+        } else if let Err(PieceCountMismatch {
+            expected, actual, ..
+        }) = self.piece_count_consistency()
+        {
+            issues.push(HealthIssue::PieceCountMismatch { expected, actual });
+        }
+
+        HealthReport { issues }
+    }
+
+    /// Check if `piece_length` is unusual enough to cause trouble,
+    /// e.g. huge metadata or huge memory usage per piece.
+    ///
+    /// `None` is returned for conventional piece lengths, i.e. powers
+    /// of 2 from 16 KiB to 16 MiB. Otherwise, the first of these applies:
+    /// [`TooSmallForSwarmEfficiency`], [`TooLargeForMemoryBudget`],
+    /// and [`NotPowerOfTwo`]. A torrent smaller than a single piece
+    /// is only as large in memory as its `length`, so it is not
+    /// considered too large.
+    ///
+    /// This is purely advisory and never fails, even if `self` is
+    /// inconsistent (e.g. `piece_length` <= 0).
+    ///
+    /// [`TooSmallForSwarmEfficiency`]: enum.PieceLengthAdvisory.html#variant.TooSmallForSwarmEfficiency
+    /// [`TooLargeForMemoryBudget`]: enum.PieceLengthAdvisory.html#variant.TooLargeForMemoryBudget
+    /// [`NotPowerOfTwo`]: enum.PieceLengthAdvisory.html#variant.NotPowerOfTwo
+    pub fn piece_length_advisory(&self) -> Option<PieceLengthAdvisory> {
+        let piece_length = self.piece_length;
+        let per_piece_bytes = piece_length.min(self.length.max(0));
+
+        if piece_length < MIN_PIECE_LENGTH {
+            let num_pieces = self.pieces.len();
+            Some(PieceLengthAdvisory::TooSmallForSwarmEfficiency {
+                piece_length,
+                num_pieces,
+                metadata_size_estimate: (num_pieces as u64).saturating_mul(PIECE_HASH_LENGTH),
+            })
+        } else if per_piece_bytes > MAX_PIECE_LENGTH {
+            Some(PieceLengthAdvisory::TooLargeForMemoryBudget {
+                piece_length,
+                per_piece_bytes,
+            })
+        } else if (piece_length & (piece_length - 1)) != 0 {
+            // `piece_length` can still be huge if `length` is small, in
+            // which case the next power of 2 may not fit in an `Integer`
+            let lower: Integer = 1 << piece_length.ilog2();
+            let nearest = match lower.checked_mul(2) {
+                Some(upper) if (upper - piece_length) <= (piece_length - lower) => upper,
+                _ => lower,
+            };
+            Some(PieceLengthAdvisory::NotPowerOfTwo {
+                piece_length,
+                nearest,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Look for Unicode tricks in `name` that could make it display as
+    /// something else, e.g. to disguise an executable as an image.
+    ///
+    /// Such names are valid and are thus accepted when reading a torrent.
+    /// Use [`sanitize_display_name()`] before showing them in a UI.
+    /// See [`SpoofingIssue`] for what is detected.
+    ///
+    /// [`sanitize_display_name()`]: fn.sanitize_display_name.html
+    pub fn name_spoofing_issues(&self) -> Vec<SpoofingIssue> {
+        spoofing_issues(0, &self.name)
+    }
+
+    /// Check that `self` is internally consistent.
+    ///
+    /// Since all fields of `Torrent` are public, it is possible to
+    /// end up with a torrent that can still be written but is rejected
+    /// by other clients (e.g. after changing `piece_length` or truncating
+    /// `pieces`). Call this after mutating `self` manually and before
+    /// writing it out.
+    ///
+    /// Everything checked when reading a torrent is checked again,
+    /// along with:
+    /// - `pieces` has exactly as many entries as `length` requires.
+    /// - The lengths of `files` (if any) add up to `length`.
+    /// - `name` and every component of every file path are safe to use
+    ///   as a file name, i.e. not empty, `"."` or `".."`, without a root
+    ///   or drive letter (e.g. `"/etc"` or `"C:"`), and without path
+    ///   separators or control chars. No file path is empty.
+    ///
+    /// All problems found are returned, not just the first one.
+    pub fn revalidate(&self) -> Result<(), Vec<LavaTorrentError>> {
+        let mut errors = Vec::new();
+
+        if let Err(e) = self.validate_lengths() {
+            errors.push(e);
+        }
+        if self.piece_length <= 0 {
+            errors.push(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
+                r#""piece length" <= 0."#,
+            )));
+        } else if let Err(mismatch) = self.piece_count_consistency() {
+            // too few pieces are already reported by `validate_lengths()`
+            if mismatch.excess > 0 {
+                errors.push(LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
+                    "Torrent has {} pieces, {} expected for length {}: {} bytes in excess.",
+                    mismatch.actual, mismatch.expected, self.length, mismatch.excess,
+                ))));
+            }
+        }
+
+        if !Self::is_safe_component(&self.name) {
+            errors.push(LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
+                r#""name" is unsafe: {:?}."#,
+                self.name
+            ))));
+        }
+        if let Some(ref files) = self.files {
+            if files.is_empty() {
+                errors.push(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
+                    r#""files" is empty."#,
+                )));
+            }
+
+            let mut total_length: Option<Integer> = Some(0);
+            for (index, file) in files.iter().enumerate() {
+                if file.length < 0 {
+                    errors.push(LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
+                        "Length of file {} < 0.",
+                        index
+                    ))));
+                }
+                if !Self::is_safe_path(&file.path) {
+                    errors.push(LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
+                        "Path of file {} is unsafe: {:?}.",
+                        index, file.path
+                    ))));
+                }
+                total_length = total_length.and_then(|total| total.checked_add(file.length));
+            }
+
+            match total_length {
+                Some(total) if total != self.length => {
+                    errors.push(LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
+                        "Sum of file lengths {} != torrent's length {}.",
+                        total, self.length,
+                    ))))
+                }
+                Some(_) => (),
+                None => errors.push(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
+                    "Sum of file lengths overflowed in i64.",
+                ))),
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Check that `pieces` has exactly as many entries as `length`
+    /// and `piece_length` require.
+    ///
+    /// Too few pieces leave the end of the content unverifiable, e.g. when
+    /// `pieces` was truncated to hide appended files. Too many pieces
+    /// can smuggle arbitrary data in the metadata itself.
+    ///
+    /// `Ok(())` is returned if `length` is negative or `piece_length` is not
+    /// positive, as there is no expected # of pieces then. Both are
+    /// reported by [`revalidate()`](#method.revalidate).
+    pub fn piece_count_consistency(&self) -> Result<(), PieceCountMismatch> {
+        let expected = match Self::expected_piece_count(self.length, self.piece_length) {
+            Some(expected) if expected != self.pieces.len() => expected,
+            _ => return Ok(()),
+        };
+        // both are non-negative, as checked by `expected_piece_count()`
+        let length = self.length as u64;
+        let piece_length = self.piece_length as u64;
+        let covered = piece_length.saturating_mul(self.pieces.len() as u64);
+
+        Err(PieceCountMismatch {
+            expected,
+            actual: self.pieces.len(),
+            shortfall: length.saturating_sub(covered),
+            excess: covered.saturating_sub(piece_length.saturating_mul(expected as u64)),
+        })
+    }
+
+    // `None` if either argument is invalid (e.g. negative).
+    pub(crate) fn expected_piece_count(length: Integer, piece_length: Integer) -> Option<usize> {
+        let length = u64::try_from(length).ok()?;
+        let piece_length = u64::try_from(piece_length).ok().filter(|&len| len > 0)?;
+        usize::try_from(length.div_ceil(piece_length)).ok()
+    }
+
+    pub(crate) fn is_safe_path(path: &[String]) -> bool {
+        !path.is_empty()
+            && path
+                .iter()
+                .all(|component| Self::is_safe_component(component))
+    }
+
+    // Check that `component` is usable as a file name on common platforms.
+    fn is_safe_component(component: &str) -> bool {
+        Self::component_issue(component).is_none()
+    }
+
+    // Why `component` is not usable as a file name on common platforms, if it isn't.
+    pub(crate) fn component_issue(component: &str) -> Option<&'static str> {
+        if component.is_empty() {
+            Some("its length is 0")
+        } else if (component == ".") || (component == "..") {
+            Some("it is `.` or `..`")
+        } else if component.contains(['/', '\\']) {
+            Some("it contains a path separator")
+        } else if File::path_root_len(component) != 0 {
+            Some("it starts with a drive letter")
+        } else if component.chars().any(char::is_control) {
+            Some("it contains a control character")
+        } else {
+            None
+        }
+    }
+}
+
+impl File {
+    /// Look for Unicode tricks in each component of `path`, like
+    /// [`Torrent::name_spoofing_issues()`] does for `name`.
+    ///
+    /// [`Torrent::name_spoofing_issues()`]: struct.Torrent.html#method.name_spoofing_issues
+    pub fn path_spoofing_issues(&self) -> Vec<SpoofingIssue> {
+        self.path
+            .iter()
+            .enumerate()
+            .flat_map(|(index, component)| spoofing_issues(index, component))
+            .collect()
+    }
+}
+
+impl HealthIssue {
+    /// Return the severity of `self`.
+    ///
+    /// Issues that make the torrent unusable or potentially harmful
+    /// (e.g. a path escaping the download directory) are errors.
+    /// The others (e.g. an unconventional piece length) are warnings.
+    pub fn severity(&self) -> HealthSeverity {
+        match *self {
+            HealthIssue::PrivateWithoutTracker
+            | HealthIssue::SuspiciousName { .. }
+            | HealthIssue::UnsafeFilePath { .. }
+            | HealthIssue::ExcessivePieceCount { .. }
+            | HealthIssue::PieceCountMismatch { .. }
+            | HealthIssue::ZeroLengthTorrent => HealthSeverity::Error,
+            HealthIssue::NoTrackersOrNodes
+            | HealthIssue::MisplacedPrivateFlag
+            | HealthIssue::ConflictingPrivateFlags
+            | HealthIssue::UnconventionalPieceLength { .. }
+            | HealthIssue::OversizedPieceLength { .. }
+            | HealthIssue::SpoofableName { .. }
+            | HealthIssue::SpoofableFilePath { .. } => HealthSeverity::Warning,
+        }
+    }
+}
+
+impl HealthReport {
+    /// Check if no issue of severity `Error` has been found.
+    /// Warnings do not count.
+    pub fn is_ok(&self) -> bool {
+        self.issues
+            .iter()
+            .all(|issue| issue.severity() < HealthSeverity::Error)
+    }
+
+    /// Return the highest severity among the issues found,
+    /// or `None` if there is no issue.
+    pub fn max_severity(&self) -> Option<HealthSeverity> {
+        self.issues.iter().map(HealthIssue::severity).max()
+    }
+}
+
+impl fmt::Display for HealthSeverity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            HealthSeverity::Warning => write!(f, "warning"),
+            HealthSeverity::Error => write!(f, "error"),
+        }
+    }
+}
+
+impl fmt::Display for HealthIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            HealthIssue::NoTrackersOrNodes => write!(f, "no trackers or DHT nodes"),
+            HealthIssue::PrivateWithoutTracker => write!(f, "private torrent without trackers"),
+            HealthIssue::MisplacedPrivateFlag => {
+                write!(f, r#""private" is outside of "info" and has no effect"#)
+            }
+            HealthIssue::ConflictingPrivateFlags => {
+                write!(f, r#""private" inside and outside of "info" disagree"#)
+            }
+            HealthIssue::SuspiciousName { ref name } => write!(f, "suspicious name: {:?}", name),
+            HealthIssue::UnsafeFilePath { index, ref path } => {
+                write!(f, "unsafe path for file [{}]: {:?}", index, path)
+            }
+            HealthIssue::UnconventionalPieceLength { piece_length } => {
+                write!(f, "unconventional piece length: [{}] bytes", piece_length)
+            }
+            HealthIssue::OversizedPieceLength {
+                piece_length,
+                per_piece_bytes,
+            } => write!(
+                f,
+                "oversized piece length: [{}] bytes, [{}] bytes in memory per piece",
+                piece_length, per_piece_bytes
+            ),
+            HealthIssue::ExcessivePieceCount { count, max } => {
+                write!(f, "too many pieces: [{}] > [{}]", count, max)
+            }
+            HealthIssue::PieceCountMismatch { expected, actual } => write!(
+                f,
+                "wrong # of pieces: [{}], expected [{}]",
+                actual, expected
+            ),
+            HealthIssue::ZeroLengthTorrent => write!(f, "torrent contains no data"),
+            HealthIssue::SpoofableName { ref issues } => {
+                write!(f, "spoofable name: {}", issues.iter().join(", "))
+            }
+            HealthIssue::SpoofableFilePath { index, ref issues } => write!(
+                f,
+                "spoofable path for file [{}]: {}",
+                index,
+                issues.iter().join(", ")
+            ),
+        }
+    }
+}
+
+impl fmt::Display for PieceLengthAdvisory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PieceLengthAdvisory::TooSmallForSwarmEfficiency {
+                piece_length,
+                num_pieces,
+                metadata_size_estimate,
+            } => write!(
+                f,
+                "piece length too small: [{}] bytes, [{}] pieces with [{}] bytes of hashes",
+                piece_length, num_pieces, metadata_size_estimate
+            ),
+            PieceLengthAdvisory::TooLargeForMemoryBudget {
+                piece_length,
+                per_piece_bytes,
+            } => write!(
+                f,
+                "piece length too large: [{}] bytes, [{}] bytes in memory per piece",
+                piece_length, per_piece_bytes
+            ),
+            PieceLengthAdvisory::NotPowerOfTwo {
+                piece_length,
+                nearest,
+            } => write!(
+                f,
+                "piece length not a power of 2: [{}] bytes, nearest is [{}] bytes",
+                piece_length, nearest
+            ),
+        }
+    }
+}
+
+impl fmt::Display for SpoofingIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SpoofingIssue::BidiControl {
+                component,
+                character,
+            } => write!(
+                f,
+                "bidi control U+{:04X} in component [{}]",
+                u32::from(character),
+                component
+            ),
+            SpoofingIssue::ControlCharacter {
+                component,
+                character,
+            } => write!(
+                f,
+                "control character U+{:04X} in component [{}]",
+                u32::from(character),
+                component
+            ),
+            SpoofingIssue::ZeroWidthCharacter {
+                component,
+                character,
+            } => write!(
+                f,
+                "invisible character U+{:04X} in component [{}]",
+                u32::from(character),
+                component
+            ),
+            SpoofingIssue::MixedScripts {
+                component,
+                ref word,
+            } => write!(
+                f,
+                "mixed scripts in word {:?} in component [{}]",
+                word, component
+            ),
+        }
+    }
+}
+
+impl fmt::Display for PieceCountMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}] pieces, expected [{}]", self.actual, self.expected)?;
+        if self.shortfall > 0 {
+            write!(f, ", [{}] bytes not covered", self.shortfall)
+        } else {
+            write!(f, ", [{}] bytes in excess", self.excess)
+        }
+    }
+}
+
+impl fmt::Display for HealthReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for issue in &self.issues {
+            writeln!(f, "[{}] {}", issue.severity(), issue)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod health_tests {
+    use super::*;
+    use crate::torrent::v1::torrent_tests::{files, healthy};
+    use std::iter::FromIterator;
+
+    #[test]
+    fn health_check_ok() {
+        let report = healthy().health_check();
+        assert_eq!(report, HealthReport::default());
+        assert!(report.is_ok());
+        assert_eq!(report.max_severity(), None);
+    }
+
+    #[test]
+    fn health_check_no_trackers_or_nodes() {
+        let mut torrent = healthy();
+        torrent.announce = None;
+        torrent.announce_list = Some(vec![vec!["".to_owned()]]);
+
+        let report = torrent.health_check();
+        assert_eq!(report.issues, vec![HealthIssue::NoTrackersOrNodes]);
+        assert!(report.is_ok());
+        assert_eq!(report.max_severity(), Some(HealthSeverity::Warning));
+    }
+
+    #[test]
+    fn health_check_nodes_only() {
+        let mut torrent = healthy();
+        torrent.announce = None;
+        torrent.extra_fields = Some(HashMap::from_iter(vec![(
+            "nodes".to_owned(),
+            bencode_elem!([["router.example.com", 6881]]),
+        )]));
+
+        assert_eq!(torrent.health_check(), HealthReport::default());
+    }
+
+    #[test]
+    fn health_check_private_without_tracker() {
+        let mut torrent = healthy();
+        torrent.announce = None;
+        torrent.private = Some(true);
+        // nodes are of no use to a private torrent
+        torrent.extra_fields = Some(HashMap::from_iter(vec![(
+            "nodes".to_owned(),
+            bencode_elem!([["router.example.com", 6881]]),
+        )]));
+
+        let report = torrent.health_check();
+        assert_eq!(report.issues, vec![HealthIssue::PrivateWithoutTracker]);
+        assert!(!report.is_ok());
+    }
+
+    #[test]
+    fn health_check_misplaced_private_flag() {
+        let mut torrent = healthy();
+        torrent.extra_fields = Some(HashMap::from_iter(vec![(
+            "private".to_owned(),
+            bencode_elem!(1),
+        )]));
+
+        let report = torrent.health_check();
+        assert_eq!(report.issues, vec![HealthIssue::MisplacedPrivateFlag]);
+        assert!(report.is_ok());
+    }
+
+    #[test]
+    fn health_check_conflicting_private_flags() {
+        let mut torrent = healthy();
+        torrent.private = Some(true);
+        torrent.extra_fields = Some(HashMap::from_iter(vec![(
+            "private".to_owned(),
+            bencode_elem!(0),
+        )]));
+
+        let report = torrent.health_check();
+        assert_eq!(report.issues, vec![HealthIssue::ConflictingPrivateFlags]);
+        assert!(report.is_ok());
+    }
+
+    #[test]
+    fn health_check_suspicious_name() {
+        for name in ["", ".", "..", "a/b", "a\\b", "a\nb"] {
+            let mut torrent = healthy();
+            torrent.name = name.to_owned();
+
+            let report = torrent.health_check();
+            assert_eq!(
+                report.issues,
+                vec![HealthIssue::SuspiciousName {
+                    name: name.to_owned()
+                }],
+                "{:?}",
+                name
+            );
+            assert!(!report.is_ok());
+        }
+    }
+
+    #[test]
+    fn health_check_unsafe_file_path() {
+        let mut torrent = healthy();
+        torrent.files = files(&[
+            &["ok"],
+            &["..", "escape"],
+            &["", "etc", "passwd"],
+            &["dir\\file"],
+            &["dir/file"],
+            &[],
+            &["C:", "Windows"],
+            &["dir", "ok"],
+        ]);
+
+        let report = torrent.health_check();
+        assert_eq!(
+            report
+                .issues
+                .iter()
+                .map(|issue| match issue {
+                    HealthIssue::UnsafeFilePath { index, .. } => *index,
+                    _ => panic!(),
+                })
+                .collect::<Vec<usize>>(),
+            vec![1, 2, 3, 4, 5, 6]
+        );
+        assert_eq!(
+            report.issues[0],
+            HealthIssue::UnsafeFilePath {
+                index: 1,
+                path: vec!["..".to_owned(), "escape".to_owned()],
+            }
+        );
+        assert!(!report.is_ok());
+    }
+
+    #[test]
+    fn health_check_unconventional_piece_length() {
+        for piece_length in [8192, 20000] {
+            let mut torrent = healthy();
+            torrent.piece_length = piece_length;
+            torrent.length = piece_length * 2;
+
+            let report = torrent.health_check();
+            assert_eq!(
+                report.issues,
+                vec![HealthIssue::UnconventionalPieceLength { piece_length }]
+            );
+            assert!(report.is_ok());
+        }
+    }
+
+    #[test]
+    fn health_check_oversized_piece_length() {
+        let mut torrent = healthy();
+        torrent.piece_length = 32 * 1024 * 1024;
+        torrent.length = torrent.piece_length * 2;
+
+        let report = torrent.health_check();
+        assert_eq!(
+            report.issues,
+            vec![HealthIssue::OversizedPieceLength {
+                piece_length: 32 * 1024 * 1024,
+                per_piece_bytes: 32 * 1024 * 1024,
+            }]
+        );
+        assert!(report.is_ok());
+        assert_eq!(
+            report.issues[0].to_string(),
+            "oversized piece length: [33554432] bytes, [33554432] bytes in memory per piece"
+        );
+    }
+
+    #[test]
+    fn piece_length_advisory_ok() {
+        const KI: Integer = 1024;
+        const MI: Integer = 1024 * KI;
+        const GI: Integer = 1024 * MI;
+
+        for (piece_length, length, expected) in [
+            (
+                4 * KI,
+                GI,
+                Some(PieceLengthAdvisory::TooSmallForSwarmEfficiency {
+                    piece_length: 4 * KI,
+                    num_pieces: 262_144,
+                    metadata_size_estimate: 5_242_880,
+                }),
+            ),
+            (16 * KI, GI, None),
+            (16 * MI, 4 * GI, None),
+            (
+                32 * MI,
+                4 * GI,
+                Some(PieceLengthAdvisory::TooLargeForMemoryBudget {
+                    piece_length: 32 * MI,
+                    per_piece_bytes: 32 * MI,
+                }),
+            ),
+            (
+                256 * MI,
+                4 * GI,
+                Some(PieceLengthAdvisory::TooLargeForMemoryBudget {
+                    piece_length: 256 * MI,
+                    per_piece_bytes: 256 * MI,
+                }),
+            ),
+            // only as large as the torrent itself
+            (
+                256 * MI,
+                100 * MI,
+                Some(PieceLengthAdvisory::TooLargeForMemoryBudget {
+                    piece_length: 256 * MI,
+                    per_piece_bytes: 100 * MI,
+                }),
+            ),
+            (256 * MI, MI, None),
+            (
+                1_000_000,
+                GI,
+                Some(PieceLengthAdvisory::NotPowerOfTwo {
+                    piece_length: 1_000_000,
+                    nearest: MI,
+                }),
+            ),
+            (
+                20_000,
+                GI,
+                Some(PieceLengthAdvisory::NotPowerOfTwo {
+                    piece_length: 20_000,
+                    nearest: 16 * KI,
+                }),
+            ),
+        ] {
+            let mut torrent = healthy();
+            torrent.piece_length = piece_length;
+            torrent.length = length;
+            torrent.pieces =
+                vec![[1; 20]; Torrent::expected_piece_count(length, piece_length).unwrap()];
+
+            assert_eq!(
+                torrent.piece_length_advisory(),
+                expected,
+                "{} {}",
+                piece_length,
+                length
+            );
+        }
+    }
+
+    #[test]
+    fn piece_length_advisory_huge() {
+        // the next power of 2 (2^63) doesn't fit in an `Integer`
+        let mut bytes = b"d8:announce3:url4:infod6:lengthi1e4:name1:a\
+                          12:piece lengthi4611686018427387905e6:pieces20:"
+            .to_vec();
+        bytes.extend_from_slice(&[1; 20]);
+        bytes.extend_from_slice(b"ee");
+        let torrent = Torrent::read_from_bytes(bytes).unwrap();
+
+        assert_eq!(
+            torrent.piece_length_advisory(),
+            Some(PieceLengthAdvisory::NotPowerOfTwo {
+                piece_length: (1 << 62) + 1,
+                nearest: 1 << 62,
+            })
+        );
+        torrent.health_check();
+
+        let mut torrent = healthy();
+        torrent.length = 1;
+        torrent.pieces = vec![[1; 20]];
+        torrent.piece_length = Integer::MAX;
+        assert_eq!(
+            torrent.piece_length_advisory(),
+            Some(PieceLengthAdvisory::NotPowerOfTwo {
+                piece_length: Integer::MAX,
+                nearest: 1 << 62,
+            })
+        );
+    }
+
+    #[test]
+    fn piece_length_advisory_invalid() {
+        let mut torrent = healthy();
+        torrent.piece_length = 0;
+        assert_eq!(
+            torrent.piece_length_advisory(),
+            Some(PieceLengthAdvisory::TooSmallForSwarmEfficiency {
+                piece_length: 0,
+                num_pieces: 2,
+                metadata_size_estimate: 40,
+            })
+        );
+    }
+
+    #[test]
+    fn piece_length_advisory_display() {
+        assert_eq!(
+            PieceLengthAdvisory::TooSmallForSwarmEfficiency {
+                piece_length: 4096,
+                num_pieces: 262_144,
+                metadata_size_estimate: 5_242_880,
+            }
+            .to_string(),
+            "piece length too small: [4096] bytes, [262144] pieces with [5242880] bytes of hashes"
+        );
+        assert_eq!(
+            PieceLengthAdvisory::NotPowerOfTwo {
+                piece_length: 1_000_000,
+                nearest: 1_048_576,
+            }
+            .to_string(),
+            "piece length not a power of 2: [1000000] bytes, nearest is [1048576] bytes"
+        );
+    }
+
+    #[test]
+    fn health_check_excessive_piece_count() {
+        let mut torrent = healthy();
+        torrent.pieces = vec![[1; 20]; MAX_PIECE_COUNT + 1];
+        torrent.length = torrent.piece_length * (MAX_PIECE_COUNT as Integer + 1);
+
+        let report = torrent.health_check();
+        assert_eq!(
+            report.issues,
+            vec![HealthIssue::ExcessivePieceCount {
+                count: MAX_PIECE_COUNT + 1,
+                max: MAX_PIECE_COUNT,
+            }]
+        );
+        assert!(!report.is_ok());
+    }
+
+    #[test]
+    fn health_check_piece_count_mismatch() {
+        let mut torrent = healthy();
+        torrent.length += 1;
+
+        let report = torrent.health_check();
+        assert_eq!(
+            report.issues,
+            vec![HealthIssue::PieceCountMismatch {
+                expected: 3,
+                actual: 2,
+            }]
+        );
+        assert!(!report.is_ok());
+    }
+
+    #[test]
+    fn piece_count_consistency_exact() {
+        let torrent = healthy();
+        assert_eq!(torrent.piece_count_consistency(), Ok(()));
+
+        // the last piece may be partial
+        let mut torrent = healthy();
+        torrent.length -= 1;
+        assert_eq!(torrent.piece_count_consistency(), Ok(()));
+    }
+
+    #[test]
+    fn piece_count_consistency_truncated_by_one() {
+        let mut torrent = healthy();
+        torrent.length -= 1;
+        torrent.pieces.pop();
+
+        let mismatch = torrent.piece_count_consistency().unwrap_err();
+        assert_eq!(
+            mismatch,
+            PieceCountMismatch {
+                expected: 2,
+                actual: 1,
+                shortfall: 16383,
+                excess: 0,
+            }
+        );
+        assert_eq!(
+            mismatch.to_string(),
+            "[1] pieces, expected [2], [16383] bytes not covered"
+        );
+    }
+
+    #[test]
+    fn piece_count_consistency_excess_by_one() {
+        let mut torrent = healthy();
+        torrent.length -= 1;
+        torrent.pieces.push([3; 20]);
+
+        let mismatch = torrent.piece_count_consistency().unwrap_err();
+        assert_eq!(
+            mismatch,
+            PieceCountMismatch {
+                expected: 2,
+                actual: 3,
+                shortfall: 0,
+                excess: 16384,
+            }
+        );
+        assert_eq!(
+            mismatch.to_string(),
+            "[3] pieces, expected [2], [16384] bytes in excess"
+        );
+    }
+
+    #[test]
+    fn piece_count_consistency_invalid_lengths() {
+        let mut torrent = healthy();
+        torrent.piece_length = 0;
+        assert_eq!(torrent.piece_count_consistency(), Ok(()));
+
+        let mut torrent = healthy();
+        torrent.length = -1;
+        assert_eq!(torrent.piece_count_consistency(), Ok(()));
+    }
+
+    #[test]
+    fn health_check_zero_length_torrent() {
+        let mut torrent = healthy();
+        torrent.length = 0;
+        torrent.pieces = vec![];
+
+        let report = torrent.health_check();
+        assert_eq!(report.issues, vec![HealthIssue::ZeroLengthTorrent]);
+        assert!(!report.is_ok());
+    }
+
+    #[test]
+    fn health_check_multiple_issues() {
+        let mut torrent = healthy();
+        torrent.announce = None;
+        torrent.name = "..".to_owned();
+        torrent.piece_length = 1000;
+
+        let report = torrent.health_check();
+        assert_eq!(
+            report.issues,
+            vec![
+                HealthIssue::NoTrackersOrNodes,
+                HealthIssue::SuspiciousName {
+                    name: "..".to_owned()
+                },
+                HealthIssue::UnconventionalPieceLength { piece_length: 1000 },
+                HealthIssue::PieceCountMismatch {
+                    expected: 33,
+                    actual: 2,
+                },
+            ]
+        );
+        assert_eq!(report.max_severity(), Some(HealthSeverity::Error));
+        assert_eq!(
+            report.to_string(),
+            "[warning] no trackers or DHT nodes\n\
+             [error] suspicious name: \"..\"\n\
+             [warning] unconventional piece length: [1000] bytes\n\
+             [error] wrong # of pieces: [2], expected [33]\n"
+        );
+    }
+
+    #[test]
+    fn health_check_does_not_mutate() {
+        let mut torrent = healthy();
+        torrent.extra_fields = Some(HashMap::from_iter(vec![(
+            "private".to_owned(),
+            bencode_elem!(1),
+        )]));
+        let copy = torrent.clone();
+
+        torrent.health_check();
+        assert_eq!(torrent, copy);
+    }
+
+    #[test]
+    fn name_spoofing_issues_attacks() {
+        for (name, expected) in [
+            (
+                "photo\u{202E}gpj.exe",
+                vec![SpoofingIssue::BidiControl {
+                    component: 0,
+                    character: '\u{202E}',
+                }],
+            ),
+            (
+                "\u{2067}a\u{2069}\u{2067}b\u{2069}",
+                vec![
+                    SpoofingIssue::BidiControl {
+                        component: 0,
+                        character: '\u{2067}',
+                    },
+                    SpoofingIssue::BidiControl {
+                        component: 0,
+                        character: '\u{2069}',
+                    },
+                ],
+            ),
+            (
+                "setup\u{200D}.exe",
+                vec![SpoofingIssue::ZeroWidthCharacter {
+                    component: 0,
+                    character: '\u{200D}',
+                }],
+            ),
+            (
+                "\u{FEFF}readme",
+                vec![SpoofingIssue::ZeroWidthCharacter {
+                    component: 0,
+                    character: '\u{FEFF}',
+                }],
+            ),
+            (
+                "a\u{0007}b",
+                vec![SpoofingIssue::ControlCharacter {
+                    component: 0,
+                    character: '\u{0007}',
+                }],
+            ),
+            (
+                "a\u{0085}b",
+                vec![SpoofingIssue::ControlCharacter {
+                    component: 0,
+                    character: '\u{0085}',
+                }],
+            ),
+            (
+                // Cyrillic "а"
+                "p\u{0430}ypal-invoice.pdf",
+                vec![SpoofingIssue::MixedScripts {
+                    component: 0,
+                    word: "p\u{0430}ypal".to_owned(),
+                }],
+            ),
+            (
+                // Greek "ο"
+                "micr\u{03BF}soft",
+                vec![SpoofingIssue::MixedScripts {
+                    component: 0,
+                    word: "micr\u{03BF}soft".to_owned(),
+                }],
+            ),
+        ] {
+            let mut torrent = healthy();
+            torrent.name = name.to_owned();
+            assert_eq!(torrent.name_spoofing_issues(), expected, "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn name_spoofing_issues_legitimate() {
+        for name in [
+            "sample.tar.gz",
+            "東京物語 (1953) [1080p]",
+            "となりのトトロ",
+            "Мастер и Маргарита 1080p",
+            "Война и мир - Лев Толстой.epub",
+            "ألف ليلة وليلة",
+            "Ελληνική Μυθολογία",
+            "Café Ünïcödé Þórr",
+        ] {
+            let mut torrent = healthy();
+            torrent.name = name.to_owned();
+            assert_eq!(torrent.name_spoofing_issues(), vec![], "{:?}", name);
+            assert_eq!(sanitize_display_name(name), name);
+            assert_eq!(torrent.health_check(), HealthReport::default());
+        }
+    }
+
+    #[test]
+    fn path_spoofing_issues_ok() {
+        let file = files(&[&["ok", "x\u{202E}fdp.exe", "d\u{200B}ir", "ok"]])
+            .unwrap()
+            .remove(0);
+        assert_eq!(
+            file.path_spoofing_issues(),
+            vec![
+                SpoofingIssue::BidiControl {
+                    component: 1,
+                    character: '\u{202E}',
+                },
+                SpoofingIssue::ZeroWidthCharacter {
+                    component: 2,
+                    character: '\u{200B}',
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn sanitize_display_name_ok() {
+        assert_eq!(
+            sanitize_display_name("photo\u{202E}gpj.exe"),
+            "photo\\u{202e}gpj.exe"
+        );
+        assert_eq!(
+            sanitize_display_name("a\u{200B}b\u{0007}c\u{2066}"),
+            "a\\u{200b}b\\u{7}c\\u{2066}"
+        );
+        // nothing can be done about mixed scripts
+        assert_eq!(sanitize_display_name("p\u{0430}ypal"), "p\u{0430}ypal");
+    }
+
+    #[test]
+    fn health_check_spoofing() {
+        let mut torrent = healthy();
+        torrent.name = "photo\u{202E}gpj.exe".to_owned();
+        torrent.files = files(&[&["ok"], &["d\u{200B}ir", "ok"], &["a\nb"]]);
+
+        let report = torrent.health_check();
+        assert_eq!(
+            report.issues,
+            vec![
+                HealthIssue::SpoofableName {
+                    issues: vec![SpoofingIssue::BidiControl {
+                        component: 0,
+                        character: '\u{202E}',
+                    }],
+                },
+                HealthIssue::SpoofableFilePath {
+                    index: 1,
+                    issues: vec![SpoofingIssue::ZeroWidthCharacter {
+                        component: 0,
+                        character: '\u{200B}',
+                    }],
+                },
+                // already unsafe, so not reported as spoofable
+                HealthIssue::UnsafeFilePath {
+                    index: 2,
+                    path: vec!["a\nb".to_owned()],
+                },
+            ]
+        );
+        assert_eq!(report.max_severity(), Some(HealthSeverity::Error));
+        assert_eq!(
+            report.issues[0].to_string(),
+            "spoofable name: bidi control U+202E in component [0]"
+        );
+    }
+}
@@ -0,0 +1,249 @@
+use super::*;
+use crate::util;
+
+/// Smallest file length FAT32 can't hold (4 GiB).
+const FAT32_FILE_LENGTH_LIMIT: u64 = 4 * 1024 * 1024 * 1024;
+
+impl Torrent {
+    /// Tell where `length` comes from, e.g. to flag torrents whose declared
+    /// and computed lengths disagree even though they could be read
+    /// (see [`ReadOptions::allow_length_with_files`]).
+    ///
+    /// This is derived from `self` rather than recorded while reading, so it
+    /// survives e.g. a round trip through [`encode()`]: a multi-file torrent
+    /// with an integer `length` in `extra_info_fields` is [`Reconciled`],
+    /// with `computed` being `self.length`.
+    ///
+    /// [`ReadOptions::allow_length_with_files`]: struct.ReadOptions.html#structfield.allow_length_with_files
+    /// [`encode()`]: #method.encode
+    /// [`Reconciled`]: enum.LengthProvenance.html#variant.Reconciled
+    pub fn length_provenance(&self) -> LengthProvenance {
+        let declared = self
+            .extra_info_fields
+            .as_ref()
+            .and_then(|fields| fields.get("length"));
+
+        match (&self.files, declared) {
+            (None, _) => LengthProvenance::FromLengthKey,
+            (Some(_), Some(&BencodeElem::Integer(declared))) => LengthProvenance::Reconciled {
+                declared,
+                computed: self.length,
+            },
+            (Some(_), _) => LengthProvenance::FromFileSum,
+        }
+    }
+
+    /// Check whether the content of `self` would fit in `target_dir`,
+    /// without creating any file.
+    ///
+    /// `available_bytes` is the free space of the filesystem holding
+    /// `target_dir`, which the caller has to look up itself (e.g. with
+    /// `statvfs()` or `GetDiskFreeSpaceExW()`), and `max_path_length` is
+    /// the longest absolute path allowed (e.g. 260 for Windows without
+    /// long path support, or 4096 for Linux). Path lengths are measured in
+    /// bytes of the platform's encoding, which is never less than the # of
+    /// UTF-16 units Windows counts.
+    ///
+    /// Files end up where a client would put them, i.e. at `target_dir/name`
+    /// for a single-file torrent and `target_dir/name/path` for a multi-file
+    /// one. Padding files are skipped, as clients don't write them.
+    ///
+    /// An error is returned if `target_dir` is not absolute, or if
+    /// `name` or a path would escape `target_dir`.
+    pub fn preflight_with_available_bytes(
+        &self,
+        target_dir: &Path,
+        available_bytes: u64,
+        max_path_length: usize,
+    ) -> Result<PreflightReport, LavaTorrentError> {
+        if !target_dir.is_absolute() {
+            return Err(LavaTorrentError::InvalidArgument(Cow::Owned(format!(
+                "Target directory [{}] is not absolute.",
+                target_dir.display()
+            ))));
+        }
+        if !Self::is_safe_path(std::slice::from_ref(&self.name)) {
+            return Err(LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
+                "Name is unsafe: {:?}.",
+                self.name
+            ))));
+        }
+
+        let root = target_dir.join(&self.name);
+        let files = match self.files {
+            Some(ref files) => files
+                .iter()
+                .enumerate()
+                .filter(|(_, file)| !file.is_padding())
+                .map(|(index, file)| {
+                    if Self::is_safe_path(&file.path) {
+                        Ok((
+                            root.join(file.to_path_buf()),
+                            util::i64_to_u64(file.length)?,
+                        ))
+                    } else {
+                        Err(LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
+                            "Path of file {} is unsafe: {:?}.",
+                            index, file.path
+                        ))))
+                    }
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            None => vec![(root, util::i64_to_u64(self.length)?)],
+        };
+
+        let mut report = PreflightReport {
+            available_bytes,
+            ..Default::default()
+        };
+        for (path, length) in files {
+            report.required_bytes = report.required_bytes.saturating_add(length);
+            if length >= FAT32_FILE_LENGTH_LIMIT {
+                report.files_exceeding_4gib.push(path.clone());
+            }
+            if path.as_os_str().len() > max_path_length {
+                report.paths_too_long.push(path);
+            }
+        }
+        Ok(report)
+    }
+}
+
+impl PreflightReport {
+    /// Check if the content fits, i.e. if there is enough space and no
+    /// file is too large or has too long a path.
+    pub fn ok(&self) -> bool {
+        (self.required_bytes <= self.available_bytes)
+            && self.files_exceeding_4gib.is_empty()
+            && self.paths_too_long.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod inspect_tests {
+    use super::*;
+    use crate::torrent::v1::torrent_tests::{files, healthy};
+    use std::iter::FromIterator;
+
+    #[cfg(unix)]
+    const TARGET_DIR: &str = "/downloads";
+    #[cfg(windows)]
+    const TARGET_DIR: &str = "C:\\downloads";
+
+    #[test]
+    fn preflight_with_available_bytes_single_file() {
+        let target_dir = Path::new(TARGET_DIR);
+        let report = healthy()
+            .preflight_with_available_bytes(target_dir, 32768, 4096)
+            .unwrap();
+        assert_eq!(
+            report,
+            PreflightReport {
+                required_bytes: 32768,
+                available_bytes: 32768,
+                files_exceeding_4gib: vec![],
+                paths_too_long: vec![],
+            }
+        );
+        assert!(report.ok());
+
+        let report = healthy()
+            .preflight_with_available_bytes(target_dir, 32767, 4096)
+            .unwrap();
+        assert!(!report.ok());
+    }
+
+    #[test]
+    fn preflight_with_available_bytes_large_file() {
+        let mut torrent = healthy();
+        torrent.length = 4 * 1024 * 1024 * 1024;
+        torrent.files = files(&[&["small"], &["large"]]);
+        let files = torrent.files.as_mut().unwrap();
+        files[0].length = 1;
+        files[1].length = 4 * 1024 * 1024 * 1024 - 1;
+
+        // 4 GiB - 1 is the largest file FAT32 can hold
+        let target_dir = Path::new(TARGET_DIR);
+        let report = torrent
+            .preflight_with_available_bytes(target_dir, u64::MAX, 4096)
+            .unwrap();
+        assert!(report.ok());
+
+        let files = torrent.files.as_mut().unwrap();
+        files[0].length -= 1;
+        files[1].length += 1;
+        let report = torrent
+            .preflight_with_available_bytes(target_dir, u64::MAX, 4096)
+            .unwrap();
+        assert_eq!(
+            report.files_exceeding_4gib,
+            vec![target_dir.join("sample").join("large")]
+        );
+        assert!(!report.ok());
+    }
+
+    #[test]
+    fn preflight_with_available_bytes_long_path() {
+        let long = "a".repeat(300);
+        let mut torrent = healthy();
+        torrent.files = files(&[&["short"], &["dir", &long]]);
+
+        let target_dir = Path::new(TARGET_DIR);
+        let report = torrent
+            .preflight_with_available_bytes(target_dir, u64::MAX, 260)
+            .unwrap();
+        assert_eq!(
+            report.paths_too_long,
+            vec![target_dir.join("sample").join("dir").join(&long)]
+        );
+        assert!(!report.ok());
+
+        let report = torrent
+            .preflight_with_available_bytes(target_dir, u64::MAX, 4096)
+            .unwrap();
+        assert!(report.ok());
+    }
+
+    #[test]
+    fn preflight_with_available_bytes_padding() {
+        let mut torrent = healthy();
+        torrent.files = files(&[&["file"], &[".pad", "16384"]]);
+        torrent.files.as_mut().unwrap()[1].extra_fields = Some(HashMap::from_iter(vec![(
+            "attr".to_owned(),
+            bencode_elem!("p"),
+        )]));
+
+        // the padding file is neither counted nor checked
+        let report = torrent
+            .preflight_with_available_bytes(Path::new(TARGET_DIR), 16384, 1)
+            .unwrap();
+        assert_eq!(report.required_bytes, 16384);
+        assert_eq!(report.paths_too_long.len(), 1);
+    }
+
+    #[test]
+    fn preflight_with_available_bytes_invalid() {
+        match healthy().preflight_with_available_bytes(Path::new("downloads"), u64::MAX, 4096) {
+            Err(LavaTorrentError::InvalidArgument(m)) => {
+                assert_eq!(m, "Target directory [downloads] is not absolute.");
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        let mut torrent = healthy();
+        torrent.name = "..".to_owned();
+        assert!(matches!(
+            torrent.preflight_with_available_bytes(Path::new(TARGET_DIR), u64::MAX, 4096),
+            Err(LavaTorrentError::MalformedTorrent(_))
+        ));
+
+        let mut torrent = healthy();
+        torrent.files = files(&[&["..", "escape"], &["file"]]);
+        match torrent.preflight_with_available_bytes(Path::new(TARGET_DIR), u64::MAX, 4096) {
+            Err(LavaTorrentError::MalformedTorrent(m)) => {
+                assert_eq!(m, r#"Path of file 0 is unsafe: ["..", "escape"]."#);
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
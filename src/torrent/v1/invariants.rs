@@ -0,0 +1,323 @@
+//! Invariants of the builder's piece accounting.
+//!
+//! Every build path checks its result against these before returning it,
+//! if `debug_assertions` or the `paranoid` feature is enabled. A violation
+//! is a bug in this crate rather than a problem with the input: it panics
+//! in debug builds, and fails the build with a `TorrentBuilderFailure`
+//! in release builds with `paranoid` enabled.
+use super::*;
+use crate::util;
+
+/// Whether the checks below do anything.
+const CHECKS_ENABLED: bool = cfg!(any(debug_assertions, feature = "paranoid"));
+
+#[cfg(test)]
+thread_local! {
+    // Added to the length pieces are split at, to check
+    // that the invariants catch broken piece accounting.
+    static PIECE_LENGTH_SKEW: std::cell::Cell<u64> = const { std::cell::Cell::new(0) };
+}
+
+impl TorrentBuilder {
+    // The length the read loops and `map_pieces()` split pieces at.
+    // This is always `piece_length`, except in tests that skew it.
+    pub(crate) fn split_length(piece_length: u64) -> u64 {
+        #[cfg(test)]
+        let piece_length = piece_length + PIECE_LENGTH_SKEW.with(std::cell::Cell::get);
+        piece_length
+    }
+
+    // Check the result of a build: `pieces` must cover `length`
+    // exactly, and so must `files` (if any).
+    pub(crate) fn check_accounting(torrent: Torrent) -> Result<Torrent, LavaTorrentError> {
+        if !CHECKS_ENABLED {
+            return Ok(torrent);
+        }
+
+        let expected = Torrent::expected_piece_count(torrent.length, torrent.piece_length);
+        if expected != Some(torrent.pieces.len()) {
+            Self::invariant_violated(format!(
+                "{} pieces for {} bytes with piece length {}, expected {:?}.",
+                torrent.pieces.len(),
+                torrent.length,
+                torrent.piece_length,
+                expected,
+            ))?;
+        }
+        if let Some(ref files) = torrent.files {
+            let sum = files
+                .iter()
+                .try_fold(0 as Integer, |sum, file| sum.checked_add(file.length));
+            if sum != Some(torrent.length) {
+                Self::invariant_violated(format!(
+                    "lengths of {} files sum to {:?}, expected the total length {}.",
+                    files.len(),
+                    sum,
+                    torrent.length,
+                ))?;
+            }
+        }
+        Ok(torrent)
+    }
+
+    // Check a piece map returned by `map_pieces()`: every piece must be
+    // `piece_length` long, except the last one, which holds the remainder.
+    pub(crate) fn check_piece_map(
+        pieces: &IndexedPieceMap,
+        total_length: u64,
+        piece_length: u64,
+    ) -> Result<(), LavaTorrentError> {
+        if !CHECKS_ENABLED {
+            return Ok(());
+        }
+
+        let n_pieces = util::usize_to_u64(pieces.len())?;
+        let expected_n_pieces = Self::piece_count_u64(total_length, piece_length)?;
+        if n_pieces != expected_n_pieces {
+            return Self::invariant_violated(format!(
+                "piece map has {} pieces for {} bytes with piece length {}, expected {}.",
+                n_pieces, total_length, piece_length, expected_n_pieces,
+            ));
+        }
+
+        for (index, chunks) in pieces.iter().enumerate() {
+            let index = util::usize_to_u64(index)?;
+            let len = chunks
+                .iter()
+                .try_fold(0_u64, |sum, &(_, _, len)| sum.checked_add(len));
+            let expected = if index + 1 < n_pieces {
+                piece_length
+            } else {
+                total_length - index * piece_length
+            };
+            if len != Some(expected) {
+                return Self::invariant_violated(format!(
+                    "piece {} of {} has {} chunks of {:?} bytes in total, \
+                     expected {} (total length {}, piece length {}).",
+                    index,
+                    n_pieces,
+                    chunks.len(),
+                    len,
+                    expected,
+                    total_length,
+                    piece_length,
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // Check the largest # of bytes a read loop put into a single piece.
+    pub(crate) fn check_piece_filled(
+        max_filled: u64,
+        piece_length: u64,
+    ) -> Result<(), LavaTorrentError> {
+        if CHECKS_ENABLED && (max_filled > piece_length) {
+            return Self::invariant_violated(format!(
+                "a piece was filled with {} bytes, exceeding the piece length {}.",
+                max_filled, piece_length,
+            ));
+        }
+        Ok(())
+    }
+
+    fn invariant_violated(message: String) -> Result<(), LavaTorrentError> {
+        if cfg!(debug_assertions) {
+            panic!("Piece accounting invariant violated: {}", message);
+        }
+        Err(LavaTorrentError::TorrentBuilderFailure(Cow::Owned(
+            format!("Piece accounting invariant violated: {}", message),
+        )))
+    }
+}
+
+// Violations only panic if the checks are enabled, and release builds
+// with `paranoid` enabled return errors instead, hence the `unwrap()`s.
+#[cfg(all(test, any(debug_assertions, feature = "paranoid")))]
+mod invariants_tests {
+    use super::*;
+    use crate::torrent::v1::torrent_tests::healthy;
+
+    // Run `f` with pieces split at `piece_length + skew`.
+    fn with_skew<F, T>(skew: u64, f: F) -> T
+    where
+        F: FnOnce() -> T,
+    {
+        struct Reset;
+        impl Drop for Reset {
+            fn drop(&mut self) {
+                PIECE_LENGTH_SKEW.with(|s| s.set(0));
+            }
+        }
+
+        PIECE_LENGTH_SKEW.with(|s| s.set(skew));
+        let _reset = Reset;
+        f()
+    }
+
+    fn torrent(length: Integer, n_pieces: usize, files: Option<Vec<File>>) -> Torrent {
+        Torrent {
+            length,
+            files,
+            pieces: vec![[0; 20]; n_pieces],
+            ..healthy()
+        }
+    }
+
+    fn file(length: Integer) -> File {
+        File {
+            length,
+            path: vec!["file".to_owned()],
+            extra_fields: None,
+        }
+    }
+
+    #[test]
+    fn check_accounting_ok() {
+        for (length, n_pieces) in [(0, 0), (1, 1), (16384, 1), (16385, 2), (32768, 2)] {
+            let torrent = torrent(length, n_pieces, None);
+            assert_eq!(
+                TorrentBuilder::check_accounting(torrent.clone()).unwrap(),
+                torrent
+            );
+        }
+
+        let torrent = torrent(16385, 2, Some(vec![file(16384), file(1), file(0)]));
+        assert_eq!(
+            TorrentBuilder::check_accounting(torrent.clone()).unwrap(),
+            torrent
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Piece accounting invariant violated: \
+                    1 pieces for 16385 bytes with piece length 16384, expected Some(2).")]
+    fn check_accounting_piece_count() {
+        TorrentBuilder::check_accounting(torrent(16385, 1, None)).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "Piece accounting invariant violated: \
+                    lengths of 2 files sum to Some(16384), expected the total length 16385.")]
+    fn check_accounting_file_lengths() {
+        TorrentBuilder::check_accounting(torrent(16385, 2, Some(vec![file(16383), file(1)])))
+            .unwrap();
+    }
+
+    #[test]
+    fn check_piece_map_ok() {
+        let entries = [("a", 300), ("b", 0), ("c", 213)];
+        let pieces = TorrentBuilder::map_pieces(&entries, 256).unwrap();
+        TorrentBuilder::check_piece_map(&pieces, 513, 256).unwrap();
+        TorrentBuilder::check_piece_map(&Vec::new(), 0, 256).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "Piece accounting invariant violated: \
+                    piece 2 of 3 has 1 chunks of Some(2) bytes in total, \
+                    expected 1 (total length 513, piece length 256).")]
+    fn check_piece_map_remainder() {
+        let pieces = vec![
+            vec![(0, 0, 256)],
+            vec![(0, 256, 44), (2, 0, 212)],
+            vec![(2, 212, 2)],
+        ];
+        TorrentBuilder::check_piece_map(&pieces, 513, 256).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "Piece accounting invariant violated: \
+                    piece map has 2 pieces for 513 bytes with piece length 256, expected 3.")]
+    fn check_piece_map_piece_count() {
+        let pieces = vec![vec![(0, 0, 256)], vec![(0, 256, 257)]];
+        TorrentBuilder::check_piece_map(&pieces, 513, 256).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "Piece accounting invariant violated: \
+                    a piece was filled with 257 bytes, exceeding the piece length 256.")]
+    fn check_piece_filled_exceeded() {
+        TorrentBuilder::check_piece_filled(256, 256).unwrap();
+        TorrentBuilder::check_piece_filled(257, 256).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "Piece accounting invariant violated: piece map has")]
+    fn skewed_map_pieces() {
+        let entries = [("a", 300), ("b", 213)];
+        with_skew(1, || TorrentBuilder::map_pieces(&entries, 256)).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "Piece accounting invariant violated: a piece was filled with")]
+    fn skewed_read_file() {
+        with_skew(1, || {
+            TorrentBuilder::read_file(
+                "tests/files/tails-amd64-3.6.1.torrent",
+                1024,
+                64,
+                &FilePins::default(),
+            )
+        })
+        .unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "Piece accounting invariant violated: a piece was filled with")]
+    fn skewed_read_dir() {
+        let path = std::fs::canonicalize("tests/files").unwrap();
+        let entries = vec![
+            (path.join("tails-amd64-3.6.1.torrent"), 1000),
+            (PathBuf::new(), 100),
+        ];
+        with_skew(7, || {
+            TorrentBuilder::read_dir(&path, entries, 256, 64, &FilePins::default())
+        })
+        .unwrap();
+    }
+
+    // Build a directory holding a 1025-byte file with piece length 1024
+    // and `strategy`, with pieces split at 1025 bytes.
+    fn skewed_build(name: &str, strategy: HashingStrategy) {
+        let path = std::env::temp_dir().join(format!(
+            "lava_torrent-skewed_build-{}-{}",
+            name,
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&path).unwrap();
+        std::fs::write(path.join("file"), [0; 1025]).unwrap();
+        let result = std::panic::catch_unwind(|| {
+            with_skew(1, || {
+                TorrentBuilder::new(&path, 1024)
+                    .set_hashing_strategy(strategy)
+                    .set_num_threads(2)
+                    .build()
+            })
+        });
+        std::fs::remove_dir_all(&path).unwrap();
+        result
+            .unwrap_or_else(|e| std::panic::resume_unwind(e))
+            .unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "Piece accounting invariant violated: \
+                    a piece was filled with 1025 bytes, exceeding the piece length 1024.")]
+    fn skewed_build_sequential() {
+        skewed_build("sequential", HashingStrategy::Sequential);
+    }
+
+    #[test]
+    #[should_panic(expected = "Piece accounting invariant violated: \
+                    piece map has 1 pieces for 1025 bytes with piece length 1024, expected 2.")]
+    fn skewed_build_parallel() {
+        skewed_build("parallel", HashingStrategy::Parallel);
+    }
+
+    #[test]
+    #[should_panic(expected = "Piece accounting invariant violated: \
+                    a piece was filled with 1025 bytes, exceeding the piece length 1024.")]
+    fn skewed_build_pipelined() {
+        skewed_build("pipelined", HashingStrategy::Pipelined);
+    }
+}
@@ -1,23 +1,37 @@
 use super::*;
-use crate::bencode::BencodeElem;
+use crate::bencode::write;
+use crate::bencode::{BencodeElem, TorrentSpans};
+use crate::hex;
 use crate::util;
+use itertools::Itertools;
 use std::borrow::Cow;
 use std::collections::HashMap;
-use std::path::{Path, PathBuf};
+use std::path::Path;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+/// Max nesting depth searched by `Torrent::extract_all()`.
+const MAX_EXTRACT_DEPTH: usize = 32;
+/// Max size of a decompressed *.torrent* file. Anything larger is rejected
+/// to guard against decompression bombs.
+#[cfg(feature = "gzip")]
+const MAX_DECOMPRESSED_SIZE: u64 = 64 * 1024 * 1024; // 64 MiB
 
 impl File {
-    fn extract_file(elem: BencodeElem) -> Result<File, LavaTorrentError> {
+    fn extract_file(
+        elem: BencodeElem,
+        index: usize,
+        options: &ReadOptions,
+    ) -> Result<File, LavaTorrentError> {
         match elem {
             BencodeElem::Dictionary(mut dict) => Ok(File {
                 length: Self::extract_file_length(&mut dict)?,
-                path: Self::extract_file_path(&mut dict)?,
+                path: Self::extract_file_path(&mut dict, index, options)?,
                 extra_fields: Self::extract_file_extra_fields(dict),
             }),
-            _ => {
-                return Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
-                    r#""files" contains a non-dictionary element."#,
-                )));
-            }
+            other => Err(LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
+                r#""files" contains a {} element, expected Dictionary."#,
+                other.kind()
+            )))),
         }
     }
 
@@ -29,37 +43,47 @@ impl File {
                 if len >= 0 {
                     Ok(len)
                 } else {
-                    return Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
+                    Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
                         r#""length" < 0."#,
-                    )));
+                    )))
                 }
             }
-            Some(_) => {
-                return Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
-                    r#""length" does not map to an integer."#,
-                )));
-            }
-            None => {
-                return Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
-                    r#""length" does not exist."#,
-                )));
-            }
+            Some(other) => Err(LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
+                r#""length" maps to {}, expected Integer."#,
+                other.kind()
+            )))),
+            None => Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
+                r#""length" does not exist."#,
+            ))),
         }
     }
 
+    // `index` is the index of this file in `files`, used for error messages.
     fn extract_file_path(
         dict: &mut HashMap<String, BencodeElem>,
-    ) -> Result<PathBuf, LavaTorrentError> {
+        index: usize,
+        options: &ReadOptions,
+    ) -> Result<Vec<String>, LavaTorrentError> {
         match dict.remove("path") {
             Some(BencodeElem::List(list)) => {
                 if list.is_empty() {
-                    return Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
+                    Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
                         r#""path" maps to a 0-length list."#,
-                    )));
+                    )))
+                } else if list.len() > options.max_path_components {
+                    Err(LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
+                        r#""path" of file {} has {} components, exceeding max_path_components ({})."#,
+                        index,
+                        list.len(),
+                        options.max_path_components,
+                    ))))
                 } else {
-                    let mut path = PathBuf::new();
+                    let mut path = Vec::with_capacity(list.len());
+                    let mut path_bytes: usize = 0;
                     for component in list {
                         if let BencodeElem::String(component) = component {
+                            let component = Self::check_path_component(component, options)?;
+
                             // "Path components exactly matching '.' and '..'
                             // must be sanitized. This sanitizing step must
                             // happen after normalizing overlong UTF-8 encodings."
@@ -68,28 +92,84 @@ impl File {
                                 return Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
                                     r#""path" contains "." or ".."."#,
                                 )));
-                            } else {
-                                path.push(component);
                             }
+
+                            path_bytes = path_bytes.saturating_add(component.len());
+                            if path_bytes > options.max_path_bytes {
+                                return Err(LavaTorrentError::MalformedTorrent(Cow::Owned(
+                                    format!(
+                                        r#""path" of file {} exceeds max_path_bytes ({})."#,
+                                        index, options.max_path_bytes,
+                                    ),
+                                )));
+                            }
+                            path.push(component);
                         } else {
-                            return Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
-                                r#""path" contains a non-string element."#,
-                            )));
+                            return Err(LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
+                                r#""path" contains a {} element, expected String."#,
+                                component.kind()
+                            ))));
                         }
                     }
                     Ok(path)
                 }
             }
-            Some(_) => {
-                return Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
-                    r#""path" does not map to a list."#,
-                )));
+            Some(other) => Err(LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
+                r#""path" maps to {}, expected List."#,
+                other.kind()
+            )))),
+            None => Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
+                r#""path" does not exist."#,
+            ))),
+        }
+    }
+
+    // Reject components that would discard the parent directory when
+    // joined to it: ones with a root (`/etc`, or `\\server\share` on
+    // Windows) or a drive letter (`C:\Windows`). With `sanitize_paths`,
+    // the roots and prefixes are stripped instead, and a component left
+    // empty (e.g. "/") becomes "_". NUL bytes are rejected either way,
+    // as they would truncate the path in C APIs.
+    fn check_path_component(
+        mut component: String,
+        options: &ReadOptions,
+    ) -> Result<String, LavaTorrentError> {
+        if component.contains('\0') {
+            return Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
+                r#""path" contains a NUL byte."#,
+            )));
+        }
+
+        let root_len = Self::path_root_len(&component);
+        if root_len == 0 {
+            Ok(component)
+        } else if options.sanitize_paths {
+            component.drain(..root_len);
+            if component.is_empty() {
+                component.push('_');
             }
-            None => {
-                return Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
-                    r#""path" does not exist."#,
-                )));
+            Ok(component)
+        } else {
+            Err(LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
+                r#""path" contains an absolute component: {:?}."#,
+                component
+            ))))
+        }
+    }
+
+    // Length of the (possibly repeated) roots and
+    // drive letters at the start of `component`.
+    pub(crate) fn path_root_len(component: &str) -> usize {
+        let mut rest = component;
+        loop {
+            let stripped = match rest.as_bytes() {
+                [letter, b':', ..] if letter.is_ascii_alphabetic() => &rest[2..],
+                _ => rest.trim_start_matches(['/', '\\']),
+            };
+            if stripped.len() == rest.len() {
+                return component.len() - rest.len();
             }
+            rest = stripped;
         }
     }
 
@@ -107,51 +187,411 @@ impl Torrent {
     ///
     /// If `bytes` is missing any required field (e.g. `info`), or if any other
     /// error is encountered (e.g. `IOError`), then `Err(error)` will be returned.
+    ///
+    /// If `bytes` is gzip/zlib-compressed, it will be decompressed
+    /// first if the `gzip` feature is enabled. Otherwise `Err(error)`
+    /// will be returned, with a message saying that the input is compressed.
     pub fn read_from_bytes<B>(bytes: B) -> Result<Torrent, LavaTorrentError>
     where
         B: AsRef<[u8]>,
     {
-        Self::from_parsed(BencodeElem::from_bytes(bytes)?)?.validate()
+        Self::read_from_bytes_with(bytes, &ReadOptions::default())
+    }
+
+    /// Same as [`read_from_bytes()`](#method.read_from_bytes), but
+    /// with the limits in `options` instead of the default ones.
+    pub fn read_from_bytes_with<B>(
+        bytes: B,
+        options: &ReadOptions,
+    ) -> Result<Torrent, LavaTorrentError>
+    where
+        B: AsRef<[u8]>,
+    {
+        Ok(Self::read_from_bytes_with_warnings(bytes, options)?.0)
+    }
+
+    /// Same as [`read_from_bytes_with()`](#method.read_from_bytes_with),
+    /// but also return the problems that were worked around instead of
+    /// failing (e.g. trackers dropped because of
+    /// [`ReadOptions::max_trackers_per_torrent`]).
+    ///
+    /// [`ReadOptions::max_trackers_per_torrent`]: struct.ReadOptions.html#structfield.max_trackers_per_torrent
+    pub fn read_from_bytes_with_warnings<B>(
+        bytes: B,
+        options: &ReadOptions,
+    ) -> Result<(Torrent, Vec<ReadWarning>), LavaTorrentError>
+    where
+        B: AsRef<[u8]>,
+    {
+        let bytes = Self::decompress(bytes.as_ref())?;
+        let mut warnings = Vec::new();
+        let torrent = Self::from_parsed(BencodeElem::from_bytes(bytes)?, options, &mut warnings)?
+            .validate()?;
+        Ok((torrent, warnings))
+    }
+
+    /// Same as [`read_from_bytes()`](#method.read_from_bytes), but also
+    /// return the structural features of the torrent as it was read,
+    /// e.g. for classifying the torrents in a corpus.
+    ///
+    /// Only the keys of the top-level and `info` dicts are looked at,
+    /// so this costs little more than `read_from_bytes()`.
+    pub fn read_from_bytes_with_features<B>(
+        bytes: B,
+    ) -> Result<(Torrent, TorrentFeatures), LavaTorrentError>
+    where
+        B: AsRef<[u8]>,
+    {
+        let bytes = Self::decompress(bytes.as_ref())?;
+        let elem = Self::single_top_level(BencodeElem::from_bytes(bytes)?)?;
+        // recorded before extraction moves the keys out
+        let mut features = match elem {
+            BencodeElem::Dictionary(ref dict) => TorrentFeatures::record(dict),
+            _ => TorrentFeatures::default(),
+        };
+        let torrent =
+            Self::from_elem(elem, &ReadOptions::default(), &mut Vec::new())?.validate()?;
+        features.has_padding_files = torrent.files.iter().flatten().any(File::is_padding);
+        Ok((torrent, features))
+    }
+
+    /// Same as [`read_from_bytes()`](#method.read_from_bytes), except that
+    /// only `info` is extracted and has to be valid.
+    ///
+    /// This is useful when only the info hash and the files matter, e.g. for
+    /// indexing metadata. `announce` and `announce_list` are left as `None`,
+    /// and all top-level fields besides `info` (including malformed ones,
+    /// e.g. an `announce` that is not a string) are left as is in
+    /// `extra_fields`. Thus, encoding the returned `Torrent` gives back
+    /// the same top-level fields.
+    pub fn read_info_only<B>(bytes: B) -> Result<Torrent, LavaTorrentError>
+    where
+        B: AsRef<[u8]>,
+    {
+        let bytes = Self::decompress(bytes.as_ref())?;
+        let elem = Self::single_top_level(BencodeElem::from_bytes(bytes)?)?;
+        Self::from_elem_info_only(elem, &ReadOptions::default(), &mut Vec::new())?.validate()
+    }
+
+    /// Same as [`read_from_bytes()`](#method.read_from_bytes), but also
+    /// keep a copy of `bytes` (decompressed, if needed) and record where
+    /// each field is in it.
+    ///
+    /// This is only needed for [`SpannedTorrent::raw_field_bytes()`]
+    /// and [`SpannedTorrent::raw_info_hash()`].
+    pub fn read_from_bytes_spanned<B>(bytes: B) -> Result<SpannedTorrent, LavaTorrentError>
+    where
+        B: AsRef<[u8]>,
+    {
+        Self::read_from_bytes_spanned_with(bytes, &ReadOptions::default())
+    }
+
+    /// Same as [`read_from_bytes_spanned()`](#method.read_from_bytes_spanned),
+    /// but with custom [`ReadOptions`].
+    pub fn read_from_bytes_spanned_with<B>(
+        bytes: B,
+        options: &ReadOptions,
+    ) -> Result<SpannedTorrent, LavaTorrentError>
+    where
+        B: AsRef<[u8]>,
+    {
+        let bytes = Self::decompress(bytes.as_ref())?.into_owned();
+        // parsed once, for both the spans and the torrent
+        let (elem, spans) = TorrentSpans::capture_elem(&bytes)?;
+        let mut warnings = Vec::new();
+        let torrent = Self::from_elem(elem, options, &mut warnings)?.validate()?;
+        Ok(SpannedTorrent {
+            torrent,
+            bytes,
+            spans,
+            warnings,
+        })
+    }
+
+    /// Extract a `Torrent` from an already-parsed `elem`.
+    ///
+    /// `elem` must be a dictionary containing a valid `info` dictionary,
+    /// otherwise `Err(error)` will be returned.
+    pub fn from_bencode(elem: BencodeElem) -> Result<Torrent, LavaTorrentError> {
+        Self::from_bencode_with(elem, &ReadOptions::default())
+    }
+
+    /// Same as [`from_bencode()`](#method.from_bencode), but
+    /// with the limits in `options` instead of the default ones.
+    pub fn from_bencode_with(
+        elem: BencodeElem,
+        options: &ReadOptions,
+    ) -> Result<Torrent, LavaTorrentError> {
+        Self::from_elem(elem, options, &mut Vec::new())?.validate()
+    }
+
+    /// Search `elem` recursively for dictionaries that look like torrents
+    /// and try to extract each of them with [`from_bencode()`](#method.from_bencode).
+    ///
+    /// A dictionary looks like a torrent if its `info` maps to a dictionary
+    /// containing `name`, `piece length`, and `pieces`. Each result is paired
+    /// with the path from `elem` to that dictionary (empty if `elem` itself
+    /// looks like a torrent). Dictionaries are searched in sorted key order,
+    /// so results are in the same order as in the encoded form of `elem`.
+    ///
+    /// Dictionaries that look like torrents are not searched any
+    /// further. Neither are elements nested more than 32 levels deep.
+    pub fn extract_all(elem: &BencodeElem) -> Vec<ExtractedTorrent> {
+        let mut found = Vec::new();
+        Self::extract_all_from(elem, &mut Vec::new(), &mut found);
+        found
+    }
+
+    fn extract_all_from(
+        elem: &BencodeElem,
+        path: &mut Vec<PathSegment>,
+        found: &mut Vec<ExtractedTorrent>,
+    ) {
+        if path.len() > MAX_EXTRACT_DEPTH {
+            return;
+        }
+
+        match elem {
+            BencodeElem::Dictionary(dict) => {
+                if Self::looks_like_torrent(dict) {
+                    found.push((path.clone(), Self::from_bencode(elem.clone())));
+                    return;
+                }
+                for (key, value) in dict.iter().sorted_by_key(|&(key, _)| key) {
+                    path.push(PathSegment::Key(key.as_bytes().to_vec()));
+                    Self::extract_all_from(value, path, found);
+                    path.pop();
+                }
+            }
+            BencodeElem::RawDictionary(dict) => {
+                for (key, value) in dict.iter().sorted_by_key(|&(key, _)| key) {
+                    path.push(PathSegment::Key(key.clone()));
+                    Self::extract_all_from(value, path, found);
+                    path.pop();
+                }
+            }
+            BencodeElem::List(list) => {
+                for (index, value) in list.iter().enumerate() {
+                    path.push(PathSegment::Index(index));
+                    Self::extract_all_from(value, path, found);
+                    path.pop();
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn looks_like_torrent(dict: &Dictionary) -> bool {
+        match dict.get("info") {
+            Some(BencodeElem::Dictionary(info)) => ["name", "piece length", "pieces"]
+                .iter()
+                .all(|&key| info.contains_key(key)),
+            _ => false,
+        }
     }
 
     /// Parse the content of the file at `path` and return the extracted `Torrent`.
     ///
     /// If the file at `path` is missing any required field (e.g. `info`), or if any other
     /// error is encountered (e.g. `IOError`), then `Err(error)` will be returned.
+    /// Errors name `path` in their message.
+    ///
+    /// Compressed files are handled in the same way as in
+    /// [`read_from_bytes()`](#method.read_from_bytes).
     pub fn read_from_file<P>(path: P) -> Result<Torrent, LavaTorrentError>
     where
         P: AsRef<Path>,
     {
-        Self::from_parsed(BencodeElem::from_file(path)?)?.validate()
+        Self::read_from_file_with(path, &ReadOptions::default())
+    }
+
+    /// Same as [`read_from_file()`](#method.read_from_file), but
+    /// with the limits in `options` instead of the default ones.
+    pub fn read_from_file_with<P>(
+        path: P,
+        options: &ReadOptions,
+    ) -> Result<Torrent, LavaTorrentError>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        std::fs::read(path)
+            .map_err(LavaTorrentError::from)
+            .and_then(|bytes| Self::read_from_bytes_with(bytes, options))
+            .map_err(|e| write::with_path_context(e, path))
+    }
+
+    /// Parse `bytes` as a bundle of torrents, i.e. any number of complete
+    /// torrents concatenated together, and return the torrents extracted.
+    ///
+    /// Unlike [`read_from_bytes()`](#method.read_from_bytes), `bytes` may
+    /// contain any number of top-level elements (including 0). Each of
+    /// them is extracted as a torrent on its own, and those that fail are
+    /// handled according to `policy`. If `bytes` is not valid bencode as a
+    /// whole, then `Err(error)` will be returned regardless of `policy`.
+    ///
+    /// Compressed bundles are handled in the same way as in `read_from_bytes()`.
+    pub fn read_all_from_bytes<B>(
+        bytes: B,
+        policy: BundlePolicy,
+    ) -> Result<BundleRead, LavaTorrentError>
+    where
+        B: AsRef<[u8]>,
+    {
+        let bytes = Self::decompress(bytes.as_ref())?;
+        let mut torrents = Vec::new();
+        let mut skipped = Vec::new();
+
+        for (index, elem) in BencodeElem::from_bytes(bytes)?.into_iter().enumerate() {
+            match Self::from_bencode(elem) {
+                Ok(torrent) => torrents.push(torrent),
+                Err(e) => match policy {
+                    BundlePolicy::FailFast => {
+                        return Err(match e {
+                            LavaTorrentError::MalformedTorrent(m) => {
+                                LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
+                                    "Element {} of the bundle: {}",
+                                    index, m
+                                )))
+                            }
+                            e => e,
+                        });
+                    }
+                    BundlePolicy::SkipInvalid => skipped.push((index, e)),
+                },
+            }
+        }
+
+        Ok((torrents, skipped))
+    }
+
+    /// Parse the content of the file at `path` as a bundle of torrents.
+    ///
+    /// See [`read_all_from_bytes()`](#method.read_all_from_bytes) for details.
+    /// Errors name `path` in their message, but those of skipped
+    /// torrents do not.
+    pub fn read_all_from_file<P>(
+        path: P,
+        policy: BundlePolicy,
+    ) -> Result<BundleRead, LavaTorrentError>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        std::fs::read(path)
+            .map_err(LavaTorrentError::from)
+            .and_then(|bytes| Self::read_all_from_bytes(bytes, policy))
+            .map_err(|e| write::with_path_context(e, path))
+    }
+
+    // Decompress `bytes` if it is gzip/zlib-compressed. Uncompressed
+    // `bytes` is returned as-is.
+    //
+    // A zlib stream starts with 0x78 ('x'), which can never start
+    // a valid bencoded torrent, so there's no ambiguity here.
+    fn decompress(bytes: &[u8]) -> Result<Cow<'_, [u8]>, LavaTorrentError> {
+        let is_gzip = bytes.starts_with(&GZIP_MAGIC);
+        let is_zlib = bytes.len() >= 2
+            && bytes[0] == 0x78
+            && ((u16::from(bytes[0]) << 8) | u16::from(bytes[1])) % 31 == 0;
+
+        if !is_gzip && !is_zlib {
+            return Ok(Cow::Borrowed(bytes));
+        }
+
+        #[cfg(feature = "gzip")]
+        {
+            use std::io::Read;
+
+            let reader: Box<dyn Read> = if is_gzip {
+                Box::new(flate2::read::GzDecoder::new(bytes))
+            } else {
+                Box::new(flate2::read::ZlibDecoder::new(bytes))
+            };
+            let mut decompressed = Vec::new();
+            reader
+                .take(MAX_DECOMPRESSED_SIZE + 1)
+                .read_to_end(&mut decompressed)?;
+
+            if util::usize_to_u64(decompressed.len())? > MAX_DECOMPRESSED_SIZE {
+                Err(LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
+                    "Decompressed input exceeds {} bytes.",
+                    MAX_DECOMPRESSED_SIZE,
+                ))))
+            } else {
+                Ok(Cow::Owned(decompressed))
+            }
+        }
+
+        #[cfg(not(feature = "gzip"))]
+        {
+            Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
+                if is_gzip {
+                    "Input is gzip-compressed. Decompress it first, or enable the `gzip` feature."
+                } else {
+                    "Input is zlib-compressed. Decompress it first, or enable the `gzip` feature."
+                },
+            )))
+        }
     }
 
     // @note: Most of validation is done when bdecoding and parsing torrent,
     // so there's not much going on here. More validation could be
     // added in the future if necessary.
     fn validate(self) -> Result<Torrent, LavaTorrentError> {
+        self.validate_lengths()?;
+        Ok(self)
+    }
+
+    // `length` and `piece_length` are byte counts rather than in-memory
+    // sizes, so the math is done in u64 to allow > 4 GiB torrents on
+    // 32-bit targets. Only the # of pieces is bounded by `usize`.
+    pub(crate) fn validate_lengths(&self) -> Result<(), LavaTorrentError> {
         if let Some(total_piece_length) =
-            util::i64_to_usize(self.piece_length)?.checked_mul(self.pieces.len())
+            util::i64_to_u64(self.piece_length)?.checked_mul(util::usize_to_u64(self.pieces.len())?)
         {
-            if total_piece_length < util::i64_to_usize(self.length)? {
-                return Err(LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
-                    "Total piece length {} < torrent's length {}.",
-                    total_piece_length, self.length,
-                ))));
+            if total_piece_length < util::i64_to_u64(self.length)? {
+                Err(LavaTorrentError::MalformedTorrent(Cow::Owned(
+                    match self.piece_count_consistency() {
+                        Err(mismatch) => format!(
+                            "Total piece length {} < torrent's length {}: \
+                             {} pieces, {} expected, {} bytes not covered.",
+                            total_piece_length,
+                            self.length,
+                            mismatch.actual,
+                            mismatch.expected,
+                            mismatch.shortfall,
+                        ),
+                        // `piece_length` <= 0
+                        Ok(()) => format!(
+                            "Total piece length {} < torrent's length {}.",
+                            total_piece_length, self.length,
+                        ),
+                    },
+                )))
             } else if self.length <= 0 {
-                return Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
+                Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
                     r#""length" <= 0."#,
-                )));
+                )))
             } else {
-                Ok(self)
+                Ok(())
             }
         } else {
-            return Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
-                "Torrent's total piece length overflowed in usize.",
-            )));
+            Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
+                "Torrent's total piece length overflowed in u64.",
+            )))
         }
     }
 
-    fn from_parsed(mut parsed: Vec<BencodeElem>) -> Result<Torrent, LavaTorrentError> {
+    fn from_parsed(
+        parsed: Vec<BencodeElem>,
+        options: &ReadOptions,
+        warnings: &mut Vec<ReadWarning>,
+    ) -> Result<Torrent, LavaTorrentError> {
+        Self::from_elem(Self::single_top_level(parsed)?, options, warnings)
+    }
+
+    fn single_top_level(mut parsed: Vec<BencodeElem>) -> Result<BencodeElem, LavaTorrentError> {
         if parsed.len() != 1 {
             return Err(LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
                 "Torrent should contain 1 and only 1 top-level element, {} found.",
@@ -159,132 +599,353 @@ impl Torrent {
             ))));
         }
 
-        if let BencodeElem::Dictionary(mut parsed) = parsed.remove(0) {
-            // 2nd-level items
-            let announce = Self::extract_announce(&mut parsed)?;
-            let announce_list = Self::extract_announce_list(&mut parsed)?;
-            let info = parsed.remove("info");
-            let extra_fields = Self::extract_extra_fields(parsed);
-
-            match info {
-                Some(BencodeElem::Dictionary(mut info)) => {
-                    // 3rd-level items
-                    // handle `files` separately because `extract_length()` needs it
-                    let files = Self::extract_files(&mut info)?;
-
-                    Ok(Torrent {
-                        announce,
-                        announce_list,
-                        length: Self::extract_length(&mut info, &files)?,
-                        files,
-                        name: Self::extract_name(&mut info)?,
-                        piece_length: Self::extract_piece_length(&mut info)?,
-                        pieces: Self::extract_pieces(&mut info)?,
-                        extra_fields,
-                        extra_info_fields: Self::extract_extra_fields(info),
-                    })
+        Ok(parsed.remove(0))
+    }
+
+    fn top_level_dictionary(
+        elem: BencodeElem,
+    ) -> Result<HashMap<String, BencodeElem>, LavaTorrentError> {
+        match elem {
+            BencodeElem::Dictionary(dict) => Ok(dict),
+            _ => Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
+                "Torrent's top-level element is not a dictionary.",
+            ))),
+        }
+    }
+
+    fn from_elem(
+        elem: BencodeElem,
+        options: &ReadOptions,
+        warnings: &mut Vec<ReadWarning>,
+    ) -> Result<Torrent, LavaTorrentError> {
+        let mut parsed = Self::top_level_dictionary(elem)?;
+
+        // 2nd-level items
+        let announce = Self::extract_announce(&mut parsed, options, warnings)?;
+        let mut announce_list = Self::extract_announce_list(&mut parsed, options, warnings)?;
+        if let (Some(list), Some(max)) = (&mut announce_list, options.max_trackers_per_torrent) {
+            let dropped = Self::truncate_announce_list(list, max);
+            if dropped > 0 {
+                warnings.push(ReadWarning::TrackersTruncated { kept: max, dropped });
+                if list.is_empty() {
+                    announce_list = None;
                 }
-                Some(_) => {
-                    return Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
-                        r#""info" is not a dictionary."#,
-                    )));
+            }
+        }
+        let info = parsed.remove("info");
+
+        Ok(Torrent {
+            announce,
+            announce_list,
+            extra_fields: Self::extract_extra_fields(parsed),
+            ..Self::from_info(info, options, warnings)?
+        })
+    }
+
+    // Same as `from_elem()`, except that only `info` is extracted.
+    // Everything else (valid or not) is left as is in `extra_fields`.
+    fn from_elem_info_only(
+        elem: BencodeElem,
+        options: &ReadOptions,
+        warnings: &mut Vec<ReadWarning>,
+    ) -> Result<Torrent, LavaTorrentError> {
+        let mut parsed = Self::top_level_dictionary(elem)?;
+        let info = parsed.remove("info");
+
+        Ok(Torrent {
+            extra_fields: Self::extract_extra_fields(parsed),
+            ..Self::from_info(info, options, warnings)?
+        })
+    }
+
+    // Extract the 3rd-level items, i.e. those in `info`. The 2nd-level
+    // ones (e.g. `announce`) are left empty for the caller to fill in.
+    fn from_info(
+        info: Option<BencodeElem>,
+        options: &ReadOptions,
+        warnings: &mut Vec<ReadWarning>,
+    ) -> Result<Torrent, LavaTorrentError> {
+        match info {
+            Some(BencodeElem::Dictionary(mut info)) => {
+                // handle `files` separately because `extract_length()` needs it
+                let files = Self::extract_files(&mut info, options)?;
+
+                Ok(Torrent {
+                    announce: None,
+                    announce_list: None,
+                    length: Self::extract_length(&mut info, &files, options, warnings)?,
+                    files,
+                    name: Self::extract_name(&mut info, options, warnings)?,
+                    piece_length: Self::extract_piece_length(&mut info)?,
+                    pieces: Self::extract_pieces(&mut info, options, warnings)?,
+                    private: Self::extract_private(&mut info),
+                    extra_fields: None,
+                    extra_info_fields: Self::extract_extra_fields(info),
+                })
+            }
+            Some(other) => Err(LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
+                r#""info" maps to {}, expected Dictionary."#,
+                other.kind()
+            )))),
+            None => Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
+                r#""info" does not exist."#,
+            ))),
+        }
+    }
+
+    // Only `0` and `1` are extracted. Anything else is left
+    // in `dict` (and thus ends up in `extra_info_fields`).
+    fn extract_private(dict: &mut HashMap<String, BencodeElem>) -> Option<bool> {
+        let private = match dict.get("private") {
+            Some(&BencodeElem::Integer(0)) => false,
+            Some(&BencodeElem::Integer(1)) => true,
+            _ => return None,
+        };
+        dict.remove("private");
+        Some(private)
+    }
+
+    pub(crate) fn from_fastresume_bytes(
+        bytes: &[u8],
+    ) -> Result<(Torrent, Dictionary), LavaTorrentError> {
+        let mut parsed = BencodeElem::from_bytes(bytes)?;
+        if parsed.len() != 1 {
+            return Err(LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
+                "Fastresume file should contain 1 and only 1 top-level element, {} found.",
+                parsed.len()
+            ))));
+        }
+
+        let mut resume = match parsed.remove(0) {
+            BencodeElem::Dictionary(dict) => dict,
+            _ => {
+                return Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
+                    "Fastresume file's top-level element is not a dictionary.",
+                )));
+            }
+        };
+
+        // modern resume files embed the `info` dict directly, while
+        // older ones store the entire torrent under `metadata`
+        // (which is only looked at, and removed, if there's no `info`)
+        let mut torrent = match resume.remove("info") {
+            Some(info) => HashMap::from_iter(vec![("info".to_owned(), info)]),
+            None => match resume.remove("metadata") {
+                Some(BencodeElem::Dictionary(metadata)) => metadata,
+                Some(other) => {
+                    return Err(LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
+                        r#""metadata" maps to {}, expected Dictionary."#,
+                        other.kind()
+                    ))));
                 }
                 None => {
                     return Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
-                        r#""info" does not exist."#,
+                        r#"Neither "info" nor "metadata" exists."#,
                     )));
                 }
+            },
+        };
+
+        // trackers in the resume file take precedence
+        let trackers = Self::extract_fastresume_trackers(&resume)?;
+        let urls = trackers.iter().flatten().collect::<Vec<&String>>();
+        if let Some(&first) = urls.first() {
+            torrent.insert("announce".to_owned(), BencodeElem::String(first.clone()));
+            if urls.len() > 1 {
+                torrent.insert(
+                    "announce-list".to_owned(),
+                    BencodeElem::List(
+                        trackers
+                            .iter()
+                            .map(|tier| {
+                                BencodeElem::List(
+                                    tier.iter().cloned().map(BencodeElem::String).collect(),
+                                )
+                            })
+                            .collect(),
+                    ),
+                );
+            } else {
+                torrent.remove("announce-list");
+            }
+        }
+
+        let torrent = Self::from_bencode(BencodeElem::Dictionary(torrent))?;
+
+        let info_hash = match resume.get("info-hash") {
+            Some(BencodeElem::Bytes(bytes)) => Some(bytes.as_slice()),
+            Some(BencodeElem::String(string)) => Some(string.as_bytes()),
+            Some(other) => {
+                return Err(LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
+                    r#""info-hash" maps to {}, expected String or Bytes."#,
+                    other.kind()
+                ))));
+            }
+            None => None,
+        };
+        if let Some(info_hash) = info_hash {
+            if info_hash != torrent.info_hash_bytes().as_slice() {
+                return Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
+                    r#""info-hash" does not match the hash of the embedded "info"."#,
+                )));
             }
+        }
+
+        Ok((torrent, resume))
+    }
+
+    fn extract_fastresume_trackers(
+        resume: &HashMap<String, BencodeElem>,
+    ) -> Result<AnnounceList, LavaTorrentError> {
+        match resume.get("trackers") {
+            Some(BencodeElem::List(tiers)) => tiers
+                .iter()
+                .cloned()
+                // strict, so there are never any warnings
+                .map(|tier| {
+                    Self::extract_announce_list_tier(tier, &ReadOptions::default(), &mut Vec::new())
+                })
+                .collect(),
+            Some(other) => Err(LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
+                r#""trackers" maps to {}, expected List."#,
+                other.kind()
+            )))),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    // `bytes` decoded lossily if `options` allow it, `None` otherwise.
+    fn decode_lossy(
+        field: &'static str,
+        bytes: &[u8],
+        options: &ReadOptions,
+        warnings: &mut Vec<ReadWarning>,
+    ) -> Option<String> {
+        if options.lossy_utf8 {
+            warnings.push(ReadWarning::LossyUtf8 { field });
+            Some(String::from_utf8_lossy(bytes).into_owned())
         } else {
-            return Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
-                "Torrent's top-level element is not a dictionary.",
-            )));
+            None
         }
     }
 
     fn extract_announce(
         dict: &mut HashMap<String, BencodeElem>,
+        options: &ReadOptions,
+        warnings: &mut Vec<ReadWarning>,
     ) -> Result<Option<String>, LavaTorrentError> {
         match dict.remove("announce") {
             Some(BencodeElem::String(url)) => Ok(Some(url)),
-            Some(_) => {
-                return Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
-                    r#""announce" does not map to a string (or maps to invalid UTF8)."#,
-                )));
+            Some(BencodeElem::Bytes(bytes)) => {
+                match Self::decode_lossy("announce", &bytes, options, warnings) {
+                    Some(url) => Ok(Some(url)),
+                    None => Err(LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
+                        r#""announce" maps to {}."#,
+                        util::describe_invalid_utf8(&bytes)
+                    )))),
+                }
             }
+            Some(other) => Err(LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
+                r#""announce" maps to {}, expected String."#,
+                other.kind()
+            )))),
             None => Ok(None),
         }
     }
 
     fn extract_announce_list(
         dict: &mut HashMap<String, BencodeElem>,
+        options: &ReadOptions,
+        warnings: &mut Vec<ReadWarning>,
     ) -> Result<Option<AnnounceList>, LavaTorrentError> {
         let mut announce_list = Vec::new();
 
         match dict.remove("announce-list") {
             Some(BencodeElem::List(tiers)) => {
                 for tier in tiers {
-                    announce_list.push(Self::extract_announce_list_tier(tier)?);
+                    announce_list.push(Self::extract_announce_list_tier(tier, options, warnings)?);
                 }
                 Ok(Some(announce_list))
             }
-            Some(_) => {
-                return Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
-                    r#""announce-list" does not map to a list."#,
-                )));
-            }
+            Some(other) => Err(LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
+                r#""announce-list" maps to {}, expected List."#,
+                other.kind()
+            )))),
             // Since BEP 12 is an extension,
             // the existence of `announce-list` is not guaranteed.
             None => Ok(None),
         }
     }
 
-    fn extract_announce_list_tier(elem: BencodeElem) -> Result<Vec<String>, LavaTorrentError> {
+    fn extract_announce_list_tier(
+        elem: BencodeElem,
+        options: &ReadOptions,
+        warnings: &mut Vec<ReadWarning>,
+    ) -> Result<Vec<String>, LavaTorrentError> {
         match elem {
             BencodeElem::List(urls) => {
                 let mut tier = Vec::new();
                 for url in urls {
                     match url {
                         BencodeElem::String(url) => tier.push(url),
-                        _ => {
-                            return Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
-                                r#"A tier within "announce-list" contains a non-string element."#,
-                            )));
+                        BencodeElem::Bytes(bytes) => {
+                            match Self::decode_lossy("announce-list", &bytes, options, warnings) {
+                                Some(url) => tier.push(url),
+                                None => {
+                                    return Err(LavaTorrentError::MalformedTorrent(Cow::Owned(
+                                        format!(
+                                            r#"A tier within "announce-list" contains {}."#,
+                                            util::describe_invalid_utf8(&bytes)
+                                        ),
+                                    )));
+                                }
+                            }
+                        }
+                        other => {
+                            return Err(LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
+                                r#"A tier within "announce-list" contains a {} element, expected String."#,
+                                other.kind()
+                            ))));
                         }
                     }
                 }
                 Ok(tier)
             }
-            _ => {
-                return Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
-                    r#""announce-list" contains a non-list element."#,
-                )));
-            }
+            other => Err(LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
+                r#""announce-list" contains a {} element, expected List."#,
+                other.kind()
+            )))),
         }
     }
 
     fn extract_files(
         dict: &mut HashMap<String, BencodeElem>,
+        options: &ReadOptions,
     ) -> Result<Option<Vec<File>>, LavaTorrentError> {
         match dict.remove("files") {
             Some(BencodeElem::List(list)) => {
                 if list.is_empty() {
-                    return Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
+                    Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
                         r#""files" maps to an empty list."#,
-                    )));
+                    )))
+                } else if list.len() > options.max_files {
+                    Err(LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
+                        r#""files" has {} entries, exceeding max_files ({})."#,
+                        list.len(),
+                        options.max_files,
+                    ))))
                 } else {
-                    let mut files = Vec::new();
-                    for file in list {
-                        files.push(File::extract_file(file)?);
+                    let mut files = Vec::with_capacity(list.len());
+                    for (index, file) in list.into_iter().enumerate() {
+                        files.push(File::extract_file(file, index, options)?);
                     }
                     Ok(Some(files))
                 }
             }
-            Some(_) => {
-                return Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
-                    r#""files" does not map to a list."#,
-                )));
-            }
+            Some(other) => Err(LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
+                r#""files" maps to {}, expected List."#,
+                other.kind()
+            )))),
             None => Ok(None),
         }
     }
@@ -292,60 +953,75 @@ impl Torrent {
     fn extract_length(
         dict: &mut HashMap<String, BencodeElem>,
         files: &Option<Vec<File>>,
+        options: &ReadOptions,
+        warnings: &mut Vec<ReadWarning>,
     ) -> Result<i64, LavaTorrentError> {
-        match dict.remove("length") {
-            Some(BencodeElem::Integer(len)) => {
-                if files.is_some() {
-                    return Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
-                        r#"Both "length" and "files" exist."#,
-                    )));
+        match (dict.remove("length"), files) {
+            (Some(BencodeElem::Integer(len)), None) => Ok(len),
+            (Some(BencodeElem::Integer(declared)), Some(files)) => {
+                if options.allow_length_with_files {
+                    let computed = Self::sum_file_lengths(files)?;
+                    // keep it so that the info hash does not change
+                    dict.insert("length".to_owned(), BencodeElem::Integer(declared));
+                    warnings.push(ReadWarning::LengthWithFiles { declared, computed });
+                    Ok(computed)
                 } else {
-                    Ok(len)
+                    Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
+                        r#"Both "length" and "files" exist."#,
+                    )))
                 }
             }
-            Some(_) => {
-                return Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
-                    r#""length" does not map to an integer."#,
-                )));
-            }
-            None => {
-                if let Some(ref files) = *files {
-                    let mut length: i64 = 0;
-                    for file in files {
-                        match length.checked_add(file.length) {
-                            Some(sum) => {
-                                length = sum;
-                            }
-                            None => {
-                                return Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
-                                    r#"Torrent's length overflowed in i64."#,
-                                )));
-                            }
-                        }
-                    }
-                    Ok(length)
-                } else {
+            (Some(other), _) => Err(LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
+                r#""length" maps to {}, expected Integer."#,
+                other.kind()
+            )))),
+            (None, Some(files)) => Self::sum_file_lengths(files),
+            (None, None) => Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
+                r#"Neither "length" nor "files" exists."#,
+            ))),
+        }
+    }
+
+    fn sum_file_lengths(files: &[File]) -> Result<i64, LavaTorrentError> {
+        let mut length: i64 = 0;
+        for file in files {
+            match length.checked_add(file.length) {
+                Some(sum) => {
+                    length = sum;
+                }
+                None => {
                     return Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
-                        r#"Neither "length" nor "files" exists."#,
+                        r#"Torrent's length overflowed in i64."#,
                     )));
                 }
             }
         }
+        Ok(length)
     }
 
-    fn extract_name(dict: &mut HashMap<String, BencodeElem>) -> Result<String, LavaTorrentError> {
+    fn extract_name(
+        dict: &mut HashMap<String, BencodeElem>,
+        options: &ReadOptions,
+        warnings: &mut Vec<ReadWarning>,
+    ) -> Result<String, LavaTorrentError> {
         match dict.remove("name") {
             Some(BencodeElem::String(name)) => Ok(name),
-            Some(_) => {
-                return Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
-                    r#""name" does not map to a string (or maps to invalid UTF8)."#,
-                )));
-            }
-            None => {
-                return Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
-                    r#""name" does not exist."#,
-                )));
+            Some(BencodeElem::Bytes(bytes)) => {
+                match Self::decode_lossy("name", &bytes, options, warnings) {
+                    Some(name) => Ok(name),
+                    None => Err(LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
+                        r#""name" maps to {}."#,
+                        util::describe_invalid_utf8(&bytes)
+                    )))),
+                }
             }
+            Some(other) => Err(LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
+                r#""name" maps to {}, expected String."#,
+                other.kind()
+            )))),
+            None => Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
+                r#""name" does not exist."#,
+            ))),
         }
     }
 
@@ -357,58 +1033,101 @@ impl Torrent {
                 if len > 0 {
                     Ok(len)
                 } else {
-                    return Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
+                    Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
                         r#""piece length" <= 0."#,
-                    )));
+                    )))
                 }
             }
-            Some(_) => {
-                return Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
-                    r#""piece length" does not map to an integer."#,
-                )));
-            }
-            None => {
-                return Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
-                    r#""piece length" does not exist."#,
-                )));
-            }
+            Some(other) => Err(LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
+                r#""piece length" maps to {}, expected Integer."#,
+                other.kind()
+            )))),
+            None => Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
+                r#""piece length" does not exist."#,
+            ))),
         }
     }
 
     fn extract_pieces(
         dict: &mut HashMap<String, BencodeElem>,
+        options: &ReadOptions,
+        warnings: &mut Vec<ReadWarning>,
     ) -> Result<Vec<Piece>, LavaTorrentError> {
-        match dict.remove("pieces") {
+        // hashes that happen to be valid utf8 are parsed as a string
+        let pieces = dict.remove("pieces").map(|pieces| match pieces {
+            BencodeElem::String(string) => BencodeElem::Bytes(string.into_bytes()),
+            other => other,
+        });
+
+        match pieces {
             Some(BencodeElem::Bytes(bytes)) => {
                 if bytes.is_empty() {
-                    return Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
+                    Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
                         r#""pieces" maps to an empty sequence."#,
-                    )));
+                    )))
                 } else if (bytes.len() % PIECE_STRING_LENGTH) != 0 {
-                    return Err(LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
+                    Err(LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
                         r#""pieces"' length is not a multiple of {}."#,
                         PIECE_STRING_LENGTH,
-                    ))));
+                    ))))
                 } else {
                     Ok(bytes
-                        .chunks(PIECE_STRING_LENGTH)
-                        .map(|chunk| chunk.to_vec())
+                        .chunks_exact(PIECE_STRING_LENGTH)
+                        .map(|chunk| Piece::try_from(chunk).unwrap())
                         .collect())
                 }
             }
-            Some(_) => {
-                return Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
-                    r#""pieces" does not map to a sequence of bytes."#,
-                )));
-            }
-            None => {
-                return Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
-                    r#""pieces" does not exist."#,
-                )));
+            Some(BencodeElem::List(list)) if options.allow_pieces_list => {
+                let pieces = Self::extract_piece_list(list)?;
+                warnings.push(ReadWarning::PiecesList {
+                    count: pieces.len(),
+                });
+                Ok(pieces)
             }
+            Some(other) => Err(LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
+                r#""pieces" maps to {}, expected Bytes."#,
+                other.kind()
+            )))),
+            None => Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
+                r#""pieces" does not exist."#,
+            ))),
         }
     }
 
+    // `pieces` in list form, i.e. one 20-byte string per piece.
+    fn extract_piece_list(list: Vec<BencodeElem>) -> Result<Vec<Piece>, LavaTorrentError> {
+        if list.is_empty() {
+            return Err(LavaTorrentError::MalformedTorrent(Cow::Borrowed(
+                r#""pieces" maps to an empty sequence."#,
+            )));
+        }
+
+        list.into_iter()
+            .enumerate()
+            .map(|(index, elem)| {
+                let bytes = match elem {
+                    BencodeElem::String(string) => string.into_bytes(),
+                    BencodeElem::Bytes(bytes) => bytes,
+                    other => {
+                        return Err(LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
+                            r#""pieces"[{}] maps to {}, expected Bytes."#,
+                            index,
+                            other.kind()
+                        ))));
+                    }
+                };
+                Piece::try_from(bytes.as_slice()).map_err(|_| {
+                    LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
+                        r#""pieces"[{}] is {} bytes long, expected {}."#,
+                        index,
+                        bytes.len(),
+                        PIECE_STRING_LENGTH,
+                    )))
+                })
+            })
+            .collect()
+    }
+
     fn extract_extra_fields(dict: HashMap<String, BencodeElem>) -> Option<Dictionary> {
         if dict.is_empty() {
             None
@@ -418,6 +1137,196 @@ impl Torrent {
     }
 }
 
+impl TorrentFeatures {
+    // Everything but `has_padding_files`, which is easier
+    // to tell from the extracted `files`.
+    fn record(dict: &HashMap<String, BencodeElem>) -> TorrentFeatures {
+        let is_non_utf8 = |(key, val): (&String, &BencodeElem)| {
+            (key != "pieces") && matches!(val, BencodeElem::Bytes(_))
+        };
+        let info = match dict.get("info") {
+            Some(BencodeElem::Dictionary(info)) => Some(info),
+            _ => None,
+        };
+
+        TorrentFeatures {
+            single_file_form: info.is_some_and(|info| !info.contains_key("files")),
+            has_announce: dict.contains_key("announce"),
+            has_announce_list: dict.contains_key("announce-list"),
+            announce_list_tier_count: match dict.get("announce-list") {
+                Some(BencodeElem::List(tiers)) => tiers.len(),
+                _ => 0,
+            },
+            has_url_list: dict.contains_key("url-list"),
+            has_nodes: dict.contains_key("nodes"),
+            has_private_in_info: info.is_some_and(|info| info.contains_key("private")),
+            has_private_top_level: dict.contains_key("private"),
+            has_creation_date: dict.contains_key("creation date"),
+            has_source: info.is_some_and(|info| info.contains_key("source")),
+            has_padding_files: false,
+            non_utf8_strings: dict.iter().filter(|&entry| is_non_utf8(entry)).count()
+                + info.map_or(0, |info| {
+                    info.iter().filter(|&entry| is_non_utf8(entry)).count()
+                }),
+        }
+    }
+}
+
+impl MagnetStub {
+    /// Assemble a `Torrent` from `self` and `info_bytes`, the raw bencoded
+    /// `info` dict (e.g. as fetched from peers via `ut_metadata`).
+    ///
+    /// The SHA1 hash of `info_bytes` is checked against `self.info_hash`
+    /// before anything is parsed, and the info hash of the returned
+    /// `Torrent` is checked again after, as the latter is rebuilt from the
+    /// parsed dict and only keeps the hash if `info_bytes` is canonical.
+    /// If either doesn't match, or if `info_bytes` is not a valid `info`
+    /// dict, then `Err(error)` will be returned.
+    ///
+    /// `announce` is set to the first tracker in `self.trackers`. If there
+    /// is more than 1 tracker, `announce_list` is set as well, with each
+    /// tracker in its own tier. `self.name` is not used, as the name
+    /// in `info_bytes` is authoritative.
+    pub fn upgrade(self, info_bytes: &[u8]) -> Result<Torrent, LavaTorrentError> {
+        let expected = self.info_hash;
+        let info_hash: [u8; 20] = Sha1::digest(info_bytes).into();
+        if info_hash != self.info_hash {
+            return Err(LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
+                "Info hash mismatch: expected [{}], found [{}].",
+                hex::display(&self.info_hash),
+                hex::display(&info_hash),
+            ))));
+        }
+
+        let mut parsed = BencodeElem::from_bytes(info_bytes)?;
+        if parsed.len() != 1 {
+            return Err(LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
+                r#""info" should contain 1 and only 1 top-level element, {} found."#,
+                parsed.len()
+            ))));
+        }
+
+        let mut torrent = HashMap::new();
+        torrent.insert("info".to_owned(), parsed.remove(0));
+        if let Some(first) = self.trackers.first() {
+            torrent.insert("announce".to_owned(), BencodeElem::String(first.clone()));
+        }
+        if self.trackers.len() > 1 {
+            torrent.insert(
+                "announce-list".to_owned(),
+                BencodeElem::List(
+                    self.trackers
+                        .into_iter()
+                        .map(|url| BencodeElem::List(vec![BencodeElem::String(url)]))
+                        .collect(),
+                ),
+            );
+        }
+
+        Self::check_upgraded(
+            &expected,
+            Torrent::from_bencode(BencodeElem::Dictionary(torrent))?,
+        )
+    }
+
+    // `torrent` is rebuilt from the parsed `info` dict, so it only keeps
+    // the info hash `info_bytes` had if `info_bytes` is canonical.
+    fn check_upgraded(expected: &[u8; 20], torrent: Torrent) -> Result<Torrent, LavaTorrentError> {
+        let info_hash = torrent.info_hash_bytes();
+        if info_hash[..] != expected[..] {
+            return Err(LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
+                "Info dict is not canonical: re-encoding it changes the info hash \
+                 from [{}] to [{}].",
+                hex::display(expected),
+                hex::display(&info_hash),
+            ))));
+        }
+        Ok(torrent)
+    }
+}
+
+impl SpannedTorrent {
+    /// Return the torrent.
+    pub fn torrent(&self) -> &Torrent {
+        &self.torrent
+    }
+
+    /// Return the torrent, dropping the bytes it was read from
+    /// along with the spans.
+    pub fn into_torrent(self) -> Torrent {
+        self.torrent
+    }
+
+    /// Return the (decompressed) bytes the torrent was read from.
+    pub fn raw_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Return where each element is in [`raw_bytes()`](#method.raw_bytes).
+    pub fn spans(&self) -> &TorrentSpans {
+        &self.spans
+    }
+
+    /// Return the problems that were worked around while reading the
+    /// torrent, as [`Torrent::read_from_bytes_with_warnings()`] does.
+    ///
+    /// [`Torrent::read_from_bytes_with_warnings()`]: struct.Torrent.html#method.read_from_bytes_with_warnings
+    pub fn warnings(&self) -> &[ReadWarning] {
+        &self.warnings
+    }
+
+    /// Return the encoded bytes of the field at `path`, exactly as they
+    /// appear in the bytes the torrent was read from.
+    ///
+    /// Each step in `path` is a dictionary key, so `&["creation date"]` is a
+    /// top-level field and `&["info", "source"]` is a field in `info`. If
+    /// there is no field at `path`, then `None` will be returned.
+    pub fn raw_field_bytes(&self, path: &[&str]) -> Option<&[u8]> {
+        let path = path.iter().map(|key| key.as_bytes()).collect::<Vec<_>>();
+        BencodeElem::raw_bytes_at(&self.spans, &self.bytes, &path)
+    }
+
+    /// Calculate the info hash of `info` exactly as it appears in the bytes
+    /// the torrent was read from, in lowercase hex.
+    ///
+    /// This is the same as [`Torrent::info_hash()`] unless `info` was not
+    /// encoded the way this crate encodes it, e.g. if `pieces` was a list
+    /// (see [`ReadOptions::allow_pieces_list`]). Peers and trackers know the
+    /// torrent by this hash, so use it as long as the torrent is only read.
+    ///
+    /// [`Torrent::info_hash()`]: struct.Torrent.html#method.info_hash
+    /// [`ReadOptions::allow_pieces_list`]: struct.ReadOptions.html#structfield.allow_pieces_list
+    pub fn raw_info_hash(&self) -> String {
+        // a torrent can't be read without `info`
+        crate::hash::sha1_hex(self.raw_field_bytes(&["info"]).unwrap())
+    }
+}
+
+impl fmt::Display for ReadWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ReadWarning::TrackersTruncated { kept, dropped } => write!(
+                f,
+                r#""announce-list" truncated to [{}] trackers, [{}] dropped"#,
+                kept, dropped
+            ),
+            ReadWarning::LengthWithFiles { declared, computed } => write!(
+                f,
+                r#""length" ([{}]) ignored in favor of the sum of "files" ([{}])"#,
+                declared, computed
+            ),
+            ReadWarning::LossyUtf8 { field } => {
+                write!(f, r#""{}" is not valid UTF-8, decoded lossily"#, field)
+            }
+            ReadWarning::PiecesList { count } => write!(
+                f,
+                r#""pieces" is a list of {} hashes, which re-encoding concatenates (changing the info hash)"#,
+                count
+            ),
+        }
+    }
+}
+
 #[cfg(test)]
 mod file_read_tests {
     use super::*;
@@ -432,10 +1341,10 @@ mod file_read_tests {
         });
 
         assert_eq!(
-            File::extract_file(file).unwrap(),
+            File::extract_file(file, 0, &ReadOptions::default()).unwrap(),
             File {
                 length: 42,
-                path: PathBuf::from("root/.bashrc"),
+                path: vec!["root".to_owned(), ".bashrc".to_owned()],
                 extra_fields: Some(HashMap::from_iter(
                     vec![("comment".to_owned(), bencode_elem!("no comment"))].into_iter()
                 )),
@@ -447,9 +1356,12 @@ mod file_read_tests {
     fn extract_file_not_dictionary() {
         let file = bencode_elem!([]);
 
-        match File::extract_file(file) {
+        match File::extract_file(file, 0, &ReadOptions::default()) {
             Err(LavaTorrentError::MalformedTorrent(m)) => {
-                assert_eq!(m, r#""files" contains a non-dictionary element."#);
+                assert_eq!(
+                    m,
+                    r#""files" contains a List element, expected Dictionary."#
+                );
             }
             _ => panic!(),
         }
@@ -457,15 +1369,13 @@ mod file_read_tests {
 
     #[test]
     fn extract_file_length_ok() {
-        let mut dict =
-            HashMap::from_iter(vec![("length".to_owned(), bencode_elem!(42))].into_iter());
+        let mut dict = HashMap::from_iter(vec![("length".to_owned(), bencode_elem!(42))]);
         assert_eq!(File::extract_file_length(&mut dict).unwrap(), 42);
     }
 
     #[test]
     fn extract_file_length_is_negative() {
-        let mut dict =
-            HashMap::from_iter(vec![("length".to_owned(), bencode_elem!(-1))].into_iter());
+        let mut dict = HashMap::from_iter(vec![("length".to_owned(), bencode_elem!(-1))]);
 
         match File::extract_file_length(&mut dict) {
             Err(LavaTorrentError::MalformedTorrent(m)) => assert_eq!(m, r#""length" < 0."#),
@@ -475,12 +1385,11 @@ mod file_read_tests {
 
     #[test]
     fn extract_file_length_not_integer() {
-        let mut dict =
-            HashMap::from_iter(vec![("length".to_owned(), bencode_elem!("42"))].into_iter());
+        let mut dict = HashMap::from_iter(vec![("length".to_owned(), bencode_elem!("42"))]);
 
         match File::extract_file_length(&mut dict) {
             Err(LavaTorrentError::MalformedTorrent(m)) => {
-                assert_eq!(m, r#""length" does not map to an integer."#);
+                assert_eq!(m, r#""length" maps to String, expected Integer."#);
             }
             _ => panic!(),
         }
@@ -500,25 +1409,35 @@ mod file_read_tests {
 
     #[test]
     fn extract_file_path_ok() {
-        let mut dict = HashMap::from_iter(
-            vec![("path".to_owned(), bencode_elem!(["root", ".bashrc"]))].into_iter(),
+        let mut dict = HashMap::from_iter(vec![(
+            "path".to_owned(),
+            bencode_elem!(["root", ".bashrc"]),
+        )]);
+
+        assert_eq!(
+            File::extract_file_path(&mut dict, 0, &ReadOptions::default()).unwrap(),
+            vec!["root".to_owned(), ".bashrc".to_owned()]
         );
+    }
+
+    #[test]
+    fn extract_file_path_separators() {
+        let mut dict =
+            HashMap::from_iter(vec![("path".to_owned(), bencode_elem!(["a/b", "c\\d"]))]);
 
         assert_eq!(
-            File::extract_file_path(&mut dict).unwrap(),
-            PathBuf::from("root/.bashrc")
+            File::extract_file_path(&mut dict, 0, &ReadOptions::default()).unwrap(),
+            vec!["a/b".to_owned(), "c\\d".to_owned()]
         );
     }
 
     #[test]
     fn extract_file_path_not_list() {
-        let mut dict = HashMap::from_iter(
-            vec![("path".to_owned(), bencode_elem!("root/.bashrc"))].into_iter(),
-        );
+        let mut dict = HashMap::from_iter(vec![("path".to_owned(), bencode_elem!("root/.bashrc"))]);
 
-        match File::extract_file_path(&mut dict) {
+        match File::extract_file_path(&mut dict, 0, &ReadOptions::default()) {
             Err(LavaTorrentError::MalformedTorrent(m)) => {
-                assert_eq!(m, r#""path" does not map to a list."#);
+                assert_eq!(m, r#""path" maps to String, expected List."#);
             }
             _ => panic!(),
         }
@@ -528,7 +1447,7 @@ mod file_read_tests {
     fn extract_file_path_missing() {
         let mut dict = HashMap::new();
 
-        match File::extract_file_path(&mut dict) {
+        match File::extract_file_path(&mut dict, 0, &ReadOptions::default()) {
             Err(LavaTorrentError::MalformedTorrent(m)) => {
                 assert_eq!(m, r#""path" does not exist."#);
             }
@@ -538,9 +1457,9 @@ mod file_read_tests {
 
     #[test]
     fn extract_file_path_empty_list() {
-        let mut dict = HashMap::from_iter(vec![("path".to_owned(), bencode_elem!([]))].into_iter());
+        let mut dict = HashMap::from_iter(vec![("path".to_owned(), bencode_elem!([]))]);
 
-        match File::extract_file_path(&mut dict) {
+        match File::extract_file_path(&mut dict, 0, &ReadOptions::default()) {
             Err(LavaTorrentError::MalformedTorrent(m)) => {
                 assert_eq!(m, r#""path" maps to a 0-length list."#);
             }
@@ -550,20 +1469,17 @@ mod file_read_tests {
 
     #[test]
     fn extract_file_path_component_not_string() {
-        let mut dict = HashMap::from_iter(
-            vec![(
-                "path".to_owned(),
-                BencodeElem::List(vec![
-                    BencodeElem::String("root".to_owned()),
-                    BencodeElem::Bytes(".bashrc".as_bytes().to_vec()),
-                ]),
-            )]
-            .into_iter(),
-        );
-
-        match File::extract_file_path(&mut dict) {
+        let mut dict = HashMap::from_iter(vec![(
+            "path".to_owned(),
+            BencodeElem::List(vec![
+                BencodeElem::String("root".to_owned()),
+                BencodeElem::Bytes(".bashrc".as_bytes().to_vec()),
+            ]),
+        )]);
+
+        match File::extract_file_path(&mut dict, 0, &ReadOptions::default()) {
             Err(LavaTorrentError::MalformedTorrent(m)) => {
-                assert_eq!(m, r#""path" contains a non-string element."#);
+                assert_eq!(m, r#""path" contains a Bytes element, expected String."#);
             }
             _ => panic!(),
         }
@@ -571,18 +1487,101 @@ mod file_read_tests {
 
     #[test]
     fn extract_file_path_component_invalid() {
-        let mut dict = HashMap::from_iter(
-            vec![(
+        let mut dict = HashMap::from_iter(vec![(
+            "path".to_owned(),
+            BencodeElem::List(vec![
+                BencodeElem::String("root".to_owned()),
+                BencodeElem::String(".".to_owned()),
+            ]),
+        )]);
+
+        match File::extract_file_path(&mut dict, 0, &ReadOptions::default()) {
+            Err(LavaTorrentError::MalformedTorrent(m)) => {
+                assert_eq!(m, r#""path" contains "." or ".."."#);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn extract_file_path_component_invalid_2() {
+        let mut dict = HashMap::from_iter(vec![(
+            "path".to_owned(),
+            BencodeElem::List(vec![
+                BencodeElem::String("root".to_owned()),
+                BencodeElem::String("..".to_owned()),
+            ]),
+        )]);
+
+        match File::extract_file_path(&mut dict, 0, &ReadOptions::default()) {
+            Err(LavaTorrentError::MalformedTorrent(m)) => {
+                assert_eq!(m, r#""path" contains "." or ".."."#);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn extract_file_path_absolute_component() {
+        for component in [
+            "/etc",
+            "/",
+            "\\Windows",
+            "\\\\server\\share\\file",
+            "//server/share/file",
+            "C:\\Windows\\system32",
+            "c:/Windows",
+            "C:file",
+            "C:",
+        ] {
+            let mut dict = HashMap::from_iter(vec![(
                 "path".to_owned(),
-                BencodeElem::List(vec![
-                    BencodeElem::String("root".to_owned()),
-                    BencodeElem::String(".".to_owned()),
-                ]),
-            )]
-            .into_iter(),
-        );
+                bencode_elem!(["root", component]),
+            )]);
+            match File::extract_file_path(&mut dict, 0, &ReadOptions::default()) {
+                Err(LavaTorrentError::MalformedTorrent(m)) => assert_eq!(
+                    m,
+                    format!(r#""path" contains an absolute component: {:?}."#, component)
+                ),
+                _ => panic!("{:?} was accepted", component),
+            }
+        }
+    }
+
+    #[test]
+    fn extract_file_path_absolute_component_sanitized() {
+        let options = ReadOptions {
+            sanitize_paths: true,
+            ..ReadOptions::default()
+        };
+
+        for (component, sanitized) in [
+            ("/etc", "etc"),
+            ("/", "_"),
+            ("C:", "_"),
+            ("\\\\", "_"),
+            ("\\\\server\\share\\file", "server\\share\\file"),
+            ("C:\\Windows\\system32", "Windows\\system32"),
+            ("C:file", "file"),
+            // prefixes are stripped until none is left
+            ("C:\\D:/\\etc", "etc"),
+            // not a prefix
+            ("file:C", "file:C"),
+            ("CD:", "CD:"),
+        ] {
+            let mut dict = HashMap::from_iter(vec![(
+                "path".to_owned(),
+                bencode_elem!(["root", component]),
+            )]);
+            assert_eq!(
+                File::extract_file_path(&mut dict, 0, &options).unwrap(),
+                vec!["root".to_owned(), sanitized.to_owned()]
+            );
+        }
 
-        match File::extract_file_path(&mut dict) {
+        // the result is still checked
+        let mut dict = HashMap::from_iter(vec![("path".to_owned(), bencode_elem!(["/.."]))]);
+        match File::extract_file_path(&mut dict, 0, &options) {
             Err(LavaTorrentError::MalformedTorrent(m)) => {
                 assert_eq!(m, r#""path" contains "." or ".."."#);
             }
@@ -591,21 +1590,68 @@ mod file_read_tests {
     }
 
     #[test]
-    fn extract_file_path_component_invalid_2() {
-        let mut dict = HashMap::from_iter(
-            vec![(
+    fn extract_file_path_nul() {
+        for options in [
+            ReadOptions::default(),
+            ReadOptions {
+                sanitize_paths: true,
+                ..ReadOptions::default()
+            },
+        ] {
+            let mut dict = HashMap::from_iter(vec![(
                 "path".to_owned(),
-                BencodeElem::List(vec![
-                    BencodeElem::String("root".to_owned()),
-                    BencodeElem::String("..".to_owned()),
-                ]),
-            )]
-            .into_iter(),
+                bencode_elem!(["root", "file\0.txt"]),
+            )]);
+            match File::extract_file_path(&mut dict, 0, &options) {
+                Err(LavaTorrentError::MalformedTorrent(m)) => {
+                    assert_eq!(m, r#""path" contains a NUL byte."#);
+                }
+                _ => panic!(),
+            }
+        }
+    }
+
+    #[test]
+    fn extract_file_path_max_components() {
+        let options = ReadOptions {
+            max_path_components: 2,
+            ..ReadOptions::default()
+        };
+
+        let mut dict = HashMap::from_iter(vec![("path".to_owned(), bencode_elem!(["a", "b"]))]);
+        assert_eq!(
+            File::extract_file_path(&mut dict, 3, &options).unwrap(),
+            vec!["a".to_owned(), "b".to_owned()]
+        );
+
+        let mut dict =
+            HashMap::from_iter(vec![("path".to_owned(), bencode_elem!(["a", "b", "c"]))]);
+        match File::extract_file_path(&mut dict, 3, &options) {
+            Err(LavaTorrentError::MalformedTorrent(m)) => assert_eq!(
+                m,
+                r#""path" of file 3 has 3 components, exceeding max_path_components (2)."#
+            ),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn extract_file_path_max_bytes() {
+        let options = ReadOptions {
+            max_path_bytes: 4,
+            ..ReadOptions::default()
+        };
+
+        let mut dict = HashMap::from_iter(vec![("path".to_owned(), bencode_elem!(["ab", "cd"]))]);
+        assert_eq!(
+            File::extract_file_path(&mut dict, 3, &options).unwrap(),
+            vec!["ab".to_owned(), "cd".to_owned()]
         );
 
-        match File::extract_file_path(&mut dict) {
+        let mut dict = HashMap::from_iter(vec![("path".to_owned(), bencode_elem!(["ab", "cde"]))]);
+        match File::extract_file_path(&mut dict, 3, &options) {
             Err(LavaTorrentError::MalformedTorrent(m)) => {
-                assert_eq!(m, r#""path" contains "." or ".."."#);
+                assert_eq!(m, r#""path" of file 3 exceeds max_path_bytes (4)."#)
             }
             _ => panic!(),
         }
@@ -634,8 +1680,55 @@ mod torrent_read_tests {
     // @note: `read_from_bytes()` and `read_from_file()` are not tested
     // as they are best left to integration tests (in `tests/`).
     use super::*;
+    use crate::torrent::v1::torrent_tests::torrent_with_length;
     use std::iter::FromIterator;
 
+    #[test]
+    fn decompress_not_compressed() {
+        let bytes = b"d4:spam4:eggse";
+        match Torrent::decompress(bytes).unwrap() {
+            Cow::Borrowed(b) => assert_eq!(b, bytes),
+            Cow::Owned(_) => panic!(),
+        }
+    }
+
+    #[cfg(not(feature = "gzip"))]
+    #[test]
+    fn decompress_gzip_disabled() {
+        match Torrent::decompress(&[0x1f, 0x8b, 0x08, 0x00]) {
+            Err(LavaTorrentError::MalformedTorrent(m)) => {
+                assert_eq!(
+                    m,
+                    "Input is gzip-compressed. Decompress it first, or enable the `gzip` feature."
+                );
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[cfg(not(feature = "gzip"))]
+    #[test]
+    fn decompress_zlib_disabled() {
+        match Torrent::decompress(&[0x78, 0x9c, 0x03, 0x00]) {
+            Err(LavaTorrentError::MalformedTorrent(m)) => {
+                assert_eq!(
+                    m,
+                    "Input is zlib-compressed. Decompress it first, or enable the `gzip` feature."
+                );
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn decompress_corrupted() {
+        match Torrent::decompress(&[0x1f, 0x8b, 0xff, 0xff]) {
+            Err(LavaTorrentError::Io(_)) => (),
+            _ => panic!(),
+        }
+    }
+
     #[test]
     fn validate_ok() {
         // torrent is actually invalid (incorrect pieces' length)
@@ -647,7 +1740,8 @@ mod torrent_read_tests {
             files: None,
             name: "sample".to_owned(),
             piece_length: 2,
-            pieces: vec![vec![1, 2], vec![3, 4]],
+            pieces: vec![[1; 20], [2; 20]],
+            private: None,
             extra_fields: None,
             extra_info_fields: None,
         };
@@ -666,14 +1760,19 @@ mod torrent_read_tests {
             files: None,
             name: "sample".to_owned(),
             piece_length: 2,
-            pieces: vec![vec![1, 2], vec![3, 4]],
+            pieces: vec![[1; 20], [2; 20]],
+            private: None,
             extra_fields: None,
             extra_info_fields: None,
         };
 
         match torrent.validate() {
             Err(LavaTorrentError::MalformedTorrent(m)) => {
-                assert_eq!(m, "Total piece length 4 < torrent's length 6.");
+                assert_eq!(
+                    m,
+                    "Total piece length 4 < torrent's length 6: \
+                     2 pieces, 3 expected, 2 bytes not covered."
+                );
             }
             _ => panic!(),
         }
@@ -688,7 +1787,8 @@ mod torrent_read_tests {
             files: None,
             name: "sample".to_owned(),
             piece_length: 2,
-            pieces: vec![vec![1, 2], vec![3, 4]],
+            pieces: vec![[1; 20], [2; 20]],
+            private: None,
             extra_fields: None,
             extra_info_fields: None,
         };
@@ -707,156 +1807,590 @@ mod torrent_read_tests {
             length: 1,
             files: None,
             name: "sample".to_owned(),
-            piece_length: i64::max_value(),
-            pieces: vec![vec![1, 2], vec![3, 4], vec![5, 6]],
+            piece_length: i64::MAX,
+            pieces: vec![[1; 20], [2; 20], [3; 20]],
+            private: None,
             extra_fields: None,
             extra_info_fields: None,
         };
 
-        match torrent.validate() {
-            Err(LavaTorrentError::MalformedTorrent(m)) => {
-                assert_eq!(m, "Torrent's total piece length overflowed in usize.");
-            }
-            _ => panic!(),
-        }
+        match torrent.validate() {
+            Err(LavaTorrentError::MalformedTorrent(m)) => {
+                assert_eq!(m, "Torrent's total piece length overflowed in u64.");
+            }
+            _ => panic!(),
+        }
+    }
+
+    // Lengths beyond 4 GiB don't fit into `usize` on 32-bit targets, which is
+    // exactly what the tests below are meant to exercise. They don't rely on
+    // the pointer width otherwise, so they can be run as is on such targets
+    // (e.g. `cargo test --target i686-unknown-linux-gnu`).
+    const GIB: Integer = 1024 * 1024 * 1024;
+    #[cfg(target_pointer_width = "32")]
+    const _: () = assert!(4 * GIB > usize::MAX as Integer);
+
+    #[test]
+    fn validate_length_gib_boundaries() {
+        let piece_length = 1024 * 1024; // 1 MiB
+
+        for length in [2 * GIB, 4 * GIB, 8 * GIB] {
+            for length in [length - 1, length, length + 1] {
+                let n_pieces = (length as u64).div_ceil(piece_length as u64) as usize;
+                let mut torrent = torrent_with_length(length, piece_length);
+                assert_eq!(torrent.pieces.len(), n_pieces);
+                assert!(torrent.clone().validate().is_ok());
+
+                torrent.pieces.pop();
+                match torrent.validate() {
+                    Err(LavaTorrentError::MalformedTorrent(m)) => assert_eq!(
+                        m,
+                        format!(
+                            "Total piece length {} < torrent's length {}: \
+                             {} pieces, {} expected, {} bytes not covered.",
+                            piece_length * (n_pieces as Integer - 1),
+                            length,
+                            n_pieces - 1,
+                            n_pieces,
+                            length - piece_length * (n_pieces as Integer - 1),
+                        )
+                    ),
+                    _ => panic!(),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn validate_length_i64_max() {
+        // 8192 pieces of 1 PiB cover all of i64
+        let piece_length = 1 << 50;
+        let mut torrent = torrent_with_length(Integer::MAX, piece_length);
+        assert_eq!(torrent.pieces.len(), 8192);
+        assert!(torrent.clone().validate().is_ok());
+
+        torrent.pieces.pop();
+        match torrent.validate() {
+            Err(LavaTorrentError::MalformedTorrent(m)) => assert_eq!(
+                m,
+                format!(
+                    "Total piece length {} < torrent's length {}: \
+                     8191 pieces, 8192 expected, {} bytes not covered.",
+                    piece_length * 8191,
+                    Integer::MAX,
+                    Integer::MAX - piece_length * 8191,
+                )
+            ),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn from_parsed_ok() {
+        let dict = vec![bencode_elem!({
+            ("announce", "url"),
+            ("info", {
+                ("name", "??"),
+                ("length", 2),
+                ("piece length", 2),
+                (
+                    "pieces",
+                    (0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09,
+                        0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f, 0x10, 0x11, 0x12, 0x13)
+                ),
+            }),
+        })];
+
+        assert_eq!(
+            Torrent::from_parsed(dict, &ReadOptions::default(), &mut Vec::new()).unwrap(),
+            Torrent {
+                announce: Some("url".to_owned()),
+                announce_list: None,
+                length: 2,
+                files: None,
+                name: "??".to_owned(),
+                piece_length: 2,
+                pieces: vec![[
+                    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c,
+                    0x0d, 0x0e, 0x0f, 0x10, 0x11, 0x12, 0x13,
+                ]],
+                private: None,
+                extra_fields: None,
+                extra_info_fields: None,
+            }
+        );
+    }
+
+    #[test]
+    fn from_parsed_top_level_multiple_elem() {
+        let dict = vec![bencode_elem!({}), bencode_elem!([])];
+
+        match Torrent::from_parsed(dict, &ReadOptions::default(), &mut Vec::new()) {
+            Err(LavaTorrentError::MalformedTorrent(m)) => assert_eq!(
+                m,
+                "Torrent should contain 1 and only 1 top-level element, 2 found."
+            ),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn from_parsed_top_level_no_elem() {
+        let dict = Vec::new();
+
+        match Torrent::from_parsed(dict, &ReadOptions::default(), &mut Vec::new()) {
+            Err(LavaTorrentError::MalformedTorrent(m)) => assert_eq!(
+                m,
+                "Torrent should contain 1 and only 1 top-level element, 0 found."
+            ),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn from_parsed_top_level_not_dict() {
+        let dict = vec![bencode_elem!([])];
+
+        match Torrent::from_parsed(dict, &ReadOptions::default(), &mut Vec::new()) {
+            Err(LavaTorrentError::MalformedTorrent(m)) => {
+                assert_eq!(m, "Torrent's top-level element is not a dictionary.");
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn from_parsed_info_missing() {
+        // "announce" is needed here because it is parsed before "info"
+        // missing "announce-list" is fine as that won't trigger an error
+        let dict = vec![bencode_elem!({ ("announce", "url") })];
+
+        match Torrent::from_parsed(dict, &ReadOptions::default(), &mut Vec::new()) {
+            Err(LavaTorrentError::MalformedTorrent(m)) => {
+                assert_eq!(m, r#""info" does not exist."#);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn from_parsed_info_not_dict() {
+        // "announce" is needed here because it is parsed before "info"
+        // missing "announce-list" is fine as that won't trigger an error
+        let parsed = vec![bencode_elem!({ ("announce", "url"), ("info", []) })];
+
+        match Torrent::from_parsed(parsed, &ReadOptions::default(), &mut Vec::new()) {
+            Err(LavaTorrentError::MalformedTorrent(m)) => {
+                assert_eq!(m, r#""info" maps to List, expected Dictionary."#);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn from_bencode_ok() {
+        let elem = bencode_elem!({
+            ("info", {
+                ("name", "??"),
+                ("length", 2),
+                ("piece length", 2),
+                (
+                    "pieces",
+                    (0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09,
+                        0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f, 0x10, 0x11, 0x12, 0x13)
+                ),
+            }),
+        });
+
+        let torrent = Torrent::from_bencode(elem).unwrap();
+        assert_eq!(torrent.name, "??");
+        assert_eq!(torrent.length, 2);
+    }
+
+    #[test]
+    fn from_bencode_not_dict() {
+        match Torrent::from_bencode(bencode_elem!([])) {
+            Err(LavaTorrentError::MalformedTorrent(m)) => {
+                assert_eq!(m, "Torrent's top-level element is not a dictionary.");
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn from_bencode_invalid() {
+        // `pieces` is not a multiple of 20 bytes
+        let elem = bencode_elem!({
+            ("info", {
+                ("name", "??"),
+                ("length", 2),
+                ("piece length", 2),
+                ("pieces", (0x00, 0x01)),
+            }),
+        });
+
+        match Torrent::from_bencode(elem) {
+            Err(LavaTorrentError::MalformedTorrent(_)) => (),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn extract_all_depth_limit() {
+        let torrent = bencode_elem!({
+            ("info", {
+                ("name", "??"),
+                ("length", 2),
+                ("piece length", 2),
+                (
+                    "pieces",
+                    (0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09,
+                        0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f, 0x10, 0x11, 0x12, 0x13)
+                ),
+            }),
+        });
+        let nest =
+            |depth| (0..depth).fold(torrent.clone(), |elem, _| BencodeElem::List(vec![elem]));
+
+        let found = Torrent::extract_all(&nest(MAX_EXTRACT_DEPTH));
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, vec![PathSegment::Index(0); MAX_EXTRACT_DEPTH]);
+        assert!(found[0].1.is_ok());
+
+        assert!(Torrent::extract_all(&nest(MAX_EXTRACT_DEPTH + 1)).is_empty());
+    }
+
+    #[test]
+    fn magnet_stub_upgrade_ok() {
+        let info = bencode_elem!({
+            ("name", "??"),
+            ("length", 2),
+            ("piece length", 2),
+            ("pieces", ([0xff; 20].to_vec())),
+        })
+        .encode();
+        let stub = MagnetStub {
+            info_hash: Sha1::digest(&info).into(),
+            name: Some("ignored".to_owned()),
+            trackers: vec!["url1".to_owned(), "url2".to_owned()],
+        };
+
+        let torrent = stub.clone().upgrade(&info).unwrap();
+        assert_eq!(torrent.name, "??");
+        assert_eq!(torrent.announce, Some("url1".to_owned()));
+        assert_eq!(
+            torrent.announce_list,
+            Some(vec![vec!["url1".to_owned()], vec!["url2".to_owned()]])
+        );
+        assert_eq!(torrent.info_hash_bytes(), stub.info_hash.to_vec());
+    }
+
+    #[test]
+    fn magnet_stub_upgrade_single_tracker() {
+        let info = bencode_elem!({
+            ("name", "??"),
+            ("length", 2),
+            ("piece length", 2),
+            ("pieces", ([0xff; 20].to_vec())),
+        })
+        .encode();
+        let stub = MagnetStub {
+            info_hash: Sha1::digest(&info).into(),
+            name: None,
+            trackers: vec!["url1".to_owned()],
+        };
+
+        let torrent = stub.upgrade(&info).unwrap();
+        assert_eq!(torrent.announce, Some("url1".to_owned()));
+        assert_eq!(torrent.announce_list, None);
+    }
+
+    #[test]
+    fn magnet_stub_upgrade_hash_mismatch() {
+        let stub = MagnetStub {
+            info_hash: [0; 20],
+            name: None,
+            trackers: Vec::new(),
+        };
+
+        // SHA1 of an empty input
+        match stub.upgrade(&[]) {
+            Err(LavaTorrentError::MalformedTorrent(m)) => assert_eq!(
+                m,
+                "Info hash mismatch: expected [0000000000000000000000000000000000000000], \
+                 found [da39a3ee5e6b4b0d3255bfef95601890afd80709]."
+            ),
+            _ => panic!(),
+        }
+    }
+
+    // Inputs that aren't canonical are rejected by the decoder with the
+    // default options, so the check after upgrading is tested on its own.
+    #[test]
+    fn magnet_stub_check_upgraded() {
+        let info = bencode_elem!({
+            ("name", "??"),
+            ("length", 2),
+            ("piece length", 2),
+            ("pieces", ([0xff; 20].to_vec())),
+        })
+        .encode();
+        let info_hash: [u8; 20] = Sha1::digest(&info).into();
+        let torrent = MagnetStub {
+            info_hash,
+            name: None,
+            trackers: Vec::new(),
+        }
+        .upgrade(&info)
+        .unwrap();
+
+        assert_eq!(
+            MagnetStub::check_upgraded(&info_hash, torrent.clone()).unwrap(),
+            torrent
+        );
+        match MagnetStub::check_upgraded(&[0; 20], torrent) {
+            Err(LavaTorrentError::MalformedTorrent(m)) => assert_eq!(
+                m,
+                format!(
+                    "Info dict is not canonical: re-encoding it changes the info hash \
+                     from [0000000000000000000000000000000000000000] to [{}].",
+                    hex::encode(&info_hash),
+                )
+            ),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn magnet_stub_upgrade_multiple_elem() {
+        let info = [bencode_elem!({}).encode(), bencode_elem!({}).encode()].concat();
+        let stub = MagnetStub {
+            info_hash: Sha1::digest(&info).into(),
+            name: None,
+            trackers: Vec::new(),
+        };
+
+        match stub.upgrade(&info) {
+            Err(LavaTorrentError::MalformedTorrent(m)) => assert_eq!(
+                m,
+                r#""info" should contain 1 and only 1 top-level element, 2 found."#
+            ),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn from_fastresume_bytes_ok() {
+        let resume = bencode_elem!({
+            ("file-format", "libtorrent resume file"),
+            ("info", {
+                ("name", "??"),
+                ("length", 2),
+                ("piece length", 2),
+                (
+                    "pieces",
+                    (0xff, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09,
+                        0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f, 0x10, 0x11, 0x12, 0x13)
+                ),
+            }),
+            ("trackers", [["url1", "url2"], ["url3"]]),
+        });
+
+        let (torrent, remaining) = Torrent::from_fastresume_bytes(&resume.encode()).unwrap();
+        assert_eq!(torrent.announce, Some("url1".to_owned()));
+        assert_eq!(
+            torrent.announce_list,
+            Some(vec![
+                vec!["url1".to_owned(), "url2".to_owned()],
+                vec!["url3".to_owned()],
+            ])
+        );
+        assert_eq!(torrent.name, "??");
+        assert_eq!(
+            remaining,
+            HashMap::from_iter(vec![
+                (
+                    "file-format".to_owned(),
+                    bencode_elem!("libtorrent resume file")
+                ),
+                (
+                    "trackers".to_owned(),
+                    bencode_elem!([["url1", "url2"], ["url3"]])
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn from_fastresume_bytes_metadata() {
+        let resume = bencode_elem!({
+            ("metadata", {
+                ("announce", "url"),
+                ("info", {
+                    ("name", "??"),
+                    ("length", 2),
+                    ("piece length", 2),
+                    (
+                        "pieces",
+                        (0xff, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09,
+                            0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f, 0x10, 0x11, 0x12, 0x13)
+                    ),
+                }),
+            }),
+        });
+
+        let (torrent, remaining) = Torrent::from_fastresume_bytes(&resume.encode()).unwrap();
+        assert_eq!(torrent.announce, Some("url".to_owned()));
+        assert_eq!(torrent.announce_list, None);
+        assert!(remaining.is_empty());
     }
 
     #[test]
-    fn from_parsed_ok() {
-        let dict = vec![bencode_elem!({
-            ("announce", "url"),
+    fn from_fastresume_bytes_info_and_metadata() {
+        let resume = bencode_elem!({
             ("info", {
                 ("name", "??"),
                 ("length", 2),
                 ("piece length", 2),
                 (
                     "pieces",
-                    (0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09,
+                    (0xff, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09,
                         0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f, 0x10, 0x11, 0x12, 0x13)
                 ),
             }),
-        })];
+            ("metadata", "unused"),
+        });
 
+        // `metadata` is not used, so it is left in the remainder
+        let (torrent, remaining) = Torrent::from_fastresume_bytes(&resume.encode()).unwrap();
+        assert_eq!(torrent.name, "??");
         assert_eq!(
-            Torrent::from_parsed(dict).unwrap(),
-            Torrent {
-                announce: Some("url".to_owned()),
-                announce_list: None,
-                length: 2,
-                files: None,
-                name: "??".to_owned(),
-                piece_length: 2,
-                pieces: vec![vec![
-                    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c,
-                    0x0d, 0x0e, 0x0f, 0x10, 0x11, 0x12, 0x13,
-                ]],
-                extra_fields: None,
-                extra_info_fields: None,
-            }
+            remaining,
+            HashMap::from_iter(vec![("metadata".to_owned(), bencode_elem!("unused"))])
         );
     }
 
     #[test]
-    fn from_parsed_top_level_multiple_elem() {
-        let dict = vec![bencode_elem!({}), bencode_elem!([])];
+    fn from_fastresume_bytes_info_hash_mismatch() {
+        let resume = bencode_elem!({
+            ("info", {
+                ("name", "??"),
+                ("length", 2),
+                ("piece length", 2),
+                (
+                    "pieces",
+                    (0xff, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09,
+                        0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f, 0x10, 0x11, 0x12, 0x13)
+                ),
+            }),
+            ("info-hash", (0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+                0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff)),
+        });
 
-        match Torrent::from_parsed(dict) {
+        match Torrent::from_fastresume_bytes(&resume.encode()) {
             Err(LavaTorrentError::MalformedTorrent(m)) => assert_eq!(
                 m,
-                "Torrent should contain 1 and only 1 top-level element, 2 found."
+                r#""info-hash" does not match the hash of the embedded "info"."#
             ),
             _ => panic!(),
         }
     }
 
     #[test]
-    fn from_parsed_top_level_no_elem() {
-        let dict = Vec::new();
+    fn from_fastresume_bytes_info_missing() {
+        let resume = bencode_elem!({ ("save_path", "/downloads") });
 
-        match Torrent::from_parsed(dict) {
-            Err(LavaTorrentError::MalformedTorrent(m)) => assert_eq!(
-                m,
-                "Torrent should contain 1 and only 1 top-level element, 0 found."
-            ),
+        match Torrent::from_fastresume_bytes(&resume.encode()) {
+            Err(LavaTorrentError::MalformedTorrent(m)) => {
+                assert_eq!(m, r#"Neither "info" nor "metadata" exists."#);
+            }
             _ => panic!(),
         }
     }
 
     #[test]
-    fn from_parsed_top_level_not_dict() {
-        let dict = vec![bencode_elem!([])];
+    fn extract_announce_ok() {
+        let mut dict = HashMap::from_iter(vec![("announce".to_owned(), bencode_elem!("url"))]);
 
-        match Torrent::from_parsed(dict) {
-            Err(LavaTorrentError::MalformedTorrent(m)) => {
-                assert_eq!(m, "Torrent's top-level element is not a dictionary.");
-            }
-            _ => panic!(),
-        }
+        assert_eq!(
+            Torrent::extract_announce(&mut dict, &ReadOptions::default(), &mut Vec::new()).unwrap(),
+            Some("url".to_owned()),
+        );
     }
 
     #[test]
-    fn from_parsed_info_missing() {
-        // "announce" is needed here because it is parsed before "info"
-        // missing "announce-list" is fine as that won't trigger an error
-        let dict = vec![bencode_elem!({ ("announce", "url") })];
+    fn extract_announce_missing() {
+        let mut dict = HashMap::new();
 
-        match Torrent::from_parsed(dict) {
-            Err(LavaTorrentError::MalformedTorrent(m)) => {
-                assert_eq!(m, r#""info" does not exist."#);
-            }
-            _ => panic!(),
-        }
+        assert_eq!(
+            Torrent::extract_announce(&mut dict, &ReadOptions::default(), &mut Vec::new()).unwrap(),
+            None,
+        );
     }
 
     #[test]
-    fn from_parsed_info_not_dict() {
-        // "announce" is needed here because it is parsed before "info"
-        // missing "announce-list" is fine as that won't trigger an error
-        let parsed = vec![bencode_elem!({ ("announce", "url"), ("info", []) })];
+    fn extract_announce_not_string() {
+        let mut dict = HashMap::from_iter(vec![("announce".to_owned(), bencode_elem!(42))]);
 
-        match Torrent::from_parsed(parsed) {
+        match Torrent::extract_announce(&mut dict, &ReadOptions::default(), &mut Vec::new()) {
             Err(LavaTorrentError::MalformedTorrent(m)) => {
-                assert_eq!(m, r#""info" is not a dictionary."#);
+                assert_eq!(m, r#""announce" maps to Integer, expected String."#)
             }
             _ => panic!(),
         }
     }
 
     #[test]
-    fn extract_announce_ok() {
-        let mut dict =
-            HashMap::from_iter(vec![("announce".to_owned(), bencode_elem!("url"))].into_iter());
+    fn extract_announce_not_utf8() {
+        let mut dict = HashMap::from_iter(vec![(
+            "announce".to_owned(),
+            BencodeElem::Bytes(b"\xffurl".to_vec()),
+        )]);
 
-        assert_eq!(
-            Torrent::extract_announce(&mut dict).unwrap(),
-            Some("url".to_owned()),
-        );
+        match Torrent::extract_announce(&mut dict, &ReadOptions::default(), &mut Vec::new()) {
+            Err(LavaTorrentError::MalformedTorrent(m)) => assert_eq!(
+                m,
+                r#""announce" maps to a byte string that is not valid UTF-8 (4 bytes, starts with 0xff75726c)."#
+            ),
+            _ => panic!(),
+        }
     }
 
     #[test]
-    fn extract_announce_missing() {
-        let mut dict = HashMap::new();
+    fn extract_announce_not_utf8_lossy() {
+        let mut dict = HashMap::from_iter(vec![(
+            "announce".to_owned(),
+            BencodeElem::Bytes(b"\xffurl".to_vec()),
+        )]);
+        let options = ReadOptions {
+            lossy_utf8: true,
+            ..ReadOptions::default()
+        };
+        let mut warnings = Vec::new();
 
-        assert_eq!(Torrent::extract_announce(&mut dict).unwrap(), None,);
+        assert_eq!(
+            Torrent::extract_announce(&mut dict, &options, &mut warnings).unwrap(),
+            Some("\u{fffd}url".to_owned()),
+        );
+        assert_eq!(warnings, vec![ReadWarning::LossyUtf8 { field: "announce" }]);
     }
 
     #[test]
-    fn extract_announce_not_string() {
-        let mut dict = HashMap::from_iter(
-            vec![(
-                "announce".to_owned(),
-                BencodeElem::Bytes("url".as_bytes().to_vec()),
-            )]
-            .into_iter(),
-        );
+    fn extract_announce_not_string_lossy() {
+        let mut dict = HashMap::from_iter(vec![("announce".to_owned(), bencode_elem!(42))]);
+        let options = ReadOptions {
+            lossy_utf8: true,
+            ..ReadOptions::default()
+        };
+        let mut warnings = Vec::new();
 
-        match Torrent::extract_announce(&mut dict) {
-            Err(LavaTorrentError::MalformedTorrent(m)) => assert_eq!(
-                m,
-                r#""announce" does not map to a string (or maps to invalid UTF8)."#
-            ),
+        match Torrent::extract_announce(&mut dict, &options, &mut warnings) {
+            Err(LavaTorrentError::MalformedTorrent(m)) => {
+                assert_eq!(m, r#""announce" maps to Integer, expected String."#)
+            }
             _ => panic!(),
         }
+        assert!(warnings.is_empty());
     }
 
     #[test]
@@ -864,7 +2398,8 @@ mod torrent_read_tests {
         let tier = bencode_elem!(["url1", "url2"]);
 
         assert_eq!(
-            Torrent::extract_announce_list_tier(tier).unwrap(),
+            Torrent::extract_announce_list_tier(tier, &ReadOptions::default(), &mut Vec::new())
+                .unwrap(),
             vec!["url1".to_owned(), "url2".to_owned()]
         );
     }
@@ -872,9 +2407,12 @@ mod torrent_read_tests {
     #[test]
     fn extract_announce_list_tier_not_list() {
         let tier = bencode_elem!({});
-        match Torrent::extract_announce_list_tier(tier) {
+        match Torrent::extract_announce_list_tier(tier, &ReadOptions::default(), &mut Vec::new()) {
             Err(LavaTorrentError::MalformedTorrent(m)) => {
-                assert_eq!(m, r#""announce-list" contains a non-list element."#);
+                assert_eq!(
+                    m,
+                    r#""announce-list" contains a Dictionary element, expected List."#
+                );
             }
             _ => panic!(),
         }
@@ -882,32 +2420,75 @@ mod torrent_read_tests {
 
     #[test]
     fn extract_announce_list_tier_url_not_string() {
+        let tier = bencode_elem!(["url1", 2]);
+
+        match Torrent::extract_announce_list_tier(tier, &ReadOptions::default(), &mut Vec::new()) {
+            Err(LavaTorrentError::MalformedTorrent(m)) => assert_eq!(
+                m,
+                r#"A tier within "announce-list" contains a Integer element, expected String."#
+            ),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn extract_announce_list_tier_url_not_utf8() {
         let tier = BencodeElem::List(vec![
             bencode_elem!("url1"),
-            BencodeElem::Bytes("url2".as_bytes().to_vec()),
+            BencodeElem::Bytes(b"url2\xfe\xff http://example.com".to_vec()),
         ]);
 
-        match Torrent::extract_announce_list_tier(tier) {
+        match Torrent::extract_announce_list_tier(tier, &ReadOptions::default(), &mut Vec::new()) {
             Err(LavaTorrentError::MalformedTorrent(m)) => assert_eq!(
                 m,
-                r#"A tier within "announce-list" contains a non-string element."#
+                r#"A tier within "announce-list" contains a byte string that is not valid UTF-8 (25 bytes, starts with 0x75726c32feff2068)."#
             ),
             _ => panic!(),
         }
     }
 
     #[test]
-    fn extract_announce_list_ok() {
-        let mut dict = HashMap::from_iter(
-            vec![(
-                "announce-list".to_owned(),
-                bencode_elem!([["url1", "url2"], ["url3", "url4"]]),
-            )]
-            .into_iter(),
+    fn extract_announce_list_tier_url_not_utf8_lossy() {
+        let tier = BencodeElem::List(vec![
+            BencodeElem::Bytes(b"url1\xff".to_vec()),
+            bencode_elem!("url2"),
+            BencodeElem::Bytes(b"url3\xff".to_vec()),
+        ]);
+        let options = ReadOptions {
+            lossy_utf8: true,
+            ..ReadOptions::default()
+        };
+        let mut warnings = Vec::new();
+
+        assert_eq!(
+            Torrent::extract_announce_list_tier(tier, &options, &mut warnings).unwrap(),
+            vec![
+                "url1\u{fffd}".to_owned(),
+                "url2".to_owned(),
+                "url3\u{fffd}".to_owned()
+            ]
         );
+        assert_eq!(
+            warnings,
+            vec![
+                ReadWarning::LossyUtf8 {
+                    field: "announce-list"
+                };
+                2
+            ]
+        );
+    }
+
+    #[test]
+    fn extract_announce_list_ok() {
+        let mut dict = HashMap::from_iter(vec![(
+            "announce-list".to_owned(),
+            bencode_elem!([["url1", "url2"], ["url3", "url4"]]),
+        )]);
 
         assert_eq!(
-            Torrent::extract_announce_list(&mut dict).unwrap(),
+            Torrent::extract_announce_list(&mut dict, &ReadOptions::default(), &mut Vec::new())
+                .unwrap(),
             Some(vec![
                 vec!["url1".to_owned(), "url2".to_owned()],
                 vec!["url3".to_owned(), "url4".to_owned()],
@@ -918,16 +2499,20 @@ mod torrent_read_tests {
     #[test]
     fn extract_announce_list_missing() {
         let mut dict = HashMap::new();
-        assert_eq!(Torrent::extract_announce_list(&mut dict).unwrap(), None);
+        assert_eq!(
+            Torrent::extract_announce_list(&mut dict, &ReadOptions::default(), &mut Vec::new())
+                .unwrap(),
+            None
+        );
     }
 
     #[test]
     fn extract_announce_list_not_list() {
         let mut dict = HashMap::from_iter(vec![("announce-list".to_owned(), bencode_elem!({}))]);
 
-        match Torrent::extract_announce_list(&mut dict) {
+        match Torrent::extract_announce_list(&mut dict, &ReadOptions::default(), &mut Vec::new()) {
             Err(LavaTorrentError::MalformedTorrent(m)) => {
-                assert_eq!(m, r#""announce-list" does not map to a list."#);
+                assert_eq!(m, r#""announce-list" maps to Dictionary, expected List."#);
             }
             _ => panic!(),
         }
@@ -935,25 +2520,24 @@ mod torrent_read_tests {
 
     #[test]
     fn extract_files_ok() {
-        let mut dict = HashMap::from_iter(
-            vec![(
-                "files".to_owned(),
-                bencode_elem!([{
-                    ("length", 42),
-                    ("path", ["root", ".bashrc"]),
-                    ("comment", "no comment"),
-                }]),
-            )]
-            .into_iter(),
-        );
-
-        let files = Torrent::extract_files(&mut dict).unwrap().unwrap();
+        let mut dict = HashMap::from_iter(vec![(
+            "files".to_owned(),
+            bencode_elem!([{
+                ("length", 42),
+                ("path", ["root", ".bashrc"]),
+                ("comment", "no comment"),
+            }]),
+        )]);
+
+        let files = Torrent::extract_files(&mut dict, &ReadOptions::default())
+            .unwrap()
+            .unwrap();
         assert_eq!(files.len(), 1);
         assert_eq!(
             files[0],
             File {
                 length: 42,
-                path: PathBuf::from("root/.bashrc"),
+                path: vec!["root".to_owned(), ".bashrc".to_owned()],
                 extra_fields: Some(HashMap::from_iter(
                     vec![("comment".to_owned(), bencode_elem!("no comment"))].into_iter()
                 )),
@@ -965,9 +2549,9 @@ mod torrent_read_tests {
     fn extract_files_not_list() {
         let mut dict = HashMap::from_iter(vec![("files".to_owned(), bencode_elem!({}))]);
 
-        match Torrent::extract_files(&mut dict) {
+        match Torrent::extract_files(&mut dict, &ReadOptions::default()) {
             Err(LavaTorrentError::MalformedTorrent(m)) => {
-                assert_eq!(m, r#""files" does not map to a list."#);
+                assert_eq!(m, r#""files" maps to Dictionary, expected List."#);
             }
             _ => panic!(),
         }
@@ -976,14 +2560,17 @@ mod torrent_read_tests {
     #[test]
     fn extract_files_missing() {
         let mut dict = HashMap::new();
-        assert_eq!(Torrent::extract_files(&mut dict).unwrap(), None);
+        assert_eq!(
+            Torrent::extract_files(&mut dict, &ReadOptions::default()).unwrap(),
+            None
+        );
     }
 
     #[test]
     fn extract_files_empty_list() {
         let mut dict = HashMap::from_iter(vec![("files".to_owned(), bencode_elem!([]))]);
 
-        match Torrent::extract_files(&mut dict) {
+        match Torrent::extract_files(&mut dict, &ReadOptions::default()) {
             Err(LavaTorrentError::MalformedTorrent(m)) => {
                 assert_eq!(m, r#""files" maps to an empty list."#);
             }
@@ -992,23 +2579,78 @@ mod torrent_read_tests {
     }
 
     #[test]
-    fn extract_length_ok() {
+    fn extract_files_max_files() {
+        let options = ReadOptions {
+            max_files: 2,
+            ..ReadOptions::default()
+        };
+        let file = bencode_elem!({ ("length", 1), ("path", ["a"]) });
+
+        let mut dict = HashMap::from_iter(vec![(
+            "files".to_owned(),
+            BencodeElem::List(vec![file.clone(); 2]),
+        )]);
+        assert_eq!(
+            Torrent::extract_files(&mut dict, &options)
+                .unwrap()
+                .unwrap()
+                .len(),
+            2
+        );
+
         let mut dict =
-            HashMap::from_iter(vec![("length".to_owned(), bencode_elem!(42))].into_iter());
-        assert_eq!(Torrent::extract_length(&mut dict, &None).unwrap(), 42);
+            HashMap::from_iter(vec![("files".to_owned(), BencodeElem::List(vec![file; 3]))]);
+        match Torrent::extract_files(&mut dict, &options) {
+            Err(LavaTorrentError::MalformedTorrent(m)) => {
+                assert_eq!(m, r#""files" has 3 entries, exceeding max_files (2)."#)
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn extract_files_reports_index() {
+        let options = ReadOptions {
+            max_path_components: 1,
+            ..ReadOptions::default()
+        };
+        let mut dict = HashMap::from_iter(vec![(
+            "files".to_owned(),
+            bencode_elem!([
+                { ("length", 1), ("path", ["a"]) },
+                { ("length", 1), ("path", ["a", "b"]) },
+            ]),
+        )]);
+
+        match Torrent::extract_files(&mut dict, &options) {
+            Err(LavaTorrentError::MalformedTorrent(m)) => assert_eq!(
+                m,
+                r#""path" of file 1 has 2 components, exceeding max_path_components (1)."#
+            ),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn extract_length_ok() {
+        let mut dict = HashMap::from_iter(vec![("length".to_owned(), bencode_elem!(42))]);
+        assert_eq!(
+            Torrent::extract_length(&mut dict, &None, &ReadOptions::default(), &mut Vec::new())
+                .unwrap(),
+            42
+        );
     }
 
     #[test]
     fn extract_length_conflict_with_files() {
-        let mut dict =
-            HashMap::from_iter(vec![("length".to_owned(), bencode_elem!(42))].into_iter());
+        let mut dict = HashMap::from_iter(vec![("length".to_owned(), bencode_elem!(42))]);
         let files = Some(vec![File {
             length: 100,
-            path: PathBuf::new(),
+            path: Vec::new(),
             extra_fields: None,
         }]);
 
-        match Torrent::extract_length(&mut dict, &files) {
+        match Torrent::extract_length(&mut dict, &files, &ReadOptions::default(), &mut Vec::new()) {
             Err(LavaTorrentError::MalformedTorrent(m)) => {
                 assert_eq!(m, r#"Both "length" and "files" exist."#);
             }
@@ -1018,12 +2660,11 @@ mod torrent_read_tests {
 
     #[test]
     fn extract_length_not_integer() {
-        let mut dict =
-            HashMap::from_iter(vec![("length".to_owned(), bencode_elem!("42"))].into_iter());
+        let mut dict = HashMap::from_iter(vec![("length".to_owned(), bencode_elem!("42"))]);
 
-        match Torrent::extract_length(&mut dict, &None) {
+        match Torrent::extract_length(&mut dict, &None, &ReadOptions::default(), &mut Vec::new()) {
             Err(LavaTorrentError::MalformedTorrent(m)) => {
-                assert_eq!(m, r#""length" does not map to an integer."#);
+                assert_eq!(m, r#""length" maps to String, expected Integer."#);
             }
             _ => panic!(),
         }
@@ -1033,7 +2674,7 @@ mod torrent_read_tests {
     fn extract_length_missing_no_files() {
         let mut dict = HashMap::new();
 
-        match Torrent::extract_length(&mut dict, &None) {
+        match Torrent::extract_length(&mut dict, &None, &ReadOptions::default(), &mut Vec::new()) {
             Err(LavaTorrentError::MalformedTorrent(m)) => {
                 assert_eq!(m, r#"Neither "length" nor "files" exists."#);
             }
@@ -1041,16 +2682,53 @@ mod torrent_read_tests {
         }
     }
 
+    #[test]
+    fn extract_length_conflict_with_files_allowed() {
+        let mut dict = HashMap::from_iter(vec![("length".to_owned(), bencode_elem!(42))]);
+        let files = Some(vec![File {
+            length: 100,
+            path: Vec::new(),
+            extra_fields: None,
+        }]);
+        let options = ReadOptions {
+            allow_length_with_files: true,
+            ..ReadOptions::default()
+        };
+        let mut warnings = Vec::new();
+
+        assert_eq!(
+            Torrent::extract_length(&mut dict, &files, &options, &mut warnings).unwrap(),
+            100
+        );
+        // left for `extra_info_fields`
+        assert_eq!(dict.get("length"), Some(&bencode_elem!(42)));
+        assert_eq!(
+            warnings,
+            vec![ReadWarning::LengthWithFiles {
+                declared: 42,
+                computed: 100,
+            }]
+        );
+        assert_eq!(
+            warnings[0].to_string(),
+            r#""length" ([42]) ignored in favor of the sum of "files" ([100])"#
+        );
+    }
+
     #[test]
     fn extract_length_missing_have_files() {
         let mut dict = HashMap::new();
         let files = Some(vec![File {
             length: 100,
-            path: PathBuf::new(),
+            path: Vec::new(),
             extra_fields: None,
         }]);
 
-        assert_eq!(Torrent::extract_length(&mut dict, &files).unwrap(), 100);
+        assert_eq!(
+            Torrent::extract_length(&mut dict, &files, &ReadOptions::default(), &mut Vec::new())
+                .unwrap(),
+            100
+        );
     }
 
     #[test]
@@ -1059,17 +2737,17 @@ mod torrent_read_tests {
         let files = Some(vec![
             File {
                 length: 1,
-                path: PathBuf::new(),
+                path: Vec::new(),
                 extra_fields: None,
             },
             File {
-                length: i64::max_value(),
-                path: PathBuf::new(),
+                length: i64::MAX,
+                path: Vec::new(),
                 extra_fields: None,
             },
         ]);
 
-        match Torrent::extract_length(&mut dict, &files) {
+        match Torrent::extract_length(&mut dict, &files, &ReadOptions::default(), &mut Vec::new()) {
             Err(LavaTorrentError::MalformedTorrent(m)) => {
                 assert_eq!(m, r#"Torrent's length overflowed in i64."#);
             }
@@ -1079,39 +2757,66 @@ mod torrent_read_tests {
 
     #[test]
     fn extract_name_ok() {
-        let mut dict =
-            HashMap::from_iter(vec![("name".to_owned(), bencode_elem!("not name"))].into_iter());
+        let mut dict = HashMap::from_iter(vec![("name".to_owned(), bencode_elem!("not name"))]);
 
         assert_eq!(
-            Torrent::extract_name(&mut dict).unwrap(),
+            Torrent::extract_name(&mut dict, &ReadOptions::default(), &mut Vec::new()).unwrap(),
             "not name".to_owned()
         );
     }
 
     #[test]
     fn extract_name_not_string() {
-        let mut dict = HashMap::from_iter(
-            vec![(
-                "name".to_owned(),
-                BencodeElem::Bytes("not name".as_bytes().to_vec()),
-            )]
-            .into_iter(),
-        );
+        let mut dict = HashMap::from_iter(vec![("name".to_owned(), bencode_elem!(["not name"]))]);
+
+        match Torrent::extract_name(&mut dict, &ReadOptions::default(), &mut Vec::new()) {
+            Err(LavaTorrentError::MalformedTorrent(m)) => {
+                assert_eq!(m, r#""name" maps to List, expected String."#)
+            }
+            _ => panic!(),
+        }
+    }
 
-        match Torrent::extract_name(&mut dict) {
+    #[test]
+    fn extract_name_not_utf8() {
+        let mut dict = HashMap::from_iter(vec![(
+            "name".to_owned(),
+            BencodeElem::Bytes(b"\xc3\x28name".to_vec()),
+        )]);
+
+        match Torrent::extract_name(&mut dict, &ReadOptions::default(), &mut Vec::new()) {
             Err(LavaTorrentError::MalformedTorrent(m)) => assert_eq!(
                 m,
-                r#""name" does not map to a string (or maps to invalid UTF8)."#
+                r#""name" maps to a byte string that is not valid UTF-8 (6 bytes, starts with 0xc3286e616d65)."#
             ),
             _ => panic!(),
         }
     }
 
+    #[test]
+    fn extract_name_not_utf8_lossy() {
+        let mut dict = HashMap::from_iter(vec![(
+            "name".to_owned(),
+            BencodeElem::Bytes(b"\xc3\x28name".to_vec()),
+        )]);
+        let options = ReadOptions {
+            lossy_utf8: true,
+            ..ReadOptions::default()
+        };
+        let mut warnings = Vec::new();
+
+        assert_eq!(
+            Torrent::extract_name(&mut dict, &options, &mut warnings).unwrap(),
+            "\u{fffd}(name"
+        );
+        assert_eq!(warnings, vec![ReadWarning::LossyUtf8 { field: "name" }]);
+    }
+
     #[test]
     fn extract_name_missing() {
         let mut dict = HashMap::new();
 
-        match Torrent::extract_name(&mut dict) {
+        match Torrent::extract_name(&mut dict, &ReadOptions::default(), &mut Vec::new()) {
             Err(LavaTorrentError::MalformedTorrent(m)) => {
                 assert_eq!(m, r#""name" does not exist."#);
             }
@@ -1121,19 +2826,17 @@ mod torrent_read_tests {
 
     #[test]
     fn extract_piece_length_ok() {
-        let mut dict =
-            HashMap::from_iter(vec![("piece length".to_owned(), bencode_elem!(1))].into_iter());
+        let mut dict = HashMap::from_iter(vec![("piece length".to_owned(), bencode_elem!(1))]);
         assert_eq!(Torrent::extract_piece_length(&mut dict).unwrap(), 1);
     }
 
     #[test]
     fn extract_piece_length_not_integer() {
-        let mut dict =
-            HashMap::from_iter(vec![("piece length".to_owned(), bencode_elem!("1"))].into_iter());
+        let mut dict = HashMap::from_iter(vec![("piece length".to_owned(), bencode_elem!("1"))]);
 
         match Torrent::extract_piece_length(&mut dict) {
             Err(LavaTorrentError::MalformedTorrent(m)) => {
-                assert_eq!(m, r#""piece length" does not map to an integer."#);
+                assert_eq!(m, r#""piece length" maps to String, expected Integer."#);
             }
             _ => panic!(),
         }
@@ -1153,8 +2856,7 @@ mod torrent_read_tests {
 
     #[test]
     fn extract_piece_length_not_positive() {
-        let mut dict =
-            HashMap::from_iter(vec![("piece length".to_owned(), bencode_elem!(0))].into_iter());
+        let mut dict = HashMap::from_iter(vec![("piece length".to_owned(), bencode_elem!(0))]);
 
         match Torrent::extract_piece_length(&mut dict) {
             Err(LavaTorrentError::MalformedTorrent(m)) => {
@@ -1166,47 +2868,123 @@ mod torrent_read_tests {
 
     #[test]
     fn extract_pieces_ok() {
-        let mut dict = HashMap::from_iter(
-            vec![(
-                "pieces".to_owned(),
-                BencodeElem::Bytes(vec![
-                    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c,
-                    0x0d, 0x0e, 0x0f, 0x10, 0x11, 0x12, 0x13,
-                ]),
-            )]
-            .into_iter(),
-        );
+        let mut dict = HashMap::from_iter(vec![(
+            "pieces".to_owned(),
+            BencodeElem::Bytes(vec![
+                0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d,
+                0x0e, 0x0f, 0x10, 0x11, 0x12, 0x13,
+            ]),
+        )]);
 
-        let pieces = Torrent::extract_pieces(&mut dict).unwrap();
+        let pieces =
+            Torrent::extract_pieces(&mut dict, &ReadOptions::default(), &mut Vec::new()).unwrap();
         assert_eq!(pieces.len(), 1);
-        assert_eq!(pieces[0].len(), PIECE_STRING_LENGTH);
         assert_eq!(
             pieces[0],
-            vec![
+            [
                 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d,
                 0x0e, 0x0f, 0x10, 0x11, 0x12, 0x13,
             ]
         );
     }
 
+    #[test]
+    fn extract_pieces_string() {
+        let mut dict = HashMap::from_iter(vec![(
+            "pieces".to_owned(),
+            bencode_elem!("abcdefghijklmnopqrst"),
+        )]);
+
+        let pieces =
+            Torrent::extract_pieces(&mut dict, &ReadOptions::default(), &mut Vec::new()).unwrap();
+        assert_eq!(pieces, vec![*b"abcdefghijklmnopqrst"]);
+    }
+
     #[test]
     fn extract_pieces_not_bytes() {
-        let mut dict =
-            HashMap::from_iter(vec![("pieces".to_owned(), bencode_elem!("???"))].into_iter());
+        let mut dict = HashMap::from_iter(vec![("pieces".to_owned(), bencode_elem!(42))]);
+
+        match Torrent::extract_pieces(&mut dict, &ReadOptions::default(), &mut Vec::new()) {
+            Err(LavaTorrentError::MalformedTorrent(m)) => {
+                assert_eq!(m, r#""pieces" maps to Integer, expected Bytes."#);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn extract_pieces_list_ok() {
+        let options = ReadOptions {
+            allow_pieces_list: true,
+            ..ReadOptions::default()
+        };
+        let mut warnings = Vec::new();
+        let mut dict = HashMap::from_iter(vec![(
+            "pieces".to_owned(),
+            BencodeElem::List(vec![
+                bencode_elem!("abcdefghijklmnopqrst"),
+                BencodeElem::Bytes(vec![0; 20]),
+            ]),
+        )]);
+
+        let pieces = Torrent::extract_pieces(&mut dict, &options, &mut warnings).unwrap();
+        assert_eq!(pieces, vec![*b"abcdefghijklmnopqrst", [0; 20]]);
+        assert_eq!(warnings, vec![ReadWarning::PiecesList { count: 2 }]);
+    }
+
+    #[test]
+    fn extract_pieces_list_strict() {
+        let mut dict = HashMap::from_iter(vec![(
+            "pieces".to_owned(),
+            bencode_elem!(["abcdefghijklmnopqrst"]),
+        )]);
 
-        match Torrent::extract_pieces(&mut dict) {
+        match Torrent::extract_pieces(&mut dict, &ReadOptions::default(), &mut Vec::new()) {
             Err(LavaTorrentError::MalformedTorrent(m)) => {
-                assert_eq!(m, r#""pieces" does not map to a sequence of bytes."#);
+                assert_eq!(m, r#""pieces" maps to List, expected Bytes."#);
             }
             _ => panic!(),
         }
     }
 
+    #[test]
+    fn extract_pieces_list_invalid() {
+        let options = ReadOptions {
+            allow_pieces_list: true,
+            ..ReadOptions::default()
+        };
+        let cases = vec![
+            (bencode_elem!([]), r#""pieces" maps to an empty sequence."#),
+            (
+                bencode_elem!(["abcdefghijklmnopqrst", "abc"]),
+                r#""pieces"[1] is 3 bytes long, expected 20."#,
+            ),
+            (
+                BencodeElem::List(vec![BencodeElem::Bytes(vec![0; 21])]),
+                r#""pieces"[0] is 21 bytes long, expected 20."#,
+            ),
+            (
+                bencode_elem!(["abcdefghijklmnopqrst", 42]),
+                r#""pieces"[1] maps to Integer, expected Bytes."#,
+            ),
+        ];
+
+        for (pieces, expected) in cases {
+            let mut warnings = Vec::new();
+            let mut dict = HashMap::from_iter(vec![("pieces".to_owned(), pieces)]);
+            match Torrent::extract_pieces(&mut dict, &options, &mut warnings) {
+                Err(LavaTorrentError::MalformedTorrent(m)) => assert_eq!(m, expected),
+                _ => panic!(),
+            }
+            assert!(warnings.is_empty());
+        }
+    }
+
     #[test]
     fn extract_pieces_missing() {
         let mut dict = HashMap::new();
 
-        match Torrent::extract_pieces(&mut dict) {
+        match Torrent::extract_pieces(&mut dict, &ReadOptions::default(), &mut Vec::new()) {
             Err(LavaTorrentError::MalformedTorrent(m)) => {
                 assert_eq!(m, r#""pieces" does not exist."#);
             }
@@ -1216,10 +2994,9 @@ mod torrent_read_tests {
 
     #[test]
     fn extract_pieces_empty() {
-        let mut dict =
-            HashMap::from_iter(vec![("pieces".to_owned(), bencode_elem!(()))].into_iter());
+        let mut dict = HashMap::from_iter(vec![("pieces".to_owned(), bencode_elem!(()))]);
 
-        match Torrent::extract_pieces(&mut dict) {
+        match Torrent::extract_pieces(&mut dict, &ReadOptions::default(), &mut Vec::new()) {
             Err(LavaTorrentError::MalformedTorrent(m)) => {
                 assert_eq!(m, r#""pieces" maps to an empty sequence."#);
             }
@@ -1229,18 +3006,15 @@ mod torrent_read_tests {
 
     #[test]
     fn extract_pieces_invalid_length() {
-        let mut dict = HashMap::from_iter(
-            vec![(
-                "pieces".to_owned(),
-                BencodeElem::Bytes(vec![
-                    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c,
-                    0x0d, 0x0e, 0x0f, 0x10, 0x11, 0x12,
-                ]),
-            )]
-            .into_iter(),
-        );
+        let mut dict = HashMap::from_iter(vec![(
+            "pieces".to_owned(),
+            BencodeElem::Bytes(vec![
+                0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d,
+                0x0e, 0x0f, 0x10, 0x11, 0x12,
+            ]),
+        )]);
 
-        match Torrent::extract_pieces(&mut dict) {
+        match Torrent::extract_pieces(&mut dict, &ReadOptions::default(), &mut Vec::new()) {
             Err(LavaTorrentError::MalformedTorrent(m)) => assert_eq!(
                 m,
                 format!(
@@ -1252,6 +3026,41 @@ mod torrent_read_tests {
         }
     }
 
+    #[test]
+    fn extract_private_ok() {
+        let mut dict = HashMap::from_iter(vec![("private".to_owned(), bencode_elem!(1))]);
+
+        assert_eq!(Torrent::extract_private(&mut dict), Some(true));
+        assert!(dict.is_empty());
+    }
+
+    #[test]
+    fn extract_private_zero() {
+        let mut dict = HashMap::from_iter(vec![("private".to_owned(), bencode_elem!(0))]);
+
+        assert_eq!(Torrent::extract_private(&mut dict), Some(false));
+        assert!(dict.is_empty());
+    }
+
+    #[test]
+    fn extract_private_missing() {
+        let mut dict = HashMap::new();
+
+        assert_eq!(Torrent::extract_private(&mut dict), None);
+    }
+
+    #[test]
+    fn extract_private_other_value() {
+        // left untouched so that the info hash is preserved
+        let mut dict = HashMap::from_iter(vec![("private".to_owned(), bencode_elem!(2))]);
+
+        assert_eq!(Torrent::extract_private(&mut dict), None);
+        assert_eq!(
+            dict,
+            HashMap::from_iter(vec![("private".to_owned(), bencode_elem!(2))])
+        );
+    }
+
     #[test]
     fn extract_extra_fields_ok() {
         assert_eq!(
@@ -1,7 +1,12 @@
 use super::*;
+use crate::bencode::write::{self, WriteFileOptions};
 use crate::bencode::BencodeElem;
 use crate::LavaTorrentError;
-use std::io::{BufWriter, Write};
+use std::fs::OpenOptions;
+use std::io::{ErrorKind, Write};
+
+// not allowed in file names on Windows (`/` is not allowed anywhere)
+const UNSAFE_FILE_NAME_CHARS: &str = r#"/\<>:"|?*"#;
 
 impl File {
     pub(crate) fn into_bencode_elem(self) -> BencodeElem {
@@ -10,12 +15,7 @@ impl File {
         result.insert("length".to_owned(), BencodeElem::Integer(self.length));
         result.insert(
             "path".to_owned(),
-            BencodeElem::List(
-                self.path
-                    .iter()
-                    .map(|component| BencodeElem::String(component.to_string_lossy().into_owned()))
-                    .collect(),
-            ),
+            BencodeElem::List(self.path.into_iter().map(BencodeElem::String).collect()),
         );
 
         if let Some(extra_fields) = self.extra_fields {
@@ -27,26 +27,31 @@ impl File {
 }
 
 impl Torrent {
-    /// Encode `self` as bencode and write the result to `dst`.
-    pub fn write_into<W>(self, dst: &mut W) -> Result<(), LavaTorrentError>
-    where
-        W: Write,
-    {
+    pub(crate) fn into_bencode_elem(self) -> BencodeElem {
+        let mut result = self.root_without_info();
+        result
+            .entry("info".to_owned())
+            .or_insert_with(|| self.construct_info());
+        BencodeElem::Dictionary(result)
+    }
+
+    // The top-level dict, except for `info`.
+    fn root_without_info(&self) -> HashMap<String, BencodeElem> {
         let mut result: HashMap<String, BencodeElem> = HashMap::new();
-        let mut info: HashMap<String, BencodeElem> = HashMap::new();
 
-        if let Some(announce) = self.announce {
-            result.insert("announce".to_owned(), BencodeElem::String(announce));
+        if let Some(ref announce) = self.announce {
+            result.insert("announce".to_owned(), BencodeElem::String(announce.clone()));
         }
 
-        if let Some(list) = self.announce_list {
+        if let Some(ref list) = self.announce_list {
             result.insert(
                 "announce-list".to_owned(),
                 BencodeElem::List(
-                    list.into_iter()
+                    list.iter()
                         .map(|tier| {
                             BencodeElem::List(
-                                tier.into_iter()
+                                tier.iter()
+                                    .cloned()
                                     .map(BencodeElem::String) // url -> string
                                     .collect::<Vec<BencodeElem>>(),
                             )
@@ -56,13 +61,30 @@ impl Torrent {
             );
         }
 
-        if let Some(files) = self.files {
+        if let Some(ref extra_fields) = self.extra_fields {
+            result.extend(extra_fields.clone());
+        }
+
+        result
+    }
+
+    // The `info` dict, except for `pieces`, which can be large and
+    // is thus left to the caller.
+    //
+    // This is the only place where `info` is assembled: `construct_info()`
+    // (and thus `into_bencode_elem()`) and `write_info_streaming()` (and
+    // thus `info_hash()` and `write_into()`) all start from it, so the
+    // info hash always matches what is written.
+    pub(crate) fn info_without_pieces(&self) -> HashMap<String, BencodeElem> {
+        let mut info: HashMap<String, BencodeElem> = HashMap::new();
+
+        if let Some(ref files) = self.files {
             info.insert(
                 "files".to_owned(),
                 BencodeElem::List(
                     files
-                        .into_iter()
-                        .map(|file| file.into_bencode_elem())
+                        .iter()
+                        .map(|file| file.clone().into_bencode_elem())
                         .collect(),
                 ),
             );
@@ -70,27 +92,99 @@ impl Torrent {
             info.insert("length".to_owned(), BencodeElem::Integer(self.length));
         }
 
-        info.insert("name".to_owned(), BencodeElem::String(self.name));
+        info.insert("name".to_owned(), BencodeElem::String(self.name.clone()));
         info.insert(
             "piece length".to_owned(),
             BencodeElem::Integer(self.piece_length),
         );
-        info.insert(
-            "pieces".to_owned(),
-            BencodeElem::Bytes(self.pieces.into_iter().flatten().collect()),
-        );
 
-        if let Some(extra_info_fields) = self.extra_info_fields {
-            info.extend(extra_info_fields);
+        if let Some(ref extra_info_fields) = self.extra_info_fields {
+            info.extend(extra_info_fields.clone());
         }
 
-        result.insert("info".to_owned(), BencodeElem::Dictionary(info));
-
-        if let Some(extra_fields) = self.extra_fields {
-            result.extend(extra_fields);
+        if let Some(private) = self.private {
+            info.insert(
+                "private".to_owned(),
+                BencodeElem::Integer(Integer::from(private)),
+            );
         }
 
-        BencodeElem::Dictionary(result).write_into(dst)
+        info
+    }
+
+    /// Encode the `info` dict of `self` and write the result to `dst`.
+    ///
+    /// Equivalent to `construct_info().write_into(dst)`, except that
+    /// `pieces` is written hash by hash instead of being concatenated
+    /// (and cloned) first.
+    pub(crate) fn write_info_streaming<W>(&self, dst: &mut W) -> Result<(), LavaTorrentError>
+    where
+        W: Write + ?Sized,
+    {
+        write::write_dictionary_with(
+            &self.info_without_pieces(),
+            "pieces",
+            |dst| {
+                write::write_bytes_chunked(
+                    self.pieces.len() * PIECE_STRING_LENGTH,
+                    self.pieces.iter().map(|piece| &piece[..]),
+                    dst,
+                )
+            },
+            dst,
+        )
+    }
+
+    /// Encode `self` as bencode and write the result to `dst`.
+    ///
+    /// `self` is taken by reference, and `pieces` is written hash by
+    /// hash, so nothing large is cloned.
+    pub fn write_into<W>(&self, dst: &mut W) -> Result<(), LavaTorrentError>
+    where
+        W: Write,
+    {
+        // same as `self.into_bencode_elem().write_into(dst)`,
+        // but without concatenating `pieces` into a new buffer
+        write::write_dictionary_with(
+            &self.root_without_info(),
+            "info",
+            |dst| self.write_info_streaming(dst),
+            dst,
+        )
+    }
+
+    /// Calculate the SHA-1 digest of the encoded form of `self`, i.e. of
+    /// what [`encode()`](Self::encode) would return.
+    ///
+    /// This identifies the whole *.torrent* file rather than its `info`
+    /// dict (see [`info_hash()`](Self::info_hash)). The encoded bytes are
+    /// fed to the hasher as they are produced, so they are never held in
+    /// memory as a whole.
+    pub fn encoded_sha1(&self) -> Result<[u8; 20], LavaTorrentError> {
+        Ok(self.encoded_digest()?.into())
+    }
+
+    /// Same as [`encoded_sha1()`](Self::encoded_sha1), but return
+    /// the digest as a hex string.
+    pub fn encoded_sha1_hex(&self) -> Result<String, LavaTorrentError> {
+        Ok(hex::encode(&self.encoded_digest()?))
+    }
+
+    fn encoded_digest(&self) -> Result<sha1::digest::Output<Sha1>, LavaTorrentError> {
+        let mut hasher = Sha1::new();
+        self.write_into(&mut hasher)?;
+        Ok(hasher.finalize())
+    }
+
+    /// Like [`write_into()`](Self::write_into), but calls
+    /// [`revalidate()`](Self::revalidate) first and fails with the
+    /// first problem found, if any.
+    pub fn write_into_validated<W>(self, dst: &mut W) -> Result<(), LavaTorrentError>
+    where
+        W: Write,
+    {
+        self.revalidate_first()?;
+        self.write_into(dst)
     }
 
     /// Encode `self` as bencode and write the result to `path`.
@@ -100,17 +194,162 @@ impl Torrent {
     /// "This function will create a file if it does
     /// not exist, and will truncate it if it does."
     ///
-    /// Note: it is the client's responsibility to ensure
-    /// that all directories in `path` actually exist (e.g.
-    /// by calling [`create_dir_all`](https://doc.rust-lang.org/std/fs/fn.create_dir_all.html)).
+    /// Note: the directories in `path` must already exist, unless
+    /// [`WriteFileOptions::create_parent_dirs`] is set (see
+    /// [`write_into_file_with()`](Self::write_into_file_with)).
+    ///
+    /// Errors name `path` in their message.
     pub fn write_into_file<P>(self, path: P) -> Result<(), LavaTorrentError>
     where
         P: AsRef<Path>,
     {
-        let file = std::fs::File::create(&path)?;
-        self.write_into(&mut BufWriter::new(&file))?;
-        file.sync_all()?;
-        Ok(())
+        self.write_into_file_with(path, WriteFileOptions::default())
+    }
+
+    /// Like [`write_into_file()`](Self::write_into_file), but
+    /// allows controlling how the file is synced and whether missing
+    /// parent directories are created via `options`.
+    pub fn write_into_file_with<P>(
+        self,
+        path: P,
+        options: WriteFileOptions,
+    ) -> Result<(), LavaTorrentError>
+    where
+        P: AsRef<Path>,
+    {
+        write::write_file_with(path, options, |dst| self.write_into(dst))
+    }
+
+    /// Like [`write_into_file_with()`](Self::write_into_file_with), but
+    /// calls [`revalidate()`](Self::revalidate) first and fails with the
+    /// first problem found, if any. `path` is then left untouched.
+    pub fn write_into_file_validated<P>(
+        self,
+        path: P,
+        options: WriteFileOptions,
+    ) -> Result<(), LavaTorrentError>
+    where
+        P: AsRef<Path>,
+    {
+        self.revalidate_first()
+            .map_err(|e| write::with_path_context(e, path.as_ref()))?;
+        self.write_into_file_with(path, options)
+    }
+
+    /// Encode each of `torrents` as bencode and write them one after
+    /// another to `path`, i.e. as a bundle that can be read back with
+    /// [`read_all_from_file()`](Self::read_all_from_file).
+    ///
+    /// `path` is handled in the same way as in
+    /// [`write_into_file()`](Self::write_into_file).
+    pub fn write_all_into_file<P>(torrents: &[Torrent], path: P) -> Result<(), LavaTorrentError>
+    where
+        P: AsRef<Path>,
+    {
+        write::write_file_with(path, WriteFileOptions::default(), |dst| {
+            torrents
+                .iter()
+                .try_for_each(|torrent| torrent.write_into(dst))
+        })
+    }
+
+    /// Return the file name `self` is conventionally saved under,
+    /// i.e. `name` followed by `.torrent`.
+    ///
+    /// Characters that are unsafe in file names on common platforms
+    /// (path separators, `<>:"|?*`, and control/invisible characters, see
+    /// [`SpoofingIssue`]) are replaced with `_`.
+    ///
+    /// [`SpoofingIssue`]: enum.SpoofingIssue.html
+    pub fn default_file_name(&self) -> String {
+        let mut file_name: String = self
+            .name
+            .chars()
+            .map(|c| {
+                if UNSAFE_FILE_NAME_CHARS.contains(c) || hidden_char_issue(0, c).is_some() {
+                    '_'
+                } else {
+                    c
+                }
+            })
+            .collect();
+        file_name.push_str(".torrent");
+        file_name
+    }
+
+    /// Encode `self` as bencode and write the result next to
+    /// `source_path` (i.e. into its parent directory), under
+    /// [`default_file_name()`](Self::default_file_name).
+    ///
+    /// `source_path` is usually the path `self` was built from. If it
+    /// ends in `..`, it is canonicalized first to find its parent.
+    ///
+    /// `overwrite` decides what happens if the file already exists.
+    /// Files are created with `create_new`, so a name is never
+    /// taken from under another process. With
+    /// [`OverwritePolicy::Overwrite`], the file is written under a
+    /// temporary name and then renamed over the existing one, which is
+    /// thus never left half-written. The path actually written to is
+    /// returned.
+    pub fn write_beside<P>(
+        &self,
+        source_path: P,
+        overwrite: OverwritePolicy,
+    ) -> Result<PathBuf, LavaTorrentError>
+    where
+        P: AsRef<Path>,
+    {
+        let source_path = source_path.as_ref();
+        let dir = match source_path.file_name() {
+            Some(_) => source_path.parent().map(Path::to_path_buf),
+            None => source_path.canonicalize()?.parent().map(Path::to_path_buf),
+        }
+        .ok_or_else(|| {
+            LavaTorrentError::InvalidArgument(Cow::Owned(format!(
+                "[{}] has no parent directory.",
+                source_path.display()
+            )))
+        })?;
+        let file_name = self.default_file_name();
+
+        let (path, file) = match overwrite {
+            OverwritePolicy::Overwrite => {
+                let path = dir.join(&file_name);
+                write::replace_file_with(&path, WriteFileOptions::default(), |dst| {
+                    self.write_into(dst)
+                })?;
+                return Ok(path);
+            }
+            OverwritePolicy::Error => {
+                let path = dir.join(&file_name);
+                let file = OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .open(&path)?;
+                (path, file)
+            }
+            OverwritePolicy::RenameWithSuffix => {
+                let stem = file_name.strip_suffix(".torrent").unwrap_or(&file_name);
+                let mut n = 0_u64;
+                loop {
+                    let path = if n == 0 {
+                        dir.join(&file_name)
+                    } else {
+                        dir.join(format!("{} ({}).torrent", stem, n))
+                    };
+                    match OpenOptions::new().write(true).create_new(true).open(&path) {
+                        Ok(file) => break (path, file),
+                        Err(e) if e.kind() == ErrorKind::AlreadyExists => n += 1,
+                        Err(e) => return Err(e.into()),
+                    }
+                }
+            }
+        };
+
+        write::write_opened_file_with(file, WriteFileOptions::default(), |dst| {
+            self.write_into(dst)
+        })?;
+        Ok(path)
     }
 
     /// Encode `self` as bencode and return the result in a `Vec`.
@@ -119,6 +358,104 @@ impl Torrent {
         self.write_into(&mut result)?;
         Ok(result)
     }
+
+    /// Like [`encode()`](Self::encode), but calls
+    /// [`revalidate()`](Self::revalidate) first and fails with the
+    /// first problem found, if any.
+    pub fn encode_validated(self) -> Result<Vec<u8>, LavaTorrentError> {
+        let mut result = Vec::new();
+        self.write_into_validated(&mut result)?;
+        Ok(result)
+    }
+
+    /// Apply `edits` to the top-level fields of the encoded torrent `original`,
+    /// reproducing the rest of `original` byte for byte.
+    ///
+    /// Each edit sets a key to a new value, or removes it if the value is
+    /// `None`. This is meant for archival, where re-encoding a parsed `Torrent`
+    /// would change the file's hash: some historical torrents have their
+    /// top-level keys out of order (which [`read_from_bytes()`] rejects),
+    /// or use non-canonical encodings.
+    ///
+    /// - Existing keys stay where they are, whether or not they are sorted.
+    /// - New keys are inserted right after the greatest existing key that
+    ///   sorts before them (or first, if there is none). For a sorted
+    ///   `original`, this is where sorted order puts them.
+    /// - Edited values are re-encoded, except for their untouched parts
+    ///   (see [`reencode_preserving()`]).
+    ///
+    /// `info` can't be edited, so the info hash never changes. Nested
+    /// dictionaries (including `info`) must still be sorted.
+    ///
+    /// [`read_from_bytes()`]: #method.read_from_bytes
+    /// [`reencode_preserving()`]: ../../bencode/write/fn.reencode_preserving.html
+    pub fn rewrite_preserving_layout(
+        original: &[u8],
+        edits: &[(&str, Option<BencodeElem>)],
+    ) -> Result<Vec<u8>, LavaTorrentError> {
+        if edits.iter().any(|&(key, _)| key == "info") {
+            return Err(LavaTorrentError::InvalidArgument(Cow::Borrowed(
+                r#""info" cannot be edited."#,
+            )));
+        }
+        write::splice_dictionary(original, edits)
+    }
+
+    fn revalidate_first(&self) -> Result<(), LavaTorrentError> {
+        self.revalidate().map_err(|mut errors| errors.remove(0))
+    }
+
+    /// Construct the skeleton of a [libtorrent] fastresume entry for `self`.
+    ///
+    /// Only the minimal keys (`file-format`, `file-version`, `info-hash`,
+    /// `name`, `save_path`, and `trackers`) are emitted. This is enough for
+    /// libtorrent to re-add the torrent magnet-style (i.e. the metadata will
+    /// be fetched from peers). The `info` dict is not included; insert it
+    /// yourself (e.g. with [`construct_info()`]) if you want a complete entry.
+    ///
+    /// `trackers` is built from `self.announce_list` if present, otherwise
+    /// from `self.announce`.
+    ///
+    /// [libtorrent]: https://www.libtorrent.org/manual-ref.html#fast-resume
+    /// [`construct_info()`]: #method.construct_info
+    pub fn to_fastresume_skeleton(&self, save_path: &str) -> BencodeElem {
+        let trackers = if let Some(ref list) = self.announce_list {
+            list.clone()
+        } else if let Some(ref announce) = self.announce {
+            vec![vec![announce.clone()]]
+        } else {
+            Vec::new()
+        };
+
+        let mut result: HashMap<String, BencodeElem> = HashMap::new();
+        result.insert(
+            "file-format".to_owned(),
+            BencodeElem::String("libtorrent resume file".to_owned()),
+        );
+        result.insert("file-version".to_owned(), BencodeElem::Integer(1));
+        result.insert(
+            "info-hash".to_owned(),
+            BencodeElem::Bytes(self.info_hash_bytes()),
+        );
+        result.insert("name".to_owned(), BencodeElem::String(self.name.clone()));
+        result.insert(
+            "save_path".to_owned(),
+            BencodeElem::String(save_path.to_owned()),
+        );
+        result.insert(
+            "trackers".to_owned(),
+            BencodeElem::List(
+                trackers
+                    .into_iter()
+                    .map(|tier| {
+                        BencodeElem::List(tier.into_iter().map(BencodeElem::String).collect())
+                    })
+                    .collect(),
+            ),
+        );
+
+        BencodeElem::Dictionary(result)
+    }
 }
 
 #[cfg(test)]
@@ -130,7 +467,7 @@ mod file_write_tests {
     fn into_bencode_elem_ok() {
         let file = File {
             length: 42,
-            path: PathBuf::from("dir1/dir2/file"),
+            path: vec!["dir1".to_owned(), "dir2".to_owned(), "file".to_owned()],
             extra_fields: None,
         };
 
@@ -144,10 +481,11 @@ mod file_write_tests {
     fn into_bencode_elem_with_extra_fields() {
         let file = File {
             length: 42,
-            path: PathBuf::from("dir1/dir2/file"),
-            extra_fields: Some(HashMap::from_iter(
-                vec![("comment".to_owned(), bencode_elem!("no comment"))].into_iter(),
-            )),
+            path: vec!["dir1".to_owned(), "dir2".to_owned(), "file".to_owned()],
+            extra_fields: Some(HashMap::from_iter(vec![(
+                "comment".to_owned(),
+                bencode_elem!("no comment"),
+            )])),
         };
 
         assert_eq!(
@@ -177,7 +515,8 @@ mod torrent_write_tests {
             files: None,
             name: "sample".to_owned(),
             piece_length: 2,
-            pieces: vec![vec![1, 2], vec![3, 4]],
+            pieces: vec![[1; 20], [2; 20]],
+            private: None,
             extra_fields: None,
             extra_info_fields: None,
         };
@@ -192,13 +531,48 @@ mod torrent_write_tests {
                     ("length", 4),
                     ("name", "sample"),
                     ("piece length", 2),
-                    ("pieces", (1, 2, 3, 4)),
+                    ("pieces", ([[1; 20], [2; 20]].concat())),
                 })
             })
             .encode()
         );
     }
 
+    #[test]
+    fn default_file_name_ok() {
+        let mut torrent = Torrent {
+            announce: None,
+            announce_list: None,
+            length: 4,
+            files: None,
+            name: "ubuntu-16.04.4-desktop-amd64.iso".to_owned(),
+            piece_length: 2,
+            pieces: vec![[1; 20], [2; 20]],
+            private: None,
+            extra_fields: None,
+            extra_info_fields: None,
+        };
+        assert_eq!(
+            torrent.default_file_name(),
+            "ubuntu-16.04.4-desktop-amd64.iso.torrent"
+        );
+
+        for (name, file_name) in [
+            ("a/b\\c", "a_b_c.torrent"),
+            ("what? <1|2>: \"*\"", "what_ _1_2__ ___.torrent"),
+            ("photo\u{202E}gpj.exe", "photo_gpj.exe.torrent"),
+            ("tab\tzero\u{200B}width", "tab_zero_width.torrent"),
+            ("..", "...torrent"),
+            (
+                "\u{0442}\u{0435}\u{0441}\u{0442}",
+                "\u{0442}\u{0435}\u{0441}\u{0442}.torrent",
+            ),
+        ] {
+            torrent.name = name.to_owned();
+            assert_eq!(torrent.default_file_name(), file_name);
+        }
+    }
+
     #[test]
     fn write_with_announce_list() {
         let torrent = Torrent {
@@ -211,7 +585,8 @@ mod torrent_write_tests {
             files: None,
             name: "sample".to_owned(),
             piece_length: 2,
-            pieces: vec![vec![1, 2], vec![3, 4]],
+            pieces: vec![[1; 20], [2; 20]],
+            private: None,
             extra_fields: None,
             extra_info_fields: None,
         };
@@ -227,7 +602,7 @@ mod torrent_write_tests {
                     ("length", 4),
                     ("name", "sample"),
                     ("piece length", 2),
-                    ("pieces", (1, 2, 3, 4)),
+                    ("pieces", ([[1; 20], [2; 20]].concat())),
                 })
             })
             .encode()
@@ -243,14 +618,12 @@ mod torrent_write_tests {
             files: None,
             name: "sample".to_owned(),
             piece_length: 2,
-            pieces: vec![vec![1, 2], vec![3, 4]],
-            extra_fields: Some(HashMap::from_iter(
-                vec![
-                    ("comment2".to_owned(), bencode_elem!("no comment")),
-                    ("comment1".to_owned(), bencode_elem!("no comment")),
-                ]
-                .into_iter(),
-            )),
+            pieces: vec![[1; 20], [2; 20]],
+            private: None,
+            extra_fields: Some(HashMap::from_iter(vec![
+                ("comment2".to_owned(), bencode_elem!("no comment")),
+                ("comment1".to_owned(), bencode_elem!("no comment")),
+            ])),
             extra_info_fields: None,
         };
         let mut result = Vec::new();
@@ -266,7 +639,7 @@ mod torrent_write_tests {
                     ("length", 4),
                     ("name", "sample"),
                     ("piece length", 2),
-                    ("pieces", (1, 2, 3, 4)),
+                    ("pieces", ([[1; 20], [2; 20]].concat())),
                 })
             })
             .encode()
@@ -282,15 +655,13 @@ mod torrent_write_tests {
             files: None,
             name: "sample".to_owned(),
             piece_length: 2,
-            pieces: vec![vec![1, 2], vec![3, 4]],
+            pieces: vec![[1; 20], [2; 20]],
+            private: None,
             extra_fields: None,
-            extra_info_fields: Some(HashMap::from_iter(
-                vec![
-                    ("comment2".to_owned(), bencode_elem!("no comment")),
-                    ("comment1".to_owned(), bencode_elem!("no comment")),
-                ]
-                .into_iter(),
-            )),
+            extra_info_fields: Some(HashMap::from_iter(vec![
+                ("comment2".to_owned(), bencode_elem!("no comment")),
+                ("comment1".to_owned(), bencode_elem!("no comment")),
+            ])),
         };
         let mut result = Vec::new();
 
@@ -305,7 +676,7 @@ mod torrent_write_tests {
                     ("length", 4),
                     ("name", "sample"),
                     ("piece length", 2),
-                    ("pieces", (1, 2, 3, 4)),
+                    ("pieces", ([[1; 20], [2; 20]].concat())),
                 })
             })
             .encode()
@@ -321,18 +692,19 @@ mod torrent_write_tests {
             files: Some(vec![
                 File {
                     length: 2,
-                    path: PathBuf::from("dir1/dir2/file1"),
+                    path: vec!["dir1".to_owned(), "dir2".to_owned(), "file1".to_owned()],
                     extra_fields: None,
                 },
                 File {
                     length: 2,
-                    path: PathBuf::from("dir1/dir2/file2"),
+                    path: vec!["dir1".to_owned(), "dir2".to_owned(), "file2".to_owned()],
                     extra_fields: None,
                 },
             ]),
             name: "sample".to_owned(),
             piece_length: 2,
-            pieces: vec![vec![1, 2], vec![3, 4]],
+            pieces: vec![[1; 20], [2; 20]],
+            private: None,
             extra_fields: None,
             extra_info_fields: None,
         };
@@ -350,13 +722,41 @@ mod torrent_write_tests {
                     ]),
                     ("name", "sample"),
                     ("piece length", 2),
-                    ("pieces", (1, 2, 3, 4)),
+                    ("pieces", ([[1; 20], [2; 20]].concat())),
                 })
             })
             .encode()
         );
     }
 
+    #[test]
+    fn to_fastresume_skeleton_ok() {
+        let torrent = Torrent {
+            announce: Some("url".to_owned()),
+            announce_list: None,
+            length: 4,
+            files: None,
+            name: "sample".to_owned(),
+            piece_length: 2,
+            pieces: vec![[1; 20], [2; 20]],
+            private: None,
+            extra_fields: None,
+            extra_info_fields: None,
+        };
+
+        assert_eq!(
+            torrent.to_fastresume_skeleton("/downloads"),
+            bencode_elem!({
+                ("file-format", "libtorrent resume file"),
+                ("file-version", 1),
+                ("info-hash", (torrent.info_hash_bytes())),
+                ("name", "sample"),
+                ("save_path", "/downloads"),
+                ("trackers", [["url"]]),
+            })
+        );
+    }
+
     #[test]
     fn encode_ok() {
         let torrent = Torrent {
@@ -366,7 +766,38 @@ mod torrent_write_tests {
             files: None,
             name: "sample".to_owned(),
             piece_length: 2,
-            pieces: vec![vec![1, 2], vec![3, 4]],
+            pieces: vec![[1; 20], [2; 20]],
+            private: None,
+            extra_fields: None,
+            extra_info_fields: None,
+        };
+
+        assert_eq!(
+            torrent.encode().unwrap(),
+            bencode_elem!({
+                ("announce", "url"),
+                ("info", {
+                    ("length", 4),
+                    ("name", "sample"),
+                    ("piece length", 2),
+                    ("pieces", ([[1; 20], [2; 20]].concat())),
+                })
+            })
+            .encode()
+        );
+    }
+
+    #[test]
+    fn encode_with_private() {
+        let torrent = Torrent {
+            announce: Some("url".to_owned()),
+            announce_list: None,
+            length: 4,
+            files: None,
+            name: "sample".to_owned(),
+            piece_length: 2,
+            pieces: vec![[1; 20], [2; 20]],
+            private: Some(false),
             extra_fields: None,
             extra_info_fields: None,
         };
@@ -379,7 +810,8 @@ mod torrent_write_tests {
                     ("length", 4),
                     ("name", "sample"),
                     ("piece length", 2),
-                    ("pieces", (1, 2, 3, 4)),
+                    ("pieces", ([[1; 20], [2; 20]].concat())),
+                    ("private", 0),
                 })
             })
             .encode()
@@ -398,7 +830,8 @@ mod torrent_write_tests {
             files: None,
             name: "sample".to_owned(),
             piece_length: 2,
-            pieces: vec![vec![1, 2], vec![3, 4]],
+            pieces: vec![[1; 20], [2; 20]],
+            private: None,
             extra_fields: None,
             extra_info_fields: None,
         };
@@ -412,7 +845,7 @@ mod torrent_write_tests {
                     ("length", 4),
                     ("name", "sample"),
                     ("piece length", 2),
-                    ("pieces", (1, 2, 3, 4)),
+                    ("pieces", ([[1; 20], [2; 20]].concat())),
                 })
             })
             .encode()
@@ -428,14 +861,12 @@ mod torrent_write_tests {
             files: None,
             name: "sample".to_owned(),
             piece_length: 2,
-            pieces: vec![vec![1, 2], vec![3, 4]],
-            extra_fields: Some(HashMap::from_iter(
-                vec![
-                    ("comment2".to_owned(), bencode_elem!("no comment")),
-                    ("comment1".to_owned(), bencode_elem!("no comment")),
-                ]
-                .into_iter(),
-            )),
+            pieces: vec![[1; 20], [2; 20]],
+            private: None,
+            extra_fields: Some(HashMap::from_iter(vec![
+                ("comment2".to_owned(), bencode_elem!("no comment")),
+                ("comment1".to_owned(), bencode_elem!("no comment")),
+            ])),
             extra_info_fields: None,
         };
 
@@ -449,7 +880,7 @@ mod torrent_write_tests {
                     ("length", 4),
                     ("name", "sample"),
                     ("piece length", 2),
-                    ("pieces", (1, 2, 3, 4)),
+                    ("pieces", ([[1; 20], [2; 20]].concat())),
                 })
             })
             .encode()
@@ -465,15 +896,13 @@ mod torrent_write_tests {
             files: None,
             name: "sample".to_owned(),
             piece_length: 2,
-            pieces: vec![vec![1, 2], vec![3, 4]],
+            pieces: vec![[1; 20], [2; 20]],
+            private: None,
             extra_fields: None,
-            extra_info_fields: Some(HashMap::from_iter(
-                vec![
-                    ("comment2".to_owned(), bencode_elem!("no comment")),
-                    ("comment1".to_owned(), bencode_elem!("no comment")),
-                ]
-                .into_iter(),
-            )),
+            extra_info_fields: Some(HashMap::from_iter(vec![
+                ("comment2".to_owned(), bencode_elem!("no comment")),
+                ("comment1".to_owned(), bencode_elem!("no comment")),
+            ])),
         };
 
         assert_eq!(
@@ -486,7 +915,7 @@ mod torrent_write_tests {
                     ("length", 4),
                     ("name", "sample"),
                     ("piece length", 2),
-                    ("pieces", (1, 2, 3, 4)),
+                    ("pieces", ([[1; 20], [2; 20]].concat())),
                 })
             })
             .encode()
@@ -502,18 +931,19 @@ mod torrent_write_tests {
             files: Some(vec![
                 File {
                     length: 2,
-                    path: PathBuf::from("dir1/dir2/file1"),
+                    path: vec!["dir1".to_owned(), "dir2".to_owned(), "file1".to_owned()],
                     extra_fields: None,
                 },
                 File {
                     length: 2,
-                    path: PathBuf::from("dir1/dir2/file2"),
+                    path: vec!["dir1".to_owned(), "dir2".to_owned(), "file2".to_owned()],
                     extra_fields: None,
                 },
             ]),
             name: "sample".to_owned(),
             piece_length: 2,
-            pieces: vec![vec![1, 2], vec![3, 4]],
+            pieces: vec![[1; 20], [2; 20]],
+            private: None,
             extra_fields: None,
             extra_info_fields: None,
         };
@@ -529,10 +959,108 @@ mod torrent_write_tests {
                     ]),
                     ("name", "sample"),
                     ("piece length", 2),
-                    ("pieces", (1, 2, 3, 4)),
+                    ("pieces", ([[1; 20], [2; 20]].concat())),
                 })
             })
             .encode()
         );
     }
+
+    // `write_into()` and `info_hash_bytes()` stream `pieces`, and must
+    // produce the same bytes as encoding the equivalent `BencodeElem`s.
+    fn assert_streaming_matches(torrent: &Torrent) {
+        assert_eq!(
+            torrent.clone().encode().unwrap(),
+            torrent.clone().into_bencode_elem().encode()
+        );
+
+        let mut info = Vec::new();
+        torrent.write_info_streaming(&mut info).unwrap();
+        assert_eq!(info, torrent.construct_info().encode());
+        assert_eq!(
+            torrent.info_hash_bytes(),
+            Sha1::digest(torrent.construct_info().encode()).to_vec()
+        );
+    }
+
+    #[test]
+    fn streaming_matches_fixtures() {
+        for dir in ["tests/files", "tests/samples"] {
+            for entry in std::fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.extension().is_some_and(|ext| ext == "torrent") {
+                    assert_streaming_matches(&Torrent::read_from_file(path).unwrap());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn streaming_matches_many_pieces() {
+        let n_pieces: usize = 1 << 20;
+        let torrent = Torrent {
+            announce: Some("url".to_owned()),
+            announce_list: None,
+            length: n_pieces as Integer,
+            files: None,
+            name: "sample".to_owned(),
+            piece_length: 1,
+            pieces: (0..n_pieces)
+                .map(|i| {
+                    let mut piece = [0; PIECE_STRING_LENGTH];
+                    piece[..8].copy_from_slice(&i.to_be_bytes());
+                    piece
+                })
+                .collect(),
+            private: Some(true),
+            extra_fields: None,
+            extra_info_fields: None,
+        };
+
+        assert_streaming_matches(&torrent);
+    }
+
+    #[test]
+    fn streaming_matches_overridden_keys() {
+        // `pieces` and `info` set in the extra fields win,
+        // as they do when encoding `BencodeElem`s
+        let torrent = Torrent {
+            announce: None,
+            announce_list: None,
+            length: 4,
+            files: None,
+            name: "sample".to_owned(),
+            piece_length: 2,
+            pieces: vec![[1; 20], [2; 20]],
+            private: None,
+            extra_fields: None,
+            extra_info_fields: Some(HashMap::from_iter(vec![(
+                "pieces".to_owned(),
+                bencode_elem!("overridden"),
+            )])),
+        };
+        assert_streaming_matches(&torrent);
+        assert_eq!(
+            torrent.construct_info(),
+            bencode_elem!({
+                ("length", 4),
+                ("name", "sample"),
+                ("piece length", 2),
+                ("pieces", "overridden"),
+            })
+        );
+
+        let torrent = Torrent {
+            extra_fields: Some(HashMap::from_iter(vec![(
+                "info".to_owned(),
+                bencode_elem!("overridden"),
+            )])),
+            ..torrent
+        };
+        assert_streaming_matches(&torrent);
+        assert_eq!(
+            torrent.encode().unwrap(),
+            bencode_elem!({ ("info", "overridden") }).encode()
+        );
+    }
 }
@@ -1,35 +1,151 @@
-//! Module containing structs for tracker responses.
+//! Module containing structs for tracker requests and responses.
 //!
 //! At the moment, `lava_torrent` does not handle communication
 //! with trackers. Users will have to send requests themselves and
 //! pass the received responses to `lava_torrent` for parsing.
+//! Trackers can parse the announce requests they receive with
+//! [`AnnounceRequest::from_query()`], and clients can
+//! build them with [`AnnounceRequest::to_query()`].
+//!
+//! # Stability
+//! This module follows the same [semver] rules as the rest of the crate, i.e.
+//! breaking changes only happen in releases that bump the minor version
+//! (while the crate is at `0.x`). To keep such releases rare, the response
+//! types ([`Peer`], [`TrackerResponse`]'s variants, [`SwarmMetadata`], and
+//! [`TrackerScrapeResponse`]) are `#[non_exhaustive]`: new fields (e.g. for
+//! newly supported BEPs) may be added in any release. Match them with `..`,
+//! and don't rely on `Debug`/`Display` output, which may change at any time.
+//!
+//! # Privacy
+//! Peer addresses are personal data in some jurisdictions. To keep them
+//! out of logs, `Debug` output masks them by default (see
+//! [`set_debug_full_addresses()`]), and so does the alternate form of
+//! `Display` (e.g. `{:#}` or [`TrackerResponse::display_anonymized()`]).
+//! IPv4 addresses keep their /16 prefix (`1.2.x.x:6881`) and IPv6
+//! addresses keep their /32 prefix (`[2001:db8:…]:6881`). DNS names
+//! (see [`PeerAddr`]) are replaced with `REDACTED` as a whole.
+//!
+//! Parsing is lenient where trackers are known to misbehave (see
+//! [`TrackerResponse::from_bytes()`]). Inputs that are accepted by a
+//! release will not be rejected by later releases of the same minor version.
+//! The one exception is [`ResponseLimits`]: responses are size-limited so that
+//! a malicious tracker can't exhaust memory, and the default limits may be
+//! lowered if real responses turn out to be smaller.
+//!
+//! [semver]: https://doc.rust-lang.org/cargo/reference/semver.html
 
 use crate::bencode::BencodeElem;
+use crate::display::{display_bytes_summary, display_fields};
+use crate::hex;
 use crate::torrent::v1::{Dictionary, Integer};
+use crate::util;
 use crate::LavaTorrentError;
 use itertools::Itertools;
+use percent_encoding::{percent_decode_str, percent_encode, AsciiSet, NON_ALPHANUMERIC};
 use std::borrow::Cow;
-use std::collections::HashMap;
-use std::convert::TryInto;
+use std::collections::{HashMap, HashSet};
+use std::convert::{TryFrom, TryInto};
 use std::fmt;
-use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+mod normalize;
+mod scrape;
+
+const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+
+// everything but unreserved characters, as defined in RFC 3986
+const QUERY_COMPONENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+// whether `Debug` output contains full peer addresses
+static DEBUG_FULL_ADDRESSES: AtomicBool = AtomicBool::new(false);
+
+/// A raw (i.e. not hex-encoded) v1 info hash.
+pub type InfoHash = [u8; 20];
 
 /// Peer information returned in a tracker response.
 ///
 /// Modeled after the specifications in
 /// [BEP 3](http://bittorrent.org/beps/bep_0003.html) and
 /// [BEP 23](http://www.bittorrent.org/beps/bep_0023.html).
-#[derive(Clone, Debug, Eq, PartialEq)]
+///
+/// `addr` is masked in `Debug` output and in the alternate form of
+/// `Display` (see [the module docs](index.html#privacy)).
+#[derive(Clone, Eq, PartialEq)]
+#[non_exhaustive]
 pub struct Peer {
-    /// A string of length 20 which this peer uses as its id.
+    /// The id (usually 20 bytes) this peer uses. Peer ids are
+    /// raw bytes, which are not necessarily valid UTF-8.
     /// This field will be `None` for compact peer info.
-    pub id: Option<String>,
-    /// The IP/port this peer is listening on.
-    pub addr: SocketAddr,
+    pub id: Option<Vec<u8>>,
+    /// The address this peer is listening on. BEP 3 allows trackers
+    /// to give a DNS name instead of an IP, which is kept unresolved
+    /// (see [`PeerAddr`](enum.PeerAddr.html)).
+    pub addr: PeerAddr,
     /// Fields not listed above.
     pub extra_fields: Option<Dictionary>,
 }
 
+/// The address of a [`Peer`](struct.Peer.html).
+///
+/// Parsing never does any network IO, so a peer whose `ip` is a
+/// DNS name is returned as `Hostname`. Call [`Peer::resolve()`] to
+/// look it up, or [`Peer::socket_addr()`] to skip such peers.
+///
+/// Masked in `Debug` output and in the alternate form of `Display`,
+/// the same way [`Peer`](struct.Peer.html) is. The host of `Hostname`
+/// can't be masked partially, and is shown as `REDACTED`.
+///
+/// # Migrating from `Peer.addr: SocketAddr`
+/// `Peer.addr` used to be a `SocketAddr`, and peers with a DNS name
+/// were skipped (and counted in `skipped_peers`). [`Peer::new()`] and
+/// [`Peer::with_id()`] still take a `SocketAddr`; code that reads
+/// `addr` can either use [`Peer::socket_addr()`] to keep ignoring
+/// DNS names, or [`Peer::resolve()`] to connect to them too.
+///
+/// ```
+/// use lava_torrent::tracker::{Peer, PeerAddr};
+/// use std::net::SocketAddr;
+///
+/// let peer = Peer::new("10.0.0.1:6881".parse().unwrap());
+///
+/// // before: `let addr: SocketAddr = peer.addr;`
+/// let addr: Option<SocketAddr> = peer.socket_addr();
+/// assert_eq!(addr, Some("10.0.0.1:6881".parse().unwrap()));
+///
+/// // before: `peer.addr.port()`
+/// assert_eq!(peer.addr.port(), 6881);
+///
+/// // before: `peer.addr == addr`
+/// assert_eq!(peer.addr, PeerAddr::from(addr.unwrap()));
+///
+/// let peer = Peer::from_addr(PeerAddr::new("peer.example", 6881).unwrap());
+/// assert_eq!(peer.socket_addr(), None);
+/// assert_eq!(peer.to_string(), "\t-addr: peer.example:6881\n\t========================================\n");
+/// ```
+///
+/// [`Peer::new()`]: struct.Peer.html#method.new
+/// [`Peer::with_id()`]: struct.Peer.html#method.with_id
+/// [`Peer::resolve()`]: struct.Peer.html#method.resolve
+/// [`Peer::socket_addr()`]: struct.Peer.html#method.socket_addr
+#[derive(Clone, Eq, Hash, PartialEq)]
+pub enum PeerAddr {
+    /// An IP address and port.
+    Resolved(SocketAddr),
+    /// A DNS name and port, not resolved yet.
+    Hostname {
+        /// The DNS name, as given by the tracker.
+        host: String,
+        /// The port the peer is listening on.
+        port: u16,
+    },
+}
+
 /// Everything found in a tracker response.
 ///
 /// Modeled after the specifications in
@@ -39,6 +155,7 @@ pub struct Peer {
 /// need any of those extra fields you would have to parse it yourself.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum TrackerResponse {
+    #[non_exhaustive]
     Success {
         /// The number of seconds the downloader should wait between
         /// regular requests.
@@ -57,15 +174,64 @@ pub enum TrackerResponse {
         complete: Option<Integer>,
         /// Number of non-seeder peers, i.e. leechers.
         incomplete: Option<Integer>,
+        /// Number of peer entries dropped because their port is not
+        /// within `1..=65535`. A misbehaving tracker
+        /// shouldn't invalidate the entire response, so such entries
+        /// are skipped and counted here instead. Entries dropped by
+        /// [`ResponseLimits::truncate_peers`] are counted here too.
+        ///
+        /// [`ResponseLimits::truncate_peers`]: struct.ResponseLimits.html#structfield.truncate_peers
+        skipped_peers: usize,
+        /// Number of bytes found after the response, not counting
+        /// trailing ASCII whitespace. Some trackers append junk
+        /// (e.g. a blank line or an HTML footer) to their responses,
+        /// which is ignored instead of invalidating the entire response.
+        trailing_bytes: usize,
         /// Fields not listed above.
         extra_fields: Option<Dictionary>,
     },
+    #[non_exhaustive]
     Failure {
         /// Error message.
         reason: String,
     },
 }
 
+/// Builder for [`TrackerResponse::Success`], for when
+/// [`TrackerResponse::success_minimal()`] isn't enough.
+///
+/// Fields that are not set are `None` (or `0` for counts).
+///
+/// ```
+/// use lava_torrent::tracker::{Peer, TrackerResponse, TrackerResponseBuilder};
+///
+/// let response = TrackerResponseBuilder::new(1800)
+///     .set_peers(vec![Peer::new(([127, 0, 0, 1], 6881).into())])
+///     .set_complete(10)
+///     .set_incomplete(3)
+///     .build();
+/// assert!(matches!(
+///     response,
+///     TrackerResponse::Success { complete: Some(10), .. }
+/// ));
+/// ```
+///
+/// [`TrackerResponse::Success`]: enum.TrackerResponse.html#variant.Success
+/// [`TrackerResponse::success_minimal()`]: enum.TrackerResponse.html#method.success_minimal
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TrackerResponseBuilder {
+    interval: Integer,
+    peers: Vec<Peer>,
+    warning: Option<String>,
+    min_interval: Option<Integer>,
+    tracker_id: Option<String>,
+    complete: Option<Integer>,
+    incomplete: Option<Integer>,
+    skipped_peers: usize,
+    trailing_bytes: usize,
+    extra_fields: Option<Dictionary>,
+}
+
 /// Swarm metadata returned in a tracker scrape response.
 ///
 /// Modeled after the specifications in
@@ -73,6 +239,7 @@ pub enum TrackerResponse {
 /// Unknown/extension fields will be placed in `extra_fields`. If you
 /// need any of those extra fields you would have to parse it yourself.
 #[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
 pub struct SwarmMetadata {
     /// The number of active peers that have completed downloading.
     pub complete: Integer,
@@ -90,41 +257,462 @@ pub struct SwarmMetadata {
 /// [BEP 48](http://www.bittorrent.org/beps/bep_0048.html) and
 /// [theory.org](https://wiki.theory.org/index.php/BitTorrentSpecification#Tracker_.27scrape.27_Convention).
 #[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
 pub struct TrackerScrapeResponse {
     /// File info (info hash -> metadata).
-    pub files: HashMap<Vec<u8>, SwarmMetadata>,
+    ///
+    /// Info hashes are always raw, even if the tracker
+    /// keyed `files` by 40-char hex strings instead.
+    pub files: HashMap<InfoHash, SwarmMetadata>,
+    /// Number of bytes found after the response, not counting trailing
+    /// ASCII whitespace. See [`TrackerResponse::Success`] for details.
+    ///
+    /// [`TrackerResponse::Success`]: enum.TrackerResponse.html#variant.Success
+    pub trailing_bytes: usize,
     /// Fields not listed above.
     pub extra_fields: Option<Dictionary>,
 }
 
+/// Limits on the size of tracker responses, enforced while parsing them.
+///
+/// A response is parsed in its entirety before any of it is returned, so
+/// a malicious tracker could exhaust memory with an endless stream of
+/// valid bencode (e.g. millions of peers). Therefore, responses that exceed
+/// these limits are rejected with a `MalformedResponse` error that names the
+/// limit and the observed size. The default limits are already generous:
+/// a real tracker response is a few KiB at most.
+///
+/// [`TrackerResponse::from_bytes()`] and [`TrackerScrapeResponse::from_bytes()`]
+/// use the default limits. Use their `from_bytes_with_limits()` variants to
+/// pick others. Full scrapes of large trackers do exceed the default limits;
+/// read those with a [`ScrapeIter`] instead. It only holds one entry at a time,
+/// and limits the size of that entry rather than that of the whole response
+/// (see [`ScrapeIter::with_limits()`]).
+///
+/// ```
+/// use lava_torrent::tracker::{ResponseLimits, TrackerResponse};
+///
+/// let limits = ResponseLimits {
+///     max_peers: 1,
+///     truncate_peers: true,
+///     ..ResponseLimits::default()
+/// };
+/// let bytes = b"d8:intervali1800e5:peers12:\x0a\x00\x00\x01\x1a\xe1\x0a\x00\x00\x02\x1a\xe1e";
+/// let response = TrackerResponse::from_bytes_with_limits(bytes, &limits).unwrap();
+/// assert!(matches!(
+///     response,
+///     TrackerResponse::Success { ref peers, skipped_peers: 1, .. } if peers.len() == 1
+/// ));
+/// ```
+///
+/// [`TrackerResponse::from_bytes()`]: enum.TrackerResponse.html#method.from_bytes
+/// [`TrackerScrapeResponse::from_bytes()`]: struct.TrackerScrapeResponse.html#method.from_bytes
+/// [`ScrapeIter`]: struct.ScrapeIter.html
+/// [`ScrapeIter::with_limits()`]: struct.ScrapeIter.html#method.with_limits
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct ResponseLimits {
+    /// Max # of bytes in a response, including anything
+    /// after it (see `trailing_bytes`). Defaults to 1 MiB.
+    pub max_response_bytes: usize,
+    /// Max # of entries in `peers` (valid or not). Only applies to
+    /// announce responses. Defaults to `10_000`.
+    pub max_peers: usize,
+    /// Max # of fields that end up in `extra_fields` of the top-level
+    /// dictionary. Defaults to `64`.
+    pub max_extra_fields: usize,
+    /// If `true`, `peers` with more than `max_peers` entries is truncated
+    /// to its first `max_peers` entries instead of failing the response.
+    /// The dropped entries are counted in `skipped_peers`. Defaults to `false`.
+    pub truncate_peers: bool,
+}
+
+/// Streaming reader for tracker scrape responses, yielding the
+/// entries of `files` one at a time.
+///
+/// Full scrapes of large trackers list millions of info hashes, which
+/// [`TrackerScrapeResponse::from_bytes()`] would have to hold in memory all
+/// at once. `ScrapeIter` only ever holds a single entry instead, so memory
+/// usage doesn't depend on the size of the response. A single key or value
+/// could still be arbitrarily large, so these are limited to 64 KiB each
+/// (see [`with_limits()`] for other limits).
+///
+/// Entries are parsed as leniently as by [`TrackerScrapeResponse::from_bytes()`],
+/// and are yielded in the order they are encoded in. Unlike with
+/// `from_bytes()`, an info hash listed more than once (e.g. both raw and
+/// in hex) is yielded each time. Fields other than `files` are skipped.
+///
+/// The response is validated as it is read: after the first error,
+/// the error is yielded and iteration stops. See [`scrape_lookup()`]
+/// for looking up specific info hashes.
+///
+/// [`TrackerScrapeResponse::from_bytes()`]: struct.TrackerScrapeResponse.html#method.from_bytes
+/// [`scrape_lookup()`]: fn.scrape_lookup.html
+/// [`with_limits()`]: #method.with_limits
+#[derive(Debug)]
+pub struct ScrapeIter<R> {
+    reader: std::io::BufReader<R>,
+    // max # of bytes in `key` or `value`
+    max_entry_bytes: usize,
+    // key and encoded value of the current entry, reused between entries
+    key: Vec<u8>,
+    value: Vec<u8>,
+    // key of the previous entry, for checking that `files` is sorted
+    previous_key: Vec<u8>,
+    is_done: bool,
+}
+
+/// Swarm health in a form common to announce and scrape responses.
+///
+/// Announce responses report `complete`/`incomplete`, while scrape
+/// responses additionally report `downloaded`. `SwarmStats` normalizes
+/// these so that the numbers from different trackers can be combined.
+/// A field is `None` if the tracker did not report it, or if it
+/// reported a negative number.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct SwarmStats {
+    /// Number of peers with the entire file.
+    pub seeders: Option<u64>,
+    /// Number of peers without the entire file.
+    pub leechers: Option<u64>,
+    /// Number of peers that have ever completed downloading.
+    pub completed: Option<u64>,
+}
+
+/// The `event` of an [`AnnounceRequest`].
+///
+/// [`AnnounceRequest`]: struct.AnnounceRequest.html
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum AnnounceEvent {
+    /// The first request of a download.
+    Started,
+    /// The client is shutting down gracefully.
+    Stopped,
+    /// The download has just completed.
+    Completed,
+    /// The client is a partial seed, as defined in
+    /// [BEP 21](http://bittorrent.org/beps/bep_0021.html).
+    Paused,
+    /// A regular request, i.e. `event` is empty or absent.
+    #[default]
+    Empty,
+}
+
+/// Everything found in an announce request, i.e. the query
+/// string a client sends to a tracker.
+///
+/// Modeled after the specifications in
+/// [BEP 3](http://bittorrent.org/beps/bep_0003.html) and
+/// [theory.org](https://wiki.theory.org/index.php/BitTorrentSpecification#Tracker_Request_Parameters).
+/// Unknown parameters will be placed in `extra_params`.
+#[derive(Clone, Eq, PartialEq)]
+pub struct AnnounceRequest {
+    /// The info hash of the torrent.
+    pub info_hash: InfoHash,
+    /// The id of the client, as raw bytes.
+    pub peer_id: [u8; 20],
+    /// The port the client is listening on.
+    pub port: u16,
+    /// Total # of bytes uploaded.
+    pub uploaded: u64,
+    /// Total # of bytes downloaded.
+    pub downloaded: u64,
+    /// # of bytes the client still has to download.
+    pub left: u64,
+    /// Whether the client accepts compact peer lists
+    /// (see [BEP 23](http://bittorrent.org/beps/bep_0023.html)).
+    pub compact: Option<bool>,
+    /// # of peers the client would like to receive.
+    pub numwant: Option<u32>,
+    /// See [`AnnounceEvent`](enum.AnnounceEvent.html).
+    pub event: AnnounceEvent,
+    /// A key the client uses to prove its identity if its IP changes.
+    pub key: Option<String>,
+    /// The `tracker id` received in a previous response.
+    pub tracker_id: Option<String>,
+    /// The IP (or DNS name) the client claims to have.
+    pub ip: Option<String>,
+    /// Parameters not listed above (name -> percent-decoded value).
+    pub extra_params: HashMap<String, Vec<u8>>,
+}
+
+/// A tracker URL in a canonical form, for telling whether two URLs
+/// refer to the same tracker. See [`normalize_url()`].
+///
+/// `Display` gives the canonical URL back.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct NormalizedTracker {
+    // lowercase, without "://"
+    scheme: String,
+    // kept as is, including the trailing '@'
+    userinfo: Option<String>,
+    // lowercase, IPv6 literals in brackets
+    host: String,
+    // `None` if absent or the default port of `scheme`
+    port: Option<u16>,
+    // including the query, always empty for UDP
+    path: String,
+}
+
+/// Read the scrape response in `reader` with [`ScrapeIter`], and return
+/// the swarm metadata of the info hashes in `wanted`.
+///
+/// Reading stops as soon as all of `wanted` have been found, so the rest of
+/// the response is neither read nor validated. Info hashes that are not
+/// listed in the response are absent from the result. If an info hash is
+/// listed more than once, the first occurrence wins.
+///
+/// [`ScrapeIter`]: struct.ScrapeIter.html
+pub fn scrape_lookup<R>(
+    reader: R,
+    wanted: &HashSet<InfoHash>,
+) -> Result<HashMap<InfoHash, SwarmMetadata>, LavaTorrentError>
+where
+    R: std::io::Read,
+{
+    ScrapeIter::lookup(reader, wanted)
+}
+
+/// Normalize the tracker URL `url`, so that URLs referring to the
+/// same tracker compare equal.
+///
+/// - The scheme and host are lowercased, and IPv6 literals are
+///   written in their canonical form.
+/// - The port is dropped if it is the default for the scheme
+///   (80 for `http` and `ws`, 443 for `https` and `wss`).
+/// - For `udp`, only the host and port are kept, since UDP trackers
+///   ignore the rest ([BEP 15](http://bittorrent.org/beps/bep_0015.html)).
+/// - Otherwise, the path and query are kept as is, since they often
+///   carry a passkey. A missing path becomes `/`, and the fragment
+///   (which is never sent) is dropped.
+///
+/// Surrounding whitespace is ignored. `Err(InvalidArgument)` is returned if
+/// `url` has no scheme or host, or if its port is not a valid port number.
+///
+/// ```
+/// use lava_torrent::tracker;
+///
+/// let a = tracker::normalize_url("UDP://Tracker.Example:6969/announce").unwrap();
+/// let b = tracker::normalize_url("udp://tracker.example:6969").unwrap();
+/// assert_eq!(a, b);
+/// assert_eq!(a.to_string(), "udp://tracker.example:6969");
+///
+/// let a = tracker::normalize_url("http://tracker.example:80/announce").unwrap();
+/// assert_eq!(a.to_string(), "http://tracker.example/announce");
+/// ```
+pub fn normalize_url(url: &str) -> Result<NormalizedTracker, LavaTorrentError> {
+    NormalizedTracker::parse(url)
+}
+
+/// Choose whether the `Debug` output of [`Peer`] and [`AnnounceRequest`]
+/// contains full IP addresses instead of masked ones (the default).
+///
+/// This applies process-wide and is meant for local debugging only.
+///
+/// [`Peer`]: struct.Peer.html
+/// [`AnnounceRequest`]: struct.AnnounceRequest.html
+pub fn set_debug_full_addresses(full: bool) {
+    DEBUG_FULL_ADDRESSES.store(full, Ordering::Relaxed);
+}
+
+fn debug_full_addresses() -> bool {
+    DEBUG_FULL_ADDRESSES.load(Ordering::Relaxed)
+}
+
+// Keep the /16 prefix of IPv4 and the /32 prefix of IPv6 addresses.
+fn mask_ip(ip: &IpAddr) -> String {
+    match ip {
+        IpAddr::V4(ip) => {
+            let octets = ip.octets();
+            format!("{}.{}.x.x", octets[0], octets[1])
+        }
+        IpAddr::V6(ip) => {
+            let segments = ip.segments();
+            format!("{:x}:{:x}:…", segments[0], segments[1])
+        }
+    }
+}
+
+fn mask_addr(addr: &SocketAddr) -> String {
+    match addr {
+        SocketAddr::V4(_) => format!("{}:{}", mask_ip(&addr.ip()), addr.port()),
+        SocketAddr::V6(_) => format!("[{}]:{}", mask_ip(&addr.ip()), addr.port()),
+    }
+}
+
+// Whether `host` is a valid DNS name (RFC 1123), with an optional
+// trailing dot. A name whose last label is all digits is rejected,
+// so that e.g. "127.0.0.256" isn't mistaken for one.
+fn is_valid_hostname(host: &str) -> bool {
+    let host = host.strip_suffix('.').unwrap_or(host);
+    if host.is_empty() || host.len() > 253 {
+        return false;
+    }
+
+    let valid_labels = host.split('.').all(|label| {
+        (1..=63).contains(&label.len())
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label
+                .bytes()
+                .all(|byte| byte.is_ascii_alphanumeric() || byte == b'-')
+    });
+    valid_labels
+        && !host
+            .rsplit('.')
+            .next()
+            .unwrap_or(host)
+            .bytes()
+            .all(|byte| byte.is_ascii_digit())
+}
+
+impl PeerAddr {
+    /// Create a `PeerAddr` from `host`, which can either be
+    /// an IP address or a DNS name, and `port`.
+    ///
+    /// If `host` is neither, then `Err(error)` will be returned.
+    /// No lookup is done for DNS names.
+    pub fn new(host: &str, port: u16) -> Result<PeerAddr, LavaTorrentError> {
+        Self::parse(host, port).ok_or_else(|| {
+            LavaTorrentError::InvalidArgument(Cow::Owned(format!(
+                "{:?} is neither an IP address nor a hostname.",
+                host
+            )))
+        })
+    }
+
+    /// Return the port, which both variants have.
+    pub fn port(&self) -> u16 {
+        match self {
+            PeerAddr::Resolved(addr) => addr.port(),
+            PeerAddr::Hostname { port, .. } => *port,
+        }
+    }
+
+    /// Return the `SocketAddr` if `self` is `Resolved`, or `None`
+    /// if `self` is a DNS name.
+    pub fn socket_addr(&self) -> Option<SocketAddr> {
+        match self {
+            PeerAddr::Resolved(addr) => Some(*addr),
+            PeerAddr::Hostname { .. } => None,
+        }
+    }
+
+    /// Return the addresses `self` refers to, looking up DNS names
+    /// with [`ToSocketAddrs`] (which blocks). `Resolved` is returned
+    /// as is, without any lookup.
+    ///
+    /// [`ToSocketAddrs`]: https://doc.rust-lang.org/std/net/trait.ToSocketAddrs.html
+    pub fn resolve(&self) -> io::Result<Vec<SocketAddr>> {
+        match self {
+            PeerAddr::Resolved(addr) => Ok(vec![*addr]),
+            PeerAddr::Hostname { host, port } => {
+                Ok((host.as_str(), *port).to_socket_addrs()?.collect())
+            }
+        }
+    }
+
+    fn parse(host: &str, port: u16) -> Option<PeerAddr> {
+        if let Ok(ip) = host.parse::<IpAddr>() {
+            Some(PeerAddr::Resolved(SocketAddr::from((ip, port))))
+        } else if is_valid_hostname(host) {
+            Some(PeerAddr::Hostname {
+                host: host.to_owned(),
+                port,
+            })
+        } else {
+            None
+        }
+    }
+
+    fn masked(&self) -> String {
+        match self {
+            PeerAddr::Resolved(addr) => mask_addr(addr),
+            PeerAddr::Hostname { port, .. } => format!("REDACTED:{}", port),
+        }
+    }
+}
+
+impl From<SocketAddr> for PeerAddr {
+    fn from(addr: SocketAddr) -> PeerAddr {
+        PeerAddr::Resolved(addr)
+    }
+}
+
 impl Peer {
+    /// Create a `Peer` listening on `addr`, with neither an id
+    /// nor extra fields (i.e. as if it came from compact peer info).
+    pub fn new(addr: SocketAddr) -> Peer {
+        Peer::from_addr(PeerAddr::Resolved(addr))
+    }
+
+    /// Same as [`new()`](#method.new), but `addr` may also be a DNS name.
+    pub fn from_addr(addr: PeerAddr) -> Peer {
+        Peer {
+            id: None,
+            addr,
+            extra_fields: None,
+        }
+    }
+
+    /// Create a `Peer` listening on `addr` and using `id`,
+    /// without extra fields.
+    pub fn with_id(addr: SocketAddr, id: Vec<u8>) -> Peer {
+        Peer {
+            id: Some(id),
+            ..Peer::new(addr)
+        }
+    }
+
+    /// Return the `SocketAddr` this peer is listening on, or `None`
+    /// if the tracker gave a DNS name (see [`resolve()`](#method.resolve)).
+    pub fn socket_addr(&self) -> Option<SocketAddr> {
+        self.addr.socket_addr()
+    }
+
+    /// Return the addresses this peer may be listening on, looking up
+    /// `addr` if it is a DNS name. See [`PeerAddr::resolve()`].
+    ///
+    /// This is the only method of this module that does network IO.
+    ///
+    /// [`PeerAddr::resolve()`]: enum.PeerAddr.html#method.resolve
+    pub fn resolve(&self) -> io::Result<Vec<SocketAddr>> {
+        self.addr.resolve()
+    }
+
     /// Go through `dict` and return the extracted `Peer`.
     ///
-    /// If `dict` is missing any required field (e.g. `ip`),
+    /// `ip` can either be an IP address or a DNS name (which is not
+    /// resolved). If `dict` is missing any required field (e.g. `ip`),
+    /// if `ip` is neither, or if `port` is not within `1..=65535`,
     /// then `Err(error)` will be returned.
+    ///
+    /// Note that port `0` is rejected as well: while it can be
+    /// represented, it is not something we can connect to.
     fn from_dict(mut dict: HashMap<String, BencodeElem>) -> Result<Peer, LavaTorrentError> {
         let id = match dict.remove("peer id") {
-            Some(BencodeElem::String(string)) => Some(string),
-            Some(BencodeElem::Bytes(bytes)) => Some(
-                bytes
-                    .iter()
-                    .map(|b| format!("{:x}", b))
-                    .format("")
-                    .to_string(),
-            ),
-            Some(_) => {
-                return Err(LavaTorrentError::MalformedResponse(Cow::Borrowed(
-                    r#""peer id" maps to neither a utf8 string nor a string of bytes."#,
-                )));
+            Some(BencodeElem::String(string)) => Some(string.into_bytes()),
+            Some(BencodeElem::Bytes(bytes)) => Some(bytes),
+            Some(other) => {
+                return Err(LavaTorrentError::MalformedResponse(Cow::Owned(format!(
+                    r#""peer id" maps to {}, expected String or Bytes."#,
+                    other.kind()
+                ))));
             }
             None => None,
         };
         let ip = match dict.remove("ip") {
             Some(BencodeElem::String(ip)) => ip,
-            Some(_) => {
-                return Err(LavaTorrentError::MalformedResponse(Cow::Borrowed(
-                    r#""ip" does not map to a string (or maps to invalid UTF8)."#,
-                )));
+            Some(BencodeElem::Bytes(bytes)) => {
+                return Err(LavaTorrentError::MalformedResponse(Cow::Owned(format!(
+                    r#""ip" maps to {}."#,
+                    util::describe_invalid_utf8(&bytes)
+                ))));
+            }
+            Some(other) => {
+                return Err(LavaTorrentError::MalformedResponse(Cow::Owned(format!(
+                    r#""ip" maps to {}, expected String."#,
+                    other.kind()
+                ))));
             }
             None => {
                 return Err(LavaTorrentError::MalformedResponse(Cow::Borrowed(
@@ -133,11 +721,12 @@ impl Peer {
             }
         };
         let port = match dict.remove("port") {
-            Some(BencodeElem::Integer(port)) => port,
-            Some(_) => {
-                return Err(LavaTorrentError::MalformedResponse(Cow::Borrowed(
-                    r#""port" does not map to an integer."#,
-                )));
+            Some(BencodeElem::Integer(port)) => Self::validate_port(port)?,
+            Some(other) => {
+                return Err(LavaTorrentError::MalformedResponse(Cow::Owned(format!(
+                    r#""port" maps to {}, expected Integer."#,
+                    other.kind()
+                ))));
             }
             None => {
                 return Err(LavaTorrentError::MalformedResponse(Cow::Borrowed(
@@ -147,9 +736,9 @@ impl Peer {
         };
         let extra_fields = if dict.is_empty() { None } else { Some(dict) };
 
-        let ip = match ip.parse::<IpAddr>() {
-            Ok(ip) => ip,
-            Err(_) => {
+        let addr = match PeerAddr::parse(&ip, port) {
+            Some(addr) => addr,
+            None => {
                 return Err(LavaTorrentError::MalformedResponse(Cow::Borrowed(
                     r#""ip" is invalid."#,
                 )));
@@ -158,7 +747,7 @@ impl Peer {
 
         Ok(Peer {
             id,
-            addr: SocketAddr::from((ip, port as u16)),
+            addr,
             extra_fields,
         })
     }
@@ -166,7 +755,10 @@ impl Peer {
     /// Parse `bytes` and return the extracted `Peer`.
     ///
     /// `bytes` must contain exactly 6 bytes.
-    fn from_bytes<B>(bytes: B) -> Peer
+    ///
+    /// If the port is `0`, then `Err(error)` will be returned
+    /// (for the same reason as in `from_dict()`).
+    fn from_bytes<B>(bytes: B) -> Result<Peer, LavaTorrentError>
     where
         B: AsRef<[u8]>,
     {
@@ -180,56 +772,118 @@ impl Peer {
 
         let ip = Ipv4Addr::from(u32::from_be_bytes(bytes[..4].try_into().unwrap()));
         let port = u16::from_be_bytes(bytes[4..].try_into().unwrap());
+        let port = Self::validate_port(i64::from(port))?;
 
-        Peer {
-            id: None,
-            addr: SocketAddr::from((ip, port)),
-            extra_fields: None,
+        Ok(Peer::new(SocketAddr::from((ip, port))))
+    }
+
+    fn validate_port(port: i64) -> Result<u16, LavaTorrentError> {
+        match u16::try_from(port) {
+            Ok(port) if port != 0 => Ok(port),
+            _ => Err(LavaTorrentError::MalformedResponse(Cow::Owned(format!(
+                r#""port" [{}] is not within 1..=65535."#,
+                port
+            )))),
         }
     }
 }
 
 impl TrackerResponse {
+    /// Create a `TrackerResponse::Success` with the given `interval`
+    /// and `peers`, and every other field `None` (or `0` for counts).
+    ///
+    /// This is mostly useful for faking responses in tests:
+    ///
+    /// ```
+    /// use lava_torrent::tracker::{Peer, TrackerResponse};
+    ///
+    /// let peer = Peer::with_id(([10, 0, 0, 1], 6881).into(), b"-LT0001-abcdefghijkl".to_vec());
+    /// let response = TrackerResponse::success_minimal(1800, vec![peer]);
+    /// assert!(matches!(response, TrackerResponse::Success { ref peers, .. } if peers.len() == 1));
+    /// ```
+    ///
+    /// See [`TrackerResponseBuilder`] for setting the other fields.
+    ///
+    /// [`TrackerResponseBuilder`]: struct.TrackerResponseBuilder.html
+    pub fn success_minimal(interval: Integer, peers: Vec<Peer>) -> TrackerResponse {
+        TrackerResponseBuilder::new(interval)
+            .set_peers(peers)
+            .build()
+    }
+
     /// Parse `bytes` and return the extracted `TrackerResponse`.
     ///
+    /// Unlike torrents, tracker responses are parsed leniently: a leading
+    /// UTF-8 BOM is skipped, and anything after the response is ignored
+    /// (see `trailing_bytes`). The response itself must be valid though.
+    ///
+    /// The default [`ResponseLimits`] are enforced.
+    ///
     /// If `bytes` is missing any required field (e.g. `interval`), or if any other
     /// error is encountered (e.g. `IOError`), then `Err(error)` will be returned.
+    ///
+    /// [`ResponseLimits`]: struct.ResponseLimits.html
     pub fn from_bytes<B>(bytes: B) -> Result<TrackerResponse, LavaTorrentError>
     where
         B: AsRef<[u8]>,
     {
-        let mut parsed = BencodeElem::from_bytes(bytes)?;
-        if parsed.len() != 1 {
-            return Err(LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
-                "Tracker response should contain 1 and only 1 top-level element, {} found.",
-                parsed.len()
-            ))));
-        }
-        let mut parsed = match parsed.remove(0) {
-            BencodeElem::Dictionary(dict) => dict,
-            _ => {
-                return Err(LavaTorrentError::MalformedResponse(Cow::Borrowed(
-                    "Tracker response doesn't contain a dictionary.",
-                )));
-            }
-        };
+        Self::from_bytes_with_limits(bytes, &ResponseLimits::default())
+    }
+
+    /// Same as [`from_bytes()`](#method.from_bytes), but with custom `limits`.
+    pub fn from_bytes_with_limits<B>(
+        bytes: B,
+        limits: &ResponseLimits,
+    ) -> Result<TrackerResponse, LavaTorrentError>
+    where
+        B: AsRef<[u8]>,
+    {
+        let (parsed, trailing_bytes) = parse_top_level(bytes.as_ref(), "Tracker response", limits)?;
+        Self::from_dict(parsed, trailing_bytes, limits)
+    }
+
+    /// Extract a `TrackerResponse` from an already-parsed `elem`.
+    ///
+    /// `elem` must be a dictionary, and is checked in the same way as
+    /// in [`from_bytes()`](#method.from_bytes), except that its size in
+    /// bytes is unknown and thus not limited. `trailing_bytes` is 0.
+    pub fn from_bencode(elem: BencodeElem) -> Result<TrackerResponse, LavaTorrentError> {
+        Self::from_dict(
+            into_dictionary(elem, "Tracker response")?,
+            0,
+            &ResponseLimits::default(),
+        )
+    }
 
+    fn from_dict(
+        mut parsed: Dictionary,
+        trailing_bytes: usize,
+        limits: &ResponseLimits,
+    ) -> Result<TrackerResponse, LavaTorrentError> {
         match parsed.remove("failure reason") {
             Some(BencodeElem::String(reason)) => return Ok(TrackerResponse::Failure { reason }),
-            Some(_) => {
-                return Err(LavaTorrentError::MalformedResponse(Cow::Borrowed(
-                    r#""failure reason" does not map to a string (or maps to invalid UTF8)."#,
-                )));
+            Some(BencodeElem::Bytes(bytes)) => {
+                return Err(LavaTorrentError::MalformedResponse(Cow::Owned(format!(
+                    r#""failure reason" maps to {}."#,
+                    util::describe_invalid_utf8(&bytes)
+                ))));
+            }
+            Some(other) => {
+                return Err(LavaTorrentError::MalformedResponse(Cow::Owned(format!(
+                    r#""failure reason" maps to {}, expected String."#,
+                    other.kind()
+                ))));
             }
             None => (),
         }
 
         let interval = match parsed.remove("interval") {
             Some(BencodeElem::Integer(interval)) => interval,
-            Some(_) => {
-                return Err(LavaTorrentError::MalformedResponse(Cow::Borrowed(
-                    r#""interval" does not map to an integer."#,
-                )));
+            Some(other) => {
+                return Err(LavaTorrentError::MalformedResponse(Cow::Owned(format!(
+                    r#""interval" maps to {}, expected Integer."#,
+                    other.kind()
+                ))));
             }
             None => {
                 return Err(LavaTorrentError::MalformedResponse(Cow::Borrowed(
@@ -237,13 +891,18 @@ impl TrackerResponse {
                 )));
             }
         };
-        let peers = match parsed.remove("peers") {
-            Some(BencodeElem::List(list)) => Self::extract_peers_from_list(list)?,
-            Some(BencodeElem::Bytes(bytes)) => Self::extract_peers_from_bytes(bytes)?,
-            Some(_) => {
-                return Err(LavaTorrentError::MalformedResponse(Cow::Borrowed(
-                    r#""peers" does not map to a dict or a string of bytes."#,
-                )));
+        let (peers, skipped_peers) = match parsed.remove("peers") {
+            Some(BencodeElem::List(list)) => Self::extract_peers_from_list(list, limits)?,
+            Some(BencodeElem::Bytes(bytes)) => Self::extract_peers_from_bytes(bytes, limits)?,
+            // compact peers that happen to be valid utf8 (e.g. no peers at all)
+            Some(BencodeElem::String(string)) => {
+                Self::extract_peers_from_bytes(string.into_bytes(), limits)?
+            }
+            Some(other) => {
+                return Err(LavaTorrentError::MalformedResponse(Cow::Owned(format!(
+                    r#""peers" maps to {}, expected List or String/Bytes."#,
+                    other.kind()
+                ))));
             }
             None => {
                 return Err(LavaTorrentError::MalformedResponse(Cow::Borrowed(
@@ -253,49 +912,67 @@ impl TrackerResponse {
         };
         let warning = match parsed.remove("warning") {
             Some(BencodeElem::String(warning)) => Some(warning),
-            Some(_) => {
-                return Err(LavaTorrentError::MalformedResponse(Cow::Borrowed(
-                    r#""warning" does not map to a string (or maps to invalid UTF8)."#,
-                )));
+            Some(BencodeElem::Bytes(bytes)) => {
+                return Err(LavaTorrentError::MalformedResponse(Cow::Owned(format!(
+                    r#""warning" maps to {}."#,
+                    util::describe_invalid_utf8(&bytes)
+                ))));
+            }
+            Some(other) => {
+                return Err(LavaTorrentError::MalformedResponse(Cow::Owned(format!(
+                    r#""warning" maps to {}, expected String."#,
+                    other.kind()
+                ))));
             }
             None => None,
         };
         let min_interval = match parsed.remove("min interval") {
             Some(BencodeElem::Integer(min_interval)) => Some(min_interval),
-            Some(_) => {
-                return Err(LavaTorrentError::MalformedResponse(Cow::Borrowed(
-                    r#""min interval" does not map to an integer."#,
-                )));
+            Some(other) => {
+                return Err(LavaTorrentError::MalformedResponse(Cow::Owned(format!(
+                    r#""min interval" maps to {}, expected Integer."#,
+                    other.kind()
+                ))));
             }
             None => None,
         };
         let tracker_id = match parsed.remove("tracker id") {
             Some(BencodeElem::String(tracker_id)) => Some(tracker_id),
-            Some(_) => {
-                return Err(LavaTorrentError::MalformedResponse(Cow::Borrowed(
-                    r#""tracker id" does not map to a string (or maps to invalid UTF8)."#,
-                )));
+            Some(BencodeElem::Bytes(bytes)) => {
+                return Err(LavaTorrentError::MalformedResponse(Cow::Owned(format!(
+                    r#""tracker id" maps to {}."#,
+                    util::describe_invalid_utf8(&bytes)
+                ))));
+            }
+            Some(other) => {
+                return Err(LavaTorrentError::MalformedResponse(Cow::Owned(format!(
+                    r#""tracker id" maps to {}, expected String."#,
+                    other.kind()
+                ))));
             }
             None => None,
         };
         let complete = match parsed.remove("complete") {
             Some(BencodeElem::Integer(complete)) => Some(complete),
-            Some(_) => {
-                return Err(LavaTorrentError::MalformedResponse(Cow::Borrowed(
-                    r#""complete" does not map to an integer."#,
-                )));
+            Some(other) => {
+                return Err(LavaTorrentError::MalformedResponse(Cow::Owned(format!(
+                    r#""complete" maps to {}, expected Integer."#,
+                    other.kind()
+                ))));
             }
             None => None,
         };
         let incomplete = match parsed.remove("incomplete") {
             Some(BencodeElem::Integer(incomplete)) => Some(incomplete),
-            Some(_) => {
-                return Err(LavaTorrentError::MalformedResponse(Cow::Borrowed(
-                    r#""incomplete" does not map to an integer."#,
-                )));
+            Some(other) => {
+                return Err(LavaTorrentError::MalformedResponse(Cow::Owned(format!(
+                    r#""incomplete" maps to {}, expected Integer."#,
+                    other.kind()
+                ))));
             }
             None => None,
         };
+        check_extra_fields(&parsed, "Tracker response", limits)?;
         let extra_fields = if parsed.is_empty() {
             None
         } else {
@@ -310,38 +987,109 @@ impl TrackerResponse {
             tracker_id,
             complete,
             incomplete,
+            skipped_peers,
+            trailing_bytes,
             extra_fields,
         })
     }
 
-    fn extract_peers_from_list(list: Vec<BencodeElem>) -> Result<Vec<Peer>, LavaTorrentError> {
-        list.into_iter()
-            .map(|elem| match elem {
-                BencodeElem::Dictionary(dict) => Ok(Peer::from_dict(dict)?),
-                _ => Err(LavaTorrentError::MalformedResponse(Cow::Borrowed(
-                    r#""peers" contains a non-dictionary element."#,
-                ))),
-            })
-            .collect()
+    /// Return a `Display`-able version of `self` with all peer addresses
+    /// masked (see [the module docs](index.html#privacy)), e.g. for logging.
+    ///
+    /// This is the same as formatting `self` with `{:#}`. Counts,
+    /// intervals, and everything else are shown as usual.
+    pub fn display_anonymized(&self) -> impl fmt::Display + '_ {
+        struct Anonymized<'a>(&'a TrackerResponse);
+
+        impl fmt::Display for Anonymized<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "{:#}", self.0)
+            }
+        }
+
+        Anonymized(self)
+    }
+
+    // Peer entries with an out-of-range port are skipped, and the # of
+    // skipped entries is returned alongside the valid peers. Any other
+    // problem with an entry fails the entire response.
+    fn extract_peers_from_list(
+        list: Vec<BencodeElem>,
+        limits: &ResponseLimits,
+    ) -> Result<(Vec<Peer>, usize), LavaTorrentError> {
+        let n_peers = Self::limit_peers(list.len(), limits)?;
+        let mut peers = Vec::with_capacity(n_peers);
+        let mut skipped = list.len() - n_peers;
+
+        for elem in list.into_iter().take(n_peers) {
+            match elem {
+                BencodeElem::Dictionary(dict) => match dict.get("port") {
+                    Some(BencodeElem::Integer(port)) if Peer::validate_port(*port).is_err() => {
+                        skipped += 1;
+                    }
+                    _ => peers.push(Peer::from_dict(dict)?),
+                },
+                other => {
+                    return Err(LavaTorrentError::MalformedResponse(Cow::Owned(format!(
+                        r#""peers" contains a {} element, expected Dictionary."#,
+                        other.kind()
+                    ))));
+                }
+            }
+        }
+        Ok((peers, skipped))
     }
 
-    fn extract_peers_from_bytes(bytes: Vec<u8>) -> Result<Vec<Peer>, LavaTorrentError> {
-        if (bytes.len() % 6) != 0 {
+    // Same as `extract_peers_from_list()`, peers with port 0 are skipped and counted.
+    fn extract_peers_from_bytes(
+        bytes: Vec<u8>,
+        limits: &ResponseLimits,
+    ) -> Result<(Vec<Peer>, usize), LavaTorrentError> {
+        if !bytes.len().is_multiple_of(6) {
             return Err(LavaTorrentError::MalformedResponse(Cow::Borrowed(
                 r#"Compact "peers" contains incorrect number of bytes"#,
             )));
         }
 
-        let n_peers = bytes.len() / 6;
+        let n_entries = bytes.len() / 6;
+        let n_peers = Self::limit_peers(n_entries, limits)?;
         let mut peers = Vec::with_capacity(n_peers);
+        let mut skipped = n_entries - n_peers;
         for i in 0..(n_peers) {
-            peers.push(Peer::from_bytes(bytes[(i * 6)..((i + 1) * 6)].as_ref()));
+            match Peer::from_bytes(bytes[(i * 6)..((i + 1) * 6)].as_ref()) {
+                Ok(peer) => peers.push(peer),
+                Err(_) => skipped += 1,
+            }
+        }
+        Ok((peers, skipped))
+    }
+
+    // Return the # of the `n_entries` peer entries to extract.
+    fn limit_peers(n_entries: usize, limits: &ResponseLimits) -> Result<usize, LavaTorrentError> {
+        if n_entries <= limits.max_peers {
+            Ok(n_entries)
+        } else if limits.truncate_peers {
+            Ok(limits.max_peers)
+        } else {
+            Err(LavaTorrentError::MalformedResponse(Cow::Owned(format!(
+                r#""peers" has {} entries, exceeding the limit of {} (max_peers)."#,
+                n_entries, limits.max_peers
+            ))))
         }
-        Ok(peers)
     }
 }
 
 impl SwarmMetadata {
+    /// Create a `SwarmMetadata` with the given counts and no extra fields.
+    pub fn new(complete: Integer, incomplete: Integer, downloaded: Integer) -> SwarmMetadata {
+        SwarmMetadata {
+            complete,
+            incomplete,
+            downloaded,
+            extra_fields: None,
+        }
+    }
+
     /// Go through `dict` and return the extracted `SwarmMetadata`.
     ///
     /// If `dict` is missing any required field (e.g. `complete`), then
@@ -351,10 +1099,11 @@ impl SwarmMetadata {
     ) -> Result<SwarmMetadata, LavaTorrentError> {
         let complete = match dict.remove("complete") {
             Some(BencodeElem::Integer(complete)) => complete,
-            Some(_) => {
-                return Err(LavaTorrentError::MalformedResponse(Cow::Borrowed(
-                    r#""complete" does not map to an integer."#,
-                )));
+            Some(other) => {
+                return Err(LavaTorrentError::MalformedResponse(Cow::Owned(format!(
+                    r#""complete" maps to {}, expected Integer."#,
+                    other.kind()
+                ))));
             }
             None => {
                 return Err(LavaTorrentError::MalformedResponse(Cow::Borrowed(
@@ -364,10 +1113,11 @@ impl SwarmMetadata {
         };
         let incomplete = match dict.remove("incomplete") {
             Some(BencodeElem::Integer(incomplete)) => incomplete,
-            Some(_) => {
-                return Err(LavaTorrentError::MalformedResponse(Cow::Borrowed(
-                    r#""incomplete" does not map to an integer."#,
-                )));
+            Some(other) => {
+                return Err(LavaTorrentError::MalformedResponse(Cow::Owned(format!(
+                    r#""incomplete" maps to {}, expected Integer."#,
+                    other.kind()
+                ))));
             }
             None => {
                 return Err(LavaTorrentError::MalformedResponse(Cow::Borrowed(
@@ -377,10 +1127,11 @@ impl SwarmMetadata {
         };
         let downloaded = match dict.remove("downloaded") {
             Some(BencodeElem::Integer(downloaded)) => downloaded,
-            Some(_) => {
-                return Err(LavaTorrentError::MalformedResponse(Cow::Borrowed(
-                    r#""downloaded" does not map to an integer."#,
-                )));
+            Some(other) => {
+                return Err(LavaTorrentError::MalformedResponse(Cow::Owned(format!(
+                    r#""downloaded" maps to {}, expected Integer."#,
+                    other.kind()
+                ))));
             }
             None => {
                 return Err(LavaTorrentError::MalformedResponse(Cow::Borrowed(
@@ -391,98 +1142,751 @@ impl SwarmMetadata {
         let extra_fields = if dict.is_empty() { None } else { Some(dict) };
 
         Ok(SwarmMetadata {
-            complete,
-            incomplete,
-            downloaded,
             extra_fields,
+            ..SwarmMetadata::new(complete, incomplete, downloaded)
         })
     }
 }
 
-impl TrackerScrapeResponse {
-    /// Parse `bytes` and return the extracted `TrackerScrapeResponse`.
-    ///
-    /// If `bytes` is missing any required field (e.g. `files`), or if any other
-    /// error is encountered (e.g. `IOError`), then `Err(error)` will be returned.
-    pub fn from_bytes<B>(bytes: B) -> Result<TrackerScrapeResponse, LavaTorrentError>
-    where
-        B: AsRef<[u8]>,
-    {
-        let mut parsed = BencodeElem::from_bytes(bytes)?;
-        if parsed.len() != 1 {
-            return Err(LavaTorrentError::MalformedTorrent(Cow::Owned(format!(
-                "Tracker scrape response should contain 1 and only 1 top-level element, {} found.",
-                parsed.len()
-            ))));
-        }
-        let mut parsed = match parsed.remove(0) {
-            BencodeElem::Dictionary(dict) => dict,
-            _ => {
-                return Err(LavaTorrentError::MalformedResponse(Cow::Borrowed(
-                    "Tracker scrape response doesn't contain a dictionary.",
-                )));
-            }
-        };
+impl Default for ResponseLimits {
+    fn default() -> ResponseLimits {
+        ResponseLimits {
+            max_response_bytes: 1 << 20,
+            max_peers: 10_000,
+            max_extra_fields: 64,
+            truncate_peers: false,
+        }
+    }
+}
 
-        let files = match parsed.remove("files") {
-            Some(BencodeElem::RawDictionary(dict)) => dict,
-            Some(_) => {
-                return Err(LavaTorrentError::MalformedResponse(Cow::Borrowed(
-                    r#""files" does not map to a raw dict."#,
-                )));
-            }
-            None => {
+impl Default for SwarmMetadata {
+    /// All counts are `0`.
+    fn default() -> SwarmMetadata {
+        SwarmMetadata::new(0, 0, 0)
+    }
+}
+
+impl TrackerResponseBuilder {
+    /// Create a new `TrackerResponseBuilder` for a successful
+    /// response with the given `interval`.
+    pub fn new(interval: Integer) -> TrackerResponseBuilder {
+        TrackerResponseBuilder {
+            interval,
+            peers: Vec::new(),
+            warning: None,
+            min_interval: None,
+            tracker_id: None,
+            complete: None,
+            incomplete: None,
+            skipped_peers: 0,
+            trailing_bytes: 0,
+            extra_fields: None,
+        }
+    }
+
+    /// Build a `TrackerResponse::Success` from this `TrackerResponseBuilder`.
+    pub fn build(self) -> TrackerResponse {
+        TrackerResponse::Success {
+            interval: self.interval,
+            peers: self.peers,
+            warning: self.warning,
+            min_interval: self.min_interval,
+            tracker_id: self.tracker_id,
+            complete: self.complete,
+            incomplete: self.incomplete,
+            skipped_peers: self.skipped_peers,
+            trailing_bytes: self.trailing_bytes,
+            extra_fields: self.extra_fields,
+        }
+    }
+
+    /// Set the interval (in seconds) between regular requests.
+    ///
+    /// Calling this method multiple times will simply override previous settings.
+    pub fn set_interval(self, interval: Integer) -> TrackerResponseBuilder {
+        TrackerResponseBuilder { interval, ..self }
+    }
+
+    /// Set the peers of the response.
+    ///
+    /// Calling this method multiple times will simply override previous settings.
+    pub fn set_peers(self, peers: Vec<Peer>) -> TrackerResponseBuilder {
+        TrackerResponseBuilder { peers, ..self }
+    }
+
+    /// Add a peer to the response.
+    pub fn add_peer(mut self, peer: Peer) -> TrackerResponseBuilder {
+        self.peers.push(peer);
+        self
+    }
+
+    /// Set the warning message.
+    ///
+    /// Calling this method multiple times will simply override previous settings.
+    pub fn set_warning(self, warning: String) -> TrackerResponseBuilder {
+        TrackerResponseBuilder {
+            warning: Some(warning),
+            ..self
+        }
+    }
+
+    /// Set the minimum announce interval.
+    ///
+    /// Calling this method multiple times will simply override previous settings.
+    pub fn set_min_interval(self, min_interval: Integer) -> TrackerResponseBuilder {
+        TrackerResponseBuilder {
+            min_interval: Some(min_interval),
+            ..self
+        }
+    }
+
+    /// Set the tracker id.
+    ///
+    /// Calling this method multiple times will simply override previous settings.
+    pub fn set_tracker_id(self, tracker_id: String) -> TrackerResponseBuilder {
+        TrackerResponseBuilder {
+            tracker_id: Some(tracker_id),
+            ..self
+        }
+    }
+
+    /// Set the number of seeders.
+    ///
+    /// Calling this method multiple times will simply override previous settings.
+    pub fn set_complete(self, complete: Integer) -> TrackerResponseBuilder {
+        TrackerResponseBuilder {
+            complete: Some(complete),
+            ..self
+        }
+    }
+
+    /// Set the number of leechers.
+    ///
+    /// Calling this method multiple times will simply override previous settings.
+    pub fn set_incomplete(self, incomplete: Integer) -> TrackerResponseBuilder {
+        TrackerResponseBuilder {
+            incomplete: Some(incomplete),
+            ..self
+        }
+    }
+
+    /// Set the number of skipped peer entries.
+    ///
+    /// Calling this method multiple times will simply override previous settings.
+    pub fn set_skipped_peers(self, skipped_peers: usize) -> TrackerResponseBuilder {
+        TrackerResponseBuilder {
+            skipped_peers,
+            ..self
+        }
+    }
+
+    /// Set the number of trailing bytes.
+    ///
+    /// Calling this method multiple times will simply override previous settings.
+    pub fn set_trailing_bytes(self, trailing_bytes: usize) -> TrackerResponseBuilder {
+        TrackerResponseBuilder {
+            trailing_bytes,
+            ..self
+        }
+    }
+
+    /// Set the extra fields.
+    ///
+    /// Calling this method multiple times will simply override previous settings.
+    pub fn set_extra_fields(self, extra_fields: Dictionary) -> TrackerResponseBuilder {
+        TrackerResponseBuilder {
+            extra_fields: Some(extra_fields),
+            ..self
+        }
+    }
+}
+
+impl SwarmStats {
+    /// Combine `self` and `other` by taking the larger value of each field.
+    ///
+    /// This is useful when several trackers report on the same swarm,
+    /// as each of them only sees part of it. `None` is ignored, i.e. a field
+    /// is only `None` if it is `None` in both `self` and `other`.
+    pub fn max(&self, other: &SwarmStats) -> SwarmStats {
+        fn max(a: Option<u64>, b: Option<u64>) -> Option<u64> {
+            match (a, b) {
+                (Some(a), Some(b)) => Some(a.max(b)),
+                (a, b) => a.or(b),
+            }
+        }
+
+        SwarmStats {
+            seeders: max(self.seeders, other.seeders),
+            leechers: max(self.leechers, other.leechers),
+            completed: max(self.completed, other.completed),
+        }
+    }
+
+    /// Combine `self` and `other` by adding up each field.
+    ///
+    /// This is useful when `self` and `other` describe disjoint swarms.
+    /// `None` means unknown, so a field is `None` if it is `None` in
+    /// either `self` or `other`. Sums saturate at `u64::MAX`.
+    pub fn sum(&self, other: &SwarmStats) -> SwarmStats {
+        fn sum(a: Option<u64>, b: Option<u64>) -> Option<u64> {
+            Some(a?.saturating_add(b?))
+        }
+
+        SwarmStats {
+            seeders: sum(self.seeders, other.seeders),
+            leechers: sum(self.leechers, other.leechers),
+            completed: sum(self.completed, other.completed),
+        }
+    }
+
+    /// Return the # of seeders per leecher.
+    ///
+    /// `None` will be returned if either number is unknown,
+    /// or if there are no leechers.
+    pub fn ratio(&self) -> Option<f64> {
+        match (self.seeders, self.leechers) {
+            (Some(seeders), Some(leechers)) if leechers > 0 => {
+                Some(seeders as f64 / leechers as f64)
+            }
+            _ => None,
+        }
+    }
+
+    // Negative counts are bogus, so treat them as unknown.
+    fn count(n: Integer) -> Option<u64> {
+        u64::try_from(n).ok()
+    }
+}
+
+impl From<&SwarmMetadata> for SwarmStats {
+    fn from(metadata: &SwarmMetadata) -> SwarmStats {
+        SwarmStats {
+            seeders: SwarmStats::count(metadata.complete),
+            leechers: SwarmStats::count(metadata.incomplete),
+            completed: SwarmStats::count(metadata.downloaded),
+        }
+    }
+}
+
+impl TryFrom<&TrackerResponse> for SwarmStats {
+    type Error = LavaTorrentError;
+
+    /// Extract the swarm stats from a `TrackerResponse::Success`.
+    ///
+    /// Announce responses do not report `completed`, so it is always `None`.
+    /// If `response` is a `TrackerResponse::Failure`, then `Err(error)`
+    /// will be returned.
+    fn try_from(response: &TrackerResponse) -> Result<SwarmStats, LavaTorrentError> {
+        match *response {
+            TrackerResponse::Success {
+                complete,
+                incomplete,
+                ..
+            } => Ok(SwarmStats {
+                seeders: complete.and_then(SwarmStats::count),
+                leechers: incomplete.and_then(SwarmStats::count),
+                completed: None,
+            }),
+            TrackerResponse::Failure { .. } => Err(LavaTorrentError::InvalidArgument(
+                Cow::Borrowed("Failure responses contain no swarm stats."),
+            )),
+        }
+    }
+}
+
+impl TrackerScrapeResponse {
+    /// Parse `bytes` and return the extracted `TrackerScrapeResponse`.
+    ///
+    /// Parsed as leniently as [`TrackerResponse::from_bytes()`], and
+    /// the default [`ResponseLimits`] are enforced.
+    ///
+    /// If `bytes` is missing any required field (e.g. `files`), or if any other
+    /// error is encountered (e.g. `IOError`), then `Err(error)` will be returned.
+    ///
+    /// [`TrackerResponse::from_bytes()`]: enum.TrackerResponse.html#method.from_bytes
+    /// [`ResponseLimits`]: struct.ResponseLimits.html
+    pub fn from_bytes<B>(bytes: B) -> Result<TrackerScrapeResponse, LavaTorrentError>
+    where
+        B: AsRef<[u8]>,
+    {
+        Self::from_bytes_with_limits(bytes, &ResponseLimits::default())
+    }
+
+    /// Same as [`from_bytes()`](#method.from_bytes), but with custom `limits`.
+    pub fn from_bytes_with_limits<B>(
+        bytes: B,
+        limits: &ResponseLimits,
+    ) -> Result<TrackerScrapeResponse, LavaTorrentError>
+    where
+        B: AsRef<[u8]>,
+    {
+        let (parsed, trailing_bytes) =
+            parse_top_level(bytes.as_ref(), "Tracker scrape response", limits)?;
+        Self::from_dict(parsed, trailing_bytes, limits)
+    }
+
+    /// Extract a `TrackerScrapeResponse` from an already-parsed `elem`.
+    ///
+    /// `elem` must be a dictionary, and is checked in the same way as
+    /// in [`from_bytes()`](#method.from_bytes), except that its size in
+    /// bytes is unknown and thus not limited. `trailing_bytes` is 0.
+    pub fn from_bencode(elem: BencodeElem) -> Result<TrackerScrapeResponse, LavaTorrentError> {
+        Self::from_dict(
+            into_dictionary(elem, "Tracker scrape response")?,
+            0,
+            &ResponseLimits::default(),
+        )
+    }
+
+    fn from_dict(
+        mut parsed: Dictionary,
+        trailing_bytes: usize,
+        limits: &ResponseLimits,
+    ) -> Result<TrackerScrapeResponse, LavaTorrentError> {
+        // info hashes that happen to be valid utf8 end up
+        // as ordinary keys, so accept both kinds of dictionary
+        let files = match parsed.remove("files").map(BencodeElem::into_raw_dictionary) {
+            Some(Ok(dict)) => dict,
+            Some(Err(other)) => {
+                return Err(LavaTorrentError::MalformedResponse(Cow::Owned(format!(
+                    r#""files" maps to {}, expected Dictionary or RawDictionary."#,
+                    other.kind()
+                ))));
+            }
+            None => {
                 return Err(LavaTorrentError::MalformedResponse(Cow::Borrowed(
                     r#""files" does not exist."#,
                 )));
             }
         };
+        check_extra_fields(&parsed, "Tracker scrape response", limits)?;
         let extra_fields = if parsed.is_empty() {
             None
         } else {
             Some(parsed)
         };
 
-        let files = files
-            .into_iter()
-            .map(|(k, v)| match v {
-                BencodeElem::Dictionary(dict) => Ok((k, SwarmMetadata::from_dict(dict)?)),
-                _ => Err(LavaTorrentError::MalformedResponse(Cow::Owned(format!(
-                    r#"swarm metadata for {} is not a dictionary."#,
-                    k.iter().map(|b| format!("{:x}", b)).format("")
-                )))),
-            })
-            .collect::<Result<HashMap<Vec<u8>, SwarmMetadata>, LavaTorrentError>>()?;
+        let mut result = HashMap::with_capacity(files.len());
+        let mut from_hex = Vec::new();
+        for (k, v) in files {
+            let metadata = match v {
+                BencodeElem::Dictionary(dict) => SwarmMetadata::from_dict(dict)?,
+                _ => {
+                    return Err(LavaTorrentError::MalformedResponse(Cow::Owned(format!(
+                        r#"swarm metadata for {} is not a dictionary."#,
+                        hex::display(&k)
+                    ))))
+                }
+            };
+            match Self::decode_info_hash(&k)? {
+                (info_hash, true) => from_hex.push((info_hash, metadata)),
+                (info_hash, false) => {
+                    result.insert(info_hash, metadata);
+                }
+            }
+        }
+        // if an info hash is present in both forms, the raw one wins
+        for (info_hash, metadata) in from_hex {
+            result.entry(info_hash).or_insert(metadata);
+        }
 
         Ok(TrackerScrapeResponse {
-            files,
+            files: result,
+            trailing_bytes,
             extra_fields,
         })
     }
+
+    // Some trackers (and proxies re-serializing JSON) key `files` by
+    // 40-char hex strings instead of raw 20-byte info hashes.
+    //
+    // Return the info hash `key` stands for, and whether it is hex.
+    fn decode_info_hash(key: &[u8]) -> Result<(InfoHash, bool), LavaTorrentError> {
+        const INFO_HASH_LENGTH: usize = 20;
+
+        if let Ok(info_hash) = InfoHash::try_from(key) {
+            Ok((info_hash, false))
+        } else if let Some(info_hash) = std::str::from_utf8(key)
+            .ok()
+            .filter(|key| key.len() == INFO_HASH_LENGTH * 2)
+            .and_then(|key| hex::decode(key).ok())
+        {
+            // the length is checked above
+            Ok((InfoHash::try_from(info_hash.as_slice()).unwrap(), true))
+        } else {
+            Err(LavaTorrentError::MalformedResponse(Cow::Owned(format!(
+                r#""files" has a key of {} bytes ({:?}), expected a 20-byte info hash or 40 hex chars."#,
+                key.len(),
+                String::from_utf8_lossy(key),
+            ))))
+        }
+    }
+}
+
+impl AnnounceEvent {
+    /// Return the value of `event` for this event.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AnnounceEvent::Started => "started",
+            AnnounceEvent::Stopped => "stopped",
+            AnnounceEvent::Completed => "completed",
+            AnnounceEvent::Paused => "paused",
+            AnnounceEvent::Empty => "",
+        }
+    }
+}
+
+impl AnnounceRequest {
+    /// Parse the query string of an announce request (with or
+    /// without the leading `?`) and return the extracted `AnnounceRequest`.
+    ///
+    /// Values are percent-decoded into raw bytes, so `info_hash` and
+    /// `peer_id` do not have to be valid UTF-8 once decoded (they
+    /// usually aren't). `+` is decoded as a space.
+    ///
+    /// If the query is missing any required parameter (e.g. `info_hash`),
+    /// if a parameter is repeated, or if a value is invalid (e.g. `port` is
+    /// not within `1..=65535`), then `Err(error)` will be returned.
+    pub fn from_query(query: &str) -> Result<AnnounceRequest, LavaTorrentError> {
+        let query = query.strip_prefix('?').unwrap_or(query);
+        let mut params = HashMap::new();
+        for param in query.split('&').filter(|param| !param.is_empty()) {
+            let (name, value) = param.split_once('=').unwrap_or((param, ""));
+            let name = String::from_utf8_lossy(&Self::decode_component(name)).into_owned();
+            if params
+                .insert(name.clone(), Self::decode_component(value))
+                .is_some()
+            {
+                return Err(LavaTorrentError::InvalidArgument(Cow::Owned(format!(
+                    r#""{}" appears more than once."#,
+                    name
+                ))));
+            }
+        }
+
+        let info_hash = Self::take_raw_20(&mut params, "info_hash")?;
+        let peer_id = Self::take_raw_20(&mut params, "peer_id")?;
+        let port =
+            Self::take_number::<u64>(&mut params, "port")?.ok_or_else(|| Self::missing("port"))?;
+        let port = match u16::try_from(port) {
+            Ok(port) if port != 0 => port,
+            _ => {
+                return Err(LavaTorrentError::InvalidArgument(Cow::Owned(format!(
+                    r#""port" [{}] is not within 1..=65535."#,
+                    port
+                ))));
+            }
+        };
+        let uploaded =
+            Self::take_number(&mut params, "uploaded")?.ok_or_else(|| Self::missing("uploaded"))?;
+        let downloaded = Self::take_number(&mut params, "downloaded")?
+            .ok_or_else(|| Self::missing("downloaded"))?;
+        let left = Self::take_number(&mut params, "left")?.ok_or_else(|| Self::missing("left"))?;
+        let compact = match Self::take_number::<u8>(&mut params, "compact")? {
+            Some(0) => Some(false),
+            Some(1) => Some(true),
+            Some(other) => {
+                return Err(LavaTorrentError::InvalidArgument(Cow::Owned(format!(
+                    r#""compact" [{}] is neither 0 nor 1."#,
+                    other
+                ))));
+            }
+            None => None,
+        };
+        let numwant = Self::take_number(&mut params, "numwant")?;
+        let event = match params.remove("event").as_deref() {
+            Some(b"started") => AnnounceEvent::Started,
+            Some(b"stopped") => AnnounceEvent::Stopped,
+            Some(b"completed") => AnnounceEvent::Completed,
+            Some(b"paused") => AnnounceEvent::Paused,
+            Some(b"") | None => AnnounceEvent::Empty,
+            Some(other) => {
+                return Err(LavaTorrentError::InvalidArgument(Cow::Owned(format!(
+                    r#""event" [{}] is not a known event."#,
+                    String::from_utf8_lossy(other)
+                ))));
+            }
+        };
+        let key = Self::take_string(&mut params, "key")?;
+        let tracker_id = Self::take_string(&mut params, "trackerid")?;
+        let ip = Self::take_string(&mut params, "ip")?;
+
+        Ok(AnnounceRequest {
+            info_hash,
+            peer_id,
+            port,
+            uploaded,
+            downloaded,
+            left,
+            compact,
+            numwant,
+            event,
+            key,
+            tracker_id,
+            ip,
+            extra_params: params,
+        })
+    }
+
+    /// Return the address the client claims to listen on, i.e. `ip`
+    /// and `port`, or `None` if `ip` is not set (trackers use the
+    /// source address of the request in that case).
+    ///
+    /// `ip` is kept as a string by [`from_query()`], as it may also be
+    /// a DNS name (which is not resolved here). If it is neither an IP
+    /// address nor a DNS name, then `Err(error)` will be returned.
+    ///
+    /// [`from_query()`]: #method.from_query
+    pub fn peer_addr(&self) -> Result<Option<PeerAddr>, LavaTorrentError> {
+        match self.ip {
+            Some(ref ip) => match PeerAddr::parse(ip, self.port) {
+                Some(addr) => Ok(Some(addr)),
+                None => Err(LavaTorrentError::InvalidArgument(Cow::Owned(format!(
+                    r#""ip" [{}] is neither an IP address nor a hostname."#,
+                    ip
+                )))),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Encode `self` as the query string of an announce request (without
+    /// the leading `?`), i.e. the inverse of [`from_query()`].
+    ///
+    /// Everything but unreserved characters (`A-Z a-z 0-9 - . _ ~`) is
+    /// percent-encoded. Optional parameters are left out if they are
+    /// `None` (or `Empty` for `event`), and `extra_params` are appended
+    /// in the order of their names.
+    ///
+    /// [`from_query()`]: #method.from_query
+    pub fn to_query(&self) -> String {
+        let mut params = vec![
+            ("info_hash", Cow::Borrowed(&self.info_hash[..])),
+            ("peer_id", Cow::Borrowed(&self.peer_id[..])),
+            ("port", Cow::Owned(self.port.to_string().into_bytes())),
+            (
+                "uploaded",
+                Cow::Owned(self.uploaded.to_string().into_bytes()),
+            ),
+            (
+                "downloaded",
+                Cow::Owned(self.downloaded.to_string().into_bytes()),
+            ),
+            ("left", Cow::Owned(self.left.to_string().into_bytes())),
+        ];
+        if let Some(compact) = self.compact {
+            params.push(("compact", Cow::Borrowed(if compact { b"1" } else { b"0" })));
+        }
+        if let Some(numwant) = self.numwant {
+            params.push(("numwant", Cow::Owned(numwant.to_string().into_bytes())));
+        }
+        if self.event != AnnounceEvent::Empty {
+            params.push(("event", Cow::Borrowed(self.event.as_str().as_bytes())));
+        }
+        for (name, value) in [
+            ("key", &self.key),
+            ("trackerid", &self.tracker_id),
+            ("ip", &self.ip),
+        ] {
+            if let Some(value) = value {
+                params.push((name, Cow::Borrowed(value.as_bytes())));
+            }
+        }
+        params.extend(
+            self.extra_params
+                .iter()
+                .sorted_by_key(|&(name, _)| name)
+                .map(|(name, value)| (name.as_str(), Cow::Borrowed(&value[..]))),
+        );
+
+        params
+            .iter()
+            .format_with("&", |(name, value), f| {
+                f(&format_args!(
+                    "{}={}",
+                    percent_encode(name.as_bytes(), QUERY_COMPONENT),
+                    percent_encode(value, QUERY_COMPONENT)
+                ))
+            })
+            .to_string()
+    }
+
+    fn decode_component(component: &str) -> Vec<u8> {
+        percent_decode_str(&component.replace('+', " ")).collect()
+    }
+
+    fn missing(name: &str) -> LavaTorrentError {
+        LavaTorrentError::InvalidArgument(Cow::Owned(format!(r#""{}" does not exist."#, name)))
+    }
+
+    fn take_raw_20(
+        params: &mut HashMap<String, Vec<u8>>,
+        name: &str,
+    ) -> Result<[u8; 20], LavaTorrentError> {
+        let value = params.remove(name).ok_or_else(|| Self::missing(name))?;
+        <[u8; 20]>::try_from(&value[..]).map_err(|_| {
+            LavaTorrentError::InvalidArgument(Cow::Owned(format!(
+                r#""{}" is {} bytes long, expected 20."#,
+                name,
+                value.len()
+            )))
+        })
+    }
+
+    fn take_number<T>(
+        params: &mut HashMap<String, Vec<u8>>,
+        name: &str,
+    ) -> Result<Option<T>, LavaTorrentError>
+    where
+        T: std::str::FromStr,
+    {
+        match params.remove(name) {
+            Some(value) => match std::str::from_utf8(&value)
+                .ok()
+                .and_then(|v| v.parse().ok())
+            {
+                Some(number) => Ok(Some(number)),
+                None => Err(LavaTorrentError::InvalidArgument(Cow::Owned(format!(
+                    r#""{}" [{}] is not a valid number."#,
+                    name,
+                    String::from_utf8_lossy(&value)
+                )))),
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn take_string(
+        params: &mut HashMap<String, Vec<u8>>,
+        name: &str,
+    ) -> Result<Option<String>, LavaTorrentError> {
+        match params.remove(name).map(String::from_utf8) {
+            Some(Ok(value)) => Ok(Some(value)),
+            Some(Err(_)) => Err(LavaTorrentError::InvalidArgument(Cow::Owned(format!(
+                r#""{}" is not valid UTF-8."#,
+                name
+            )))),
+            None => Ok(None),
+        }
+    }
+}
+
+// Return the top-level dictionary of a tracker response, along with
+// the # of bytes after it that aren't ASCII whitespace.
+//
+// Trackers are not adversarial in the way torrents can be, and quite
+// a few of them prepend a BOM or append junk to their responses, so
+// these are tolerated here instead of in `BencodeElem::from_bytes()`.
+fn parse_top_level(
+    bytes: &[u8],
+    what: &str,
+    limits: &ResponseLimits,
+) -> Result<(Dictionary, usize), LavaTorrentError> {
+    if bytes.len() > limits.max_response_bytes {
+        return Err(LavaTorrentError::MalformedResponse(Cow::Owned(format!(
+            "{} is {} bytes long, exceeding the limit of {} (max_response_bytes).",
+            what,
+            bytes.len(),
+            limits.max_response_bytes
+        ))));
+    }
+
+    let bytes = bytes.strip_prefix(UTF8_BOM).unwrap_or(bytes);
+    if bytes.trim_ascii_end().is_empty() {
+        return Err(LavaTorrentError::MalformedResponse(Cow::Owned(format!(
+            "{} should contain 1 and only 1 top-level element, 0 found.",
+            what
+        ))));
+    }
+
+    let (elem, len) = BencodeElem::from_bytes_prefix(bytes)?;
+    Ok((
+        into_dictionary(elem, what)?,
+        bytes[len..].trim_ascii_end().len(),
+    ))
+}
+
+// `extra` is what is left of a top-level dictionary after
+// extracting all known fields, i.e. its extra fields.
+fn check_extra_fields(
+    extra: &Dictionary,
+    what: &str,
+    limits: &ResponseLimits,
+) -> Result<(), LavaTorrentError> {
+    if extra.len() > limits.max_extra_fields {
+        return Err(LavaTorrentError::MalformedResponse(Cow::Owned(format!(
+            "{} has {} extra fields, exceeding the limit of {} (max_extra_fields).",
+            what,
+            extra.len(),
+            limits.max_extra_fields
+        ))));
+    }
+    Ok(())
+}
+
+fn into_dictionary(elem: BencodeElem, what: &str) -> Result<Dictionary, LavaTorrentError> {
+    match elem {
+        BencodeElem::Dictionary(dict) => Ok(dict),
+        _ => Err(LavaTorrentError::MalformedResponse(Cow::Owned(format!(
+            "{} doesn't contain a dictionary.",
+            what
+        )))),
+    }
 }
 
 impl fmt::Display for Peer {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         if let Some(ref id) = self.id {
-            writeln!(f, "\t-id: {}", id)?;
+            match std::str::from_utf8(id) {
+                Ok(id) => writeln!(f, "\t-id: {}", id)?,
+                Err(_) => writeln!(f, "\t-id: {}", display_bytes_summary(id))?,
+            }
+        }
+        if f.alternate() {
+            writeln!(f, "\t-addr: {:#}", self.addr)?;
+        } else {
+            writeln!(f, "\t-addr: {}", self.addr)?;
         }
-        writeln!(f, "\t-addr: {}", self.addr)?;
 
         if let Some(ref fields) = self.extra_fields {
-            write!(
-                f,
-                "{}",
-                fields
-                    .iter()
-                    .sorted_by_key(|&(key, _)| key.as_bytes())
-                    .format_with("", |(k, v), f| f(&format_args!("-{}: {}\n", k, v)))
-            )?;
+            write!(f, "{}", display_fields(fields))?;
         }
 
         writeln!(f, "\t========================================")
     }
 }
 
+impl fmt::Debug for Peer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Peer")
+            .field("id", &self.id)
+            .field("addr", &self.addr)
+            .field("extra_fields", &self.extra_fields)
+            .finish()
+    }
+}
+
+// `{:#}` masks the address, the same way `Peer` does.
+impl fmt::Display for PeerAddr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            _ if f.alternate() => write!(f, "{}", self.masked()),
+            PeerAddr::Resolved(addr) => write!(f, "{}", addr),
+            PeerAddr::Hostname { host, port } => write!(f, "{}:{}", host, port),
+        }
+    }
+}
+
+impl fmt::Debug for PeerAddr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if debug_full_addresses() {
+            write!(f, "{}", self)
+        } else {
+            write!(f, "{}", self.masked())
+        }
+    }
+}
+
 impl fmt::Display for TrackerResponse {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -494,6 +1898,8 @@ impl fmt::Display for TrackerResponse {
                 tracker_id,
                 complete,
                 incomplete,
+                skipped_peers,
+                trailing_bytes,
                 extra_fields,
             } => {
                 writeln!(f, "-interval: {}", interval)?;
@@ -512,19 +1918,27 @@ impl fmt::Display for TrackerResponse {
                 if let Some(ref incomplete) = incomplete {
                     writeln!(f, "-incomplete: {}", incomplete)?;
                 }
+                if *skipped_peers > 0 {
+                    writeln!(f, "-skipped_peers: {}", skipped_peers)?;
+                }
+                if *trailing_bytes > 0 {
+                    writeln!(f, "-trailing_bytes: {}", trailing_bytes)?;
+                }
 
                 if let Some(ref fields) = extra_fields {
-                    write!(
-                        f,
-                        "{}",
-                        fields
-                            .iter()
-                            .sorted_by_key(|&(key, _)| key.as_bytes())
-                            .format_with("", |(k, v), f| f(&format_args!("-{}: {}\n", k, v)))
-                    )?;
+                    write!(f, "{}", display_fields(fields))?;
                 }
 
-                writeln!(f, "-peers ({}):\n{}", peers.len(), peers.iter().format(""))
+                if f.alternate() {
+                    writeln!(
+                        f,
+                        "-peers ({}):\n{:#}",
+                        peers.len(),
+                        peers.iter().format("")
+                    )
+                } else {
+                    writeln!(f, "-peers ({}):\n{}", peers.len(), peers.iter().format(""))
+                }
             }
             TrackerResponse::Failure { reason } => writeln!(f, "failure: {}", reason),
         }
@@ -538,14 +1952,7 @@ impl fmt::Display for SwarmMetadata {
         writeln!(f, "-downloaded: {}", self.downloaded)?;
 
         if let Some(ref fields) = self.extra_fields {
-            write!(
-                f,
-                "{}",
-                fields
-                    .iter()
-                    .sorted_by_key(|&(key, _)| key.as_bytes())
-                    .format_with("", |(k, v), f| f(&format_args!("-{}: {}\n", k, v)))
-            )?;
+            write!(f, "{}", display_fields(fields))?;
         }
 
         writeln!(f, "========================================")
@@ -561,24 +1968,2351 @@ impl fmt::Display for TrackerScrapeResponse {
                 .iter()
                 .format_with("", |(k, v), f| f(&format_args!(
                     "{}\n{}",
-                    k.iter().map(|b| format!("{:x}", b)).format(""),
+                    hex::display(k),
                     v
                 )))
         )?;
 
+        if self.trailing_bytes > 0 {
+            writeln!(f, "-trailing_bytes: {}", self.trailing_bytes)?;
+        }
+
         if let Some(ref fields) = self.extra_fields {
-            write!(
-                f,
-                "{}",
-                fields
-                    .iter()
-                    .sorted_by_key(|&(key, _)| key.as_bytes())
-                    .format_with("", |(k, v), f| f(&format_args!("-{}: {}\n", k, v)))
-            )?;
+            write!(f, "{}", display_fields(fields))?;
         }
 
         Ok(())
     }
 }
 
-// @todo: add unit tests
+impl fmt::Display for NormalizedTracker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}://", self.scheme)?;
+        if let Some(ref userinfo) = self.userinfo {
+            write!(f, "{}", userinfo)?;
+        }
+        write!(f, "{}", self.host)?;
+        if let Some(port) = self.port {
+            write!(f, ":{}", port)?;
+        }
+        write!(f, "{}", self.path)
+    }
+}
+
+impl fmt::Debug for AnnounceRequest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // `ip` may also be a DNS name, which can't be masked partially
+        let ip = self.ip.as_ref().map(|ip| {
+            if debug_full_addresses() {
+                ip.clone()
+            } else if let Ok(ip) = ip.parse() {
+                mask_ip(&ip)
+            } else {
+                "REDACTED".to_owned()
+            }
+        });
+
+        f.debug_struct("AnnounceRequest")
+            .field("info_hash", &self.info_hash)
+            .field("peer_id", &self.peer_id)
+            .field("port", &self.port)
+            .field("uploaded", &self.uploaded)
+            .field("downloaded", &self.downloaded)
+            .field("left", &self.left)
+            .field("compact", &self.compact)
+            .field("numwant", &self.numwant)
+            .field("event", &self.event)
+            .field("key", &self.key)
+            .field("tracker_id", &self.tracker_id)
+            .field("ip", &ip)
+            .field("extra_params", &self.extra_params)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod peer_tests {
+    use super::*;
+    use std::iter::FromIterator;
+
+    fn peer_dict(port: i64) -> HashMap<String, BencodeElem> {
+        HashMap::from_iter(vec![
+            ("ip".to_owned(), bencode_elem!("127.0.0.1")),
+            ("port".to_owned(), bencode_elem!(port)),
+        ])
+    }
+
+    #[test]
+    fn from_dict_port_ok() {
+        assert_eq!(
+            Peer::from_dict(peer_dict(1)).unwrap().socket_addr(),
+            Some("127.0.0.1:1".parse().unwrap()),
+        );
+    }
+
+    #[test]
+    fn from_dict_port_ok_2() {
+        assert_eq!(
+            Peer::from_dict(peer_dict(65535)).unwrap().socket_addr(),
+            Some("127.0.0.1:65535".parse().unwrap()),
+        );
+    }
+
+    #[test]
+    fn from_dict_port_zero() {
+        match Peer::from_dict(peer_dict(0)) {
+            Err(LavaTorrentError::MalformedResponse(m)) => {
+                assert_eq!(m, r#""port" [0] is not within 1..=65535."#);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn from_dict_port_too_large() {
+        match Peer::from_dict(peer_dict(65536)) {
+            Err(LavaTorrentError::MalformedResponse(m)) => {
+                assert_eq!(m, r#""port" [65536] is not within 1..=65535."#);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn from_dict_port_negative() {
+        match Peer::from_dict(peer_dict(-1)) {
+            Err(LavaTorrentError::MalformedResponse(m)) => {
+                assert_eq!(m, r#""port" [-1] is not within 1..=65535."#);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn from_bytes_port_zero() {
+        assert!(Peer::from_bytes([127, 0, 0, 1, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn extract_peers_from_list_skip_invalid() {
+        let list = vec![
+            BencodeElem::Dictionary(peer_dict(6881)),
+            BencodeElem::Dictionary(peer_dict(65536)),
+            BencodeElem::Dictionary(peer_dict(-1)),
+        ];
+        let (peers, skipped) =
+            TrackerResponse::extract_peers_from_list(list, &ResponseLimits::default()).unwrap();
+
+        assert_eq!(peers.len(), 1);
+        assert_eq!(
+            peers[0].socket_addr(),
+            Some("127.0.0.1:6881".parse().unwrap())
+        );
+        assert_eq!(skipped, 2);
+    }
+
+    #[test]
+    fn extract_peers_from_list_missing_ip() {
+        let mut dict = peer_dict(6881);
+        dict.remove("ip");
+        let list = vec![
+            BencodeElem::Dictionary(peer_dict(6881)),
+            BencodeElem::Dictionary(dict),
+        ];
+
+        match TrackerResponse::extract_peers_from_list(list, &ResponseLimits::default()) {
+            Err(LavaTorrentError::MalformedResponse(m)) => {
+                assert_eq!(m, r#""ip" does not exist."#);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn extract_peers_from_bytes_skip_invalid() {
+        let bytes = vec![127, 0, 0, 1, 0x1a, 0xe1, 127, 0, 0, 1, 0, 0];
+        let (peers, skipped) =
+            TrackerResponse::extract_peers_from_bytes(bytes, &ResponseLimits::default()).unwrap();
+
+        assert_eq!(peers.len(), 1);
+        assert_eq!(
+            peers[0].socket_addr(),
+            Some("127.0.0.1:6881".parse().unwrap())
+        );
+        assert_eq!(skipped, 1);
+    }
+
+    #[test]
+    fn from_bytes_skipped_peers() {
+        let response = bencode_elem!({
+            ("interval", 1800),
+            ("peers", [
+                { ("ip", "127.0.0.1"), ("port", 6881) },
+                { ("ip", "127.0.0.1"), ("port", 65536) },
+            ]),
+        })
+        .encode();
+
+        match TrackerResponse::from_bytes(response).unwrap() {
+            TrackerResponse::Success {
+                peers,
+                skipped_peers,
+                ..
+            } => {
+                assert_eq!(peers.len(), 1);
+                assert_eq!(skipped_peers, 1);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn from_dict_ok() {
+        let mut dict = peer_dict(6881);
+        dict.insert("peer id".to_owned(), bencode_elem!("-LT2080-abcdefghijkl"));
+        dict.insert("key".to_owned(), bencode_elem!("value"));
+
+        assert_eq!(
+            Peer::from_dict(dict).unwrap(),
+            Peer {
+                id: Some(b"-LT2080-abcdefghijkl".to_vec()),
+                addr: PeerAddr::Resolved("127.0.0.1:6881".parse().unwrap()),
+                extra_fields: Some(HashMap::from_iter(vec![(
+                    "key".to_owned(),
+                    bencode_elem!("value")
+                )])),
+            }
+        );
+    }
+
+    #[test]
+    fn from_dict_no_peer_id() {
+        let peer = Peer::from_dict(peer_dict(6881)).unwrap();
+        assert_eq!(peer.id, None);
+        assert_eq!(peer.extra_fields, None);
+    }
+
+    #[test]
+    fn from_dict_peer_id_bytes() {
+        // not valid utf8, so kept as is (and not hex-encoded)
+        let id = [&b"-qB4250-"[..], &[0x00, 0x0a, 0xff, 0xfe], &[b'x'; 8]].concat();
+        let mut dict = peer_dict(6881);
+        dict.insert("peer id".to_owned(), BencodeElem::Bytes(id.clone()));
+
+        assert_eq!(Peer::from_dict(dict).unwrap().id, Some(id));
+    }
+
+    #[test]
+    fn from_dict_peer_id_invalid() {
+        let mut dict = peer_dict(6881);
+        dict.insert("peer id".to_owned(), bencode_elem!(42));
+
+        match Peer::from_dict(dict) {
+            Err(LavaTorrentError::MalformedResponse(m)) => {
+                assert_eq!(m, r#""peer id" maps to Integer, expected String or Bytes."#);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn from_dict_ipv6() {
+        let mut dict = peer_dict(6881);
+        dict.insert("ip".to_owned(), bencode_elem!("2001:db8::1"));
+
+        assert_eq!(
+            Peer::from_dict(dict).unwrap().socket_addr(),
+            Some("[2001:db8::1]:6881".parse().unwrap()),
+        );
+    }
+
+    #[test]
+    fn from_dict_ip_missing() {
+        let mut dict = peer_dict(6881);
+        dict.remove("ip");
+
+        match Peer::from_dict(dict) {
+            Err(LavaTorrentError::MalformedResponse(m)) => {
+                assert_eq!(m, r#""ip" does not exist."#);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn from_dict_ip_not_string() {
+        let mut dict = peer_dict(6881);
+        dict.insert("ip".to_owned(), bencode_elem!([127, 0, 0, 1]));
+
+        match Peer::from_dict(dict) {
+            Err(LavaTorrentError::MalformedResponse(m)) => {
+                assert_eq!(m, r#""ip" maps to List, expected String."#);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn from_dict_ip_not_utf8() {
+        let mut dict = peer_dict(6881);
+        dict.insert("ip".to_owned(), BencodeElem::Bytes(vec![127, 0, 0, 0xff]));
+
+        match Peer::from_dict(dict) {
+            Err(LavaTorrentError::MalformedResponse(m)) => assert_eq!(
+                m,
+                r#""ip" maps to a byte string that is not valid UTF-8 (4 bytes, starts with 0x7f0000ff)."#
+            ),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn from_dict_ip_invalid() {
+        for ip in [
+            "",
+            "127.0.0.256",
+            "127.0.0.1:6881",
+            "[::1]",
+            "peer example.com",
+            "peer..example.com",
+            "-peer.example.com",
+            "peer_1.example.com",
+            "peer.example.123",
+        ] {
+            let mut dict = peer_dict(6881);
+            dict.insert("ip".to_owned(), bencode_elem!(ip));
+
+            match Peer::from_dict(dict) {
+                Err(LavaTorrentError::MalformedResponse(m)) => {
+                    assert_eq!(m, r#""ip" is invalid."#);
+                }
+                _ => panic!(),
+            }
+        }
+    }
+
+    #[test]
+    fn from_dict_port_missing() {
+        let mut dict = peer_dict(6881);
+        dict.remove("port");
+
+        match Peer::from_dict(dict) {
+            Err(LavaTorrentError::MalformedResponse(m)) => {
+                assert_eq!(m, r#""port" does not exist."#);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn from_dict_port_not_integer() {
+        let mut dict = peer_dict(6881);
+        dict.insert("port".to_owned(), bencode_elem!("6881"));
+
+        match Peer::from_dict(dict) {
+            Err(LavaTorrentError::MalformedResponse(m)) => {
+                assert_eq!(m, r#""port" maps to String, expected Integer."#);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn from_bytes_ok() {
+        assert_eq!(
+            Peer::from_bytes([192, 168, 1, 2, 0x1a, 0xe1]).unwrap(),
+            Peer {
+                id: None,
+                addr: PeerAddr::Resolved("192.168.1.2:6881".parse().unwrap()),
+                extra_fields: None,
+            }
+        );
+        assert_eq!(
+            Peer::from_bytes([255, 255, 255, 255, 0xff, 0xff])
+                .unwrap()
+                .socket_addr(),
+            Some("255.255.255.255:65535".parse().unwrap()),
+        );
+    }
+
+    #[test]
+    fn new_ok() {
+        let addr = "192.168.1.2:6881".parse().unwrap();
+        assert_eq!(
+            Peer::new(addr),
+            Peer {
+                id: None,
+                addr: PeerAddr::Resolved(addr),
+                extra_fields: None,
+            }
+        );
+    }
+
+    #[test]
+    fn with_id_ok() {
+        let addr = "[2001:db8::1]:6881".parse().unwrap();
+        assert_eq!(
+            Peer::with_id(addr, b"-LT0001-abcdefghijkl".to_vec()),
+            Peer {
+                id: Some(b"-LT0001-abcdefghijkl".to_vec()),
+                addr: PeerAddr::Resolved(addr),
+                extra_fields: None,
+            }
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn from_bytes_wrong_length() {
+        let _ = Peer::from_bytes([127, 0, 0, 1, 0x1a]);
+    }
+
+    #[test]
+    fn from_dict_ip_hostname() {
+        for host in [
+            "localhost",
+            "peer.example.com",
+            "peer.example.com.",
+            "Peer-1.dyndns.example",
+            "1.2.3.example",
+        ] {
+            let mut dict = peer_dict(6881);
+            dict.insert("ip".to_owned(), bencode_elem!(host));
+
+            let peer = Peer::from_dict(dict).unwrap();
+            assert_eq!(
+                peer.addr,
+                PeerAddr::Hostname {
+                    host: host.to_owned(),
+                    port: 6881,
+                }
+            );
+            assert_eq!(peer.socket_addr(), None);
+        }
+    }
+
+    #[test]
+    fn is_valid_hostname_lengths() {
+        let label = "a".repeat(63);
+        assert!(is_valid_hostname(&label));
+        assert!(!is_valid_hostname(&"a".repeat(64)));
+
+        // 4 * 63 + 3 dots = 255 bytes
+        let host = [&label[..]; 4].join(".");
+        assert!(!is_valid_hostname(&host));
+        assert!(is_valid_hostname(&host[2..]));
+        assert!(is_valid_hostname(&format!("{}.", &host[2..])));
+        assert!(!is_valid_hostname("."));
+    }
+
+    #[test]
+    fn peer_addr_new() {
+        assert_eq!(
+            PeerAddr::new("10.0.0.1", 6881).unwrap(),
+            PeerAddr::Resolved("10.0.0.1:6881".parse().unwrap())
+        );
+        assert_eq!(
+            PeerAddr::new("::1", 6881).unwrap(),
+            PeerAddr::Resolved("[::1]:6881".parse().unwrap())
+        );
+        assert_eq!(
+            PeerAddr::new("peer.example.com", 6881).unwrap(),
+            PeerAddr::Hostname {
+                host: "peer.example.com".to_owned(),
+                port: 6881,
+            }
+        );
+        match PeerAddr::new("peer example.com", 6881) {
+            Err(LavaTorrentError::InvalidArgument(m)) => assert_eq!(
+                m,
+                r#""peer example.com" is neither an IP address nor a hostname."#
+            ),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn peer_addr_accessors() {
+        let resolved = PeerAddr::from("[2001:db8::1]:6881".parse::<SocketAddr>().unwrap());
+        assert_eq!(resolved.port(), 6881);
+        assert_eq!(
+            resolved.socket_addr(),
+            Some("[2001:db8::1]:6881".parse().unwrap())
+        );
+
+        let hostname = PeerAddr::new("peer.example.com", 51413).unwrap();
+        assert_eq!(hostname.port(), 51413);
+        assert_eq!(hostname.socket_addr(), None);
+    }
+
+    // Looking up an actual DNS name would make this test depend on
+    // the network, so only hosts that don't need a lookup are used.
+    #[test]
+    fn resolve_without_lookup() {
+        let addr = "10.0.0.1:6881".parse().unwrap();
+        assert_eq!(Peer::new(addr).resolve().unwrap(), vec![addr]);
+
+        let peer = Peer::from_addr(PeerAddr::Hostname {
+            host: "10.0.0.1".to_owned(),
+            port: 6881,
+        });
+        assert_eq!(peer.socket_addr(), None);
+        assert_eq!(peer.resolve().unwrap(), vec![addr]);
+    }
+
+    #[test]
+    fn extract_peers_from_list_hostname() {
+        let mut hostname = peer_dict(6882);
+        hostname.insert("ip".to_owned(), bencode_elem!("peer.example.com"));
+        let list = vec![
+            BencodeElem::Dictionary(peer_dict(6881)),
+            BencodeElem::Dictionary(hostname),
+        ];
+        let (peers, skipped) =
+            TrackerResponse::extract_peers_from_list(list, &ResponseLimits::default()).unwrap();
+
+        assert_eq!(
+            peers.iter().map(|peer| &peer.addr).collect::<Vec<_>>(),
+            vec![
+                &PeerAddr::Resolved("127.0.0.1:6881".parse().unwrap()),
+                &PeerAddr::Hostname {
+                    host: "peer.example.com".to_owned(),
+                    port: 6882,
+                },
+            ]
+        );
+        assert_eq!(skipped, 0);
+
+        // unlike an out-of-range port, this fails the entire response
+        let mut invalid = peer_dict(6883);
+        invalid.insert("ip".to_owned(), bencode_elem!("peer example.com"));
+        match TrackerResponse::extract_peers_from_list(
+            vec![BencodeElem::Dictionary(invalid)],
+            &ResponseLimits::default(),
+        ) {
+            Err(LavaTorrentError::MalformedResponse(m)) => assert_eq!(m, r#""ip" is invalid."#),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn extract_peers_from_list_ok() {
+        let list = vec![
+            BencodeElem::Dictionary(peer_dict(6881)),
+            BencodeElem::Dictionary(peer_dict(6882)),
+        ];
+        let (peers, skipped) =
+            TrackerResponse::extract_peers_from_list(list, &ResponseLimits::default()).unwrap();
+
+        assert_eq!(
+            peers
+                .iter()
+                .map(|peer| peer.addr.port())
+                .collect::<Vec<_>>(),
+            vec![6881, 6882]
+        );
+        assert_eq!(skipped, 0);
+    }
+
+    #[test]
+    fn extract_peers_from_list_empty() {
+        let (peers, skipped) =
+            TrackerResponse::extract_peers_from_list(vec![], &ResponseLimits::default()).unwrap();
+        assert!(peers.is_empty());
+        assert_eq!(skipped, 0);
+    }
+
+    #[test]
+    fn extract_peers_from_list_not_dictionary() {
+        // unlike invalid peers, this fails the entire response
+        let list = vec![
+            BencodeElem::Dictionary(peer_dict(6881)),
+            bencode_elem!("127.0.0.1:6881"),
+        ];
+
+        match TrackerResponse::extract_peers_from_list(list, &ResponseLimits::default()) {
+            Err(LavaTorrentError::MalformedResponse(m)) => {
+                assert_eq!(
+                    m,
+                    r#""peers" contains a String element, expected Dictionary."#
+                );
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn extract_peers_from_bytes_ok() {
+        let bytes = vec![127, 0, 0, 1, 0x1a, 0xe1, 10, 0, 0, 2, 0x1a, 0xe2];
+        let (peers, skipped) =
+            TrackerResponse::extract_peers_from_bytes(bytes, &ResponseLimits::default()).unwrap();
+
+        assert_eq!(
+            peers
+                .iter()
+                .filter_map(Peer::socket_addr)
+                .collect::<Vec<_>>(),
+            vec![
+                "127.0.0.1:6881".parse().unwrap(),
+                "10.0.0.2:6882".parse().unwrap()
+            ]
+        );
+        assert!(peers.iter().all(|peer| peer.id.is_none()));
+        assert_eq!(skipped, 0);
+    }
+
+    #[test]
+    fn extract_peers_from_bytes_empty() {
+        let (peers, skipped) =
+            TrackerResponse::extract_peers_from_bytes(vec![], &ResponseLimits::default()).unwrap();
+        assert!(peers.is_empty());
+        assert_eq!(skipped, 0);
+    }
+
+    #[test]
+    fn extract_peers_from_bytes_wrong_length() {
+        for len in [1, 5, 7, 13] {
+            match TrackerResponse::extract_peers_from_bytes(
+                vec![1; len],
+                &ResponseLimits::default(),
+            ) {
+                Err(LavaTorrentError::MalformedResponse(m)) => {
+                    assert_eq!(m, r#"Compact "peers" contains incorrect number of bytes"#);
+                }
+                _ => panic!(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod response_tests {
+    use super::*;
+    use std::iter::FromIterator;
+
+    #[test]
+    fn success_minimal_ok() {
+        let peers = vec![Peer::new("127.0.0.1:6881".parse().unwrap())];
+        assert_eq!(
+            TrackerResponse::success_minimal(1800, peers.clone()),
+            TrackerResponse::Success {
+                interval: 1800,
+                peers,
+                warning: None,
+                min_interval: None,
+                tracker_id: None,
+                complete: None,
+                incomplete: None,
+                skipped_peers: 0,
+                trailing_bytes: 0,
+                extra_fields: None,
+            }
+        );
+    }
+
+    #[test]
+    fn builder_ok() {
+        let peer1 = Peer::new("127.0.0.1:6881".parse().unwrap());
+        let peer2 = Peer::with_id("127.0.0.2:6882".parse().unwrap(), b"id".to_vec());
+        let extra_fields = HashMap::from_iter(vec![("ext".to_owned(), bencode_elem!(1))]);
+        let response = TrackerResponseBuilder::new(900)
+            .set_interval(1800)
+            .set_peers(vec![peer1.clone()])
+            .add_peer(peer2.clone())
+            .set_warning("warning".to_owned())
+            .set_min_interval(60)
+            .set_tracker_id("id".to_owned())
+            .set_complete(10)
+            .set_incomplete(3)
+            .set_skipped_peers(2)
+            .set_trailing_bytes(5)
+            .set_extra_fields(extra_fields.clone())
+            .build();
+
+        assert_eq!(
+            response,
+            TrackerResponse::Success {
+                interval: 1800,
+                peers: vec![peer1, peer2],
+                warning: Some("warning".to_owned()),
+                min_interval: Some(60),
+                tracker_id: Some("id".to_owned()),
+                complete: Some(10),
+                incomplete: Some(3),
+                skipped_peers: 2,
+                trailing_bytes: 5,
+                extra_fields: Some(extra_fields),
+            }
+        );
+    }
+
+    #[test]
+    fn builder_defaults_to_success_minimal() {
+        assert_eq!(
+            TrackerResponseBuilder::new(1800).build(),
+            TrackerResponse::success_minimal(1800, vec![]),
+        );
+    }
+
+    fn response() -> Vec<u8> {
+        bencode_elem!({
+            ("interval", 1800),
+            ("peers", [{ ("ip", "127.0.0.1"), ("port", 6881) }]),
+        })
+        .encode()
+    }
+
+    fn trailing_bytes(bytes: &[u8]) -> usize {
+        match TrackerResponse::from_bytes(bytes).unwrap() {
+            TrackerResponse::Success {
+                peers,
+                trailing_bytes,
+                ..
+            } => {
+                assert_eq!(peers.len(), 1);
+                trailing_bytes
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn from_bytes_trailing_newline() {
+        assert_eq!(trailing_bytes(&[response(), b"\n".to_vec()].concat()), 0);
+        assert_eq!(
+            trailing_bytes(&[response(), b"\r\n\r\n".to_vec()].concat()),
+            0
+        );
+    }
+
+    #[test]
+    fn from_bytes_bom() {
+        assert_eq!(trailing_bytes(&[UTF8_BOM, &response()].concat()), 0);
+        assert_eq!(trailing_bytes(&[UTF8_BOM, &response(), b"\n"].concat()), 0);
+    }
+
+    #[test]
+    fn from_bytes_trailing_garbage() {
+        assert_eq!(trailing_bytes(&[response(), b"i1e".to_vec()].concat()), 3);
+        assert_eq!(
+            trailing_bytes(&[response(), b"\n<br />\n".to_vec()].concat()),
+            7
+        );
+        assert_eq!(
+            trailing_bytes(&[response(), response(), b"\n".to_vec()].concat()),
+            response().len()
+        );
+    }
+
+    #[test]
+    fn from_bytes_failure_trailing_garbage() {
+        let bytes = [
+            bencode_elem!({ ("failure reason", "unregistered torrent") }).encode(),
+            b"\x00junk".to_vec(),
+        ]
+        .concat();
+        assert_eq!(
+            TrackerResponse::from_bytes(bytes).unwrap(),
+            TrackerResponse::Failure {
+                reason: "unregistered torrent".to_owned()
+            }
+        );
+    }
+
+    #[test]
+    fn from_bytes_empty() {
+        for bytes in [&b""[..], b"\n", UTF8_BOM] {
+            match TrackerResponse::from_bytes(bytes) {
+                Err(LavaTorrentError::MalformedResponse(m)) => assert_eq!(
+                    m,
+                    "Tracker response should contain 1 and only 1 top-level element, 0 found."
+                ),
+                _ => panic!(),
+            }
+        }
+    }
+
+    #[test]
+    fn from_bytes_not_dictionary() {
+        match TrackerResponse::from_bytes(b"i1ed8:intervali1800ee") {
+            Err(LavaTorrentError::MalformedResponse(m)) => {
+                assert_eq!(m, "Tracker response doesn't contain a dictionary.")
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn from_bytes_malformed() {
+        // a valid prefix doesn't make a malformed response valid
+        let mut bytes = response();
+        bytes.pop();
+        assert!(TrackerResponse::from_bytes(bytes).is_err());
+    }
+
+    fn parse_err(response: BencodeElem) -> String {
+        match TrackerResponse::from_bytes(response.encode()) {
+            Err(LavaTorrentError::MalformedResponse(m)) => m.into_owned(),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn from_bytes_ok() {
+        let response = bencode_elem!({
+            ("complete", 5),
+            ("incomplete", 10),
+            ("interval", 1800),
+            ("key", "value"),
+            ("min interval", 900),
+            ("peers", [
+                { ("ip", "127.0.0.1"), ("peer id", "-LT2080-abcdefghijkl"), ("port", 6881) },
+                { ("ip", "::1"), ("port", 6882) },
+            ]),
+            ("tracker id", "abc"),
+            ("warning message", "ignored"),
+            ("warning", "slow down"),
+        });
+
+        assert_eq!(
+            TrackerResponse::from_bytes(response.encode()).unwrap(),
+            TrackerResponse::Success {
+                interval: 1800,
+                peers: vec![
+                    Peer {
+                        id: Some(b"-LT2080-abcdefghijkl".to_vec()),
+                        addr: PeerAddr::Resolved("127.0.0.1:6881".parse().unwrap()),
+                        extra_fields: None,
+                    },
+                    Peer {
+                        id: None,
+                        addr: PeerAddr::Resolved("[::1]:6882".parse().unwrap()),
+                        extra_fields: None,
+                    },
+                ],
+                warning: Some("slow down".to_owned()),
+                min_interval: Some(900),
+                tracker_id: Some("abc".to_owned()),
+                complete: Some(5),
+                incomplete: Some(10),
+                skipped_peers: 0,
+                trailing_bytes: 0,
+                extra_fields: Some(HashMap::from_iter(vec![
+                    ("key".to_owned(), bencode_elem!("value")),
+                    ("warning message".to_owned(), bencode_elem!("ignored")),
+                ])),
+            }
+        );
+    }
+
+    #[test]
+    fn from_bytes_minimal() {
+        assert_eq!(
+            TrackerResponse::from_bytes(response()).unwrap(),
+            TrackerResponse::Success {
+                interval: 1800,
+                peers: vec![Peer {
+                    id: None,
+                    addr: PeerAddr::Resolved("127.0.0.1:6881".parse().unwrap()),
+                    extra_fields: None,
+                }],
+                warning: None,
+                min_interval: None,
+                tracker_id: None,
+                complete: None,
+                incomplete: None,
+                skipped_peers: 0,
+                trailing_bytes: 0,
+                extra_fields: None,
+            }
+        );
+    }
+
+    #[test]
+    fn from_bytes_compact_peers() {
+        let response = bencode_elem!({
+            ("interval", 1800),
+            ("peers", ([127, 0, 0, 1, 0x1a, 0xe1, 10, 0, 0, 2, 0, 0].to_vec())),
+        });
+
+        match TrackerResponse::from_bytes(response.encode()).unwrap() {
+            TrackerResponse::Success {
+                peers,
+                skipped_peers,
+                ..
+            } => {
+                assert_eq!(peers.len(), 1);
+                assert_eq!(
+                    peers[0].socket_addr(),
+                    Some("127.0.0.1:6881".parse().unwrap())
+                );
+                assert_eq!(skipped_peers, 1);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn from_bytes_compact_peers_utf8() {
+        // 127.0.0.1:6689 and 10.0.0.2:65 are valid utf8
+        let response = bencode_elem!({
+            ("interval", 1800),
+            ("peers", "\x7f\x00\x00\x01\x1a\x21\x0a\x00\x00\x02\x00\x41"),
+        });
+
+        match TrackerResponse::from_bytes(response.encode()).unwrap() {
+            TrackerResponse::Success { peers, .. } => assert_eq!(
+                peers
+                    .iter()
+                    .filter_map(Peer::socket_addr)
+                    .collect::<Vec<_>>(),
+                vec![
+                    "127.0.0.1:6689".parse().unwrap(),
+                    "10.0.0.2:65".parse().unwrap()
+                ]
+            ),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn from_bytes_no_peers() {
+        // compact and non-compact empty lists are both fine
+        for peers in [bencode_elem!([]), bencode_elem!("")] {
+            let response = BencodeElem::Dictionary(HashMap::from_iter(vec![
+                ("interval".to_owned(), bencode_elem!(1800)),
+                ("peers".to_owned(), peers),
+            ]));
+            match TrackerResponse::from_bytes(response.encode()).unwrap() {
+                TrackerResponse::Success { peers, .. } => assert!(peers.is_empty()),
+                _ => panic!(),
+            }
+        }
+    }
+
+    #[test]
+    fn from_bytes_failure() {
+        // everything else is ignored
+        let response = bencode_elem!({
+            ("failure reason", "unregistered torrent"),
+            ("interval", "not an integer"),
+        });
+
+        assert_eq!(
+            TrackerResponse::from_bytes(response.encode()).unwrap(),
+            TrackerResponse::Failure {
+                reason: "unregistered torrent".to_owned()
+            }
+        );
+    }
+
+    #[test]
+    fn from_bytes_failure_not_string() {
+        assert_eq!(
+            parse_err(bencode_elem!({ ("failure reason", 404) })),
+            r#""failure reason" maps to Integer, expected String."#
+        );
+    }
+
+    #[test]
+    fn from_bytes_missing_field() {
+        assert_eq!(
+            parse_err(bencode_elem!({ ("peers", []) })),
+            r#""interval" does not exist."#
+        );
+        assert_eq!(
+            parse_err(bencode_elem!({ ("interval", 1800) })),
+            r#""peers" does not exist."#
+        );
+    }
+
+    #[test]
+    fn from_bytes_wrong_type() {
+        for (key, value, message) in [
+            (
+                "interval",
+                bencode_elem!("1800"),
+                r#""interval" maps to String, expected Integer."#,
+            ),
+            (
+                "peers",
+                bencode_elem!(0),
+                r#""peers" maps to Integer, expected List or String/Bytes."#,
+            ),
+            (
+                "warning",
+                bencode_elem!(["a"]),
+                r#""warning" maps to List, expected String."#,
+            ),
+            (
+                "min interval",
+                bencode_elem!("900"),
+                r#""min interval" maps to String, expected Integer."#,
+            ),
+            (
+                "tracker id",
+                bencode_elem!(1),
+                r#""tracker id" maps to Integer, expected String."#,
+            ),
+            (
+                "complete",
+                bencode_elem!({ ("a", 1) }),
+                r#""complete" maps to Dictionary, expected Integer."#,
+            ),
+            (
+                "incomplete",
+                bencode_elem!("10"),
+                r#""incomplete" maps to String, expected Integer."#,
+            ),
+        ] {
+            let mut response = BencodeElem::from_bytes(response()).unwrap().remove(0);
+            response.insert(key.to_owned(), value).unwrap();
+            assert_eq!(parse_err(response), message);
+        }
+    }
+
+    #[test]
+    fn from_bytes_not_utf8() {
+        let mut failure = bencode_elem!({});
+        failure
+            .insert(
+                "failure reason".to_owned(),
+                BencodeElem::Bytes(b"\xffgone".to_vec()),
+            )
+            .unwrap();
+        assert_eq!(
+            parse_err(failure),
+            r#""failure reason" maps to a byte string that is not valid UTF-8 (5 bytes, starts with 0xff676f6e65)."#
+        );
+        for key in ["warning", "tracker id"] {
+            let mut response = BencodeElem::from_bytes(response()).unwrap().remove(0);
+            response
+                .insert(key.to_owned(), BencodeElem::Bytes(b"\xfe\xff".to_vec()))
+                .unwrap();
+            assert_eq!(
+                parse_err(response),
+                format!(
+                    r#""{}" maps to a byte string that is not valid UTF-8 (2 bytes, starts with 0xfeff)."#,
+                    key
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn from_bytes_invalid_bencode() {
+        match TrackerResponse::from_bytes("d8:intervali1800e5:peersl") {
+            Err(LavaTorrentError::MalformedBencode(_)) => (),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn from_bencode_ok() {
+        let response = bencode_elem!({
+            ("interval", 1800),
+            ("peers", [{ ("ip", "127.0.0.1"), ("port", 6881) }]),
+            ("key", "value"),
+        });
+
+        let parsed = TrackerResponse::from_bencode(response.clone()).unwrap();
+        assert_eq!(
+            parsed,
+            TrackerResponse::from_bytes(response.encode()).unwrap()
+        );
+        match parsed {
+            TrackerResponse::Success {
+                interval,
+                peers,
+                trailing_bytes,
+                extra_fields,
+                ..
+            } => {
+                assert_eq!(interval, 1800);
+                assert_eq!(peers.len(), 1);
+                assert_eq!(trailing_bytes, 0);
+                assert_eq!(
+                    extra_fields,
+                    Some(HashMap::from_iter(vec![(
+                        "key".to_owned(),
+                        bencode_elem!("value")
+                    )]))
+                );
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn from_bencode_failure_reason() {
+        let response = bencode_elem!({ ("failure reason", "unregistered torrent") });
+        assert_eq!(
+            TrackerResponse::from_bencode(response).unwrap(),
+            TrackerResponse::Failure {
+                reason: "unregistered torrent".to_owned()
+            }
+        );
+    }
+
+    #[test]
+    fn from_bencode_missing_field() {
+        for (response, expected) in [
+            (
+                bencode_elem!({ ("peers", []) }),
+                r#""interval" does not exist."#,
+            ),
+            (
+                bencode_elem!({ ("interval", 1800) }),
+                r#""peers" does not exist."#,
+            ),
+        ] {
+            match TrackerResponse::from_bencode(response) {
+                Err(LavaTorrentError::MalformedResponse(m)) => assert_eq!(m, expected),
+                _ => panic!(),
+            }
+        }
+    }
+
+    #[test]
+    fn from_bencode_not_dictionary() {
+        match TrackerResponse::from_bencode(bencode_elem!([])) {
+            Err(LavaTorrentError::MalformedResponse(m)) => {
+                assert_eq!(m, "Tracker response doesn't contain a dictionary.");
+            }
+            _ => panic!(),
+        }
+    }
+
+    // a response with `n_peers` compact peers and `n_extra_fields` extra fields
+    fn limits_response(n_peers: usize, n_extra_fields: usize) -> Vec<u8> {
+        let peers = (0..n_peers)
+            .flat_map(|i| {
+                let [_, b, c, d] = u32::try_from(i).unwrap().to_be_bytes();
+                [10, b, c, d, 0x1a, 0xe1]
+            })
+            .collect();
+        let mut dict = HashMap::from_iter(vec![
+            ("interval".to_owned(), bencode_elem!(1800)),
+            ("peers".to_owned(), BencodeElem::Bytes(peers)),
+        ]);
+        for i in 0..n_extra_fields {
+            dict.insert(format!("x{}", i), bencode_elem!(1));
+        }
+        BencodeElem::Dictionary(dict).encode()
+    }
+
+    fn peer_counts(response: TrackerResponse) -> (usize, usize) {
+        match response {
+            TrackerResponse::Success {
+                peers,
+                skipped_peers,
+                ..
+            } => (peers.len(), skipped_peers),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn limits_under() {
+        let response = limits_response(10_000, 64);
+        match TrackerResponse::from_bytes(&response).unwrap() {
+            TrackerResponse::Success {
+                peers,
+                skipped_peers,
+                extra_fields,
+                ..
+            } => {
+                assert_eq!(peers.len(), 10_000);
+                assert_eq!(
+                    peers[9_999].socket_addr(),
+                    Some("10.0.39.15:6881".parse().unwrap())
+                );
+                assert_eq!(skipped_peers, 0);
+                assert_eq!(extra_fields.unwrap().len(), 64);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn limits_too_many_peers() {
+        let response = limits_response(100_000, 0);
+        match TrackerResponse::from_bytes(&response) {
+            Err(LavaTorrentError::MalformedResponse(m)) => assert_eq!(
+                m,
+                r#""peers" has 100000 entries, exceeding the limit of 10000 (max_peers)."#
+            ),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn limits_truncate_peers() {
+        let limits = ResponseLimits {
+            truncate_peers: true,
+            ..ResponseLimits::default()
+        };
+
+        let response = limits_response(100_000, 0);
+        let parsed = TrackerResponse::from_bytes_with_limits(&response, &limits).unwrap();
+        match parsed {
+            TrackerResponse::Success { ref peers, .. } => {
+                assert_eq!(
+                    peers[0].socket_addr(),
+                    Some("10.0.0.0:6881".parse().unwrap())
+                );
+                assert_eq!(
+                    peers[9_999].socket_addr(),
+                    Some("10.0.39.15:6881".parse().unwrap())
+                );
+            }
+            _ => panic!(),
+        }
+        assert_eq!(peer_counts(parsed), (10_000, 90_000));
+
+        // untouched if under the limit
+        let response = limits_response(10_000, 0);
+        assert_eq!(
+            peer_counts(TrackerResponse::from_bytes_with_limits(&response, &limits).unwrap()),
+            (10_000, 0)
+        );
+    }
+
+    #[test]
+    fn limits_truncate_peers_list() {
+        let limits = ResponseLimits {
+            max_peers: 1,
+            truncate_peers: true,
+            ..ResponseLimits::default()
+        };
+        let response = bencode_elem!({
+            ("interval", 1800),
+            ("peers", [
+                { ("ip", "127.0.0.1"), ("port", 65536) },
+                { ("ip", "127.0.0.1"), ("port", 6881) },
+            ]),
+        })
+        .encode();
+
+        // the invalid peer counts towards the limit
+        assert_eq!(
+            peer_counts(TrackerResponse::from_bytes_with_limits(&response, &limits).unwrap()),
+            (0, 2)
+        );
+        match TrackerResponse::from_bytes_with_limits(
+            &response,
+            &ResponseLimits {
+                truncate_peers: false,
+                ..limits
+            },
+        ) {
+            Err(LavaTorrentError::MalformedResponse(m)) => assert_eq!(
+                m,
+                r#""peers" has 2 entries, exceeding the limit of 1 (max_peers)."#
+            ),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn limits_too_many_extra_fields() {
+        let response = limits_response(1, 65);
+        match TrackerResponse::from_bytes(&response) {
+            Err(LavaTorrentError::MalformedResponse(m)) => assert_eq!(
+                m,
+                "Tracker response has 65 extra fields, exceeding the limit of 64 (max_extra_fields)."
+            ),
+            _ => panic!(),
+        }
+        // failures have no extra fields
+        let failure = b"d14:failure reason3:bad1:xi1e1:yi1ee";
+        let limits = ResponseLimits {
+            max_extra_fields: 0,
+            ..ResponseLimits::default()
+        };
+        assert!(TrackerResponse::from_bytes_with_limits(failure, &limits).is_ok());
+    }
+
+    #[test]
+    fn limits_response_bytes() {
+        let limits = ResponseLimits::default();
+        let mut response = limits_response(1, 0);
+        // trailing bytes count towards the limit
+        response.resize(limits.max_response_bytes, b' ');
+        assert_eq!(
+            peer_counts(TrackerResponse::from_bytes(&response).unwrap()),
+            (1, 0)
+        );
+
+        response.push(b' ');
+        match TrackerResponse::from_bytes(&response) {
+            Err(LavaTorrentError::MalformedResponse(m)) => assert_eq!(
+                m,
+                "Tracker response is 1048577 bytes long, exceeding the limit of 1048576 \
+                 (max_response_bytes)."
+            ),
+            _ => panic!(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod announce_request_tests {
+    use super::*;
+
+    fn hex_20(s: &str) -> [u8; 20] {
+        hex::decode(s).unwrap().try_into().unwrap()
+    }
+
+    fn request() -> AnnounceRequest {
+        AnnounceRequest {
+            info_hash: hex_20("778ce280b595e57780ff083f2eb6f897dfa4a4ee"),
+            peer_id: *b"-LT2080-abcdefghijkl",
+            port: 6881,
+            uploaded: 0,
+            downloaded: 0,
+            left: 1048576,
+            compact: None,
+            numwant: None,
+            event: AnnounceEvent::Empty,
+            key: None,
+            tracker_id: None,
+            ip: None,
+            extra_params: HashMap::new(),
+        }
+    }
+
+    fn parse_err(query: &str) -> String {
+        match AnnounceRequest::from_query(query) {
+            Err(LavaTorrentError::InvalidArgument(m)) => m.into_owned(),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn from_query_qbittorrent() {
+        let query = "?info_hash=%9f%29%d8%85%9c%e5%b4%e4%f1%2b%a2%b4%b8%b4%f8%2e%95%06%6f%a8\
+                     &peer_id=-qB4450-S3n2PcQ0WqLs&port=8999&uploaded=0&downloaded=0\
+                     &left=3826831360&corrupt=0&key=1A2B3C4D&event=started&numwant=200\
+                     &compact=1&no_peer_id=1&supportcrypto=1&redundant=0";
+
+        assert_eq!(
+            AnnounceRequest::from_query(query).unwrap(),
+            AnnounceRequest {
+                info_hash: hex_20("9f29d8859ce5b4e4f12ba2b4b8b4f82e95066fa8"),
+                peer_id: *b"-qB4450-S3n2PcQ0WqLs",
+                port: 8999,
+                uploaded: 0,
+                downloaded: 0,
+                left: 3826831360,
+                compact: Some(true),
+                numwant: Some(200),
+                event: AnnounceEvent::Started,
+                key: Some("1A2B3C4D".to_owned()),
+                tracker_id: None,
+                ip: None,
+                extra_params: vec![
+                    ("corrupt".to_owned(), b"0".to_vec()),
+                    ("no_peer_id".to_owned(), b"1".to_vec()),
+                    ("supportcrypto".to_owned(), b"1".to_vec()),
+                    ("redundant".to_owned(), b"0".to_vec()),
+                ]
+                .into_iter()
+                .collect(),
+            }
+        );
+    }
+
+    #[test]
+    fn from_query_transmission() {
+        // unreserved characters are not escaped, others are in upper case
+        let query = "info_hash=Z%B9%FB%2C%0F%97%3B%9F%8DQ8zMb%94%E5%7B%EF%93%17\
+                     &peer_id=-TR3000-m1k2j3h4g5f6&port=51413&uploaded=1024&downloaded=2048\
+                     &left=0&numwant=80&key=6d1a2b3c&compact=1&supportcrypto=1\
+                     &event=completed&ipv6=2001%3Adb8%3A%3A1";
+        let request = AnnounceRequest::from_query(query).unwrap();
+
+        assert_eq!(
+            request.info_hash,
+            hex_20("5ab9fb2c0f973b9f8d51387a4d6294e57bef9317")
+        );
+        assert_eq!(&request.peer_id, b"-TR3000-m1k2j3h4g5f6");
+        assert_eq!(request.port, 51413);
+        assert_eq!(
+            (request.uploaded, request.downloaded, request.left),
+            (1024, 2048, 0)
+        );
+        assert_eq!(request.numwant, Some(80));
+        assert_eq!(request.event, AnnounceEvent::Completed);
+        assert_eq!(request.extra_params["ipv6"], b"2001:db8::1");
+    }
+
+    #[test]
+    fn from_query_rtorrent() {
+        // the random part of rtorrent's peer id is binary, and the info
+        // hash (%ff, %c3%28, ...) is not valid utf8 once decoded
+        let query = "info_hash=%ff%fe%c3%28%80%81%00%01%02%03%04%05%06%07%08%09%0a%0b%0c%0d\
+                     &peer_id=-lt0D80-%8c%12%ae%f0%00%d1%97%3a%b4%e2%5f%01\
+                     &key=1a2b3c4d&compact=1&port=6881&uploaded=0&downloaded=0\
+                     &left=1048576&event=stopped&ip=192.0.2.7&trackerid=abc%20def";
+        let request = AnnounceRequest::from_query(query).unwrap();
+
+        assert_eq!(
+            request.info_hash,
+            hex_20("fffec3288081000102030405060708090a0b0c0d")
+        );
+        assert!(std::str::from_utf8(&request.info_hash).is_err());
+        assert_eq!(
+            request.peer_id,
+            hex_20("2d6c74304438302d8c12aef000d1973ab4e25f01")
+        );
+        assert_eq!(request.event, AnnounceEvent::Stopped);
+        assert_eq!(request.ip, Some("192.0.2.7".to_owned()));
+        assert_eq!(request.tracker_id, Some("abc def".to_owned()));
+        assert!(request.extra_params.is_empty());
+    }
+
+    #[test]
+    fn from_query_minimal() {
+        let query = "info_hash=%77%8c%e2%80%b5%95%e5%77%80%ff%08%3f%2e%b6%f8%97%df%a4%a4%ee\
+                     &peer_id=-LT2080-abcdefghijkl&port=6881&uploaded=0&downloaded=0\
+                     &left=1048576&event=";
+        assert_eq!(AnnounceRequest::from_query(query).unwrap(), request());
+    }
+
+    #[test]
+    fn peer_addr_ok() {
+        let mut request = request();
+        assert_eq!(request.peer_addr().unwrap(), None);
+
+        request.ip = Some("192.0.2.7".to_owned());
+        assert_eq!(
+            request.peer_addr().unwrap(),
+            Some(PeerAddr::Resolved("192.0.2.7:6881".parse().unwrap()))
+        );
+
+        request.ip = Some("client.example.com".to_owned());
+        assert_eq!(
+            request.peer_addr().unwrap(),
+            Some(PeerAddr::Hostname {
+                host: "client.example.com".to_owned(),
+                port: 6881,
+            })
+        );
+    }
+
+    #[test]
+    fn peer_addr_invalid() {
+        let mut request = request();
+        request.ip = Some("192.0.2.7:6881".to_owned());
+
+        match request.peer_addr() {
+            Err(LavaTorrentError::InvalidArgument(m)) => assert_eq!(
+                m,
+                r#""ip" [192.0.2.7:6881] is neither an IP address nor a hostname."#
+            ),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn from_query_plus_is_space() {
+        let mut expected = request();
+        expected.key = Some("a b+c".to_owned());
+        let query = request().to_query() + "&key=a+b%2Bc";
+
+        assert_eq!(AnnounceRequest::from_query(&query).unwrap(), expected);
+    }
+
+    #[test]
+    fn from_query_missing() {
+        for name in [
+            "info_hash",
+            "peer_id",
+            "port",
+            "uploaded",
+            "downloaded",
+            "left",
+        ] {
+            let query = request()
+                .to_query()
+                .split('&')
+                .filter(|param| !param.starts_with(&format!("{}=", name)))
+                .join("&");
+            assert_eq!(parse_err(&query), format!(r#""{}" does not exist."#, name));
+        }
+    }
+
+    #[test]
+    fn from_query_invalid() {
+        for (param, message) in [
+            (
+                "info_hash=abc",
+                r#""info_hash" is 3 bytes long, expected 20."#,
+            ),
+            (
+                "peer_id=-LT2080-abcdefghijklm",
+                r#""peer_id" is 21 bytes long, expected 20."#,
+            ),
+            ("port=0", r#""port" [0] is not within 1..=65535."#),
+            ("port=65536", r#""port" [65536] is not within 1..=65535."#),
+            ("port=-1", r#""port" [-1] is not a valid number."#),
+            ("left=-1", r#""left" [-1] is not a valid number."#),
+            (
+                "uploaded=18446744073709551616",
+                r#""uploaded" [18446744073709551616] is not a valid number."#,
+            ),
+            ("downloaded=", r#""downloaded" [] is not a valid number."#),
+            ("numwant=lots", r#""numwant" [lots] is not a valid number."#),
+            ("compact=2", r#""compact" [2] is neither 0 nor 1."#),
+            ("event=paws", r#""event" [paws] is not a known event."#),
+            ("key=%ff", r#""key" is not valid UTF-8."#),
+        ] {
+            let name = param.split('=').next().unwrap();
+            let query = request()
+                .to_query()
+                .split('&')
+                .filter(|p| !p.starts_with(&format!("{}=", name)))
+                .chain(std::iter::once(param))
+                .join("&");
+            assert_eq!(parse_err(&query), message);
+        }
+    }
+
+    #[test]
+    fn from_query_repeated() {
+        let query = request().to_query() + "&port=6882";
+        assert_eq!(parse_err(&query), r#""port" appears more than once."#);
+    }
+
+    #[test]
+    fn to_query_ok() {
+        assert_eq!(
+            request().to_query(),
+            "info_hash=w%8C%E2%80%B5%95%E5w%80%FF%08%3F.%B6%F8%97%DF%A4%A4%EE\
+             &peer_id=-LT2080-abcdefghijkl&port=6881&uploaded=0&downloaded=0&left=1048576"
+        );
+    }
+
+    #[test]
+    fn to_query_round_trip() {
+        let mut request = request();
+        request.info_hash = hex_20("fffec3288081000102030405060708090a0b0c0d");
+        request.peer_id = hex_20("2d6c74304438302d8c12aef000d1973ab4e25f01");
+        request.compact = Some(false);
+        request.numwant = Some(0);
+        request.key = Some("a b&c=d".to_owned());
+        request.tracker_id = Some("\u{00e9}t\u{00e9}".to_owned());
+        request.ip = Some("2001:db8::1".to_owned());
+        request
+            .extra_params
+            .insert("z".to_owned(), vec![0xff, b'+']);
+        request
+            .extra_params
+            .insert("supportcrypto".to_owned(), b"1".to_vec());
+
+        for event in [
+            AnnounceEvent::Started,
+            AnnounceEvent::Stopped,
+            AnnounceEvent::Completed,
+            AnnounceEvent::Paused,
+            AnnounceEvent::Empty,
+        ] {
+            request.event = event;
+            let query = request.to_query();
+            assert_eq!(AnnounceRequest::from_query(&query).unwrap(), request);
+            assert_eq!(query.contains("event="), event != AnnounceEvent::Empty);
+        }
+        assert!(request
+            .to_query()
+            .ends_with("&key=a%20b%26c%3Dd&trackerid=%C3%A9t%C3%A9&ip=2001%3Adb8%3A%3A1&supportcrypto=1&z=%FF%2B"));
+    }
+}
+
+#[cfg(test)]
+mod swarm_stats_tests {
+    use super::*;
+
+    fn metadata(complete: Integer, incomplete: Integer, downloaded: Integer) -> SwarmMetadata {
+        SwarmMetadata::new(complete, incomplete, downloaded)
+    }
+
+    fn success(complete: Option<Integer>, incomplete: Option<Integer>) -> TrackerResponse {
+        TrackerResponse::Success {
+            interval: 1800,
+            peers: vec![],
+            warning: None,
+            min_interval: None,
+            tracker_id: None,
+            complete,
+            incomplete,
+            skipped_peers: 0,
+            trailing_bytes: 0,
+            extra_fields: None,
+        }
+    }
+
+    fn stats(seeders: Option<u64>, leechers: Option<u64>, completed: Option<u64>) -> SwarmStats {
+        SwarmStats {
+            seeders,
+            leechers,
+            completed,
+        }
+    }
+
+    #[test]
+    fn swarm_metadata_new_ok() {
+        assert_eq!(
+            SwarmMetadata::new(5, 10, 100),
+            SwarmMetadata {
+                complete: 5,
+                incomplete: 10,
+                downloaded: 100,
+                extra_fields: None,
+            }
+        );
+        assert_eq!(SwarmMetadata::default(), SwarmMetadata::new(0, 0, 0));
+    }
+
+    #[test]
+    fn from_swarm_metadata_ok() {
+        assert_eq!(
+            SwarmStats::from(&metadata(5, 10, 100)),
+            stats(Some(5), Some(10), Some(100))
+        );
+    }
+
+    #[test]
+    fn from_swarm_metadata_negative() {
+        assert_eq!(
+            SwarmStats::from(&metadata(-1, 10, i64::MIN)),
+            stats(None, Some(10), None)
+        );
+    }
+
+    #[test]
+    fn try_from_tracker_response_ok() {
+        assert_eq!(
+            SwarmStats::try_from(&success(Some(5), Some(10))).unwrap(),
+            stats(Some(5), Some(10), None)
+        );
+    }
+
+    #[test]
+    fn try_from_tracker_response_missing() {
+        assert_eq!(
+            SwarmStats::try_from(&success(None, Some(10))).unwrap(),
+            stats(None, Some(10), None)
+        );
+        assert_eq!(
+            SwarmStats::try_from(&success(Some(5), None)).unwrap(),
+            stats(Some(5), None, None)
+        );
+    }
+
+    #[test]
+    fn try_from_tracker_response_negative() {
+        assert_eq!(
+            SwarmStats::try_from(&success(Some(-5), Some(-1))).unwrap(),
+            stats(None, None, None)
+        );
+    }
+
+    #[test]
+    fn try_from_tracker_response_failure() {
+        let response = TrackerResponse::Failure {
+            reason: "unregistered torrent".to_owned(),
+        };
+        match SwarmStats::try_from(&response) {
+            Err(LavaTorrentError::InvalidArgument(_)) => (),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn max_ok() {
+        let scrape = SwarmStats::from(&metadata(5, -1, 100));
+        let announce = SwarmStats::try_from(&success(Some(7), None)).unwrap();
+        assert_eq!(scrape.max(&announce), stats(Some(7), None, Some(100)));
+        assert_eq!(announce.max(&scrape), stats(Some(7), None, Some(100)));
+        assert_eq!(
+            stats(Some(1), Some(9), None).max(&stats(Some(3), Some(2), None)),
+            stats(Some(3), Some(9), None)
+        );
+    }
+
+    #[test]
+    fn sum_ok() {
+        let scrape = SwarmStats::from(&metadata(5, 10, 100));
+        let announce = SwarmStats::try_from(&success(Some(7), Some(-3))).unwrap();
+        assert_eq!(scrape.sum(&scrape), stats(Some(10), Some(20), Some(200)));
+        assert_eq!(scrape.sum(&announce), stats(Some(12), None, None));
+        assert_eq!(announce.sum(&scrape), stats(Some(12), None, None));
+        assert_eq!(
+            stats(Some(u64::MAX), None, None).sum(&stats(Some(1), None, None)),
+            stats(Some(u64::MAX), None, None)
+        );
+    }
+
+    #[test]
+    fn ratio_ok() {
+        assert_eq!(stats(Some(5), Some(10), None).ratio(), Some(0.5));
+        assert_eq!(stats(Some(5), Some(0), None).ratio(), None);
+        assert_eq!(stats(None, Some(10), None).ratio(), None);
+        assert_eq!(stats(Some(5), None, None).ratio(), None);
+    }
+}
+
+#[cfg(test)]
+mod scrape_response_tests {
+    use super::*;
+    use std::iter::FromIterator;
+
+    const INFO_HASH: [u8; 20] = [
+        0x77, 0x8c, 0xe2, 0x80, 0xb5, 0x95, 0xe5, 0x77, 0x80, 0xff, 0x08, 0x3f, 0x2e, 0xb6, 0xf8,
+        0x97, 0xdf, 0xa4, 0xa4, 0xee,
+    ];
+    const INFO_HASH_HEX: &str = "778ce280b595e57780ff083f2eb6f897dfa4a4ee";
+
+    // a scrape response with `complete` set to the given value for each key
+    fn scrape(files: Vec<(&[u8], Integer)>) -> Vec<u8> {
+        let files = files
+            .into_iter()
+            .map(|(key, complete)| {
+                (
+                    key.to_vec(),
+                    bencode_elem!({
+                        ("complete", complete),
+                        ("downloaded", 50),
+                        ("incomplete", 10),
+                    }),
+                )
+            })
+            .collect();
+        BencodeElem::Dictionary(HashMap::from_iter(vec![(
+            "files".to_owned(),
+            BencodeElem::RawDictionary(files),
+        )]))
+        .encode()
+    }
+
+    #[test]
+    fn from_bytes_raw_keys() {
+        let response =
+            TrackerScrapeResponse::from_bytes(scrape(vec![(&INFO_HASH, 5), (&[0xff; 20], 6)]))
+                .unwrap();
+
+        assert_eq!(response.files.len(), 2);
+        assert_eq!(response.files[&INFO_HASH].complete, 5);
+        assert_eq!(response.files[&[0xff; 20]].complete, 6);
+    }
+
+    #[test]
+    fn from_bytes_trailing_bytes() {
+        let bytes = scrape(vec![(&INFO_HASH, 5)]);
+        assert_eq!(
+            TrackerScrapeResponse::from_bytes(&bytes)
+                .unwrap()
+                .trailing_bytes,
+            0
+        );
+
+        let bytes = [UTF8_BOM, &bytes, b"\n\njunk\n"].concat();
+        let response = TrackerScrapeResponse::from_bytes(bytes).unwrap();
+        assert_eq!(response.files[&INFO_HASH].complete, 5);
+        assert_eq!(response.trailing_bytes, 6);
+    }
+
+    #[test]
+    fn from_bytes_hex_keys() {
+        let upper = INFO_HASH_HEX.to_uppercase();
+        let response = TrackerScrapeResponse::from_bytes(scrape(vec![
+            (INFO_HASH_HEX.as_bytes(), 5),
+            (&[b'f'; 40], 6),
+        ]))
+        .unwrap();
+
+        assert_eq!(response.files.len(), 2);
+        assert_eq!(response.files[&INFO_HASH].complete, 5);
+        assert_eq!(response.files[&[0xff; 20]].complete, 6);
+
+        // upper case works too
+        let response =
+            TrackerScrapeResponse::from_bytes(scrape(vec![(upper.as_bytes(), 5)])).unwrap();
+        assert_eq!(response.files[&INFO_HASH].complete, 5);
+    }
+
+    #[test]
+    fn from_bytes_mixed_keys() {
+        let response = TrackerScrapeResponse::from_bytes(scrape(vec![
+            (INFO_HASH_HEX.as_bytes(), 5),
+            (&[0xff; 20], 6),
+        ]))
+        .unwrap();
+        assert_eq!(response.files.len(), 2);
+        assert_eq!(response.files[&INFO_HASH].complete, 5);
+        assert_eq!(response.files[&[0xff; 20]].complete, 6);
+
+        // the same info hash in both forms: the raw one wins
+        let response = TrackerScrapeResponse::from_bytes(scrape(vec![
+            (INFO_HASH_HEX.as_bytes(), 5),
+            (&INFO_HASH, 6),
+        ]))
+        .unwrap();
+        assert_eq!(response.files.len(), 1);
+        assert_eq!(response.files[&INFO_HASH].complete, 6);
+    }
+
+    #[test]
+    fn from_bytes_invalid_key() {
+        match TrackerScrapeResponse::from_bytes(scrape(vec![(&INFO_HASH_HEX.as_bytes()[1..], 5)])) {
+            Err(LavaTorrentError::MalformedResponse(m)) => assert_eq!(
+                m,
+                format!(
+                    r#""files" has a key of 39 bytes ("{}"), expected a 20-byte info hash or 40 hex chars."#,
+                    &INFO_HASH_HEX[1..]
+                )
+            ),
+            _ => panic!(),
+        }
+
+        // right length, but not hex
+        assert!(TrackerScrapeResponse::from_bytes(scrape(vec![(&[b'g'; 40], 5)])).is_err());
+    }
+
+    #[test]
+    fn from_bytes_ok() {
+        let response = bencode_elem!({
+            ("files", {
+                (INFO_HASH_HEX, {
+                    ("complete", 5),
+                    ("downloaded", 50),
+                    ("incomplete", 10),
+                    ("name", "ubuntu"),
+                }),
+            }),
+            ("flags", { ("min_request_interval", 3600) }),
+        });
+
+        assert_eq!(
+            TrackerScrapeResponse::from_bytes(response.encode()).unwrap(),
+            TrackerScrapeResponse {
+                files: HashMap::from_iter(vec![(
+                    INFO_HASH,
+                    SwarmMetadata {
+                        complete: 5,
+                        incomplete: 10,
+                        downloaded: 50,
+                        extra_fields: Some(HashMap::from_iter(vec![(
+                            "name".to_owned(),
+                            bencode_elem!("ubuntu")
+                        )])),
+                    }
+                )]),
+                trailing_bytes: 0,
+                extra_fields: Some(HashMap::from_iter(vec![(
+                    "flags".to_owned(),
+                    bencode_elem!({ ("min_request_interval", 3600) })
+                )])),
+            }
+        );
+    }
+
+    #[test]
+    fn from_bytes_no_files() {
+        let response = TrackerScrapeResponse::from_bytes("d5:filesdee").unwrap();
+        assert!(response.files.is_empty());
+        assert_eq!(response.extra_fields, None);
+    }
+
+    #[test]
+    fn from_bytes_files_missing() {
+        match TrackerScrapeResponse::from_bytes("d5:flagsdee") {
+            Err(LavaTorrentError::MalformedResponse(m)) => {
+                assert_eq!(m, r#""files" does not exist."#);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn from_bytes_files_not_dictionary() {
+        match TrackerScrapeResponse::from_bytes("d5:filesleee") {
+            Err(LavaTorrentError::MalformedResponse(m)) => assert_eq!(
+                m,
+                r#""files" maps to List, expected Dictionary or RawDictionary."#
+            ),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn from_bytes_metadata_not_dictionary() {
+        let mut key = [0; 20];
+        key[19] = 0x0a;
+        let response = BencodeElem::Dictionary(HashMap::from_iter(vec![(
+            "files".to_owned(),
+            BencodeElem::RawDictionary(HashMap::from_iter(vec![(key.to_vec(), bencode_elem!(5))])),
+        )]));
+
+        match TrackerScrapeResponse::from_bytes(response.encode()) {
+            // hex digits are zero-padded
+            Err(LavaTorrentError::MalformedResponse(m)) => assert_eq!(
+                m,
+                format!(
+                    "swarm metadata for {}0a is not a dictionary.",
+                    "00".repeat(19)
+                )
+            ),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn from_bytes_metadata_invalid() {
+        for (key, value, message) in [
+            ("complete", None, r#""complete" does not exist."#),
+            ("incomplete", None, r#""incomplete" does not exist."#),
+            ("downloaded", None, r#""downloaded" does not exist."#),
+            (
+                "complete",
+                Some(bencode_elem!("5")),
+                r#""complete" maps to String, expected Integer."#,
+            ),
+            (
+                "incomplete",
+                Some(bencode_elem!([])),
+                r#""incomplete" maps to List, expected Integer."#,
+            ),
+            (
+                "downloaded",
+                Some(bencode_elem!({})),
+                r#""downloaded" maps to Dictionary, expected Integer."#,
+            ),
+        ] {
+            let mut metadata = HashMap::from_iter(vec![
+                ("complete".to_owned(), bencode_elem!(5)),
+                ("downloaded".to_owned(), bencode_elem!(50)),
+                ("incomplete".to_owned(), bencode_elem!(10)),
+            ]);
+            match value {
+                Some(value) => metadata.insert(key.to_owned(), value),
+                None => metadata.remove(key),
+            };
+
+            match SwarmMetadata::from_dict(metadata) {
+                Err(LavaTorrentError::MalformedResponse(m)) => assert_eq!(m, message),
+                _ => panic!(),
+            }
+        }
+    }
+
+    #[test]
+    fn from_bytes_not_dictionary() {
+        match TrackerScrapeResponse::from_bytes("le") {
+            Err(LavaTorrentError::MalformedResponse(m)) => {
+                assert_eq!(m, "Tracker scrape response doesn't contain a dictionary.");
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn from_bencode_ok() {
+        let response = bencode_elem!({
+            ("files", {
+                (INFO_HASH_HEX, { ("complete", 5), ("downloaded", 50), ("incomplete", 10) }),
+            }),
+            ("flags", { ("min_request_interval", 900) }),
+        });
+
+        let parsed = TrackerScrapeResponse::from_bencode(response.clone()).unwrap();
+        assert_eq!(
+            parsed,
+            TrackerScrapeResponse::from_bytes(response.encode()).unwrap()
+        );
+        assert_eq!(parsed.files.len(), 1);
+        assert_eq!(parsed.files[&INFO_HASH].complete, 5);
+        assert_eq!(parsed.trailing_bytes, 0);
+        assert_eq!(
+            parsed.extra_fields,
+            Some(HashMap::from_iter(vec![(
+                "flags".to_owned(),
+                bencode_elem!({ ("min_request_interval", 900) })
+            )]))
+        );
+    }
+
+    #[test]
+    fn from_bencode_missing_files() {
+        match TrackerScrapeResponse::from_bencode(bencode_elem!({ ("flags", {}) })) {
+            Err(LavaTorrentError::MalformedResponse(m)) => {
+                assert_eq!(m, r#""files" does not exist."#);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn from_bencode_not_dictionary() {
+        match TrackerScrapeResponse::from_bencode(bencode_elem!("files")) {
+            Err(LavaTorrentError::MalformedResponse(m)) => {
+                assert_eq!(m, "Tracker scrape response doesn't contain a dictionary.");
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn swarm_stats_from_scrape() {
+        let response = TrackerScrapeResponse::from_bytes(scrape(vec![(&INFO_HASH, -1)])).unwrap();
+        assert_eq!(
+            SwarmStats::from(&response.files[&INFO_HASH]),
+            SwarmStats {
+                seeders: None,
+                leechers: Some(10),
+                completed: Some(50),
+            }
+        );
+    }
+
+    #[test]
+    fn from_bytes_limits() {
+        let response = scrape(vec![(&INFO_HASH, 5)]);
+        let limits = ResponseLimits {
+            max_response_bytes: response.len(),
+            ..ResponseLimits::default()
+        };
+        assert_eq!(
+            TrackerScrapeResponse::from_bytes_with_limits(&response, &limits)
+                .unwrap()
+                .files
+                .len(),
+            1
+        );
+
+        let limits = ResponseLimits {
+            max_response_bytes: response.len() - 1,
+            ..ResponseLimits::default()
+        };
+        match TrackerScrapeResponse::from_bytes_with_limits(&response, &limits) {
+            Err(LavaTorrentError::MalformedResponse(m)) => assert_eq!(
+                m,
+                format!(
+                    "Tracker scrape response is {} bytes long, exceeding the limit of {} \
+                     (max_response_bytes).",
+                    response.len(),
+                    response.len() - 1
+                )
+            ),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn from_bytes_too_many_extra_fields() {
+        let response = b"d5:filesde1:xi1e1:yi1ee";
+        let limits = ResponseLimits {
+            max_extra_fields: 1,
+            ..ResponseLimits::default()
+        };
+        match TrackerScrapeResponse::from_bytes_with_limits(response, &limits) {
+            Err(LavaTorrentError::MalformedResponse(m)) => assert_eq!(
+                m,
+                "Tracker scrape response has 2 extra fields, exceeding the limit of 1 \
+                 (max_extra_fields)."
+            ),
+            _ => panic!(),
+        }
+        assert!(TrackerScrapeResponse::from_bytes(response).is_ok());
+    }
+}
+
+#[cfg(test)]
+mod tracker_display_tests {
+    use super::*;
+
+    #[test]
+    fn peer_display_anonymized() {
+        let mut peer = Peer {
+            id: None,
+            addr: PeerAddr::Resolved("1.2.3.4:6881".parse().unwrap()),
+            extra_fields: None,
+        };
+        assert_eq!(
+            format!("{:#}", peer),
+            "\t-addr: 1.2.x.x:6881\n\t========================================\n"
+        );
+
+        peer.addr = PeerAddr::Resolved("[2001:db8:85a3::8a2e:370:7334]:51413".parse().unwrap());
+        assert_eq!(
+            format!("{:#}", peer),
+            "\t-addr: [2001:db8:…]:51413\n\t========================================\n"
+        );
+        assert!(peer
+            .to_string()
+            .contains("[2001:db8:85a3::8a2e:370:7334]:51413"));
+    }
+
+    #[test]
+    fn success_display_anonymized() {
+        let response = TrackerResponse::Success {
+            interval: 1800,
+            peers: vec![
+                Peer {
+                    id: None,
+                    addr: PeerAddr::Resolved("10.20.30.40:1".parse().unwrap()),
+                    extra_fields: None,
+                },
+                Peer {
+                    id: None,
+                    addr: PeerAddr::Resolved("[fe80::1]:2".parse().unwrap()),
+                    extra_fields: None,
+                },
+            ],
+            warning: None,
+            min_interval: Some(60),
+            tracker_id: None,
+            complete: Some(5),
+            incomplete: Some(3),
+            skipped_peers: 0,
+            trailing_bytes: 0,
+            extra_fields: None,
+        };
+
+        let anonymized = response.display_anonymized().to_string();
+        assert_eq!(anonymized, format!("{:#}", response));
+        assert_eq!(
+            anonymized,
+            "-interval: 1800\n-min_interval: 60\n-complete: 5\n-incomplete: 3\n-peers (2):\n\
+             \t-addr: 10.20.x.x:1\n\t========================================\n\
+             \t-addr: [fe80:0:…]:2\n\t========================================\n\n"
+        );
+        assert!(!anonymized.contains("30.40"));
+        assert!(response.to_string().contains("10.20.30.40:1"));
+    }
+
+    // The only test that touches the global switch, as
+    // tests in the same process run concurrently.
+    #[test]
+    fn debug_masked_unless_opted_in() {
+        let peer = Peer {
+            id: None,
+            addr: PeerAddr::Resolved("1.2.3.4:6881".parse().unwrap()),
+            extra_fields: None,
+        };
+        let mut request =
+            AnnounceRequest::from_query("info_hash=aaaaaaaaaaaaaaaaaaaa&peer_id=bbbbbbbbbbbbbbbbbbbb&port=6881&uploaded=0&downloaded=0&left=0&ip=2001:db8::1")
+                .unwrap();
+
+        assert_eq!(
+            format!("{:?}", peer),
+            "Peer { id: None, addr: 1.2.x.x:6881, extra_fields: None }"
+        );
+        let hostname = PeerAddr::new("peer.example.com", 6881).unwrap();
+        assert_eq!(format!("{:?}", hostname), "REDACTED:6881");
+        let response = TrackerResponse::Success {
+            interval: 1800,
+            peers: vec![peer.clone()],
+            warning: None,
+            min_interval: None,
+            tracker_id: None,
+            complete: None,
+            incomplete: None,
+            skipped_peers: 0,
+            trailing_bytes: 0,
+            extra_fields: None,
+        };
+        assert!(format!("{:?}", response).contains("addr: 1.2.x.x:6881"));
+        assert!(!format!("{:?}", response).contains("1.2.3.4"));
+        assert!(format!("{:?}", request).contains(r#"ip: Some("2001:db8:…")"#));
+        request.ip = Some("peer.example.com".to_owned());
+        assert!(format!("{:?}", request).contains(r#"ip: Some("REDACTED")"#));
+
+        set_debug_full_addresses(true);
+        let full_peer = format!("{:?}", peer);
+        let full_request = format!("{:?}", request);
+        let full_hostname = format!("{:?}", hostname);
+        set_debug_full_addresses(false);
+
+        assert_eq!(
+            full_peer,
+            "Peer { id: None, addr: 1.2.3.4:6881, extra_fields: None }"
+        );
+        assert!(full_request.contains(r#"ip: Some("peer.example.com")"#));
+        assert_eq!(full_hostname, "peer.example.com:6881");
+        assert!(format!("{:?}", peer).contains("1.2.x.x"));
+    }
+
+    #[test]
+    fn peer_display_hostname() {
+        let peer = Peer::from_addr(PeerAddr::new("peer.example.com", 6881).unwrap());
+        assert_eq!(
+            peer.to_string(),
+            "\t-addr: peer.example.com:6881\n\t========================================\n"
+        );
+        assert_eq!(
+            format!("{:#}", peer),
+            "\t-addr: REDACTED:6881\n\t========================================\n"
+        );
+        assert_eq!(format!("{:#}", peer.addr), "REDACTED:6881");
+    }
+
+    #[test]
+    fn peer_display() {
+        let mut peer = Peer {
+            id: Some(b"-LT2080-abcdefghijkl".to_vec()),
+            addr: PeerAddr::Resolved("127.0.0.1:6881".parse().unwrap()),
+            extra_fields: None,
+        };
+        assert_eq!(
+            peer.to_string(),
+            "\t-id: -LT2080-abcdefghijkl\n\t-addr: 127.0.0.1:6881\n\t========================================\n"
+        );
+
+        // ids that aren't utf8 are summarized in hex
+        peer.id = Some(vec![0x00, 0x0a, 0xff]);
+        assert!(peer.to_string().starts_with("\t-id: <3 bytes: 0x000aff>\n"));
+
+        peer.id = Some(vec![0xff; 1024]);
+        assert!(peer
+            .to_string()
+            .starts_with(&format!("\t-id: <1024 bytes: 0x{}...>\n", "ff".repeat(32))));
+
+        peer.id = None;
+        peer.extra_fields = Some(HashMap::from([(
+            "key".to_owned(),
+            BencodeElem::Bytes(vec![0xfe, 0xff]),
+        )]));
+        assert!(peer.to_string().contains("-key: <2 bytes: 0xfeff>\n"));
+    }
+
+    #[test]
+    fn failure_display() {
+        let response = TrackerResponse::Failure {
+            reason: "unregistered torrent".to_owned(),
+        };
+        assert_eq!(response.to_string(), "failure: unregistered torrent\n");
+    }
+
+    #[test]
+    fn success_display() {
+        let response = TrackerResponse::Success {
+            interval: 1800,
+            peers: vec![],
+            warning: Some("slow down".to_owned()),
+            min_interval: None,
+            tracker_id: None,
+            complete: Some(5),
+            incomplete: None,
+            skipped_peers: 2,
+            trailing_bytes: 3,
+            extra_fields: None,
+        };
+        assert_eq!(
+            response.to_string(),
+            "-interval: 1800\n\
+             -warning: slow down\n\
+             -complete: 5\n\
+             -skipped_peers: 2\n\
+             -trailing_bytes: 3\n\
+             -peers (0):\n\n"
+        );
+    }
+
+    #[test]
+    fn success_display_binary_fields() {
+        let response = TrackerResponse::Success {
+            interval: 1800,
+            peers: vec![],
+            warning: None,
+            min_interval: None,
+            tracker_id: None,
+            complete: None,
+            incomplete: None,
+            skipped_peers: 0,
+            trailing_bytes: 0,
+            extra_fields: Some(HashMap::from([
+                ("blob".to_owned(), BencodeElem::Bytes(vec![0x80; 100])),
+                (
+                    "raw".to_owned(),
+                    BencodeElem::RawDictionary(HashMap::from([(
+                        vec![0xff],
+                        BencodeElem::Integer(1),
+                    )])),
+                ),
+            ])),
+        };
+        assert_eq!(
+            response.to_string(),
+            format!(
+                "-interval: 1800\n\
+                 -blob: <100 bytes: 0x{}...>\n\
+                 -raw: {{ (<1 byte: 0xff>, 1) }}\n\
+                 -peers (0):\n\n",
+                "80".repeat(32)
+            )
+        );
+    }
+
+    #[test]
+    fn scrape_display_binary_fields() {
+        let response = TrackerScrapeResponse {
+            files: vec![(
+                [0xab; 20],
+                SwarmMetadata {
+                    complete: 5,
+                    incomplete: 10,
+                    downloaded: 50,
+                    extra_fields: Some(HashMap::from([(
+                        "name".to_owned(),
+                        BencodeElem::Bytes(vec![0xfe, 0xff]),
+                    )])),
+                },
+            )]
+            .into_iter()
+            .collect(),
+            trailing_bytes: 0,
+            extra_fields: Some(HashMap::from([(
+                "flags".to_owned(),
+                BencodeElem::Bytes(vec![0x00]),
+            )])),
+        };
+        let display = response.to_string();
+        assert!(
+            display.contains("-name: <2 bytes: 0xfeff>\n"),
+            "{}",
+            display
+        );
+        assert!(display.ends_with("-flags: <1 byte: 0x00>\n"), "{}", display);
+    }
+
+    #[test]
+    fn scrape_display() {
+        let mut info_hash = [0xab; 20];
+        info_hash[0] = 0x01;
+        let response = TrackerScrapeResponse {
+            files: vec![(
+                info_hash,
+                SwarmMetadata {
+                    complete: 5,
+                    incomplete: 10,
+                    downloaded: 50,
+                    extra_fields: None,
+                },
+            )]
+            .into_iter()
+            .collect(),
+            trailing_bytes: 0,
+            extra_fields: None,
+        };
+        assert_eq!(
+            response.to_string(),
+            format!(
+                "files:\n01{}\n-complete: 5\n-incomplete: 10\n-downloaded: 50\n{}\n",
+                "ab".repeat(19),
+                "=".repeat(40)
+            )
+        );
+    }
+}
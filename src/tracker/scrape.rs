@@ -0,0 +1,657 @@
+use super::*;
+use crate::util;
+use std::io::{BufRead, BufReader, Read};
+
+const WHAT: &str = "Tracker scrape response";
+// String lengths with more digits than this can't be valid, and integers
+// with more digits than this can't be parsed anyway. Limiting them keeps
+// a missing delimiter from making us buffer the rest of the input.
+const MAX_DIGITS: usize = 20;
+// Real entries are well below 100 bytes.
+const DEFAULT_MAX_ENTRY_BYTES: usize = 64 * 1024;
+
+impl<R> ScrapeIter<R>
+where
+    R: Read,
+{
+    /// Start reading the scrape response in `reader`.
+    ///
+    /// Everything up to the start of `files` is read and validated here,
+    /// so `Err(error)` is returned if the response is not a dictionary or
+    /// has no `files` at all. The entries are only read when iterating.
+    ///
+    /// `reader` is buffered internally, so there is no need to wrap
+    /// it in a `BufReader`.
+    ///
+    /// Keys and (encoded) values are limited to 64 KiB each.
+    pub fn new(reader: R) -> Result<ScrapeIter<R>, LavaTorrentError> {
+        Self::with_limits(reader, DEFAULT_MAX_ENTRY_BYTES)
+    }
+
+    /// Same as [`new()`](#method.new), but keys and (encoded) values are
+    /// limited to `max_entry_bytes` each instead of 64 KiB.
+    ///
+    /// A key or value that exceeds it makes iteration fail with a
+    /// `MalformedResponse` error. String lengths are checked before
+    /// their content is read, so a bogus length is caught up front
+    /// instead of being buffered. Skipped values (e.g. fields before
+    /// `files`) are not buffered, and are thus not limited.
+    pub fn with_limits(
+        reader: R,
+        max_entry_bytes: usize,
+    ) -> Result<ScrapeIter<R>, LavaTorrentError> {
+        let mut iter = ScrapeIter {
+            reader: BufReader::new(reader),
+            max_entry_bytes,
+            key: Vec::new(),
+            value: Vec::new(),
+            previous_key: Vec::new(),
+            is_done: false,
+        };
+        iter.read_until_files()?;
+        Ok(iter)
+    }
+
+    pub(crate) fn lookup(
+        reader: R,
+        wanted: &HashSet<InfoHash>,
+    ) -> Result<HashMap<InfoHash, SwarmMetadata>, LavaTorrentError> {
+        let mut found = HashMap::with_capacity(wanted.len());
+        if wanted.is_empty() {
+            return Ok(found);
+        }
+
+        for entry in ScrapeIter::new(reader)? {
+            let (info_hash, metadata) = entry?;
+            if wanted.contains(&info_hash) {
+                found.entry(info_hash).or_insert(metadata);
+                if found.len() == wanted.len() {
+                    break;
+                }
+            }
+        }
+        Ok(found)
+    }
+
+    // Read the top-level dictionary up to (and including) the
+    // prefix of `files`, skipping the entries before it.
+    fn read_until_files(&mut self) -> Result<(), LavaTorrentError> {
+        // same leniency as `parse_top_level()`
+        if self.reader.fill_buf()?.starts_with(UTF8_BOM) {
+            self.reader.consume(UTF8_BOM.len());
+        }
+        let mut has_whitespace = false;
+        while self
+            .peek_byte()?
+            .is_some_and(|byte| byte.is_ascii_whitespace())
+        {
+            self.reader.consume(1);
+            has_whitespace = true;
+        }
+        match self.peek_byte()? {
+            None => {
+                return Err(LavaTorrentError::MalformedResponse(Cow::Owned(format!(
+                    "{} should contain 1 and only 1 top-level element, 0 found.",
+                    WHAT
+                ))));
+            }
+            Some(b'd') if !has_whitespace => self.reader.consume(1),
+            Some(_) => {
+                return Err(LavaTorrentError::MalformedResponse(Cow::Owned(format!(
+                    "{} doesn't contain a dictionary.",
+                    WHAT
+                ))));
+            }
+        }
+
+        loop {
+            if self.next_byte_if(b'e')? {
+                return Err(LavaTorrentError::MalformedResponse(Cow::Borrowed(
+                    r#""files" does not exist."#,
+                )));
+            }
+            self.read_key()?;
+            let is_files = self.key.as_slice() == b"files";
+            self.check_sorted()?;
+            if is_files {
+                break;
+            }
+            self.skip_value()?;
+        }
+
+        if !self.next_byte_if(b'd')? {
+            self.read_value()?;
+            let kind = match BencodeElem::from_bytes(&self.value)?.pop() {
+                Some(value) => value.kind_str(),
+                None => "nothing",
+            };
+            return Err(LavaTorrentError::MalformedResponse(Cow::Owned(format!(
+                r#""files" maps to {}, expected Dictionary or RawDictionary."#,
+                kind
+            ))));
+        }
+        // entries of `files` are checked on their own
+        self.previous_key.clear();
+        Ok(())
+    }
+
+    // Read the next entry of `files`, or the rest of the
+    // response (returning `None`) if there is none left.
+    fn read_entry(&mut self) -> Result<Option<(InfoHash, SwarmMetadata)>, LavaTorrentError> {
+        if self.next_byte_if(b'e')? {
+            // skip the entries after `files`
+            self.previous_key = b"files".to_vec();
+            while !self.next_byte_if(b'e')? {
+                self.read_key()?;
+                self.check_sorted()?;
+                self.skip_value()?;
+            }
+            return Ok(None);
+        }
+
+        self.read_key()?;
+        self.check_sorted()?;
+        self.read_value()?;
+
+        let metadata = match BencodeElem::from_bytes(&self.value)?.pop() {
+            Some(BencodeElem::Dictionary(dict)) => SwarmMetadata::from_dict(dict)?,
+            _ => {
+                return Err(LavaTorrentError::MalformedResponse(Cow::Owned(format!(
+                    r#"swarm metadata for {} is not a dictionary."#,
+                    hex::display(&self.previous_key)
+                ))))
+            }
+        };
+        // `check_sorted()` moved the key over
+        let (info_hash, _) = TrackerScrapeResponse::decode_info_hash(&self.previous_key)?;
+        Ok(Some((info_hash, metadata)))
+    }
+
+    // Check that `self.key` is not sorted before the previous key in the
+    // same dictionary, then make it the previous key for the next one.
+    fn check_sorted(&mut self) -> Result<(), LavaTorrentError> {
+        // "sorted as raw strings, not alphanumerics"
+        if self.previous_key > self.key {
+            return Err(LavaTorrentError::MalformedBencode(Cow::Borrowed(
+                "A dictionary is not properly sorted.",
+            )));
+        }
+        std::mem::swap(&mut self.previous_key, &mut self.key);
+        Ok(())
+    }
+
+    // Read a dictionary key into `self.key`.
+    fn read_key(&mut self) -> Result<(), LavaTorrentError> {
+        let mut key = std::mem::take(&mut self.key);
+        key.clear();
+        let result = match self.peek_byte()? {
+            Some(b'0'..=b'9') => self.copy_string(Some(&mut key), false),
+            Some(_) => Err(LavaTorrentError::MalformedBencode(Cow::Borrowed(
+                "Non-string dictionary key.",
+            ))),
+            None => Err(Self::unexpected_end()),
+        };
+        self.key = key;
+        result
+    }
+
+    // Read the next value (i.e. its encoded bytes) into `self.value`.
+    fn read_value(&mut self) -> Result<(), LavaTorrentError> {
+        let mut value = std::mem::take(&mut self.value);
+        value.clear();
+        let result = self.copy_value(Some(&mut value));
+        self.value = value;
+        result
+    }
+
+    // Skip the next value without keeping it around.
+    fn skip_value(&mut self) -> Result<(), LavaTorrentError> {
+        self.copy_value(None)
+    }
+
+    // Copy the encoded bytes of the next value to `out` (if any). Only the
+    // structure is checked here; what's copied is parsed afterwards.
+    //
+    // Nested values are tracked with a counter rather than recursion,
+    // so deeply nested input can't overflow the stack.
+    fn copy_value(&mut self, mut out: Option<&mut Vec<u8>>) -> Result<(), LavaTorrentError> {
+        let mut depth = 0_usize;
+        loop {
+            match self.peek_byte()? {
+                Some(prefix @ (b'd' | b'l')) => {
+                    self.reader.consume(1);
+                    if let Some(ref mut out) = out {
+                        self.check_entry_bytes(out.len(), 1)?;
+                        out.push(prefix);
+                    }
+                    depth += 1;
+                    continue;
+                }
+                Some(b'e') if depth > 0 => {
+                    self.reader.consume(1);
+                    if let Some(ref mut out) = out {
+                        self.check_entry_bytes(out.len(), 1)?;
+                        out.push(b'e');
+                    }
+                    depth -= 1;
+                }
+                Some(b'i') => {
+                    self.reader.consume(1);
+                    let mut digits = Vec::new();
+                    self.read_digits(&mut digits, b'e', "Integer delimiter not found.")?;
+                    if let Some(ref mut out) = out {
+                        self.check_entry_bytes(out.len(), digits.len() as u64 + 2)?;
+                        out.push(b'i');
+                        out.extend_from_slice(&digits);
+                        out.push(b'e');
+                    }
+                }
+                Some(b'0'..=b'9') => self.copy_string(out.as_deref_mut(), true)?,
+                Some(_) => {
+                    return Err(LavaTorrentError::MalformedBencode(Cow::Borrowed(
+                        "Unexpected byte found where a value should start.",
+                    )));
+                }
+                None => return Err(Self::unexpected_end()),
+            }
+
+            if depth == 0 {
+                return Ok(());
+            }
+        }
+    }
+
+    // Read a string and append its content to `out` (along with its
+    // length prefix if `with_prefix` is set), or discard it if `out` is `None`.
+    fn copy_string(
+        &mut self,
+        out: Option<&mut Vec<u8>>,
+        with_prefix: bool,
+    ) -> Result<(), LavaTorrentError> {
+        let mut digits = Vec::new();
+        self.read_digits(&mut digits, b':', "String delimiter not found.")?;
+        // `read_digits()` only accepts ASCII
+        let length = std::str::from_utf8(&digits)
+            .ok()
+            .filter(|digits| !digits.starts_with('0') || (digits.len() == 1))
+            .and_then(|digits| digits.parse::<u64>().ok())
+            .ok_or(LavaTorrentError::MalformedBencode(Cow::Borrowed(
+                "Invalid string length found.",
+            )))?;
+
+        let read = match out {
+            Some(out) => {
+                // checked before anything is read, as `length` is untrusted
+                let prefix_length = if with_prefix { digits.len() + 1 } else { 0 };
+                self.check_entry_bytes(out.len(), (prefix_length as u64).saturating_add(length))?;
+                if with_prefix {
+                    out.extend_from_slice(&digits);
+                    out.push(b':');
+                }
+                let mut content = self.reader.by_ref().take(length);
+                util::usize_to_u64(content.read_to_end(out)?)?
+            }
+            None => std::io::copy(&mut self.reader.by_ref().take(length), &mut std::io::sink())?,
+        };
+        if read != length {
+            return Err(Self::unexpected_end());
+        }
+        Ok(())
+    }
+
+    // Read the bytes before `delimiter` (which is consumed) into `digits`.
+    // Only `-` and ASCII digits are accepted.
+    fn read_digits(
+        &mut self,
+        digits: &mut Vec<u8>,
+        delimiter: u8,
+        error: &'static str,
+    ) -> Result<(), LavaTorrentError> {
+        loop {
+            match self.peek_byte()? {
+                Some(byte) if byte == delimiter => {
+                    self.reader.consume(1);
+                    return Ok(());
+                }
+                Some(byte @ (b'-' | b'0'..=b'9')) if digits.len() < MAX_DIGITS => {
+                    self.reader.consume(1);
+                    digits.push(byte);
+                }
+                Some(_) => {
+                    return Err(LavaTorrentError::MalformedBencode(Cow::Borrowed(error)));
+                }
+                None => return Err(Self::unexpected_end()),
+            }
+        }
+    }
+
+    // Check that adding `additional` bytes to a key or value
+    // that is `buffered` bytes long keeps it within the limit.
+    fn check_entry_bytes(&self, buffered: usize, additional: u64) -> Result<(), LavaTorrentError> {
+        let total = util::usize_to_u64(buffered)?.saturating_add(additional);
+        if total > util::usize_to_u64(self.max_entry_bytes)? {
+            return Err(LavaTorrentError::MalformedResponse(Cow::Owned(format!(
+                "{} has a key or value of more than {} bytes (max_entry_bytes).",
+                WHAT, self.max_entry_bytes
+            ))));
+        }
+        Ok(())
+    }
+
+    // Consume the next byte if it is `byte`, and tell whether it was.
+    fn next_byte_if(&mut self, byte: u8) -> Result<bool, LavaTorrentError> {
+        match self.peek_byte()? {
+            Some(next) if next == byte => {
+                self.reader.consume(1);
+                Ok(true)
+            }
+            Some(_) => Ok(false),
+            None => Err(Self::unexpected_end()),
+        }
+    }
+
+    fn peek_byte(&mut self) -> Result<Option<u8>, LavaTorrentError> {
+        Ok(self.reader.fill_buf()?.first().copied())
+    }
+
+    fn unexpected_end() -> LavaTorrentError {
+        LavaTorrentError::MalformedBencode(Cow::Borrowed("Expected more bytes, but none found."))
+    }
+}
+
+impl<R> Iterator for ScrapeIter<R>
+where
+    R: Read,
+{
+    type Item = Result<(InfoHash, SwarmMetadata), LavaTorrentError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.is_done {
+            return None;
+        }
+
+        match self.read_entry() {
+            Ok(Some(entry)) => Some(Ok(entry)),
+            Ok(None) => {
+                self.is_done = true;
+                None
+            }
+            Err(e) => {
+                self.is_done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod scrape_iter_tests {
+    use super::*;
+
+    const ENTRY: &[u8] = b"d8:completei5e10:downloadedi50e10:incompletei10ee";
+
+    fn collect(bytes: &[u8]) -> Result<Vec<(InfoHash, SwarmMetadata)>, LavaTorrentError> {
+        ScrapeIter::new(bytes)?.collect()
+    }
+
+    fn entry(key: &[u8]) -> Vec<u8> {
+        [key.len().to_string().as_bytes(), b":", key, ENTRY].concat()
+    }
+
+    #[test]
+    fn iter_ok() {
+        let bytes = [
+            UTF8_BOM,
+            b"d5:filesd",
+            &entry(&[0x01; 20]),
+            &entry(&[0xff; 20]),
+            b"e5:flagsd20:min_request_intervali60eee\n",
+        ]
+        .concat();
+        let entries = collect(&bytes).unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].0, [0x01; 20]);
+        assert_eq!(entries[1].0, [0xff; 20]);
+        assert_eq!(entries[1].1.complete, 5);
+        assert_eq!(entries[1].1.downloaded, 50);
+        assert_eq!(entries[1].1.incomplete, 10);
+    }
+
+    #[test]
+    fn iter_hex_keys() {
+        let bytes = [
+            b"d5:filesd".as_slice(),
+            &entry(b"778ce280b595e57780ff083f2eb6f897dfa4a4ee"),
+            b"ee",
+        ]
+        .concat();
+        let entries = collect(&bytes).unwrap();
+
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].0[..2], [0x77, 0x8c]);
+    }
+
+    #[test]
+    fn iter_skip_fields_before_files() {
+        let bytes = [
+            b"d4:abcdl1:ai-3ed1:xleee5:filesd".as_slice(),
+            &entry(&[0x01; 20]),
+            b"ee",
+        ]
+        .concat();
+        assert_eq!(collect(&bytes).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn iter_empty_files() {
+        assert!(collect(b"d5:filesdee").unwrap().is_empty());
+    }
+
+    #[test]
+    fn iter_empty() {
+        match ScrapeIter::new(b" \n".as_slice()) {
+            Err(LavaTorrentError::MalformedResponse(m)) => assert_eq!(
+                m,
+                "Tracker scrape response should contain 1 and only 1 top-level element, 0 found."
+            ),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn iter_not_dictionary() {
+        match ScrapeIter::new(b"li1ee".as_slice()) {
+            Err(LavaTorrentError::MalformedResponse(m)) => {
+                assert_eq!(m, "Tracker scrape response doesn't contain a dictionary.")
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn iter_files_missing() {
+        match ScrapeIter::new(b"d4:abcdi1ee".as_slice()) {
+            Err(LavaTorrentError::MalformedResponse(m)) => {
+                assert_eq!(m, r#""files" does not exist."#)
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn iter_files_not_dictionary() {
+        match ScrapeIter::new(b"d5:filesli1eee".as_slice()) {
+            Err(LavaTorrentError::MalformedResponse(m)) => assert_eq!(
+                m,
+                r#""files" maps to List, expected Dictionary or RawDictionary."#
+            ),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn iter_metadata_not_dictionary() {
+        let bytes = [b"d5:filesd20:".as_slice(), &[0xff; 20], b"i1eee"].concat();
+        match collect(&bytes) {
+            Err(LavaTorrentError::MalformedResponse(m)) => assert_eq!(
+                m,
+                format!(
+                    "swarm metadata for {} is not a dictionary.",
+                    "ff".repeat(20)
+                )
+            ),
+            _ => panic!(),
+        }
+
+        // hex digits are zero-padded
+        let bytes = [b"d5:filesd20:".as_slice(), &[0x0a; 20], b"i1eee"].concat();
+        match collect(&bytes) {
+            Err(LavaTorrentError::MalformedResponse(m)) => assert_eq!(
+                m,
+                format!(
+                    "swarm metadata for {} is not a dictionary.",
+                    "0a".repeat(20)
+                )
+            ),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn iter_unsorted() {
+        let bytes = [
+            b"d5:filesd".as_slice(),
+            &entry(&[0xff; 20]),
+            &entry(&[0x01; 20]),
+            b"ee",
+        ]
+        .concat();
+        let mut iter = ScrapeIter::new(bytes.as_slice()).unwrap();
+
+        assert!(iter.next().unwrap().is_ok());
+        match iter.next() {
+            Some(Err(LavaTorrentError::MalformedBencode(m))) => {
+                assert_eq!(m, "A dictionary is not properly sorted.")
+            }
+            _ => panic!(),
+        }
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn iter_truncated() {
+        let bytes = [b"d5:filesd".as_slice(), &entry(&[0x01; 20])].concat();
+        for end in 9..bytes.len() {
+            match collect(&bytes[..end]) {
+                Err(LavaTorrentError::MalformedBencode(m)) => {
+                    assert_eq!(m, "Expected more bytes, but none found.")
+                }
+                _ => panic!(),
+            }
+        }
+    }
+
+    #[test]
+    fn iter_invalid_string_length() {
+        match collect(b"d5:filesd020:") {
+            Err(LavaTorrentError::MalformedBencode(m)) => {
+                assert_eq!(m, "Invalid string length found.")
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn iter_huge_string_length() {
+        // would take forever (or OOM) if the declared length were trusted
+        match collect(b"d5:filesd99999999999:") {
+            Err(LavaTorrentError::MalformedResponse(m)) => assert_eq!(
+                m,
+                "Tracker scrape response has a key or value of more than 65536 bytes \
+                 (max_entry_bytes)."
+            ),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn iter_with_limits() {
+        let key = [b'a'; 20];
+        let bytes = [b"d5:filesd".as_slice(), &entry(&key), b"ee"].concat();
+
+        let entries: Vec<_> = ScrapeIter::with_limits(bytes.as_slice(), ENTRY.len())
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(entries.len(), 1);
+
+        let mut iter = ScrapeIter::with_limits(bytes.as_slice(), ENTRY.len() - 1).unwrap();
+        match iter.next() {
+            Some(Err(LavaTorrentError::MalformedResponse(m))) => assert_eq!(
+                m,
+                format!(
+                    "Tracker scrape response has a key or value of more than {} bytes \
+                     (max_entry_bytes).",
+                    ENTRY.len() - 1
+                )
+            ),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn iter_non_string_key() {
+        match collect(b"d5:filesdi1ei2eee") {
+            Err(LavaTorrentError::MalformedBencode(m)) => {
+                assert_eq!(m, "Non-string dictionary key.")
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn iter_matches_from_bytes() {
+        let bytes = [
+            b"d5:filesd".as_slice(),
+            &entry(&[0x01; 20]),
+            &entry(&[0x02; 20]),
+            &entry(&[0xff; 20]),
+            b"ee",
+        ]
+        .concat();
+        let expected = TrackerScrapeResponse::from_bytes(&bytes).unwrap().files;
+        let actual = collect(&bytes)
+            .unwrap()
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn lookup_ok() {
+        let bytes = [
+            b"d5:filesd".as_slice(),
+            &entry(&[0x01; 20]),
+            &entry(&[0x02; 20]),
+            // never reached
+            b"junk",
+        ]
+        .concat();
+        let wanted = HashSet::from([[0x01; 20], [0x02; 20]]);
+        let found = scrape_lookup(bytes.as_slice(), &wanted).unwrap();
+
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[&[0x02; 20]].complete, 5);
+    }
+
+    #[test]
+    fn lookup_missing() {
+        let bytes = [b"d5:filesd".as_slice(), &entry(&[0x01; 20]), b"ee"].concat();
+        let wanted = HashSet::from([[0x01; 20], [0x02; 20]]);
+        let found = scrape_lookup(bytes.as_slice(), &wanted).unwrap();
+
+        assert_eq!(found.len(), 1);
+        assert!(found.contains_key(&[0x01; 20]));
+    }
+}
@@ -1,8 +1,13 @@
 use crate::LavaTorrentError;
-use std::borrow::Cow;
-use std::convert::TryFrom;
-use std::path::{Path, PathBuf};
-
+use alloc::borrow::Cow;
+use alloc::format;
+#[cfg(feature = "std")]
+use alloc::string::String;
+use core::convert::TryFrom;
+#[cfg(feature = "std")]
+use std::path::Path;
+
+#[cfg(feature = "std")]
 pub(crate) fn u64_to_usize(src: u64) -> Result<usize, LavaTorrentError> {
     usize::try_from(src).map_err(|_| {
         LavaTorrentError::FailedNumericConv(Cow::Owned(format!(
@@ -12,6 +17,7 @@ pub(crate) fn u64_to_usize(src: u64) -> Result<usize, LavaTorrentError> {
     })
 }
 
+#[cfg(feature = "std")]
 pub(crate) fn usize_to_u64(src: usize) -> Result<u64, LavaTorrentError> {
     u64::try_from(src).map_err(|_| {
         LavaTorrentError::FailedNumericConv(Cow::Owned(format!("[{}] does not fit into u64.", src)))
@@ -27,50 +33,33 @@ pub(crate) fn i64_to_usize(src: i64) -> Result<usize, LavaTorrentError> {
     })
 }
 
+#[cfg(feature = "std")]
 pub(crate) fn i64_to_u64(src: i64) -> Result<u64, LavaTorrentError> {
     u64::try_from(src).map_err(|_| {
         LavaTorrentError::FailedNumericConv(Cow::Owned(format!("[{}] does not fit into u64.", src)))
     })
 }
 
+// `a / b`, rounded up. Fails instead of panicking if `b` is 0.
+#[cfg(feature = "std")]
+pub(crate) fn div_ceil_u64(a: u64, b: u64) -> Result<u64, LavaTorrentError> {
+    match b {
+        0 => Err(LavaTorrentError::FailedNumericConv(Cow::Owned(format!(
+            "[{}] cannot be divided by 0.",
+            a
+        )))),
+        _ => Ok(a.div_ceil(b)),
+    }
+}
+
+#[cfg(feature = "std")]
 pub(crate) fn u64_to_i64(src: u64) -> Result<i64, LavaTorrentError> {
     i64::try_from(src).map_err(|_| {
         LavaTorrentError::FailedNumericConv(Cow::Owned(format!("[{}] does not fit into i64.", src)))
     })
 }
 
-// this method is recursive, i.e. entries in subdirectories
-// are also returned
-//
-// *nix hidden files/dirs are ignored
-//
-// returned vec is sorted by path
-pub(crate) fn list_dir<P>(path: P) -> Result<Vec<(PathBuf, u64)>, LavaTorrentError>
-where
-    P: AsRef<Path>,
-{
-    let mut entries = Vec::new();
-
-    for entry in path.as_ref().read_dir()? {
-        let entry = entry?;
-        let path = entry.path();
-        let metadata = path.metadata()?;
-
-        if last_component(&path)?.starts_with('.') {
-            continue;
-        } // hidden files/dirs are ignored
-
-        if metadata.is_dir() {
-            entries.extend(list_dir(path)?);
-        } else {
-            entries.push((path, metadata.len()));
-        }
-    }
-
-    entries.sort_by(|(p1, _), (p2, _)| p1.cmp(p2));
-    Ok(entries)
-}
-
+#[cfg(feature = "std")]
 pub(crate) fn last_component<P>(path: P) -> Result<String, LavaTorrentError>
 where
     P: AsRef<Path>,
@@ -85,6 +74,21 @@ where
     }
 }
 
+// How many leading bytes `describe_invalid_utf8()` shows.
+#[cfg(feature = "std")]
+const INVALID_UTF8_PREFIX: usize = 8;
+
+/// Describe `bytes`, a byte string that is not valid UTF-8, for error
+/// messages, e.g. `a byte string that is not valid UTF-8 (3 bytes, starts with 0xff6869)`.
+#[cfg(feature = "std")]
+pub(crate) fn describe_invalid_utf8(bytes: &[u8]) -> String {
+    format!(
+        "a byte string that is not valid UTF-8 ({} bytes, starts with 0x{})",
+        bytes.len(),
+        crate::hex::display(&bytes[..bytes.len().min(INVALID_UTF8_PREFIX)])
+    )
+}
+
 pub(crate) struct ByteBuffer<'a> {
     bytes: &'a [u8],
     position: usize, // current cursor position
@@ -92,7 +96,7 @@ pub(crate) struct ByteBuffer<'a> {
 }
 
 impl<'a> ByteBuffer<'a> {
-    pub(crate) fn new(bytes: &[u8]) -> ByteBuffer {
+    pub(crate) fn new(bytes: &[u8]) -> ByteBuffer<'_> {
         ByteBuffer {
             bytes,
             position: 0,
@@ -119,6 +123,18 @@ impl<'a> ByteBuffer<'a> {
         self.position
     }
 
+    // The bytes after the cursor, which is not moved.
+    pub(crate) fn remaining(&self) -> &'a [u8] {
+        &self.bytes[self.position..]
+    }
+
+    // Consume `n` bytes, or whatever is left if there are fewer.
+    pub(crate) fn take_up_to(&mut self, n: usize) -> &'a [u8] {
+        let taken = &self.remaining()[..n.min(self.length - self.position)];
+        self.advance(taken.len());
+        taken
+    }
+
     pub(crate) fn is_empty(&self) -> bool {
         self.position >= self.length
     }
@@ -142,42 +158,30 @@ mod util_tests {
     use super::*;
 
     #[test]
-    fn list_dir_ok() {
-        assert_eq!(
-            list_dir("tests/files").unwrap(),
-            vec![
-                "tests/files/byte_sequence",
-                "tests/files/symlink",
-                "tests/files/tails-amd64-3.6.1.torrent",
-                "tests/files/ubuntu-16.04.4-desktop-amd64.iso.torrent",
-                // no [.hidden]
-            ]
-            .iter()
-            .map(PathBuf::from)
-            .map(|p| (p.clone(), p.metadata().unwrap().len()))
-            .collect::<Vec<(PathBuf, u64)>>()
-        );
+    #[cfg(feature = "std")]
+    fn div_ceil_u64_ok() {
+        assert_eq!(div_ceil_u64(0, 1).unwrap(), 0);
+        assert_eq!(div_ceil_u64(5, 2).unwrap(), 3);
+        assert_eq!(div_ceil_u64(u64::MAX, 1).unwrap(), u64::MAX);
+        assert_eq!(div_ceil_u64(u64::MAX, 2).unwrap(), 1 << 63);
+        assert_eq!(div_ceil_u64(u64::MAX - 1, u64::MAX).unwrap(), 1);
+        assert_eq!(div_ceil_u64(u64::MAX, u64::MAX).unwrap(), 1);
+        assert_eq!(div_ceil_u64(u64::MAX, 1 << 62).unwrap(), 4);
     }
 
     #[test]
-    fn list_dir_with_subdir() {
-        assert_eq!(
-            list_dir("src/torrent").unwrap(),
-            vec![
-                "src/torrent/mod.rs",
-                "src/torrent/v1/build.rs",
-                "src/torrent/v1/mod.rs",
-                "src/torrent/v1/read.rs",
-                "src/torrent/v1/write.rs",
-            ]
-            .iter()
-            .map(PathBuf::from)
-            .map(|p| (p.clone(), p.metadata().unwrap().len()))
-            .collect::<Vec<(PathBuf, u64)>>()
-        );
+    #[cfg(feature = "std")]
+    fn div_ceil_u64_by_zero() {
+        match div_ceil_u64(42, 0) {
+            Err(LavaTorrentError::FailedNumericConv(m)) => {
+                assert_eq!(m, "[42] cannot be divided by 0.")
+            }
+            _ => panic!(),
+        }
     }
 
     #[test]
+    #[cfg(feature = "std")]
     fn last_component_ok() {
         assert_eq!(
             last_component("/root/dir/file.ext").unwrap(),
@@ -186,11 +190,13 @@ mod util_tests {
     }
 
     #[test]
+    #[cfg(feature = "std")]
     fn last_component_ok_2() {
         assert_eq!(last_component("/root/dir/dir2").unwrap(), "dir2".to_owned());
     }
 
     #[test]
+    #[cfg(feature = "std")]
     fn last_component_err() {
         match last_component("/root/dir/..") {
             Err(LavaTorrentError::InvalidArgument(m)) => {
@@ -201,12 +207,27 @@ mod util_tests {
     }
 
     #[test]
+    #[cfg(feature = "std")]
+    fn describe_invalid_utf8_ok() {
+        assert_eq!(
+            describe_invalid_utf8(b"\xffhi"),
+            "a byte string that is not valid UTF-8 (3 bytes, starts with 0xff6869)"
+        );
+        assert_eq!(
+            describe_invalid_utf8(b"\xfe\xff0123456789"),
+            "a byte string that is not valid UTF-8 (12 bytes, starts with 0xfeff303132333435)"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
     fn u64_to_usize_ok() {
         // @todo: add test for err
         assert_eq!(u64_to_usize(42).unwrap(), 42);
     }
 
     #[test]
+    #[cfg(feature = "std")]
     fn usize_to_u64_ok() {
         // @todo: add test for err
         assert_eq!(usize_to_u64(42).unwrap(), 42);
@@ -228,11 +249,13 @@ mod util_tests {
     }
 
     #[test]
+    #[cfg(feature = "std")]
     fn i64_to_u64_ok() {
         assert_eq!(i64_to_u64(42).unwrap(), 42);
     }
 
     #[test]
+    #[cfg(feature = "std")]
     fn i64_to_u64_err() {
         match i64_to_u64(-1) {
             Err(LavaTorrentError::FailedNumericConv(m)) => {
@@ -243,11 +266,13 @@ mod util_tests {
     }
 
     #[test]
+    #[cfg(feature = "std")]
     fn u64_to_i64_ok() {
         assert_eq!(u64_to_i64(42).unwrap(), 42);
     }
 
     #[test]
+    #[cfg(feature = "std")]
     fn u64_to_i64_err() {
         match u64_to_i64(u64::MAX) {
             Err(LavaTorrentError::FailedNumericConv(m)) => {
@@ -1,10 +1,17 @@
+#![cfg(feature = "std")]
+
 extern crate lava_torrent;
 extern crate rand;
 
 use lava_torrent::bencode::BencodeElem;
-use lava_torrent::torrent::v1::{Integer, Torrent, TorrentBuilder};
+use lava_torrent::torrent::v1::{
+    self, AssembleOptions, BuildEachOptions, BuildReport, ContentFingerprint, DryRunTorrent,
+    FileIssue, FileIssuePolicy, HashingStrategy, Integer, Piece, PieceStats, RuleViolation,
+    Torrent, TorrentBuilder, UploadRules,
+};
 use lava_torrent::LavaTorrentError;
 use rand::Rng;
+use sha1::{Digest, Sha1};
 
 const OUTPUT_ROOT: &str = "tests/tmp/";
 const PIECE_LENGTH: Integer = 32 * 1024; // n * 1024 KiB
@@ -273,6 +280,7 @@ fn build_multi_file_parallel_ok() {
             BencodeElem::String("UTF-8".to_owned()),
         )
         .add_extra_info_field("private".to_owned(), BencodeElem::Integer(0))
+        .set_hashing_strategy(HashingStrategy::Parallel)
         .build()
         .unwrap()
         .write_into_file(&output_name)
@@ -299,6 +307,7 @@ fn build_multi_file_parallel_non_blocking_ok() {
             BencodeElem::String("UTF-8".to_owned()),
         )
         .add_extra_info_field("private".to_owned(), BencodeElem::Integer(0))
+        .set_hashing_strategy(HashingStrategy::Parallel)
         .build_non_blocking()
         .unwrap();
 
@@ -326,6 +335,7 @@ fn build_multi_file_parallel_non_blocking_ok() {
 #[test]
 fn build_multi_file_parallel_non_blocking_cancel() {
     let build = TorrentBuilder::new("tests/files", PIECE_LENGTH)
+        .set_hashing_strategy(HashingStrategy::Parallel)
         .build_non_blocking()
         .unwrap();
 
@@ -339,6 +349,121 @@ fn build_multi_file_parallel_non_blocking_cancel() {
     }
 }
 
+#[test]
+fn build_multi_file_pipelined_non_blocking_ok() {
+    let build = TorrentBuilder::new("tests/files", PIECE_LENGTH)
+        .set_announce(Some(
+            "udp://tracker.coppersurfer.tk:6969/announce".to_owned(),
+        ))
+        .add_extra_field("creation date".to_owned(), BencodeElem::Integer(1523607302))
+        .add_extra_field(
+            "encoding".to_owned(),
+            BencodeElem::String("UTF-8".to_owned()),
+        )
+        .add_extra_info_field("private".to_owned(), BencodeElem::Integer(0))
+        .set_hashing_strategy(HashingStrategy::Pipelined)
+        .set_pipeline_depth(1)
+        .build_non_blocking()
+        .unwrap();
+
+    let mut prev_progress = 0;
+    while !build.is_finished() {
+        let curr_progress = build.get_progress();
+        assert!(prev_progress <= curr_progress);
+        prev_progress = curr_progress;
+    }
+    assert_eq!(build.get_progress(), 100);
+
+    // compare against a sample file created by Deluge
+    let encoded = build.get_output().unwrap().encode().unwrap();
+    assert_eq!(
+        Torrent::read_from_bytes(encoded).unwrap(),
+        Torrent::read_from_file("tests/samples/files.torrent").unwrap(),
+    );
+}
+
+#[test]
+fn build_multi_file_pipelined_non_blocking_cancel() {
+    let build = TorrentBuilder::new("tests/files", PIECE_LENGTH)
+        .set_hashing_strategy(HashingStrategy::Pipelined)
+        .build_non_blocking()
+        .unwrap();
+
+    build.cancel();
+
+    match build.get_output() {
+        Err(LavaTorrentError::TorrentBuilderFailure(m)) => {
+            assert_eq!(m, "build canceled by client")
+        }
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn build_hashing_strategy_does_not_affect_output() {
+    let samples = [
+        ("tests/files", 1523607302, "tests/samples/files.torrent"),
+        (
+            "tests/files/tails-amd64-3.6.1.torrent",
+            1523448537,
+            "tests/samples/tails-amd64-3.6.1.torrent.torrent",
+        ),
+    ];
+
+    for (path, creation_date, sample) in samples {
+        // compare against sample files created by Deluge
+        let expected = Torrent::read_from_file(sample).unwrap();
+
+        for strategy in [
+            HashingStrategy::Auto,
+            HashingStrategy::Sequential,
+            HashingStrategy::Parallel,
+            HashingStrategy::Pipelined,
+        ] {
+            for (num_threads, pipeline_depth) in [(1, 0), (3, 1), (0, 0)] {
+                let torrent = TorrentBuilder::new(path, PIECE_LENGTH)
+                    .set_announce(Some(
+                        "udp://tracker.coppersurfer.tk:6969/announce".to_owned(),
+                    ))
+                    .add_extra_field(
+                        "creation date".to_owned(),
+                        BencodeElem::Integer(creation_date),
+                    )
+                    .add_extra_field(
+                        "encoding".to_owned(),
+                        BencodeElem::String("UTF-8".to_owned()),
+                    )
+                    .add_extra_info_field("private".to_owned(), BencodeElem::Integer(0))
+                    .set_hashing_strategy(strategy)
+                    .set_num_threads(num_threads)
+                    .set_pipeline_depth(pipeline_depth)
+                    .set_chunk_size(1000)
+                    .build()
+                    .unwrap();
+                let encoded = torrent.encode().unwrap();
+                assert_eq!(Torrent::read_from_bytes(encoded).unwrap(), expected);
+            }
+        }
+    }
+}
+
+#[test]
+fn build_secure_open_does_not_affect_output() {
+    for path in ["tests/files", "tests/files/tails-amd64-3.6.1.torrent"] {
+        let builder = TorrentBuilder::new(path, PIECE_LENGTH)
+            .set_hashing_strategy(HashingStrategy::Parallel)
+            .set_num_threads(3);
+        let expected = builder.clone().build().unwrap();
+
+        let builder = builder.set_secure_open(true);
+        assert_eq!(builder.clone().build().unwrap(), expected);
+        assert_eq!(
+            builder.build_non_blocking().unwrap().get_output().unwrap(),
+            expected
+        );
+    }
+}
+
 #[test]
 fn build_with_name() {
     let output_name = rand_file_name() + ".torrent";
@@ -436,6 +561,7 @@ fn build_nested_dir_parallel_ok() {
 
     TorrentBuilder::new("tests/nested", PIECE_LENGTH)
         .add_extra_field("creation date".to_owned(), BencodeElem::Integer(1678689103))
+        .set_hashing_strategy(HashingStrategy::Parallel)
         .build()
         .unwrap()
         .write_into_file(&output_name)
@@ -447,3 +573,1444 @@ fn build_nested_dir_parallel_ok() {
         Torrent::read_from_file("tests/samples/nested.torrent").unwrap(),
     );
 }
+
+#[test]
+fn build_symbolic_link_name() {
+    let torrent = TorrentBuilder::new("tests/files/symlink", PIECE_LENGTH)
+        .build()
+        .unwrap();
+
+    // name is taken from the symlink, not its target
+    assert_eq!(torrent.name, "symlink");
+}
+
+#[cfg(unix)]
+#[test]
+fn build_symbolic_link_dir_name() {
+    let link = rand_file_name() + "-current";
+    std::os::unix::fs::symlink(std::fs::canonicalize("tests/nested").unwrap(), &link).unwrap();
+
+    let builder = TorrentBuilder::new(&link, PIECE_LENGTH);
+    assert_eq!(
+        builder.resolved_root().unwrap(),
+        std::fs::canonicalize("tests/nested").unwrap()
+    );
+
+    let torrent = builder.build().unwrap();
+    std::fs::remove_file(&link).unwrap();
+
+    // name is taken from the symlink, not its target
+    assert_eq!(
+        torrent.name,
+        link.trim_start_matches(OUTPUT_ROOT).to_owned()
+    );
+    let expected = TorrentBuilder::new("tests/nested", PIECE_LENGTH)
+        .build()
+        .unwrap();
+    assert_eq!(torrent.files, expected.files);
+    assert_eq!(torrent.pieces, expected.pieces);
+}
+
+#[test]
+fn build_dot_dot_name() {
+    let torrent = TorrentBuilder::new("tests/nested/x/y/..", PIECE_LENGTH)
+        .build()
+        .unwrap();
+
+    // `..` has no meaningful name, so the canonicalized path is used
+    assert_eq!(torrent.name, "x");
+}
+
+#[cfg(unix)]
+#[test]
+fn build_preserve_symlinks() {
+    let output_name = rand_file_name() + ".torrent";
+
+    TorrentBuilder::new("tests/files", PIECE_LENGTH)
+        .set_preserve_symlinks(true)
+        .build()
+        .unwrap()
+        .write_into_file(&output_name)
+        .unwrap();
+    let torrent = Torrent::read_from_file(&output_name).unwrap();
+
+    let files = torrent.files.unwrap();
+    assert_eq!(files.len(), 4);
+    let link = &files[1];
+    assert_eq!(link.path, vec!["symlink".to_owned()]);
+    assert_eq!(link.length, 0);
+    assert_eq!(link.attr(), Some("l"));
+    assert_eq!(
+        link.symlink_target(),
+        Some(std::path::PathBuf::from(
+            "ubuntu-16.04.4-desktop-amd64.iso.torrent"
+        ))
+    );
+    assert_eq!(files[0].attr(), None);
+    assert_eq!(files[0].symlink_target(), None);
+}
+
+#[cfg(unix)]
+#[test]
+fn build_preserve_symlinks_outside_root() {
+    let dir = rand_file_name() + "-dir";
+    std::fs::create_dir(&dir).unwrap();
+    std::os::unix::fs::symlink(
+        std::fs::canonicalize("tests/files/byte_sequence").unwrap(),
+        std::path::Path::new(&dir).join("link"),
+    )
+    .unwrap();
+
+    std::fs::write(std::path::Path::new(&dir).join("file"), b"hello\n").unwrap();
+    let link = std::fs::canonicalize(&dir).unwrap().join("link");
+
+    let builder = TorrentBuilder::new(&dir, PIECE_LENGTH).set_preserve_symlinks(true);
+    let failed = builder.clone().build();
+    let (skipped, report) = builder
+        .set_file_issue_policy(FileIssuePolicy::Skip)
+        .build_with_report();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    match failed {
+        Err(LavaTorrentError::TorrentBuilderFailure(m)) => assert_eq!(
+            m,
+            format!(
+                "1 file(s) have issues: [{}] symlink pointing outside of `path`.",
+                link.display()
+            )
+        ),
+        _ => panic!(),
+    }
+    let files = skipped.unwrap().files.unwrap();
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].path, vec!["file".to_owned()]);
+    assert_eq!(
+        report.file_issues,
+        vec![(link, FileIssue::SymlinkOutsideRoot)]
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn build_preserve_symlinks_dangling() {
+    let dir = rand_file_name() + "-dir";
+    std::fs::create_dir(&dir).unwrap();
+    std::fs::write(std::path::Path::new(&dir).join("file"), b"hello\n").unwrap();
+    std::os::unix::fs::symlink("nothing", std::path::Path::new(&dir).join("link")).unwrap();
+    let link = std::fs::canonicalize(&dir).unwrap().join("link");
+
+    let builder = TorrentBuilder::new(&dir, PIECE_LENGTH).set_preserve_symlinks(true);
+    let failed = builder.clone().build();
+    let skipped = builder.set_file_issue_policy(FileIssuePolicy::Skip).build();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    match failed {
+        Err(LavaTorrentError::TorrentBuilderFailure(m)) => assert_eq!(
+            m,
+            format!(
+                "1 file(s) have issues: [{}] unreadable (entity not found).",
+                link.display()
+            )
+        ),
+        _ => panic!(),
+    }
+    assert_eq!(skipped.unwrap().files.unwrap().len(), 1);
+}
+
+// A torrent with a symlink entry, laid out the way libtorrent's
+// `create_torrent` records symlinks (BEP 47 `attr` and `symlink path`).
+// It is written out by hand, as libtorrent is not a dependency.
+#[cfg(unix)]
+const SYMLINK_GOLDEN: &[u8] = b"d4:infod5:filesld6:lengthi6e4:pathl1:aeed4:attr1:l6:lengthi0e\
+    4:pathl4:linke12:symlink pathl1:aeee4:name6:golden12:piece lengthi16384e\
+    6:pieces20:\xf5\x72\xd3\x96\xfa\xe9\x20\x66\x28\x71\x4f\xb2\xce\x00\xf7\x2e\x94\xf2\x25\x8fee";
+
+#[cfg(unix)]
+#[test]
+fn build_preserve_symlinks_golden() {
+    let torrent = Torrent::read_from_bytes(SYMLINK_GOLDEN).unwrap();
+    let files = torrent.files.as_ref().unwrap();
+    assert_eq!(files[1].attr(), Some("l"));
+    assert_eq!(
+        files[1].symlink_target(),
+        Some(std::path::PathBuf::from("a"))
+    );
+    assert_eq!(torrent.encode().unwrap(), SYMLINK_GOLDEN);
+
+    // building the same content gives the same bytes
+    let dir = rand_file_name() + "-dir";
+    std::fs::create_dir(&dir).unwrap();
+    std::fs::write(std::path::Path::new(&dir).join("a"), b"hello\n").unwrap();
+    std::os::unix::fs::symlink("a", std::path::Path::new(&dir).join("link")).unwrap();
+    let built = TorrentBuilder::new(&dir, 16384)
+        .set_name("golden".to_owned())
+        .set_preserve_symlinks(true)
+        .build();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(built.unwrap().encode().unwrap(), SYMLINK_GOLDEN);
+}
+
+#[test]
+fn check_rules() {
+    let nested = TorrentBuilder::new("tests/nested", PIECE_LENGTH);
+    let files = TorrentBuilder::new("tests/files", PIECE_LENGTH);
+    let single = TorrentBuilder::new("tests/files/byte_sequence", PIECE_LENGTH);
+
+    // make sure the counts below agree with what build() produces
+    let torrent = nested.clone().build().unwrap();
+    assert_eq!(torrent.files.unwrap().len(), 8);
+    assert_eq!(torrent.pieces.len(), 14);
+
+    let cases: Vec<(&TorrentBuilder, UploadRules, Vec<&str>)> = vec![
+        (&nested, UploadRules::UNRESTRICTED, vec![]),
+        (&single, UploadRules::UNRESTRICTED, vec![]),
+        (
+            &nested,
+            UploadRules {
+                max_files: Some(8),
+                ..Default::default()
+            },
+            vec![],
+        ),
+        (
+            &nested,
+            UploadRules {
+                max_files: Some(7),
+                ..Default::default()
+            },
+            vec!["too many files: [8] > [7]"],
+        ),
+        (
+            &single,
+            UploadRules {
+                max_files: Some(0),
+                ..Default::default()
+            },
+            vec!["too many files: [1] > [0]"],
+        ),
+        (
+            &nested,
+            UploadRules {
+                max_file_size: Some(90000),
+                ..Default::default()
+            },
+            vec![
+                "file too large: tails-amd64-3.6.1.torrent ([94016] > [90000] bytes)",
+                "file too large: x/y/tails-amd64-3.6.1.torrent ([94016] > [90000] bytes)",
+            ],
+        ),
+        (
+            &single,
+            UploadRules {
+                max_file_size: Some(255),
+                ..Default::default()
+            },
+            vec!["file too large: byte_sequence ([256] > [255] bytes)"],
+        ),
+        (
+            &nested,
+            UploadRules {
+                min_piece_length: Some(2 * PIECE_LENGTH),
+                ..Default::default()
+            },
+            vec!["piece length too small: [32768] < [65536] bytes"],
+        ),
+        (
+            &nested,
+            UploadRules {
+                max_piece_length: Some(PIECE_LENGTH / 2),
+                ..Default::default()
+            },
+            vec!["piece length too large: [32768] > [16384] bytes"],
+        ),
+        (
+            &nested,
+            UploadRules {
+                max_piece_count: Some(14),
+                ..Default::default()
+            },
+            vec![],
+        ),
+        (
+            &nested,
+            UploadRules {
+                max_piece_count: Some(13),
+                ..Default::default()
+            },
+            vec!["too many pieces: [14] > [13]"],
+        ),
+        (
+            &files,
+            UploadRules {
+                forbidden_extensions: vec!["Torrent".to_owned()],
+                ..Default::default()
+            },
+            vec![
+                "forbidden extension [torrent]: tails-amd64-3.6.1.torrent",
+                "forbidden extension [torrent]: ubuntu-16.04.4-desktop-amd64.iso.torrent",
+            ],
+        ),
+        (
+            &files,
+            UploadRules::PRIVATE_TRACKER,
+            vec![
+                "torrent is not private",
+                "torrent does not have exactly 1 tracker",
+            ],
+        ),
+    ];
+
+    for (builder, rules, expected) in cases {
+        let violations = match builder.check_rules(&rules) {
+            Ok(()) => vec![],
+            Err(violations) => violations.iter().map(ToString::to_string).collect(),
+        };
+        assert_eq!(violations, expected, "rules: {:?}", rules);
+    }
+}
+
+#[test]
+fn check_rules_padding() {
+    let builder =
+        TorrentBuilder::new("tests/nested", PIECE_LENGTH).set_hybrid_compatible_layout(true);
+    let torrent = builder.clone().build().unwrap();
+    let n_files = torrent.files.as_ref().unwrap().len();
+    let n_pieces = torrent.pieces.len();
+    // padding files are counted, as they are part of the torrent
+    assert!(n_files > 8);
+    assert!(n_pieces > 14);
+
+    for (rules, expected) in [
+        (
+            UploadRules {
+                max_files: Some(n_files),
+                max_piece_count: Some(n_pieces),
+                ..Default::default()
+            },
+            vec![],
+        ),
+        (
+            UploadRules {
+                max_files: Some(n_files - 1),
+                max_piece_count: Some(n_pieces - 1),
+                ..Default::default()
+            },
+            vec![
+                format!("too many files: [{}] > [{}]", n_files, n_files - 1),
+                format!("too many pieces: [{}] > [{}]", n_pieces, n_pieces - 1),
+            ],
+        ),
+    ] {
+        let violations = match builder.check_rules(&rules) {
+            Ok(()) => vec![],
+            Err(violations) => violations.iter().map(ToString::to_string).collect(),
+        };
+        assert_eq!(violations, expected, "rules: {:?}", rules);
+    }
+}
+
+#[test]
+fn check_rules_private_tracker_ok() {
+    let builder = TorrentBuilder::new("tests/files", PIECE_LENGTH)
+        .set_announce(Some("udp://tracker.example.com:6969/announce".to_owned()))
+        .set_privacy(true);
+
+    assert!(builder.check_rules(&UploadRules::PRIVATE_TRACKER).is_ok());
+}
+
+#[test]
+fn check_rules_invalid_builder() {
+    let builder = TorrentBuilder::new("tests/does-not-exist", PIECE_LENGTH);
+
+    match builder.check_rules(&UploadRules::UNRESTRICTED) {
+        Err(violations) => {
+            assert_eq!(violations.len(), 1);
+            assert!(matches!(violations[0], RuleViolation::InvalidBuilder(_)));
+        }
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn build_piece_stats_zeros() {
+    let path = rand_file_name() + "-zeros";
+    // 10 full pieces + a partial one
+    std::fs::write(&path, vec![0u8; 10 * 1024 + 100]).unwrap();
+
+    let torrent = TorrentBuilder::new(&path, 1024).build().unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(torrent.pieces.len(), 11);
+    assert_eq!(
+        torrent.piece_stats(),
+        PieceStats {
+            unique_pieces: 2,
+            most_common_piece: Some((torrent.pieces[0], 10)),
+            zero_piece_count: 11,
+        }
+    );
+}
+
+#[test]
+fn build_piece_stats_all_unique() {
+    let torrent = TorrentBuilder::new("tests/files/byte_sequence", 16)
+        .build()
+        .unwrap();
+
+    assert_eq!(torrent.pieces.len(), 16);
+    assert_eq!(
+        torrent.piece_stats(),
+        PieceStats {
+            unique_pieces: 16,
+            most_common_piece: None,
+            zero_piece_count: 0,
+        }
+    );
+}
+
+#[test]
+fn build_private_survives_clearing_extra_info_fields() {
+    let output_name = rand_file_name() + ".torrent";
+
+    TorrentBuilder::new("tests/files/byte_sequence", PIECE_LENGTH)
+        .set_privacy(true)
+        .build()
+        .unwrap()
+        .write_into_file(&output_name)
+        .unwrap();
+    let mut torrent = Torrent::read_from_file(&output_name).unwrap();
+    let info_hash = torrent.info_hash();
+
+    assert_eq!(torrent.private, Some(true));
+    assert_eq!(torrent.extra_info_fields, None);
+
+    // e.g. a sanitizer stripping unknown fields
+    torrent.extra_info_fields = None;
+    assert!(torrent.is_private());
+    assert_eq!(torrent.info_hash(), info_hash);
+}
+
+#[test]
+fn build_large_piece_length() {
+    // the whole file fits into 1 (partial) piece
+    let torrent = TorrentBuilder::new("tests/files/byte_sequence", 8 * 1024 * 1024)
+        .set_chunk_size(100)
+        .build()
+        .unwrap();
+
+    let expected: Piece = Sha1::digest(std::fs::read("tests/files/byte_sequence").unwrap()).into();
+    assert_eq!(torrent.pieces, vec![expected]);
+}
+
+#[test]
+fn build_chunk_size_does_not_affect_output() {
+    for num_threads in [1, 3] {
+        let expected = TorrentBuilder::new("tests/nested", PIECE_LENGTH)
+            .set_num_threads(num_threads)
+            .build()
+            .unwrap();
+
+        for chunk_size in [1, 7, 4096, PIECE_LENGTH as usize * 2] {
+            let torrent = TorrentBuilder::new("tests/nested", PIECE_LENGTH)
+                .set_num_threads(num_threads)
+                .set_chunk_size(chunk_size)
+                .build()
+                .unwrap();
+            assert_eq!(torrent, expected);
+        }
+    }
+}
+
+fn assert_dry_run_matches(builder: TorrentBuilder) {
+    let dry_run = builder.dry_run().unwrap();
+    let torrent = builder.build().unwrap();
+
+    assert_eq!(
+        dry_run,
+        DryRunTorrent {
+            announce: torrent.announce.clone(),
+            announce_list: torrent.announce_list.clone(),
+            length: torrent.length,
+            files: torrent.files.clone(),
+            name: torrent.name.clone(),
+            piece_length: torrent.piece_length,
+            piece_count: torrent.pieces.len(),
+            private: torrent.private,
+            extra_fields: torrent.extra_fields.clone(),
+            extra_info_fields: torrent.extra_info_fields.clone(),
+        }
+    );
+    assert_eq!(
+        dry_run.estimated_encoded_len(),
+        torrent.encode().unwrap().len()
+    );
+}
+
+#[test]
+fn dry_run_single_file() {
+    assert_dry_run_matches(TorrentBuilder::new(
+        "tests/files/tails-amd64-3.6.1.torrent",
+        PIECE_LENGTH,
+    ));
+    // exact multiple of the piece length
+    assert_dry_run_matches(TorrentBuilder::new("tests/files/byte_sequence", 256));
+}
+
+#[test]
+fn dry_run_multi_file() {
+    assert_dry_run_matches(
+        TorrentBuilder::new("tests/nested", PIECE_LENGTH)
+            .set_announce(Some("url".to_owned()))
+            .set_announce_list(vec![vec!["url".to_owned()], vec!["url2".to_owned()]])
+            .set_name("sample".to_owned())
+            .set_privacy(true)
+            .add_extra_field("comment".to_owned(), BencodeElem::String("??".to_owned()))
+            .add_extra_info_field("source".to_owned(), BencodeElem::String("??".to_owned())),
+    );
+    assert_dry_run_matches(TorrentBuilder::new("tests/files", 1024).set_num_threads(1));
+}
+
+#[cfg(unix)]
+#[test]
+fn dry_run_preserve_symlinks() {
+    assert_dry_run_matches(
+        TorrentBuilder::new("tests/files", PIECE_LENGTH).set_preserve_symlinks(true),
+    );
+}
+
+#[test]
+fn dry_run_invalid_builder() {
+    match TorrentBuilder::new("tests/files", 1000).dry_run() {
+        Err(LavaTorrentError::TorrentBuilderFailure(m)) => assert_eq!(
+            m,
+            "TorrentBuilder has `piece_length` that is not a power of 2."
+        ),
+        _ => panic!(),
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn build_each() {
+    // root/
+    //   a/{1, 2}
+    //   b
+    //   c -> (nothing)
+    //   .d/
+    let root = std::path::PathBuf::from(rand_file_name() + "-each");
+    std::fs::create_dir_all(root.join("a")).unwrap();
+    std::fs::create_dir_all(root.join(".d")).unwrap();
+    std::fs::write(root.join("a").join("1"), vec![1; 1000]).unwrap();
+    std::fs::write(root.join("a").join("2"), vec![2; 100_000]).unwrap();
+    std::fs::write(root.join("b"), vec![3; 50_000]).unwrap();
+    std::os::unix::fs::symlink(root.join("nothing"), root.join("c")).unwrap();
+
+    let template = TorrentBuilder::new("", PIECE_LENGTH).set_announce(Some("url".to_owned()));
+    let options = BuildEachOptions {
+        num_threads: 2,
+        include_files: true,
+        ..Default::default()
+    };
+    let results = v1::build_each(&root, &template, &options).unwrap();
+
+    assert_eq!(results.len(), 3);
+    for (i, name) in ["a", "b"].iter().enumerate() {
+        assert_eq!(results[i].0, root.join(name));
+        assert_eq!(
+            results[i].1.as_ref().unwrap(),
+            &template.clone().set_path(root.join(name)).build().unwrap()
+        );
+    }
+    assert_eq!(results[2].0, root.join("c"));
+    match results[2].1 {
+        Err(LavaTorrentError::TorrentBuilderFailure(ref m)) => assert_eq!(
+            m,
+            "TorrentBuilder has `path` but it does not point to anything."
+        ),
+        _ => panic!(),
+    }
+
+    // directories only
+    let results = v1::build_each(&root, &template, &BuildEachOptions::default()).unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].0, root.join("a"));
+
+    // skip everything that already has a torrent
+    std::fs::write(root.join("a.torrent"), b"").unwrap();
+    let options = BuildEachOptions {
+        skip: Some(Box::new(|path| {
+            path.extension().is_some_and(|ext| ext == "torrent")
+                || path.with_extension("torrent").exists()
+        })),
+        include_files: true,
+        ..Default::default()
+    };
+    let results = v1::build_each(&root, &template, &options).unwrap();
+    assert_eq!(
+        results.iter().map(|(path, _)| path).collect::<Vec<_>>(),
+        vec![&root.join("b"), &root.join("c")]
+    );
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn content_fingerprint_stable() {
+    for path in ["tests/files", "tests/nested", "tests/files/byte_sequence"] {
+        let path = std::path::Path::new(path);
+        let fingerprint = Torrent::content_fingerprint(path).unwrap();
+        assert!(fingerprint.matches(&Torrent::content_fingerprint(path).unwrap()));
+        assert_eq!(
+            ContentFingerprint::from(*fingerprint.as_bytes()),
+            fingerprint
+        );
+    }
+
+    assert!(!Torrent::content_fingerprint("tests/files".as_ref())
+        .unwrap()
+        .matches(&Torrent::content_fingerprint("tests/nested".as_ref()).unwrap()));
+}
+
+#[test]
+fn content_fingerprint_changes() {
+    let root = std::path::PathBuf::from(rand_file_name() + "-fingerprint");
+    std::fs::create_dir_all(root.join("a")).unwrap();
+    std::fs::write(root.join("a").join("1"), vec![1; 1000]).unwrap();
+    std::fs::write(root.join("b"), vec![2; 1000]).unwrap();
+    let fingerprint = || Torrent::content_fingerprint(&root).unwrap();
+    let original = fingerprint();
+
+    // touched
+    let file = std::fs::File::options()
+        .write(true)
+        .open(root.join("b"))
+        .unwrap();
+    let mtime = file.metadata().unwrap().modified().unwrap();
+    file.set_modified(mtime + std::time::Duration::from_secs(1))
+        .unwrap();
+    let touched = fingerprint();
+    assert_ne!(touched, original);
+    file.set_modified(mtime).unwrap();
+    assert_eq!(fingerprint(), original);
+
+    // added
+    std::fs::write(root.join("a").join("2"), b"").unwrap();
+    assert_ne!(fingerprint(), original);
+    std::fs::remove_file(root.join("a").join("2")).unwrap();
+    assert_eq!(fingerprint(), original);
+
+    // hidden files are ignored, as when building
+    std::fs::write(root.join(".hidden"), b"").unwrap();
+    assert_eq!(fingerprint(), original);
+
+    // removed
+    std::fs::remove_file(root.join("a").join("1")).unwrap();
+    assert_ne!(fingerprint(), original);
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn build_if_changed() {
+    let root = std::path::PathBuf::from(rand_file_name() + "-if-changed");
+    std::fs::create_dir_all(&root).unwrap();
+    std::fs::write(root.join("a"), vec![1; 1000]).unwrap();
+    let builder = TorrentBuilder::new(&root, PIECE_LENGTH);
+
+    let (torrent, fingerprint) = builder.build_if_changed(None).unwrap().unwrap();
+    assert_eq!(torrent, builder.clone().build().unwrap());
+    assert_eq!(fingerprint, Torrent::content_fingerprint(&root).unwrap());
+
+    // nothing changed
+    assert_eq!(builder.build_if_changed(Some(&fingerprint)).unwrap(), None);
+    // other settings are not part of the fingerprint
+    let renamed = builder.clone().set_name("renamed".to_owned());
+    assert_eq!(renamed.build_if_changed(Some(&fingerprint)).unwrap(), None);
+
+    std::fs::write(root.join("b"), vec![2; 1000]).unwrap();
+    let (rebuilt, new_fingerprint) = builder
+        .build_if_changed(Some(&fingerprint))
+        .unwrap()
+        .unwrap();
+    assert_eq!(rebuilt.files.unwrap().len(), 2);
+    assert_ne!(new_fingerprint, fingerprint);
+    assert_eq!(
+        builder.build_if_changed(Some(&new_fingerprint)).unwrap(),
+        None
+    );
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn build_if_changed_file_issues() {
+    let root = std::path::PathBuf::from(rand_file_name() + "-if-changed-issues");
+    std::fs::create_dir_all(&root).unwrap();
+    std::fs::write(root.join("a"), vec![1; 1000]).unwrap();
+    std::os::unix::fs::symlink("nothing", root.join("link")).unwrap();
+    let builder = TorrentBuilder::new(&root, PIECE_LENGTH).set_preserve_symlinks(true);
+
+    // the content is listed the way `build()` lists it
+    match builder.build_if_changed(None) {
+        Err(LavaTorrentError::TorrentBuilderFailure(m)) => {
+            assert!(m.starts_with("1 file(s) have issues: "), "{}", m)
+        }
+        other => panic!("{:?}", other),
+    }
+
+    let builder = builder.set_file_issue_policy(FileIssuePolicy::Skip);
+    let (torrent, fingerprint) = builder.build_if_changed(None).unwrap().unwrap();
+    assert_eq!(torrent.files.unwrap().len(), 1);
+
+    // skipped entries are not part of the fingerprint either
+    std::fs::remove_file(root.join("link")).unwrap();
+    assert_eq!(builder.build_if_changed(Some(&fingerprint)).unwrap(), None);
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn build_dedupe_trackers() {
+    let builder = TorrentBuilder::new("tests/files/byte_sequence", PIECE_LENGTH)
+        .set_announce(Some("udp://tracker:1337".to_owned()))
+        .set_announce_list(vec![
+            vec![
+                "udp://tracker:1337".to_owned(),
+                " udp://tracker:1337".to_owned(),
+            ],
+            vec![],
+        ]);
+
+    // validation fails on the empty tier unless it is removed first
+    assert!(builder.clone().build().is_err());
+
+    // like Deluge, a single tracker given both as `announce`
+    // and in `announce-list` ends up in `announce` only
+    let expected = TorrentBuilder::new("tests/files/byte_sequence", PIECE_LENGTH)
+        .set_announce(Some("udp://tracker:1337".to_owned()))
+        .build()
+        .unwrap();
+    let builder = builder.set_dedupe_trackers(true);
+    assert_eq!(builder.dry_run().unwrap().announce_list, None);
+    assert_eq!(builder.clone().build().unwrap(), expected);
+    assert_eq!(
+        builder
+            .build_non_blocking()
+            .unwrap()
+            .get_output()
+            .unwrap()
+            .encode()
+            .unwrap(),
+        expected.encode().unwrap()
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn build_with_report() {
+    // root/
+    //   a
+    //   b/{empty, socket}
+    //   c -> (nothing)
+    //   empty
+    let root = std::path::PathBuf::from(rand_file_name() + "-report");
+    std::fs::create_dir_all(root.join("b")).unwrap();
+    std::fs::write(root.join("a"), vec![1; 1000]).unwrap();
+    std::fs::write(root.join("b").join("empty"), b"").unwrap();
+    std::fs::write(root.join("empty"), b"").unwrap();
+    let _socket = std::os::unix::net::UnixListener::bind(root.join("b").join("socket")).unwrap();
+    std::os::unix::fs::symlink(root.join("nothing"), root.join("c")).unwrap();
+    let canonicalized_root = std::fs::canonicalize(&root).unwrap();
+
+    // all issues are reported at once
+    let expected = BuildReport {
+        file_issues: vec![
+            (canonicalized_root.join("b/empty"), FileIssue::Empty),
+            (
+                canonicalized_root.join("b/socket"),
+                FileIssue::SpecialFileSkipped,
+            ),
+            (
+                canonicalized_root.join("c"),
+                FileIssue::Unreadable(std::io::ErrorKind::NotFound),
+            ),
+            (canonicalized_root.join("empty"), FileIssue::Empty),
+        ],
+        ..BuildReport::default()
+    };
+    let builder = TorrentBuilder::new(&root, PIECE_LENGTH);
+    let (torrent, report) = builder.clone().build_with_report();
+    assert_eq!(report, expected);
+    match torrent {
+        Err(LavaTorrentError::TorrentBuilderFailure(m)) => assert_eq!(
+            m,
+            format!(
+                "1 file(s) have issues: [{}] unreadable (entity not found).",
+                canonicalized_root.join("c").display()
+            )
+        ),
+        _ => panic!(),
+    }
+    assert!(builder.clone().build().is_err());
+    assert!(builder.dry_run().is_err());
+
+    // files with serious issues are left out, others are kept
+    let builder = builder.set_file_issue_policy(FileIssuePolicy::Skip);
+    let (torrent, report) = builder.clone().build_with_report();
+    assert_eq!(report, expected);
+    let paths = |torrent: &Torrent| {
+        torrent
+            .files
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|file| file.path.join("/"))
+            .collect::<Vec<_>>()
+    };
+    let torrent = torrent.unwrap();
+    assert_eq!(paths(&torrent), vec!["a", "b/empty", "empty"]);
+    assert_eq!(torrent.length, 1000);
+    assert_eq!(builder.clone().set_num_threads(2).build().unwrap(), torrent);
+    assert_eq!(
+        builder.build_non_blocking().unwrap().get_output().unwrap(),
+        torrent
+    );
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn rebuild_with_piece_length_ok() {
+    let source = Torrent::read_from_file("tests/samples/files.torrent").unwrap();
+    let rebuilt =
+        TorrentBuilder::rebuild_with_piece_length(&source, "tests/files", PIECE_LENGTH * 2)
+            .unwrap();
+
+    // same pieces as a torrent built from scratch with the new piece length
+    let expected = TorrentBuilder::new("tests/files", PIECE_LENGTH * 2)
+        .set_num_threads(1)
+        .build()
+        .unwrap();
+    assert_eq!(rebuilt.piece_length, expected.piece_length);
+    assert_eq!(rebuilt.pieces, expected.pieces);
+
+    // everything else is unchanged
+    assert_eq!(
+        Torrent {
+            piece_length: source.piece_length,
+            pieces: source.pieces.clone(),
+            ..rebuilt
+        },
+        source
+    );
+}
+
+#[test]
+fn rebuild_with_piece_length_bit_rot() {
+    let source = Torrent::read_from_file("tests/samples/files.torrent").unwrap();
+    let root = std::path::PathBuf::from(rand_file_name() + "-rot");
+    std::fs::create_dir_all(&root).unwrap();
+    for file in source.files.as_ref().unwrap() {
+        std::fs::copy(
+            std::path::Path::new("tests/files").join(file.to_path_buf()),
+            root.join(file.to_path_buf()),
+        )
+        .unwrap();
+    }
+    TorrentBuilder::rebuild_with_piece_length(&source, &root, PIECE_LENGTH * 2).unwrap();
+
+    // flip a byte in the middle of the last file
+    let last = source.files.as_ref().unwrap().last().unwrap();
+    let path = root.join(last.to_path_buf());
+    let mut bytes = std::fs::read(&path).unwrap();
+    let offset = bytes.len() / 2;
+    bytes[offset] ^= 0xff;
+    std::fs::write(&path, bytes).unwrap();
+
+    let index = (source.length - last.length + offset as Integer) / PIECE_LENGTH;
+    match TorrentBuilder::rebuild_with_piece_length(&source, &root, PIECE_LENGTH * 2) {
+        Err(LavaTorrentError::TorrentBuilderFailure(m)) => assert_eq!(
+            m,
+            format!("Piece {} does not match the source torrent.", index)
+        ),
+        _ => panic!(),
+    }
+
+    // so does a file that is not of the expected length
+    std::fs::write(&path, b"").unwrap();
+    match TorrentBuilder::rebuild_with_piece_length(&source, &root, PIECE_LENGTH * 2) {
+        Err(LavaTorrentError::TorrentBuilderFailure(m)) => assert_eq!(
+            m,
+            format!(
+                "[{}] is 0 bytes long, {} expected.",
+                path.display(),
+                last.length
+            )
+        ),
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn builder_config_round_trip() {
+    let builder = TorrentBuilder::new("tests/files", PIECE_LENGTH)
+        .set_announce(Some(
+            "udp://tracker.coppersurfer.tk:6969/announce".to_owned(),
+        ))
+        .add_extra_field("creation date".to_owned(), BencodeElem::Integer(1523607302))
+        .add_extra_field(
+            "encoding".to_owned(),
+            BencodeElem::String("UTF-8".to_owned()),
+        )
+        .add_extra_info_field("private".to_owned(), BencodeElem::Integer(0))
+        .set_hashing_strategy(HashingStrategy::Pipelined)
+        .set_num_threads(2);
+
+    // as if the job went through a queue
+    let job = builder.to_bencode().encode();
+    let config = BencodeElem::from_bytes(job).unwrap().remove(0);
+    let restored = TorrentBuilder::from_bencode(config, None).unwrap();
+    assert_eq!(restored, builder);
+
+    let expected = builder.build().unwrap().encode().unwrap();
+    assert_eq!(restored.build().unwrap().encode().unwrap(), expected);
+    // compare against a sample file created by Deluge
+    assert_eq!(
+        Torrent::read_from_bytes(expected).unwrap(),
+        Torrent::read_from_file("tests/samples/files.torrent").unwrap(),
+    );
+}
+
+#[test]
+fn builder_config_missing_newer_keys() {
+    // a config serialized before hashing strategies were configurable
+    let job = b"d10:chunk sizei0e14:config versioni1e15:dedupe trackersi0e\
+        17:file issue policy4:fail11:num threadsi0e4:path11:tests/files\
+        12:piece lengthi32768e17:preserve symlinksi0e7:privatei1ee";
+    let config = BencodeElem::from_bytes(&job[..]).unwrap().remove(0);
+    let restored = TorrentBuilder::from_bencode(config, None).unwrap();
+
+    let builder = TorrentBuilder::new("tests/files", PIECE_LENGTH).set_privacy(true);
+    assert_eq!(restored, builder);
+    assert_eq!(
+        restored.build().unwrap().encode().unwrap(),
+        builder.build().unwrap().encode().unwrap()
+    );
+}
+
+#[test]
+fn build_eq_ignoring_volatile() {
+    // same content as the sample, but without "creation date" and "encoding"
+    let built = TorrentBuilder::new("tests/files", PIECE_LENGTH)
+        .set_announce(Some(
+            "udp://tracker.coppersurfer.tk:6969/announce".to_owned(),
+        ))
+        .add_extra_info_field("private".to_owned(), BencodeElem::Integer(0))
+        .build()
+        .unwrap();
+    let built = Torrent::read_from_bytes(built.encode().unwrap()).unwrap();
+    let sample = Torrent::read_from_file("tests/samples/files.torrent").unwrap();
+
+    assert_ne!(built, sample);
+    assert!(built.eq_ignoring_volatile(&sample));
+    assert!(sample.eq_ignoring_volatile(&built));
+    assert!(!built.eq_ignoring(&sample, &["encoding"]));
+    assert!(built.volatile_fields().is_empty());
+    let mut keys: Vec<_> = sample.volatile_fields().into_keys().collect();
+    keys.sort();
+    assert_eq!(keys, vec!["creation date", "encoding"]);
+}
+
+#[test]
+fn build_huge_piece_length() {
+    // the largest power of 2 that fits into `Integer`
+    let piece_length = 1 << 62;
+    let byte_sequence: Piece =
+        Sha1::digest(std::fs::read("tests/files/byte_sequence").unwrap()).into();
+    let expected = TorrentBuilder::new("tests/files", PIECE_LENGTH)
+        .set_num_threads(1)
+        .build()
+        .unwrap();
+
+    for strategy in [
+        HashingStrategy::Sequential,
+        HashingStrategy::Parallel,
+        HashingStrategy::Pipelined,
+    ] {
+        // everything fits into 1 (partial) piece, without allocating for a whole one
+        let torrent = TorrentBuilder::new("tests/files/byte_sequence", piece_length)
+            .set_hashing_strategy(strategy)
+            .build()
+            .unwrap();
+        assert_eq!(torrent.pieces, vec![byte_sequence]);
+
+        let torrent = TorrentBuilder::new("tests/files", piece_length)
+            .set_hashing_strategy(strategy)
+            .build_non_blocking()
+            .unwrap()
+            .get_output()
+            .unwrap();
+        assert_eq!(torrent.pieces.len(), 1);
+        assert_eq!(torrent.files, expected.files);
+        assert_eq!(torrent.length, expected.length);
+    }
+}
+
+#[test]
+fn build_keep_piece_map_locates_bad_piece() {
+    let root = std::path::PathBuf::from(rand_file_name() + "-piece-map");
+    std::fs::create_dir_all(&root).unwrap();
+    for entry in std::fs::read_dir("tests/files").unwrap() {
+        let entry = entry.unwrap();
+        std::fs::copy(entry.path(), root.join(entry.file_name())).unwrap();
+    }
+
+    let (torrent, report) = TorrentBuilder::new(&root, PIECE_LENGTH)
+        .set_keep_piece_map(true)
+        .build_with_report();
+    let torrent = torrent.unwrap();
+    let piece_map = report.piece_map.unwrap();
+    assert_eq!(piece_map.len(), torrent.pieces.len());
+    assert!(torrent.validate_files(&root).unwrap().is_empty());
+
+    // not kept by default
+    let (_, report) = TorrentBuilder::new(&root, PIECE_LENGTH).build_with_report();
+    assert_eq!(report.piece_map, None);
+
+    // corrupt a byte in the middle of a file
+    let corrupted = root
+        .canonicalize()
+        .unwrap()
+        .join("tails-amd64-3.6.1.torrent");
+    let offset = 40000;
+    let mut bytes = std::fs::read(&corrupted).unwrap();
+    bytes[offset] ^= 0xff;
+    std::fs::write(&corrupted, bytes).unwrap();
+
+    let bad_pieces = torrent.validate_files(&root).unwrap();
+    assert_eq!(bad_pieces.len(), 1);
+    assert!(piece_map[bad_pieces[0]]
+        .iter()
+        .any(|(path, start, len)| *path == corrupted
+            && *start <= offset as u64
+            && (offset as u64) < start + len));
+
+    // missing files make the pieces covering them bad
+    std::fs::remove_file(root.join("byte_sequence")).unwrap();
+    assert_eq!(torrent.validate_files(&root).unwrap()[0], 0);
+}
+
+#[test]
+fn build_hybrid_compatible_layout_ok() {
+    // No hybrid torrent is available as a fixture, so what libtorrent
+    // produces for v1 is reconstructed here instead: each file except the
+    // last one is followed by `.pad/<length>` (with `attr` = `p`) up to the
+    // next piece boundary, and padding is hashed as zeros.
+    let mut paths = std::fs::read_dir("tests/files")
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| !path.file_name().unwrap().to_str().unwrap().starts_with('.'))
+        .collect::<Vec<_>>();
+    paths.sort();
+    let piece_length = PIECE_LENGTH as usize;
+    let mut content = Vec::new();
+    let mut files = Vec::new();
+    for (index, path) in paths.iter().enumerate() {
+        let bytes = std::fs::read(path).unwrap();
+        content.extend_from_slice(&bytes);
+        files.push((
+            vec![path.file_name().unwrap().to_str().unwrap().to_owned()],
+            bytes.len() as Integer,
+            false,
+        ));
+
+        let padding = (piece_length - bytes.len() % piece_length) % piece_length;
+        if (padding > 0) && (index + 1 < paths.len()) {
+            content.resize(content.len() + padding, 0);
+            files.push((
+                vec![".pad".to_owned(), padding.to_string()],
+                padding as Integer,
+                true,
+            ));
+        }
+    }
+    let pieces = content
+        .chunks(piece_length)
+        .map(|piece| Piece::from(Sha1::digest(piece)))
+        .collect::<Vec<Piece>>();
+
+    let builder =
+        TorrentBuilder::new("tests/files", PIECE_LENGTH).set_hybrid_compatible_layout(true);
+    for strategy in [
+        HashingStrategy::Sequential,
+        HashingStrategy::Parallel,
+        HashingStrategy::Pipelined,
+    ] {
+        let builder = builder.clone().set_hashing_strategy(strategy);
+        let torrents = [
+            builder.clone().build().unwrap(),
+            builder
+                .clone()
+                .build_non_blocking()
+                .unwrap()
+                .get_output()
+                .unwrap(),
+        ];
+        for torrent in torrents {
+            assert!(torrent.is_hybrid_compatible_layout());
+            assert_eq!(torrent.length, content.len() as Integer);
+            assert_eq!(torrent.pieces, pieces);
+            assert_eq!(
+                torrent
+                    .files
+                    .as_ref()
+                    .unwrap()
+                    .iter()
+                    .map(|file| (file.path.clone(), file.length, file.is_padding()))
+                    .collect::<Vec<_>>(),
+                files
+            );
+            assert!(torrent.validate_files("tests/files").unwrap().is_empty());
+        }
+    }
+
+    let dry_run = builder.dry_run().unwrap();
+    let torrent = builder.build().unwrap();
+    assert_eq!(dry_run.files, torrent.files);
+    assert_eq!(dry_run.piece_count, torrent.pieces.len());
+
+    // files in tests/files are not aligned otherwise
+    let torrent = TorrentBuilder::new("tests/files", PIECE_LENGTH)
+        .build()
+        .unwrap();
+    assert!(!torrent.is_hybrid_compatible_layout());
+
+    // v2 does not allow pieces smaller than 16 KiB
+    match TorrentBuilder::new("tests/files", 8192)
+        .set_hybrid_compatible_layout(true)
+        .build()
+    {
+        Err(LavaTorrentError::TorrentBuilderFailure(m)) => assert_eq!(
+            m,
+            "TorrentBuilder has `piece_length` < 16 KiB, which a hybrid compatible layout does not allow."
+        ),
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn build_force_multi_file() {
+    let path = "tests/files/tails-amd64-3.6.1.torrent";
+    let single = TorrentBuilder::new(path, PIECE_LENGTH).build().unwrap();
+
+    for builder in [
+        TorrentBuilder::new(path, PIECE_LENGTH).set_force_multi_file(true),
+        TorrentBuilder::new(path, PIECE_LENGTH)
+            .set_force_multi_file(true)
+            .set_hashing_strategy(HashingStrategy::Parallel)
+            .set_num_threads(2),
+    ] {
+        let dry_run = builder.dry_run().unwrap();
+        let multi = builder.build().unwrap();
+        let multi = Torrent::read_from_bytes(multi.encode().unwrap()).unwrap();
+
+        assert_eq!(multi.name, "tails-amd64-3.6.1");
+        assert_eq!(
+            multi.files,
+            Some(vec![v1::File {
+                length: single.length,
+                path: vec!["tails-amd64-3.6.1.torrent".to_owned()],
+                extra_fields: None,
+            }])
+        );
+        assert_eq!(multi.length, single.length);
+        assert_eq!(multi.pieces, single.pieces);
+        // `info` has `files` instead of `length`
+        assert_ne!(multi.info_hash(), single.info_hash());
+        assert!(multi.validate_files("tests/files").unwrap().is_empty());
+        assert_eq!(dry_run.name, multi.name);
+        assert_eq!(dry_run.files, multi.files);
+    }
+
+    let torrent = TorrentBuilder::new(path, PIECE_LENGTH)
+        .set_force_multi_file(true)
+        .set_name("dir".to_owned())
+        .build_non_blocking()
+        .unwrap()
+        .get_output()
+        .unwrap();
+    assert_eq!(torrent.name, "dir");
+    assert_eq!(torrent.files.unwrap().len(), 1);
+    assert_eq!(torrent.pieces, single.pieces);
+
+    // no effect on directories
+    assert_eq!(
+        TorrentBuilder::new("tests/files", PIECE_LENGTH)
+            .set_force_multi_file(true)
+            .build()
+            .unwrap(),
+        TorrentBuilder::new("tests/files", PIECE_LENGTH)
+            .build()
+            .unwrap()
+    );
+}
+
+#[test]
+fn build_force_single_file() {
+    let dir = rand_file_name() + "-single";
+    std::fs::create_dir(&dir).unwrap();
+    std::fs::copy(
+        "tests/files/tails-amd64-3.6.1.torrent",
+        format!("{}/content.bin", dir),
+    )
+    .unwrap();
+    // hidden files are skipped
+    std::fs::write(format!("{}/.hidden", dir), b"hidden").unwrap();
+
+    let expected = TorrentBuilder::new(format!("{}/content.bin", dir), PIECE_LENGTH)
+        .build()
+        .unwrap();
+    let builder = TorrentBuilder::new(&dir, PIECE_LENGTH).set_force_single_file(true);
+    let dry_run = builder.dry_run().unwrap();
+    let torrent = builder.clone().build().unwrap();
+    assert_eq!(torrent, expected);
+    assert_eq!(torrent.name, "content.bin");
+    assert_eq!(dry_run.files, None);
+    assert_eq!(dry_run.name, "content.bin");
+    assert_eq!(
+        builder.build_non_blocking().unwrap().get_output().unwrap(),
+        expected
+    );
+
+    let torrent = TorrentBuilder::new(&dir, PIECE_LENGTH)
+        .set_force_single_file(true)
+        .set_name("renamed.bin".to_owned())
+        .build()
+        .unwrap();
+    assert_eq!(torrent.name, "renamed.bin");
+    assert_eq!(torrent.files, None);
+
+    // no effect on files
+    assert_eq!(
+        TorrentBuilder::new(format!("{}/content.bin", dir), PIECE_LENGTH)
+            .set_force_single_file(true)
+            .build()
+            .unwrap(),
+        expected
+    );
+
+    std::fs::write(format!("{}/other.bin", dir), b"other").unwrap();
+    for result in [
+        TorrentBuilder::new(&dir, PIECE_LENGTH)
+            .set_force_single_file(true)
+            .build(),
+        TorrentBuilder::new(&dir, PIECE_LENGTH)
+            .set_force_single_file(true)
+            .build_non_blocking()
+            .map(|build| build.get_output().unwrap()),
+    ] {
+        match result {
+            Err(LavaTorrentError::TorrentBuilderFailure(m)) => assert_eq!(
+                m,
+                format!(
+                    "TorrentBuilder has `force_single_file` set but [{}] contains 2 files.",
+                    std::fs::canonicalize(&dir).unwrap().display()
+                )
+            ),
+            _ => panic!(),
+        }
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn build_resumable_cancel_and_resume() {
+    let checkpoint = std::path::PathBuf::from(rand_file_name() + ".checkpoint");
+    // small pieces and chunks, so that there is time to cancel halfway
+    let builder = TorrentBuilder::new("tests/files", 1024)
+        .set_chunk_size(16)
+        .set_checkpoint_interval(1);
+    let expected = builder.clone().build().unwrap();
+
+    let build = builder.clone().build_resumable(&checkpoint).unwrap();
+    while build.get_n_piece_total() == 0
+        || build.get_n_piece_processed() * 2 < build.get_n_piece_total()
+    {
+        if build.is_finished() {
+            break;
+        }
+    }
+    build.cancel();
+    let n_piece_total = build.get_n_piece_total();
+    if let Err(e) = build.get_output() {
+        match e {
+            LavaTorrentError::TorrentBuilderFailure(m) => {
+                assert_eq!(m, "build canceled by client")
+            }
+            _ => panic!("{:?}", e),
+        }
+    }
+
+    // pieces are saved before they are counted as processed
+    let saved = match BencodeElem::from_file(&checkpoint).unwrap().remove(0) {
+        BencodeElem::Dictionary(mut dict) => match dict.remove("pieces") {
+            Some(BencodeElem::Bytes(pieces)) => pieces.len() as u64 / 20,
+            other => panic!("{:?}", other),
+        },
+        other => panic!("{:?}", other),
+    };
+    assert!(saved * 2 >= n_piece_total, "{} of {}", saved, n_piece_total);
+
+    let build = builder.clone().build_resumable(&checkpoint).unwrap();
+    assert_eq!(build.get_output().unwrap(), expected);
+
+    // the finished checkpoint is reused as is
+    let build = builder.build_resumable(&checkpoint).unwrap();
+    assert_eq!(build.get_output().unwrap(), expected);
+
+    std::fs::remove_file(&checkpoint).unwrap();
+}
+
+#[test]
+fn build_resumable_from_partial_checkpoint() {
+    let checkpoint = std::path::PathBuf::from(rand_file_name() + ".checkpoint");
+
+    for (path, piece_length, preserve_symlinks, hybrid_compatible_layout) in [
+        ("tests/files/tails-amd64-3.6.1.torrent", 1024, false, false),
+        ("tests/files", 1024, false, false),
+        ("tests/files", 1024, true, false),
+        ("tests/files", 16 * 1024, false, true),
+    ] {
+        let builder = TorrentBuilder::new(path, piece_length)
+            .set_preserve_symlinks(preserve_symlinks)
+            .set_hybrid_compatible_layout(hybrid_compatible_layout);
+        let expected = builder.clone().build().unwrap();
+
+        builder
+            .clone()
+            .build_resumable(&checkpoint)
+            .unwrap()
+            .get_output()
+            .unwrap();
+
+        // drop the last pieces, as if the build was interrupted
+        for n_kept in [0, 1, expected.pieces.len() / 2, expected.pieces.len() - 1] {
+            let mut saved = BencodeElem::from_file(&checkpoint).unwrap().remove(0);
+            saved
+                .insert(
+                    "pieces".to_owned(),
+                    BencodeElem::Bytes(expected.pieces[..n_kept].concat()),
+                )
+                .unwrap();
+            saved.write_into_file(&checkpoint).unwrap();
+
+            let build = builder.clone().build_resumable(&checkpoint).unwrap();
+            assert_eq!(build.get_output().unwrap(), expected, "{}", path);
+        }
+        std::fs::remove_file(&checkpoint).unwrap();
+    }
+}
+
+#[test]
+fn build_resumable_checkpoint_mismatch() {
+    let root = std::path::PathBuf::from(rand_file_name() + "-resumable");
+    let checkpoint = std::path::PathBuf::from(rand_file_name() + ".checkpoint");
+    std::fs::create_dir_all(&root).unwrap();
+    std::fs::write(root.join("a"), vec![1; 1000]).unwrap();
+    std::fs::write(root.join("b"), vec![2; 1000]).unwrap();
+    let builder = TorrentBuilder::new(&root, 1024);
+
+    builder
+        .clone()
+        .build_resumable(&checkpoint)
+        .unwrap()
+        .get_output()
+        .unwrap();
+
+    let expect_mismatch = |builder: TorrentBuilder, reason: &str| match builder
+        .build_resumable(&checkpoint)
+        .unwrap()
+        .get_output()
+    {
+        Err(LavaTorrentError::TorrentBuilderFailure(m)) => assert_eq!(
+            m,
+            format!(
+                "Checkpoint [{}] does not match the content: {}. Delete it to start over.",
+                checkpoint.display(),
+                reason
+            )
+        ),
+        other => panic!("{:?}", other),
+    };
+
+    expect_mismatch(
+        builder.clone().set_piece_length(2048),
+        "it was made with a piece length of 1024, not 2048",
+    );
+    std::fs::write(root.join("c"), vec![3; 1000]).unwrap();
+    expect_mismatch(builder.clone(), "it lists 2 file(s), but 3 were found");
+    std::fs::remove_file(root.join("c")).unwrap();
+    std::fs::write(root.join("b"), vec![2; 1001]).unwrap();
+    expect_mismatch(
+        builder.clone(),
+        "[b] was 1000 bytes long, but is 1001 bytes long now",
+    );
+
+    // starting over works
+    std::fs::remove_file(&checkpoint).unwrap();
+    let build = builder.clone().build_resumable(&checkpoint).unwrap();
+    assert_eq!(build.get_output().unwrap(), builder.build().unwrap());
+
+    std::fs::write(&checkpoint, b"not a checkpoint").unwrap();
+    match TorrentBuilder::new(&root, 1024)
+        .build_resumable(&checkpoint)
+        .unwrap()
+        .get_output()
+    {
+        Err(LavaTorrentError::TorrentBuilderFailure(m)) => assert!(
+            m.starts_with(&format!(
+                "Checkpoint [{}] is malformed: ",
+                checkpoint.display()
+            )),
+            "{}",
+            m
+        ),
+        other => panic!("{:?}", other),
+    }
+
+    std::fs::remove_file(&checkpoint).unwrap();
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn from_pieces_matches_build() {
+    for path in ["tests/files", "tests/files/byte_sequence"] {
+        let built = TorrentBuilder::new(path, PIECE_LENGTH)
+            .set_announce(Some("udp://tracker.example:6969".to_owned()))
+            .add_extra_info_field("source".to_owned(), BencodeElem::Integer(1))
+            .set_privacy(true)
+            .build()
+            .unwrap();
+
+        let assembled = Torrent::from_pieces(
+            built.name.clone(),
+            built.piece_length,
+            built.files.clone().unwrap_or_default(),
+            built.pieces.clone(),
+            AssembleOptions {
+                length: built.files.is_none().then_some(built.length),
+                announce: built.announce.clone(),
+                announce_list: built.announce_list.clone(),
+                extra_fields: built.extra_fields.clone(),
+                extra_info_fields: built.extra_info_fields.clone(),
+                private: built.is_private(),
+            },
+        )
+        .unwrap();
+
+        assert_eq!(assembled.info_hash(), built.info_hash());
+        assert_eq!(assembled, built);
+    }
+}
@@ -0,0 +1,104 @@
+#![cfg(feature = "std")]
+
+extern crate lava_torrent;
+extern crate rand;
+
+use lava_torrent::bencode::BencodeElem;
+use lava_torrent::torrent::v1::{File, Torrent};
+use rand::Rng;
+use std::collections::HashMap;
+use std::iter::FromIterator;
+
+// a random multi-file torrent, some of whose files are padding files
+fn rand_torrent<R: Rng>(rng: &mut R) -> Torrent {
+    let piece_length = 1 << rng.gen_range(0..6);
+    let files = (0..rng.gen_range(1..8))
+        .map(|i| File {
+            length: rng.gen_range(0..100),
+            path: vec![i.to_string()],
+            extra_fields: if rng.gen_bool(0.3) {
+                Some(HashMap::from_iter(vec![(
+                    "attr".to_owned(),
+                    BencodeElem::String("p".to_owned()),
+                )]))
+            } else {
+                None
+            },
+        })
+        .collect::<Vec<File>>();
+    let length = files.iter().map(|file| file.length).sum::<i64>().max(1);
+    let n_pieces = (length as usize).div_ceil(piece_length as usize);
+
+    Torrent {
+        announce: None,
+        announce_list: None,
+        length,
+        files: Some(files),
+        name: "sample".to_owned(),
+        piece_length,
+        pieces: vec![[0; 20]; n_pieces],
+        private: None,
+        extra_fields: None,
+        extra_info_fields: None,
+    }
+}
+
+// a random bitfield for `n_pieces` pieces, with spare bits cleared
+fn rand_bitfield<R: Rng>(rng: &mut R, n_pieces: usize) -> Vec<u8> {
+    let mut have = (0..n_pieces.div_ceil(8))
+        .map(|_| rng.gen())
+        .collect::<Vec<u8>>();
+    if !n_pieces.is_multiple_of(8) {
+        *have.last_mut().unwrap() &= !(0xff >> (n_pieces % 8));
+    }
+    have
+}
+
+#[test]
+fn bytes_left_plus_completed_is_length() {
+    let mut rng = rand::thread_rng();
+
+    for _ in 0..1000 {
+        let torrent = rand_torrent(&mut rng);
+        let have = rand_bitfield(&mut rng, torrent.pieces.len());
+        let length = torrent.length as u64;
+        let padding = torrent
+            .files
+            .as_ref()
+            .unwrap()
+            .iter()
+            .filter(|file| file.is_padding())
+            .map(|file| file.length as u64)
+            .sum::<u64>();
+
+        let left = torrent.bytes_left(&have).unwrap();
+        let completed = torrent.bytes_completed(&have).unwrap();
+        assert_eq!(left + completed, length);
+
+        let left_no_padding = torrent.bytes_left_with(&have, true).unwrap();
+        let completed_no_padding = torrent.bytes_completed_with(&have, true).unwrap();
+        assert_eq!(left_no_padding + completed_no_padding, length - padding);
+        assert!(left_no_padding <= left);
+        assert!(completed_no_padding <= completed);
+    }
+}
+
+#[test]
+fn bytes_left_fixture() {
+    let torrent = Torrent::read_from_file("tests/files/tails-amd64-3.6.1.torrent").unwrap();
+    let n_pieces = torrent.pieces.len();
+    let length = torrent.length as u64;
+    let last_piece_length = length - (n_pieces as u64 - 1) * torrent.piece_length as u64;
+
+    let mut have = vec![0xff; n_pieces.div_ceil(8)];
+    if !n_pieces.is_multiple_of(8) {
+        *have.last_mut().unwrap() &= !(0xff >> (n_pieces % 8));
+    }
+    assert_eq!(torrent.bytes_left(&have).unwrap(), 0);
+    assert_eq!(torrent.bytes_completed(&have).unwrap(), length);
+
+    // clear the bit of the last piece
+    have[(n_pieces - 1) / 8] &= !(0x80 >> ((n_pieces - 1) % 8));
+    assert_eq!(torrent.bytes_left(&have).unwrap(), last_piece_length);
+    assert!(last_piece_length < torrent.piece_length as u64);
+}
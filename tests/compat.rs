@@ -0,0 +1,123 @@
+#![cfg(feature = "std")]
+// Old-style matching through `compat` must keep working
+// against the errors the current API produces.
+#![allow(deprecated)]
+
+extern crate lava_torrent;
+
+use lava_torrent::compat::{self, Error, ErrorKind};
+use lava_torrent::hex;
+use lava_torrent::torrent::v1::{Torrent, TorrentBuilder};
+use lava_torrent::tracker::TrackerResponse;
+
+fn errors() -> Vec<(Error, ErrorKind)> {
+    vec![
+        (
+            Torrent::read_from_file("tests/files/does-not-exist").unwrap_err(),
+            ErrorKind::IOError,
+        ),
+        (
+            Torrent::read_from_bytes(b"d4:spam").unwrap_err(),
+            ErrorKind::MalformedBencode,
+        ),
+        (
+            Torrent::read_from_bytes(b"d4:spam4:eggse").unwrap_err(),
+            ErrorKind::MalformedTorrent,
+        ),
+        (
+            TrackerResponse::from_bytes(b"d4:spam4:eggse").unwrap_err(),
+            ErrorKind::MalformedResponse,
+        ),
+        (
+            TorrentBuilder::new("tests/files", 1048576)
+                .set_name(String::new())
+                .build()
+                .unwrap_err(),
+            ErrorKind::TorrentBuilderFailure,
+        ),
+        (hex::decode("abc").unwrap_err(), ErrorKind::InvalidArgument),
+        (
+            Torrent {
+                length: -1,
+                ..Torrent::read_from_file("tests/files/ubuntu-16.04.4-desktop-amd64.iso.torrent")
+                    .unwrap()
+            }
+            .preflight_with_available_bytes(&std::env::temp_dir(), 0, 255)
+            .unwrap_err(),
+            ErrorKind::FailedNumericConv,
+        ),
+    ]
+}
+
+#[test]
+fn compare_kinds() {
+    for (error, expected) in errors() {
+        assert!(error.kind() == expected, "{:?}", error);
+        assert_eq!(compat::kind(&error), expected);
+        for (_, other) in errors() {
+            assert_eq!(error.kind() == other, other == expected);
+        }
+    }
+}
+
+#[test]
+fn match_kinds() {
+    let labels = errors()
+        .iter()
+        .map(|(error, _)| match compat::kind(error) {
+            ErrorKind::IOError => "io",
+            ErrorKind::MalformedBencode => "bencode",
+            ErrorKind::MalformedTorrent => "torrent",
+            ErrorKind::MalformedResponse => "response",
+            ErrorKind::TorrentBuilderFailure => "builder",
+            ErrorKind::InvalidArgument => "argument",
+            ErrorKind::FailedNumericConv => "conv",
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        labels,
+        vec!["io", "bencode", "torrent", "response", "builder", "argument", "conv"]
+    );
+}
+
+#[test]
+fn matches_helpers() {
+    for (error, expected) in errors() {
+        assert_eq!(compat::matches_io(&error), expected == ErrorKind::IOError);
+        assert_eq!(
+            compat::matches_malformed_bencode(&error),
+            expected == ErrorKind::MalformedBencode
+        );
+        assert_eq!(
+            compat::matches_malformed_torrent(&error),
+            expected == ErrorKind::MalformedTorrent
+        );
+        assert_eq!(
+            compat::matches_malformed_response(&error),
+            expected == ErrorKind::MalformedResponse
+        );
+        assert_eq!(
+            compat::matches_torrent_builder_failure(&error),
+            expected == ErrorKind::TorrentBuilderFailure
+        );
+        assert_eq!(
+            compat::matches_invalid_argument(&error),
+            expected == ErrorKind::InvalidArgument
+        );
+        assert_eq!(
+            compat::matches_failed_numeric_conv(&error),
+            expected == ErrorKind::FailedNumericConv
+        );
+    }
+}
+
+#[test]
+fn result_alias() {
+    fn read(bytes: &[u8]) -> compat::Result<Torrent> {
+        let torrent = Torrent::read_from_bytes(bytes)?;
+        Ok(torrent)
+    }
+
+    let error = read(b"d4:spam4:eggse").unwrap_err();
+    assert!(error.kind() == ErrorKind::MalformedTorrent);
+}
@@ -0,0 +1,305 @@
+//! Generator for a corpus of edge-case torrents.
+//!
+//! Every case is synthesized here instead of being checked in as a binary,
+//! so each one is documented by the code that produces it. The name of a
+//! case encodes the expected outcome of reading it:
+//!
+//! - `ok_*`: read successfully. The info hash is that of [`Case::info`],
+//!   and it does not change when the torrent is encoded and read again.
+//! - `err_*`: rejected, even with every lenient `ReadOptions` enabled.
+//!   Some of these (e.g. a trailing newline) are salvaged by other
+//!   clients, but `ReadOptions` has no option for them.
+//! - `lenient_ok_*`: rejected by default, but read successfully with
+//!   every lenient `ReadOptions` enabled. As the reader may normalize the
+//!   input, the info hash need not be that of [`Case::info`], but it does
+//!   not change when the torrent is encoded and read again.
+//!
+//! The corpus is checked by `tests/read_corpus.rs`. Run
+//! `cargo run --example gen_corpus [DIR]` to write it to disk.
+
+/// A single torrent of the corpus.
+pub struct Case {
+    pub name: &'static str,
+    pub bytes: Vec<u8>,
+    /// The `info` dictionary, exactly as it appears in `bytes`.
+    pub info: Vec<u8>,
+}
+
+const ANNOUNCE: &[u8] = b"http://tracker.example.com/announce";
+
+// The helpers below encode bencode by hand (keeping dictionary entries
+// in the given order), so that the corpus does not depend on the
+// encoder under test.
+
+fn string(bytes: &[u8]) -> Vec<u8> {
+    let mut encoded = format!("{}:", bytes.len()).into_bytes();
+    encoded.extend_from_slice(bytes);
+    encoded
+}
+
+fn integer(n: i64) -> Vec<u8> {
+    format!("i{}e", n).into_bytes()
+}
+
+fn list(items: &[Vec<u8>]) -> Vec<u8> {
+    let mut encoded = vec![b'l'];
+    for item in items {
+        encoded.extend_from_slice(item);
+    }
+    encoded.push(b'e');
+    encoded
+}
+
+fn dictionary(entries: &[(&[u8], Vec<u8>)]) -> Vec<u8> {
+    let mut encoded = vec![b'd'];
+    for (key, val) in entries {
+        encoded.extend(string(key));
+        encoded.extend_from_slice(val);
+    }
+    encoded.push(b'e');
+    encoded
+}
+
+// `n` piece hashes. 0xab is not valid utf8 on its own.
+fn pieces(n: usize) -> Vec<u8> {
+    string(&vec![0xab; 20 * n])
+}
+
+fn single_file_info() -> Vec<u8> {
+    dictionary(&[
+        (b"length", integer(256)),
+        (b"name", string(b"sample")),
+        (b"piece length", integer(256)),
+        (b"pieces", pieces(1)),
+    ])
+}
+
+fn multi_file_info(files: &[Vec<u8>]) -> Vec<u8> {
+    dictionary(&[
+        (b"files", list(files)),
+        (b"name", string(b"sample")),
+        (b"piece length", integer(256)),
+        (b"pieces", pieces(1)),
+    ])
+}
+
+fn file(length: i64, path: &[&[u8]]) -> Vec<u8> {
+    let path = path.iter().map(|c| string(c)).collect::<Vec<_>>();
+    dictionary(&[(b"length", integer(length)), (b"path", list(&path))])
+}
+
+fn torrent(info: &[u8]) -> Vec<u8> {
+    dictionary(&[(b"announce", string(ANNOUNCE)), (b"info", info.to_vec())])
+}
+
+fn case(name: &'static str, info: Vec<u8>) -> Case {
+    Case {
+        name,
+        bytes: torrent(&info),
+        info,
+    }
+}
+
+/// Return every case of the corpus.
+pub fn cases() -> Vec<Case> {
+    let mut cases = vec![
+        // baseline
+        case("ok_single_file", single_file_info()),
+        // 0-length files take up no room in any piece
+        case(
+            "ok_zero_length_file",
+            multi_file_info(&[file(0, &[b"empty"]), file(256, &[b"dir", b"data"])]),
+        ),
+        // piece hashes that happen to be valid utf8
+        case(
+            "ok_utf8_pieces",
+            dictionary(&[
+                (b"length", integer(256)),
+                (b"name", string(b"sample")),
+                (b"piece length", integer(256)),
+                (b"pieces", string(b"abcdefghijklmnopqrst")),
+            ]),
+        ),
+        // "pieces" must not be empty, even if there is nothing to hash
+        case(
+            "err_empty_pieces",
+            dictionary(&[
+                (b"length", integer(0)),
+                (b"name", string(b"sample")),
+                (b"piece length", integer(256)),
+                (b"pieces", pieces(0)),
+            ]),
+        ),
+        // a key that is not valid utf8, sorted as raw bytes
+        case(
+            "ok_binary_info_key",
+            dictionary(&[
+                (b"length", integer(256)),
+                (b"name", string(b"sample")),
+                (b"piece length", integer(256)),
+                (b"pieces", pieces(1)),
+                (b"x-vendor\xff", string(b"blob")),
+            ]),
+        ),
+        // fields unknown to the reader end up in `extra_info_fields`
+        case(
+            "ok_unknown_info_fields",
+            dictionary(&[
+                (b"length", integer(256)),
+                (b"name", string(b"sample")),
+                (b"piece length", integer(256)),
+                (b"pieces", pieces(1)),
+                (b"source", string(b"example")),
+                (
+                    b"x-nested",
+                    list(&[integer(1), dictionary(&[(b"a", list(&[]))])]),
+                ),
+            ]),
+        ),
+        // non-ascii (but valid utf8) path components
+        case(
+            "ok_unicode_path",
+            multi_file_info(&[file(256, &["日本語".as_bytes(), "😀.txt".as_bytes()])]),
+        ),
+        case(
+            "err_path_traversal",
+            multi_file_info(&[file(256, &[b"..", b"etc", b"passwd"])]),
+        ),
+        case(
+            "err_negative_length",
+            dictionary(&[
+                (b"length", integer(-1)),
+                (b"name", string(b"sample")),
+                (b"piece length", integer(256)),
+                (b"pieces", pieces(1)),
+            ]),
+        ),
+        case(
+            "err_partial_piece_hash",
+            dictionary(&[
+                (b"length", integer(256)),
+                (b"name", string(b"sample")),
+                (b"piece length", integer(256)),
+                (b"pieces", string(&[0xab; 19])),
+            ]),
+        ),
+        case("err_empty_path", multi_file_info(&[file(256, &[])])),
+        // the info dict should be sorted as raw strings
+        case(
+            "err_unsorted_info",
+            dictionary(&[
+                (b"name", string(b"sample")),
+                (b"length", integer(256)),
+                (b"piece length", integer(256)),
+                (b"pieces", pieces(1)),
+            ]),
+        ),
+        // path components in a legacy encoding (here latin-1)
+        case(
+            "err_non_utf8_path",
+            multi_file_info(&[file(256, &[b"caf\xe9.txt"])]),
+        ),
+        // a name in a legacy encoding, decoded lossily
+        case(
+            "lenient_ok_non_utf8_name",
+            dictionary(&[
+                (b"length", integer(256)),
+                (b"name", string(b"caf\xe9")),
+                (b"piece length", integer(256)),
+                (b"pieces", pieces(1)),
+            ]),
+        ),
+        // an absolute path, made relative
+        case(
+            "lenient_ok_absolute_path",
+            multi_file_info(&[file(256, &[b"/etc", b"passwd"])]),
+        ),
+        // both `length` and `files`, the latter taking precedence
+        case(
+            "lenient_ok_length_with_files",
+            dictionary(&[
+                (b"files", list(&[file(256, &[b"data"])])),
+                (b"length", integer(256)),
+                (b"name", string(b"sample")),
+                (b"piece length", integer(256)),
+                (b"pieces", pieces(1)),
+            ]),
+        ),
+        // one piece hash per list entry instead of their concatenation
+        case(
+            "lenient_ok_pieces_list",
+            dictionary(&[
+                (b"length", integer(512)),
+                (b"name", string(b"sample")),
+                (b"piece length", integer(256)),
+                (b"pieces", list(&[string(&[0xab; 20]), string(&[0xcd; 20])])),
+            ]),
+        ),
+    ];
+
+    // duplicate keys: the last one wins
+    let info = single_file_info();
+    cases.push(Case {
+        name: "ok_duplicate_key",
+        bytes: dictionary(&[
+            (b"announce", string(b"http://first.example.com/announce")),
+            (b"announce", string(ANNOUNCE)),
+            (b"info", info.clone()),
+        ]),
+        info,
+    });
+
+    // a list of urls instead of a list of tiers
+    let info = single_file_info();
+    cases.push(Case {
+        name: "err_flat_announce_list",
+        bytes: dictionary(&[
+            (b"announce", string(ANNOUNCE)),
+            (
+                b"announce-list",
+                list(&[string(ANNOUNCE), string(b"udp://tracker.example.com:6969")]),
+            ),
+            (b"info", info.clone()),
+        ]),
+        info,
+    });
+
+    // a utf8 BOM, as prepended by some text-oriented tools
+    let info = single_file_info();
+    let mut bytes = b"\xef\xbb\xbf".to_vec();
+    bytes.extend(torrent(&info));
+    cases.push(Case {
+        name: "err_bom_prefix",
+        bytes,
+        info,
+    });
+
+    // a trailing newline, as appended by some download scripts
+    let info = single_file_info();
+    let mut bytes = torrent(&info);
+    bytes.push(b'\n');
+    cases.push(Case {
+        name: "err_trailing_newline",
+        bytes,
+        info,
+    });
+
+    // cut off in the middle of the info dict
+    let info = single_file_info();
+    let mut bytes = torrent(&info);
+    bytes.truncate(bytes.len() - 10);
+    cases.push(Case {
+        name: "err_truncated",
+        bytes,
+        info,
+    });
+
+    let info = single_file_info();
+    cases.push(Case {
+        name: "err_missing_info",
+        bytes: dictionary(&[(b"announce", string(ANNOUNCE))]),
+        info,
+    });
+
+    cases
+}
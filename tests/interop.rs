@@ -0,0 +1,197 @@
+//! Reproduce torrents created by other clients from the same content.
+//!
+//! Each row of `tests/interop/matrix.tsv` names (paths relative to `tests/`):
+//!
+//! - `reference`: a *.torrent* file created by another client,
+//! - `creator`: the client, which decides the fields it always writes
+//!   (see `creator_fields()`),
+//! - `content`: what the reference was created from,
+//! - `options`: comma-separated builder options the reference was created
+//!   with (`private`), or `-` for none,
+//! - `divergences`: comma-separated fields that can't be reproduced, or `-`
+//!   for none. Fields in `info` are prefixed with `info.`.
+//!
+//! The built torrent must differ from the reference in exactly the listed
+//! fields, and be byte-for-byte identical once they are copied over (which
+//! is only possible outside of `info`). Any change that breaks interop
+//! (or fixes a divergence) thus fails until the matrix is updated.
+//!
+//! References have to be created with the actual clients. Only Deluge and
+//! qBittorrent are covered. To cover another client, create a reference
+//! with it from the content above, check it in under `tests/samples/`,
+//! teach `creator_fields()` about the client, and add a row.
+
+#![cfg(feature = "std")]
+
+extern crate lava_torrent;
+
+use lava_torrent::bencode::BencodeElem;
+use lava_torrent::torrent::v1::{Torrent, TorrentBuilder};
+use std::collections::{BTreeSet, HashMap};
+
+const MATRIX: &str = "tests/interop/matrix.tsv";
+
+struct Row {
+    reference: String,
+    creator: String,
+    content: String,
+    options: BTreeSet<String>,
+    divergences: BTreeSet<String>,
+}
+
+fn split_set(column: &str) -> BTreeSet<String> {
+    match column {
+        "-" => BTreeSet::new(),
+        _ => column.split(',').map(str::to_owned).collect(),
+    }
+}
+
+fn read_matrix() -> Vec<Row> {
+    std::fs::read_to_string(MATRIX)
+        .unwrap()
+        .lines()
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let columns: Vec<&str> = line.split('\t').collect();
+            assert_eq!(columns.len(), 5, "malformed row: {}", line);
+            Row {
+                reference: format!("tests/{}", columns[0]),
+                creator: columns[1].to_owned(),
+                content: format!("tests/{}", columns[2]),
+                options: split_set(columns[3]),
+                divergences: split_set(columns[4]),
+            }
+        })
+        .collect()
+}
+
+type Fields = Vec<(&'static str, BencodeElem)>;
+
+// Fields that `creator` writes regardless of options, as
+// (top-level fields, `info` fields).
+fn creator_fields(creator: &str, private: bool) -> (Fields, Fields) {
+    match creator {
+        "deluge" => (
+            vec![("encoding", BencodeElem::String("UTF-8".to_owned()))],
+            if private {
+                vec![]
+            } else {
+                vec![("private", BencodeElem::Integer(0))]
+            },
+        ),
+        "qbittorrent" => (vec![], vec![]),
+        _ => panic!("unknown creator: {}", creator),
+    }
+}
+
+fn build(row: &Row, reference: &Torrent) -> Torrent {
+    for option in &row.options {
+        assert_eq!(option, "private", "unknown option: {}", option);
+    }
+    let private = row.options.contains("private");
+
+    let mut builder = TorrentBuilder::new(&row.content, reference.piece_length)
+        .set_announce(reference.announce.clone())
+        .set_num_threads(1);
+    if let Some(ref announce_list) = reference.announce_list {
+        builder = builder.set_announce_list(announce_list.clone());
+    }
+    if private {
+        builder = builder.set_privacy(true);
+    }
+    let (extra_fields, extra_info_fields) = creator_fields(&row.creator, private);
+    for (key, value) in extra_fields {
+        builder = builder.add_extra_field(key.to_owned(), value);
+    }
+    for (key, value) in extra_info_fields {
+        builder = builder.add_extra_info_field(key.to_owned(), value);
+    }
+
+    // round trip, so that fields are where reading puts them
+    // (e.g. `private` is moved out of `extra_info_fields`)
+    Torrent::read_from_bytes(builder.build().unwrap().encode().unwrap()).unwrap()
+}
+
+fn extra_field_divergences(
+    prefix: &str,
+    a: &Option<HashMap<String, BencodeElem>>,
+    b: &Option<HashMap<String, BencodeElem>>,
+) -> BTreeSet<String> {
+    let empty = HashMap::new();
+    let (a, b) = (a.as_ref().unwrap_or(&empty), b.as_ref().unwrap_or(&empty));
+    a.keys()
+        .chain(b.keys())
+        .filter(|key| a.get(*key) != b.get(*key))
+        .map(|key| format!("{}{}", prefix, key))
+        .collect()
+}
+
+// Names of the fields that differ between `a` and `b`.
+fn divergences(a: &Torrent, b: &Torrent) -> BTreeSet<String> {
+    let mut divergences = extra_field_divergences("", &a.extra_fields, &b.extra_fields);
+    divergences.extend(extra_field_divergences(
+        "info.",
+        &a.extra_info_fields,
+        &b.extra_info_fields,
+    ));
+
+    for (field, differs) in [
+        ("announce", a.announce != b.announce),
+        ("announce-list", a.announce_list != b.announce_list),
+        ("info.name", a.name != b.name),
+        ("info.piece length", a.piece_length != b.piece_length),
+        ("info.pieces", a.pieces != b.pieces),
+        ("info.length", a.length != b.length),
+        ("info.files", a.files != b.files),
+        ("info.private", a.private != b.private),
+    ] {
+        if differs {
+            divergences.insert(field.to_owned());
+        }
+    }
+    divergences
+}
+
+#[test]
+fn interop_matrix() {
+    let rows = read_matrix();
+    assert!(!rows.is_empty());
+
+    for row in rows {
+        let raw = std::fs::read(&row.reference).unwrap();
+        let reference = Torrent::read_from_bytes(&raw).unwrap();
+        let mut built = build(&row, &reference);
+
+        assert_eq!(
+            divergences(&built, &reference),
+            row.divergences,
+            "{}",
+            row.reference
+        );
+
+        // copy over what can't be reproduced, which must leave `info` alone
+        for field in &row.divergences {
+            assert!(!field.starts_with("info."), "{}: {}", row.reference, field);
+            match reference.extra_fields.as_ref().and_then(|f| f.get(field)) {
+                Some(value) => {
+                    built
+                        .extra_fields
+                        .get_or_insert_with(HashMap::new)
+                        .insert(field.clone(), value.clone());
+                }
+                None => {
+                    if let Some(ref mut fields) = built.extra_fields {
+                        fields.remove(field);
+                    }
+                }
+            }
+        }
+        assert_eq!(
+            built.info_hash(),
+            reference.info_hash(),
+            "{}",
+            row.reference
+        );
+        assert!(built.encode().unwrap() == raw, "{}", row.reference);
+    }
+}
@@ -0,0 +1,81 @@
+//! The bencode core must work in `#![no_std]` + `alloc` environments.
+//!
+//! This crate is `#![no_std]` itself, so it can only use what `core`
+//! and `alloc` provide. Run it against a `no_std` build with
+//! `cargo test --no-default-features --features alloc --lib --tests`
+//! (the doc examples assume `std`).
+
+#![no_std]
+
+extern crate alloc;
+extern crate lava_torrent;
+
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec;
+use lava_torrent::bencode::write::encode_integer;
+use lava_torrent::bencode::{BencodeElem, BencodeKind};
+
+const SAMPLE: &[u8] =
+    b"d8:announce9:localhost4:infod6:lengthi42e4:name4:spam6:pieces4:\xff\xfe\xfd\xfcee";
+
+#[test]
+fn round_trip() {
+    let parsed = BencodeElem::from_bytes(SAMPLE).unwrap();
+    assert_eq!(parsed.len(), 1);
+    assert_eq!(parsed[0].encode(), SAMPLE);
+}
+
+#[test]
+fn kinds() {
+    let parsed = BencodeElem::from_bytes(SAMPLE).unwrap().remove(0);
+    assert_eq!(parsed.kind(), BencodeKind::Dictionary);
+
+    match parsed {
+        BencodeElem::Dictionary(dict) => {
+            assert_eq!(
+                dict["announce"],
+                BencodeElem::String("localhost".to_string())
+            );
+            match dict["info"] {
+                BencodeElem::Dictionary(ref info) => {
+                    assert_eq!(info["length"], BencodeElem::Integer(42));
+                    assert_eq!(info["pieces"].kind(), BencodeKind::Bytes);
+                }
+                _ => panic!(),
+            }
+        }
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn encode() {
+    assert_eq!(encode_integer(-42), b"i-42e");
+    assert_eq!(
+        BencodeElem::List(vec![BencodeElem::Integer(42), BencodeElem::from("spam")]).encode(),
+        b"li42e4:spame"
+    );
+}
+
+#[test]
+fn display() {
+    let parsed = BencodeElem::from_bytes(b"l4:spami42ee").unwrap().remove(0);
+    assert_eq!(format!("{}", parsed), r#"["spam", 42]"#);
+}
+
+#[test]
+fn malformed() {
+    assert!(BencodeElem::from_bytes(b"i-0e").is_err());
+    assert!(BencodeElem::from_bytes(b"d4:spami42e3:cowi0ee").is_err());
+}
+
+#[test]
+fn macro_and_builders() {
+    let mut elem = lava_torrent::bencode_elem!({ ("list", [1, "two"]), ("bytes", (0xff, 0xfe)) });
+    elem.insert("int".into(), BencodeElem::from(3)).unwrap();
+    assert_eq!(
+        elem.encode(),
+        b"d5:bytes2:\xff\xfe3:inti3e4:listli1e3:twoee"
+    );
+}
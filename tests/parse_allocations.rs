@@ -0,0 +1,171 @@
+#![cfg(feature = "std")]
+
+extern crate lava_torrent;
+
+use lava_torrent::bencode::BencodeElem;
+use lava_torrent::torrent::v1::Torrent;
+use lava_torrent::LavaTorrentError;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+const SAMPLE: &str = "tests/files/ubuntu-16.04.4-desktop-amd64.iso.torrent";
+
+// Counts calls to `alloc()` and `realloc()`.
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+// the counter is global, so the tests measuring it can't run concurrently
+static SERIAL: Mutex<()> = Mutex::new(());
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOC: CountingAlloc = CountingAlloc;
+
+// Run `f` and return its result along with the number
+// of allocations made while it ran.
+fn count_allocations<T, F: FnOnce() -> T>(f: F) -> (T, usize) {
+    let before = ALLOCATIONS.load(Ordering::SeqCst);
+    let result = f();
+    (result, ALLOCATIONS.load(Ordering::SeqCst) - before)
+}
+
+// Count the strings (including dictionary keys) and containers in `elem`.
+fn count_elements(elem: &BencodeElem) -> (usize, usize) {
+    match elem {
+        BencodeElem::String(_) | BencodeElem::Bytes(_) => (1, 0),
+        BencodeElem::Integer(_) => (0, 0),
+        BencodeElem::List(list) => list.iter().map(count_elements).fold((0, 1), add),
+        BencodeElem::Dictionary(dict) => {
+            dict.values().map(count_elements).fold((dict.len(), 1), add)
+        }
+        BencodeElem::RawDictionary(dict) => {
+            dict.values().map(count_elements).fold((dict.len(), 1), add)
+        }
+    }
+}
+
+fn add((a, b): (usize, usize), (c, d): (usize, usize)) -> (usize, usize) {
+    (a + c, b + d)
+}
+
+#[test]
+fn parse_sample_allocations() {
+    let _guard = SERIAL.lock().unwrap();
+    let bytes = std::fs::read(SAMPLE).unwrap();
+
+    let (parsed, allocations) = count_allocations(|| BencodeElem::from_bytes(&bytes).unwrap());
+    assert_eq!(parsed.len(), 1);
+    let (strings, containers) = count_elements(&parsed[0]);
+    // 1 per string and up to 2 per container, as integers
+    // (including string lengths) are decoded in place
+    assert!(
+        allocations <= strings + 2 * containers,
+        "{} allocations for {} strings and {} containers",
+        allocations,
+        strings,
+        containers
+    );
+}
+
+#[test]
+fn parse_malformed_allocations() {
+    let _guard = SERIAL.lock().unwrap();
+    let long_digits = "1".repeat(64 * 1024);
+    // fixed messages are never allocated, and the others are only
+    // formatted once, when the error leaves the parser
+    let cases = [
+        ("i-0e".to_owned(), "-0 found.", 0),
+        ("i03e".to_owned(), "Integer with leading zero(s) found.", 0),
+        ("i4ae".to_owned(), "Input contains invalid integer: 4a.", 1),
+        (
+            "i9223372036854775808e".to_owned(),
+            "Input contains invalid integer: 9223372036854775808.",
+            1,
+        ),
+        ("i42".to_owned(), "Integer delimiter not found.", 0),
+        // digits are not collected before looking for the delimiter
+        (
+            format!("l{}", long_digits),
+            "Integer delimiter not found.",
+            0,
+        ),
+        (
+            format!("i{}", long_digits),
+            "Integer delimiter not found.",
+            0,
+        ),
+        (
+            "di1e0:e".to_owned(),
+            "Dictionary key must be a string, found integer at offset 1.",
+            1,
+        ),
+        // what was parsed so far is allocated as usual
+        (
+            "d3:keyi1e".to_owned(),
+            "Expected more bytes, but none found.",
+            2,
+        ),
+        (
+            "d1:b0:1:a0:e".to_owned(),
+            "A dictionary is not properly sorted.",
+            3,
+        ),
+    ];
+
+    for (bytes, expected, max_allocations) in cases.iter() {
+        let (result, allocations) = count_allocations(|| BencodeElem::from_bytes(bytes));
+        match result {
+            Err(LavaTorrentError::MalformedBencode(m)) => assert_eq!(m, *expected),
+            other => panic!("{:?}", other),
+        }
+        assert!(
+            allocations <= *max_allocations,
+            "{}: {} allocations",
+            expected,
+            allocations
+        );
+    }
+}
+
+#[test]
+fn info_hash_allocations() {
+    let _guard = SERIAL.lock().unwrap();
+    let sample = Torrent::read_from_file(SAMPLE).unwrap();
+    let with_pieces = |n_pieces: usize| Torrent {
+        length: sample.piece_length * n_pieces as i64,
+        pieces: (0..n_pieces)
+            .map(|i| {
+                let mut piece = [0; 20];
+                piece[..8].copy_from_slice(&i.to_be_bytes());
+                piece
+            })
+            .collect(),
+        ..sample.clone()
+    };
+
+    // `pieces` is streamed into the hasher, so the # of allocations
+    // (all for the other fields of `info`) doesn't depend on it
+    let (small, large) = (with_pieces(1), with_pieces(500_000));
+    let (_, small_allocations) = count_allocations(|| small.info_hash_bytes());
+    let (_, large_allocations) = count_allocations(|| large.info_hash_bytes());
+    assert_eq!(small_allocations, large_allocations);
+
+    let (digest, _) = count_allocations(|| large.info_hash_bytes());
+    assert_eq!(digest, large.construct_info().sha1());
+}
@@ -0,0 +1,65 @@
+#![cfg(feature = "std")]
+
+extern crate lava_torrent;
+
+mod corpus;
+
+use lava_torrent::torrent::v1::{ReadOptions, Torrent};
+use sha1::{Digest, Sha1};
+
+fn lenient_options() -> ReadOptions {
+    ReadOptions {
+        sanitize_paths: true,
+        lossy_utf8: true,
+        allow_length_with_files: true,
+        allow_pieces_list: true,
+        ..ReadOptions::default()
+    }
+}
+
+// the info hash must survive a round trip
+fn assert_round_trip(name: &str, torrent: Torrent, options: &ReadOptions) {
+    let info_hash = torrent.info_hash();
+    let reread = Torrent::read_from_bytes_with(torrent.encode().unwrap(), options)
+        .unwrap_or_else(|e| panic!("{}: {}", name, e));
+    assert_eq!(reread.info_hash(), info_hash, "{}", name);
+}
+
+#[test]
+fn read_corpus() {
+    for case in corpus::cases() {
+        let name = case.name;
+        let strict = Torrent::read_from_bytes(&case.bytes);
+        let lenient = Torrent::read_from_bytes_with(&case.bytes, &lenient_options());
+
+        if name.starts_with("ok_") {
+            let info_hash = Sha1::digest(&case.info).to_vec();
+            for result in [strict, lenient] {
+                let torrent = result.unwrap_or_else(|e| panic!("{}: {}", name, e));
+                assert_eq!(torrent.info_hash_bytes(), info_hash, "{}", name);
+                assert_round_trip(name, torrent, &ReadOptions::default());
+            }
+        } else if name.starts_with("lenient_ok_") {
+            assert!(strict.is_err(), "{}: expected an error", name);
+            let torrent = lenient.unwrap_or_else(|e| panic!("{}: {}", name, e));
+            assert_round_trip(name, torrent, &lenient_options());
+        } else if name.starts_with("err_") {
+            assert!(strict.is_err(), "{}: expected an error", name);
+            assert!(lenient.is_err(), "{}: expected an error", name);
+        } else {
+            panic!("{}: unknown prefix", name);
+        }
+    }
+}
+
+#[test]
+fn corpus_names_unique() {
+    let mut names = corpus::cases()
+        .into_iter()
+        .map(|case| case.name)
+        .collect::<Vec<_>>();
+    let len = names.len();
+    names.sort_unstable();
+    names.dedup();
+    assert_eq!(names.len(), len);
+}
@@ -1,13 +1,16 @@
+#![cfg(feature = "std")]
+
 extern crate conv;
 extern crate lava_torrent;
 
 use conv::ValueFrom;
 use lava_torrent::bencode::BencodeElem;
-use lava_torrent::torrent::v1::{File, Torrent};
+use lava_torrent::hash;
+use lava_torrent::torrent::v1::{self, File, MagnetStub, PathSegment, Torrent};
+use lava_torrent::{LavaTorrentError, LavaTorrentErrorKind};
 use std::collections::HashMap;
 use std::io::{BufReader, Read};
 use std::iter::FromIterator;
-use std::path::PathBuf;
 
 #[test]
 fn read_from_bytes() {
@@ -114,12 +117,12 @@ fn read_from_bytes_multiple_files() {
         Some(vec![
             File {
                 length: 1_225_568_256,
-                path: PathBuf::from("tails-amd64-3.6.1.iso"),
+                path: vec!["tails-amd64-3.6.1.iso".to_owned()],
                 extra_fields: None,
             },
             File {
                 length: 228,
-                path: PathBuf::from("tails-amd64-3.6.1.iso.sig"),
+                path: vec!["tails-amd64-3.6.1.iso.sig".to_owned()],
                 extra_fields: None,
             },
         ])
@@ -182,3 +185,1141 @@ fn read_from_files_multiple_files() {
         Torrent::read_from_file("tests/files/tails-amd64-3.6.1.torrent").unwrap(),
     );
 }
+
+#[test]
+fn read_binary_keys() {
+    // both the root and info dictionaries have a non-utf8 key
+    let bytes = std::fs::read("tests/samples/binary_key.torrent").unwrap();
+    let torrent = Torrent::read_from_bytes(&bytes).unwrap();
+
+    assert_eq!(torrent.name, "byte_sequence");
+    assert_eq!(torrent.length, 256);
+    assert_eq!(
+        torrent.info_hash(),
+        "a5ec090f441fd7ffacdd7a3ee3db09219f756286"
+    );
+    assert_eq!(torrent.encode().unwrap(), bytes);
+}
+
+#[test]
+fn read_from_fastresume() {
+    let original =
+        Torrent::read_from_file("tests/files/ubuntu-16.04.4-desktop-amd64.iso.torrent").unwrap();
+
+    // resume data built from the skeleton, in the layout libtorrent uses
+    // (this is not a file written by libtorrent itself)
+    let mut resume = match original.to_fastresume_skeleton("/downloads") {
+        BencodeElem::Dictionary(dict) => dict,
+        _ => panic!(),
+    };
+    resume.insert("info".to_owned(), original.construct_info());
+
+    let (parsed, remaining) =
+        v1::from_fastresume(BencodeElem::Dictionary(resume).encode()).unwrap();
+    assert_eq!(parsed.info_hash(), original.info_hash());
+    assert_eq!(parsed.announce, original.announce);
+    assert_eq!(parsed.announce_list, original.announce_list);
+    assert_eq!(
+        remaining.get("save_path"),
+        Some(&BencodeElem::String("/downloads".to_owned()))
+    );
+}
+
+#[test]
+fn extract_all_from_wrapper() {
+    let ubuntu = BencodeElem::from_file("tests/files/ubuntu-16.04.4-desktop-amd64.iso.torrent")
+        .unwrap()
+        .remove(0);
+    let tails = BencodeElem::from_file("tests/files/tails-amd64-3.6.1.torrent")
+        .unwrap()
+        .remove(0);
+    let wrapper = BencodeElem::Dictionary(HashMap::from_iter(vec![
+        ("added_on".to_owned(), BencodeElem::Integer(123)),
+        ("torrent".to_owned(), ubuntu),
+        (
+            "session".to_owned(),
+            BencodeElem::List(vec![
+                // decoy: `info` is missing `pieces`
+                BencodeElem::Dictionary(HashMap::from_iter(vec![(
+                    "info".to_owned(),
+                    BencodeElem::Dictionary(HashMap::from_iter(vec![
+                        ("name".to_owned(), BencodeElem::String("decoy".to_owned())),
+                        ("piece length".to_owned(), BencodeElem::Integer(16384)),
+                    ])),
+                )])),
+                // decoy: `info` is not a dict
+                BencodeElem::Dictionary(HashMap::from_iter(vec![(
+                    "info".to_owned(),
+                    BencodeElem::String("decoy".to_owned()),
+                )])),
+                BencodeElem::Dictionary(HashMap::from_iter(vec![("metadata".to_owned(), tails)])),
+            ]),
+        ),
+    ]));
+
+    let found = Torrent::extract_all(&wrapper);
+    assert_eq!(found.len(), 2);
+
+    // keys are visited in sorted order, so "session" comes before "torrent"
+    assert_eq!(
+        found[0].0,
+        vec![
+            PathSegment::Key(b"session".to_vec()),
+            PathSegment::Index(2),
+            PathSegment::Key(b"metadata".to_vec()),
+        ]
+    );
+    assert_eq!(
+        found[0].1.as_ref().unwrap(),
+        &Torrent::read_from_file("tests/files/tails-amd64-3.6.1.torrent").unwrap()
+    );
+    assert_eq!(found[1].0, vec![PathSegment::Key(b"torrent".to_vec())]);
+    assert_eq!(
+        found[1].1.as_ref().unwrap(),
+        &Torrent::read_from_file("tests/files/ubuntu-16.04.4-desktop-amd64.iso.torrent").unwrap()
+    );
+}
+
+#[test]
+fn extract_all_invalid() {
+    // looks like a torrent, but `pieces` is malformed
+    let elem = BencodeElem::List(vec![BencodeElem::Dictionary(HashMap::from_iter(vec![(
+        "info".to_owned(),
+        BencodeElem::Dictionary(HashMap::from_iter(vec![
+            ("name".to_owned(), BencodeElem::String("bad".to_owned())),
+            ("length".to_owned(), BencodeElem::Integer(1)),
+            ("piece length".to_owned(), BencodeElem::Integer(16384)),
+            ("pieces".to_owned(), BencodeElem::Bytes(vec![0x00; 19])),
+        ])),
+    )]))]);
+
+    let found = Torrent::extract_all(&elem);
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].0, vec![PathSegment::Index(0)]);
+    assert!(found[0].1.is_err());
+}
+
+// the raw `info` dict in `bytes`, assuming that it's the last top-level key
+fn info_span(bytes: &[u8]) -> &[u8] {
+    let start = bytes.windows(7).position(|w| w == b"4:infod").unwrap() + 6;
+    &bytes[start..bytes.len() - 1]
+}
+
+#[test]
+fn magnet_stub_upgrade() {
+    let bytes = std::fs::read("tests/files/ubuntu-16.04.4-desktop-amd64.iso.torrent").unwrap();
+    let expected = Torrent::read_from_bytes(&bytes).unwrap();
+    let stub = MagnetStub::from(&expected);
+    assert_eq!(
+        stub.trackers,
+        vec![
+            "http://torrent.ubuntu.com:6969/announce".to_owned(),
+            "http://ipv6.torrent.ubuntu.com:6969/announce".to_owned(),
+        ]
+    );
+
+    let torrent = stub.upgrade(info_span(&bytes)).unwrap();
+    assert_eq!(torrent.info_hash(), expected.info_hash());
+    // everything outside of `info` (except the trackers) is lost
+    assert_eq!(
+        torrent,
+        Torrent {
+            extra_fields: None,
+            ..expected
+        }
+    );
+}
+
+#[test]
+fn magnet_stub_upgrade_flipped_byte() {
+    let bytes = std::fs::read("tests/files/ubuntu-16.04.4-desktop-amd64.iso.torrent").unwrap();
+    let stub = MagnetStub::from(&Torrent::read_from_bytes(&bytes).unwrap());
+
+    let mut info = info_span(&bytes).to_vec();
+    let last = info.len() - 2; // somewhere in `pieces`
+    info[last] ^= 0x01;
+
+    match stub.upgrade(&info) {
+        Err(LavaTorrentError::MalformedTorrent(m)) => {
+            assert!(m.starts_with("Info hash mismatch"));
+        }
+        _ => panic!(),
+    }
+}
+
+// a multi-file torrent whose only file has `path`
+fn torrent_with_path(path: Vec<String>) -> Vec<u8> {
+    let file = HashMap::from_iter(vec![
+        ("length".to_owned(), BencodeElem::Integer(1)),
+        (
+            "path".to_owned(),
+            BencodeElem::List(path.into_iter().map(BencodeElem::String).collect()),
+        ),
+    ]);
+    let info = HashMap::from_iter(vec![
+        (
+            "files".to_owned(),
+            BencodeElem::List(vec![BencodeElem::Dictionary(file)]),
+        ),
+        ("name".to_owned(), BencodeElem::String("root".to_owned())),
+        ("piece length".to_owned(), BencodeElem::Integer(1)),
+        ("pieces".to_owned(), BencodeElem::Bytes(vec![0xff; 20])),
+    ]);
+    BencodeElem::Dictionary(HashMap::from_iter(vec![(
+        "info".to_owned(),
+        BencodeElem::Dictionary(info),
+    )]))
+    .encode()
+}
+
+#[test]
+fn read_features_fixtures() {
+    let features = |path| {
+        let bytes = std::fs::read(path).unwrap();
+        Torrent::read_from_bytes_with_features(bytes).unwrap().1
+    };
+
+    assert_eq!(
+        features("tests/files/ubuntu-16.04.4-desktop-amd64.iso.torrent"),
+        v1::TorrentFeatures {
+            single_file_form: true,
+            has_announce: true,
+            has_announce_list: true,
+            announce_list_tier_count: 2,
+            has_creation_date: true,
+            ..v1::TorrentFeatures::default()
+        }
+    );
+    assert_eq!(
+        features("tests/files/tails-amd64-3.6.1.torrent"),
+        v1::TorrentFeatures {
+            has_announce: true,
+            has_announce_list: true,
+            announce_list_tier_count: 3,
+            has_creation_date: true,
+            ..v1::TorrentFeatures::default()
+        }
+    );
+    assert_eq!(
+        features("tests/samples/files-private.torrent"),
+        v1::TorrentFeatures {
+            has_announce: true,
+            has_private_in_info: true,
+            has_creation_date: true,
+            ..v1::TorrentFeatures::default()
+        }
+    );
+    assert_eq!(
+        features("tests/samples/symlink.torrent"),
+        v1::TorrentFeatures {
+            single_file_form: true,
+            has_announce: true,
+            has_private_in_info: true,
+            has_creation_date: true,
+            ..v1::TorrentFeatures::default()
+        }
+    );
+    assert_eq!(
+        features("tests/samples/nested.torrent"),
+        v1::TorrentFeatures {
+            has_creation_date: true,
+            ..v1::TorrentFeatures::default()
+        }
+    );
+    assert_eq!(
+        features("tests/samples/binary_key.torrent"),
+        v1::TorrentFeatures {
+            single_file_form: true,
+            has_announce: true,
+            ..v1::TorrentFeatures::default()
+        }
+    );
+}
+
+#[test]
+fn read_features_extensions() {
+    let file = |path: &[&str], attr: Option<&str>| {
+        let mut file = HashMap::from_iter(vec![
+            ("length".to_owned(), BencodeElem::Integer(1)),
+            (
+                "path".to_owned(),
+                BencodeElem::List(path.iter().map(|&c| BencodeElem::from(c)).collect()),
+            ),
+        ]);
+        if let Some(attr) = attr {
+            file.insert("attr".to_owned(), BencodeElem::from(attr));
+        }
+        BencodeElem::Dictionary(file)
+    };
+    let info = HashMap::from_iter(vec![
+        (
+            "files".to_owned(),
+            BencodeElem::List(vec![file(&["a"], None), file(&[".pad", "1"], Some("p"))]),
+        ),
+        ("name".to_owned(), BencodeElem::from("root")),
+        ("piece length".to_owned(), BencodeElem::Integer(1)),
+        ("pieces".to_owned(), BencodeElem::Bytes(vec![0xff; 40])),
+        ("private".to_owned(), BencodeElem::Integer(1)),
+        ("source".to_owned(), BencodeElem::from("tracker")),
+    ]);
+    let bytes = BencodeElem::Dictionary(HashMap::from_iter(vec![
+        ("announce-list".to_owned(), BencodeElem::List(vec![])),
+        ("comment".to_owned(), BencodeElem::Bytes(vec![0xff])),
+        ("info".to_owned(), BencodeElem::Dictionary(info)),
+        ("nodes".to_owned(), BencodeElem::List(vec![])),
+        ("private".to_owned(), BencodeElem::Integer(1)),
+        (
+            "url-list".to_owned(),
+            BencodeElem::from("http://example.org/"),
+        ),
+    ]))
+    .encode();
+
+    let (torrent, features) = Torrent::read_from_bytes_with_features(&bytes).unwrap();
+    assert_eq!(torrent, Torrent::read_from_bytes(&bytes).unwrap());
+    assert_eq!(
+        features,
+        v1::TorrentFeatures {
+            single_file_form: false,
+            has_announce: false,
+            // present, even though it is empty
+            has_announce_list: true,
+            announce_list_tier_count: 0,
+            has_url_list: true,
+            has_nodes: true,
+            has_private_in_info: true,
+            has_private_top_level: true,
+            has_creation_date: false,
+            has_source: true,
+            has_padding_files: true,
+            non_utf8_strings: 1,
+        }
+    );
+}
+
+#[test]
+fn read_path_components_limit() {
+    let bytes = torrent_with_path(vec!["a".to_owned(); 256]);
+    assert!(Torrent::read_from_bytes(bytes).is_ok());
+
+    let bytes = torrent_with_path(vec!["a".to_owned(); 257]);
+    match Torrent::read_from_bytes(&bytes) {
+        Err(LavaTorrentError::MalformedTorrent(m)) => assert_eq!(
+            m,
+            r#""path" of file 0 has 257 components, exceeding max_path_components (256)."#
+        ),
+        _ => panic!(),
+    }
+
+    let options = v1::ReadOptions {
+        max_path_components: 257,
+        ..v1::ReadOptions::default()
+    };
+    assert!(Torrent::read_from_bytes_with(&bytes, &options).is_ok());
+}
+
+#[test]
+fn read_path_bytes_limit() {
+    let bytes = torrent_with_path(vec!["a".repeat(32 * 1024), "b".repeat(32 * 1024)]);
+    assert!(Torrent::read_from_bytes(bytes).is_ok());
+
+    let bytes = torrent_with_path(vec!["a".repeat(32 * 1024), "b".repeat(32 * 1024 + 1)]);
+    match Torrent::read_from_bytes(&bytes) {
+        Err(LavaTorrentError::MalformedTorrent(m)) => {
+            assert_eq!(m, r#""path" of file 0 exceeds max_path_bytes (65536)."#)
+        }
+        _ => panic!(),
+    }
+
+    let options = v1::ReadOptions {
+        max_path_bytes: 64 * 1024 + 1,
+        ..v1::ReadOptions::default()
+    };
+    assert!(Torrent::read_from_bytes_with(&bytes, &options).is_ok());
+}
+
+#[test]
+fn read_path_separators_in_components() {
+    // separators inside a component must not create new components,
+    // regardless of the platform the torrent is read on
+    for path in [
+        vec!["dir\\file.ext".to_owned()],
+        vec!["a/b".to_owned()],
+        vec!["dir:".to_owned(), "x\\y/z".to_owned()],
+    ] {
+        let bytes = torrent_with_path(path.clone());
+        let torrent = Torrent::read_from_bytes(&bytes).unwrap();
+        let info_hash = torrent.info_hash();
+
+        assert_eq!(torrent.files.as_ref().unwrap()[0].path, path);
+        let encoded = torrent.encode().unwrap();
+        assert_eq!(encoded, bytes);
+        assert_eq!(
+            Torrent::read_from_bytes(encoded).unwrap().info_hash(),
+            info_hash
+        );
+    }
+}
+
+#[test]
+fn read_absolute_components() {
+    let sanitize = v1::ReadOptions {
+        sanitize_paths: true,
+        ..v1::ReadOptions::default()
+    };
+
+    // whether the sanitized component is safe
+    // (backslashes are kept, but `revalidate()` rejects them)
+    for (component, sanitized, safe) in [
+        ("/etc", "etc", true),
+        ("C:\\Windows\\system32", "Windows\\system32", false),
+        ("\\\\server\\share", "server\\share", false),
+        // nothing is left after stripping
+        ("c:", "_", true),
+        ("/", "_", true),
+    ] {
+        let bytes = torrent_with_path(vec!["dir".to_owned(), component.to_owned()]);
+        match Torrent::read_from_bytes(&bytes) {
+            Err(LavaTorrentError::MalformedTorrent(m)) => assert_eq!(
+                m,
+                format!(r#""path" contains an absolute component: {:?}."#, component)
+            ),
+            _ => panic!("{:?} was accepted", component),
+        }
+
+        let torrent = Torrent::read_from_bytes_with(&bytes, &sanitize).unwrap();
+        let file = &torrent.files.as_ref().unwrap()[0];
+        assert_eq!(file.path, vec!["dir".to_owned(), sanitized.to_owned()]);
+        assert!(file.absolute_path(std::env::current_dir().unwrap()).is_ok());
+        assert_eq!(torrent.revalidate().is_ok(), safe, "{:?}", component);
+    }
+}
+
+#[test]
+fn read_files_limit() {
+    let path = "tests/files/tails-amd64-3.6.1.torrent";
+    let options = v1::ReadOptions {
+        max_files: 2,
+        ..v1::ReadOptions::default()
+    };
+    assert!(Torrent::read_from_file_with(path, &options).is_ok());
+
+    let options = v1::ReadOptions {
+        max_files: 1,
+        ..v1::ReadOptions::default()
+    };
+    match Torrent::read_from_file_with(path, &options) {
+        Err(LavaTorrentError::MalformedTorrent(m)) => {
+            assert_eq!(
+                m,
+                format!(
+                    r#"[{}]: "files" has 2 entries, exceeding max_files (1)."#,
+                    path
+                )
+            )
+        }
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn read_from_file_error_names_path() {
+    let path = "tests/files/byte_sequence";
+    for error in [
+        Torrent::read_from_file(path).unwrap_err(),
+        BencodeElem::from_file(path).unwrap_err(),
+    ] {
+        // the variant is kept, only the message gains the path
+        assert_eq!(error.kind(), LavaTorrentErrorKind::MalformedBencode);
+        match error {
+            LavaTorrentError::MalformedBencode(ref m) => {
+                assert!(m.starts_with(&format!("[{}]: ", path)), "{}", m)
+            }
+            _ => panic!(),
+        }
+        assert!(error
+            .to_string()
+            .starts_with(&format!("malformed bencode: [{}]: ", path)));
+    }
+
+    let path = "tests/files/does-not-exist.torrent";
+    match Torrent::read_from_file(path) {
+        Err(LavaTorrentError::Io(e)) => {
+            assert_eq!(e.kind(), std::io::ErrorKind::NotFound);
+            assert!(e.to_string().starts_with(&format!("[{}]: ", path)));
+            // the original error is kept as the source
+            let source = std::error::Error::source(&*e)
+                .and_then(|source| source.downcast_ref::<std::io::Error>())
+                .unwrap();
+            assert_eq!(source.kind(), std::io::ErrorKind::NotFound);
+            assert!(source.raw_os_error().is_some());
+            assert!(e.to_string().ends_with(&source.to_string()));
+        }
+        _ => panic!(),
+    }
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn read_from_file_gzip() {
+    assert_eq!(
+        Torrent::read_from_file("tests/compressed/ubuntu-16.04.4-desktop-amd64.iso.torrent.gz")
+            .unwrap(),
+        Torrent::read_from_file("tests/files/ubuntu-16.04.4-desktop-amd64.iso.torrent").unwrap(),
+    );
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn read_from_file_zlib() {
+    assert_eq!(
+        Torrent::read_from_file("tests/compressed/ubuntu-16.04.4-desktop-amd64.iso.torrent.zz")
+            .unwrap(),
+        Torrent::read_from_file("tests/files/ubuntu-16.04.4-desktop-amd64.iso.torrent").unwrap(),
+    );
+}
+
+#[cfg(not(feature = "gzip"))]
+#[test]
+fn read_from_file_gzip_disabled() {
+    let path = "tests/compressed/ubuntu-16.04.4-desktop-amd64.iso.torrent.gz";
+    match Torrent::read_from_file(path) {
+        Err(lava_torrent::LavaTorrentError::MalformedTorrent(m)) => {
+            assert!(m.starts_with(&format!("[{}]: Input is gzip-compressed.", path)));
+        }
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn read_from_bytes_spanned() {
+    let bytes = std::fs::read("tests/files/ubuntu-16.04.4-desktop-amd64.iso.torrent").unwrap();
+    let spanned = Torrent::read_from_bytes_spanned(&bytes).unwrap();
+    assert_eq!(
+        spanned.torrent(),
+        &Torrent::read_from_bytes(&bytes).unwrap()
+    );
+    assert_eq!(spanned.raw_bytes(), &bytes[..]);
+
+    let creation_date = spanned.raw_field_bytes(&["creation date"]).unwrap();
+    assert_eq!(creation_date, b"i1519934077e");
+    assert_eq!(
+        BencodeElem::from_bytes(creation_date).unwrap(),
+        vec![BencodeElem::Integer(1519934077)]
+    );
+
+    let name = spanned.raw_field_bytes(&["info", "name"]).unwrap();
+    assert_eq!(
+        BencodeElem::from_bytes(name).unwrap(),
+        vec![BencodeElem::String(
+            "ubuntu-16.04.4-desktop-amd64.iso".to_owned()
+        )]
+    );
+
+    assert_eq!(spanned.raw_field_bytes(&["info", "source"]), None);
+    assert_eq!(spanned.raw_field_bytes(&["info", "name", "x"]), None);
+    assert_eq!(spanned.raw_field_bytes(&[]), Some(&bytes[..]));
+    // spans are dropped along with the bytes
+    assert_eq!(
+        spanned.into_torrent(),
+        Torrent::read_from_bytes(&bytes).unwrap()
+    );
+}
+
+#[test]
+fn read_from_bytes_8_gib() {
+    // > 4 GiB, i.e. larger than `usize` on 32-bit targets
+    let length = 8 * 1024 * 1024 * 1024;
+    let piece_length = 4 * 1024 * 1024;
+    let torrent = Torrent {
+        announce: Some("url".to_owned()),
+        announce_list: None,
+        length,
+        files: None,
+        name: "large.img".to_owned(),
+        piece_length,
+        pieces: vec![[7; 20]; (length / piece_length) as usize],
+        private: None,
+        extra_fields: None,
+        extra_info_fields: None,
+    };
+
+    let parsed = Torrent::read_from_bytes(torrent.clone().encode().unwrap()).unwrap();
+    assert_eq!(parsed, torrent);
+    assert_eq!(parsed.pieces.len(), 2048);
+    assert!(parsed.revalidate().is_ok());
+}
+
+// 6 tiers of 100 URLs each: 50 repeating the same 5 `udp` trackers,
+// 30 `dht` junk URLs, and 20 unique `http` trackers.
+fn torrent_with_spammed_trackers() -> Torrent {
+    let mut torrent =
+        Torrent::read_from_file("tests/files/ubuntu-16.04.4-desktop-amd64.iso.torrent").unwrap();
+    torrent.announce_list = Some(
+        (0..6)
+            .map(|tier| {
+                (0..100)
+                    .map(|i| match i {
+                        0..=49 => format!("udp://tracker{}.example:6969/announce", i % 5),
+                        50..=79 => format!("dht://junk{}-{}", tier, i),
+                        _ => format!("http://t{}-{}.example/announce", tier, i),
+                    })
+                    .collect()
+            })
+            .collect(),
+    );
+    torrent
+}
+
+#[test]
+fn read_max_trackers_per_torrent() {
+    let torrent = torrent_with_spammed_trackers();
+    let info_hash = torrent.info_hash();
+    let bytes = torrent.clone().encode().unwrap();
+
+    // no limit by default
+    let (read, warnings) =
+        Torrent::read_from_bytes_with_warnings(&bytes, &v1::ReadOptions::default()).unwrap();
+    assert_eq!(read, torrent);
+    assert!(warnings.is_empty());
+
+    let options = v1::ReadOptions {
+        max_trackers_per_torrent: Some(250),
+        ..v1::ReadOptions::default()
+    };
+    let (read, warnings) = Torrent::read_from_bytes_with_warnings(&bytes, &options).unwrap();
+    assert_eq!(
+        warnings,
+        vec![v1::ReadWarning::TrackersTruncated {
+            kept: 250,
+            dropped: 350,
+        }]
+    );
+    assert_eq!(
+        warnings[0].to_string(),
+        r#""announce-list" truncated to [250] trackers, [350] dropped"#
+    );
+    let list = read.announce_list.as_ref().unwrap();
+    assert_eq!(
+        list.iter().map(Vec::len).collect::<Vec<_>>(),
+        vec![100, 100, 50]
+    );
+    // the URLs that are kept come first
+    let original = torrent.announce_list.as_ref().unwrap();
+    assert_eq!(list[..2], original[..2]);
+    assert_eq!(list[2][..], original[2][..50]);
+    assert_eq!(read.announce, torrent.announce);
+    assert_eq!(read.info_hash(), info_hash);
+    assert_eq!(
+        Torrent::read_from_bytes_with(&bytes, &options).unwrap(),
+        read
+    );
+
+    // at the limit, nothing is dropped
+    let options = v1::ReadOptions {
+        max_trackers_per_torrent: Some(600),
+        ..v1::ReadOptions::default()
+    };
+    let (_, warnings) = Torrent::read_from_bytes_with_warnings(&bytes, &options).unwrap();
+    assert!(warnings.is_empty());
+}
+
+#[test]
+fn read_lossy_utf8() {
+    let mut bytes = b"d8:announce4:\xffurl4:infod6:lengthi4e4:name5:\xffname".to_vec();
+    bytes.extend_from_slice(b"12:piece lengthi4e6:pieces20:");
+    bytes.extend_from_slice(&[0; 20]);
+    bytes.extend_from_slice(b"ee");
+
+    // rejected by default
+    match Torrent::read_from_bytes(&bytes) {
+        Err(LavaTorrentError::MalformedTorrent(m)) => assert_eq!(
+            m,
+            r#""announce" maps to a byte string that is not valid UTF-8 (4 bytes, starts with 0xff75726c)."#
+        ),
+        _ => panic!(),
+    }
+
+    let options = v1::ReadOptions {
+        lossy_utf8: true,
+        ..v1::ReadOptions::default()
+    };
+    let (torrent, warnings) = Torrent::read_from_bytes_with_warnings(&bytes, &options).unwrap();
+    assert_eq!(torrent.announce, Some("\u{fffd}url".to_owned()));
+    assert_eq!(torrent.name, "\u{fffd}name");
+    assert_eq!(
+        warnings,
+        vec![
+            v1::ReadWarning::LossyUtf8 { field: "announce" },
+            v1::ReadWarning::LossyUtf8 { field: "name" },
+        ]
+    );
+    assert_eq!(
+        warnings[1].to_string(),
+        r#""name" is not valid UTF-8, decoded lossily"#
+    );
+}
+
+// A multi-file torrent with 2 files of 3 and 4 bytes, declaring `length`
+// (if `Some`) in its `info` dict as well.
+fn multi_file_torrent_bytes(declared_length: Option<i64>) -> Vec<u8> {
+    let mut bytes = b"d4:infod5:filesld6:lengthi3e4:pathl1:aeed6:lengthi4e4:pathl1:beee".to_vec();
+    if let Some(length) = declared_length {
+        bytes.extend_from_slice(format!("6:lengthi{}e", length).as_bytes());
+    }
+    bytes.extend_from_slice(b"4:name3:dir12:piece lengthi16384e6:pieces20:");
+    bytes.extend_from_slice(&[0; 20]);
+    bytes.extend_from_slice(b"ee");
+    bytes
+}
+
+#[test]
+fn length_provenance_strict() {
+    let torrent =
+        Torrent::read_from_file("tests/files/ubuntu-16.04.4-desktop-amd64.iso.torrent").unwrap();
+    assert_eq!(
+        torrent.length_provenance(),
+        v1::LengthProvenance::FromLengthKey
+    );
+
+    let torrent = Torrent::read_from_bytes(multi_file_torrent_bytes(None)).unwrap();
+    assert_eq!(torrent.length, 7);
+    assert_eq!(
+        torrent.length_provenance(),
+        v1::LengthProvenance::FromFileSum
+    );
+
+    for declared in [7, 100] {
+        match Torrent::read_from_bytes(multi_file_torrent_bytes(Some(declared))) {
+            Err(LavaTorrentError::MalformedTorrent(m)) => {
+                assert_eq!(m, r#"Both "length" and "files" exist."#);
+            }
+            _ => panic!(),
+        }
+    }
+}
+
+#[test]
+fn length_provenance_reconciled() {
+    let options = v1::ReadOptions {
+        allow_length_with_files: true,
+        ..v1::ReadOptions::default()
+    };
+
+    for declared in [7, 100, -1] {
+        let bytes = multi_file_torrent_bytes(Some(declared));
+        let (torrent, warnings) = Torrent::read_from_bytes_with_warnings(&bytes, &options).unwrap();
+        assert_eq!(torrent.length, 7);
+        assert_eq!(
+            torrent.length_provenance(),
+            v1::LengthProvenance::Reconciled {
+                declared,
+                computed: 7,
+            }
+        );
+        assert_eq!(
+            warnings,
+            vec![v1::ReadWarning::LengthWithFiles {
+                declared,
+                computed: 7,
+            }]
+        );
+        // survives a round trip, i.e. the info hash does not change either
+        let encoded = torrent.clone().encode().unwrap();
+        assert_eq!(encoded, bytes);
+        let (read, _) = Torrent::read_from_bytes_with_warnings(&encoded, &options).unwrap();
+        assert_eq!(read.length_provenance(), torrent.length_provenance());
+    }
+
+    // without a conflict, nothing is reported
+    let bytes = multi_file_torrent_bytes(None);
+    let (torrent, warnings) = Torrent::read_from_bytes_with_warnings(&bytes, &options).unwrap();
+    assert_eq!(
+        torrent.length_provenance(),
+        v1::LengthProvenance::FromFileSum
+    );
+    assert!(warnings.is_empty());
+}
+
+// `pieces` as a list of one hash per piece.
+fn pieces_list_torrent_bytes() -> (Vec<u8>, Vec<u8>) {
+    let info = b"d6:lengthi20000e4:name4:file12:piece lengthi16384e6:piecesl20:\
+                 aaaaaaaaaaaaaaaaaaaa20:bbbbbbbbbbbbbbbbbbbbee"
+        .to_vec();
+    let mut bytes = b"d4:info".to_vec();
+    bytes.extend_from_slice(&info);
+    bytes.extend_from_slice(b"e");
+    (bytes, info)
+}
+
+#[test]
+fn pieces_list_strict() {
+    let (bytes, _) = pieces_list_torrent_bytes();
+    match Torrent::read_from_bytes(&bytes) {
+        Err(LavaTorrentError::MalformedTorrent(m)) => {
+            assert_eq!(m, r#""pieces" maps to List, expected Bytes."#);
+        }
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn pieces_list_lenient() {
+    let options = v1::ReadOptions {
+        allow_pieces_list: true,
+        ..v1::ReadOptions::default()
+    };
+    let (bytes, info) = pieces_list_torrent_bytes();
+
+    let (torrent, warnings) = Torrent::read_from_bytes_with_warnings(&bytes, &options).unwrap();
+    assert_eq!(torrent.pieces, vec![[b'a'; 20], [b'b'; 20]]);
+    assert_eq!(warnings, vec![v1::ReadWarning::PiecesList { count: 2 }]);
+    assert_eq!(
+        warnings[0].to_string(),
+        r#""pieces" is a list of 2 hashes, which re-encoding concatenates (changing the info hash)"#
+    );
+
+    // `pieces` is written concatenated, so the info hash changes...
+    let original_info_hash = hash::sha1_hex(&info);
+    assert_ne!(torrent.info_hash(), original_info_hash);
+    let encoded = torrent.clone().encode().unwrap();
+    assert_ne!(encoded, bytes);
+    let read = Torrent::read_from_bytes(&encoded).unwrap();
+    assert_eq!(read, torrent);
+    assert_eq!(read.info_hash(), torrent.info_hash());
+
+    // ...but the original one can still be had
+    let spanned = Torrent::read_from_bytes_spanned_with(&bytes, &options).unwrap();
+    assert_eq!(spanned.torrent(), &torrent);
+    assert_eq!(spanned.raw_info_hash(), original_info_hash);
+    assert_eq!(spanned.warnings(), warnings);
+    match Torrent::read_from_bytes_spanned(&bytes) {
+        Err(LavaTorrentError::MalformedTorrent(m)) => {
+            assert_eq!(m, r#""pieces" maps to List, expected Bytes."#);
+        }
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn raw_info_hash_canonical() {
+    let bytes = std::fs::read("tests/files/ubuntu-16.04.4-desktop-amd64.iso.torrent").unwrap();
+    let spanned = Torrent::read_from_bytes_spanned(&bytes).unwrap();
+    assert_eq!(spanned.raw_info_hash(), spanned.torrent().info_hash());
+}
+
+#[test]
+fn prune_spammed_trackers() {
+    let mut torrent = torrent_with_spammed_trackers();
+    let info_hash = torrent.info_hash();
+
+    let report = torrent.prune_trackers(v1::PrunePolicy {
+        drop_duplicates: true,
+        allowed_schemes: Some(vec![
+            "udp".to_owned(),
+            "http".to_owned(),
+            "https".to_owned(),
+        ]),
+        max_trackers: Some(100),
+    });
+    assert_eq!(
+        report,
+        v1::PruneReport {
+            disallowed_schemes: 180,
+            duplicates: 295,
+            over_cap: 25,
+        }
+    );
+    assert_eq!(torrent.info_hash(), info_hash);
+
+    let list = torrent.announce_list.as_ref().unwrap();
+    assert_eq!(
+        list.iter().map(Vec::len).collect::<Vec<_>>(),
+        vec![25, 20, 20, 20, 15]
+    );
+    let urls = list.iter().flatten().collect::<Vec<_>>();
+    assert_eq!(
+        urls.iter().collect::<std::collections::HashSet<_>>().len(),
+        100
+    );
+    assert!(urls.iter().all(|url| !url.starts_with("dht://")));
+
+    // each remaining tracker is listed once
+    let magnet = torrent.magnet_link().unwrap();
+    assert_eq!(magnet.matches("&tr=").count(), 100);
+    assert!(!magnet.contains("dht"));
+    let display = torrent.to_string();
+    assert!(!display.contains("dht://"));
+    assert_eq!(
+        display
+            .matches("udp://tracker0.example:6969/announce")
+            .count(),
+        1
+    );
+}
+
+#[test]
+fn read_all_from_bytes_bundle() {
+    let ubuntu = std::fs::read("tests/files/ubuntu-16.04.4-desktop-amd64.iso.torrent").unwrap();
+    let tails = std::fs::read("tests/files/tails-amd64-3.6.1.torrent").unwrap();
+    let bundle = [ubuntu.as_slice(), b"i42e", &tails].concat();
+
+    // the single-torrent functions still require exactly 1 element
+    assert!(Torrent::read_from_bytes(&bundle).is_err());
+
+    let (torrents, skipped) =
+        Torrent::read_all_from_bytes(&bundle, v1::BundlePolicy::SkipInvalid).unwrap();
+    assert_eq!(
+        torrents
+            .iter()
+            .map(Torrent::info_hash)
+            .collect::<Vec<String>>(),
+        vec![
+            Torrent::read_from_bytes(&ubuntu).unwrap().info_hash(),
+            Torrent::read_from_bytes(&tails).unwrap().info_hash(),
+        ]
+    );
+    assert_eq!(skipped.len(), 1);
+    assert_eq!(skipped[0].0, 1);
+    match skipped[0].1 {
+        LavaTorrentError::MalformedTorrent(ref m) => {
+            assert_eq!(m, "Torrent's top-level element is not a dictionary.")
+        }
+        _ => panic!(),
+    }
+
+    match Torrent::read_all_from_bytes(&bundle, v1::BundlePolicy::FailFast) {
+        Err(LavaTorrentError::MalformedTorrent(m)) => assert_eq!(
+            m,
+            "Element 1 of the bundle: Torrent's top-level element is not a dictionary."
+        ),
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn read_all_from_bytes_empty_or_malformed() {
+    let (torrents, skipped) =
+        Torrent::read_all_from_bytes(b"", v1::BundlePolicy::FailFast).unwrap();
+    assert!(torrents.is_empty());
+    assert!(skipped.is_empty());
+
+    // bencode errors can't be skipped
+    assert!(matches!(
+        Torrent::read_all_from_bytes(b"d4:infoi1eel4:spam", v1::BundlePolicy::SkipInvalid),
+        Err(LavaTorrentError::MalformedBencode(_))
+    ));
+}
+
+#[test]
+fn read_info_only() {
+    let original =
+        Torrent::read_from_file("tests/files/ubuntu-16.04.4-desktop-amd64.iso.torrent").unwrap();
+    let mut broken = original.clone();
+    broken.announce = None;
+    broken.announce_list = None;
+    broken.extra_fields = Some(HashMap::from_iter(vec![
+        ("announce".to_owned(), BencodeElem::Integer(42)),
+        (
+            "announce-list".to_owned(),
+            BencodeElem::String("not a list".to_owned()),
+        ),
+        (
+            "comment".to_owned(),
+            BencodeElem::String("still here".to_owned()),
+        ),
+    ]));
+    let bytes = broken.clone().encode().unwrap();
+
+    match Torrent::read_from_bytes(&bytes) {
+        Err(LavaTorrentError::MalformedTorrent(m)) => {
+            assert_eq!(m, r#""announce" maps to Integer, expected String."#);
+        }
+        _ => panic!(),
+    }
+
+    let torrent = Torrent::read_info_only(&bytes).unwrap();
+    assert_eq!(torrent.info_hash(), original.info_hash());
+    assert_eq!(torrent.name, original.name);
+    assert_eq!(torrent.length, original.length);
+    assert_eq!(torrent.pieces, original.pieces);
+    assert_eq!(torrent.announce, None);
+    assert_eq!(torrent.announce_list, None);
+    // top-level fields are left untouched
+    assert_eq!(torrent.extra_fields, broken.extra_fields);
+    assert_eq!(torrent.encode().unwrap(), bytes);
+
+    // valid top-level fields are not extracted either
+    let torrent = Torrent::read_info_only(original.clone().encode().unwrap()).unwrap();
+    assert_eq!(torrent.info_hash(), original.info_hash());
+    assert_eq!(torrent.announce, None);
+    assert_eq!(
+        torrent.extra_fields.unwrap()["announce"],
+        BencodeElem::String(original.announce.unwrap())
+    );
+}
+
+#[test]
+fn read_info_only_invalid_info() {
+    for (bytes, message) in [
+        (&b"d8:announcei42ee"[..], r#""info" does not exist."#),
+        (
+            &b"d8:announcei42e4:infoi1ee"[..],
+            r#""info" maps to Integer, expected Dictionary."#,
+        ),
+        (
+            &b"d8:announcei42e4:infod4:name1:aee"[..],
+            r#"Neither "length" nor "files" exists."#,
+        ),
+        (
+            &b"l4:infoe"[..],
+            "Torrent's top-level element is not a dictionary.",
+        ),
+    ] {
+        match Torrent::read_info_only(bytes) {
+            Err(LavaTorrentError::MalformedTorrent(m)) => assert_eq!(m, message),
+            _ => panic!("{:?}", bytes),
+        }
+    }
+}
+
+#[test]
+fn cache_round_trip() {
+    for path in [
+        "tests/files/ubuntu-16.04.4-desktop-amd64.iso.torrent",
+        "tests/files/tails-amd64-3.6.1.torrent",
+    ] {
+        let torrent = Torrent::read_from_file(path).unwrap();
+        let loaded = Torrent::from_cache_bytes(torrent.to_cache_bytes(true)).unwrap();
+        assert!(loaded.has_pieces());
+        assert_eq!(loaded.info_hash(), torrent.info_hash());
+        assert_eq!(loaded, torrent);
+    }
+}
+
+#[test]
+fn cache_stub() {
+    for path in [
+        "tests/files/ubuntu-16.04.4-desktop-amd64.iso.torrent",
+        "tests/files/tails-amd64-3.6.1.torrent",
+    ] {
+        let torrent = Torrent::read_from_file(path).unwrap();
+        let bytes = torrent.to_cache_bytes(false);
+        assert!(bytes.len() < torrent.pieces.len() * 20);
+
+        let stub = Torrent::from_cache_bytes(bytes).unwrap();
+        assert!(torrent.has_pieces());
+        assert!(!stub.has_pieces());
+        assert!(stub.pieces.is_empty());
+        assert_eq!(
+            stub,
+            Torrent {
+                pieces: Vec::new(),
+                ..torrent
+            }
+        );
+    }
+}
+
+#[test]
+fn preflight_with_available_bytes_fixtures() {
+    let target_dir = std::env::temp_dir().join("lava_torrent-preflight");
+    for path in [
+        "tests/files/ubuntu-16.04.4-desktop-amd64.iso.torrent",
+        "tests/files/tails-amd64-3.6.1.torrent",
+    ] {
+        let torrent = Torrent::read_from_file(path).unwrap();
+        let length = u64::try_from(torrent.length).unwrap();
+
+        let report = torrent
+            .preflight_with_available_bytes(&target_dir, u64::MAX, 4096)
+            .unwrap();
+        assert_eq!(report.required_bytes, length);
+        assert!(report.files_exceeding_4gib.is_empty());
+        assert!(report.paths_too_long.is_empty());
+        assert!(report.ok());
+
+        // a tiny filesystem can't hold it
+        let report = torrent
+            .preflight_with_available_bytes(&target_dir, 1024, 4096)
+            .unwrap();
+        assert!(!report.ok());
+
+        // every path is longer than the target directory itself
+        let report = torrent
+            .preflight_with_available_bytes(&target_dir, u64::MAX, target_dir.as_os_str().len())
+            .unwrap();
+        assert_eq!(
+            report.paths_too_long.len(),
+            torrent.files.as_ref().map_or(1, Vec::len)
+        );
+        assert!(report
+            .paths_too_long
+            .iter()
+            .all(|path| path.starts_with(target_dir.join(&torrent.name))));
+    }
+
+    // nothing was created
+    assert!(!target_dir.exists());
+}
+
+#[test]
+fn detected_beps_fixtures() {
+    use std::collections::BTreeSet;
+    use v1::Bep;
+
+    for (path, expected) in [
+        (
+            "tests/files/tails-amd64-3.6.1.torrent",
+            BTreeSet::from([Bep::MultiTracker]),
+        ),
+        (
+            "tests/files/ubuntu-16.04.4-desktop-amd64.iso.torrent",
+            BTreeSet::from([Bep::MultiTracker]),
+        ),
+        (
+            "tests/samples/files-private.torrent",
+            BTreeSet::from([Bep::Private]),
+        ),
+        ("tests/samples/files.torrent", BTreeSet::new()),
+        ("tests/samples/nested.torrent", BTreeSet::new()),
+    ] {
+        assert_eq!(
+            Torrent::read_from_file(path).unwrap().detected_beps(),
+            expected,
+            "{}",
+            path
+        );
+    }
+}
+
+#[test]
+fn detected_beps_read() {
+    use lava_torrent::bencode_elem;
+    use std::collections::BTreeSet;
+    use v1::Bep;
+
+    // a hybrid torrent with a padding file, web seeds and DHT nodes
+    let bytes = bencode_elem!({
+        ("info", {
+            ("files", [
+                { ("length", 3), ("path", ["a"]) },
+                { ("attr", "p"), ("length", 1), ("path", [".pad", "1"]) },
+                { ("length", 4), ("path", ["b"]) },
+            ]),
+            ("meta version", 2),
+            ("name", "dir"),
+            ("piece length", 16384),
+            ("pieces", (0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00)),
+        }),
+        ("nodes", [["router.example", 6881]]),
+        ("url-list", ["http://seed.example/"]),
+    })
+    .encode();
+
+    assert_eq!(
+        Torrent::read_from_bytes(bytes).unwrap().detected_beps(),
+        BTreeSet::from([Bep::Dht, Bep::WebSeeds, Bep::FileAttributes, Bep::V2])
+    );
+}
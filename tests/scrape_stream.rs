@@ -0,0 +1,160 @@
+#![cfg(feature = "std")]
+
+extern crate lava_torrent;
+
+use lava_torrent::tracker::{self, InfoHash, ScrapeIter, TrackerScrapeResponse};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+const N_ENTRIES: u32 = 100_000;
+// well above what a single entry needs, far below what the whole response does
+const MAX_PEAK_BYTES: usize = 64 * 1024;
+
+// Counts the bytes currently allocated, and the peak since the last reset.
+struct CountingAlloc;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+// the counters are global, so the tests measuring them can't run concurrently
+static SERIAL: Mutex<()> = Mutex::new(());
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let current = CURRENT.fetch_add(layout.size(), Ordering::SeqCst) + layout.size();
+            PEAK.fetch_max(current, Ordering::SeqCst);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        CURRENT.fetch_sub(layout.size(), Ordering::SeqCst);
+    }
+}
+
+#[global_allocator]
+static ALLOC: CountingAlloc = CountingAlloc;
+
+// Run `f` and return its result along with the peak number
+// of bytes allocated (on top of what was already allocated) while it ran.
+fn measure_peak<T, F: FnOnce() -> T>(f: F) -> (T, usize) {
+    let baseline = CURRENT.load(Ordering::SeqCst);
+    PEAK.store(baseline, Ordering::SeqCst);
+    let result = f();
+    (result, PEAK.load(Ordering::SeqCst) - baseline)
+}
+
+fn info_hash(i: u32) -> InfoHash {
+    let mut info_hash = [0xab; 20];
+    // big-endian so that the keys come out sorted
+    info_hash[..4].copy_from_slice(&i.to_be_bytes());
+    info_hash
+}
+
+// a full scrape response with `N_ENTRIES` entries
+fn full_scrape() -> Vec<u8> {
+    let mut bytes = b"d5:filesd".to_vec();
+    for i in 0..N_ENTRIES {
+        bytes.extend_from_slice(b"20:");
+        bytes.extend_from_slice(&info_hash(i));
+        bytes.extend_from_slice(
+            format!(
+                "d8:completei{}e10:downloadedi{}e10:incompletei{}ee",
+                i % 97,
+                i,
+                i % 13
+            )
+            .as_bytes(),
+        );
+    }
+    bytes.extend_from_slice(b"e5:flagsd20:min_request_intervali3600eee");
+    bytes
+}
+
+// Counts the bytes read from the wrapped reader.
+struct CountingReader<'a> {
+    bytes: &'a [u8],
+    read: usize,
+}
+
+impl Read for CountingReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.bytes.read(buf)?;
+        self.read += n;
+        Ok(n)
+    }
+}
+
+// Full scrapes exceed the default `ResponseLimits`.
+fn from_bytes_unlimited(bytes: &[u8]) -> HashMap<InfoHash, tracker::SwarmMetadata> {
+    let limits = tracker::ResponseLimits {
+        max_response_bytes: usize::MAX,
+        ..tracker::ResponseLimits::default()
+    };
+    TrackerScrapeResponse::from_bytes_with_limits(bytes, &limits)
+        .unwrap()
+        .files
+}
+
+#[test]
+fn scrape_iter_matches_from_bytes() {
+    let _guard = SERIAL.lock().unwrap();
+    let bytes = full_scrape();
+
+    let (count, peak) = measure_peak(|| {
+        let mut count = 0;
+        for entry in ScrapeIter::new(bytes.as_slice()).unwrap() {
+            let (info_hash, metadata) = entry.unwrap();
+            assert_eq!(info_hash, self::info_hash(count));
+            assert_eq!(metadata.downloaded, i64::from(count));
+            count += 1;
+        }
+        count
+    });
+    assert_eq!(count, N_ENTRIES);
+    assert!(peak < MAX_PEAK_BYTES, "peak allocation: {}", peak);
+
+    let expected = from_bytes_unlimited(&bytes);
+    let actual = ScrapeIter::new(bytes.as_slice())
+        .unwrap()
+        .collect::<Result<HashMap<_, _>, _>>()
+        .unwrap();
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn scrape_lookup_stops_early() {
+    let _guard = SERIAL.lock().unwrap();
+    let bytes = full_scrape();
+    let expected = from_bytes_unlimited(&bytes);
+
+    let wanted = HashSet::from([info_hash(7), info_hash(1_000), info_hash(N_ENTRIES + 1)]);
+    let (found, peak) = measure_peak(|| {
+        let mut reader = CountingReader {
+            bytes: &bytes,
+            read: 0,
+        };
+        let found = tracker::scrape_lookup(&mut reader, &wanted).unwrap();
+        // one of `wanted` is missing, so the whole response is read
+        assert_eq!(reader.read, bytes.len());
+        found
+    });
+    assert_eq!(found.len(), 2);
+    assert_eq!(found[&info_hash(7)], expected[&info_hash(7)]);
+    assert_eq!(found[&info_hash(1_000)], expected[&info_hash(1_000)]);
+    assert!(peak < MAX_PEAK_BYTES, "peak allocation: {}", peak);
+
+    let wanted = HashSet::from([info_hash(7), info_hash(1_000)]);
+    let mut reader = CountingReader {
+        bytes: &bytes,
+        read: 0,
+    };
+    let found = tracker::scrape_lookup(&mut reader, &wanted).unwrap();
+    assert_eq!(found.len(), 2);
+    assert!(reader.read < bytes.len() / 10, "read: {}", reader.read);
+}
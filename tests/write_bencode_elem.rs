@@ -1,6 +1,9 @@
+#![cfg(feature = "std")]
+
 extern crate lava_torrent;
 extern crate rand;
 
+use lava_torrent::bencode::write::{self, SyncPolicy, WriteFileOptions};
 use lava_torrent::bencode::BencodeElem;
 use rand::Rng;
 use std::collections::HashMap;
@@ -61,13 +64,10 @@ fn bencode_elem_write_list_to_file_ok() {
 
 #[test]
 fn bencode_elem_write_dictionary_to_file_ok() {
-    let original = BencodeElem::Dictionary(HashMap::from_iter(
-        vec![
-            ("spam".to_owned(), BencodeElem::Integer(42)),
-            ("cow".to_owned(), BencodeElem::String("moo".to_owned())),
-        ]
-        .into_iter(),
-    ));
+    let original = BencodeElem::Dictionary(HashMap::from_iter(vec![
+        ("spam".to_owned(), BencodeElem::Integer(42)),
+        ("cow".to_owned(), BencodeElem::String("moo".to_owned())),
+    ]));
     let output = rand_file_name();
 
     original.write_into_file(&output).unwrap();
@@ -75,3 +75,82 @@ fn bencode_elem_write_dictionary_to_file_ok() {
     assert_eq!(duplicate.len(), 1);
     assert_eq!(original, duplicate[0]);
 }
+
+#[test]
+fn bencode_elem_write_to_file_with_ok() {
+    let original = BencodeElem::List(vec![
+        BencodeElem::Integer(42),
+        BencodeElem::String("spam".to_owned()),
+    ]);
+
+    for sync in [SyncPolicy::Full, SyncPolicy::DataOnly, SyncPolicy::None] {
+        let output = rand_file_name();
+        original
+            .write_into_file_with(&output, WriteFileOptions::default().set_sync(sync))
+            .unwrap();
+        assert_eq!(std::fs::read(&output).unwrap(), original.encode());
+    }
+}
+
+#[test]
+fn bencode_elem_write_to_file_create_parent_dirs() {
+    let root = std::path::PathBuf::from(rand_file_name() + "-parents");
+    let output = root.join("a").join("b").join("out");
+    let original = BencodeElem::Integer(42);
+
+    let e = original.write_into_file(&output).unwrap_err();
+    assert_eq!(e.kind(), lava_torrent::LavaTorrentErrorKind::Io, "{:?}", e);
+    assert!(
+        e.to_string()
+            .starts_with(&format!("[{}]: ", output.display())),
+        "{}",
+        e
+    );
+    assert!(!root.exists());
+
+    original
+        .write_into_file_with(
+            &output,
+            WriteFileOptions::default().set_create_parent_dirs(true),
+        )
+        .unwrap();
+    assert_eq!(std::fs::read(&output).unwrap(), original.encode());
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn bencode_elem_reencode_preserving_ok() {
+    let bytes = std::fs::read("tests/files/ubuntu-16.04.4-desktop-amd64.iso.torrent").unwrap();
+    let canonical = b"13:creation datei1519934077e";
+    let odd = b"13:creation datei+1519934077e";
+    let start = bytes
+        .windows(canonical.len())
+        .position(|w| w == canonical)
+        .unwrap();
+    let original = [&bytes[..start], &odd[..], &bytes[start + canonical.len()..]].concat();
+
+    let mut elem = BencodeElem::from_bytes(&original).unwrap().remove(0);
+    match elem {
+        BencodeElem::Dictionary(ref mut dict) => {
+            dict.insert(
+                "announce".to_owned(),
+                BencodeElem::String("http://localhost/announce".to_owned()),
+            );
+        }
+        _ => panic!(),
+    }
+
+    let encoded = write::reencode_preserving(&original, &elem).unwrap();
+    // the oddity survives...
+    assert!(encoded.windows(odd.len()).any(|w| w == odd));
+    // ...as does everything else except `announce`
+    let expected = [
+        &b"d8:announce25:http://localhost/announce"[..],
+        &original[b"d8:announce39:http://torrent.ubuntu.com:6969/announce".len()..],
+    ]
+    .concat();
+    assert_eq!(encoded, expected);
+    // while a plain encode normalizes it
+    assert!(!elem.encode().windows(odd.len()).any(|w| w == odd));
+}
@@ -1,12 +1,22 @@
+#![cfg(feature = "std")]
+
 extern crate lava_torrent;
 extern crate rand;
 
-use lava_torrent::torrent::v1::Torrent;
+use lava_torrent::bencode::write::{SyncPolicy, WriteFileOptions};
+use lava_torrent::bencode::{BencodeElem, TorrentSpans};
+use lava_torrent::hash;
+use lava_torrent::torrent::v1::{
+    self, OverwritePolicy, PieceCompatibility, Torrent, TorrentBuilder,
+};
+use lava_torrent::LavaTorrentError;
 use rand::Rng;
 use std::fs::File;
 use std::io::{BufReader, Read};
 
 const OUTPUT_ROOT: &str = "tests/tmp/";
+const UBUNTU: &str = "tests/files/ubuntu-16.04.4-desktop-amd64.iso.torrent";
+const TAILS: &str = "tests/files/tails-amd64-3.6.1.torrent";
 
 fn rand_file_name() -> String {
     OUTPUT_ROOT.to_owned() + &rand::thread_rng().gen::<u16>().to_string()
@@ -36,6 +46,25 @@ fn write_torrent_to_file_ok() {
     assert_eq!(original, duplicate);
 }
 
+#[test]
+fn write_torrent_to_file_with_ok() {
+    let file = File::open("tests/files/ubuntu-16.04.4-desktop-amd64.iso.torrent").unwrap();
+    let mut bytes = Vec::new();
+    BufReader::new(file).read_to_end(&mut bytes).unwrap();
+    let original = Torrent::read_from_bytes(&bytes).unwrap();
+
+    for sync in [SyncPolicy::Full, SyncPolicy::DataOnly, SyncPolicy::None] {
+        let output = rand_file_name();
+        original
+            .clone()
+            .write_into_file_with(&output, WriteFileOptions::default().set_sync(sync))
+            .unwrap();
+        // all bytes must have left the buffer by the time the call
+        // returns, even if the file is not synced
+        assert_eq!(std::fs::read(&output).unwrap(), bytes);
+    }
+}
+
 #[test]
 fn encode_torrent_multiple_files() {
     let file = File::open("tests/files/tails-amd64-3.6.1.torrent").unwrap();
@@ -59,3 +88,695 @@ fn write_torrent_to_file_multiple_files() {
     let duplicate = Torrent::read_from_file(&output).unwrap();
     assert_eq!(original, duplicate);
 }
+
+// the messages of all errors reported by `revalidate()`
+fn revalidate_errors(torrent: &Torrent) -> Vec<String> {
+    match torrent.revalidate() {
+        Ok(()) => Vec::new(),
+        Err(errors) => errors
+            .into_iter()
+            .map(|e| match e {
+                LavaTorrentError::MalformedTorrent(m) => m.into_owned(),
+                _ => panic!(),
+            })
+            .collect(),
+    }
+}
+
+#[test]
+fn revalidate_ok() {
+    for path in [
+        "tests/files/ubuntu-16.04.4-desktop-amd64.iso.torrent",
+        "tests/files/tails-amd64-3.6.1.torrent",
+    ] {
+        assert!(Torrent::read_from_file(path).unwrap().revalidate().is_ok());
+    }
+}
+
+#[test]
+fn revalidate_truncated_pieces() {
+    let mut torrent = Torrent::read_from_file(UBUNTU).unwrap();
+    torrent.pieces.pop();
+
+    assert_eq!(
+        revalidate_errors(&torrent),
+        vec![format!(
+            "Total piece length {} < torrent's length {}: \
+             {} pieces, {} expected, {} bytes not covered.",
+            torrent.piece_length * torrent.pieces.len() as i64,
+            torrent.length,
+            torrent.pieces.len(),
+            torrent.pieces.len() + 1,
+            torrent.length - torrent.piece_length * torrent.pieces.len() as i64,
+        )]
+    );
+}
+
+#[test]
+fn revalidate_extra_pieces() {
+    let mut torrent = Torrent::read_from_file(UBUNTU).unwrap();
+    let expected = torrent.pieces.len();
+    torrent.pieces.push([0; 20]);
+
+    assert_eq!(
+        revalidate_errors(&torrent),
+        vec![format!(
+            "Torrent has {} pieces, {} expected for length {}: {} bytes in excess.",
+            expected + 1,
+            expected,
+            torrent.length,
+            torrent.piece_length,
+        )]
+    );
+}
+
+#[test]
+fn revalidate_piece_length_changed() {
+    let mut torrent = Torrent::read_from_file(UBUNTU).unwrap();
+    torrent.piece_length *= 2;
+
+    assert_eq!(
+        revalidate_errors(&torrent),
+        vec![format!(
+            "Torrent has {} pieces, {} expected for length {}: {} bytes in excess.",
+            torrent.pieces.len(),
+            torrent.pieces.len().div_ceil(2),
+            torrent.length,
+            torrent.piece_length * (torrent.pieces.len() / 2) as i64,
+        )]
+    );
+
+    torrent.piece_length = 0;
+    assert_eq!(
+        revalidate_errors(&torrent),
+        vec![
+            format!(
+                "Total piece length 0 < torrent's length {}.",
+                torrent.length
+            ),
+            r#""piece length" <= 0."#.to_owned(),
+        ]
+    );
+}
+
+#[test]
+fn revalidate_multiple_files() {
+    let mut torrent = Torrent::read_from_file(TAILS).unwrap();
+    let files = torrent.files.as_mut().unwrap();
+    files[0].length += 1;
+    files[0].path = vec!["..".to_owned(), "escape".to_owned()];
+    let original_path = std::mem::take(&mut files[1].path);
+
+    // all problems are reported at once
+    assert_eq!(
+        revalidate_errors(&torrent),
+        vec![
+            r#"Path of file 0 is unsafe: ["..", "escape"]."#.to_owned(),
+            "Path of file 1 is unsafe: [].".to_owned(),
+            format!(
+                "Sum of file lengths {} != torrent's length {}.",
+                torrent.length + 1,
+                torrent.length,
+            ),
+        ]
+    );
+
+    let files = torrent.files.as_mut().unwrap();
+    files[0].length -= 1;
+    files[0].path = vec!["escape".to_owned()];
+    files[1].path = original_path;
+    assert!(torrent.revalidate().is_ok());
+
+    torrent.files = Some(Vec::new());
+    assert_eq!(
+        revalidate_errors(&torrent),
+        vec![
+            r#""files" is empty."#.to_owned(),
+            format!(
+                "Sum of file lengths 0 != torrent's length {}.",
+                torrent.length
+            ),
+        ]
+    );
+}
+
+#[test]
+fn revalidate_unsafe_paths() {
+    let original = Torrent::read_from_file(TAILS).unwrap();
+    for component in ["/etc/passwd", "C:", "a\\b", "a\nb", "nul\0"] {
+        let mut torrent = original.clone();
+        torrent.files.as_mut().unwrap()[1].path = vec!["dir".to_owned(), component.to_owned()];
+        assert_eq!(
+            revalidate_errors(&torrent),
+            vec![format!(
+                "Path of file 1 is unsafe: {:?}.",
+                ["dir", component]
+            )]
+        );
+
+        let mut torrent = original.clone();
+        torrent.name = component.to_owned();
+        assert_eq!(
+            revalidate_errors(&torrent),
+            vec![format!(r#""name" is unsafe: {:?}."#, component)]
+        );
+    }
+
+    let mut torrent = original;
+    torrent.files.as_mut().unwrap()[0].path = vec!["/etc/passwd".to_owned()];
+
+    // the file is not written either
+    // never created, so the name is not taken by an earlier run
+    let output = rand_file_name() + "-unsafe";
+    assert!(torrent
+        .write_into_file_validated(&output, WriteFileOptions::default())
+        .is_err());
+    assert!(!std::path::Path::new(&output).exists());
+}
+
+#[test]
+fn write_torrent_create_parent_dirs() {
+    let root = std::path::PathBuf::from(rand_file_name() + "-parents");
+    let output = root.join("a").join("b").join("out.torrent");
+    let torrent = Torrent::read_from_file(UBUNTU).unwrap();
+
+    match torrent.clone().write_into_file(&output) {
+        Err(LavaTorrentError::Io(e)) => {
+            assert_eq!(e.kind(), std::io::ErrorKind::NotFound);
+            assert!(
+                e.to_string()
+                    .starts_with(&format!("[{}]: ", output.display())),
+                "{}",
+                e
+            );
+        }
+        other => panic!("{:?}", other),
+    }
+    assert!(!root.exists());
+
+    torrent
+        .clone()
+        .write_into_file_with(
+            &output,
+            WriteFileOptions::default().set_create_parent_dirs(true),
+        )
+        .unwrap();
+    assert_eq!(Torrent::read_from_file(&output).unwrap(), torrent);
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn write_torrent_validate() {
+    let mut torrent = Torrent::read_from_file(UBUNTU).unwrap();
+    torrent.pieces.pop();
+
+    // not validated by default
+    assert!(torrent.clone().encode().is_ok());
+
+    let mut encoded = Vec::new();
+    assert!(torrent.clone().write_into_validated(&mut encoded).is_err());
+    assert!(encoded.is_empty());
+
+    match torrent.clone().encode_validated() {
+        Err(LavaTorrentError::MalformedTorrent(m)) => {
+            assert!(m.starts_with("Total piece length"))
+        }
+        _ => panic!(),
+    }
+
+    // the file is not created if validation fails
+    let output = rand_file_name() + "-invalid";
+    assert!(torrent
+        .write_into_file_validated(&output, WriteFileOptions::default())
+        .is_err());
+    assert!(!std::path::Path::new(&output).exists());
+}
+
+#[test]
+fn encoded_sha1_matches_sha1sum() {
+    // from `sha1sum` and `sha256sum`
+    for (path, sha1, sha256) in [
+        (
+            UBUNTU,
+            "5ab9fb2c0f973b9f8d51387a4d6294e57bef9317",
+            "62325af8ebe319def0cc11b7c47e36078eb6d6921b3497fa0c571d8ce9e6768b",
+        ),
+        (
+            TAILS,
+            "687804014bc0be6460290c45a9e8daf4d4f7cb14",
+            "bc3d556b3c0f710ae2bee4fadd133989e81284a57e3a15c49370a98cf9c70f62",
+        ),
+    ] {
+        let bytes = std::fs::read(path).unwrap();
+        assert_eq!(hash::sha1_hex(&bytes), sha1);
+        assert_eq!(hash::sha256_hex(&bytes), sha256);
+
+        let torrent = Torrent::read_from_bytes(&bytes).unwrap();
+        assert_eq!(torrent.encoded_sha1_hex().unwrap(), sha1);
+        assert_eq!(torrent.encoded_sha1().unwrap(), hash::sha1(&bytes));
+    }
+}
+
+#[test]
+fn constructed_info_sha1_matches_info_hash() {
+    for path in [UBUNTU, TAILS] {
+        let torrent = Torrent::read_from_file(path).unwrap();
+        let info = torrent.construct_info();
+        assert_eq!(info.sha1().to_vec(), torrent.info_hash_bytes());
+        assert_eq!(info.sha1_hex(), torrent.info_hash());
+    }
+}
+
+// The `info` dict embedded in what `write_into()` writes must be
+// exactly what `info_hash()` hashes.
+fn assert_written_info_matches_info_hash(torrent: &Torrent) {
+    let mut bytes = Vec::new();
+    torrent.write_into(&mut bytes).unwrap();
+
+    let spans = TorrentSpans::capture(&bytes).unwrap();
+    let info = BencodeElem::raw_bytes_at(&spans, &bytes, &[b"info"]).unwrap();
+    assert_eq!(
+        hash::sha1_hex(info),
+        torrent.info_hash(),
+        "{}",
+        torrent.name
+    );
+    assert_eq!(torrent.construct_info().encode(), info, "{}", torrent.name);
+}
+
+// Variations of `torrent` touching every field that ends up in `info`.
+fn mutations(torrent: &Torrent) -> Vec<Torrent> {
+    let mut extra_info_fields = torrent.extra_info_fields.clone().unwrap_or_default();
+    // sorted before, between and after the standard keys
+    extra_info_fields.insert("a".to_owned(), BencodeElem::Integer(1));
+    extra_info_fields.insert("meta version".to_owned(), BencodeElem::Integer(1));
+    extra_info_fields.insert("source".to_owned(), BencodeElem::Bytes(vec![0xff, 0]));
+
+    let mut with_file_fields = torrent.clone();
+    if let Some(ref mut files) = with_file_fields.files {
+        for file in files {
+            file.extra_fields
+                .get_or_insert_with(Default::default)
+                .insert("attr".to_owned(), BencodeElem::String("x".to_owned()));
+        }
+    }
+
+    vec![
+        Torrent {
+            private: Some(!torrent.is_private()),
+            ..torrent.clone()
+        },
+        Torrent {
+            private: None,
+            ..torrent.clone()
+        },
+        Torrent {
+            extra_info_fields: Some(extra_info_fields),
+            ..torrent.clone()
+        },
+        Torrent {
+            name: format!("{} (2) \u{e9}", torrent.name),
+            piece_length: torrent.piece_length * 2,
+            pieces: torrent.pieces.iter().step_by(2).copied().collect(),
+            ..torrent.clone()
+        },
+        Torrent {
+            announce: None,
+            announce_list: Some(vec![vec!["udp://a".to_owned()]]),
+            extra_fields: None,
+            ..torrent.clone()
+        },
+        with_file_fields,
+    ]
+}
+
+#[test]
+fn info_hash_matches_encoded_info_random_builds() {
+    let mut rng = rand::thread_rng();
+    let root = std::path::PathBuf::from(rand_file_name() + "-info-hash");
+
+    for i in 0..16 {
+        let dir = root.join(i.to_string());
+        for j in 0..rng.gen_range(1..6) {
+            let subdir = dir.join(["", "a", "b/c"][rng.gen_range(0..3)]);
+            std::fs::create_dir_all(&subdir).unwrap();
+            let len = rng.gen_range(0..40_000);
+            let bytes = (0..len).map(|_| rng.gen()).collect::<Vec<u8>>();
+            std::fs::write(
+                subdir.join(format!("{} {}.bin", j, "\u{e9}".repeat(j))),
+                bytes,
+            )
+            .unwrap();
+        }
+
+        let mut builder = TorrentBuilder::new(&dir, 16384 << rng.gen_range(0..3));
+        if rng.gen() {
+            builder = builder.set_privacy(true);
+        }
+        if rng.gen() {
+            builder = builder.add_extra_info_field(
+                "source".to_owned(),
+                BencodeElem::Integer(rng.gen_range(-1000..1000)),
+            );
+        }
+        // all files may be empty, which fails the build
+        if let Ok(torrent) = builder.build() {
+            let expected = torrent.construct_info().encode();
+            assert_eq!(torrent.info_hash_bytes(), hash::sha1(&expected).to_vec());
+            assert_eq!(torrent.info_hash(), hash::sha1_hex(&expected));
+            assert_written_info_matches_info_hash(&torrent);
+        }
+    }
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn written_info_matches_info_hash() {
+    let mut torrents = Vec::new();
+    for dir in ["tests/files", "tests/samples"] {
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_some_and(|ext| ext == "torrent") {
+                torrents.push(Torrent::read_from_file(path).unwrap());
+            }
+        }
+    }
+    for path in ["tests/files", TAILS] {
+        torrents.push(
+            TorrentBuilder::new(path, 32 * 1024)
+                .set_announce(Some("udp://tracker.example:6969".to_owned()))
+                .add_extra_info_field("source".to_owned(), BencodeElem::Integer(1))
+                .set_privacy(true)
+                .build()
+                .unwrap(),
+        );
+    }
+
+    for torrent in &torrents {
+        assert_written_info_matches_info_hash(torrent);
+        for mutated in mutations(torrent) {
+            assert_written_info_matches_info_hash(&mutated);
+        }
+    }
+}
+
+#[test]
+fn write_beside_policies() {
+    let dir = std::path::PathBuf::from(rand_file_name() + "-beside");
+    std::fs::create_dir(&dir).unwrap();
+    let source = dir.join("ubuntu-16.04.4-desktop-amd64.iso");
+    let torrent = Torrent::read_from_file(UBUNTU).unwrap();
+    let bytes = std::fs::read(UBUNTU).unwrap();
+    let expected = dir.join("ubuntu-16.04.4-desktop-amd64.iso.torrent");
+
+    assert_eq!(
+        torrent
+            .write_beside(&source, OverwritePolicy::Error)
+            .unwrap(),
+        expected
+    );
+    assert_eq!(std::fs::read(&expected).unwrap(), bytes);
+
+    // the existing file is left untouched
+    std::fs::write(&expected, b"old").unwrap();
+    match torrent.write_beside(&source, OverwritePolicy::Error) {
+        Err(LavaTorrentError::Io(e)) => {
+            assert_eq!(e.kind(), std::io::ErrorKind::AlreadyExists)
+        }
+        _ => panic!(),
+    }
+    assert_eq!(std::fs::read(&expected).unwrap(), b"old");
+
+    // the existing file is replaced rather than truncated,
+    // so other links to it keep the old content
+    let link = dir.join("link");
+    std::fs::hard_link(&expected, &link).unwrap();
+    assert_eq!(
+        torrent
+            .write_beside(&source, OverwritePolicy::Overwrite)
+            .unwrap(),
+        expected
+    );
+    assert_eq!(std::fs::read(&expected).unwrap(), bytes);
+    assert_eq!(std::fs::read(&link).unwrap(), b"old");
+
+    // and no temporary file is left behind
+    let mut names: Vec<_> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    names.sort();
+    assert_eq!(names, ["link", "ubuntu-16.04.4-desktop-amd64.iso.torrent"]);
+}
+
+#[test]
+fn write_beside_rename_with_suffix() {
+    let dir = std::path::PathBuf::from(rand_file_name() + "-suffix");
+    std::fs::create_dir_all(dir.join("content")).unwrap();
+    let mut torrent = Torrent::read_from_file(TAILS).unwrap();
+    torrent.name = "tails".to_owned();
+
+    // the first free name is picked each time
+    let written: Vec<_> = (0..3)
+        .map(|_| {
+            torrent
+                .write_beside(dir.join("content"), OverwritePolicy::RenameWithSuffix)
+                .unwrap()
+        })
+        .collect();
+    assert_eq!(
+        written,
+        vec![
+            dir.join("tails.torrent"),
+            dir.join("tails (1).torrent"),
+            dir.join("tails (2).torrent"),
+        ]
+    );
+
+    // including gaps left by removed files
+    std::fs::remove_file(dir.join("tails (1).torrent")).unwrap();
+    assert_eq!(
+        torrent
+            .write_beside(dir.join("content/."), OverwritePolicy::RenameWithSuffix)
+            .unwrap(),
+        dir.join("tails (1).torrent")
+    );
+    for path in written {
+        assert_eq!(Torrent::read_from_file(path).unwrap(), torrent);
+    }
+
+    // `..` is resolved before looking for the parent
+    std::fs::create_dir(dir.join("content/sub")).unwrap();
+    assert_eq!(
+        torrent
+            .write_beside(
+                dir.join("content/sub/.."),
+                OverwritePolicy::RenameWithSuffix
+            )
+            .unwrap(),
+        dir.canonicalize().unwrap().join("tails (3).torrent")
+    );
+}
+
+#[test]
+fn pieces_compatible_fixtures() {
+    let tails = Torrent::read_from_file(TAILS).unwrap();
+    let ubuntu = Torrent::read_from_file(UBUNTU).unwrap();
+
+    // same content published with different metadata
+    let mut cross_seed = tails.clone();
+    cross_seed.announce = Some("udp://other.tracker/announce".to_owned());
+    cross_seed.name = "renamed".to_owned();
+    for file in cross_seed.files.as_mut().unwrap() {
+        file.path.insert(0, "subdir".to_owned());
+    }
+    cross_seed.extra_fields = None;
+    assert_eq!(
+        tails.pieces_compatible(&cross_seed),
+        PieceCompatibility::Identical
+    );
+    assert_eq!(
+        cross_seed.pieces_compatible(&tails),
+        PieceCompatibility::Identical
+    );
+
+    let mut corrupt = tails.clone();
+    corrupt.pieces[1][0] ^= 0xff;
+    assert_eq!(
+        tails.pieces_compatible(&corrupt),
+        PieceCompatibility::LayoutMatch
+    );
+
+    let incompatible = |reason: String| PieceCompatibility::Incompatible { reason };
+    assert_eq!(
+        tails.pieces_compatible(&ubuntu),
+        incompatible(format!(
+            "Piece lengths differ: {} != {}.",
+            tails.piece_length, ubuntu.piece_length
+        ))
+    );
+
+    let mut longer = tails.clone();
+    longer.length += 1;
+    longer.files.as_mut().unwrap()[1].length += 1;
+    assert_eq!(
+        tails.pieces_compatible(&longer),
+        incompatible(format!(
+            "Lengths differ: {} != {}.",
+            tails.length,
+            tails.length + 1
+        ))
+    );
+
+    // same total length, different split
+    let mut resplit = tails.clone();
+    let files = resplit.files.as_mut().unwrap();
+    files[0].length += 1;
+    files[1].length -= 1;
+    let files = tails.files.as_ref().unwrap();
+    assert_eq!(
+        tails.pieces_compatible(&resplit),
+        incompatible(format!(
+            "File 0 differs: {} bytes at offset 0 != {} bytes at offset 0.",
+            files[0].length,
+            files[0].length + 1
+        ))
+    );
+
+    // a single file vs. a directory holding just that file
+    let mut single = tails.clone();
+    single.files = None;
+    assert_eq!(
+        tails.pieces_compatible(&single),
+        incompatible(format!("# of files differ: {} != 1.", files.len()))
+    );
+    let mut wrapped = ubuntu.clone();
+    wrapped.files = Some(vec![v1::File {
+        length: ubuntu.length,
+        path: vec![ubuntu.name.clone()],
+        extra_fields: None,
+    }]);
+    assert_eq!(
+        ubuntu.pieces_compatible(&wrapped),
+        PieceCompatibility::Identical
+    );
+}
+
+#[test]
+fn rewrite_preserving_layout_unsorted() {
+    let bytes = std::fs::read(UBUNTU).unwrap();
+    let torrent = Torrent::read_from_bytes(&bytes).unwrap();
+    let spans = TorrentSpans::capture(&bytes).unwrap();
+    let info = BencodeElem::raw_bytes_at(&spans, &bytes, &[b"info"]).unwrap();
+    let announce = BencodeElem::raw_bytes_at(&spans, &bytes, &[b"announce"]).unwrap();
+
+    // top-level keys out of order, as written by some old clients
+    let original = [
+        &b"d4:info"[..],
+        info,
+        b"7:comment3:old8:announce",
+        announce,
+        b"13:creation datei1e",
+        b"e",
+    ]
+    .concat();
+    assert!(Torrent::read_from_bytes(&original).is_err());
+
+    let rewritten = Torrent::rewrite_preserving_layout(
+        &original,
+        &[("comment", Some(BencodeElem::String("new".to_owned())))],
+    )
+    .unwrap();
+    assert_eq!(rewritten.len(), original.len());
+    let comment = original.windows(5).position(|w| w == b"3:old").unwrap();
+    assert_eq!(rewritten[..comment], original[..comment]);
+    assert_eq!(&rewritten[comment..comment + 5], b"3:new");
+    assert_eq!(rewritten[comment + 5..], original[comment + 5..]);
+    // the info hash is unchanged
+    assert_eq!(
+        hash::sha1(&rewritten[7..7 + info.len()]).to_vec(),
+        torrent.info_hash_bytes()
+    );
+
+    // new keys go right after the greatest existing key before them
+    let rewritten = Torrent::rewrite_preserving_layout(
+        &original,
+        &[
+            ("created by", Some(BencodeElem::String("me".to_owned()))),
+            ("announce", None),
+        ],
+    )
+    .unwrap();
+    assert_eq!(
+        rewritten,
+        [
+            &b"d4:info"[..],
+            info,
+            b"7:comment3:old10:created by2:me13:creation datei1e",
+            b"e",
+        ]
+        .concat()
+    );
+
+    match Torrent::rewrite_preserving_layout(&original, &[("info", None)]) {
+        Err(LavaTorrentError::InvalidArgument(m)) => {
+            assert_eq!(m, r#""info" cannot be edited."#)
+        }
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn rewrite_preserving_layout_sorted() {
+    // without edits, fixtures come out byte for byte
+    for path in [UBUNTU, TAILS] {
+        let bytes = std::fs::read(path).unwrap();
+        assert_eq!(
+            Torrent::rewrite_preserving_layout(&bytes, &[]).unwrap(),
+            bytes
+        );
+
+        // and edits match what re-encoding does
+        let edited = Torrent::rewrite_preserving_layout(
+            &bytes,
+            &[("comment", Some(BencodeElem::String("edited".to_owned())))],
+        )
+        .unwrap();
+        let mut torrent = Torrent::read_from_bytes(&bytes).unwrap();
+        torrent
+            .extra_fields
+            .get_or_insert_with(Default::default)
+            .insert(
+                "comment".to_owned(),
+                BencodeElem::String("edited".to_owned()),
+            );
+        assert_eq!(edited, torrent.encode().unwrap());
+    }
+}
+
+#[test]
+fn write_all_into_file_round_trip() {
+    let torrents = vec![
+        Torrent::read_from_file(UBUNTU).unwrap(),
+        Torrent::read_from_file(TAILS).unwrap(),
+    ];
+    let output = rand_file_name();
+    Torrent::write_all_into_file(&torrents, &output).unwrap();
+
+    // a bundle is just the torrents concatenated
+    let mut expected = std::fs::read(UBUNTU).unwrap();
+    expected.extend(std::fs::read(TAILS).unwrap());
+    assert_eq!(std::fs::read(&output).unwrap(), expected);
+
+    let (read, skipped) = Torrent::read_all_from_file(&output, v1::BundlePolicy::FailFast).unwrap();
+    assert!(skipped.is_empty());
+    assert_eq!(
+        read.iter().map(Torrent::info_hash).collect::<Vec<String>>(),
+        torrents
+            .iter()
+            .map(Torrent::info_hash)
+            .collect::<Vec<String>>()
+    );
+    assert_eq!(read, torrents);
+}